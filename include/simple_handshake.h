@@ -0,0 +1,83 @@
+/*
+ * C interface to the Solana RPC handshake of the handshake library, built with the `ffi` feature:
+ *
+ *   cargo rustc --release --lib --features ffi --crate-type staticlib
+ */
+
+#ifndef SIMPLE_HANDSHAKE_H
+#define SIMPLE_HANDSHAKE_H
+
+/* Generated by cbindgen from src/ffi.rs with cbindgen.toml; do not edit by hand. */
+
+#include <stdbool.h>
+#include <stdint.h>
+
+// How a handshake ended, with the codes the binary exits with.
+typedef enum SimpleHandshakeStatus {
+  // The node answered the handshake.
+  SIMPLE_HANDSHAKE_STATUS_OK = 0,
+  // The arguments were invalid, or the handshake failed unexpectedly.
+  SIMPLE_HANDSHAKE_STATUS_ERROR = 1,
+  // The address could not be resolved.
+  SIMPLE_HANDSHAKE_STATUS_DNS = 2,
+  // The connection could not be established.
+  SIMPLE_HANDSHAKE_STATUS_CONNECT = 3,
+  // The TLS handshake failed.
+  SIMPLE_HANDSHAKE_STATUS_TLS = 4,
+  // The node answered, but not as the protocol requires.
+  SIMPLE_HANDSHAKE_STATUS_PROTOCOL = 5,
+  // A phase of the handshake did not complete within the timeout.
+  SIMPLE_HANDSHAKE_STATUS_TIMEOUT = 6,
+  // The node answered with a JSON-RPC error.
+  SIMPLE_HANDSHAKE_STATUS_RPC = 9,
+  // The host did not answer a reachability check.
+  SIMPLE_HANDSHAKE_STATUS_UNREACHABLE = 10,
+  // The handshake was cancelled.
+  SIMPLE_HANDSHAKE_STATUS_CANCELLED = 130,
+} SimpleHandshakeStatus;
+
+// The outcome of `simple_handshake_shake`, released with `simple_handshake_result_free`.
+typedef struct SimpleHandshakeResult {
+  // How the handshake ended.
+  enum SimpleHandshakeStatus status;
+  // The solana-core version the node reported, or null when the handshake failed.
+  char *version;
+  // The feature set the node reported, when `has_feature_set` is set.
+  uint64_t feature_set;
+  // Whether the node reported a feature set.
+  bool has_feature_set;
+  // Time taken by the handshake, successful or not, in milliseconds.
+  uint64_t duration_ms;
+  // Why the handshake failed, or null when it succeeded.
+  char *error;
+} SimpleHandshakeResult;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Handshakes the Solana RPC node at `address`, a `host:port`, over `transport`, one of "tcp", "tls", "ws"
+// and "wss", or also "http", "https", "h2" and "h2c" in a library built with the `hyper` feature, or "tcp"
+// when null. Each phase of the handshake is bounded by `timeout_seconds`, unless 0.
+// The call blocks until the handshake ends, and never unwinds into the caller.
+//
+// # Safety
+//
+// `address` and `transport` must be null or point to NUL-terminated strings.
+struct SimpleHandshakeResult simple_handshake_shake(const char *address,
+                                                    const char *transport,
+                                                    uint32_t timeout_seconds);
+
+// Releases the strings of a result returned by `simple_handshake_shake`, leaving them null, so a result
+// freed twice is only released once.
+//
+// # Safety
+//
+// `result` must be null or point to a result returned by `simple_handshake_shake`.
+void simple_handshake_result_free(struct SimpleHandshakeResult *result);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* SIMPLE_HANDSHAKE_H */
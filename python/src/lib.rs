@@ -0,0 +1,67 @@
+// Python bindings of the handshake library, built with maturin into the `handshake` module, so tooling
+// written in Python can handshake nodes and scan clusters without shelling out to the binary.
+// Results are dictionaries with the fields of the binary's JSON output, e.g. `version` and `error_class`.
+use {
+    handshake::{
+        bindings::{self, parse_transport, runtime},
+        solana::{
+            scan::{DEFAULT_CONCURRENCY, DEFAULT_SCAN_TIMEOUT_SECONDS},
+            transport::DEFAULT_TIMEOUT_SECONDS,
+        },
+    },
+    pyo3::{exceptions::PyValueError, prelude::*},
+    serde::Serialize,
+};
+
+// Converts a value serialized as JSON into the equivalent Python object.
+fn to_python(py: Python<'_>, value: &impl Serialize) -> PyResult<PyObject> {
+    let value = serde_json::to_string(value).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let json = py.import("json")?;
+    Ok(json.call_method1("loads", (value,))?.unbind())
+}
+
+// Handshakes the node at `address`, a `host:port`, over the transport, e.g. "tls", returning its report.
+// Failed handshakes are reported rather than raised, with their `error` and `error_class`.
+#[pyfunction]
+#[pyo3(name = "handshake", signature = (address, transport = "tcp", timeout = Some(DEFAULT_TIMEOUT_SECONDS)))]
+fn handshake_node(
+    py: Python<'_>,
+    address: &str,
+    transport: &str,
+    timeout: Option<u32>,
+) -> PyResult<PyObject> {
+    let transport = parse_transport(transport).map_err(PyValueError::new_err)?;
+    let report = py.allow_threads(|| {
+        runtime().block_on(bindings::shake_reported(address, transport, timeout))
+    });
+    to_python(py, &report)
+}
+
+// Handshakes every node of `addresses` concurrently, returning their reports in the same order.
+#[pyfunction]
+#[pyo3(signature = (addresses, transport = "tcp", timeout = Some(DEFAULT_SCAN_TIMEOUT_SECONDS), concurrency = DEFAULT_CONCURRENCY))]
+fn scan(
+    py: Python<'_>,
+    addresses: Vec<String>,
+    transport: &str,
+    timeout: Option<u32>,
+    concurrency: usize,
+) -> PyResult<PyObject> {
+    let transport = parse_transport(transport).map_err(PyValueError::new_err)?;
+    if concurrency == 0 {
+        return Err(PyValueError::new_err("Concurrency must be at least 1"));
+    }
+    let reports = py.allow_threads(|| {
+        runtime().block_on(bindings::scan(&addresses, transport, timeout, concurrency))
+    });
+    to_python(py, &reports)
+}
+
+// The `handshake` module.
+#[pymodule]
+#[pyo3(name = "handshake")]
+fn handshake_python(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(handshake_node, module)?)?;
+    module.add_function(wrap_pyfunction!(scan, module)?)?;
+    Ok(())
+}
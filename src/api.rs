@@ -0,0 +1,336 @@
+// REST API mode: the targets are monitored in rounds as in daemon mode, and a small JSON API is served
+// over HTTP, so the tool can run as a standalone RPC health service. `POST /shake` handshakes a node on
+// request, and `GET /targets` and `GET /targets/:id/status` report the status of the monitored targets.
+use {
+    crate::{
+        cli::HttpArgs,
+        daemon::{self, DaemonState, TargetReport},
+        http_server::{self, Request, Response},
+        output::{HandshakeReport, Output},
+    },
+    anyhow::Context,
+    handshake::{
+        bindings,
+        solana::{
+            monitor,
+            scan::{ScanTarget, Scanner},
+            target::AddressFamily,
+            transport::tcp::{ConnectOptions, Connectable},
+        },
+    },
+    serde::{Deserialize, Serialize},
+    std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+    tokio::net::TcpListener,
+    tracing::info,
+};
+
+// Body of `POST /shake`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ShakeRequest {
+    pub address: String, // The node's address, as `host:port`.
+    #[serde(default)]
+    pub transport: Option<String>, // "tcp", "tls", "ws" or "wss"; "tcp" when absent.
+    #[serde(default)]
+    pub timeout_seconds: Option<u32>, // Bound on each phase of the handshake; the server's default when absent.
+}
+
+// Body of every response that is not a document, i.e. errors.
+#[derive(Serialize, Debug)]
+struct ErrorBody {
+    error: String, // What went wrong with the request.
+}
+
+// A response: its status line and JSON body.
+type Reply = (&'static str, String);
+
+// The API, answering requests from the state of the monitored targets and handshaking nodes on request.
+#[derive(Debug, Clone)]
+pub struct Api {
+    state: Arc<Mutex<DaemonState>>, // What the rounds so far tell of the monitored targets.
+    family: AddressFamily,          // Address family the requested nodes are reached over.
+    timeout: u32,                   // Bound on each phase, in seconds, for requests that set none.
+    connect: ConnectOptions,        // Proxy and TLS options the requested nodes are reached with.
+}
+
+impl Api {
+    // Constructs the API over the state, reaching requested nodes directly with the default TLS options.
+    pub fn new(state: Arc<Mutex<DaemonState>>, family: AddressFamily, timeout: u32) -> Self {
+        Api {
+            state,
+            family,
+            timeout,
+            connect: ConnectOptions::default(),
+        }
+    }
+
+    // Answers a request; unknown paths get a 404, and known paths with another method a 405.
+    async fn route(&self, method: &str, path: &str, body: &[u8]) -> Reply {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match (method, &segments[..]) {
+            ("POST", ["shake"]) => match serde_json::from_slice::<ShakeRequest>(body) {
+                Ok(request) => match self.shake(request).await {
+                    Ok(report) => json("200 OK", &report),
+                    Err(error) => failure("400 Bad Request", error),
+                },
+                Err(e) => failure("400 Bad Request", format!("Invalid shake request: {}", e)),
+            },
+            ("GET", ["targets"]) => {
+                let report = self.state.lock().expect("daemon lock poisoned").report();
+                json("200 OK", &report)
+            }
+            ("GET", ["targets", id, "status"]) => {
+                let report = self.state.lock().expect("daemon lock poisoned").report();
+                match find(report.targets, id) {
+                    Some(target) => json("200 OK", &target),
+                    None => failure("404 Not Found", format!("No target '{}'", id)),
+                }
+            }
+            (_, ["shake"] | ["targets"] | ["targets", _, "status"]) => failure(
+                "405 Method Not Allowed",
+                format!("{} is not allowed", method),
+            ),
+            _ => failure("404 Not Found", format!("No route for {}", path)),
+        }
+    }
+
+    // Handshakes a requested node. Handshakes that fail are reports; only invalid requests fail.
+    async fn shake(&self, request: ShakeRequest) -> Result<HandshakeReport, String> {
+        let transport = bindings::parse_transport(request.transport.as_deref().unwrap_or("tcp"))?;
+        let timeout = match request.timeout_seconds {
+            None | Some(0) => self.timeout,
+            Some(seconds) => seconds,
+        };
+        info!(
+            "Handshaking {} over {} on request",
+            request.address, transport
+        );
+
+        let started = Instant::now();
+        let target = match bindings::resolve(&request.address, self.family).await {
+            Ok(target) => target,
+            Err(error) => {
+                let report = HandshakeReport::new(&request.address, transport, started.elapsed());
+                return Ok(report.with_version(&Err(error)));
+            }
+        };
+        let scanner = Scanner::new()
+            .with_concurrency(1)
+            .with_timeout(Some(timeout))
+            .with_connect(self.connect.clone());
+        let results = scanner.scan(vec![ScanTarget::new(target, transport)]).await;
+        Ok(HandshakeReport::from_scan_result(&results[0]))
+    }
+}
+
+impl Connectable for Api {
+    fn connect_options(&mut self) -> &mut ConnectOptions {
+        &mut self.connect
+    }
+}
+
+// Finds a monitored target by its position, from 0 in the order the targets were given, or its label.
+fn find(targets: Vec<TargetReport>, id: &str) -> Option<TargetReport> {
+    match id.parse::<usize>() {
+        Ok(index) => targets.into_iter().nth(index),
+        Err(_) => targets
+            .into_iter()
+            .find(|target| target.label.as_deref() == Some(id)),
+    }
+}
+
+// A response with a document as its body.
+fn json<T: Serialize>(status: &'static str, document: &T) -> Reply {
+    match serde_json::to_string_pretty(document) {
+        Ok(body) => (status, body + "\n"),
+        Err(e) => failure("500 Internal Server Error", e.to_string()),
+    }
+}
+
+// A response with an error as its body.
+fn failure(status: &'static str, error: impl ToString) -> Reply {
+    let body = ErrorBody {
+        error: error.to_string(),
+    };
+    // An error body holds a single string, so it always serializes.
+    let body = serde_json::to_string(&body).expect("error body serializes");
+    (status, body + "\n")
+}
+
+// Monitors the targets every `interval` and serves the API until the process exits.
+// Every monitored handshake is also recorded into the output's metrics and notifier, if any.
+pub async fn run(
+    node: &HttpArgs,
+    family: AddressFamily,
+    interval: Duration,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let monitor = node.monitor.monitor(family, interval)?;
+    let state = Arc::new(Mutex::new(DaemonState::new(monitor::statuses(
+        &monitor.targets,
+    ))));
+    let listener = TcpListener::bind(node.listen)
+        .await
+        .with_context(|| format!("Could not listen on {}", node.listen))?;
+    let api =
+        Api::new(state.clone(), family, node.timeout).with_connect(node.monitor.connect.options());
+    tokio::spawn(serve(listener, api));
+
+    daemon::record_rounds(monitor, &state, output).await;
+    Ok(())
+}
+
+// Serves the API until the process exits.
+pub async fn serve(listener: TcpListener, api: Api) {
+    if let Ok(address) = listener.local_addr() {
+        info!("Serving the API on http://{}", address);
+    }
+    let respond = move |request: Request| {
+        let api = api.clone();
+        async move {
+            let (status, body) = api
+                .route(&request.method, &request.path, &request.body)
+                .await;
+            Response::new(status, "application/json", body)
+        }
+    };
+    http_server::serve(listener, "API request", respond).await
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::http_server::MAX_BODY_BYTES,
+        handshake::solana::TransportType,
+        std::net::SocketAddr,
+        test_case::test_case,
+        tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpStream,
+        },
+    };
+
+    // An API monitoring a labelled and an unlabelled target, neither handshaken yet.
+    fn api() -> Api {
+        let target = |address: &str| {
+            let address: SocketAddr = address.parse().unwrap();
+            ScanTarget::new(address, TransportType::Tcp)
+        };
+        let targets = [
+            target("127.0.0.1:8899").with_label("validator-1".to_string()),
+            target("127.0.0.2:8899"),
+        ];
+        let state = DaemonState::new(monitor::statuses(&targets));
+        Api::new(Arc::new(Mutex::new(state)), AddressFamily::Any, 5)
+    }
+
+    // The address of a local port nothing listens on.
+    fn refused() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address: SocketAddr = listener.local_addr().unwrap();
+        address.to_string()
+    }
+
+    // Targets are found by position or label, and the routes answer with the status they document.
+    #[test_case("GET", "/targets", "200 OK"; "when listing targets")]
+    #[test_case("GET", "/targets/1/status", "200 OK"; "when target is found by position")]
+    #[test_case("GET", "/targets/validator-1/status", "200 OK"; "when target is found by label")]
+    #[test_case("GET", "/targets/2/status", "404 Not Found"; "when position is out of range")]
+    #[test_case("GET", "/targets/validator-2/status", "404 Not Found"; "when label is unknown")]
+    #[test_case("GET", "/status", "404 Not Found"; "when path is unknown")]
+    #[test_case("DELETE", "/targets", "405 Method Not Allowed"; "when method is not allowed")]
+    #[test_case("GET", "/shake", "405 Method Not Allowed"; "when shake is not posted")]
+    #[tokio::test]
+    async fn test_route(method: &str, path: &str, status: &str) {
+        let (answered, body) = api().route(method, path, b"").await;
+        assert_eq!(answered, status);
+        let _: serde_json::Value = serde_json::from_str(&body).unwrap();
+    }
+
+    // A target's status is the one `/status` reports in daemon mode.
+    #[tokio::test]
+    async fn test_target_status() {
+        let (_, body) = api().route("GET", "/targets/validator-1/status", b"").await;
+        let status: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(status["target"], "127.0.0.1:8899");
+        assert_eq!(status["label"], "validator-1");
+        assert_eq!(status["checks"], 0);
+    }
+
+    // Invalid requests fail with the reason; a failed handshake is still a report.
+    #[test_case(r#"{"address": "127.0.0.1:8899", "transport": "quic"}"#, "QUIC is not an RPC transport"; "when transport is quic")]
+    #[test_case(r#"{"address": "127.0.0.1:8899", "transport": "udp"}"#, "Unknown transport 'udp'"; "when transport is unknown")]
+    #[test_case(r#"{"transport": "tls"}"#, "Invalid shake request: missing field `address`"; "when address is missing")]
+    #[test_case(r#"{"address": "127.0.0.1:8899", "port": 1}"#, "Invalid shake request: unknown field `port`"; "when field is unknown")]
+    #[tokio::test]
+    async fn test_shake_invalid(request: &str, error: &str) {
+        let (status, body) = api().route("POST", "/shake", request.as_bytes()).await;
+        assert_eq!(status, "400 Bad Request");
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert!(
+            body["error"].as_str().unwrap().starts_with(error),
+            "{}",
+            body
+        );
+    }
+
+    // A node refusing the connection is reported with the class of the failure.
+    #[tokio::test]
+    async fn test_shake_refused() {
+        let address = refused();
+        let request = format!(r#"{{"address": "{}", "timeout_seconds": 2}}"#, address);
+        let (status, body) = api().route("POST", "/shake", request.as_bytes()).await;
+        assert_eq!(status, "200 OK");
+        let report: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(report["target"], address);
+        assert_eq!(report["transport"], "tcp");
+        assert_eq!(report["error_class"], "connect");
+    }
+
+    // Requests are read over HTTP with their bodies, and answered in JSON.
+    #[tokio::test]
+    async fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, api()));
+
+        let request = |head: String, body: String| async move {
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            stream.write_all(head.as_bytes()).await.unwrap();
+            stream.write_all(body.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+
+        let get = "GET /targets HTTP/1.1\r\nHost: localhost\r\n\r\n".to_string();
+        let response = request(get, String::new()).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("Content-Type: application/json"));
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let status: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(status["targets"][1]["target"], "127.0.0.2:8899");
+
+        let body = format!(r#"{{"address": "{}", "transport": "tcp"}}"#, refused());
+        let post = format!(
+            "POST /shake HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\ncontent-length: {}\r\n\r\n",
+            body.len()
+        );
+        let response = request(post, body).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let report: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(report["error_class"], "connect");
+
+        let oversized = format!(
+            "POST /shake HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_BYTES + 1
+        );
+        let response = request(oversized, String::new()).await;
+        assert!(response.starts_with("HTTP/1.1 413 Payload Too Large"));
+    }
+}
@@ -0,0 +1,150 @@
+// What the C and Python bindings share: the runtime their blocking calls run on, the RPC transports they accept
+// by name, and the handshakes of one node or many, resolved without blocking and reported as the binary does.
+use {
+    crate::{
+        error::HandshakeError,
+        report::HandshakeReport,
+        solana::{
+            rpc::node::{RpcHandshakeResponse, RpcNode},
+            scan::{ScanTarget, Scanner},
+            target::{AddressFamily, Target},
+            transport::timed,
+            TransportType,
+        },
+    },
+    std::{
+        sync::OnceLock,
+        time::{Duration, Instant},
+    },
+    tokio::runtime::Runtime,
+};
+
+// Runtime the handshakes of the bindings run on, started on first use and shared by every call.
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+// The shared runtime.
+pub fn runtime() -> &'static Runtime {
+    RUNTIME.get_or_init(|| Runtime::new().expect("Unable to start the tokio runtime"))
+}
+
+// Parses the name of an RPC transport, e.g. "tls"; QUIC carries no JSON RPC.
+pub fn parse_transport(name: &str) -> Result<TransportType, String> {
+    match name.parse()? {
+        TransportType::Quic => Err("QUIC is not an RPC transport".to_string()),
+        transport => Ok(transport),
+    }
+}
+
+// Parses a `host:port` address; one that does not parse fails the handshake as a DNS error would.
+fn parse(address: &str) -> Result<Target, HandshakeError> {
+    Target::parse(address)
+        .map_err(|e| HandshakeError::Dns(format!("Unable to resolve {}: {}", address, e)))
+}
+
+// Resolves a `host:port` address on the runtime, without blocking its workers, keeping the addresses of the family.
+pub async fn resolve(address: &str, family: AddressFamily) -> Result<Target, HandshakeError> {
+    parse(address)?.lookup(family).await
+}
+
+// Handshakes a single node over an RPC transport.
+pub async fn shake(
+    address: &str,
+    transport: TransportType,
+    timeout: Option<u32>,
+) -> Result<RpcHandshakeResponse, HandshakeError> {
+    let node = RpcNode::new(resolve(address, AddressFamily::Any).await?, transport);
+    node.shake_typed(timeout).await
+}
+
+// Handshakes a single node, reporting the outcome as the binary does.
+pub async fn shake_reported(
+    address: &str,
+    transport: TransportType,
+    timeout: Option<u32>,
+) -> HandshakeReport {
+    let started = Instant::now();
+    let (result, timings, connection) = timed(shake(address, transport, timeout)).await;
+    HandshakeReport::new(address, transport, started.elapsed())
+        .with_version(&result)
+        .with_timings(timings)
+        .with_connection(connection)
+}
+
+// Handshakes every node concurrently, returning their reports in the order of the addresses. Hosts are resolved
+// by the scan as their handshakes start; addresses that do not parse are reported without being handshaken.
+pub async fn scan(
+    addresses: &[String],
+    transport: TransportType,
+    timeout: Option<u32>,
+    concurrency: usize,
+) -> Vec<HandshakeReport> {
+    let mut reports: Vec<Option<HandshakeReport>> = vec![None; addresses.len()];
+    let mut targets = Vec::new();
+    let mut indices = Vec::new();
+    for (index, address) in addresses.iter().enumerate() {
+        match parse(address) {
+            Ok(target) => {
+                targets.push(ScanTarget::new(target, transport));
+                indices.push(index);
+            }
+            Err(error) => {
+                let failed = HandshakeReport::new(address, transport, Duration::ZERO)
+                    .with_error(&Err::<(), _>(error));
+                reports[index] = Some(failed);
+            }
+        }
+    }
+
+    let scanner = Scanner::new()
+        .with_concurrency(concurrency)
+        .with_timeout(timeout)
+        .with_lookup(Some(AddressFamily::Any));
+    for (index, result) in indices.into_iter().zip(scanner.scan(targets).await) {
+        let report = HandshakeReport::from_scan_result(&result);
+        reports[index] = Some(HandshakeReport {
+            target: addresses[index].clone(),
+            ..report
+        });
+    }
+    reports.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, test_case::test_case};
+
+    // RPC transports are accepted by name; QUIC and unknown names are not.
+    #[test_case("tcp", Ok(TransportType::Tcp); "when transport is tcp")]
+    #[test_case("wss", Ok(TransportType::Wss); "when transport is wss")]
+    #[test_case("quic", Err("QUIC is not an RPC transport"); "when transport is quic")]
+    #[test_case("udp", Err("Unknown transport 'udp'"); "when transport is unknown")]
+    fn test_parse_transport(name: &str, expected: Result<TransportType, &str>) {
+        assert_eq!(parse_transport(name), expected.map_err(str::to_string));
+    }
+
+    // A node refusing the connection is reported with the class of the failure and no version.
+    #[tokio::test]
+    async fn test_shake_reported_refused() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let report = shake_reported(&address, TransportType::Tcp, Some(5)).await;
+        assert_eq!(report.target, address);
+        assert_eq!(report.transport, "tcp");
+        assert_eq!(report.error_class.as_deref(), Some("connect"));
+        assert_eq!(report.version, None);
+    }
+
+    // Addresses that do not resolve are reported as DNS failures, in order with the others.
+    #[tokio::test]
+    async fn test_scan_unresolvable() {
+        let addresses = vec!["node.invalid:8899".to_string(), "no port".to_string()];
+        let reports = scan(&addresses, TransportType::Tcp, Some(1), 1).await;
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].target, "node.invalid:8899");
+        assert_eq!(reports[0].error_class.as_deref(), Some("dns"));
+        assert_eq!(reports[1].target, "no port");
+        assert_eq!(reports[1].error_class.as_deref(), Some("dns"));
+    }
+}
@@ -0,0 +1,7 @@
+// Bitcoin wire protocol messages and their framing
+pub mod message;
+// Bitcoin node implementation
+pub mod node;
+
+// The types needed to handshake with a Bitcoin node.
+pub use {message::Network, node::BitcoinNode};
@@ -0,0 +1,238 @@
+// Module for handshaking with a Bitcoin node over its peer-to-peer port.
+// Our `version` is sent over TCP and the handshake succeeds once the peer has sent its own `version`,
+// which is acknowledged with a `verack`, and a `verack` acknowledging ours.
+use crate::{
+    bitcoin::message::{Message, MessageHeader, Network, Version, HEADER_SIZE},
+    error::HandshakeError,
+    solana::{
+        node::Handshake,
+        target::Target,
+        transport::{
+            tcp::{open_stream, ConnectOptions, Connectable},
+            traced, with_timeout,
+        },
+    },
+}; // Import the crate error type, the wire messages, the handshake trait and the TCP connection helpers.
+use async_trait::async_trait; // Enables async trait methods, crucial for async network operations.
+use std::{fmt, time::SystemTime}; // Standard library imports for display formatting and the version timestamp.
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+}; // Asynchronous TCP stream and IO helpers.
+use tracing::info; // Import the `info` macro for logging informational messages.
+
+// Struct defining a Bitcoin node, reached on its P2P port over plain TCP.
+pub struct BitcoinNode {
+    pub remote: Target, // The remote endpoint: its name as given and the addresses it resolved to.
+    pub network: Network, // Network the node belongs to, selecting the magic bytes of its messages.
+    pub connect: ConnectOptions, // Proxy the P2P connection is tunnelled through; it is never TLS.
+}
+
+impl BitcoinNode {
+    // Constructor for BitcoinNode, taking a target, or a socket address, and the node's network.
+    pub fn new(remote: impl Into<Target>, network: Network) -> Self {
+        BitcoinNode {
+            remote: remote.into(),
+            network,
+            connect: ConnectOptions::default(),
+        }
+    }
+
+    // Performs the version/verack handshake, returning the version the peer announced.
+    pub async fn peer_version(&self, timeout: Option<u32>) -> Result<Version, HandshakeError> {
+        traced(&self.remote, "tcp", async {
+            let remote = self.remote.to_string();
+            let mut stream =
+                open_stream(&remote, &self.connect.route(&self.remote), timeout).await?;
+
+            // Announce ourselves; the peer answers with its own version before anything else.
+            let nonce: u64 = rand::random();
+            let version = Version::new(nonce, unix_time()).to_message(stream.peer_addr().ok());
+            send(&mut stream, &version, self.network, timeout).await?;
+            info!("Sent version to {}", remote);
+
+            let peer =
+                with_timeout("read", timeout, exchange(&mut stream, self.network, nonce)).await?;
+            info!(
+                "Handshake completed with {} running {} (protocol {}, height {})",
+                remote, peer.user_agent, peer.version, peer.start_height
+            );
+            Ok(peer)
+        })
+        .await
+    }
+}
+
+impl Connectable for BitcoinNode {
+    fn connect_options(&mut self) -> &mut ConnectOptions {
+        &mut self.connect
+    }
+}
+
+// Implement the Display trait for BitcoinNode for easy logging and debugging.
+impl fmt::Display for BitcoinNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BitcoinNode({})", self.remote) // Custom display format showing the remote address.
+    }
+}
+
+// Seconds since the epoch, as carried by `version` messages.
+fn unix_time() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+// Frames and writes a message.
+async fn send(
+    stream: &mut TcpStream,
+    message: &Message,
+    network: Network,
+    timeout: Option<u32>,
+) -> Result<(), HandshakeError> {
+    let bytes = message.encode(network);
+    with_timeout("write", timeout, stream.write_all(&bytes)).await
+}
+
+// Reads the next complete message from the stream.
+async fn receive<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    network: Network,
+) -> Result<Message, HandshakeError> {
+    let mut header = [0u8; HEADER_SIZE];
+    stream.read_exact(&mut header).await?;
+    let header = MessageHeader::decode(&header, network)?;
+    let mut payload = vec![0u8; header.length as usize];
+    stream.read_exact(&mut payload).await?;
+    Message::from_parts(header, payload)
+}
+
+// Reads messages until the peer has sent both its `version` and a `verack`, acknowledging its version.
+// Modern nodes also send feature negotiation messages such as `wtxidrelay` and `sendaddrv2` in between;
+// those are ignored.
+async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    network: Network,
+    nonce: u64,
+) -> Result<Version, HandshakeError> {
+    let mut peer: Option<Version> = None;
+    let mut acknowledged = false;
+    loop {
+        let message = receive(stream, network).await?;
+        match message.command.as_str() {
+            "version" => {
+                let version = Version::decode(&message.payload)?;
+                if version.nonce == nonce {
+                    return Err(HandshakeError::Protocol(
+                        "Connected to ourselves".to_string(),
+                    ));
+                }
+                stream.write_all(&Message::verack().encode(network)).await?;
+                peer = Some(version);
+            }
+            "verack" => acknowledged = true,
+            command => info!("Ignoring {} message", command),
+        }
+        if let (Some(peer), true) = (&peer, acknowledged) {
+            return Ok(peer.clone());
+        }
+    }
+}
+
+// Async trait for performing the handshake operation, returning the user agent the peer announced.
+#[async_trait]
+impl Handshake for BitcoinNode {
+    async fn shake(&self, timeout: Option<u32>) -> Result<String, HandshakeError> {
+        Ok(self.peer_version(timeout).await?.user_agent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Test suite for BitcoinNode functionality, run against an in-process peer.
+    use {super::*, std::net::SocketAddr, tokio::net::TcpListener};
+
+    // Accepts a single connection and answers the handshake as a peer of the network would,
+    // sending the given messages after reading our version.
+    async fn serve(network: Network, replies: Vec<Message>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let version = receive(&mut socket, network).await.unwrap();
+            assert_eq!(version.command, "version");
+            for reply in replies {
+                socket.write_all(&reply.encode(network)).await.unwrap();
+            }
+            // Keep the connection open until we close it.
+            let _ = socket.read_to_end(&mut Vec::new()).await;
+        });
+        address
+    }
+
+    // The peer's version as a message.
+    fn peer_version() -> Message {
+        let mut version = Version::new(7, 1_700_000_000);
+        version.user_agent = "/Satoshi:26.0.0/".to_string();
+        version.start_height = 830_000;
+        version.to_message(None)
+    }
+
+    // Verifies that a BitcoinNode can be correctly instantiated and displayed.
+    #[test]
+    fn test_bitcoin_node_creation() {
+        let addr: SocketAddr = "127.0.0.1:8333".parse().unwrap();
+        let node = BitcoinNode::new(addr, Network::Mainnet);
+
+        assert_eq!(node.network, Network::Mainnet);
+        assert_eq!(node.to_string(), "BitcoinNode(127.0.0.1:8333)"); // Checks the display format.
+    }
+
+    // The handshake completes once both version and verack arrive, skipping feature negotiation messages.
+    #[tokio::test]
+    async fn test_bitcoin_handshake() {
+        let replies = vec![
+            peer_version(),
+            Message::new("wtxidrelay", Vec::new()),
+            Message::verack(),
+        ];
+        let address = serve(Network::Regtest, replies).await;
+
+        let peer = BitcoinNode::new(address, Network::Regtest)
+            .peer_version(Some(5))
+            .await
+            .unwrap();
+        assert_eq!(peer.user_agent, "/Satoshi:26.0.0/");
+        assert_eq!(peer.start_height, 830_000);
+    }
+
+    // A peer of another network is rejected on its magic bytes.
+    #[tokio::test]
+    async fn test_wrong_network_is_invalid_response() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let _ = receive(&mut socket, Network::Mainnet).await;
+            let reply = peer_version().encode(Network::Testnet);
+            socket.write_all(&reply).await.unwrap();
+        });
+
+        let result = BitcoinNode::new(address, Network::Mainnet)
+            .shake(Some(5))
+            .await;
+        assert!(matches!(result, Err(HandshakeError::InvalidResponse(_))));
+    }
+
+    // A peer that never acknowledges our version times out.
+    #[tokio::test]
+    async fn test_missing_verack_times_out() {
+        let address = serve(Network::Regtest, vec![peer_version()]).await;
+
+        let result = BitcoinNode::new(address, Network::Regtest)
+            .shake(Some(1))
+            .await;
+        assert!(matches!(result, Err(HandshakeError::Timeout { .. })));
+    }
+}
@@ -0,0 +1,68 @@
+// Chain-agnostic handshakes.
+// The transports only move JSON payloads, so a network's handshake comes down to the request it sends
+// and how it reads the answer. Implementing `ChainHandshake` is all a new network needs to reuse
+// the TCP, TLS and WebSocket transports.
+use {
+    crate::{error::HandshakeError, solana::transport::Transport},
+    serde::Serialize,
+};
+
+// What a node reported about itself during a handshake.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct NodeInfo {
+    pub chain: String,              // Network the node belongs to, e.g. "solana".
+    pub version: String,            // Software version the node reports.
+    pub details: serde_json::Value, // Network-specific details, e.g. Solana's feature set.
+}
+
+// A network's handshake: the request proving a node answers, and how its response is read.
+pub trait ChainHandshake: Send + Sync {
+    // The JSON payload sent to the node.
+    fn payload(&self) -> serde_json::Value;
+
+    // Parses the raw response body into what the node reported about itself.
+    fn parse(&self, raw: &str) -> Result<NodeInfo, HandshakeError>;
+}
+
+// Performs a network's handshake over any transport.
+pub async fn shake(
+    chain: &dyn ChainHandshake,
+    transport: &dyn Transport,
+    timeout: Option<u32>, // Optional timeout in seconds, applied to each phase.
+) -> Result<NodeInfo, HandshakeError> {
+    let body = transport.connect_and_send(timeout, chain.payload()).await?;
+    chain.parse(&body)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::solana::transport::mock::MockTransport};
+
+    // A network answering a `ping` with a fixed version.
+    struct Echo;
+
+    impl ChainHandshake for Echo {
+        fn payload(&self) -> serde_json::Value {
+            serde_json::json!({ "method": "ping" })
+        }
+
+        fn parse(&self, raw: &str) -> Result<NodeInfo, HandshakeError> {
+            Ok(NodeInfo {
+                chain: "echo".to_string(),
+                version: raw.to_string(),
+                details: serde_json::Value::Null,
+            })
+        }
+    }
+
+    // The chain's payload is sent over the transport, and the response parsed by the chain.
+    #[tokio::test]
+    async fn test_shake_over_any_transport() {
+        // A transport answering with the method of the payload it was sent.
+        let loopback = MockTransport::new()
+            .with_reply(|payload| Ok(payload["method"].as_str().unwrap_or_default().to_string()));
+        let info = shake(&Echo, &loopback, None).await.unwrap();
+        assert_eq!(info.chain, "echo");
+        assert_eq!(info.version, "ping");
+    }
+}
@@ -0,0 +1,471 @@
+// Single handshakes with the nodes of every protocol but Solana's RPC: each target is resolved, its node
+// built from the arguments and handshaken once, and the handshake reported with what the node answered.
+#[cfg(feature = "devp2p")]
+use {crate::cli::DevP2pArgs, handshake::ethereum::devp2p::DevP2pNode};
+#[cfg(feature = "geyser")]
+use {crate::cli::GeyserArgs, handshake::solana::geyser::node::GeyserNode};
+#[cfg(feature = "libp2p")]
+use {crate::cli::Libp2pArgs, handshake::libp2p::Libp2pNode};
+use {
+    crate::{
+        cli::{
+            BitcoinArgs, EthArgs, GossipArgs, GossipPingArgs, MtuProbeArgs, ProbeArgs, ProfileArgs,
+            TendermintArgs, TpuArgs,
+        },
+        output::{HandshakeReport, LatencyReport, Output},
+        Outcome,
+    },
+    handshake::{
+        bitcoin::BitcoinNode,
+        ethereum::EthNode,
+        registry::ProfileNode,
+        solana::{
+            cancel::Cancel,
+            gossip::node::{GossipNode, GossipPingOptions},
+            mtu::{MtuProbe, MtuProbeOptions, MtuProtocol},
+            node::Handshake,
+            probe,
+            target::{AddressFamily, Target},
+            tpu::node::TpuNode,
+            transport::{tcp::Connectable, timed},
+            TransportType,
+        },
+        tendermint::TendermintNode,
+        HandshakeError,
+    },
+    std::{
+        future::Future,
+        time::{Duration, Instant},
+    },
+    tracing::info,
+};
+
+// Resolves the target's host afresh, reporting a host that does not resolve like a failed handshake over
+// the transport, so a watch round whose lookup failed is reported as a DNS failure too.
+pub async fn resolve(
+    target: &Target,
+    family: AddressFamily,
+    transport: impl ToString,
+    output: &Output,
+) -> anyhow::Result<Target> {
+    let started = Instant::now();
+    let result = target.lookup(family).await;
+    if result.is_err() {
+        let report = HandshakeReport::new(target, transport, started.elapsed()).with_error(&result);
+        output.report(&report)?;
+    }
+    Ok(result?)
+}
+
+// Runs a handshake with the target over the transport and reports it with its phases, its connection and,
+// once it succeeded, the details drawn from what the node answered, which is then returned.
+pub async fn shake_reported<T>(
+    target: impl ToString,
+    transport: impl ToString,
+    handshake: impl Future<Output = Result<T, HandshakeError>>,
+    details: impl FnOnce(&T) -> Option<serde_json::Value>,
+    output: &Output,
+) -> anyhow::Result<T> {
+    let started = Instant::now();
+    let (result, timings, connection) = timed(handshake).await;
+    let mut report = HandshakeReport::new(target, transport, started.elapsed())
+        .with_error(&result)
+        .with_timings(timings)
+        .with_connection(connection);
+    if let Some(details) = result.as_ref().ok().and_then(details) {
+        report = report.with_details(details);
+    }
+    output.report(&report)?;
+    Ok(result?)
+}
+
+// Performs a QUIC handshake with a validator's TPU.
+pub async fn tpu(
+    node: &TpuArgs,
+    family: AddressFamily,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let address = resolve(&node.address, family, TransportType::Quic, output)
+        .await?
+        .address(family)?;
+    let tpu_node = TpuNode::new(address).with_alpn(node.alpn.clone());
+    info!("Connecting to {}", tpu_node);
+
+    let response = shake_reported(
+        address,
+        TransportType::Quic,
+        tpu_node.shake(None),
+        |_| None,
+        output,
+    )
+    .await?;
+    info!("Handshake response was {:?}", response);
+    Ok(Outcome::Passed)
+}
+
+// Performs a ping/pong exchange with a node's gossip port over UDP.
+pub async fn gossip(
+    node: &GossipArgs,
+    family: AddressFamily,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let address = resolve(&node.address, family, "udp", output)
+        .await?
+        .address(family)?;
+    let gossip_node = GossipNode::new(address);
+    info!("Connecting to {}", gossip_node);
+
+    let response =
+        shake_reported(address, "udp", gossip_node.shake(None), |_| None, output).await?;
+    info!("Handshake response was {:?}", response);
+    Ok(Outcome::Passed)
+}
+
+// Pings a node's gossip port, reporting the loss and the round trips of the pongs under `details`. A
+// series without a single pong fails as the handshake would, and one with some pings lost partially.
+pub async fn gossip_ping(
+    node: &GossipPingArgs,
+    family: AddressFamily,
+    output: &Output,
+    cancel: &Cancel,
+) -> anyhow::Result<Outcome> {
+    let address = resolve(&node.address, family, "udp", output)
+        .await?
+        .address(family)?;
+    let gossip_node = GossipNode::new(address);
+    let options = GossipPingOptions {
+        count: node.count,
+        interval: Duration::from_millis(node.interval),
+        timeout: Duration::from_secs(node.timeout.into()),
+    };
+    info!("Pinging {} {} times", gossip_node, node.count);
+
+    let started = Instant::now();
+    let result = gossip_node.ping(options, cancel).await;
+    let mut report = HandshakeReport::new(address, "udp", started.elapsed()).with_error(&result);
+    let pings = match result {
+        Ok(pings) => pings,
+        Err(error) => {
+            output.report(&report)?;
+            return Err(error.into());
+        }
+    };
+    let answered = match pings.received() {
+        0 => Err(HandshakeError::Timeout {
+            phase: "gossip pong".to_string(),
+            seconds: node.timeout,
+        }),
+        _ => Ok(()),
+    };
+    let round_trips: Vec<Option<f64>> = pings
+        .round_trips
+        .iter()
+        .map(|round_trip| round_trip.map(|round_trip| round_trip.as_secs_f64() * 1000.0))
+        .collect();
+    let stats = pings.stats();
+    let latency = stats
+        .as_ref()
+        .map(|stats| LatencyReport::new("round trip", stats));
+    report = report
+        .with_error(&answered)
+        .with_details(serde_json::json!({
+            "sent": pings.sent(),
+            "received": pings.received(),
+            "loss_percent": pings.loss_percent(),
+            "round_trips_ms": round_trips,
+            "round_trip": latency,
+        }));
+    output.report(&report)?;
+
+    info!(
+        "{} pings sent, {} pongs received, {:.1}% loss",
+        pings.sent(),
+        pings.received(),
+        pings.loss_percent().unwrap_or_default()
+    );
+    if let Some(stats) = stats {
+        info!(
+            "Round trip min/mean/p50/p95/p99/max = {:?}/{:?}/{:?}/{:?}/{:?}/{:?}",
+            stats.min, stats.mean, stats.p50, stats.p95, stats.p99, stats.max
+        );
+    }
+    answered?;
+    Ok(match pings.received() == pings.sent() {
+        true => Outcome::Passed,
+        false => Outcome::PartialFailure,
+    })
+}
+
+// Probes the path MTU to a node's gossip or TPU port, reporting the largest datagram answered and every
+// size probed under `details`.
+pub async fn mtu_probe(
+    node: &MtuProbeArgs,
+    family: AddressFamily,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let transport = match node.protocol {
+        MtuProtocol::Gossip => "udp",
+        MtuProtocol::Quic => "quic",
+    };
+    let address = resolve(&node.address, family, transport, output)
+        .await?
+        .address(family)?;
+    let probe = MtuProbe::new(address, node.protocol);
+    let options = MtuProbeOptions {
+        max_mtu: node.max_mtu,
+        timeout: Duration::from_secs(node.timeout.into()),
+    };
+    info!(
+        "Probing the path MTU to {} with {} datagrams up to {} bytes",
+        address, node.protocol, node.max_mtu
+    );
+
+    let started = Instant::now();
+    let result = probe.run(options).await;
+    let mut report =
+        HandshakeReport::new(address, transport, started.elapsed()).with_error(&result);
+    if let Ok(mtu) = &result {
+        let probes: Vec<serde_json::Value> = mtu
+            .probes
+            .iter()
+            .map(|&(size, reached)| serde_json::json!({ "size": size, "reached": reached }))
+            .collect();
+        report = report.with_details(serde_json::json!({
+            "largest_datagram": mtu.largest_datagram,
+            "path_mtu": mtu.path_mtu,
+            "max_mtu": mtu.max_mtu,
+            "at_max_mtu": mtu.at_max(),
+            "probes": probes,
+        }));
+    }
+    output.report(&report)?;
+
+    let mtu = result?;
+    match mtu.at_max() {
+        true => info!("Path MTU is at least {}, the largest probed", mtu.path_mtu),
+        false => info!(
+            "Path MTU is {}, with datagrams of up to {} bytes answered",
+            mtu.path_mtu, mtu.largest_datagram
+        ),
+    }
+    Ok(Outcome::Passed)
+}
+
+// Handshakes each well-known port of a host with the protocol its surface speaks. A host is reported
+// unreachable only when no surface answered.
+pub async fn probe_ports(
+    node: &ProbeArgs,
+    family: AddressFamily,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let host = node.address.lookup(family).await?;
+    let probes = probe::well_known_ports(node.tpu_ports.clone());
+    info!("Probing {} ports of {}", probes.len(), host.host);
+
+    let results = probe::probe(&host, probes, Some(node.timeout)).await;
+    output.probe_results(&results)?;
+
+    match results.iter().filter(|r| r.result.is_ok()).count() {
+        0 => anyhow::bail!("No surface of {} is reachable", host.host),
+        reachable if reachable == results.len() => Ok(Outcome::Passed),
+        _ => Ok(Outcome::PartialFailure),
+    }
+}
+
+// Calls GetVersion and Ping on a Geyser plugin over gRPC, reporting the version it reported.
+#[cfg(feature = "geyser")]
+pub async fn geyser(
+    node: &GeyserArgs,
+    family: AddressFamily,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let transport = match node.secure {
+        true => "grpcs",
+        false => "grpc",
+    };
+    let target = resolve(&node.address, family, transport, output).await?;
+    let geyser_node = GeyserNode::new(target.clone(), node.secure)
+        .with_connect(node.connect.options())
+        .with_x_token(node.x_token.clone());
+    info!("Connecting to {}", geyser_node);
+
+    let version = shake_reported(
+        &target,
+        geyser_node.transport(),
+        geyser_node.shake(None),
+        |version| Some(serde_json::json!({ "version": version })),
+        output,
+    )
+    .await?;
+    info!("Handshake response was {:?}", version);
+    Ok(Outcome::Passed)
+}
+
+// Asks an EVM node for its client version and network id.
+pub async fn eth(
+    node: &EthArgs,
+    family: AddressFamily,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let trans_type = TransportType::rpc(node.websocket, node.secure);
+    let target = resolve(&node.address, family, trans_type, output).await?;
+    let eth_node = EthNode::new(target.clone(), trans_type).with_connect(node.connect.options());
+    info!("Connecting to {}", eth_node);
+
+    let info = shake_reported(
+        target,
+        trans_type,
+        eth_node.node_info(None),
+        |info| {
+            Some(serde_json::json!({
+                "version": info.version,
+                "network_id": info.details["network_id"],
+            }))
+        },
+        output,
+    )
+    .await?;
+    info!("Handshake response was {:?}", info);
+    Ok(Outcome::Passed)
+}
+
+// Calls `status` on a CometBFT node, reporting its version, network and sync state.
+pub async fn tendermint(
+    node: &TendermintArgs,
+    family: AddressFamily,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let trans_type = TransportType::rpc(node.websocket, node.secure);
+    let target = resolve(&node.address, family, trans_type, output).await?;
+    let tendermint_node =
+        TendermintNode::new(target.clone(), trans_type).with_connect(node.connect.options());
+    info!("Connecting to {}", tendermint_node);
+
+    let status = shake_reported(
+        target,
+        trans_type,
+        tendermint_node.status(None),
+        |status| {
+            Some(serde_json::json!({
+                "version": status.node_info.version,
+                "network": status.node_info.network,
+                "node_id": status.node_info.id,
+                "latest_block_height": status.sync_info.latest_block_height,
+                "catching_up": status.sync_info.catching_up,
+            }))
+        },
+        output,
+    )
+    .await?;
+    info!("Handshake response was {:?}", status);
+    Ok(Outcome::Passed)
+}
+
+// Performs the version/verack handshake with a Bitcoin node, reporting what it announced in its version.
+pub async fn bitcoin(
+    node: &BitcoinArgs,
+    family: AddressFamily,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let target = resolve(&node.address, family, "tcp", output).await?;
+    let bitcoin_node =
+        BitcoinNode::new(target.clone(), node.network).with_proxy(node.proxy.proxy.clone());
+    info!("Connecting to {}", bitcoin_node);
+
+    let version = shake_reported(
+        target,
+        "tcp",
+        bitcoin_node.peer_version(None),
+        |version| {
+            Some(serde_json::json!({
+                "network": node.network.to_string(),
+                "user_agent": version.user_agent,
+                "protocol_version": version.version,
+                "services": version.services,
+                "start_height": version.start_height,
+            }))
+        },
+        output,
+    )
+    .await?;
+    info!("Handshake response was {:?}", version);
+    Ok(Outcome::Passed)
+}
+
+// Performs the RLPx handshake and Hello exchange with a devp2p node, reporting its Hello.
+#[cfg(feature = "devp2p")]
+pub async fn devp2p(
+    node: &DevP2pArgs,
+    family: AddressFamily,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let target = node.address.resolve().await?.with_family(family)?;
+    let devp2p_node = DevP2pNode::new(target.clone(), node.address.public_key()?)
+        .with_proxy(node.proxy.proxy.clone());
+    info!("Connecting to {}", devp2p_node);
+
+    let hello = shake_reported(
+        target,
+        "rlpx",
+        devp2p_node.hello(None),
+        |hello| Some(serde_json::json!(hello)),
+        output,
+    )
+    .await?;
+    info!("Handshake response was {:?}", hello);
+    Ok(Outcome::Passed)
+}
+
+// Performs the Noise and identify handshake with a libp2p peer, expecting its peer ID, if any.
+#[cfg(feature = "libp2p")]
+pub async fn libp2p(
+    node: &Libp2pArgs,
+    family: AddressFamily,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let target = node.address.resolve().await?.with_family(family)?;
+    let libp2p_node = Libp2pNode::new(target.clone())
+        .with_peer_id(node.address.peer_id.clone())
+        .with_proxy(node.proxy.proxy.clone());
+    info!("Connecting to {}", libp2p_node);
+
+    let info = shake_reported(
+        target,
+        "libp2p",
+        libp2p_node.identify(None),
+        |info| Some(serde_json::json!(info)),
+        output,
+    )
+    .await?;
+    info!("Handshake response was {:?}", info);
+    Ok(Outcome::Passed)
+}
+
+// Sends the payload of a registry's profile, with its placeholders filled, and reads the response as it
+// describes, reporting what the node reported about itself.
+pub async fn profile(
+    node: &ProfileArgs,
+    family: AddressFamily,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let trans_type = TransportType::rpc(node.websocket, node.secure);
+    let profile = node
+        .registry
+        .profile(&node.profile, node.variables.clone())
+        .map_err(anyhow::Error::msg)?;
+    let target = resolve(&node.address, family, trans_type, output).await?;
+    let profile_node =
+        ProfileNode::new(target.clone(), trans_type, profile).with_connect(node.connect.options());
+    info!("Connecting to {}", profile_node);
+
+    let info = shake_reported(
+        target,
+        trans_type,
+        profile_node.probe(None),
+        |info| Some(serde_json::json!(info)),
+        output,
+    )
+    .await?;
+    info!("Handshake response was {:?}", info);
+    Ok(Outcome::Passed)
+}
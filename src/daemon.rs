@@ -0,0 +1,281 @@
+// Daemon mode: the targets are monitored in rounds, and the status of each is served as JSON on
+// `GET /status`, so other systems can poll this tool instead of the RPC nodes themselves.
+use {
+    crate::{
+        cli::DaemonArgs,
+        http_server::{self, Request, Response},
+        output::{HandshakeReport, Output},
+    },
+    anyhow::Context,
+    handshake::solana::{
+        monitor::{self, Monitor, Round, TargetStatus},
+        target::AddressFamily,
+    },
+    schemars::JsonSchema,
+    serde::Serialize,
+    std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+    tokio::net::TcpListener,
+    tracing::info,
+};
+
+// Default time between two rounds of handshakes, in seconds, when `--watch` is not given.
+pub const DEFAULT_DAEMON_INTERVAL_SECONDS: u64 = 30;
+
+// Status of a target as served on `/status`.
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct TargetReport {
+    pub target: String, // The address monitored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>, // Optional name for the target, e.g. the node's identity pubkey.
+    pub transport: String, // The transport used, e.g. "tcp" or "wss".
+    pub healthy: bool,  // Whether the last handshake succeeded.
+    pub checks: u64,    // Handshakes made since the daemon started.
+    pub successes: u64, // Handshakes that succeeded.
+    pub consecutive_failures: u64, // Handshakes failed since the last success.
+    pub uptime_percent: Option<f64>, // Percentage of the handshakes that succeeded, if any was made.
+    pub version: Option<String>,     // Version reported by the last successful handshake.
+    pub last_result: Option<HandshakeReport>, // The last handshake, if any was made.
+}
+
+// Document served on `/status`.
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct StatusReport {
+    pub round: u64, // Number of the last round finished, 0 before the first.
+    pub targets: Vec<TargetReport>, // Status of each target, in the order they were given.
+}
+
+// What the rounds so far tell of every target, shared between the monitor and the status endpoint.
+#[derive(Debug, Default)]
+pub struct DaemonState {
+    round: u64,                                 // Number of the last round finished.
+    statuses: Vec<TargetStatus>,                // Status of each target.
+    last_results: Vec<Option<HandshakeReport>>, // Last handshake of each target.
+}
+
+impl DaemonState {
+    // Constructs the state of targets not handshaken yet.
+    pub fn new(statuses: Vec<TargetStatus>) -> Self {
+        DaemonState {
+            round: 0,
+            last_results: vec![None; statuses.len()],
+            statuses,
+        }
+    }
+
+    // Adds the results of a round.
+    pub fn record(&mut self, round: &Round) {
+        monitor::record_round(&mut self.statuses, round);
+        for (last, result) in self.last_results.iter_mut().zip(&round.results) {
+            *last = Some(HandshakeReport::from_scan_result(result));
+        }
+        self.round = round.number;
+    }
+
+    // Reports the status of every target.
+    pub fn report(&self) -> StatusReport {
+        let targets = self
+            .statuses
+            .iter()
+            .zip(&self.last_results)
+            .map(|(status, last)| TargetReport {
+                target: status.target.address.to_string(),
+                label: status.target.label.clone(),
+                transport: status.target.transport_type.to_string(),
+                healthy: status.healthy(),
+                checks: status.rounds,
+                successes: status.successes,
+                consecutive_failures: status.consecutive_failures,
+                uptime_percent: status.uptime(),
+                version: status.version.clone(),
+                last_result: last.clone(),
+            })
+            .collect();
+        StatusReport {
+            round: self.round,
+            targets,
+        }
+    }
+}
+
+// Monitors the targets every `interval` and serves their status until the process exits.
+// Every handshake is also recorded into the output's metrics and notifier, if any.
+pub async fn run(
+    node: &DaemonArgs,
+    family: AddressFamily,
+    interval: Duration,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let monitor = node.monitor.monitor(family, interval)?;
+    let state = Arc::new(Mutex::new(DaemonState::new(monitor::statuses(
+        &monitor.targets,
+    ))));
+    let listener = TcpListener::bind(node.listen)
+        .await
+        .with_context(|| format!("Could not listen on {}", node.listen))?;
+    tokio::spawn(serve(listener, state.clone()));
+    record_rounds(monitor, &state, output).await;
+    Ok(())
+}
+
+// Runs the monitor, recording every round into the state and the output until the monitor stops.
+pub async fn record_rounds(monitor: Monitor, state: &Mutex<DaemonState>, output: &Output) {
+    let mut rounds = monitor.spawn();
+    while let Some(round) = rounds.recv().await {
+        let healthy = round.results.iter().filter(|r| r.result.is_ok()).count();
+        info!(
+            "Round {} finished, {} of {} targets healthy",
+            round.number,
+            healthy,
+            round.results.len()
+        );
+        let reports: Vec<HandshakeReport> = round
+            .results
+            .iter()
+            .map(HandshakeReport::from_scan_result)
+            .collect();
+        output.observe(&reports);
+        state.lock().expect("daemon lock poisoned").record(&round);
+    }
+}
+
+// Serves the status on `GET /status` until the process exits; any other request gets a 404.
+pub async fn serve(listener: TcpListener, state: Arc<Mutex<DaemonState>>) {
+    if let Ok(address) = listener.local_addr() {
+        info!("Serving status on http://{}/status", address);
+    }
+    let respond = move |request: Request| {
+        let state = state.clone();
+        async move { respond(&request, &state) }
+    };
+    http_server::serve(listener, "status", respond).await
+}
+
+// Answers a single status request.
+fn respond(request: &Request, state: &Mutex<DaemonState>) -> Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/status") => {
+            let report = state.lock().expect("daemon lock poisoned").report();
+            match serde_json::to_string_pretty(&report) {
+                Ok(body) => Response::new("200 OK", "application/json", body + "\n"),
+                Err(e) => Response::new("500 Internal Server Error", "text/plain", e.to_string()),
+            }
+        }
+        _ => Response::new("404 Not Found", "text/plain", "Not found\n".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        handshake::{
+            error::HandshakeError,
+            solana::{
+                rpc::node::RpcHandshakeResponse,
+                scan::{ScanResult, ScanTarget},
+                TransportType,
+            },
+        },
+        std::{net::SocketAddr, time::SystemTime},
+        tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpStream,
+        },
+    };
+
+    // A state with two targets after a round in which the first answered and the second failed.
+    fn recorded() -> DaemonState {
+        let target = |address: &str| {
+            let address: SocketAddr = address.parse().unwrap();
+            ScanTarget::new(address, TransportType::Tcp)
+        };
+        let targets = [target("127.0.0.1:8899"), target("127.0.0.2:8899")];
+        let mut state = DaemonState::new(monitor::statuses(&targets));
+        let round = Round {
+            number: 1,
+            finished_at: SystemTime::now(),
+            results: vec![
+                ScanResult {
+                    target: targets[0].clone(),
+                    duration: Duration::from_millis(12),
+                    timings: Default::default(),
+                    connection: Default::default(),
+                    result: Ok(RpcHandshakeResponse {
+                        solana_core: "1.18.6".to_string(),
+                        feature_set: Some(3469865029),
+                        timings: Default::default(),
+                    }),
+                },
+                ScanResult {
+                    target: targets[1].clone(),
+                    duration: Duration::from_millis(3),
+                    timings: Default::default(),
+                    connection: Default::default(),
+                    result: Err(HandshakeError::Protocol("bad".to_string())),
+                },
+            ],
+        };
+        state.record(&round);
+        state
+    }
+
+    // Every target is reported with its uptime and last result.
+    #[test]
+    fn test_report() {
+        let report = recorded().report();
+        assert_eq!(report.round, 1);
+
+        let [answered, failed] = &report.targets[..] else {
+            panic!("Expected two targets, got {:?}", report.targets);
+        };
+        assert!(answered.healthy);
+        assert_eq!(answered.uptime_percent, Some(100.0));
+        assert_eq!(answered.version.as_deref(), Some("1.18.6"));
+        assert!(!failed.healthy);
+        assert_eq!(failed.uptime_percent, Some(0.0));
+        let last = failed.last_result.as_ref().unwrap();
+        assert_eq!(last.error.as_deref(), Some("Protocol error: bad"));
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["targets"][0]["last_result"]["duration_ms"], 12);
+    }
+
+    // Targets not handshaken yet are reported without a result.
+    #[test]
+    fn test_report_before_first_round() {
+        let address: SocketAddr = "127.0.0.1:8899".parse().unwrap();
+        let targets = [ScanTarget::new(address, TransportType::Tcp)];
+        let report = DaemonState::new(monitor::statuses(&targets)).report();
+        assert_eq!(report.round, 0);
+        assert!(!report.targets[0].healthy);
+        assert_eq!(report.targets[0].last_result, None);
+    }
+
+    // The status is served on /status, and other paths are not found.
+    #[tokio::test]
+    async fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Arc::new(Mutex::new(recorded()))));
+
+        let request = |path: &'static str| async move {
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+
+        let response = request("/status").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("Content-Type: application/json"));
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let status: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(status["targets"][1]["consecutive_failures"], 1);
+        assert!(request("/").await.starts_with("HTTP/1.1 404 Not Found"));
+    }
+}
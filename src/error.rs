@@ -0,0 +1,140 @@
+// Crate-wide error type for handshakes and the transports that carry them.
+// Each variant corresponds to a failure class, so callers can react programmatically
+// instead of inspecting `io::ErrorKind`s and message strings.
+use {std::io, thiserror::Error};
+
+/// The error returned by `Transport::connect_and_send` and `Handshake::shake`.
+#[derive(Debug, Error)]
+pub enum HandshakeError {
+    /// The remote address could not be resolved or is not a valid address.
+    #[error("DNS error: {0}")]
+    Dns(String),
+
+    /// The host did not answer a reachability check, e.g. an ICMP echo, so it is unreachable at the network layer.
+    #[error("Host unreachable: {0}")]
+    Unreachable(String),
+
+    /// The connection could not be established, or failed while in use.
+    #[error("Connection error: {0}")]
+    Connect(#[source] io::Error),
+
+    /// TLS could not be configured or the TLS handshake failed.
+    #[error("TLS error: {0}")]
+    Tls(String),
+
+    /// The WebSocket upgrade or framing failed.
+    #[error("WebSocket error: {0}")]
+    WebSocket(String),
+
+    /// The remote answered, but not as the protocol requires (e.g. an HTTP error status).
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+
+    /// The node answered the call with a JSON-RPC error object, e.g. for an unknown method.
+    #[error("RPC error {code}: {message}")]
+    Rpc { code: i64, message: String },
+
+    /// A phase of the handshake did not complete within the timeout.
+    #[error("Timed out after {seconds}s during {phase}")]
+    Timeout { phase: String, seconds: u32 },
+
+    /// The response could not be parsed.
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+
+    /// The handshake was cancelled before it completed, e.g. on the embedding service's shutdown.
+    #[error("Handshake cancelled")]
+    Cancelled,
+
+    /// The handshake was set up incorrectly, e.g. a node built without an address, so nothing was sent.
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+}
+
+impl HandshakeError {
+    /// The failure class as a short name, e.g. `"timeout"`, for machine-readable reports.
+    pub fn class(&self) -> &'static str {
+        match self {
+            HandshakeError::Dns(_) => "dns",
+            HandshakeError::Unreachable(_) => "unreachable",
+            HandshakeError::Connect(_) => "connect",
+            HandshakeError::Tls(_) => "tls",
+            HandshakeError::WebSocket(_) => "websocket",
+            HandshakeError::Protocol(_) => "protocol",
+            HandshakeError::Rpc { .. } => "rpc",
+            HandshakeError::Timeout { .. } => "timeout",
+            HandshakeError::InvalidResponse(_) => "invalid_response",
+            HandshakeError::Cancelled => "cancelled",
+            HandshakeError::InvalidConfig(_) => "invalid_config",
+        }
+    }
+}
+
+// I/O failures outside of a more specific context are connection failures.
+impl From<io::Error> for HandshakeError {
+    fn from(error: io::Error) -> Self {
+        HandshakeError::Connect(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // I/O errors convert into connection errors and keep their source.
+    #[test]
+    fn test_io_error_converts_to_connect() {
+        let error: HandshakeError =
+            io::Error::new(io::ErrorKind::ConnectionRefused, "refused").into();
+        match &error {
+            HandshakeError::Connect(e) => assert_eq!(e.kind(), io::ErrorKind::ConnectionRefused),
+            other => panic!("Expected a connection error, got {:?}", other),
+        }
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    // Timeouts name the phase and duration in their message.
+    #[test]
+    fn test_timeout_display() {
+        let error = HandshakeError::Timeout {
+            phase: "read".to_string(),
+            seconds: 5,
+        };
+        assert_eq!(error.to_string(), "Timed out after 5s during read");
+    }
+
+    // RPC errors carry the node's code and message.
+    #[test]
+    fn test_rpc_display() {
+        let error = HandshakeError::Rpc {
+            code: -32601,
+            message: "Method not found".to_string(),
+        };
+        assert_eq!(error.to_string(), "RPC error -32601: Method not found");
+        assert_eq!(error.class(), "rpc");
+    }
+
+    // Each class has a distinct short name.
+    #[test]
+    fn test_class() {
+        let timeout = HandshakeError::Timeout {
+            phase: "read".to_string(),
+            seconds: 5,
+        };
+        assert_eq!(timeout.class(), "timeout");
+        assert_eq!(HandshakeError::Dns("unresolved".to_string()).class(), "dns");
+        assert_eq!(
+            HandshakeError::Unreachable("no echo reply".to_string()).class(),
+            "unreachable"
+        );
+        assert_eq!(
+            HandshakeError::InvalidResponse("{".to_string()).class(),
+            "invalid_response"
+        );
+        assert_eq!(HandshakeError::Cancelled.class(), "cancelled");
+        assert_eq!(
+            HandshakeError::InvalidConfig("no address".to_string()).class(),
+            "invalid_config"
+        );
+    }
+}
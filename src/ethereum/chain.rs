@@ -0,0 +1,99 @@
+// Ethereum's handshake as a chain: the client version and network id, asked for in a single JSON-RPC batch.
+// Any EVM node serving the standard `web3` and `net` namespaces answers it.
+use crate::{
+    chain::{ChainHandshake, NodeInfo},
+    error::HandshakeError,
+    solana::rpc::node::RpcBatchRequest,
+};
+
+// The Ethereum network, or any EVM network speaking its JSON-RPC API, handshaken with a batch whose ids the
+// responses must echo.
+#[derive(Debug, Clone)]
+pub struct Ethereum {
+    batch: RpcBatchRequest, // The client version first, then the network id.
+}
+
+impl Ethereum {
+    // Constructs the handshake, its requests under random ids.
+    pub fn new() -> Self {
+        Ethereum {
+            batch: RpcBatchRequest::new()
+                .method("web3_clientVersion")
+                .method("net_version"),
+        }
+    }
+}
+
+impl Default for Ethereum {
+    fn default() -> Self {
+        Ethereum::new()
+    }
+}
+
+impl ChainHandshake for Ethereum {
+    // Requesting the client version and network id over JSON RPC version 2.0.
+    fn payload(&self) -> serde_json::Value {
+        self.batch.to_payload()
+    }
+
+    // Reads the batch results. The client version is required; the network id is reported when the node
+    // serves the `net` namespace, which some providers disable.
+    fn parse(&self, raw: &str) -> Result<NodeInfo, HandshakeError> {
+        let results = self.batch.parse_results(raw)?;
+        let version = match &results[0].result {
+            Ok(serde_json::Value::String(version)) => version.clone(),
+            Ok(other) => {
+                return Err(HandshakeError::InvalidResponse(format!(
+                    "Unexpected web3_clientVersion result {}",
+                    other
+                )))
+            }
+            Err(error) => return Err(error.clone().into()),
+        };
+        let network_id = results[1].result.clone().unwrap_or_default();
+        Ok(NodeInfo {
+            chain: "ethereum".to_string(),
+            version,
+            details: serde_json::json!({ "network_id": network_id }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fills the ids of the handshake's requests into a raw response, in place of `{client}` and `{net}`.
+    fn answer(ethereum: &Ethereum, raw: &str) -> String {
+        let payload = ethereum.payload();
+        raw.replace("{client}", &payload[0]["id"].to_string())
+            .replace("{net}", &payload[1]["id"].to_string())
+    }
+
+    // The client version and network id of the batch are reported.
+    #[test]
+    fn test_parse() {
+        let ethereum = Ethereum::new();
+        let raw = r#"[{"jsonrpc":"2.0","id":{net},"result":"1"},{"jsonrpc":"2.0","id":{client},"result":"Geth/v1.13.14-stable/linux-amd64/go1.21.7"}]"#;
+        let info = ethereum.parse(&answer(&ethereum, raw)).unwrap();
+
+        assert_eq!(info.chain, "ethereum");
+        assert_eq!(info.version, "Geth/v1.13.14-stable/linux-amd64/go1.21.7");
+        assert_eq!(info.details["network_id"], "1");
+    }
+
+    // A node without the `net` namespace still reports its version; one without a version fails.
+    #[test]
+    fn test_parse_errors() {
+        let ethereum = Ethereum::new();
+        let raw = r#"[{"jsonrpc":"2.0","id":{client},"result":"erigon/2.59.0"},{"jsonrpc":"2.0","id":{net},"error":{"code":-32601,"message":"Method not found"}}]"#;
+        let info = ethereum.parse(&answer(&ethereum, raw)).unwrap();
+        assert_eq!(info.details["network_id"], serde_json::Value::Null);
+
+        let raw = r#"[{"jsonrpc":"2.0","id":{client},"error":{"code":-32601,"message":"Method not found"}},{"jsonrpc":"2.0","id":{net},"result":"1"}]"#;
+        assert!(matches!(
+            ethereum.parse(&answer(&ethereum, raw)),
+            Err(HandshakeError::Rpc { .. })
+        ));
+    }
+}
@@ -0,0 +1,163 @@
+// The secp256k1 keys of devp2p nodes and ECIES, the public key encryption of the RLPx handshake messages.
+// A message is encrypted to a node's public key with a fresh ephemeral key: the x coordinate of their
+// shared point is stretched into an AES-128-CTR key and an HMAC-SHA256 key authenticating the ciphertext.
+use {
+    crate::error::HandshakeError, // Import the crate-wide error type.
+    aes::Aes128,                  // Import the cipher of the messages.
+    ctr::{
+        cipher::{KeyIvInit, StreamCipher},
+        Ctr128BE,
+    }, // Import counter mode.
+    hmac::{Hmac, Mac},            // Import the MAC of the messages.
+    libsecp256k1::{PublicKey, PublicKeyFormat, SecretKey}, // Import secp256k1 keys.
+    sha2::{Digest, Sha256},       // Import the hash of the key derivation.
+};
+
+// Size of a public key as carried by devp2p: its coordinates without the format prefix.
+pub const PUBLIC_KEY_SIZE: usize = 64;
+
+// Size of the ephemeral public key prefixed to a message, with its format prefix.
+const EPHEMERAL_KEY_SIZE: usize = 65;
+
+// Size of the initialization vector.
+const IV_SIZE: usize = 16;
+
+// Size of the MAC appended to a message.
+const MAC_SIZE: usize = 32;
+
+// Bytes added to a message by its encryption.
+pub const OVERHEAD: usize = EPHEMERAL_KEY_SIZE + IV_SIZE + MAC_SIZE;
+
+// Maps a failure of the handshake's cryptography to a handshake error.
+pub fn rlpx_error(message: &str) -> HandshakeError {
+    HandshakeError::Tls(format!("RLPx handshake failed: {}", message))
+}
+
+// Generates a fresh secret key.
+pub fn random_secret_key() -> SecretKey {
+    loop {
+        // Nearly every 32-byte value is a valid key; the rest are drawn again.
+        if let Ok(key) = SecretKey::parse(&rand::random()) {
+            return key;
+        }
+    }
+}
+
+// The public key as carried by devp2p.
+pub fn encode_public_key(key: &PublicKey) -> [u8; PUBLIC_KEY_SIZE] {
+    let mut bytes = [0; PUBLIC_KEY_SIZE];
+    bytes.copy_from_slice(&key.serialize()[1..]);
+    bytes
+}
+
+// Parses a public key as carried by devp2p.
+pub fn parse_public_key(bytes: &[u8]) -> Result<PublicKey, HandshakeError> {
+    PublicKey::parse_slice(bytes, Some(PublicKeyFormat::Raw))
+        .map_err(|_| rlpx_error("Invalid public key"))
+}
+
+// Elliptic-curve Diffie-Hellman: the x coordinate of the point shared by the two keys.
+pub fn ecdh(public: &PublicKey, secret: &SecretKey) -> Result<[u8; 32], HandshakeError> {
+    let mut point = *public;
+    point
+        .tweak_mul_assign(secret)
+        .map_err(|_| rlpx_error("Invalid shared secret"))?;
+    let mut x = [0; 32];
+    x.copy_from_slice(&point.serialize()[1..33]);
+    Ok(x)
+}
+
+// Derives the encryption key and the MAC key from the shared secret with the NIST SP 800-56
+// concatenation KDF, of which one SHA-256 round yields both.
+fn derive_keys(shared: &[u8; 32]) -> ([u8; 16], [u8; 32]) {
+    let derived = Sha256::new()
+        .chain_update(1u32.to_be_bytes())
+        .chain_update(shared)
+        .finalize();
+    let mut encryption_key = [0; 16];
+    encryption_key.copy_from_slice(&derived[..16]);
+    (encryption_key, Sha256::digest(&derived[16..]).into())
+}
+
+// HMAC-SHA256 of the IV and ciphertext, and the data shared by both sides, e.g. the message's size prefix.
+fn mac(key: &[u8; 32], iv_and_ciphertext: &[u8], shared_mac_data: &[u8]) -> [u8; MAC_SIZE] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(iv_and_ciphertext);
+    mac.update(shared_mac_data);
+    mac.finalize().into_bytes().into()
+}
+
+// Encrypts a message to the public key.
+pub fn encrypt(
+    remote: &PublicKey,
+    message: &[u8],
+    shared_mac_data: &[u8],
+) -> Result<Vec<u8>, HandshakeError> {
+    let ephemeral = random_secret_key();
+    let (encryption_key, mac_key) = derive_keys(&ecdh(remote, &ephemeral)?);
+
+    let iv: [u8; IV_SIZE] = rand::random();
+    let mut ciphertext = message.to_vec();
+    Ctr128BE::<Aes128>::new(&encryption_key.into(), &iv.into()).apply_keystream(&mut ciphertext);
+    let iv_and_ciphertext = [&iv[..], &ciphertext].concat();
+    let tag = mac(&mac_key, &iv_and_ciphertext, shared_mac_data);
+
+    Ok([
+        &PublicKey::from_secret_key(&ephemeral).serialize()[..],
+        &iv_and_ciphertext,
+        &tag,
+    ]
+    .concat())
+}
+
+// Decrypts a message encrypted to the secret key's public key, failing when it was tampered with.
+pub fn decrypt(
+    secret: &SecretKey,
+    data: &[u8],
+    shared_mac_data: &[u8],
+) -> Result<Vec<u8>, HandshakeError> {
+    if data.len() < OVERHEAD {
+        return Err(rlpx_error("Encrypted message too short"));
+    }
+    let (ephemeral, rest) = data.split_at(EPHEMERAL_KEY_SIZE);
+    let (iv_and_ciphertext, tag) = rest.split_at(rest.len() - MAC_SIZE);
+    let ephemeral = PublicKey::parse_slice(ephemeral, Some(PublicKeyFormat::Full))
+        .map_err(|_| rlpx_error("Invalid ephemeral key"))?;
+    let (encryption_key, mac_key) = derive_keys(&ecdh(&ephemeral, secret)?);
+    if mac(&mac_key, iv_and_ciphertext, shared_mac_data) != tag {
+        return Err(rlpx_error("Invalid message MAC"));
+    }
+
+    let (iv, ciphertext) = iv_and_ciphertext.split_at(IV_SIZE);
+    let mut message = ciphertext.to_vec();
+    let iv: [u8; IV_SIZE] = iv.try_into().expect("16 bytes");
+    Ctr128BE::<Aes128>::new(&encryption_key.into(), &iv.into()).apply_keystream(&mut message);
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A message encrypted to a key decrypts with it, and only with its shared MAC data.
+    #[test]
+    fn test_round_trip() {
+        let secret = random_secret_key();
+        let public = PublicKey::from_secret_key(&secret);
+        let encrypted = encrypt(&public, b"hello", b"\x00\x76").unwrap();
+        assert_eq!(encrypted.len(), OVERHEAD + 5);
+
+        assert_eq!(decrypt(&secret, &encrypted, b"\x00\x76").unwrap(), b"hello");
+        assert!(decrypt(&secret, &encrypted, b"\x00\x77").is_err());
+        assert!(decrypt(&random_secret_key(), &encrypted, b"\x00\x76").is_err());
+    }
+
+    // Public keys round-trip through their devp2p encoding.
+    #[test]
+    fn test_public_key_encoding() {
+        let public = PublicKey::from_secret_key(&random_secret_key());
+        let encoded = encode_public_key(&public);
+        assert_eq!(parse_public_key(&encoded).unwrap(), public);
+        assert!(parse_public_key(&encoded[1..]).is_err());
+    }
+}
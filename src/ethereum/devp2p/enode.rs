@@ -0,0 +1,150 @@
+// Enode URLs, the addresses of devp2p nodes: `enode://<node id>@<host>:<port>`, where the node ID is
+// the hex of the node's secp256k1 public key, which the RLPx handshake encrypts to.
+use {
+    super::ecies::{parse_public_key, PUBLIC_KEY_SIZE}, // Import public key parsing.
+    crate::{
+        error::HandshakeError,
+        solana::target::{AddressFamily, Target},
+    }, // Import the crate-wide error type and resolved targets.
+    libsecp256k1::PublicKey,                           // Import secp256k1 public keys.
+    std::{fmt, str::FromStr},                          // Import parsing and display formatting.
+};
+
+// Address of a node's devp2p listener and its public key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Enode {
+    pub node_id: [u8; PUBLIC_KEY_SIZE], // Public key of the node.
+    pub host: String,                   // IP address or name of the host.
+    pub port: u16,                      // TCP port of the listener.
+}
+
+impl Enode {
+    // The node's public key.
+    pub fn public_key(&self) -> Result<PublicKey, HandshakeError> {
+        parse_public_key(&self.node_id)
+    }
+
+    // Resolves the host of the listener, without blocking the runtime.
+    pub async fn resolve(&self) -> Result<Target, HandshakeError> {
+        let address = match self.host.contains(':') {
+            true => format!("[{}]:{}", self.host, self.port),
+            false => format!("{}:{}", self.host, self.port),
+        };
+        Target::parse(&address)
+            .map_err(|e| HandshakeError::Dns(format!("Unable to resolve {}: {}", address, e)))?
+            .lookup(AddressFamily::Any)
+            .await
+    }
+}
+
+impl FromStr for Enode {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| format!("Invalid enode URL '{}': {}", text, reason);
+        let rest = text
+            .strip_prefix("enode://")
+            .ok_or_else(|| invalid("expected the 'enode://' scheme"))?;
+        // The discovery port, if any, is irrelevant to the TCP listener.
+        let rest = rest.split('?').next().unwrap_or_default();
+        let (node_id, address) = rest
+            .split_once('@')
+            .ok_or_else(|| invalid("expected '<node id>@<host>:<port>'"))?;
+        let node_id: [u8; PUBLIC_KEY_SIZE] = hex::decode(node_id)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| invalid("the node ID must be 128 hex digits"))?;
+        parse_public_key(&node_id).map_err(|_| invalid("the node ID is not a public key"))?;
+        let (host, port) = address
+            .rsplit_once(':')
+            .ok_or_else(|| invalid("expected '<host>:<port>'"))?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return Err(invalid("the host is empty"));
+        }
+        Ok(Enode {
+            node_id,
+            host: host.to_string(),
+            port: port.parse().map_err(|_| invalid("the port is invalid"))?,
+        })
+    }
+}
+
+// Implement the Display trait for Enode, giving back its URL.
+impl fmt::Display for Enode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.host.contains(':') {
+            true => write!(
+                f,
+                "enode://{}@[{}]:{}",
+                hex::encode(self.node_id),
+                self.host,
+                self.port
+            ),
+            false => write!(
+                f,
+                "enode://{}@{}:{}",
+                hex::encode(self.node_id),
+                self.host,
+                self.port
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::ethereum::devp2p::ecies::{encode_public_key, random_secret_key},
+        test_case::test_case,
+    };
+
+    // The hex node ID of a fresh key.
+    fn node_id() -> String {
+        hex::encode(encode_public_key(&PublicKey::from_secret_key(
+            &random_secret_key(),
+        )))
+    }
+
+    // Enode URLs are parsed, ignoring the discovery port.
+    #[test_case("127.0.0.1:30303", "127.0.0.1", 30303; "when host is ipv4")]
+    #[test_case("[::1]:30303", "::1", 30303; "when host is ipv6")]
+    #[test_case("node.example.com:30304?discport=30301", "node.example.com", 30304; "when url has a discovery port")]
+    fn test_parse(address: &str, host: &str, port: u16) {
+        let id = node_id();
+        let enode: Enode = format!("enode://{}@{}", id, address).parse().unwrap();
+        assert_eq!(hex::encode(enode.node_id), id);
+        assert_eq!(enode.host, host);
+        assert_eq!(enode.port, port);
+        assert_eq!(
+            enode.to_string(),
+            format!("enode://{}@{}", id, address.split('?').next().unwrap())
+        );
+    }
+
+    // The listener's host is resolved; a name that does not resolve is a DNS error.
+    #[tokio::test]
+    async fn test_resolve() {
+        let enode: Enode = format!("enode://{}@[::1]:30303", node_id())
+            .parse()
+            .unwrap();
+        let target = enode.resolve().await.unwrap();
+        assert_eq!(target.resolved, vec!["[::1]:30303".parse().unwrap()]);
+
+        let enode: Enode = format!("enode://{}@node.invalid:30303", node_id())
+            .parse()
+            .unwrap();
+        let error = enode.resolve().await.unwrap_err();
+        assert!(matches!(error, HandshakeError::Dns(_)), "{}", error);
+    }
+
+    // Anything but a node ID and a listener is rejected.
+    #[test_case("127.0.0.1:30303"; "when url has no scheme")]
+    #[test_case("enode://abcd@127.0.0.1:30303"; "when node id is too short")]
+    #[test_case("enode://{id}@127.0.0.1"; "when port is missing")]
+    fn test_parse_failures(url: &str) {
+        let url = url.replace("{id}", &node_id());
+        assert!(url.parse::<Enode>().is_err());
+    }
+}
@@ -0,0 +1,195 @@
+// Messages of the devp2p base protocol needed for the capability exchange that follows the RLPx handshake.
+// Each side sends a `Hello` announcing its client and capabilities; a node unwilling to talk sends a
+// `Disconnect` with its reason instead.
+use {
+    super::{ecies::PUBLIC_KEY_SIZE, rlp}, // Import the public key size and RLP.
+    crate::error::HandshakeError,         // Import the crate-wide error type.
+    serde::Serialize,                     // Import serialization of what the node announced.
+    std::fmt,                             // Import display formatting.
+};
+
+// ID of the `Hello` message.
+pub const HELLO: u64 = 0x00;
+
+// ID of the `Disconnect` message.
+pub const DISCONNECT: u64 = 0x01;
+
+// Version of the base protocol announced; version 5 compresses messages after `Hello` with Snappy.
+pub const PROTOCOL_VERSION: u64 = 5;
+
+// Client ID announced in our `Hello`.
+pub const CLIENT_ID: &str = concat!("handshake/v", env!("CARGO_PKG_VERSION"));
+
+// A capability: a subprotocol and its version, e.g. `eth/68`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capability {
+    pub name: String, // Name of the subprotocol.
+    pub version: u64, // Version of the subprotocol.
+}
+
+impl Capability {
+    // Constructor for Capability.
+    pub fn new(name: &str, version: u64) -> Self {
+        Capability {
+            name: name.to_string(),
+            version,
+        }
+    }
+}
+
+// Implement the Display trait for Capability, in the usual `name/version` form.
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.name, self.version)
+    }
+}
+
+// The `Hello` message.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Hello {
+    pub protocol_version: u64, // Version of the base protocol.
+    pub client_id: String,     // Client software, e.g. "Geth/v1.13.14-stable/linux-amd64/go1.21.7".
+    #[serde(serialize_with = "serialize_capabilities")]
+    pub capabilities: Vec<Capability>, // Subprotocols the node supports.
+    pub listen_port: u64,      // Port the node listens on, or 0 when not announced.
+    #[serde(serialize_with = "serialize_node_id")]
+    pub node_id: [u8; PUBLIC_KEY_SIZE], // Public key of the node.
+}
+
+// Serializes capabilities in their `name/version` form.
+fn serialize_capabilities<S: serde::Serializer>(
+    capabilities: &[Capability],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(capabilities.iter().map(ToString::to_string))
+}
+
+// Serializes a node ID as hex.
+fn serialize_node_id<S: serde::Serializer>(
+    node_id: &[u8; PUBLIC_KEY_SIZE],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex::encode(node_id))
+}
+
+impl Hello {
+    // Our `Hello`, announcing the `eth` versions current nodes speak so they do not drop us on sight.
+    pub fn new(node_id: [u8; PUBLIC_KEY_SIZE]) -> Self {
+        Hello {
+            protocol_version: PROTOCOL_VERSION,
+            client_id: CLIENT_ID.to_string(),
+            capabilities: vec![Capability::new("eth", 67), Capability::new("eth", 68)],
+            listen_port: 0,
+            node_id,
+        }
+    }
+
+    // Encodes the message's payload.
+    pub fn encode(&self) -> Vec<u8> {
+        let capabilities: Vec<Vec<u8>> = self
+            .capabilities
+            .iter()
+            .map(|capability| {
+                rlp::encode_list(&[
+                    rlp::encode_bytes(capability.name.as_bytes()),
+                    rlp::encode_uint(capability.version),
+                ])
+            })
+            .collect();
+        rlp::encode_list(&[
+            rlp::encode_uint(self.protocol_version),
+            rlp::encode_bytes(self.client_id.as_bytes()),
+            rlp::encode_list(&capabilities),
+            rlp::encode_uint(self.listen_port),
+            rlp::encode_bytes(&self.node_id),
+        ])
+    }
+
+    // Decodes the message's payload, ignoring any fields added by later versions.
+    pub fn decode(payload: &[u8]) -> Result<Self, HandshakeError> {
+        let item = rlp::decode(payload)?;
+        let fields = item.list()?;
+        if fields.len() < 5 {
+            return Err(HandshakeError::InvalidResponse(
+                "Hello has missing fields".to_string(),
+            ));
+        }
+        let capabilities = fields[2]
+            .list()?
+            .iter()
+            .map(|capability| {
+                let parts = capability.list()?;
+                match parts {
+                    [name, version, ..] => Ok(Capability {
+                        name: String::from_utf8_lossy(name.bytes()?).to_string(),
+                        version: version.uint()?,
+                    }),
+                    _ => Err(HandshakeError::InvalidResponse(
+                        "Invalid capability in Hello".to_string(),
+                    )),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Hello {
+            protocol_version: fields[0].uint()?,
+            client_id: String::from_utf8_lossy(fields[1].bytes()?).to_string(),
+            capabilities,
+            listen_port: fields[3].uint()?,
+            node_id: fields[4].bytes()?.try_into().map_err(|_| {
+                HandshakeError::InvalidResponse("Invalid node ID in Hello".to_string())
+            })?,
+        })
+    }
+}
+
+// Decodes the reason of a `Disconnect`, sent either as a list of the reason or as the bare reason.
+pub fn disconnect_reason(payload: &[u8]) -> String {
+    let code = match rlp::decode(payload) {
+        Ok(rlp::Item::List(items)) => items.first().and_then(|item| item.uint().ok()),
+        Ok(item) => item.uint().ok(),
+        Err(_) => None,
+    };
+    let reason = match code {
+        Some(0x00) => "disconnect requested",
+        Some(0x01) => "TCP sub-system error",
+        Some(0x02) => "breach of protocol",
+        Some(0x03) => "useless peer",
+        Some(0x04) => "too many peers",
+        Some(0x05) => "already connected",
+        Some(0x06) => "incompatible P2P protocol version",
+        Some(0x07) => "null node identity received",
+        Some(0x08) => "client quitting",
+        Some(0x09) => "unexpected identity",
+        Some(0x0a) => "connected to self",
+        Some(0x0b) => "ping timeout",
+        Some(0x10) => "subprotocol error",
+        _ => "unknown reason",
+    };
+    match code {
+        Some(code) => format!("{} ({:#04x})", reason, code),
+        None => reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, test_case::test_case};
+
+    // Hello round-trips through its encoding.
+    #[test]
+    fn test_hello_round_trip() {
+        let hello = Hello::new([7; PUBLIC_KEY_SIZE]);
+        let decoded = Hello::decode(&hello.encode()).unwrap();
+        assert_eq!(decoded, hello);
+        assert_eq!(decoded.capabilities[1].to_string(), "eth/68");
+        assert!(Hello::decode(&rlp::encode_list(&[rlp::encode_uint(5)])).is_err());
+    }
+
+    // Disconnect reasons are decoded in both of their forms.
+    #[test_case(&[0xc1, 0x04], "too many peers (0x04)"; "when reason is a list")]
+    #[test_case(&[0x03], "useless peer (0x03)"; "when reason is bare")]
+    #[test_case(&[0xc0], "unknown reason"; "when reason is missing")]
+    fn test_disconnect_reason(payload: &[u8], expected: &str) {
+        assert_eq!(disconnect_reason(payload), expected);
+    }
+}
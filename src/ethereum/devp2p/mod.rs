@@ -0,0 +1,15 @@
+// ECIES and the secp256k1 keys of devp2p nodes
+pub mod ecies;
+// Enode URL parsing
+pub mod enode;
+// Hello and Disconnect, the base protocol messages of the capability exchange
+pub mod message;
+// devp2p node implementation
+pub mod node;
+// Recursive Length Prefix serialization
+pub mod rlp;
+// The RLPx handshake and frame encryption
+pub mod rlpx;
+
+// The types needed to handshake with a devp2p node.
+pub use {enode::Enode, message::Hello, node::DevP2pNode};
@@ -0,0 +1,186 @@
+// Module for handshaking with an execution-layer node over its devp2p port: the RLPx handshake
+// proves the node holds the key of its enode URL, then both sides exchange `Hello` messages.
+use crate::{
+    error::HandshakeError,
+    ethereum::devp2p::{
+        ecies::{encode_public_key, random_secret_key},
+        message::{disconnect_reason, Hello, DISCONNECT, HELLO},
+        rlpx::RlpxSession,
+    },
+    solana::{
+        node::Handshake,
+        target::Target,
+        transport::{
+            tcp::{open_stream, ConnectOptions, Connectable},
+            traced, with_timeout,
+        },
+    },
+}; // Import the crate error type, the devp2p protocols, the handshake trait and the TCP connection helpers.
+use async_trait::async_trait; // Enables async trait methods, crucial for async network operations.
+use libsecp256k1::{PublicKey, SecretKey}; // secp256k1 keys of the node and of ours.
+use std::fmt; // Standard library import for display formatting.
+use tracing::info; // Import the `info` macro for logging informational messages.
+
+// Struct defining an execution-layer node, reached on its devp2p port over TCP.
+pub struct DevP2pNode {
+    pub remote: Target, // The remote endpoint: its name as given and the addresses it resolved to.
+    pub node_id: PublicKey, // Public key of the node, from its enode URL.
+    pub connect: ConnectOptions, // Proxy the connection is tunnelled through; RLPx secures it itself.
+    secret: SecretKey,           // Ephemeral node key of ours.
+}
+
+impl DevP2pNode {
+    // Constructor for DevP2pNode, taking a target, or a socket address, and the node's public key.
+    // A fresh node key is generated for each node so the handshake needs no configuration.
+    pub fn new(remote: impl Into<Target>, node_id: PublicKey) -> Self {
+        DevP2pNode {
+            remote: remote.into(),
+            node_id,
+            connect: ConnectOptions::default(),
+            secret: random_secret_key(),
+        }
+    }
+
+    // Performs the RLPx handshake and the `Hello` exchange, returning the node's `Hello`.
+    pub async fn hello(&self, timeout: Option<u32>) -> Result<Hello, HandshakeError> {
+        traced(&self.remote, "rlpx", async {
+            let remote = self.remote.to_string();
+            let stream = open_stream(&remote, &self.connect.route(&self.remote), timeout).await?;
+
+            let mut session = with_timeout(
+                "rlpx handshake",
+                timeout,
+                RlpxSession::connect(stream, &self.secret, &self.node_id),
+            )
+            .await?;
+            info!("RLPx session established with {}", remote);
+
+            // Both sides send their Hello first, so ours goes out before the node's is read.
+            let ours = Hello::new(encode_public_key(&PublicKey::from_secret_key(&self.secret)));
+            let hello = with_timeout("read", timeout, async {
+                session.send_message(HELLO, &ours.encode()).await?;
+                match session.recv_message().await? {
+                    (HELLO, payload) => Hello::decode(&payload),
+                    (DISCONNECT, payload) => Err(HandshakeError::Protocol(format!(
+                        "The node disconnected: {}",
+                        disconnect_reason(&payload)
+                    ))),
+                    (id, _) => Err(HandshakeError::Protocol(format!(
+                        "Expected Hello, got message {:#04x}",
+                        id
+                    ))),
+                }
+            })
+            .await?;
+            if hello.node_id != encode_public_key(&self.node_id) {
+                return Err(HandshakeError::Protocol(
+                    "The node announced another node ID than its enode URL's".to_string(),
+                ));
+            }
+            info!(
+                "Node {} runs {} with capabilities {:?}",
+                remote,
+                hello.client_id,
+                hello
+                    .capabilities
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+            );
+            Ok(hello)
+        })
+        .await
+    }
+}
+
+impl Connectable for DevP2pNode {
+    fn connect_options(&mut self) -> &mut ConnectOptions {
+        &mut self.connect
+    }
+}
+
+// Implement the Display trait for DevP2pNode for easy logging and debugging.
+impl fmt::Display for DevP2pNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DevP2pNode({})", self.remote) // Custom display format showing the remote address.
+    }
+}
+
+// Async trait for performing the handshake operation, returning the client the node announced.
+#[async_trait]
+impl Handshake for DevP2pNode {
+    async fn shake(&self, timeout: Option<u32>) -> Result<String, HandshakeError> {
+        Ok(self.hello(timeout).await?.client_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Test suite for DevP2pNode functionality, run against an in-process node.
+    use {
+        super::*,
+        crate::ethereum::devp2p::{message::Capability, rlp},
+        std::net::SocketAddr,
+        tokio::net::TcpListener,
+    };
+
+    // Accepts a single connection and answers the handshake with the given message,
+    // returning the node's address and public key.
+    async fn serve(reply: Option<(u64, Vec<u8>)>) -> (SocketAddr, PublicKey) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let secret = random_secret_key();
+        let public = PublicKey::from_secret_key(&secret);
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut session = RlpxSession::accept(socket, &secret).await.unwrap();
+            let (id, _) = session.recv_message().await.unwrap();
+            assert_eq!(id, HELLO);
+            let (id, payload) = reply.unwrap_or_else(|| {
+                let mut hello = Hello::new(encode_public_key(&public));
+                hello.client_id = "Geth/v1.13.14-stable/linux-amd64/go1.21.7".to_string();
+                hello.capabilities = vec![Capability::new("eth", 68), Capability::new("snap", 1)];
+                (HELLO, hello.encode())
+            });
+            session.send_message(id, &payload).await.unwrap();
+            // Keep the connection open until we close it.
+            let _ = session.recv_message().await;
+        });
+        (address, public)
+    }
+
+    // Verifies that a DevP2pNode can be correctly instantiated and displayed.
+    #[test]
+    fn test_devp2p_node_creation() {
+        let addr: SocketAddr = "127.0.0.1:30303".parse().unwrap();
+        let node = DevP2pNode::new(addr, PublicKey::from_secret_key(&random_secret_key()));
+
+        assert_eq!(node.to_string(), "DevP2pNode(127.0.0.1:30303)"); // Checks the display format.
+    }
+
+    // The handshake returns the node's Hello.
+    #[tokio::test]
+    async fn test_hello() {
+        let (address, public) = serve(None).await;
+
+        let hello = DevP2pNode::new(address, public)
+            .hello(Some(5))
+            .await
+            .unwrap();
+        assert_eq!(hello.client_id, "Geth/v1.13.14-stable/linux-amd64/go1.21.7");
+        assert_eq!(hello.capabilities[1].to_string(), "snap/1");
+    }
+
+    // A node sending Disconnect instead of Hello fails the handshake with its reason.
+    #[tokio::test]
+    async fn test_disconnect() {
+        let reply = (DISCONNECT, rlp::encode_list(&[rlp::encode_uint(0x04)]));
+        let (address, public) = serve(Some(reply)).await;
+
+        let result = DevP2pNode::new(address, public).shake(Some(5)).await;
+        match result {
+            Err(HandshakeError::Protocol(message)) => assert!(message.contains("too many peers")),
+            other => panic!("Expected a protocol error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+// Recursive Length Prefix, the serialization of every devp2p message.
+// Only what the handshake needs is supported: byte strings, unsigned integers and lists of them.
+use crate::error::HandshakeError; // Import the crate-wide error type.
+
+// Encodes a byte string.
+pub fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    match bytes {
+        [byte] if *byte < 0x80 => vec![*byte],
+        _ => [encode_length(bytes.len(), 0x80), bytes.to_vec()].concat(),
+    }
+}
+
+// Encodes an unsigned integer, as its big-endian bytes without leading zeros.
+pub fn encode_uint(value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let start = bytes
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(bytes.len());
+    encode_bytes(&bytes[start..])
+}
+
+// Encodes a list of already encoded items.
+pub fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload = items.concat();
+    [encode_length(payload.len(), 0xc0), payload].concat()
+}
+
+// Encodes the prefix of a payload: its length added to the offset when short, otherwise the size
+// of its big-endian length followed by the length itself.
+fn encode_length(length: usize, offset: u8) -> Vec<u8> {
+    if length < 56 {
+        return vec![offset + length as u8];
+    }
+    let bytes = (length as u64).to_be_bytes();
+    let start = bytes
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(bytes.len());
+    [
+        &[offset + 55 + (bytes.len() - start) as u8],
+        &bytes[start..],
+    ]
+    .concat()
+}
+
+// A decoded item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    Bytes(Vec<u8>),  // A byte string.
+    List(Vec<Item>), // A list of items.
+}
+
+impl Item {
+    // The item as a byte string.
+    pub fn bytes(&self) -> Result<&[u8], HandshakeError> {
+        match self {
+            Item::Bytes(bytes) => Ok(bytes),
+            Item::List(_) => Err(invalid("Expected an RLP string, got a list")),
+        }
+    }
+
+    // The item as an unsigned integer.
+    pub fn uint(&self) -> Result<u64, HandshakeError> {
+        let bytes = self.bytes()?;
+        if bytes.len() > 8 {
+            return Err(invalid("RLP integer exceeds 64 bits"));
+        }
+        Ok(bytes
+            .iter()
+            .fold(0, |value, byte| (value << 8) | u64::from(*byte)))
+    }
+
+    // The item as a list.
+    pub fn list(&self) -> Result<&[Item], HandshakeError> {
+        match self {
+            Item::List(items) => Ok(items),
+            Item::Bytes(_) => Err(invalid("Expected an RLP list, got a string")),
+        }
+    }
+}
+
+// Maps malformed RLP to a handshake error.
+fn invalid(message: &str) -> HandshakeError {
+    HandshakeError::InvalidResponse(message.to_string())
+}
+
+// Decodes the item at the start of the bytes, ignoring anything after it, e.g. the random padding
+// of handshake messages.
+pub fn decode(bytes: &[u8]) -> Result<Item, HandshakeError> {
+    decode_prefix(bytes).map(|(item, _)| item)
+}
+
+// Decodes the item at the start of the bytes, returning it and the bytes after it, e.g. the ID of a
+// message and its payload.
+pub fn decode_prefix(bytes: &[u8]) -> Result<(Item, &[u8]), HandshakeError> {
+    let prefix = *bytes.first().ok_or_else(|| invalid("Truncated RLP item"))?;
+    let (offset, length, list) = match prefix {
+        0x00..=0x7f => return Ok((Item::Bytes(vec![prefix]), &bytes[1..])),
+        0x80..=0xb7 => (1, usize::from(prefix - 0x80), false),
+        0xb8..=0xbf => (1 + usize::from(prefix - 0xb7), 0, false),
+        0xc0..=0xf7 => (1, usize::from(prefix - 0xc0), true),
+        0xf8..=0xff => (1 + usize::from(prefix - 0xf7), 0, true),
+    };
+    // Long items carry their length after the prefix.
+    let length = match offset {
+        1 => length,
+        _ => {
+            let size = bytes
+                .get(1..offset)
+                .filter(|size| size.len() <= 4)
+                .ok_or_else(|| invalid("Truncated RLP length"))?;
+            size.iter()
+                .fold(0, |length, byte| (length << 8) | usize::from(*byte))
+        }
+    };
+    let payload = bytes
+        .get(offset..offset + length)
+        .ok_or_else(|| invalid("Truncated RLP item"))?;
+    let rest = &bytes[offset + length..];
+    if !list {
+        return Ok((Item::Bytes(payload.to_vec()), rest));
+    }
+    let mut items = Vec::new();
+    let mut remaining = payload;
+    while !remaining.is_empty() {
+        let (item, after) = decode_prefix(remaining)?;
+        items.push(item);
+        remaining = after;
+    }
+    Ok((Item::List(items), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, test_case::test_case};
+
+    // Strings and integers are encoded as the specification's examples.
+    #[test_case(b"", &[0x80]; "when string is empty")]
+    #[test_case(b"\x0f", &[0x0f]; "when string is a single small byte")]
+    #[test_case(b"dog", &[0x83, b'd', b'o', b'g']; "when string is short")]
+    fn test_encode_bytes(bytes: &[u8], expected: &[u8]) {
+        assert_eq!(encode_bytes(bytes), expected);
+    }
+
+    // Lists round-trip through their encoding, including long ones.
+    #[test]
+    fn test_list_round_trip() {
+        let long = vec![0xaa; 100];
+        let encoded = encode_list(&[encode_uint(0), encode_uint(1024), encode_bytes(&long)]);
+        assert_eq!(&encoded[..2], &[0xf8, 0x6a]);
+
+        let item = decode(&encoded).unwrap();
+        let items = item.list().unwrap();
+        assert_eq!(items[0].uint().unwrap(), 0);
+        assert_eq!(items[1].uint().unwrap(), 1024);
+        assert_eq!(items[2].bytes().unwrap(), long.as_slice());
+        assert!(decode(&encoded[..50]).is_err());
+    }
+}
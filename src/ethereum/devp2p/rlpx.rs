@@ -0,0 +1,369 @@
+// RLPx, the encrypted transport of devp2p, in its EIP-8 form.
+// The dialer sends an `auth` message encrypted to the listener's public key, carrying a signature made
+// with its ephemeral key, and the listener answers with an `ack` carrying its own ephemeral key. Both
+// then derive the AES-256-CTR key encrypting frames and the keccak256 states authenticating them.
+use {
+    super::{
+        ecies::{
+            self, ecdh, encode_public_key, parse_public_key, random_secret_key, rlpx_error,
+            PUBLIC_KEY_SIZE,
+        },
+        rlp,
+    }, // Import ECIES, secp256k1 key helpers and RLP.
+    crate::error::HandshakeError, // Import the crate-wide error type.
+    aes::{
+        cipher::{BlockEncrypt, KeyInit},
+        Aes256,
+    }, // Import AES, of which the frame MACs use single blocks.
+    ctr::{
+        cipher::{KeyIvInit, StreamCipher},
+        Ctr128BE,
+    }, // Import counter mode, encrypting frames.
+    libsecp256k1::{recover, sign, Message, PublicKey, RecoveryId, SecretKey, Signature},
+    sha3::{Digest, Keccak256}, // Import keccak256, the hash of the secrets and frame MACs.
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    }, // Import the TCP stream and its IO helpers.
+};
+
+// Version of the handshake messages announced by EIP-8 implementations.
+const HANDSHAKE_VERSION: u64 = 4;
+
+// Size of the nonces exchanged in the handshake.
+const NONCE_SIZE: usize = 32;
+
+// Size of a recoverable signature: r, s and the recovery ID.
+const SIGNATURE_SIZE: usize = 65;
+
+// Size of a frame header, and of every MAC.
+const BLOCK_SIZE: usize = 16;
+
+// Largest frame accepted, that of devp2p's largest messages.
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+// Header data of every frame: the RLP list of capability ID 0 and context ID 0.
+const HEADER_DATA: [u8; 3] = [0xc2, 0x80, 0x80];
+
+// AES-256-CTR, the cipher of frames.
+type FrameCipher = Ctr128BE<Aes256>;
+
+// XOR of two 32-byte values.
+fn xor(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut result = [0; 32];
+    for (i, byte) in result.iter_mut().enumerate() {
+        *byte = a[i] ^ b[i];
+    }
+    result
+}
+
+// keccak256 of the concatenated parts.
+fn keccak(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+// Encrypts a handshake message to the public key, prefixed with its size, which the encryption
+// authenticates. Random padding hides the size of the body.
+fn seal(remote: &PublicKey, body: &[u8]) -> Result<Vec<u8>, HandshakeError> {
+    let padding = vec![0; 100 + usize::from(rand::random::<u8>())];
+    let size = ((body.len() + padding.len() + ecies::OVERHEAD) as u16).to_be_bytes();
+    let encrypted = ecies::encrypt(remote, &[body, &padding].concat(), &size)?;
+    Ok([&size[..], &encrypted].concat())
+}
+
+// Reads a sealed handshake message, returning it as received and its decrypted body.
+async fn read_sealed(
+    stream: &mut TcpStream,
+    secret: &SecretKey,
+) -> Result<(Vec<u8>, rlp::Item), HandshakeError> {
+    let mut size = [0; 2];
+    stream.read_exact(&mut size).await?;
+    let mut encrypted = vec![0; usize::from(u16::from_be_bytes(size))];
+    stream.read_exact(&mut encrypted).await?;
+    let body = rlp::decode(&ecies::decrypt(secret, &encrypted, &size)?)?;
+    Ok(([&size[..], &encrypted].concat(), body))
+}
+
+// Takes a fixed-size byte string from a handshake message.
+fn fixed<const N: usize>(item: Option<&rlp::Item>, name: &str) -> Result<[u8; N], HandshakeError> {
+    item.ok_or_else(|| rlpx_error(&format!("Missing {}", name)))?
+        .bytes()?
+        .try_into()
+        .map_err(|_| rlpx_error(&format!("Invalid {}", name)))
+}
+
+// What both sides contribute to the secrets of the session.
+struct Exchange<'a> {
+    ephemeral: &'a SecretKey,       // Our ephemeral key.
+    remote_ephemeral: PublicKey,    // The peer's ephemeral key.
+    nonce: [u8; NONCE_SIZE],        // Our nonce.
+    remote_nonce: [u8; NONCE_SIZE], // The peer's nonce.
+    sent: &'a [u8],                 // Our handshake message, as sent.
+    received: &'a [u8],             // The peer's handshake message, as received.
+}
+
+// An established RLPx session, exchanging authenticated and encrypted frames.
+pub struct RlpxSession {
+    stream: TcpStream,                    // The TCP connection.
+    pub remote_id: [u8; PUBLIC_KEY_SIZE], // Public key of the peer.
+    egress: FrameCipher,                  // Cipher of the frames sent.
+    ingress: FrameCipher,                 // Cipher of the frames received.
+    mac_cipher: Aes256,                   // Cipher of the MAC seeds.
+    egress_mac: Keccak256,                // Running MAC of the frames sent.
+    ingress_mac: Keccak256,               // Running MAC of the frames received.
+}
+
+impl RlpxSession {
+    // Performs the handshake as the dialer, with the listener's public key.
+    pub async fn connect(
+        mut stream: TcpStream,
+        secret: &SecretKey,
+        remote: &PublicKey,
+    ) -> Result<Self, HandshakeError> {
+        let ephemeral = random_secret_key();
+        let nonce: [u8; NONCE_SIZE] = rand::random();
+
+        // Prove ownership of the ephemeral key with a signature over the static shared secret.
+        let signed = xor(&ecdh(remote, secret)?, &nonce);
+        let (signature, recovery_id) = sign(&Message::parse(&signed), &ephemeral);
+        let signature = [&signature.serialize()[..], &[recovery_id.serialize()]].concat();
+        let auth = seal(
+            remote,
+            &rlp::encode_list(&[
+                rlp::encode_bytes(&signature),
+                rlp::encode_bytes(&encode_public_key(&PublicKey::from_secret_key(secret))),
+                rlp::encode_bytes(&nonce),
+                rlp::encode_uint(HANDSHAKE_VERSION),
+            ]),
+        )?;
+        stream.write_all(&auth).await?;
+
+        let (ack, body) = read_sealed(&mut stream, secret).await?;
+        let fields = body.list()?;
+        let remote_ephemeral: [u8; PUBLIC_KEY_SIZE] = fixed(fields.first(), "ephemeral key")?;
+        let exchange = Exchange {
+            ephemeral: &ephemeral,
+            remote_ephemeral: parse_public_key(&remote_ephemeral)?,
+            nonce,
+            remote_nonce: fixed(fields.get(1), "nonce")?,
+            sent: &auth,
+            received: &ack,
+        };
+        Self::new(stream, encode_public_key(remote), exchange, true)
+    }
+
+    // Performs the handshake as the listener, learning the dialer's public key.
+    pub async fn accept(mut stream: TcpStream, secret: &SecretKey) -> Result<Self, HandshakeError> {
+        let (auth, body) = read_sealed(&mut stream, secret).await?;
+        let fields = body.list()?;
+        let signature: [u8; SIGNATURE_SIZE] = fixed(fields.first(), "signature")?;
+        let remote_id: [u8; PUBLIC_KEY_SIZE] = fixed(fields.get(1), "public key")?;
+        let remote_nonce: [u8; NONCE_SIZE] = fixed(fields.get(2), "nonce")?;
+
+        // Recover the dialer's ephemeral key from its signature over the static shared secret.
+        let remote = parse_public_key(&remote_id)?;
+        let signed = xor(&ecdh(&remote, secret)?, &remote_nonce);
+        let recovery_id =
+            RecoveryId::parse(signature[64]).map_err(|_| rlpx_error("Invalid recovery ID"))?;
+        let remote_ephemeral = Signature::parse_standard_slice(&signature[..64])
+            .and_then(|signature| recover(&Message::parse(&signed), &signature, &recovery_id))
+            .map_err(|_| rlpx_error("Invalid auth signature"))?;
+
+        let ephemeral = random_secret_key();
+        let nonce: [u8; NONCE_SIZE] = rand::random();
+        let ack = seal(
+            &remote,
+            &rlp::encode_list(&[
+                rlp::encode_bytes(&encode_public_key(&PublicKey::from_secret_key(&ephemeral))),
+                rlp::encode_bytes(&nonce),
+                rlp::encode_uint(HANDSHAKE_VERSION),
+            ]),
+        )?;
+        stream.write_all(&ack).await?;
+
+        let exchange = Exchange {
+            ephemeral: &ephemeral,
+            remote_ephemeral,
+            nonce,
+            remote_nonce,
+            sent: &ack,
+            received: &auth,
+        };
+        Self::new(stream, remote_id, exchange, false)
+    }
+
+    // Derives the secrets of the session from the handshake.
+    fn new(
+        stream: TcpStream,
+        remote_id: [u8; PUBLIC_KEY_SIZE],
+        exchange: Exchange,
+        initiator: bool,
+    ) -> Result<Self, HandshakeError> {
+        let ephemeral_key = ecdh(&exchange.remote_ephemeral, exchange.ephemeral)?;
+        let nonces = match initiator {
+            true => keccak(&[&exchange.remote_nonce, &exchange.nonce]),
+            false => keccak(&[&exchange.nonce, &exchange.remote_nonce]),
+        };
+        let shared_secret = keccak(&[&ephemeral_key, &nonces]);
+        let aes_secret = keccak(&[&ephemeral_key, &shared_secret]);
+        let mac_secret = keccak(&[&ephemeral_key, &aes_secret]);
+
+        let mut egress_mac = Keccak256::new();
+        egress_mac.update(xor(&mac_secret, &exchange.remote_nonce));
+        egress_mac.update(exchange.sent);
+        let mut ingress_mac = Keccak256::new();
+        ingress_mac.update(xor(&mac_secret, &exchange.nonce));
+        ingress_mac.update(exchange.received);
+
+        let iv = [0; BLOCK_SIZE];
+        Ok(RlpxSession {
+            stream,
+            remote_id,
+            egress: FrameCipher::new(&aes_secret.into(), &iv.into()),
+            ingress: FrameCipher::new(&aes_secret.into(), &iv.into()),
+            mac_cipher: Aes256::new(&mac_secret.into()),
+            egress_mac,
+            ingress_mac,
+        })
+    }
+
+    // Sends a message: its RLP-encoded ID followed by its payload.
+    pub async fn send_message(&mut self, id: u64, payload: &[u8]) -> Result<(), HandshakeError> {
+        self.write_frame(&[rlp::encode_uint(id), payload.to_vec()].concat())
+            .await
+    }
+
+    // Receives a message, returning its ID and its payload.
+    pub async fn recv_message(&mut self) -> Result<(u64, Vec<u8>), HandshakeError> {
+        let frame = self.read_frame().await?;
+        let (id, payload) = rlp::decode_prefix(&frame)?;
+        Ok((id.uint()?, payload.to_vec()))
+    }
+
+    // Encrypts and authenticates a frame, then sends it.
+    async fn write_frame(&mut self, data: &[u8]) -> Result<(), HandshakeError> {
+        let mut header = [0; BLOCK_SIZE];
+        header[..3].copy_from_slice(&(data.len() as u32).to_be_bytes()[1..]);
+        header[3..6].copy_from_slice(&HEADER_DATA);
+        self.egress.apply_keystream(&mut header);
+        let header_mac = update_mac(&mut self.egress_mac, &self.mac_cipher, &header);
+
+        let mut frame = data.to_vec();
+        frame.resize(data.len().next_multiple_of(BLOCK_SIZE), 0);
+        self.egress.apply_keystream(&mut frame);
+        self.egress_mac.update(&frame);
+        let seed = digest(&self.egress_mac);
+        let frame_mac = update_mac(&mut self.egress_mac, &self.mac_cipher, &seed);
+
+        let bytes = [&header[..], &header_mac, &frame, &frame_mac].concat();
+        Ok(self.stream.write_all(&bytes).await?)
+    }
+
+    // Reads a frame, checks its MACs and decrypts it.
+    async fn read_frame(&mut self) -> Result<Vec<u8>, HandshakeError> {
+        let mut header = [0; 2 * BLOCK_SIZE];
+        self.stream.read_exact(&mut header).await?;
+        let (header, mac) = header.split_at_mut(BLOCK_SIZE);
+        if update_mac(&mut self.ingress_mac, &self.mac_cipher, header) != mac {
+            return Err(rlpx_error("Invalid frame header MAC"));
+        }
+        self.ingress.apply_keystream(header);
+        let size = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+        if size > MAX_FRAME_SIZE {
+            return Err(HandshakeError::InvalidResponse(format!(
+                "RLPx frame of {} bytes exceeds the limit",
+                size
+            )));
+        }
+
+        let mut frame = vec![0; size.next_multiple_of(BLOCK_SIZE) + BLOCK_SIZE];
+        self.stream.read_exact(&mut frame).await?;
+        let mac = frame.split_off(frame.len() - BLOCK_SIZE);
+        self.ingress_mac.update(&frame);
+        let seed = digest(&self.ingress_mac);
+        if update_mac(&mut self.ingress_mac, &self.mac_cipher, &seed) != mac[..] {
+            return Err(rlpx_error("Invalid frame MAC"));
+        }
+        self.ingress.apply_keystream(&mut frame);
+        frame.truncate(size);
+        Ok(frame)
+    }
+}
+
+// The first 16 bytes of the running MAC's current digest.
+fn digest(mac: &Keccak256) -> [u8; BLOCK_SIZE] {
+    let mut bytes = [0; BLOCK_SIZE];
+    bytes.copy_from_slice(&mac.clone().finalize()[..BLOCK_SIZE]);
+    bytes
+}
+
+// Mixes a seed into the running MAC: the encrypted digest XORed with the seed, returning the new digest.
+fn update_mac(mac: &mut Keccak256, cipher: &Aes256, seed: &[u8]) -> [u8; BLOCK_SIZE] {
+    let mut block = digest(mac).into();
+    cipher.encrypt_block(&mut block);
+    for (byte, seed) in block.iter_mut().zip(seed) {
+        *byte ^= seed;
+    }
+    mac.update(block);
+    digest(mac)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tokio::net::TcpListener};
+
+    // Both sides of a handshake derive the same secrets and exchange messages in both directions.
+    #[tokio::test]
+    async fn test_handshake() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let listener_secret = random_secret_key();
+        let listener_key = PublicKey::from_secret_key(&listener_secret);
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut session = RlpxSession::accept(socket, &listener_secret).await.unwrap();
+            let message = session.recv_message().await.unwrap();
+            session.send_message(0x10, &[0xab; 40]).await.unwrap();
+            (session.remote_id, message)
+        });
+
+        let secret = random_secret_key();
+        let stream = TcpStream::connect(address).await.unwrap();
+        let mut session = RlpxSession::connect(stream, &secret, &listener_key)
+            .await
+            .unwrap();
+        assert_eq!(session.remote_id, encode_public_key(&listener_key));
+        session.send_message(0, b"\xc0").await.unwrap();
+        assert_eq!(
+            session.recv_message().await.unwrap(),
+            (0x10, vec![0xab; 40])
+        );
+
+        let (remote_id, message) = server.await.unwrap();
+        assert_eq!(
+            remote_id,
+            encode_public_key(&PublicKey::from_secret_key(&secret))
+        );
+        assert_eq!(message, (0, b"\xc0".to_vec()));
+    }
+
+    // A dialer expecting another node fails, as the listener cannot decrypt its auth message.
+    #[tokio::test]
+    async fn test_wrong_node_id() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let _ = RlpxSession::accept(socket, &random_secret_key()).await;
+        });
+
+        let stream = TcpStream::connect(address).await.unwrap();
+        let other = PublicKey::from_secret_key(&random_secret_key());
+        let result = RlpxSession::connect(stream, &random_secret_key(), &other).await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,15 @@
+// The `chain` module describes Ethereum's handshake: `web3_clientVersion` and `net_version`
+// sent as one JSON-RPC batch.
+pub mod chain;
+
+// The `devp2p` module verifies a node's devp2p port with the RLPx handshake and the `Hello` exchange,
+// behind the `devp2p` feature.
+#[cfg(feature = "devp2p")]
+pub mod devp2p;
+
+// The `node` module verifies an EVM JSON-RPC endpoint over the TCP, TLS and WebSocket transports
+// shared with the Solana nodes.
+pub mod node;
+
+// The types needed to handshake with an EVM node.
+pub use {chain::Ethereum, node::EthNode};
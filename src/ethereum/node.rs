@@ -0,0 +1,127 @@
+// Module for verifying an EVM JSON-RPC endpoint, reached over the same transports as Solana RPC nodes.
+use crate::chain::{self, NodeInfo}; // Chain-agnostic handshakes.
+use crate::error::HandshakeError; // Crate-wide error type reported by failed handshakes.
+use crate::ethereum::chain::Ethereum; // Ethereum's handshake request and response.
+use crate::solana::{
+    node::Handshake,
+    target::Target,
+    transport::{
+        select,
+        tcp::{ConnectOptions, Connectable},
+        traced, ChooseTransport, Transport,
+    },
+    TransportType,
+}; // Import necessary traits and structures for handshake and transport.
+use async_trait::async_trait; // Enables async trait methods, crucial for async network operations.
+use std::fmt; // Standard library import for display formatting.
+
+// Struct defining an EVM node, including its remote address and transport type for connectivity.
+pub struct EthNode {
+    pub remote: Target, // The remote endpoint: its name as given and the addresses it resolved to.
+    pub transport_type: TransportType, // Enum specifying the type of transport to use.
+    pub connect: ConnectOptions, // Proxy and TLS options of the HTTP and WebSocket connections.
+}
+
+impl EthNode {
+    // Constructor for EthNode, taking a target, or a socket address, and transport type.
+    pub fn new(remote: impl Into<Target>, transport_type: TransportType) -> Self {
+        EthNode {
+            remote: remote.into(),
+            transport_type,
+            connect: ConnectOptions::default(),
+        }
+    }
+
+    // Performs the handshake as the Ethereum chain, reporting the client version and network id.
+    // EVM nodes do not serve JSON-RPC over QUIC, so that transport is refused before connecting.
+    pub async fn node_info(&self, timeout: Option<u32>) -> Result<NodeInfo, HandshakeError> {
+        if self.transport_type == TransportType::Quic {
+            return Err(HandshakeError::Protocol(
+                "EVM nodes do not serve JSON-RPC over QUIC".to_string(),
+            ));
+        }
+        let transport = self.get_transport();
+        let ethereum = Ethereum::new();
+        let exchange = chain::shake(&ethereum, transport.as_ref(), timeout);
+        traced(&self.remote, self.transport_type, exchange).await
+    }
+}
+
+impl Connectable for EthNode {
+    fn connect_options(&mut self) -> &mut ConnectOptions {
+        &mut self.connect
+    }
+}
+
+// Implement the Display trait for EthNode for easy logging and debugging.
+impl fmt::Display for EthNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EthNode({})", self.remote) // Custom display format showing the remote address.
+    }
+}
+
+// Chooses the appropriate transport mechanism based on the transport_type attribute.
+impl ChooseTransport for EthNode {
+    fn get_transport(&self) -> Box<dyn Transport> {
+        select(
+            &self.remote,
+            self.transport_type,
+            self.connect.route(&self.remote),
+            self.connect.tls.clone(),
+        )
+    }
+}
+
+// Async trait for performing the handshake operation, returning the client version the node reported.
+#[async_trait]
+impl Handshake for EthNode {
+    async fn shake(&self, timeout: Option<u32>) -> Result<String, HandshakeError> {
+        Ok(self.node_info(timeout).await?.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Test suite for EthNode functionality, run against an in-process JSON-RPC server.
+    use {super::*, crate::test_server::serve_once_with, std::net::SocketAddr};
+
+    // Verifies that an EthNode can be correctly instantiated and displayed.
+    #[test]
+    fn test_eth_node_creation() {
+        let addr: SocketAddr = "127.0.0.1:8545".parse().unwrap();
+        let node = EthNode::new(addr, TransportType::Tcp);
+
+        assert_eq!(node.transport_type, TransportType::Tcp);
+        assert_eq!(node.to_string(), "EthNode(127.0.0.1:8545)"); // Checks the display format.
+    }
+
+    // The batch is sent in a single request and the client version is returned.
+    #[tokio::test]
+    async fn test_eth_handshake() {
+        // The ids of the batch's requests are filled in for `{client}` and `{net}`.
+        let (address, server) = serve_once_with(|batch| {
+            r#"[{"jsonrpc":"2.0","id":{client},"result":"Geth/v1.13.14"},{"jsonrpc":"2.0","id":{net},"result":"11155111"}]"#
+                .replace("{client}", &batch[0]["id"].to_string())
+                .replace("{net}", &batch[1]["id"].to_string())
+        })
+        .await;
+
+        let info = EthNode::new(address, TransportType::Tcp)
+            .node_info(Some(5))
+            .await
+            .unwrap();
+        assert_eq!(info.version, "Geth/v1.13.14");
+        assert_eq!(info.details["network_id"], "11155111");
+
+        let request = server.await.unwrap();
+        assert!(request.contains("web3_clientVersion") && request.contains("net_version"));
+    }
+
+    // QUIC is refused without connecting.
+    #[tokio::test]
+    async fn test_quic_is_refused() {
+        let addr: SocketAddr = "127.0.0.1:8545".parse().unwrap();
+        let result = EthNode::new(addr, TransportType::Quic).shake(Some(1)).await;
+        assert!(matches!(result, Err(HandshakeError::Protocol(_))));
+    }
+}
@@ -0,0 +1,255 @@
+// C interface to the Solana RPC handshake, for agents written in C or C++ linking the library as a static or
+// shared library. Its declarations are in `include/simple_handshake.h`, which cbindgen generates from this
+// module with `cbindgen.toml`, doc comments included; `test_header` checks the header is up to date.
+use {
+    crate::{
+        bindings::{self, parse_transport, runtime},
+        error::HandshakeError,
+        solana::TransportType,
+    },
+    std::{
+        ffi::{c_char, CStr, CString},
+        panic::{self, AssertUnwindSafe},
+        ptr,
+        time::Instant,
+    },
+};
+
+/// How a handshake ended, with the codes the binary exits with.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimpleHandshakeStatus {
+    /// The node answered the handshake.
+    Ok = 0,
+    /// The arguments were invalid, or the handshake failed unexpectedly.
+    Error = 1,
+    /// The address could not be resolved.
+    Dns = 2,
+    /// The connection could not be established.
+    Connect = 3,
+    /// The TLS handshake failed.
+    Tls = 4,
+    /// The node answered, but not as the protocol requires.
+    Protocol = 5,
+    /// A phase of the handshake did not complete within the timeout.
+    Timeout = 6,
+    /// The node answered with a JSON-RPC error.
+    Rpc = 9,
+    /// The host did not answer a reachability check.
+    Unreachable = 10,
+    /// The handshake was cancelled.
+    Cancelled = 130,
+}
+
+impl From<&HandshakeError> for SimpleHandshakeStatus {
+    fn from(error: &HandshakeError) -> Self {
+        match error {
+            HandshakeError::Dns(_) => SimpleHandshakeStatus::Dns,
+            HandshakeError::Connect(_) => SimpleHandshakeStatus::Connect,
+            HandshakeError::Tls(_) => SimpleHandshakeStatus::Tls,
+            HandshakeError::WebSocket(_)
+            | HandshakeError::Protocol(_)
+            | HandshakeError::InvalidResponse(_) => SimpleHandshakeStatus::Protocol,
+            HandshakeError::Timeout { .. } => SimpleHandshakeStatus::Timeout,
+            HandshakeError::Rpc { .. } => SimpleHandshakeStatus::Rpc,
+            HandshakeError::Unreachable(_) => SimpleHandshakeStatus::Unreachable,
+            HandshakeError::Cancelled => SimpleHandshakeStatus::Cancelled,
+            HandshakeError::InvalidConfig(_) => SimpleHandshakeStatus::Error,
+        }
+    }
+}
+
+/// The outcome of `simple_handshake_shake`, released with `simple_handshake_result_free`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct SimpleHandshakeResult {
+    /// How the handshake ended.
+    pub status: SimpleHandshakeStatus,
+    /// The solana-core version the node reported, or null when the handshake failed.
+    pub version: *mut c_char,
+    /// The feature set the node reported, when `has_feature_set` is set.
+    pub feature_set: u64,
+    /// Whether the node reported a feature set.
+    pub has_feature_set: bool,
+    /// Time taken by the handshake, successful or not, in milliseconds.
+    pub duration_ms: u64,
+    /// Why the handshake failed, or null when it succeeded.
+    pub error: *mut c_char,
+}
+
+impl SimpleHandshakeResult {
+    // A failed handshake with its message.
+    fn failed(status: SimpleHandshakeStatus, error: &str, duration_ms: u64) -> Self {
+        SimpleHandshakeResult {
+            status,
+            version: ptr::null_mut(),
+            feature_set: 0,
+            has_feature_set: false,
+            duration_ms,
+            error: to_c_string(error),
+        }
+    }
+}
+
+// Copies a string for C, dropping any NUL bytes, which C strings cannot hold.
+fn to_c_string(value: &str) -> *mut c_char {
+    let value = CString::new(value.replace('\0', "")).unwrap_or_default();
+    value.into_raw()
+}
+
+// Reads a string argument, failing when it is null or not UTF-8.
+//
+// Safety: `value` is null or points to a NUL-terminated string.
+unsafe fn read_str<'a>(value: *const c_char, name: &str) -> Result<&'a str, String> {
+    if value.is_null() {
+        return Err(format!("The {} is null", name));
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| format!("The {} is not UTF-8", name))
+}
+
+// Handshakes the node over an RPC transport.
+fn shake(address: &str, transport: TransportType, timeout: Option<u32>) -> SimpleHandshakeResult {
+    let started = Instant::now();
+    let result = runtime().block_on(bindings::shake(address, transport, timeout));
+    let duration_ms = started.elapsed().as_millis() as u64;
+    match result {
+        Ok(response) => SimpleHandshakeResult {
+            status: SimpleHandshakeStatus::Ok,
+            version: to_c_string(&response.solana_core),
+            feature_set: response.feature_set.unwrap_or_default(),
+            has_feature_set: response.feature_set.is_some(),
+            duration_ms,
+            error: ptr::null_mut(),
+        },
+        Err(error) => {
+            SimpleHandshakeResult::failed((&error).into(), &error.to_string(), duration_ms)
+        }
+    }
+}
+
+/// Handshakes the Solana RPC node at `address`, a `host:port`, over `transport`, one of "tcp", "tls", "ws"
+/// and "wss", or also "http", "https", "h2" and "h2c" in a library built with the `hyper` feature, or "tcp"
+/// when null. Each phase of the handshake is bounded by `timeout_seconds`, unless 0.
+/// The call blocks until the handshake ends, and never unwinds into the caller.
+///
+/// # Safety
+///
+/// `address` and `transport` must be null or point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn simple_handshake_shake(
+    address: *const c_char,
+    transport: *const c_char,
+    timeout_seconds: u32,
+) -> SimpleHandshakeResult {
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        let address = read_str(address, "address")?;
+        let transport = match transport.is_null() {
+            true => TransportType::Tcp,
+            false => parse_transport(read_str(transport, "transport")?)?,
+        };
+        let timeout = (timeout_seconds > 0).then_some(timeout_seconds);
+        Ok::<_, String>(shake(address, transport, timeout))
+    }));
+    match outcome {
+        Ok(Ok(result)) => result,
+        Ok(Err(error)) => SimpleHandshakeResult::failed(SimpleHandshakeStatus::Error, &error, 0),
+        Err(_) => {
+            SimpleHandshakeResult::failed(SimpleHandshakeStatus::Error, "The handshake panicked", 0)
+        }
+    }
+}
+
+/// Releases the strings of a result returned by `simple_handshake_shake`, leaving them null, so a result
+/// freed twice is only released once.
+///
+/// # Safety
+///
+/// `result` must be null or point to a result returned by `simple_handshake_shake`.
+#[no_mangle]
+pub unsafe extern "C" fn simple_handshake_result_free(result: *mut SimpleHandshakeResult) {
+    let Some(result) = result.as_mut() else {
+        return;
+    };
+    for string in [&mut result.version, &mut result.error] {
+        if !string.is_null() {
+            drop(CString::from_raw(*string));
+            *string = ptr::null_mut();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, test_case::test_case};
+
+    // Reads a string of a result.
+    fn read(value: *mut c_char) -> Option<String> {
+        (!value.is_null()).then(|| {
+            unsafe { CStr::from_ptr(value) }
+                .to_str()
+                .unwrap()
+                .to_string()
+        })
+    }
+
+    // Failures map to the codes the binary exits with.
+    #[test_case(HandshakeError::Dns("unresolved".to_string()), SimpleHandshakeStatus::Dns; "when dns fails")]
+    #[test_case(HandshakeError::InvalidResponse("bad".to_string()), SimpleHandshakeStatus::Protocol; "when response is invalid")]
+    #[test_case(HandshakeError::Timeout { phase: "read".to_string(), seconds: 5 }, SimpleHandshakeStatus::Timeout; "when phase times out")]
+    #[test_case(HandshakeError::Rpc { code: -32601, message: "not found".to_string() }, SimpleHandshakeStatus::Rpc; "when node returns an rpc error")]
+    #[test_case(HandshakeError::Unreachable("no echo reply".to_string()), SimpleHandshakeStatus::Unreachable; "when host is unreachable")]
+    fn test_status(error: HandshakeError, status: SimpleHandshakeStatus) {
+        assert_eq!(SimpleHandshakeStatus::from(&error), status);
+    }
+
+    // Invalid arguments fail the call with a message rather than crashing the caller.
+    #[test_case(ptr::null(), ptr::null(), "The address is null"; "when address is null")]
+    #[test_case(c"127.0.0.1:8899".as_ptr(), c"quic".as_ptr(), "QUIC is not an RPC transport"; "when transport is quic")]
+    #[test_case(c"127.0.0.1:8899".as_ptr(), c"udp".as_ptr(), "Unknown transport 'udp'"; "when transport is unknown")]
+    fn test_shake_invalid_arguments(address: *const c_char, transport: *const c_char, error: &str) {
+        let mut result = unsafe { simple_handshake_shake(address, transport, 1) };
+        assert_eq!(result.status, SimpleHandshakeStatus::Error);
+        assert_eq!(read(result.error).as_deref(), Some(error));
+        assert!(result.version.is_null());
+
+        // Freeing leaves the strings null, so freeing twice is harmless.
+        unsafe { simple_handshake_result_free(&mut result) };
+        assert!(result.error.is_null());
+        unsafe { simple_handshake_result_free(&mut result) };
+        unsafe { simple_handshake_result_free(ptr::null_mut()) };
+    }
+
+    // A node refusing the connection fails the handshake with the connection status.
+    #[test]
+    fn test_shake_refused() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = CString::new(listener.local_addr().unwrap().to_string()).unwrap();
+        drop(listener);
+
+        let mut result = unsafe { simple_handshake_shake(address.as_ptr(), ptr::null(), 5) };
+        assert_eq!(result.status, SimpleHandshakeStatus::Connect);
+        assert!(read(result.error).unwrap().starts_with("Connection error"));
+        unsafe { simple_handshake_result_free(&mut result) };
+    }
+
+    // The checked-in header is what cbindgen generates from this module.
+    #[test]
+    fn test_header() {
+        let root = env!("CARGO_MANIFEST_DIR");
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", root)).unwrap();
+        let bindings = cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(format!("{}/src/ffi.rs", root))
+            .generate()
+            .unwrap();
+        let mut generated = Vec::new();
+        bindings.write(&mut generated);
+        assert_eq!(
+            String::from_utf8(generated).unwrap(),
+            include_str!("../include/simple_handshake.h"),
+            "include/simple_handshake.h is out of date, regenerate it with cbindgen"
+        );
+    }
+}
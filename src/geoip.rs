@@ -0,0 +1,142 @@
+// Enrichment of scan results from MaxMind databases, so scans of a cluster double as a report of where
+// its nodes are, from e.g. GeoLite2 City, and of who hosts them, from e.g. GeoLite2 ASN.
+use {
+    anyhow::Context,
+    maxminddb::{geoip2, Reader},
+    serde::Serialize,
+    std::{net::IpAddr, path::Path},
+};
+
+// Where an address is, as far as the database knows.
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub country: Option<String>, // ISO 3166-1 code of the country, e.g. "DE".
+    pub city: Option<String>,    // English name of the city, e.g. "Berlin".
+}
+
+impl Location {
+    // Reads the location of a record; Country databases have no city.
+    fn from_record(record: &geoip2::City) -> Self {
+        Location {
+            country: record
+                .country
+                .as_ref()
+                .and_then(|country| country.iso_code)
+                .map(str::to_string),
+            city: record
+                .city
+                .as_ref()
+                .and_then(|city| city.names.as_ref()?.get("en"))
+                .map(|name| name.to_string()),
+        }
+    }
+}
+
+// The network an address belongs to, as far as the database knows.
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Network {
+    pub asn: Option<u32>,             // Number of the autonomous system, e.g. 16509.
+    pub organization: Option<String>, // Organization operating it, e.g. "AMAZON-02".
+}
+
+impl Network {
+    // Reads the network of a record.
+    fn from_record(record: &geoip2::Asn) -> Self {
+        Network {
+            asn: record.autonomous_system_number,
+            organization: record.autonomous_system_organization.map(str::to_string),
+        }
+    }
+}
+
+// Reads a MaxMind database whole into memory.
+fn open(path: &Path, kind: &str) -> anyhow::Result<Reader<Vec<u8>>> {
+    Reader::open_readfile(path)
+        .with_context(|| format!("Could not open the {} database {}", kind, path.display()))
+}
+
+// A MaxMind City or Country database.
+pub struct GeoIp {
+    reader: Reader<Vec<u8>>, // The database.
+}
+
+impl GeoIp {
+    // Opens the database, e.g. `GeoLite2-City.mmdb`.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let reader = open(path, "GeoIP")?;
+        Ok(GeoIp { reader })
+    }
+
+    // Looks the address up; addresses the database does not know, e.g. private ones, have no location.
+    pub fn locate(&self, address: IpAddr) -> Option<Location> {
+        let record: geoip2::City = self.reader.lookup(address).ok()?;
+        let location = Location::from_record(&record);
+        (location != Location::default()).then_some(location)
+    }
+}
+
+// A MaxMind ASN database.
+pub struct AsnDb {
+    reader: Reader<Vec<u8>>, // The database.
+}
+
+impl AsnDb {
+    // Opens the database, e.g. `GeoLite2-ASN.mmdb`.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let reader = open(path, "ASN")?;
+        Ok(AsnDb { reader })
+    }
+
+    // Looks the address up; addresses the database does not know, e.g. private ones, have no network.
+    pub fn lookup(&self, address: IpAddr) -> Option<Network> {
+        let record: geoip2::Asn = self.reader.lookup(address).ok()?;
+        let network = Network::from_record(&record);
+        (network != Network::default()).then_some(network)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, test_case::test_case};
+
+    // The country code and English city name are read, whichever the record holds.
+    #[test_case(
+        r#"{"city":{"names":{"de":"München","en":"Munich"}},"country":{"iso_code":"DE"}}"#,
+        Some("DE"), Some("Munich"); "when record is a city"
+    )]
+    #[test_case(r#"{"country":{"iso_code":"SG"}}"#, Some("SG"), None; "when record is a country")]
+    #[test_case(r#"{"city":{"names":{"de":"Köln"}}}"#, None, None; "when city has no english name")]
+    fn test_location_from_record(record: &str, country: Option<&str>, city: Option<&str>) {
+        let record: geoip2::City = serde_json::from_str(record).unwrap();
+        let location = Location::from_record(&record);
+        assert_eq!(location.country.as_deref(), country);
+        assert_eq!(location.city.as_deref(), city);
+    }
+
+    // The number and organization of the autonomous system are read.
+    #[test]
+    fn test_network_from_record() {
+        let record =
+            r#"{"autonomous_system_number":16509,"autonomous_system_organization":"AMAZON-02"}"#;
+        let record: geoip2::Asn = serde_json::from_str(record).unwrap();
+        assert_eq!(
+            Network::from_record(&record),
+            Network {
+                asn: Some(16509),
+                organization: Some("AMAZON-02".to_string()),
+            }
+        );
+    }
+
+    // A database that cannot be read fails with its kind and path.
+    #[test]
+    fn test_open_missing_database() {
+        let error = GeoIp::open(Path::new("missing.mmdb")).err().unwrap();
+        assert!(error.to_string().contains("missing.mmdb"));
+        let error = AsnDb::open(Path::new("missing.mmdb")).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Could not open the ASN database missing.mmdb"
+        );
+    }
+}
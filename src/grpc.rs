@@ -0,0 +1,362 @@
+// gRPC service mode: the `handshake.Handshake` service of proto/handshake.proto, so other services can
+// request handshakes from a central prober rather than reaching the RPC nodes themselves. As with the
+// Geyser client, the messages and service are written out rather than generated, so the build needs no
+// protobuf compiler; they must be kept in step with the proto file.
+use {
+    crate::{cli::GrpcArgs, output::HandshakeReport},
+    anyhow::Context as _,
+    futures_util::StreamExt,
+    handshake::{
+        bindings,
+        solana::{rpc::node::RpcNode, target::AddressFamily, transport::timed, TransportType},
+    },
+    prost::Message,
+    std::{
+        convert::Infallible,
+        future::ready,
+        task::{Context, Poll},
+        time::Instant,
+    },
+    tokio::net::TcpListener,
+    tonic::{
+        body::BoxBody,
+        codec::ProstCodec,
+        codegen::{http, BoxFuture, BoxStream, Service},
+        server::{Grpc, NamedService, StreamingService, UnaryService},
+        transport::{Body, Server},
+        Request, Response, Status, Streaming,
+    },
+    tracing::info,
+};
+
+// A node to handshake, the `Target` message.
+#[derive(Clone, PartialEq, Message)]
+pub struct ShakeTarget {
+    #[prost(string, tag = "1")]
+    pub address: String, // The node's address, as `host:port`.
+    #[prost(string, tag = "2")]
+    pub transport: String, // "tcp", "tls", "ws" or "wss"; "tcp" when empty.
+    #[prost(uint32, tag = "3")]
+    pub timeout_seconds: u32, // Bound on each phase of the handshake; the service's default when 0.
+}
+
+// The outcome of a handshake, the `Result` message.
+#[derive(Clone, PartialEq, Message)]
+pub struct ShakeResult {
+    #[prost(string, tag = "1")]
+    pub timestamp: String, // When the handshake finished, in RFC 3339 format.
+    #[prost(string, tag = "2")]
+    pub target: String, // The address handshaken.
+    #[prost(string, tag = "3")]
+    pub transport: String, // The transport used.
+    #[prost(bool, tag = "4")]
+    pub ok: bool, // Whether the node answered the handshake.
+    #[prost(uint64, tag = "5")]
+    pub duration_ms: u64, // Time taken by the handshake, successful or not.
+    #[prost(string, tag = "6")]
+    pub version: String, // The solana-core version the node reported; empty when it failed.
+    #[prost(uint64, optional, tag = "7")]
+    pub feature_set: Option<u64>, // The feature set the node reported, if any.
+    #[prost(string, tag = "8")]
+    pub error: String, // Why the handshake failed; empty when it succeeded.
+    #[prost(string, tag = "9")]
+    pub error_class: String, // The class of the failure, e.g. "timeout"; empty when it succeeded.
+    #[prost(string, tag = "10")]
+    pub connected_to: String, // The address connected to, when the target resolved to several.
+}
+
+impl From<HandshakeReport> for ShakeResult {
+    fn from(report: HandshakeReport) -> Self {
+        ShakeResult {
+            timestamp: report.timestamp,
+            target: report.target,
+            transport: report.transport,
+            ok: report.error.is_none(),
+            duration_ms: report.duration_ms,
+            version: report.version.unwrap_or_default(),
+            feature_set: report.feature_set,
+            error: report.error.unwrap_or_default(),
+            error_class: report.error_class.unwrap_or_default(),
+            connected_to: report.connected_to.unwrap_or_default(),
+        }
+    }
+}
+
+// Parses the transport a target asks for, TCP when it names none; QUIC carries no JSON RPC.
+fn parse_transport(name: &str) -> Result<TransportType, String> {
+    match name {
+        "" => Ok(TransportType::Tcp),
+        name => bindings::parse_transport(name),
+    }
+}
+
+// The `handshake.Handshake` service, handshaking the targets requested over the RPC transports.
+#[derive(Debug, Clone)]
+pub struct HandshakeService {
+    family: AddressFamily, // Address family the targets are reached over.
+    timeout: u32,          // Bound on each phase, in seconds, for targets that set none.
+    concurrency: usize,    // Handshakes a scan runs at once.
+}
+
+impl HandshakeService {
+    // Constructs the service.
+    pub fn new(family: AddressFamily, timeout: u32, concurrency: usize) -> Self {
+        HandshakeService {
+            family,
+            timeout,
+            concurrency,
+        }
+    }
+
+    // Handshakes a target. Handshakes that fail are results; only invalid targets fail the call.
+    async fn shake(&self, target: ShakeTarget) -> Result<ShakeResult, Status> {
+        let transport = parse_transport(&target.transport).map_err(Status::invalid_argument)?;
+        let timeout = match target.timeout_seconds {
+            0 => self.timeout,
+            seconds => seconds,
+        };
+        info!(
+            "Handshaking {} over {} on request",
+            target.address, transport
+        );
+
+        let started = Instant::now();
+        let (result, timings, connection) = timed(async {
+            let node = RpcNode::new(
+                bindings::resolve(&target.address, self.family).await?,
+                transport,
+            );
+            node.shake_typed(Some(timeout)).await
+        })
+        .await;
+        let report = HandshakeReport::new(&target.address, transport, started.elapsed())
+            .with_version(&result)
+            .with_timings(timings)
+            .with_connection(connection);
+        Ok(report.into())
+    }
+}
+
+// `Shake`, a unary call.
+struct ShakeMethod(HandshakeService);
+
+impl UnaryService<ShakeTarget> for ShakeMethod {
+    type Response = ShakeResult;
+    type Future = BoxFuture<Response<ShakeResult>, Status>;
+
+    fn call(&mut self, request: Request<ShakeTarget>) -> Self::Future {
+        let service = self.0.clone();
+        Box::pin(async move { service.shake(request.into_inner()).await.map(Response::new) })
+    }
+}
+
+// `Scan`, streaming a result back for every target streamed in, in the order the handshakes end.
+struct ScanMethod(HandshakeService);
+
+impl StreamingService<ShakeTarget> for ScanMethod {
+    type Response = ShakeResult;
+    type ResponseStream = BoxStream<ShakeResult>;
+    type Future = BoxFuture<Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, request: Request<Streaming<ShakeTarget>>) -> Self::Future {
+        let service = self.0.clone();
+        let concurrency = service.concurrency;
+        let results = request
+            .into_inner()
+            .map(move |target| {
+                let service = service.clone();
+                async move { service.shake(target?).await }
+            })
+            .buffer_unordered(concurrency);
+        let results: Self::ResponseStream = Box::pin(results);
+        Box::pin(ready(Ok(Response::new(results))))
+    }
+}
+
+impl NamedService for HandshakeService {
+    const NAME: &'static str = "handshake.Handshake";
+}
+
+impl Service<http::Request<Body>> for HandshakeService {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        let service = self.clone();
+        Box::pin(async move {
+            let mut grpc = Grpc::new(ProstCodec::default());
+            let response = match request.uri().path() {
+                "/handshake.Handshake/Shake" => grpc.unary(ShakeMethod(service), request).await,
+                "/handshake.Handshake/Scan" => grpc.streaming(ScanMethod(service), request).await,
+                _ => Status::unimplemented("Unknown method").to_http(),
+            };
+            Ok(response)
+        })
+    }
+}
+
+// Serves the service on the listener until the process stops.
+pub async fn serve(listener: TcpListener, service: HandshakeService) -> anyhow::Result<()> {
+    if let Ok(address) = listener.local_addr() {
+        info!("Serving handshakes over gRPC on {}", address);
+    }
+    let incoming = futures_util::stream::unfold(listener, |listener| async {
+        let accepted = listener.accept().await.map(|(socket, _)| socket);
+        Some((accepted, listener))
+    });
+    Server::builder()
+        .add_service(service)
+        .serve_with_incoming(incoming)
+        .await
+        .context("The gRPC server failed")
+}
+
+// Runs the gRPC service mode.
+pub async fn run(node: &GrpcArgs, family: AddressFamily) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(node.listen)
+        .await
+        .with_context(|| format!("Could not listen on {}", node.listen))?;
+    serve(
+        listener,
+        HandshakeService::new(family, node.timeout, node.concurrency),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::net::SocketAddr,
+        test_case::test_case,
+        tonic::{client, codegen::http::uri::PathAndQuery, transport::Channel},
+    };
+
+    // Serves the service on a local port and returns a client of it.
+    async fn client() -> client::Grpc<Channel> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let service = HandshakeService::new(AddressFamily::Any, 5, 4);
+        tokio::spawn(serve(listener, service));
+
+        let channel = Channel::from_shared(format!("http://{}", address))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut grpc = client::Grpc::new(channel);
+        grpc.ready().await.unwrap();
+        grpc
+    }
+
+    // The address of a local port nothing listens on.
+    fn refused() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address: SocketAddr = listener.local_addr().unwrap();
+        address.to_string()
+    }
+
+    // A node to handshake over TCP.
+    fn target(address: &str) -> ShakeTarget {
+        ShakeTarget {
+            address: address.to_string(),
+            transport: String::new(),
+            timeout_seconds: 0,
+        }
+    }
+
+    // Transports are parsed by name, defaulting to TCP; QUIC and unknown names are invalid.
+    #[test_case("", Ok(TransportType::Tcp); "when transport is empty")]
+    #[test_case("wss", Ok(TransportType::Wss); "when transport is wss")]
+    #[test_case("quic", Err("QUIC is not an RPC transport"); "when transport is quic")]
+    #[test_case("udp", Err("Unknown transport 'udp'"); "when transport is unknown")]
+    fn test_parse_transport(name: &str, expected: Result<TransportType, &str>) {
+        assert_eq!(parse_transport(name), expected.map_err(str::to_string));
+    }
+
+    // A failed handshake is a result with the class of its failure, not a failed call.
+    #[tokio::test]
+    async fn test_shake() {
+        let mut grpc = client().await;
+        let address = refused();
+        let response = grpc
+            .unary::<_, ShakeResult, _>(
+                Request::new(target(&address)),
+                PathAndQuery::from_static("/handshake.Handshake/Shake"),
+                ProstCodec::default(),
+            )
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.target, address);
+        assert_eq!(response.transport, "tcp");
+        assert!(!response.ok);
+        assert_eq!(response.error_class, "connect");
+        assert!(response.version.is_empty());
+
+        // An invalid target fails the call.
+        grpc.ready().await.unwrap();
+        let quic = ShakeTarget {
+            transport: "quic".to_string(),
+            ..target(&address)
+        };
+        let status = grpc
+            .unary::<_, ShakeResult, _>(
+                Request::new(quic),
+                PathAndQuery::from_static("/handshake.Handshake/Shake"),
+                ProstCodec::default(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    // Every target streamed in gets a result streamed back.
+    #[tokio::test]
+    async fn test_scan() {
+        let mut grpc = client().await;
+        let targets = vec![target(&refused()), target("node.invalid:8899")];
+        let mut results: Vec<ShakeResult> = grpc
+            .streaming::<_, ShakeTarget, ShakeResult, _>(
+                Request::new(futures_util::stream::iter(targets)),
+                PathAndQuery::from_static("/handshake.Handshake/Scan"),
+                ProstCodec::default(),
+            )
+            .await
+            .unwrap()
+            .into_inner()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        results.sort_by(|a, b| a.error_class.cmp(&b.error_class));
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].error_class, "connect");
+        assert_eq!(results[1].target, "node.invalid:8899");
+        assert_eq!(results[1].error_class, "dns");
+    }
+
+    // The proto file declares the service and every field of its messages.
+    #[test]
+    fn test_proto() {
+        let proto = include_str!("../proto/handshake.proto");
+        for declaration in [
+            "rpc Shake(Target) returns (Result);",
+            "rpc Scan(stream Target) returns (stream Result);",
+            "string address = 1;",
+            "uint32 timeout_seconds = 3;",
+            "optional uint64 feature_set = 7;",
+            "string connected_to = 10;",
+        ] {
+            assert!(
+                proto.contains(declaration),
+                "{} is not declared",
+                declaration
+            );
+        }
+    }
+}
@@ -0,0 +1,211 @@
+// The small HTTP/1.1 server behind the metrics, daemon status and API endpoints. Each connection carries
+// a single request, which must arrive within a read timeout and whose head is capped in size, so a slow or
+// oversized client cannot hold a connection open or exhaust memory; the connection is closed once answered.
+use {
+    std::{future::Future, io, time::Duration},
+    tokio::{
+        io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream},
+    },
+    tracing::warn,
+};
+
+// Time a client has to send its whole request, head and body.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Largest request head accepted, i.e. the request line and headers.
+pub const MAX_HEAD_BYTES: usize = 8 * 1024;
+
+// Largest request body accepted; a shake request is a few dozen bytes.
+pub const MAX_BODY_BYTES: usize = 64 * 1024;
+
+// A request, as much of it as the endpoints look at.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Request {
+    pub method: String, // e.g. "GET".
+    pub path: String,   // e.g. "/status".
+    pub body: Vec<u8>,  // Read by the request's Content-Length; empty without one.
+}
+
+// A response: its status line, content type and body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: &'static str,       // e.g. "200 OK".
+    pub content_type: &'static str, // e.g. "application/json".
+    pub body: String,
+}
+
+impl Response {
+    // Constructs a response with the body.
+    pub fn new(status: &'static str, content_type: &'static str, body: String) -> Self {
+        Response {
+            status,
+            content_type,
+            body,
+        }
+    }
+
+    // A plain-text response naming the status, for requests the server refuses to read.
+    fn refusal(status: &'static str) -> Self {
+        let reason = status.split_once(' ').map_or(status, |(_, reason)| reason);
+        Response::new(status, "text/plain", format!("{}\n", reason))
+    }
+}
+
+// Answers every request accepted on the listener with the handler until the process exits.
+// `name` says what is served, in the warnings logged when a connection fails.
+pub async fn serve<H, F>(listener: TcpListener, name: &'static str, handler: H)
+where
+    H: Fn(Request) -> F + Clone + Send + 'static,
+    F: Future<Output = Response> + Send,
+{
+    serve_within(listener, name, READ_TIMEOUT, handler).await
+}
+
+// Serves as `serve` does, giving clients `read_timeout` to send their requests.
+async fn serve_within<H, F>(
+    listener: TcpListener,
+    name: &'static str,
+    read_timeout: Duration,
+    handler: H,
+) where
+    H: Fn(Request) -> F + Clone + Send + 'static,
+    F: Future<Output = Response> + Send,
+{
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = respond(stream, read_timeout, handler).await {
+                        warn!("Failed to serve {}: {}", name, e);
+                    }
+                });
+            }
+            Err(e) => warn!("Failed to accept {} connection: {}", name, e),
+        }
+    }
+}
+
+// Reads a single request within the timeout, answers it and closes the connection.
+async fn respond<H, F>(mut stream: TcpStream, read_timeout: Duration, handler: H) -> io::Result<()>
+where
+    H: Fn(Request) -> F,
+    F: Future<Output = Response>,
+{
+    let response = match tokio::time::timeout(read_timeout, read_request(&mut stream)).await {
+        Ok(Ok(Ok(request))) => handler(request).await,
+        Ok(Ok(Err(refusal))) => refusal,
+        Ok(Err(e)) => return Err(e),
+        Err(_) => Response::refusal("408 Request Timeout"),
+    };
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+// Reads a request, its body by its Content-Length. A head or body over its cap is not read any further,
+// and the response refusing it is returned instead.
+async fn read_request<S>(stream: S) -> io::Result<Result<Request, Response>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut reader = BufReader::new(stream);
+    let mut head = String::new();
+    // Read the head line by line, never past the cap, until the blank line ending it.
+    while !head.ends_with("\r\n\r\n") && !head.ends_with("\n\n") {
+        let remaining = MAX_HEAD_BYTES.saturating_sub(head.len()) as u64;
+        if remaining == 0 {
+            return Ok(Err(Response::refusal(
+                "431 Request Header Fields Too Large",
+            )));
+        }
+        if (&mut reader).take(remaining).read_line(&mut head).await? == 0 {
+            break;
+        }
+    }
+
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let length = lines
+        .filter_map(|header| header.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .map_or(0, |(_, value)| value.trim().parse().unwrap_or(usize::MAX));
+    if length > MAX_BODY_BYTES {
+        return Ok(Err(Response::refusal("413 Payload Too Large")));
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Ok(Request { method, path, body }))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::net::SocketAddr};
+
+    // Starts a server echoing the method, path and body length of each request, with the given read timeout.
+    async fn echo_server(read_timeout: Duration) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let echo = |request: Request| async move {
+            let body = format!("{} {} {}", request.method, request.path, request.body.len());
+            Response::new("200 OK", "text/plain", body)
+        };
+        tokio::spawn(serve_within(listener, "echo", read_timeout, echo));
+        address
+    }
+
+    // Sends the raw request and returns the whole response.
+    async fn send(address: SocketAddr, request: &[u8]) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(request).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    // A request is answered with its status, content type and length, and the connection closed.
+    #[tokio::test]
+    async fn test_request_is_answered() {
+        let address = echo_server(READ_TIMEOUT).await;
+        let request = b"POST /shake HTTP/1.1\r\nHost: localhost\r\ncontent-length: 2\r\n\r\n{}";
+        assert_eq!(
+            send(address, request).await,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 13\r\nConnection: close\r\n\r\nPOST /shake 2"
+        );
+    }
+
+    // Heads and bodies over their caps are refused without being read.
+    #[tokio::test]
+    async fn test_oversized_requests_are_refused() {
+        let address = echo_server(READ_TIMEOUT).await;
+        let header = format!("X-Padding: {}\r\n", "a".repeat(MAX_HEAD_BYTES));
+        let request = format!("GET /status HTTP/1.1\r\n{}\r\n", header);
+        let response = send(address, request.as_bytes()).await;
+        assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large"));
+
+        let request = format!(
+            "POST /shake HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_BYTES + 1
+        );
+        let response = send(address, request.as_bytes()).await;
+        assert!(response.starts_with("HTTP/1.1 413 Payload Too Large"));
+    }
+
+    // A client that does not finish its request within the read timeout is answered and dropped.
+    #[tokio::test]
+    async fn test_slow_request_times_out() {
+        let address = echo_server(Duration::from_millis(100)).await;
+        let response = send(address, b"GET /status HTTP/1.1\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 408 Request Timeout"));
+    }
+}
@@ -0,0 +1,120 @@
+// Kafka publishing of scan and monitor results: every handshake report is produced to a topic as a line of
+// NDJSON, keyed by its target so the reports of a target stay in order on a single partition. Delivery runs
+// on librdkafka's own thread, retrying while the brokers are unreachable; the reports still queued when the
+// run ends are flushed before the process exits.
+use {
+    crate::output::HandshakeReport,
+    anyhow::Context,
+    rdkafka::{
+        config::ClientConfig,
+        message::Message,
+        producer::{BaseRecord, DeliveryResult, Producer as _, ProducerContext, ThreadedProducer},
+        util::Timeout,
+        ClientContext,
+    },
+    std::time::Duration,
+    tracing::{info, warn},
+};
+
+// Default topic the reports are produced to.
+pub const DEFAULT_KAFKA_TOPIC: &str = "handshake";
+
+// Longest wait for the reports still queued to be delivered when the run ends.
+const FLUSH_TIMEOUT_SECONDS: u64 = 10;
+
+// Logs the reports the brokers did not accept.
+struct Delivery;
+
+impl ClientContext for Delivery {}
+
+impl ProducerContext for Delivery {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult<'_>, _: Self::DeliveryOpaque) {
+        if let Err((e, message)) = result {
+            warn!(
+                "Failed to deliver a report to Kafka topic {}: {}",
+                message.topic(),
+                e
+            );
+        }
+    }
+}
+
+// Produces every handshake report to a Kafka topic.
+pub struct Producer {
+    producer: ThreadedProducer<Delivery>, // Queue of the reports, delivered in the background.
+    topic: String,                        // Topic the reports are produced to.
+}
+
+impl Producer {
+    // Connects to the cluster of the bootstrap brokers, a comma-separated list of `host:port`, in the
+    // background, producing the reports to the topic.
+    pub fn new(brokers: &str, topic: String) -> anyhow::Result<Self> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("client.id", "handshake")
+            .create_with_context(Delivery)
+            .with_context(|| format!("Could not create a Kafka producer for {}", brokers))?;
+        Ok(Producer { producer, topic })
+    }
+
+    // Queues a report for the brokers, dropping it when the queue is full.
+    pub fn publish(&self, report: &HandshakeReport) {
+        let payload = match serde_json::to_string(report) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Failed to serialize the report of {}: {}", report.target, e);
+                return;
+            }
+        };
+        let record = BaseRecord::to(&self.topic)
+            .key(&report.target)
+            .payload(&payload);
+        if let Err((e, _)) = self.producer.send(record) {
+            warn!("Dropped the report of {} for Kafka: {}", report.target, e);
+        }
+    }
+
+    // Reports queued and not delivered yet.
+    pub fn pending(&self) -> usize {
+        self.producer.in_flight_count().max(0) as usize
+    }
+
+    // Waits for the queued reports to be delivered, giving up on them after a while.
+    pub fn flush(&self) {
+        let pending = self.pending();
+        if pending == 0 {
+            return;
+        }
+        info!("Delivering {} reports to Kafka", pending);
+        let timeout = Timeout::After(Duration::from_secs(FLUSH_TIMEOUT_SECONDS));
+        if let Err(e) = self.producer.flush(timeout) {
+            warn!(
+                "Gave up on delivering {} reports to Kafka: {}",
+                self.pending(),
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, handshake::solana::TransportType};
+
+    // Reports are queued without waiting for the brokers, which may be unreachable. librdkafka counts its own
+    // events, e.g. the failed connections to the brokers, as in flight too, so only a lower bound holds.
+    #[test]
+    fn test_publish_queues() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let brokers = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let producer = Producer::new(&brokers, DEFAULT_KAFKA_TOPIC.to_string()).unwrap();
+        let report = HandshakeReport::new("127.0.0.1:8899", TransportType::Tcp, Duration::ZERO);
+        producer.publish(&report);
+        producer.publish(&report);
+        assert!(producer.pending() >= 2);
+    }
+}
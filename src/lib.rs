@@ -1,2 +1,73 @@
+//! Handshakes with Solana nodes over the transports they serve: JSON-RPC over TCP, TLS and
+//! WebSockets, the TPU over QUIC and gossip over UDP. EVM JSON-RPC endpoints are verified over
+//! the same TCP, TLS and WebSocket transports, as are CometBFT nodes, and Bitcoin nodes with the version/verack
+//! handshake of their peer-to-peer protocol. With the `devp2p` feature, execution-layer nodes are
+//! also verified with the RLPx handshake, and with the `libp2p` feature, libp2p peers with Noise
+//! and identify. Any other JSON service is verified with a profile from a registry file, describing
+//! the request to send and how to read the response.
+//!
+//! The `handshake` binary is a thin consumer of this library; services embedding the checks
+//! use the same types:
+//!
+//! ```no_run
+//! use handshake::solana::{Handshake, RpcNode, TransportType};
+//!
+//! # async fn example() -> Result<(), handshake::HandshakeError> {
+//! let address: std::net::SocketAddr = "127.0.0.1:8899".parse().unwrap();
+//! let node = RpcNode::new(address, TransportType::Tcp);
+//! let response = node.shake(Some(5)).await?;
+//! println!("{}", response);
+//! # Ok(())
+//! # }
+//! ```
+
+// Built for wasm32, where the browser's WebSocket is the only connection there is, the crate keeps the chain
+// handshakes, the errors and the portable pieces of the Solana modules; the rest needs sockets.
+
+// Helpers shared by the C and Python bindings
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bindings;
+// Bitcoin handshake implementations
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bitcoin;
+// Chain-agnostic handshake abstraction
+pub mod chain;
+// Crate-wide error type
+pub mod error;
+// Ethereum handshake implementations
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ethereum;
+// libp2p handshake implementations, behind the `libp2p` feature
+#[cfg(all(feature = "libp2p", not(target_arch = "wasm32")))]
+pub mod libp2p;
+// C interface to the Solana handshake, behind the `ffi` feature
+#[cfg(all(feature = "ffi", not(target_arch = "wasm32")))]
+pub mod ffi;
+// Handshake profiles loaded from registry files
+#[cfg(not(target_arch = "wasm32"))]
+pub mod registry;
+// Structured report of a handshake
+#[cfg(not(target_arch = "wasm32"))]
+pub mod report;
 // Solana handshake implementations
 pub mod solana;
+// CometBFT/Tendermint handshake implementations
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tendermint;
+// In-process HTTP responders shared by the tests of the nodes
+#[cfg(test)]
+mod test_server;
+// Proptest strategies for property tests against the library, behind the `test-util` feature
+#[cfg(all(any(test, feature = "test-util"), not(target_arch = "wasm32")))]
+pub mod test_util;
+
+// TLS connections need the cryptography of one of the providers rustls supports.
+#[cfg(not(any(
+    feature = "crypto-ring",
+    feature = "crypto-aws-lc",
+    target_arch = "wasm32"
+)))]
+compile_error!("Enable the `crypto-ring` or `crypto-aws-lc` feature to select the cryptography of TLS connections");
+
+// Every fallible call of the library returns this error.
+pub use error::HandshakeError;
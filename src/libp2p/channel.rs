@@ -0,0 +1,201 @@
+// Byte channels the libp2p protocols are layered on, and multistream-select, which negotiates
+// the protocol spoken on each: Noise on the TCP connection, yamux on the Noise session and identify
+// on a yamux stream.
+use {
+    crate::error::HandshakeError, // Import the crate-wide error type.
+    async_trait::async_trait,     // Import async_trait for asynchronous trait methods.
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    }, // Import the TCP stream and its IO helpers.
+};
+
+// Header exchanged before any protocol is proposed.
+pub const MULTISTREAM: &str = "/multistream/1.0.0";
+
+// Answer of a listener not supporting the proposed protocol.
+const NOT_AVAILABLE: &str = "na";
+
+// Longest multistream-select message accepted, well above any protocol name.
+const MAX_LINE_SIZE: usize = 1024;
+
+// A bidirectional channel of bytes.
+#[async_trait]
+pub trait Channel: Send {
+    // Sends the bytes.
+    async fn send(&mut self, data: &[u8]) -> Result<(), HandshakeError>;
+
+    // Receives exactly `n` bytes.
+    async fn recv_exact(&mut self, n: usize) -> Result<Vec<u8>, HandshakeError>;
+}
+
+// A TCP connection is the channel everything else is layered on.
+#[async_trait]
+impl Channel for TcpStream {
+    async fn send(&mut self, data: &[u8]) -> Result<(), HandshakeError> {
+        Ok(self.write_all(data).await?)
+    }
+
+    async fn recv_exact(&mut self, n: usize) -> Result<Vec<u8>, HandshakeError> {
+        let mut buf = vec![0; n];
+        self.read_exact(&mut buf).await?;
+        Ok(buf)
+    }
+}
+
+// Appends an unsigned LEB128 variable-length integer.
+pub fn encode_varint(mut value: u64, bytes: &mut Vec<u8>) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+// Reads an unsigned LEB128 variable-length integer, of at most nine bytes as libp2p allows.
+async fn read_varint(channel: &mut dyn Channel) -> Result<u64, HandshakeError> {
+    let mut value = 0u64;
+    for i in 0..9 {
+        let byte = channel.recv_exact(1).await?[0];
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(HandshakeError::InvalidResponse(
+        "Variable-length integer is too long".to_string(),
+    ))
+}
+
+// Sends a message prefixed with its length.
+pub async fn send_message(channel: &mut dyn Channel, message: &[u8]) -> Result<(), HandshakeError> {
+    let mut bytes = Vec::with_capacity(message.len() + 4);
+    encode_varint(message.len() as u64, &mut bytes);
+    bytes.extend_from_slice(message);
+    channel.send(&bytes).await
+}
+
+// Receives a message prefixed with its length, rejecting messages longer than `max_size`.
+pub async fn recv_message(
+    channel: &mut dyn Channel,
+    max_size: usize,
+) -> Result<Vec<u8>, HandshakeError> {
+    let length = read_varint(channel).await?;
+    if length > max_size as u64 {
+        return Err(HandshakeError::InvalidResponse(format!(
+            "Message of {} bytes exceeds the limit of {}",
+            length, max_size
+        )));
+    }
+    channel.recv_exact(length as usize).await
+}
+
+// Frames a multistream-select line: its length, the text and a newline.
+fn line(text: &str, bytes: &mut Vec<u8>) {
+    encode_varint(text.len() as u64 + 1, bytes);
+    bytes.extend_from_slice(text.as_bytes());
+    bytes.push(b'\n');
+}
+
+// Receives a multistream-select line, without its newline.
+async fn recv_line(channel: &mut dyn Channel) -> Result<String, HandshakeError> {
+    let message = recv_message(channel, MAX_LINE_SIZE).await?;
+    match message.strip_suffix(b"\n") {
+        Some(text) => Ok(String::from_utf8_lossy(text).to_string()),
+        None => Err(HandshakeError::InvalidResponse(
+            "Multistream message does not end with a newline".to_string(),
+        )),
+    }
+}
+
+// Proposes a protocol as the dialer, succeeding when the listener accepts it.
+// The header and the proposal are sent together, saving a round trip.
+pub async fn select(channel: &mut dyn Channel, protocol: &str) -> Result<(), HandshakeError> {
+    let mut bytes = Vec::new();
+    line(MULTISTREAM, &mut bytes);
+    line(protocol, &mut bytes);
+    channel.send(&bytes).await?;
+
+    let header = recv_line(channel).await?;
+    if header != MULTISTREAM {
+        return Err(HandshakeError::Protocol(format!(
+            "Expected the {} header, got '{}'",
+            MULTISTREAM, header
+        )));
+    }
+    match recv_line(channel).await? {
+        answer if answer == protocol => Ok(()),
+        answer if answer == NOT_AVAILABLE => Err(HandshakeError::Protocol(format!(
+            "The peer does not support {}",
+            protocol
+        ))),
+        answer => Err(HandshakeError::Protocol(format!(
+            "Proposed {}, the peer answered '{}'",
+            protocol, answer
+        ))),
+    }
+}
+
+// Answers proposals as the listener until one of the supported protocols is proposed, returning it.
+pub async fn accept(
+    channel: &mut dyn Channel,
+    supported: &[&str],
+) -> Result<String, HandshakeError> {
+    let header = recv_line(channel).await?;
+    if header != MULTISTREAM {
+        return Err(HandshakeError::Protocol(format!(
+            "Expected the {} header, got '{}'",
+            MULTISTREAM, header
+        )));
+    }
+    let mut bytes = Vec::new();
+    line(MULTISTREAM, &mut bytes);
+    channel.send(&bytes).await?;
+
+    loop {
+        let protocol = recv_line(channel).await?;
+        let supported = supported.contains(&protocol.as_str());
+        let mut bytes = Vec::new();
+        line(
+            if supported { &protocol } else { NOT_AVAILABLE },
+            &mut bytes,
+        );
+        channel.send(&bytes).await?;
+        if supported {
+            return Ok(protocol);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tokio::net::TcpListener};
+
+    // Variable-length integers use seven bits per byte, least significant group first.
+    #[test]
+    fn test_encode_varint() {
+        let mut bytes = Vec::new();
+        encode_varint(300, &mut bytes);
+        assert_eq!(bytes, [0xac, 0x02]);
+    }
+
+    // The dialer's proposal succeeds when the listener supports it, and fails otherwise.
+    #[tokio::test]
+    async fn test_select() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for _ in 0..2 {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let _ = accept(&mut socket, &["/noise"]).await;
+            }
+        });
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        assert!(select(&mut stream, "/noise").await.is_ok());
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let error = select(&mut stream, "/tls/1.0.0").await.unwrap_err();
+        assert!(matches!(error, HandshakeError::Protocol(_)));
+    }
+}
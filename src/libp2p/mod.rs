@@ -0,0 +1,15 @@
+// Byte channels and multistream-select protocol negotiation
+pub mod channel;
+// Multiaddr parsing and decoding
+pub mod multiaddr;
+// libp2p node implementation
+pub mod node;
+// The Noise handshake securing connections
+pub mod noise;
+// Protobuf messages of the Noise handshake and identify
+pub mod proto;
+// Stream multiplexing over secured connections
+pub mod yamux;
+
+// The types needed to handshake with a libp2p peer.
+pub use {multiaddr::Multiaddr, node::Libp2pNode};
@@ -0,0 +1,223 @@
+// Multiaddrs, the self-describing addresses of libp2p.
+// Peers are dialed from the text form, e.g. `/ip4/127.0.0.1/tcp/4001/p2p/12D3KooW...`, while identify
+// reports addresses in the binary form, which is decoded back to text for display.
+use {
+    crate::{
+        error::HandshakeError,
+        solana::target::{AddressFamily, Target},
+    }, // Import the crate-wide error type and resolved targets.
+    base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine}, // Import the multibase encoding of certificate hashes.
+    std::{
+        fmt,
+        net::{Ipv4Addr, Ipv6Addr},
+        str::FromStr,
+    }, // Import IP address parsing and display formatting.
+};
+
+// Address of a peer's TCP listener, and the peer expected to answer there, if known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Multiaddr {
+    pub protocol: String, // Protocol of the host: "ip4", "ip6", "dns", "dns4" or "dns6".
+    pub host: String,     // IP address or name of the host.
+    pub port: u16,        // TCP port.
+    pub peer_id: Option<String>, // Base58 ID of the expected peer, from a trailing `/p2p/` component.
+}
+
+impl Multiaddr {
+    // Address family the host is restricted to by its protocol.
+    pub fn family(&self) -> AddressFamily {
+        match self.protocol.as_str() {
+            "ip4" | "dns4" => AddressFamily::Ipv4,
+            "ip6" | "dns6" => AddressFamily::Ipv6,
+            _ => AddressFamily::Any,
+        }
+    }
+
+    // Resolves the host without blocking the runtime, keeping the addresses of its protocol's family.
+    pub async fn resolve(&self) -> Result<Target, HandshakeError> {
+        let address = match self.host.contains(':') {
+            true => format!("[{}]:{}", self.host, self.port),
+            false => format!("{}:{}", self.host, self.port),
+        };
+        Target::parse(&address)
+            .map_err(|e| HandshakeError::Dns(format!("Unable to resolve {}: {}", address, e)))?
+            .lookup(self.family())
+            .await
+    }
+}
+
+impl FromStr for Multiaddr {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Expected a multiaddr such as '/ip4/127.0.0.1/tcp/4001', got '{}'",
+                text
+            )
+        };
+        let parts: Vec<&str> = text.split('/').collect();
+        let (protocol, host, port, peer_id) = match parts.as_slice() {
+            ["", protocol, host, "tcp", port] => (*protocol, *host, *port, None),
+            ["", protocol, host, "tcp", port, "p2p" | "ipfs", peer_id] => {
+                (*protocol, *host, *port, Some(peer_id.to_string()))
+            }
+            _ => return Err(invalid()),
+        };
+        let valid_host = match protocol {
+            "ip4" => host.parse::<Ipv4Addr>().is_ok(),
+            "ip6" => host.parse::<Ipv6Addr>().is_ok(),
+            "dns" | "dns4" | "dns6" => !host.is_empty(),
+            _ => false,
+        };
+        if !valid_host {
+            return Err(invalid());
+        }
+        Ok(Multiaddr {
+            protocol: protocol.to_string(),
+            host: host.to_string(),
+            port: port.parse().map_err(|_| invalid())?,
+            peer_id,
+        })
+    }
+}
+
+// Implement the Display trait for Multiaddr, giving back its text form.
+impl fmt::Display for Multiaddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "/{}/{}/tcp/{}", self.protocol, self.host, self.port)?;
+        match &self.peer_id {
+            Some(peer_id) => write!(f, "/p2p/{}", peer_id),
+            None => Ok(()),
+        }
+    }
+}
+
+// Reads an unsigned LEB128 variable-length integer from the start of the bytes, advancing past it.
+fn take_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate().take(9) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            *bytes = &bytes[i + 1..];
+            return Some(value);
+        }
+    }
+    None
+}
+
+// Takes the next `n` bytes, advancing past them.
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+    if bytes.len() < n {
+        return None;
+    }
+    let (taken, rest) = bytes.split_at(n);
+    *bytes = rest;
+    Some(taken)
+}
+
+// Decodes a binary multiaddr into its text form.
+// Protocols unknown to the handshake end the address with `/unknown-<code>`, as their size is unknown too.
+pub fn decode(mut bytes: &[u8]) -> String {
+    let mut text = String::new();
+    while !bytes.is_empty() {
+        let code = match take_varint(&mut bytes) {
+            Some(code) => code,
+            None => {
+                text.push_str("/invalid");
+                break;
+            }
+        };
+        let component = match code {
+            4 => take(&mut bytes, 4).map(|ip| {
+                let ip: [u8; 4] = ip.try_into().expect("4 bytes");
+                format!("/ip4/{}", Ipv4Addr::from(ip))
+            }),
+            41 => take(&mut bytes, 16).map(|ip| {
+                let ip: [u8; 16] = ip.try_into().expect("16 bytes");
+                format!("/ip6/{}", Ipv6Addr::from(ip))
+            }),
+            6 | 273 => take(&mut bytes, 2).map(|port| {
+                let name = if code == 6 { "tcp" } else { "udp" };
+                format!("/{}/{}", name, u16::from_be_bytes([port[0], port[1]]))
+            }),
+            53..=56 | 421 | 466 => take_varint(&mut bytes)
+                .and_then(|length| take(&mut bytes, length as usize))
+                .map(|value| match code {
+                    53 => format!("/dns/{}", String::from_utf8_lossy(value)),
+                    54 => format!("/dns4/{}", String::from_utf8_lossy(value)),
+                    55 => format!("/dns6/{}", String::from_utf8_lossy(value)),
+                    56 => format!("/dnsaddr/{}", String::from_utf8_lossy(value)),
+                    421 => format!("/p2p/{}", bs58::encode(value).into_string()),
+                    _ => format!("/certhash/u{}", URL_SAFE_NO_PAD.encode(value)),
+                }),
+            280 => Some("/webrtc-direct".to_string()),
+            290 => Some("/p2p-circuit".to_string()),
+            448 => Some("/tls".to_string()),
+            460 => Some("/quic".to_string()),
+            461 => Some("/quic-v1".to_string()),
+            465 => Some("/webtransport".to_string()),
+            477 => Some("/ws".to_string()),
+            478 => Some("/wss".to_string()),
+            code => {
+                text.push_str(&format!("/unknown-{}", code));
+                break;
+            }
+        };
+        match component {
+            Some(component) => text.push_str(&component),
+            None => {
+                text.push_str("/invalid");
+                break;
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, test_case::test_case};
+
+    // Text multiaddrs of TCP listeners are parsed, with or without the expected peer.
+    #[test_case("/ip4/127.0.0.1/tcp/4001", "ip4", "127.0.0.1", 4001, None; "when address is ip4")]
+    #[test_case("/ip6/::1/tcp/4001", "ip6", "::1", 4001, None; "when address is ip6")]
+    #[test_case("/dns4/node.example.com/tcp/30333/p2p/12D3KooWAbc", "dns4", "node.example.com", 30333, Some("12D3KooWAbc"); "when address has a peer id")]
+    fn test_parse(text: &str, protocol: &str, host: &str, port: u16, peer_id: Option<&str>) {
+        let multiaddr: Multiaddr = text.parse().unwrap();
+        assert_eq!(multiaddr.protocol, protocol);
+        assert_eq!(multiaddr.host, host);
+        assert_eq!(multiaddr.port, port);
+        assert_eq!(multiaddr.peer_id.as_deref(), peer_id);
+        assert_eq!(multiaddr.to_string(), text);
+    }
+
+    // Anything but a TCP listener is rejected.
+    #[test_case("127.0.0.1:4001"; "when address is not a multiaddr")]
+    #[test_case("/ip4/127.0.0.1/udp/4001/quic-v1"; "when address is not tcp")]
+    #[test_case("/ip4/node.example.com/tcp/4001"; "when ip4 is not an address")]
+    #[test_case("/ip4/127.0.0.1/tcp/65536"; "when port is invalid")]
+    fn test_parse_failures(text: &str) {
+        assert!(text.parse::<Multiaddr>().is_err());
+    }
+
+    // Hosts are resolved within their protocol's family; a name that does not resolve is a DNS error.
+    #[tokio::test]
+    async fn test_resolve() {
+        let multiaddr: Multiaddr = "/ip6/::1/tcp/4001".parse().unwrap();
+        let target = multiaddr.resolve().await.unwrap();
+        assert_eq!(target.resolved, vec!["[::1]:4001".parse().unwrap()]);
+
+        let multiaddr: Multiaddr = "/dns4/node.invalid/tcp/4001".parse().unwrap();
+        let error = multiaddr.resolve().await.unwrap_err();
+        assert!(matches!(error, HandshakeError::Dns(_)), "{}", error);
+    }
+
+    // Binary multiaddrs reported by identify are decoded to text.
+    #[test]
+    fn test_decode() {
+        let bytes = [4, 127, 0, 0, 1, 0x91, 0x02, 0x0f, 0xa1, 0xcd, 0x03];
+        assert_eq!(decode(&bytes), "/ip4/127.0.0.1/udp/4001/quic-v1");
+        assert_eq!(decode(&[4, 127, 0]), "/invalid");
+    }
+}
@@ -0,0 +1,238 @@
+// Module for handshaking with a libp2p peer over TCP: Noise secures the connection and proves the peer's
+// identity, yamux multiplexes it, and the identify protocol reports the peer's agent and protocols.
+use crate::{
+    error::HandshakeError,
+    libp2p::{
+        channel::{recv_message, select},
+        multiaddr, noise,
+        proto::Identify,
+        yamux::{self, YamuxSession},
+    },
+    solana::{
+        node::Handshake,
+        target::Target,
+        transport::{
+            tcp::{open_stream, ConnectOptions, Connectable},
+            traced, with_timeout,
+        },
+    },
+}; // Import the crate error type, the libp2p protocols, the handshake trait and the TCP connection helpers.
+use async_trait::async_trait; // Enables async trait methods, crucial for async network operations.
+use prost::Message; // Protobuf decoding of the identify message.
+use serde::Serialize; // Serialization of what the peer reported.
+use solana_sdk::signature::Keypair; // Ed25519 identity proving ours during the Noise handshake.
+use std::fmt; // Standard library import for display formatting.
+use tracing::info; // Import the `info` macro for logging informational messages.
+
+// Protocol the peer reports itself on.
+pub const IDENTIFY_PROTOCOL: &str = "/ipfs/id/1.0.0";
+
+// Largest identify message accepted, leaving room for long lists of protocols and addresses.
+const MAX_IDENTIFY_SIZE: usize = 64 * 1024;
+
+// What a peer proved and reported about itself during the handshake.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PeerInfo {
+    pub peer_id: String, // Peer ID derived from the identity key proven with Noise.
+    pub agent_version: String, // Software the peer runs, e.g. "kubo/0.27.0/".
+    pub protocol_version: String, // Family of protocols the peer speaks, e.g. "ipfs/0.1.0".
+    pub protocols: Vec<String>, // Protocols the peer supports.
+    pub listen_addrs: Vec<String>, // Addresses the peer listens on.
+    pub observed_addr: Option<String>, // Address the peer sees the connection coming from.
+}
+
+// Struct defining a libp2p peer, reached over TCP.
+pub struct Libp2pNode {
+    pub remote: Target, // The remote endpoint: its name as given and the addresses it resolved to.
+    pub peer_id: Option<String>, // ID of the peer expected to answer; any peer is accepted when absent.
+    pub connect: ConnectOptions, // Proxy the connection is tunnelled through; Noise secures it itself.
+    keypair: Keypair,            // Ephemeral identity proven to the peer.
+}
+
+impl Libp2pNode {
+    // Constructor for Libp2pNode, taking a target, or a socket address.
+    // A fresh identity is generated for each node so the handshake needs no configuration.
+    pub fn new(remote: impl Into<Target>) -> Self {
+        Libp2pNode {
+            remote: remote.into(),
+            peer_id: None,
+            connect: ConnectOptions::default(),
+            keypair: Keypair::new(),
+        }
+    }
+
+    // Sets the ID of the peer expected to answer, failing the handshake when another one does.
+    pub fn with_peer_id(mut self, peer_id: Option<String>) -> Self {
+        self.peer_id = peer_id;
+        self
+    }
+
+    // Performs the handshake, returning what the peer proved and reported about itself.
+    pub async fn identify(&self, timeout: Option<u32>) -> Result<PeerInfo, HandshakeError> {
+        traced(&self.remote, "libp2p", async {
+            let remote = self.remote.to_string();
+            let mut stream =
+                open_stream(&remote, &self.connect.route(&self.remote), timeout).await?;
+
+            // Secure the connection, learning the identity of the peer.
+            let (mut session, identity) = with_timeout("noise handshake", timeout, async {
+                select(&mut stream, noise::PROTOCOL).await?;
+                noise::upgrade(stream, &self.keypair, true).await
+            })
+            .await?;
+            info!("Noise session established with peer {}", identity.peer_id);
+            if let Some(expected) = &self.peer_id {
+                if *expected != identity.peer_id {
+                    return Err(HandshakeError::Protocol(format!(
+                        "Expected peer {} but {} answered",
+                        expected, identity.peer_id
+                    )));
+                }
+            }
+
+            // Multiplex the session and ask the peer to identify itself on a stream of its own.
+            let message = with_timeout("read", timeout, async {
+                select(&mut session, yamux::PROTOCOL).await?;
+                let mut yamux = YamuxSession::new(session, true);
+                let mut stream = yamux.open().await?;
+                select(&mut stream, IDENTIFY_PROTOCOL).await?;
+                recv_message(&mut stream, MAX_IDENTIFY_SIZE).await
+            })
+            .await?;
+            let identify = Identify::decode(message.as_slice()).map_err(|e| {
+                HandshakeError::InvalidResponse(format!("Invalid identify message: {}", e))
+            })?;
+            info!(
+                "Peer {} runs {} ({} protocols)",
+                identity.peer_id,
+                identify.agent_version,
+                identify.protocols.len()
+            );
+
+            Ok(PeerInfo {
+                peer_id: identity.peer_id,
+                agent_version: identify.agent_version,
+                protocol_version: identify.protocol_version,
+                protocols: identify.protocols,
+                listen_addrs: identify
+                    .listen_addrs
+                    .iter()
+                    .map(|address| multiaddr::decode(address))
+                    .collect(),
+                observed_addr: match identify.observed_addr.is_empty() {
+                    true => None,
+                    false => Some(multiaddr::decode(&identify.observed_addr)),
+                },
+            })
+        })
+        .await
+    }
+}
+
+impl Connectable for Libp2pNode {
+    fn connect_options(&mut self) -> &mut ConnectOptions {
+        &mut self.connect
+    }
+}
+
+// Implement the Display trait for Libp2pNode for easy logging and debugging.
+impl fmt::Display for Libp2pNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Libp2pNode({})", self.remote) // Custom display format showing the remote address.
+    }
+}
+
+// Async trait for performing the handshake operation, returning the agent the peer reported.
+#[async_trait]
+impl Handshake for Libp2pNode {
+    async fn shake(&self, timeout: Option<u32>) -> Result<String, HandshakeError> {
+        Ok(self.identify(timeout).await?.agent_version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Test suite for Libp2pNode functionality, run against an in-process peer.
+    use {
+        super::*,
+        crate::libp2p::channel::{accept, send_message, Channel},
+        std::net::SocketAddr,
+        tokio::net::TcpListener,
+    };
+
+    // Accepts a single connection and answers the handshake as a libp2p peer would,
+    // returning the peer's address and ID.
+    async fn serve() -> (SocketAddr, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let identity = Keypair::new();
+        let peer_id = noise::peer_id(&noise::encode_identity(&identity));
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            accept(&mut socket, &[noise::PROTOCOL]).await.unwrap();
+            let (mut session, _) = noise::upgrade(socket, &identity, false).await.unwrap();
+            accept(&mut session, &[yamux::PROTOCOL]).await.unwrap();
+            let mut yamux = YamuxSession::new(session, false);
+            let mut stream = yamux.accept().await.unwrap();
+            accept(&mut stream, &[IDENTIFY_PROTOCOL]).await.unwrap();
+            let identify = Identify {
+                public_key: noise::encode_identity(&identity),
+                listen_addrs: vec![vec![4, 127, 0, 0, 1, 6, 0x0f, 0xa1]],
+                protocols: vec![
+                    IDENTIFY_PROTOCOL.to_string(),
+                    "/ipfs/ping/1.0.0".to_string(),
+                ],
+                observed_addr: Vec::new(),
+                protocol_version: "ipfs/0.1.0".to_string(),
+                agent_version: "kubo/0.27.0/".to_string(),
+            };
+            send_message(&mut stream, &identify.encode_to_vec())
+                .await
+                .unwrap();
+            stream.close().await.unwrap();
+            // Keep the connection open until we close it.
+            let _ = stream.recv_exact(1).await;
+        });
+        (address, peer_id)
+    }
+
+    // Verifies that a Libp2pNode can be correctly instantiated and displayed.
+    #[test]
+    fn test_libp2p_node_creation() {
+        let addr: SocketAddr = "127.0.0.1:4001".parse().unwrap();
+        let node = Libp2pNode::new(addr);
+
+        assert_eq!(node.peer_id, None);
+        assert_eq!(node.to_string(), "Libp2pNode(127.0.0.1:4001)"); // Checks the display format.
+    }
+
+    // The handshake reports the peer's ID and what it sent through identify.
+    #[tokio::test]
+    async fn test_identify() {
+        let (address, peer_id) = serve().await;
+
+        let info = Libp2pNode::new(address)
+            .with_peer_id(Some(peer_id.clone()))
+            .identify(Some(5))
+            .await
+            .unwrap();
+        assert_eq!(info.peer_id, peer_id);
+        assert_eq!(info.agent_version, "kubo/0.27.0/");
+        assert_eq!(info.protocols.len(), 2);
+        assert_eq!(info.listen_addrs, vec!["/ip4/127.0.0.1/tcp/4001"]);
+        assert_eq!(info.observed_addr, None);
+    }
+
+    // Another peer than the one expected fails the handshake.
+    #[tokio::test]
+    async fn test_unexpected_peer_id() {
+        let (address, _) = serve().await;
+        let other = noise::peer_id(&noise::encode_identity(&Keypair::new()));
+
+        let result = Libp2pNode::new(address)
+            .with_peer_id(Some(other))
+            .shake(Some(5))
+            .await;
+        assert!(matches!(result, Err(HandshakeError::Protocol(_))));
+    }
+}
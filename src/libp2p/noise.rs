@@ -0,0 +1,430 @@
+// The libp2p Noise handshake, `Noise_XX_25519_ChaChaPoly_SHA256`, securing a connection between two peers.
+// Each peer proves its identity by signing its Noise static key with its identity key in the handshake
+// payload; the identity is Ed25519, which is also the key type of Solana keypairs.
+// Once the handshake completes, every message is encrypted into a frame prefixed with its two-byte length.
+use {
+    super::{
+        channel::Channel,
+        proto::{NoiseHandshakePayload, PublicKey, KEY_TYPE_ED25519},
+    }, // Import the channel abstraction and the handshake payload messages.
+    crate::error::HandshakeError, // Import the crate-wide error type.
+    async_trait::async_trait,     // Import async_trait for asynchronous trait methods.
+    chacha20poly1305::{
+        aead::{Aead, NewAead, Payload},
+        ChaCha20Poly1305, Key, Nonce,
+    }, // Import the AEAD cipher of the handshake.
+    curve25519_dalek::montgomery::MontgomeryPoint, // Import X25519.
+    hmac::{Hmac, Mac},            // Import HMAC, from which HKDF is built.
+    prost::Message,               // Import protobuf encoding of the payloads.
+    sha2::{Digest, Sha256},       // Import the hash of the handshake.
+    solana_sdk::signature::{Keypair, Signature, Signer}, // Import Ed25519 signing and verification.
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    }, // Import the TCP stream and its IO helpers.
+};
+
+// Protocol negotiated with multistream-select before the handshake.
+pub const PROTOCOL: &str = "/noise";
+
+// Noise protocol name, exactly the 32 bytes the handshake hash starts from.
+const PROTOCOL_NAME: &[u8; 32] = b"Noise_XX_25519_ChaChaPoly_SHA256";
+
+// Prefix of the static key in the message signed by the identity key.
+const STATIC_KEY_DOMAIN: &[u8] = b"noise-libp2p-static-key:";
+
+// Size of X25519 keys.
+const KEY_SIZE: usize = 32;
+
+// Size of the authentication tag appended to every ciphertext.
+const TAG_SIZE: usize = 16;
+
+// Largest frame, bounded by its two-byte length prefix.
+const MAX_FRAME_SIZE: usize = 65535;
+
+// Largest encoded public key kept inline in a peer ID; longer keys are hashed.
+const MAX_INLINE_KEY_SIZE: usize = 42;
+
+// Identity a peer proved during the handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteIdentity {
+    pub peer_id: String, // Base58 peer ID derived from the identity key.
+    pub key_type: i32,   // Type of the identity key, e.g. 1 for Ed25519.
+    pub verified: bool, // Whether the signature of the static key was verified; only Ed25519 keys are checked.
+}
+
+// Derives the base58 peer ID of an encoded identity key: the key itself when short, its SHA-256 otherwise.
+pub fn peer_id(encoded_key: &[u8]) -> String {
+    let multihash = match encoded_key.len() <= MAX_INLINE_KEY_SIZE {
+        true => [&[0x00, encoded_key.len() as u8], encoded_key].concat(),
+        false => [&[0x12, 0x20], &Sha256::digest(encoded_key)[..]].concat(),
+    };
+    bs58::encode(multihash).into_string()
+}
+
+// Encodes the identity key of a keypair as found in handshake payloads.
+pub fn encode_identity(identity: &Keypair) -> Vec<u8> {
+    PublicKey {
+        key_type: KEY_TYPE_ED25519,
+        data: identity.pubkey().to_bytes().to_vec(),
+    }
+    .encode_to_vec()
+}
+
+// Maps a failure of the handshake's cryptography to a handshake error.
+fn noise_error(message: &str) -> HandshakeError {
+    HandshakeError::Tls(format!("Noise handshake failed: {}", message))
+}
+
+// An X25519 key pair.
+struct DhKeypair {
+    secret: [u8; KEY_SIZE], // Secret key, clamped whenever it is multiplied.
+    public: [u8; KEY_SIZE], // Public key sent to the peer.
+}
+
+impl DhKeypair {
+    // Generates a fresh key pair.
+    fn generate() -> Self {
+        let secret: [u8; KEY_SIZE] = rand::random();
+        DhKeypair {
+            secret,
+            public: MontgomeryPoint::mul_base_clamped(secret).to_bytes(),
+        }
+    }
+
+    // Performs the Diffie-Hellman exchange with the peer's public key.
+    fn dh(&self, public: &[u8; KEY_SIZE]) -> [u8; KEY_SIZE] {
+        MontgomeryPoint(*public).mul_clamped(self.secret).to_bytes()
+    }
+}
+
+// HMAC-SHA256 of the concatenated parts.
+fn hmac(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+// HKDF as defined by Noise, deriving two keys from the chaining key and the input key material.
+fn hkdf(chaining_key: &[u8; 32], input: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let temp_key = hmac(chaining_key, &[input]);
+    let first = hmac(&temp_key, &[&[1]]);
+    let second = hmac(&temp_key, &[&first, &[2]]);
+    (first, second)
+}
+
+// A key and the nonce of the next message encrypted or decrypted with it.
+struct CipherState {
+    key: [u8; 32], // ChaCha20-Poly1305 key.
+    nonce: u64,    // Counter of the messages processed.
+}
+
+impl CipherState {
+    // Constructs the cipher state of a fresh key.
+    fn new(key: [u8; 32]) -> Self {
+        CipherState { key, nonce: 0 }
+    }
+
+    // The 96-bit nonce of the next message: four zero bytes and the little-endian counter.
+    fn next_nonce(&mut self) -> [u8; 12] {
+        let mut nonce = [0; 12];
+        nonce[4..].copy_from_slice(&self.nonce.to_le_bytes());
+        self.nonce += 1;
+        nonce
+    }
+
+    // Encrypts a message, authenticating the associated data along with it.
+    fn encrypt(&mut self, aad: &[u8], msg: &[u8]) -> Result<Vec<u8>, HandshakeError> {
+        let nonce = self.next_nonce();
+        ChaCha20Poly1305::new(&Key::from(self.key))
+            .encrypt(&Nonce::from(nonce), Payload { msg, aad })
+            .map_err(|_| noise_error("Unable to encrypt"))
+    }
+
+    // Decrypts a message, failing when it or the associated data was tampered with.
+    fn decrypt(&mut self, aad: &[u8], msg: &[u8]) -> Result<Vec<u8>, HandshakeError> {
+        let nonce = self.next_nonce();
+        ChaCha20Poly1305::new(&Key::from(self.key))
+            .decrypt(&Nonce::from(nonce), Payload { msg, aad })
+            .map_err(|_| noise_error("Unable to decrypt"))
+    }
+}
+
+// Symmetric state of the handshake: the chaining key, the hash of the transcript and the current key.
+struct SymmetricState {
+    chaining_key: [u8; 32],      // Chaining key, from which every key is derived.
+    hash: [u8; 32],              // Hash of everything sent and received so far.
+    cipher: Option<CipherState>, // Key of the handshake, once a Diffie-Hellman result was mixed in.
+}
+
+impl SymmetricState {
+    // Starts the handshake with the protocol name and libp2p's empty prologue.
+    fn new() -> Self {
+        let mut state = SymmetricState {
+            chaining_key: *PROTOCOL_NAME,
+            hash: *PROTOCOL_NAME,
+            cipher: None,
+        };
+        state.mix_hash(&[]);
+        state
+    }
+
+    // Adds data to the transcript hash.
+    fn mix_hash(&mut self, data: &[u8]) {
+        self.hash = Sha256::new()
+            .chain_update(self.hash)
+            .chain_update(data)
+            .finalize()
+            .into();
+    }
+
+    // Mixes a Diffie-Hellman result into the chaining key, deriving a new key.
+    fn mix_key(&mut self, input: &[u8]) {
+        let (chaining_key, key) = hkdf(&self.chaining_key, input);
+        self.chaining_key = chaining_key;
+        self.cipher = Some(CipherState::new(key));
+    }
+
+    // Encrypts data, once there is a key, and adds the result to the transcript.
+    fn encrypt_and_hash(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, HandshakeError> {
+        let ciphertext = match &mut self.cipher {
+            Some(cipher) => cipher.encrypt(&self.hash, plaintext)?,
+            None => plaintext.to_vec(),
+        };
+        self.mix_hash(&ciphertext);
+        Ok(ciphertext)
+    }
+
+    // Decrypts data, once there is a key, and adds the ciphertext to the transcript.
+    fn decrypt_and_hash(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, HandshakeError> {
+        let plaintext = match &mut self.cipher {
+            Some(cipher) => cipher.decrypt(&self.hash, ciphertext)?,
+            None => ciphertext.to_vec(),
+        };
+        self.mix_hash(ciphertext);
+        Ok(plaintext)
+    }
+
+    // Derives the keys of the session: the initiator's sending key first, the responder's second.
+    fn split(&self) -> (CipherState, CipherState) {
+        let (first, second) = hkdf(&self.chaining_key, &[]);
+        (CipherState::new(first), CipherState::new(second))
+    }
+}
+
+// Writes a frame prefixed with its two-byte length.
+async fn write_frame(stream: &mut TcpStream, frame: &[u8]) -> Result<(), HandshakeError> {
+    let mut bytes = Vec::with_capacity(frame.len() + 2);
+    bytes.extend_from_slice(&(frame.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(frame);
+    Ok(stream.write_all(&bytes).await?)
+}
+
+// Reads a frame prefixed with its two-byte length.
+async fn read_frame(stream: &mut TcpStream) -> Result<Vec<u8>, HandshakeError> {
+    let length = stream.read_u16().await?;
+    let mut frame = vec![0; length as usize];
+    stream.read_exact(&mut frame).await?;
+    Ok(frame)
+}
+
+// Splits a handshake message at `n` bytes, failing when it is shorter.
+fn split_at(message: &[u8], n: usize) -> Result<(&[u8], &[u8]), HandshakeError> {
+    match message.len() >= n {
+        true => Ok(message.split_at(n)),
+        false => Err(noise_error("Truncated handshake message")),
+    }
+}
+
+// Reads a 32-byte public key.
+fn public_key(bytes: &[u8]) -> [u8; KEY_SIZE] {
+    bytes.try_into().expect("32-byte key")
+}
+
+// Builds our handshake payload, signing the static key with the identity key.
+fn payload(identity: &Keypair, static_key: &DhKeypair) -> Vec<u8> {
+    let signature = identity.sign_message(&[STATIC_KEY_DOMAIN, &static_key.public].concat());
+    NoiseHandshakePayload {
+        identity_key: encode_identity(identity),
+        identity_sig: signature.as_ref().to_vec(),
+    }
+    .encode_to_vec()
+}
+
+// Reads the peer's handshake payload and checks its identity key signed the static key it sent.
+fn verify_payload(
+    payload: &[u8],
+    static_key: &[u8; KEY_SIZE],
+) -> Result<RemoteIdentity, HandshakeError> {
+    let payload = NoiseHandshakePayload::decode(payload).map_err(|e| {
+        HandshakeError::InvalidResponse(format!("Invalid handshake payload: {}", e))
+    })?;
+    let key = PublicKey::decode(payload.identity_key.as_slice())
+        .map_err(|e| HandshakeError::InvalidResponse(format!("Invalid identity key: {}", e)))?;
+
+    let verified = match key.key_type {
+        KEY_TYPE_ED25519 => {
+            let signed = [STATIC_KEY_DOMAIN, static_key].concat();
+            let valid = Signature::try_from(payload.identity_sig.as_slice())
+                .is_ok_and(|signature| signature.verify(&key.data, &signed));
+            if !valid {
+                return Err(noise_error("The identity key did not sign the static key"));
+            }
+            true
+        }
+        _ => false,
+    };
+    Ok(RemoteIdentity {
+        peer_id: peer_id(&payload.identity_key),
+        key_type: key.key_type,
+        verified,
+    })
+}
+
+// Runs the handshake over the connection, returning the secured session and the identity the peer proved.
+// The dialer is the initiator; the responder side answers it, as a listener would.
+pub async fn upgrade(
+    mut stream: TcpStream, // Connection to secure.
+    identity: &Keypair,    // Our identity, signing our static key.
+    initiator: bool,       // Whether we dialed the connection.
+) -> Result<(NoiseSession, RemoteIdentity), HandshakeError> {
+    let mut state = SymmetricState::new();
+    let ephemeral = DhKeypair::generate();
+    let static_key = DhKeypair::generate();
+
+    let remote = match initiator {
+        true => {
+            // -> e
+            state.mix_hash(&ephemeral.public);
+            let payload_1 = state.encrypt_and_hash(&[])?;
+            write_frame(&mut stream, &[&ephemeral.public[..], &payload_1].concat()).await?;
+
+            // <- e, ee, s, es
+            let message = read_frame(&mut stream).await?;
+            let (remote_ephemeral, rest) = split_at(&message, KEY_SIZE)?;
+            let remote_ephemeral = public_key(remote_ephemeral);
+            state.mix_hash(&remote_ephemeral);
+            state.mix_key(&ephemeral.dh(&remote_ephemeral));
+            let (remote_static, rest) = split_at(rest, KEY_SIZE + TAG_SIZE)?;
+            let remote_static = public_key(&state.decrypt_and_hash(remote_static)?);
+            state.mix_key(&ephemeral.dh(&remote_static));
+            let remote = verify_payload(&state.decrypt_and_hash(rest)?, &remote_static)?;
+
+            // -> s, se
+            let sent_static = state.encrypt_and_hash(&static_key.public)?;
+            state.mix_key(&static_key.dh(&remote_ephemeral));
+            let payload_3 = state.encrypt_and_hash(&payload(identity, &static_key))?;
+            write_frame(&mut stream, &[sent_static, payload_3].concat()).await?;
+            remote
+        }
+        false => {
+            // -> e
+            let message = read_frame(&mut stream).await?;
+            let (remote_ephemeral, rest) = split_at(&message, KEY_SIZE)?;
+            let remote_ephemeral = public_key(remote_ephemeral);
+            state.mix_hash(&remote_ephemeral);
+            state.decrypt_and_hash(rest)?;
+
+            // <- e, ee, s, es
+            state.mix_hash(&ephemeral.public);
+            state.mix_key(&ephemeral.dh(&remote_ephemeral));
+            let sent_static = state.encrypt_and_hash(&static_key.public)?;
+            state.mix_key(&static_key.dh(&remote_ephemeral));
+            let payload_2 = state.encrypt_and_hash(&payload(identity, &static_key))?;
+            let message = [&ephemeral.public[..], &sent_static, &payload_2].concat();
+            write_frame(&mut stream, &message).await?;
+
+            // -> s, se
+            let message = read_frame(&mut stream).await?;
+            let (remote_static, rest) = split_at(&message, KEY_SIZE + TAG_SIZE)?;
+            let remote_static = public_key(&state.decrypt_and_hash(remote_static)?);
+            state.mix_key(&ephemeral.dh(&remote_static));
+            verify_payload(&state.decrypt_and_hash(rest)?, &remote_static)?
+        }
+    };
+
+    let (initiator_keys, responder_keys) = state.split();
+    let (send, recv) = match initiator {
+        true => (initiator_keys, responder_keys),
+        false => (responder_keys, initiator_keys),
+    };
+    let session = NoiseSession {
+        stream,
+        send,
+        recv,
+        buffer: Vec::new(),
+    };
+    Ok((session, remote))
+}
+
+// A connection secured by the handshake.
+pub struct NoiseSession {
+    stream: TcpStream, // The underlying connection.
+    send: CipherState, // Key of the messages sent.
+    recv: CipherState, // Key of the messages received.
+    buffer: Vec<u8>,   // Decrypted bytes not consumed yet.
+}
+
+#[async_trait]
+impl Channel for NoiseSession {
+    async fn send(&mut self, data: &[u8]) -> Result<(), HandshakeError> {
+        for chunk in data.chunks(MAX_FRAME_SIZE - TAG_SIZE) {
+            let frame = self.send.encrypt(&[], chunk)?;
+            write_frame(&mut self.stream, &frame).await?;
+        }
+        Ok(())
+    }
+
+    async fn recv_exact(&mut self, n: usize) -> Result<Vec<u8>, HandshakeError> {
+        while self.buffer.len() < n {
+            let frame = read_frame(&mut self.stream).await?;
+            let plaintext = self.recv.decrypt(&[], &frame)?;
+            self.buffer.extend_from_slice(&plaintext);
+        }
+        Ok(self.buffer.drain(..n).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tokio::net::TcpListener};
+
+    // Peer IDs of Ed25519 keys inline the key, giving them their familiar prefix.
+    #[test]
+    fn test_peer_id() {
+        let peer_id = peer_id(&encode_identity(&Keypair::new()));
+        assert!(peer_id.starts_with("12D3KooW"), "Unexpected {}", peer_id);
+    }
+
+    // Both peers derive the same keys and learn each other's identity.
+    #[tokio::test]
+    async fn test_upgrade() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (dialer, responder) = (Keypair::new(), Keypair::new());
+        let (dialer_id, responder_id) = (
+            peer_id(&encode_identity(&dialer)),
+            peer_id(&encode_identity(&responder)),
+        );
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (mut session, remote) = upgrade(socket, &responder, false).await.unwrap();
+            let message = session.recv_exact(5).await.unwrap();
+            session.send(b"world").await.unwrap();
+            (remote, message)
+        });
+
+        let stream = TcpStream::connect(address).await.unwrap();
+        let (mut session, remote) = upgrade(stream, &dialer, true).await.unwrap();
+        session.send(b"hello").await.unwrap();
+        assert_eq!(session.recv_exact(5).await.unwrap(), b"world");
+        assert_eq!(remote.peer_id, responder_id);
+        assert!(remote.verified);
+
+        let (remote, message) = server.await.unwrap();
+        assert_eq!(message, b"hello");
+        assert_eq!(remote.peer_id, dialer_id);
+    }
+}
@@ -0,0 +1,42 @@
+// Protobuf messages of the libp2p Noise handshake and of the identify protocol.
+// They are written out rather than generated from the libp2p `.proto` files, as only these few are needed
+// and the build then needs no protobuf compiler.
+use prost::Message; // Protobuf encoding of the messages.
+
+// Key type of Ed25519 identity keys, the default of most libp2p implementations.
+pub const KEY_TYPE_ED25519: i32 = 1;
+
+// Public key of a peer's identity, tagged with its type.
+#[derive(Clone, PartialEq, Message)]
+pub struct PublicKey {
+    #[prost(int32, tag = "1")]
+    pub key_type: i32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub data: Vec<u8>,
+}
+
+// Payload of the Noise handshake messages carrying a static key, binding it to the peer's identity.
+#[derive(Clone, PartialEq, Message)]
+pub struct NoiseHandshakePayload {
+    #[prost(bytes = "vec", tag = "1")]
+    pub identity_key: Vec<u8>, // The encoded `PublicKey` of the peer's identity.
+    #[prost(bytes = "vec", tag = "2")]
+    pub identity_sig: Vec<u8>, // Signature of the Noise static key by the identity key.
+}
+
+// What a peer reports about itself through the identify protocol.
+#[derive(Clone, PartialEq, Message)]
+pub struct Identify {
+    #[prost(bytes = "vec", tag = "1")]
+    pub public_key: Vec<u8>, // The encoded `PublicKey` of the peer's identity.
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub listen_addrs: Vec<Vec<u8>>, // Binary multiaddrs the peer listens on.
+    #[prost(string, repeated, tag = "3")]
+    pub protocols: Vec<String>, // Protocols the peer supports, e.g. "/ipfs/kad/1.0.0".
+    #[prost(bytes = "vec", tag = "4")]
+    pub observed_addr: Vec<u8>, // Binary multiaddr the peer sees the connection coming from.
+    #[prost(string, tag = "5")]
+    pub protocol_version: String, // Family of protocols the peer speaks, e.g. "ipfs/0.1.0".
+    #[prost(string, tag = "6")]
+    pub agent_version: String, // Software the peer runs, e.g. "kubo/0.27.0/".
+}
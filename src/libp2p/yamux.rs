@@ -0,0 +1,277 @@
+// A minimal yamux session, multiplexing the streams libp2p protocols run on over a secured connection.
+// Only what a handshake needs is supported: opening or accepting one stream at a time and reading it
+// to completion, well within the initial window, so flow control never comes into play.
+// Streams the peer opens meanwhile, e.g. to identify us in turn, are reset.
+use {
+    super::channel::Channel,      // Import the channel abstraction.
+    crate::error::HandshakeError, // Import the crate-wide error type.
+    async_trait::async_trait,     // Import async_trait for asynchronous trait methods.
+    std::io::{Error, ErrorKind},  // Import standard IO types for streams closed early.
+    tracing::info,                // Import logging macros for informational logging.
+};
+
+// Protocol negotiated with multistream-select on the secured connection.
+pub const PROTOCOL: &str = "/yamux/1.0.0";
+
+// Version of the frame format.
+const VERSION: u8 = 0;
+
+// Size of the header of every frame.
+pub const HEADER_SIZE: usize = 12;
+
+// Largest data frame accepted, the initial window of a stream.
+const MAX_DATA_SIZE: u32 = 256 * 1024;
+
+// Type of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameType {
+    Data,         // Carries stream data.
+    WindowUpdate, // Grows a stream's window; also opens and closes streams.
+    Ping,         // Measures the round trip of the session.
+    GoAway,       // Terminates the session.
+}
+
+// Flags of a frame.
+pub const FLAG_SYN: u16 = 0x1; // Opens a stream.
+pub const FLAG_ACK: u16 = 0x2; // Acknowledges a stream, or answers a ping.
+pub const FLAG_FIN: u16 = 0x4; // Half-closes a stream.
+pub const FLAG_RST: u16 = 0x8; // Resets a stream.
+
+// Header of a frame. The length is that of the data for data frames, and a value otherwise:
+// the window increment, the ping's opaque value or the reason for going away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub frame_type: FrameType, // Type of the frame.
+    pub flags: u16,            // Combination of the `FLAG_*` values.
+    pub stream_id: u32,        // Stream the frame belongs to; 0 for the session itself.
+    pub length: u32,           // Length or value, depending on the type.
+}
+
+impl Header {
+    // Encodes the header, in network byte order.
+    pub fn encode(&self) -> [u8; HEADER_SIZE] {
+        let frame_type = match self.frame_type {
+            FrameType::Data => 0,
+            FrameType::WindowUpdate => 1,
+            FrameType::Ping => 2,
+            FrameType::GoAway => 3,
+        };
+        let mut bytes = [0; HEADER_SIZE];
+        bytes[0] = VERSION;
+        bytes[1] = frame_type;
+        bytes[2..4].copy_from_slice(&self.flags.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.stream_id.to_be_bytes());
+        bytes[8..12].copy_from_slice(&self.length.to_be_bytes());
+        bytes
+    }
+
+    // Decodes a header, rejecting unknown versions and types.
+    pub fn decode(bytes: &[u8]) -> Result<Self, HandshakeError> {
+        if bytes.len() != HEADER_SIZE || bytes[0] != VERSION {
+            return Err(HandshakeError::InvalidResponse(
+                "Invalid yamux frame header".to_string(),
+            ));
+        }
+        let frame_type = match bytes[1] {
+            0 => FrameType::Data,
+            1 => FrameType::WindowUpdate,
+            2 => FrameType::Ping,
+            3 => FrameType::GoAway,
+            other => {
+                return Err(HandshakeError::InvalidResponse(format!(
+                    "Unknown yamux frame type {}",
+                    other
+                )))
+            }
+        };
+        Ok(Header {
+            frame_type,
+            flags: u16::from_be_bytes([bytes[2], bytes[3]]),
+            stream_id: u32::from_be_bytes(bytes[4..8].try_into().expect("4 bytes")),
+            length: u32::from_be_bytes(bytes[8..12].try_into().expect("4 bytes")),
+        })
+    }
+}
+
+// A yamux session over a channel.
+pub struct YamuxSession<C: Channel> {
+    channel: C,          // The secured connection.
+    next_stream_id: u32, // Identifier of the next stream opened: odd for the dialer, even for the listener.
+}
+
+impl<C: Channel> YamuxSession<C> {
+    // Starts a session over the channel; the dialer of the connection is the client.
+    pub fn new(channel: C, client: bool) -> Self {
+        YamuxSession {
+            channel,
+            next_stream_id: if client { 1 } else { 2 },
+        }
+    }
+
+    // Sends a frame without data.
+    async fn send_header(
+        &mut self,
+        frame_type: FrameType,
+        flags: u16,
+        stream_id: u32,
+        length: u32,
+    ) -> Result<(), HandshakeError> {
+        let header = Header {
+            frame_type,
+            flags,
+            stream_id,
+            length,
+        };
+        self.channel.send(&header.encode()).await
+    }
+
+    // Reads the next frame, with its data when it is a data frame.
+    async fn read_frame(&mut self) -> Result<(Header, Vec<u8>), HandshakeError> {
+        let header = Header::decode(&self.channel.recv_exact(HEADER_SIZE).await?)?;
+        let data = match header.frame_type {
+            FrameType::Data if header.length > MAX_DATA_SIZE => {
+                return Err(HandshakeError::InvalidResponse(format!(
+                    "Yamux frame of {} bytes exceeds the window",
+                    header.length
+                )))
+            }
+            FrameType::Data => self.channel.recv_exact(header.length as usize).await?,
+            _ => Vec::new(),
+        };
+        Ok((header, data))
+    }
+
+    // Handles a frame that does not belong to the stream being read: pings are answered,
+    // streams opened by the peer are reset and going away ends the session.
+    async fn handle_session_frame(&mut self, header: Header) -> Result<(), HandshakeError> {
+        match header.frame_type {
+            FrameType::Ping if header.flags & FLAG_SYN != 0 => {
+                self.send_header(FrameType::Ping, FLAG_ACK, 0, header.length)
+                    .await
+            }
+            FrameType::GoAway => Err(HandshakeError::Protocol(format!(
+                "The peer closed the yamux session with code {}",
+                header.length
+            ))),
+            FrameType::Data | FrameType::WindowUpdate if header.flags & FLAG_SYN != 0 => {
+                info!(
+                    "Resetting yamux stream {} opened by the peer",
+                    header.stream_id
+                );
+                self.send_header(FrameType::WindowUpdate, FLAG_RST, header.stream_id, 0)
+                    .await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Opens a stream.
+    pub async fn open(&mut self) -> Result<YamuxStream<'_, C>, HandshakeError> {
+        let stream_id = self.next_stream_id;
+        self.next_stream_id += 2;
+        self.send_header(FrameType::WindowUpdate, FLAG_SYN, stream_id, 0)
+            .await?;
+        Ok(YamuxStream::new(self, stream_id))
+    }
+
+    // Waits for the peer to open a stream and accepts it.
+    pub async fn accept(&mut self) -> Result<YamuxStream<'_, C>, HandshakeError> {
+        loop {
+            let (header, data) = self.read_frame().await?;
+            let opened = matches!(header.frame_type, FrameType::Data | FrameType::WindowUpdate)
+                && header.flags & FLAG_SYN != 0;
+            if !opened {
+                self.handle_session_frame(header).await?;
+                continue;
+            }
+            self.send_header(FrameType::WindowUpdate, FLAG_ACK, header.stream_id, 0)
+                .await?;
+            let mut stream = YamuxStream::new(self, header.stream_id);
+            stream.buffer = data;
+            return Ok(stream);
+        }
+    }
+}
+
+// A stream of a yamux session.
+pub struct YamuxStream<'a, C: Channel> {
+    session: &'a mut YamuxSession<C>, // The session the stream belongs to.
+    stream_id: u32,                   // Identifier of the stream.
+    buffer: Vec<u8>,                  // Data received and not consumed yet.
+    closed: bool,                     // Whether the peer closed or reset the stream.
+}
+
+impl<'a, C: Channel> YamuxStream<'a, C> {
+    // Constructs the stream with the given identifier.
+    fn new(session: &'a mut YamuxSession<C>, stream_id: u32) -> Self {
+        YamuxStream {
+            session,
+            stream_id,
+            buffer: Vec::new(),
+            closed: false,
+        }
+    }
+
+    // Half-closes the stream: nothing more is sent on it.
+    pub async fn close(&mut self) -> Result<(), HandshakeError> {
+        self.session
+            .send_header(FrameType::WindowUpdate, FLAG_FIN, self.stream_id, 0)
+            .await
+    }
+}
+
+#[async_trait]
+impl<'a, C: Channel> Channel for YamuxStream<'a, C> {
+    async fn send(&mut self, data: &[u8]) -> Result<(), HandshakeError> {
+        for chunk in data.chunks(MAX_DATA_SIZE as usize) {
+            let header = Header {
+                frame_type: FrameType::Data,
+                flags: 0,
+                stream_id: self.stream_id,
+                length: chunk.len() as u32,
+            };
+            let frame = [&header.encode()[..], chunk].concat();
+            self.session.channel.send(&frame).await?;
+        }
+        Ok(())
+    }
+
+    async fn recv_exact(&mut self, n: usize) -> Result<Vec<u8>, HandshakeError> {
+        while self.buffer.len() < n {
+            if self.closed {
+                return Err(HandshakeError::Connect(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    format!("Yamux stream {} closed by the peer", self.stream_id),
+                )));
+            }
+            let (header, data) = self.session.read_frame().await?;
+            if header.stream_id != self.stream_id {
+                self.session.handle_session_frame(header).await?;
+                continue;
+            }
+            self.buffer.extend_from_slice(&data);
+            self.closed = header.flags & (FLAG_FIN | FLAG_RST) != 0;
+        }
+        Ok(self.buffer.drain(..n).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Headers survive a round trip through their encoding.
+    #[test]
+    fn test_header_round_trip() {
+        let header = Header {
+            frame_type: FrameType::WindowUpdate,
+            flags: FLAG_SYN,
+            stream_id: 3,
+            length: 0,
+        };
+        let bytes = header.encode();
+        assert_eq!(bytes, [0, 1, 0, 1, 0, 0, 0, 3, 0, 0, 0, 0]);
+        assert_eq!(Header::decode(&bytes).unwrap(), header);
+        assert!(Header::decode(&[1; HEADER_SIZE]).is_err());
+    }
+}
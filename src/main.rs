@@ -1,62 +1,454 @@
+// The binary needs sockets; built for wasm32, where only the library's browser transport is of use, it is empty.
+#![cfg_attr(target_arch = "wasm32", no_main)]
+#![cfg(not(target_arch = "wasm32"))]
 // Import necessary modules and crates for CLI handling, networking, and logging.
-use clap::Parser; //Use clap parser
+use clap::{CommandFactory, Parser}; //Use clap parser and the command definition for completions
 use cli::{Cli, Command}; // Assuming these are defined in a local `cli` module for parsing CLI commands.
-use handshake::solana::node::Handshake; // Interface for performing handshake operations.
-use handshake::solana::rpc::node::RpcNode; // RPC node handling functionalities.
-use handshake::solana::TransportType; // Enum for different transport types (TCP, TLS, WS, WSS).
-use tracing::info; // Import the `info` macro for logging informational messages.
+use geoip::{AsnDb, GeoIp}; // Locations and networks of scanned nodes from MaxMind databases.
+use handshake::solana::cancel::Cancel; // Stopping scans and benchmarks early on Ctrl+C.
+use handshake::solana::target::AddressFamily; // Address family selection.
+use handshake::HandshakeError; // Crate-wide error type, whose classes map onto exit codes.
+use metrics::Metrics; // Prometheus metrics served in watch mode.
+use notify::{Alert, Notifier}; // Webhook notifications of changes of state in watch and daemon modes.
+use output::{render_schema, Output, OutputFile, OutputFormat}; // Structured output of results.
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+}; // Shared metrics and watch intervals.
+use tokio::{net::TcpListener, time::MissedTickBehavior}; // Metrics listener and scheduling of watch rounds.
+use tracing::{error, info}; // Import logging macros for informational and error messages.
+mod api; // Import the api module which monitors nodes and serves a JSON API for handshakes and their status.
 mod cli; // Import the CLI module which defines the `Cli` and `Command` structures.
+mod connect; // Import the connect module which handshakes the nodes of every protocol but Solana's RPC once.
+mod daemon; // Import the daemon module which monitors nodes and serves their status over HTTP.
+mod geoip; // Import the geoip module which locates scanned nodes in a MaxMind database.
+#[cfg(feature = "grpc")]
+mod grpc; // Import the grpc module which serves handshakes to other services over gRPC.
+mod http_server; // Import the http_server module which answers the requests of the metrics, daemon and API endpoints.
+#[cfg(feature = "kafka")]
+mod kafka; // Import the kafka module which produces every handshake to a Kafka topic.
+mod metrics; // Import the metrics module which records handshakes and serves them to Prometheus.
+#[cfg(feature = "mqtt")]
+mod mqtt; // Import the mqtt module which publishes every handshake to an MQTT broker.
+mod notify; // Import the notify module which posts changes of the targets' state to a webhook.
+mod output; // Import the output module which defines the output formats and reports.
+mod progress; // Import the progress module which draws the progress bar of multi-target scans.
+mod rpc; // Import the rpc module which runs the subcommands handshaking Solana RPC nodes.
+mod telemetry; // Import the telemetry module which sets up logging and optional trace export.
+#[cfg(feature = "tui")]
+mod ui; // Import the ui module which draws the live dashboard of monitored nodes.
+
+// Exit codes returned when a handshake fails outright, one per failure class, so scripts can branch on
+// the cause. Any other error exits with code 1.
+const DNS_EXIT_CODE: i32 = 2;
+const CONNECT_EXIT_CODE: i32 = 3;
+const TLS_EXIT_CODE: i32 = 4;
+const PROTOCOL_EXIT_CODE: i32 = 5;
+const TIMEOUT_EXIT_CODE: i32 = 6;
+
+// Exit code returned when a node answers but fails a check, e.g. it is unhealthy or reports an unexpected identity.
+const CHECK_FAILED_EXIT_CODE: i32 = 7;
+
+// Exit code returned when a node answers with a solana-core version not satisfying `--expect-version`.
+const VERSION_MISMATCH_EXIT_CODE: i32 = 8;
+
+// Exit code returned when a node answers the call with a JSON-RPC error object, e.g. for an unknown method.
+const RPC_ERROR_EXIT_CODE: i32 = 9;
+
+// Exit code returned when the host of a node does not answer `--precheck`, so it is unreachable at the network layer.
+const UNREACHABLE_EXIT_CODE: i32 = 10;
+
+// Exit code returned when only some of the handshakes of a run failed, e.g. some targets of a scan, some
+// surfaces of a probe or some handshakes of a benchmark. A run whose every handshake failed exits with
+// the code of its failures' class instead.
+const PARTIAL_FAILURE_EXIT_CODE: i32 = 11;
+
+// Exit code returned for invalid arguments, e.g. an unknown flag or flags that cannot be combined, as
+// sysexits.h's `EX_USAGE`.
+const USAGE_EXIT_CODE: i32 = 64;
+
+// Exit code returned when the run is interrupted with Ctrl+C, as shells report processes killed by SIGINT.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
 
 // Async entrypoint
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize `tracing` for application-wide logging.
-    // This setup uses default settings for simplicity, suitable for most applications.
-    tracing_subscriber::fmt::init();
-
     // Parse command-line arguments into the `Cli` struct, leveraging Clap's derive macros for parsing.
-    let cli = Cli::parse();
+    // Invalid arguments exit with their own code, apart from the failure classes; `--help` and
+    // `--version` exit as clap has them.
+    let mut cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(error) if error.use_stderr() => {
+            let _ = error.print();
+            std::process::exit(USAGE_EXIT_CODE);
+        }
+        Err(error) => error.exit(),
+    };
+
+    // Initialize `tracing` for application-wide logging, once the flags selecting trace export are known.
+    // Logs go to stderr, keeping stdout for results that scripts can consume.
+    telemetry::init(&cli)?;
 
-    // Handle the parsed command using pattern matching to decide the flow based on user input.
-    match cli.command {
-        // If the command is to connect via RPC, handle accordingly.
-        Command::ConnectRpc(node) => {
-            // Determine whether to use secure transport (TLS) or plain TCP based on the `secure` flag.
-            let trans_type = match node.secure {
-                true => TransportType::Tls,
-                false => TransportType::Tcp,
-            };
+    // Expand `--cluster` into its endpoint, resolved when it is handshaken like any address.
+    cli.expand_cluster();
+
+    // Targets listed on stdin could not be read again for the next round.
+    if cli.watch.is_some() && cli.reads_targets_from_stdin() {
+        anyhow::bail!(UsageError(
+            "--targets-file - cannot be used with --watch, as stdin can only be read once"
+        ));
+    }
 
-            // Instantiate an RPC node with the provided address and determined transport type.
-            let rpc_node = RpcNode::new(node.address, trans_type);
+    // Changes of state only happen over repeated handshakes.
+    let monitors =
+        cli.watch.is_some() || matches!(cli.command, Command::Daemon(_) | Command::ServeHttp(_));
+    if (cli.webhook.is_some() || cli.alerts.is_some()) && !monitors {
+        anyhow::bail!(UsageError(
+            "--webhook and --alerts can only be used with --watch or the daemon and serve-http subcommands"
+        ));
+    }
+    #[cfg(feature = "mqtt")]
+    if cli.mqtt.is_some() && !monitors {
+        anyhow::bail!(UsageError(
+            "--mqtt can only be used with --watch or the daemon and serve-http subcommands"
+        ));
+    }
 
-            // Log the connection attempt.
-            info!("Connecting to {}", rpc_node);
+    // With `--watch`, repeat the command until interrupted; otherwise run it once.
+    // Completion scripts are static, so they are never repeated.
+    let family = cli.address_family();
+    let mut output = Output::new(cli.output);
+    if let Some(path) = cli.out.clone() {
+        output = output.with_file(OutputFile::new(path, cli.append));
+    }
+    if let Some(path) = &cli.geoip {
+        output = output.with_geoip(Arc::new(GeoIp::open(path)?));
+    }
+    if let Some(path) = &cli.asn {
+        output = output.with_asn(Arc::new(AsnDb::open(path)?));
+    }
+    let alerts: Vec<Alert> = cli
+        .webhook
+        .clone()
+        .map(Alert::new)
+        .into_iter()
+        .chain(cli.alerts.iter().flat_map(|config| config.alerts.clone()))
+        .collect();
+    if !alerts.is_empty() {
+        let notifier = Notifier::new(alerts, cli.webhook_debounce);
+        output = output.with_notifier(Arc::new(notifier));
+    }
+    #[cfg(feature = "mqtt")]
+    if let Some(broker) = &cli.mqtt {
+        let publisher = mqtt::Publisher::new(broker, cli.topic.clone(), cli.mqtt_qos);
+        output = output.with_mqtt(Arc::new(publisher));
+    }
+    #[cfg(feature = "kafka")]
+    if let Some(brokers) = &cli.kafka {
+        let producer = kafka::Producer::new(brokers, cli.kafka_topic.clone())?;
+        output = output.with_kafka(Arc::new(producer));
+    }
 
-            // Attempt to perform a handshake with the RPC node, awaiting the asynchronous operation.
-            let response = rpc_node.shake(None).await?;
+    // The first Ctrl+C stops the run, which still writes out the results gathered so far; the second
+    // exits at once.
+    let cancel = Cancel::new();
+    tokio::spawn(interrupt(cancel.clone()));
 
-            // Log the response from the handshake operation.
-            info!("Handshake response was {:?}", response);
+    let outcome = match (&cli.command, cli.watch) {
+        // The dashboard repeats its handshakes itself, on the watch interval.
+        #[cfg(feature = "tui")]
+        (Command::Tui(node), Some(interval)) => {
+            let refresh = Duration::from_secs(interval);
+            ui::dashboard(node, family, refresh)
+                .await
+                .map(|_| Outcome::Passed)
         }
-        // If the command is to connect via WebSocket, the process is similar but with WebSocket protocols.
-        Command::ConnectRpcWithWebsocket(node) => {
-            // Choose between secure WebSocket (WSS) or plain WebSocket (WS) based on the `secure` flag.
-            let trans_type = match node.secure {
-                true => TransportType::Wss,
-                false => TransportType::Ws,
-            };
+        // So does the daemon.
+        (Command::Daemon(node), Some(interval)) => {
+            let interval = Duration::from_secs(interval);
+            cancel
+                .run(daemon::run(node, family, interval, &output))
+                .await
+                .unwrap_or(Ok(()))
+                .map(|_| Outcome::Passed)
+        }
+        // And the API.
+        (Command::ServeHttp(node), Some(interval)) => {
+            let interval = Duration::from_secs(interval);
+            cancel
+                .run(api::run(node, family, interval, &output))
+                .await
+                .unwrap_or(Ok(()))
+                .map(|_| Outcome::Passed)
+        }
+        (command, Some(interval))
+            if !matches!(command, Command::Schema(_) | Command::Completions(_)) =>
+        {
+            // Serve the metrics in the background for as long as the watch runs.
+            if let Some(address) = cli.metrics_address {
+                let metrics = Arc::new(Metrics::new());
+                let listener = TcpListener::bind(address).await?;
+                tokio::spawn(metrics::serve(listener, metrics.clone()));
+                output = output.with_metrics(metrics);
+            }
+            watch(&cli.command, family, &output, interval, &cancel).await
+        }
+        _ => {
+            // The results are written out even when the run failed, as they may describe why.
+            let outcome = run_until(&cli.command, family, &output, &cancel).await;
+            output.flush().and(outcome)
+        }
+    };
+
+    // Export any spans still pending before exiting.
+    telemetry::shutdown();
+    // And deliver any reports still queued for Kafka.
+    #[cfg(feature = "kafka")]
+    if let Some(producer) = &output.kafka {
+        producer.flush();
+    }
+    if cancel.is_cancelled() {
+        eprintln!("Interrupted");
+        std::process::exit(INTERRUPTED_EXIT_CODE);
+    }
+    // Report the error as returning it from `main` would, but with the code of its class.
+    if let Err(error) = &outcome {
+        eprintln!("Error: {:?}", error);
+    }
+    match outcome_exit_code(&outcome) {
+        0 => Ok(()),
+        code => std::process::exit(code),
+    }
+}
+
+// Exit code of the run's outcome, 0 when it passed.
+fn outcome_exit_code(outcome: &anyhow::Result<Outcome>) -> i32 {
+    match outcome {
+        Ok(Outcome::Passed) => 0,
+        Ok(Outcome::PartialFailure) => PARTIAL_FAILURE_EXIT_CODE,
+        Ok(Outcome::CheckFailed) => CHECK_FAILED_EXIT_CODE,
+        Ok(Outcome::VersionMismatch) => VERSION_MISMATCH_EXIT_CODE,
+        Err(error) => exit_code(error),
+    }
+}
+
+// Exit code of an error, by the class of the handshake error that caused it, if any.
+fn exit_code(error: &anyhow::Error) -> i32 {
+    if error.is::<UsageError>() {
+        return USAGE_EXIT_CODE;
+    }
+    let cause = error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<HandshakeError>());
+    match cause {
+        Some(HandshakeError::Dns(_)) => DNS_EXIT_CODE,
+        Some(HandshakeError::Unreachable(_)) => UNREACHABLE_EXIT_CODE,
+        Some(HandshakeError::Connect(_)) => CONNECT_EXIT_CODE,
+        Some(HandshakeError::Tls(_)) => TLS_EXIT_CODE,
+        Some(
+            HandshakeError::WebSocket(_)
+            | HandshakeError::Protocol(_)
+            | HandshakeError::InvalidResponse(_),
+        ) => PROTOCOL_EXIT_CODE,
+        Some(HandshakeError::Timeout { .. }) => TIMEOUT_EXIT_CODE,
+        Some(HandshakeError::Rpc { .. }) => RPC_ERROR_EXIT_CODE,
+        Some(HandshakeError::Cancelled) => INTERRUPTED_EXIT_CODE,
+        Some(HandshakeError::InvalidConfig(_)) | None => 1,
+    }
+}
+
+// Arguments clap accepts but that cannot be combined, e.g. `--race` with several addresses.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct UsageError(&'static str);
+
+// Outcome of one run of a subcommand whose handshakes did not fail outright.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Passed,          // Every handshake and check succeeded.
+    PartialFailure,  // Some targets of a multi-target run, or handshakes of a benchmark, failed.
+    CheckFailed,     // The node answered but failed a check, e.g. it is unhealthy.
+    VersionMismatch, // A node answered with a version not satisfying the expected one.
+}
+
+// Fires the signal on the first Ctrl+C, and exits on the second, for runs that do not stop in time.
+async fn interrupt(cancel: Cancel) {
+    if tokio::signal::ctrl_c().await.is_err() {
+        return; // Without a handler, Ctrl+C terminates the process as usual.
+    }
+    info!("Interrupted, writing out the results so far; press Ctrl+C again to exit at once");
+    cancel.cancel();
+    if tokio::signal::ctrl_c().await.is_ok() {
+        std::process::exit(INTERRUPTED_EXIT_CODE);
+    }
+}
+
+// Whether the command stops early once cancelled, keeping the results gathered so far: scans of
+// several targets, benchmarks and gossip pings. Any other command is dropped, as it has nothing partial to report.
+fn stops_early(command: &Command) -> bool {
+    match command {
+        Command::ConnectRpc(node) | Command::ConnectRpcWithWebsocket(node) => {
+            !node.subscribe
+                && node.ping.is_none()
+                && (node.address.len() > 1 || node.targets_file.is_some())
+        }
+        Command::ScanCluster(_) | Command::Bench(_) | Command::GossipPing(_) => true,
+        _ => false,
+    }
+}
+
+// Runs the command once, like `run`, until the signal is fired.
+async fn run_until(
+    command: &Command,
+    family: AddressFamily,
+    output: &Output,
+    cancel: &Cancel,
+) -> anyhow::Result<Outcome> {
+    match stops_early(command) {
+        true => run(command, family, output, cancel).await,
+        false => cancel
+            .run(run(command, family, output, cancel))
+            .await
+            .unwrap_or_else(|| Err(anyhow::anyhow!("Interrupted"))),
+    }
+}
 
-            // As before, instantiate an RPC node for WebSocket connection and log the attempt.
-            let rpc_node = RpcNode::new(node.address, trans_type);
-            info!("Connecting to {}", rpc_node);
+// Repeats the command every `interval` seconds, printing each result with a timestamp
+// and tracking how many rounds in a row have failed, until the signal is fired.
+async fn watch(
+    command: &Command,
+    family: AddressFamily,
+    output: &Output,
+    interval: u64,
+    cancel: &Cancel,
+) -> anyhow::Result<Outcome> {
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+    // A slow round delays the next one instead of triggering a burst of catch-up rounds.
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut consecutive_failures: u64 = 0;
 
-            // Perform the handshake over WebSocket, logging the response.
-            let response = rpc_node.shake(None).await?;
-            info!("Handshake response was {:?}", response);
+    loop {
+        if cancel.run(ticker.tick()).await.is_none() {
+            return Ok(Outcome::Passed);
+        }
+        let result = run_until(command, family, output, cancel).await;
+        // A round dropped before it finished has nothing to report.
+        if cancel.is_cancelled() && !stops_early(command) {
+            return result;
+        }
+        let status = match &result {
+            Ok(Outcome::Passed) => "ok",
+            Ok(Outcome::PartialFailure) => "partial",
+            Ok(Outcome::CheckFailed) => "check_failed",
+            Ok(Outcome::VersionMismatch) => "version_mismatch",
+            Err(_) => "failed",
+        };
+        consecutive_failures = match result {
+            Ok(Outcome::Passed) => 0,
+            _ => consecutive_failures + 1,
+        };
+        if let Err(e) = &result {
+            error!("Handshake failed: {}", e);
+        }
+        info!(
+            "Round finished with status {}, {} consecutive failures",
+            status, consecutive_failures
+        );
+
+        // The other formats carry the timestamp in each report; text mode prints one line per round.
+        if output.format == OutputFormat::Text {
+            output.print(format!(
+                "{} {} consecutive_failures={}{}\n",
+                humantime::format_rfc3339_millis(SystemTime::now()),
+                status,
+                consecutive_failures,
+                result
+                    .err()
+                    .map(|e| format!(" error=\"{}\"", e))
+                    .unwrap_or_default()
+            ))?;
+        }
+
+        // A failed write loses the round's results, but not the rounds after it.
+        if let Err(e) = output.flush() {
+            error!("{:#}", e);
         }
     }
+}
 
-    // If the command execution succeeds, return Ok.
-    Ok(())
+// Runs the command once against the targets' addresses of the given family, printing its results in the requested format.
+// Scans and benchmarks stop early when the signal is fired, keeping the results so far.
+async fn run(
+    command: &Command,
+    family: AddressFamily,
+    output: &Output,
+    cancel: &Cancel,
+) -> anyhow::Result<Outcome> {
+    match command {
+        Command::ConnectRpc(node) => rpc::connect(node, family, output, cancel).await,
+        Command::ConnectRpcWithWebsocket(node) => {
+            rpc::connect_websocket(node, family, output, cancel).await
+        }
+        Command::ConnectTpu(node) => connect::tpu(node, family, output).await,
+        Command::ConnectGossip(node) => connect::gossip(node, family, output).await,
+        Command::GossipPing(node) => connect::gossip_ping(node, family, output, cancel).await,
+        Command::MtuProbe(node) => connect::mtu_probe(node, family, output).await,
+        Command::CheckHealth(node) => rpc::check_health(node, family, output).await,
+        Command::CheckIdentity(node) => rpc::check_identity(node, family, output).await,
+        Command::ScanCluster(node) => rpc::scan_cluster(node, family, output, cancel).await,
+        #[cfg(feature = "geyser")]
+        Command::ConnectGeyser(node) => connect::geyser(node, family, output).await,
+        Command::ConnectEth(node) => connect::eth(node, family, output).await,
+        Command::ConnectTendermint(node) => connect::tendermint(node, family, output).await,
+        Command::ConnectBitcoin(node) => connect::bitcoin(node, family, output).await,
+        #[cfg(feature = "devp2p")]
+        Command::ConnectDevp2p(node) => connect::devp2p(node, family, output).await,
+        #[cfg(feature = "libp2p")]
+        Command::ConnectLibp2p(node) => connect::libp2p(node, family, output).await,
+        Command::ConnectProfile(node) => connect::profile(node, family, output).await,
+        Command::ProbePorts(node) => connect::probe_ports(node, family, output).await,
+        Command::Bench(node) => rpc::bench(node, family, output, cancel).await,
+        Command::Load(node) => rpc::load(node, family, output).await,
+        Command::Compare(node) => rpc::compare(node, family, output).await,
+        // Without `--watch`, the dashboard refreshes at its default interval.
+        #[cfg(feature = "tui")]
+        Command::Tui(node) => {
+            let refresh = Duration::from_secs(ui::DEFAULT_REFRESH_SECONDS);
+            ui::dashboard(node, family, refresh)
+                .await
+                .map(|_| Outcome::Passed)
+        }
+        // Without `--watch`, the daemon handshakes at its default interval.
+        Command::Daemon(node) => {
+            let interval = Duration::from_secs(daemon::DEFAULT_DAEMON_INTERVAL_SECONDS);
+            daemon::run(node, family, interval, output)
+                .await
+                .map(|_| Outcome::Passed)
+        }
+        // So does the API.
+        Command::ServeHttp(node) => {
+            let interval = Duration::from_secs(daemon::DEFAULT_DAEMON_INTERVAL_SECONDS);
+            api::run(node, family, interval, output)
+                .await
+                .map(|_| Outcome::Passed)
+        }
+        // Print the JSON Schema of the requested document.
+        Command::Schema(args) => {
+            output.print(render_schema(args.document)?)?;
+            Ok(Outcome::Passed)
+        }
+        // Serve handshakes over gRPC until interrupted.
+        #[cfg(feature = "grpc")]
+        Command::ServeGrpc(node) => grpc::run(node, family).await.map(|_| Outcome::Passed),
+        // Write the completion script for the requested shell to stdout.
+        Command::Completions(args) => {
+            clap_complete::generate(
+                args.shell,
+                &mut Cli::command(),
+                "handshake",
+                &mut std::io::stdout(),
+            );
+            Ok(Outcome::Passed)
+        }
+    }
 }
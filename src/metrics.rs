@@ -0,0 +1,235 @@
+// Prometheus metrics for watch mode, served on `/metrics` in the text exposition format.
+// Every handshake report is recorded per target and transport, so Prometheus can scrape node reachability.
+use {
+    crate::{
+        http_server::{self, Request, Response},
+        output::HandshakeReport,
+    },
+    std::{
+        collections::BTreeMap,
+        fmt::Write,
+        sync::{Arc, Mutex},
+    },
+    tokio::net::TcpListener,
+    tracing::info,
+};
+
+// Upper bounds, in seconds, of the handshake duration histogram buckets.
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+// Counters and histogram of the handshakes with one target.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct TargetMetrics {
+    handshakes: u64,                        // Handshakes attempted.
+    failures: u64,                          // Handshakes that failed.
+    buckets: [u64; DURATION_BUCKETS.len()], // Handshakes per duration bucket, cumulative.
+    duration_sum: f64,                      // Total time spent handshaking, in seconds.
+}
+
+// Metrics of every target handshaken so far, keyed by target and transport.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    targets: Mutex<BTreeMap<(String, String), TargetMetrics>>,
+}
+
+impl Metrics {
+    // Constructs an empty set of metrics.
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    // Records the outcome and duration of a handshake.
+    pub fn observe(&self, report: &HandshakeReport) {
+        let mut targets = self.targets.lock().expect("metrics lock poisoned");
+        let metrics = targets
+            .entry((report.target.clone(), report.transport.clone()))
+            .or_default();
+        let seconds = report.duration_ms as f64 / 1000.0;
+
+        metrics.handshakes += 1;
+        if report.error.is_some() {
+            metrics.failures += 1;
+        }
+        for (bucket, bound) in metrics.buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        metrics.duration_sum += seconds;
+    }
+
+    // Renders the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let targets = self.targets.lock().expect("metrics lock poisoned");
+        let mut out = String::new();
+
+        let _ = writeln!(
+            out,
+            "# HELP handshakes_total Handshakes attempted per target."
+        );
+        let _ = writeln!(out, "# TYPE handshakes_total counter");
+        for ((target, transport), metrics) in targets.iter() {
+            let labels = labels(target, transport);
+            let _ = writeln!(out, "handshakes_total{{{}}} {}", labels, metrics.handshakes);
+        }
+
+        let _ = writeln!(out, "# HELP failures_total Failed handshakes per target.");
+        let _ = writeln!(out, "# TYPE failures_total counter");
+        for ((target, transport), metrics) in targets.iter() {
+            let labels = labels(target, transport);
+            let _ = writeln!(out, "failures_total{{{}}} {}", labels, metrics.failures);
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP handshake_duration_seconds Time taken by handshakes per target."
+        );
+        let _ = writeln!(out, "# TYPE handshake_duration_seconds histogram");
+        for ((target, transport), metrics) in targets.iter() {
+            let labels = labels(target, transport);
+            for (count, bound) in metrics.buckets.iter().zip(DURATION_BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "handshake_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "handshake_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, metrics.handshakes
+            );
+            let _ = writeln!(
+                out,
+                "handshake_duration_seconds_sum{{{}}} {}",
+                labels, metrics.duration_sum
+            );
+            let _ = writeln!(
+                out,
+                "handshake_duration_seconds_count{{{}}} {}",
+                labels, metrics.handshakes
+            );
+        }
+        out
+    }
+}
+
+// Formats the target and transport labels, escaping backslashes, quotes and line breaks.
+fn labels(target: &str, transport: &str) -> String {
+    let escape = |value: &str| {
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    };
+    format!(
+        "target=\"{}\",transport=\"{}\"",
+        escape(target),
+        escape(transport)
+    )
+}
+
+// Serves the metrics on `GET /metrics` until the process exits; any other request gets a 404.
+pub async fn serve(listener: TcpListener, metrics: Arc<Metrics>) {
+    if let Ok(address) = listener.local_addr() {
+        info!("Serving metrics on http://{}/metrics", address);
+    }
+    let respond = move |request: Request| {
+        let metrics = metrics.clone();
+        async move { respond(&request, &metrics) }
+    };
+    http_server::serve(listener, "metrics", respond).await
+}
+
+// Answers a single scrape request.
+fn respond(request: &Request, metrics: &Metrics) -> Response {
+    let content_type = "text/plain; version=0.0.4";
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/metrics") => Response::new("200 OK", content_type, metrics.render()),
+        _ => Response::new("404 Not Found", content_type, "Not found\n".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        handshake::{error::HandshakeError, solana::TransportType},
+        std::time::Duration,
+        tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpStream,
+        },
+    };
+
+    // Records a successful and a failed handshake with the same target.
+    fn observed() -> Metrics {
+        let metrics = Metrics::new();
+        let ok = HandshakeReport::new(
+            "127.0.0.1:8899",
+            TransportType::Tcp,
+            Duration::from_millis(40),
+        );
+        let failed = HandshakeReport::new("127.0.0.1:8899", TransportType::Tcp, Duration::ZERO)
+            .with_error::<()>(&Err(HandshakeError::Dns("unresolved".to_string())));
+        metrics.observe(&ok);
+        metrics.observe(&failed);
+        metrics
+    }
+
+    // Counters and histogram series are rendered per target and transport.
+    #[test]
+    fn test_render() {
+        let rendered = observed().render();
+        let labels = "target=\"127.0.0.1:8899\",transport=\"tcp\"";
+
+        assert!(rendered.contains(&format!("handshakes_total{{{}}} 2", labels)));
+        assert!(rendered.contains(&format!("failures_total{{{}}} 1", labels)));
+        assert!(rendered.contains(&format!(
+            "handshake_duration_seconds_bucket{{{},le=\"0.025\"}} 1",
+            labels
+        )));
+        assert!(rendered.contains(&format!(
+            "handshake_duration_seconds_bucket{{{},le=\"0.05\"}} 2",
+            labels
+        )));
+        assert!(rendered.contains(&format!(
+            "handshake_duration_seconds_sum{{{}}} 0.04",
+            labels
+        )));
+        assert!(rendered.contains(&format!("handshake_duration_seconds_count{{{}}} 2", labels)));
+    }
+
+    // Label values are escaped.
+    #[test]
+    fn test_labels_are_escaped() {
+        assert_eq!(
+            labels("a\"b\\c", "tcp"),
+            "target=\"a\\\"b\\\\c\",transport=\"tcp\""
+        );
+    }
+
+    // The metrics are served on /metrics, and other paths are not found.
+    #[tokio::test]
+    async fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Arc::new(observed())));
+
+        let scrape = |path: &'static str| async move {
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+
+        let response = scrape("/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("handshakes_total"));
+        assert!(scrape("/").await.starts_with("HTTP/1.1 404 Not Found"));
+    }
+}
@@ -0,0 +1,264 @@
+// MQTT publishing for watch and daemon modes: every handshake report is published as JSON to a topic on
+// an MQTT broker, for fleets whose telemetry already flows through one. A background task keeps the
+// connection up, reconnecting with backoff whenever the broker goes away; reports published at QoS 1 or 2
+// meanwhile are queued and sent once it is back, while those at QoS 0 may be lost.
+use {
+    crate::output::HandshakeReport,
+    rumqttc::{AsyncClient, ConnectionError, Event, EventLoop, MqttOptions, Packet, QoS},
+    std::{fmt, str::FromStr, time::Duration},
+    tracing::{info, warn},
+};
+
+// Default topic every report is published to.
+pub const DEFAULT_MQTT_TOPIC: &str = "handshake/{target}";
+
+// Default quality of service of the reports published.
+pub const DEFAULT_MQTT_QOS: u8 = 1;
+
+// Port of a broker whose URL names none.
+const DEFAULT_MQTT_PORT: u16 = 1883;
+
+// Interval of the pings keeping the connection to the broker alive.
+const MQTT_KEEP_ALIVE_SECONDS: u64 = 30;
+
+// Longest wait between two attempts to reconnect to the broker; the wait doubles from a second up to it.
+const MAX_RECONNECT_DELAY_SECONDS: u64 = 30;
+
+// Reports queued for the broker before further ones are dropped, e.g. while it is unreachable.
+const QUEUE_CAPACITY: usize = 1024;
+
+// An MQTT broker, parsed from a URL such as `tcp://broker.internal:1883`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttBroker {
+    pub host: String, // Name or address of the broker, without brackets when IPv6.
+    pub port: u16,    // Port the broker listens on, 1883 unless given.
+}
+
+impl FromStr for MqttBroker {
+    type Err = String;
+
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        let rest = match url.split_once("://") {
+            Some(("tcp" | "mqtt", rest)) => rest,
+            Some((scheme, _)) => return Err(format!("Unsupported MQTT scheme '{}'", scheme)),
+            None => {
+                return Err(format!(
+                    "MQTT URL '{}' must start with tcp:// or mqtt://",
+                    url
+                ))
+            }
+        };
+        let rest = rest.strip_suffix('/').unwrap_or(rest);
+        if rest.is_empty() || rest.contains('/') {
+            return Err(format!("Invalid MQTT URL '{}'", url));
+        }
+
+        // The port follows the last colon, unless that colon is part of a bracketed IPv6 address.
+        let (host, port) = match rest.rsplit_once(':') {
+            Some((host, port)) if !port.ends_with(']') => {
+                let port = port
+                    .parse::<u16>()
+                    .map_err(|_| format!("Invalid MQTT port in '{}'", url))?;
+                (host, port)
+            }
+            _ => (rest, DEFAULT_MQTT_PORT),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return Err(format!("Invalid MQTT URL '{}'", url));
+        }
+        Ok(MqttBroker {
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+impl fmt::Display for MqttBroker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.host.contains(':') {
+            true => write!(f, "tcp://[{}]:{}", self.host, self.port),
+            false => write!(f, "tcp://{}:{}", self.host, self.port),
+        }
+    }
+}
+
+// Publishes every handshake report to a broker.
+pub struct Publisher {
+    client: AsyncClient, // Queue of the messages sent by the background connection.
+    topic: String, // Topic template, with `{target}`, `{label}` and `{transport}` placeholders.
+    qos: QoS,      // Quality of service of the reports published.
+}
+
+impl Publisher {
+    // Connects to the broker in the background, publishing the reports to the topic at the QoS, 0 to 2.
+    pub fn new(broker: &MqttBroker, topic: String, qos: u8) -> Self {
+        let client_id = format!("handshake-{}", std::process::id());
+        let mut options = MqttOptions::new(client_id, broker.host.clone(), broker.port);
+        options.set_keep_alive(Duration::from_secs(MQTT_KEEP_ALIVE_SECONDS));
+        let (client, eventloop) = AsyncClient::new(options, QUEUE_CAPACITY);
+        tokio::spawn(connect(eventloop, broker.clone()));
+        let qos = match qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            _ => QoS::ExactlyOnce,
+        };
+        Publisher { client, topic, qos }
+    }
+
+    // The topic a report is published to. A report without a label has its target in place of one.
+    pub fn topic(&self, report: &HandshakeReport) -> String {
+        self.topic
+            .replace("{target}", &report.target)
+            .replace("{label}", report.label.as_deref().unwrap_or(&report.target))
+            .replace("{transport}", &report.transport)
+    }
+
+    // Queues a report for the broker, dropping it when the queue is full.
+    pub fn publish(&self, report: &HandshakeReport) {
+        let payload = match serde_json::to_vec(report) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Failed to serialize the report of {}: {}", report.target, e);
+                return;
+            }
+        };
+        if let Err(e) = self
+            .client
+            .try_publish(self.topic(report), self.qos, false, payload)
+        {
+            warn!("Dropped the report of {} for MQTT: {}", report.target, e);
+        }
+    }
+}
+
+// Keeps the connection to the broker up, sending the queued reports, until the publisher is dropped.
+async fn connect(mut eventloop: EventLoop, broker: MqttBroker) {
+    let mut delay = Duration::from_secs(1);
+    loop {
+        match eventloop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                info!("Connected to MQTT broker {}", broker);
+                delay = Duration::from_secs(1);
+            }
+            Ok(_) => {}
+            Err(ConnectionError::RequestsDone) => return,
+            Err(e) => {
+                warn!(
+                    "Lost MQTT broker {}, reconnecting in {}s: {}",
+                    broker,
+                    delay.as_secs(),
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(Duration::from_secs(MAX_RECONNECT_DELAY_SECONDS));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        handshake::{error::HandshakeError, solana::TransportType},
+        test_case::test_case,
+        tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        },
+    };
+
+    // A failed handshake with a target.
+    fn report(target: &str) -> HandshakeReport {
+        HandshakeReport::new(target, TransportType::Tls, Duration::from_millis(5))
+            .with_version(&Err(HandshakeError::Protocol("bad".to_string())))
+    }
+
+    // Brokers are parsed from tcp:// and mqtt:// URLs, on port 1883 unless given.
+    #[test_case("tcp://broker:1883", Ok(("broker", 1883)); "when port is given")]
+    #[test_case("mqtt://broker.internal", Ok(("broker.internal", 1883)); "when port is omitted")]
+    #[test_case("tcp://[::1]:1884/", Ok(("::1", 1884)); "when host is ipv6")]
+    #[test_case("tcp://[::1]", Ok(("::1", 1883)); "when ipv6 host has no port")]
+    #[test_case("ssl://broker:8883", Err("Unsupported MQTT scheme 'ssl'"); "when scheme is unsupported")]
+    #[test_case("broker:1883", Err("MQTT URL 'broker:1883' must start with tcp:// or mqtt://"); "when scheme is missing")]
+    #[test_case("tcp://broker:port", Err("Invalid MQTT port in 'tcp://broker:port'"); "when port is invalid")]
+    #[test_case("tcp://broker/topic", Err("Invalid MQTT URL 'tcp://broker/topic'"); "when url has a path")]
+    fn test_broker(url: &str, expected: Result<(&str, u16), &str>) {
+        let expected = expected
+            .map(|(host, port)| MqttBroker {
+                host: host.to_string(),
+                port,
+            })
+            .map_err(str::to_string);
+        assert_eq!(url.parse::<MqttBroker>(), expected);
+    }
+
+    // Brokers are displayed as the URL they were parsed from.
+    #[test]
+    fn test_broker_display() {
+        let broker: MqttBroker = "mqtt://[::1]".parse().unwrap();
+        assert_eq!(broker.to_string(), "tcp://[::1]:1883");
+    }
+
+    // The placeholders of the topic are replaced by the report's.
+    #[test_case("handshake/{target}", None, "handshake/127.0.0.1:8899"; "when topic names the target")]
+    #[test_case("fleet/{label}/{transport}", Some("validator-1"), "fleet/validator-1/tls"; "when topic names the label")]
+    #[test_case("fleet/{label}", None, "fleet/127.0.0.1:8899"; "when report has no label")]
+    #[tokio::test]
+    async fn test_topic(template: &str, label: Option<&str>, expected: &str) {
+        let broker: MqttBroker = "tcp://127.0.0.1:9".parse().unwrap();
+        let publisher = Publisher::new(&broker, template.to_string(), 0);
+        let mut report = report("127.0.0.1:8899");
+        report.label = label.map(str::to_string);
+        assert_eq!(publisher.topic(&report), expected);
+    }
+
+    // Accepts a client on the listener and returns what it sends once it is connected, up to the report.
+    async fn receive(listener: &TcpListener) -> Vec<u8> {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buf = vec![0; 4096];
+        let n = socket.read(&mut buf).await.unwrap();
+        assert_eq!(buf[0] >> 4, 1, "Expected CONNECT, got {:?}", &buf[..n]);
+        socket.write_all(&[0x20, 2, 0, 0]).await.unwrap();
+
+        let mut received = Vec::new();
+        while !received.ends_with(b"}") {
+            let n = socket.read(&mut buf).await.unwrap();
+            assert!(n > 0, "The client disconnected");
+            received.extend_from_slice(&buf[..n]);
+        }
+        received
+    }
+
+    // Reports published before the broker accepts the connection are sent once it does.
+    #[tokio::test]
+    async fn test_publish_after_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let broker: MqttBroker = format!("tcp://{}", address).parse().unwrap();
+        let publisher = Publisher::new(&broker, DEFAULT_MQTT_TOPIC.to_string(), 1);
+        publisher.publish(&report("127.0.0.1:8899"));
+
+        // The first connection is dropped before it is acknowledged.
+        drop(listener.accept().await.unwrap());
+        let received = tokio::time::timeout(Duration::from_secs(10), receive(&listener))
+            .await
+            .expect("The client did not reconnect");
+
+        // A PUBLISH at QoS 1.
+        let publish = received.iter().position(|b| b >> 4 == 3).unwrap();
+        assert_eq!(received[publish] & 0x06, 0x02);
+        let received = String::from_utf8_lossy(&received);
+        assert!(
+            received.contains("handshake/127.0.0.1:8899"),
+            "{}",
+            received
+        );
+        assert!(
+            received.contains(r#""error_class":"protocol""#),
+            "{}",
+            received
+        );
+    }
+}
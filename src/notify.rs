@@ -0,0 +1,573 @@
+// Webhook notifications for watch and daemon modes: every handshake report updates the state of its
+// target, and once a target has been healthy or failing for the debounce number of handshakes in a row,
+// a change of state is posted to every webhook alerted for the target, laid out as plain JSON or as a
+// Slack or Discord message. The webhooks are given with `--webhook` or listed in an alerts file:
+//
+// ```toml
+// [[alert]]
+// url = "https://hooks.slack.com/services/T0/B0/x"
+//
+// [[alert]]
+// url = "https://discord.com/api/webhooks/1/x"
+// targets = ["rpc-2.example.com:443"]
+// ```
+use {
+    crate::output::HandshakeReport,
+    handshake::{
+        error::HandshakeError,
+        solana::transport::{tcp::Tcp, Transport},
+    },
+    schemars::JsonSchema,
+    serde::{Deserialize, Serialize},
+    serde_json::json,
+    std::{collections::HashMap, fmt, fs, path::Path, str::FromStr, sync::Mutex},
+    tracing::{info, warn},
+};
+
+// Default number of handshakes in a row a new state must hold before it is notified.
+pub const DEFAULT_WEBHOOK_DEBOUNCE: u32 = 2;
+
+// Timeout, in seconds, for each phase of posting a notification.
+const WEBHOOK_TIMEOUT_SECONDS: u32 = 10;
+
+// An HTTP(S) endpoint notifications are posted to.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct Webhook {
+    pub remote: String, // Host and port of the endpoint.
+    pub secure: bool,   // Whether the endpoint is reached over TLS.
+    pub path: String,   // Path and query notifications are posted to.
+}
+
+impl FromStr for Webhook {
+    type Err = String;
+
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        let (secure, rest) = match url.split_once("://") {
+            Some(("https", rest)) => (true, rest),
+            Some(("http", rest)) => (false, rest),
+            Some((scheme, _)) => return Err(format!("Unsupported webhook scheme '{}'", scheme)),
+            None => {
+                return Err(format!(
+                    "Webhook URL '{}' must start with http:// or https://",
+                    url
+                ))
+            }
+        };
+        let (host, path) = match rest.find(['/', '?']) {
+            Some(index) if rest[index..].starts_with('?') => {
+                (&rest[..index], format!("/{}", &rest[index..]))
+            }
+            Some(index) => (&rest[..index], rest[index..].to_string()),
+            None => (rest, "/".to_string()),
+        };
+        if host.is_empty() {
+            return Err(format!("Invalid webhook URL '{}'", url));
+        }
+
+        // The port follows the last colon, unless that colon is part of a bracketed IPv6 address.
+        let remote = match host.rsplit_once(':') {
+            Some((_, port)) if !port.ends_with(']') => {
+                port.parse::<u16>()
+                    .map_err(|_| format!("Invalid webhook port in '{}'", url))?;
+                host.to_string()
+            }
+            _ => format!("{}:{}", host, if secure { 443 } else { 80 }),
+        };
+        Ok(Webhook {
+            remote,
+            secure,
+            path,
+        })
+    }
+}
+
+// Parses webhooks of alerts files as they are parsed on the command line.
+impl TryFrom<String> for Webhook {
+    type Error = String;
+
+    fn try_from(url: String) -> Result<Self, Self::Error> {
+        url.parse()
+    }
+}
+
+// Implement the Display trait for Webhook, leaving out the path, which often carries a secret token.
+impl fmt::Display for Webhook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let scheme = if self.secure { "https" } else { "http" };
+        write!(f, "{}://{}", scheme, self.remote)
+    }
+}
+
+impl Webhook {
+    // Posts the payload, failing unless the endpoint answers with a 2xx status.
+    pub async fn post(&self, payload: serde_json::Value) -> Result<(), HandshakeError> {
+        let response = Tcp::new(self.remote.clone(), self.secure)
+            .with_path(self.path.clone())
+            .send_request(Some(WEBHOOK_TIMEOUT_SECONDS), payload)
+            .await?;
+        match response.is_success() {
+            true => Ok(()),
+            false => Err(HandshakeError::Protocol(format!(
+                "HTTP {} {}: {}",
+                response.status,
+                response.reason,
+                response.body_text()
+            ))),
+        }
+    }
+}
+
+// How a change of state is laid out for the service receiving it.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Json,    // The change as is, for services of one's own.
+    Slack,   // A Slack incoming webhook message.
+    Discord, // A Discord webhook message.
+}
+
+impl Format {
+    // The layout the webhook's service expects, told by its host; anything else gets plain JSON.
+    pub fn detect(webhook: &Webhook) -> Format {
+        let host = webhook.remote.rsplit_once(':').map_or("", |(host, _)| host);
+        match host {
+            "hooks.slack.com" => Format::Slack,
+            "discord.com" | "discordapp.com" => Format::Discord,
+            _ => Format::Json,
+        }
+    }
+}
+
+// A webhook alerted of the changes of state of some, or every, target.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Alert {
+    pub url: Webhook, // Endpoint the changes are posted to.
+    #[serde(default)]
+    pub format: Option<Format>, // Layout of the changes, told by the webhook's host when not given.
+    #[serde(default)]
+    pub targets: Vec<String>, // Targets alerted of, by address or label; every target when empty.
+}
+
+impl Alert {
+    // Constructs an alert of every target's changes, laid out for the webhook's service.
+    pub fn new(url: Webhook) -> Self {
+        Alert {
+            url,
+            format: None,
+            targets: Vec::new(),
+        }
+    }
+
+    // Layout of the changes posted.
+    pub fn format(&self) -> Format {
+        self.format.unwrap_or_else(|| Format::detect(&self.url))
+    }
+
+    // Indicates whether changes of the reported target are posted to the webhook.
+    pub fn applies_to(&self, report: &HandshakeReport) -> bool {
+        self.targets.is_empty()
+            || self
+                .targets
+                .iter()
+                .any(|target| *target == report.target || Some(target) == report.label.as_ref())
+    }
+}
+
+// Monitoring config listing the webhooks alerted of changes of state.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AlertsConfig {
+    #[serde(default, rename = "alert", alias = "alerts")]
+    pub alerts: Vec<Alert>, // The alerts, each posted independently of the others.
+}
+
+impl AlertsConfig {
+    // Loads an alerts file, read as JSON when its extension says so and as TOML otherwise.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        let config = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json(&text),
+            _ => Self::from_toml(&text),
+        };
+        config.map_err(|e| format!("Invalid alerts file {}: {}", path.display(), e))
+    }
+
+    // Parses a TOML alerts file, with an `[[alert]]` table per alert.
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    // Parses a JSON alerts file, with an `alerts` array.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    }
+}
+
+// Whether a target's handshakes succeed.
+#[derive(Serialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    Healthy, // The handshakes succeed.
+    Failing, // The handshakes fail.
+}
+
+// Implement the Display trait for State, using the lowercase names of the JSON payload.
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            State::Healthy => write!(f, "healthy"),
+            State::Failing => write!(f, "failing"),
+        }
+    }
+}
+
+// Payload posted when a target changes state.
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct StateChange {
+    pub target: String,                // The address handshaken.
+    pub transport: String,             // The transport used, e.g. "tcp" or "quic".
+    pub state: State,                  // The state the target is now in.
+    pub previous_state: Option<State>, // The state notified before, if any.
+    pub error_class: Option<String>,   // The class of the failure, e.g. "timeout", when failing.
+    pub error: Option<String>,         // Why the last handshake failed, when failing.
+    pub report: HandshakeReport,       // The handshake that confirmed the change, with its timings.
+}
+
+impl StateChange {
+    // One line telling what changed, e.g. "127.0.0.1:8899 (tcp) is failing".
+    pub fn summary(&self) -> String {
+        let target = match &self.report.label {
+            Some(label) => format!("{} ({}, {})", self.target, label, self.transport),
+            None => format!("{} ({})", self.target, self.transport),
+        };
+        match self.previous_state {
+            Some(previous) => format!("{} is {}, was {}", target, self.state, previous),
+            None => format!("{} is {}", target, self.state),
+        }
+    }
+
+    // The details shown in chat messages, leaving out those the handshake did not report.
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let report = &self.report;
+        [
+            ("Target", Some(self.target.clone())),
+            ("Version", report.version.clone()),
+            ("Error", report.error.clone()),
+            ("Duration", Some(format!("{} ms", report.duration_ms))),
+            ("Timestamp", Some(report.timestamp.clone())),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect()
+    }
+
+    // The document posted to a webhook expecting the given layout.
+    pub fn payload(&self, format: Format) -> serde_json::Value {
+        // Green when healthy, red when failing, as both services colour their messages.
+        let (color, emoji) = match self.state {
+            State::Healthy => (0x2eb67d, ":large_green_circle:"),
+            State::Failing => (0xe01e5a, ":red_circle:"),
+        };
+        match format {
+            Format::Json => serde_json::to_value(self).expect("state changes serialize to JSON"),
+            Format::Slack => {
+                let fields: Vec<serde_json::Value> = self
+                    .fields()
+                    .into_iter()
+                    .map(|(title, value)| json!({ "title": title, "value": value, "short": title != "Error" }))
+                    .collect();
+                json!({
+                    "text": format!("{} {}", emoji, self.summary()),
+                    "attachments": [{ "color": format!("#{:06x}", color), "fields": fields }],
+                })
+            }
+            Format::Discord => {
+                let fields: Vec<serde_json::Value> = self
+                    .fields()
+                    .into_iter()
+                    .map(|(name, value)| json!({ "name": name, "value": value, "inline": name != "Error" }))
+                    .collect();
+                json!({
+                    "embeds": [{
+                        "title": self.summary(),
+                        "color": color,
+                        "fields": fields,
+                        "timestamp": self.report.timestamp,
+                    }],
+                })
+            }
+        }
+    }
+}
+
+// State of a target as seen by the notifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TargetState {
+    notified: Option<State>, // The last state notified, or confirmed silently for a healthy start.
+    current: State,          // The state of the last handshake.
+    streak: u32,             // Handshakes in a row in the current state.
+}
+
+// Tracks the state of every target reported and posts its confirmed changes to the webhooks alerted.
+pub struct Notifier {
+    pub alerts: Vec<Alert>, // Webhooks changes are posted to, with the targets they are for.
+    pub debounce: u32,      // Handshakes in a row a new state must hold before it is notified.
+    states: Mutex<HashMap<(String, String), TargetState>>, // State of each target, by target and transport.
+}
+
+impl Notifier {
+    // Constructs a notifier posting to the alerts' webhooks once a new state held for `debounce` handshakes.
+    pub fn new(alerts: Vec<Alert>, debounce: u32) -> Self {
+        Notifier {
+            alerts,
+            debounce: debounce.max(1),
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Records the outcome of a handshake and returns the change of state it confirms, if any.
+    // Targets that start healthy are not notified; those that start failing are.
+    pub fn observe(&self, report: &HandshakeReport) -> Option<StateChange> {
+        let state = match report.error {
+            Some(_) => State::Failing,
+            None => State::Healthy,
+        };
+        let mut states = self.states.lock().expect("notifier lock poisoned");
+        let target = states
+            .entry((report.target.clone(), report.transport.clone()))
+            .or_insert(TargetState {
+                notified: None,
+                current: state,
+                streak: 0,
+            });
+        target.streak = match target.current == state {
+            true => target.streak + 1,
+            false => 1,
+        };
+        target.current = state;
+        if target.streak < self.debounce || target.notified == Some(state) {
+            return None;
+        }
+
+        let previous_state = target.notified.replace(state);
+        if previous_state.is_none() && state == State::Healthy {
+            return None;
+        }
+        Some(StateChange {
+            target: report.target.clone(),
+            transport: report.transport.clone(),
+            state,
+            previous_state,
+            error_class: report.error_class.clone(),
+            error: report.error.clone(),
+            report: report.clone(),
+        })
+    }
+
+    // Records the outcome of a handshake, posting the change of state it confirms in the background
+    // to every webhook alerted for the target.
+    pub fn notify(&self, report: &HandshakeReport) {
+        let Some(change) = self.observe(report) else {
+            return;
+        };
+        for alert in self.alerts.iter().filter(|alert| alert.applies_to(report)) {
+            info!("{}, notifying {}", change.summary(), alert.url);
+            let payload = change.payload(alert.format());
+            let webhook = alert.url.clone();
+            tokio::spawn(async move {
+                if let Err(e) = webhook.post(payload).await {
+                    warn!("Failed to notify {}: {}", webhook, e);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        handshake::solana::TransportType,
+        std::time::Duration,
+        test_case::test_case,
+        tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        },
+    };
+
+    // Webhook URLs are split into the remote, with the scheme's default port, and the path.
+    #[test_case("https://hooks.slack.com/services/T0/B0/x", "hooks.slack.com:443", true, "/services/T0/B0/x"; "when secure with a path")]
+    #[test_case("http://127.0.0.1:8080", "127.0.0.1:8080", false, "/"; "when plain with a port")]
+    #[test_case("http://[::1]?token=x", "[::1]:80", false, "/?token=x"; "when ipv6 with a query")]
+    fn test_parse_webhook(url: &str, remote: &str, secure: bool, path: &str) {
+        let webhook: Webhook = url.parse().unwrap();
+        assert_eq!(
+            webhook,
+            Webhook {
+                remote: remote.to_string(),
+                secure,
+                path: path.to_string(),
+            }
+        );
+    }
+
+    #[test_case("hooks.slack.com/services"; "when the scheme is missing")]
+    #[test_case("ftp://example.com"; "when the scheme is unsupported")]
+    #[test_case("https:///path"; "when the host is missing")]
+    #[test_case("http://example.com:99999/"; "when the port is invalid")]
+    // Tests parsing of invalid webhook URLs, expecting failure.
+    fn test_parse_webhook_failures(url: &str) {
+        assert!(url.parse::<Webhook>().is_err());
+    }
+
+    // Alerts files list webhooks with their layout, told by the host unless given, and their targets.
+    #[test]
+    fn test_load_alerts() {
+        let path =
+            std::env::temp_dir().join(format!("handshake-alerts-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+[[alert]]
+url = "https://hooks.slack.com/services/T0/B0/x"
+
+[[alert]]
+url = "https://alerts.example.com/discord"
+format = "discord"
+targets = ["rpc-2.example.com:443", "mainnet-2"]
+"#,
+        )
+        .unwrap();
+        let config = AlertsConfig::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let [slack, discord] = &config.alerts[..] else {
+            panic!("Expected two alerts, got {:?}", config.alerts);
+        };
+        assert_eq!(slack.format(), Format::Slack);
+        assert_eq!(discord.format(), Format::Discord);
+
+        let report = report(true);
+        assert!(slack.applies_to(&report));
+        assert!(!discord.applies_to(&report));
+        assert!(discord.applies_to(&report.clone().with_label(Some("mainnet-2".to_string()))));
+        assert!(AlertsConfig::from_toml("[[alert]]\nurl = \"hooks.slack.com\"").is_err());
+    }
+
+    // A report of a handshake with the target, failed or not.
+    fn report(failed: bool) -> HandshakeReport {
+        let report = HandshakeReport::new("127.0.0.1:8899", TransportType::Tcp, Duration::ZERO);
+        match failed {
+            true => report.with_error::<()>(&Err(HandshakeError::Timeout {
+                phase: "read".to_string(),
+                seconds: 5,
+            })),
+            false => report,
+        }
+    }
+
+    // A notifier alerting a webhook nothing listens on, with the debounce.
+    fn notifier(debounce: u32) -> Notifier {
+        let alert = Alert::new("http://127.0.0.1:9".parse().unwrap());
+        Notifier::new(vec![alert], debounce)
+    }
+
+    // A change is notified once it held for the debounce, and a flapping target is not notified.
+    #[test]
+    fn test_observe() {
+        let notifier = notifier(2);
+        let states: Vec<Option<(State, Option<State>)>> =
+            [false, false, true, false, true, true, true, false, false]
+                .into_iter()
+                .map(|failed| {
+                    notifier
+                        .observe(&report(failed))
+                        .map(|change| (change.state, change.previous_state))
+                })
+                .collect();
+        assert_eq!(
+            states,
+            vec![
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some((State::Failing, Some(State::Healthy))),
+                None,
+                None,
+                Some((State::Healthy, Some(State::Failing))),
+            ]
+        );
+    }
+
+    // A target failing from the start is notified, with the class of its failure.
+    #[test]
+    fn test_observe_failing_start() {
+        let notifier = notifier(1);
+        let change = notifier.observe(&report(true)).unwrap();
+        assert_eq!(
+            (change.state, change.previous_state),
+            (State::Failing, None)
+        );
+        assert_eq!(change.error_class.as_deref(), Some("timeout"));
+    }
+
+    // Chat messages carry the summary and the handshake's details, with a colour telling the state.
+    #[test]
+    fn test_payload() {
+        let change = notifier(1).observe(&report(true)).unwrap();
+        assert_eq!(change.summary(), "127.0.0.1:8899 (tcp) is failing");
+
+        let slack = change.payload(Format::Slack);
+        assert_eq!(
+            slack["text"],
+            ":red_circle: 127.0.0.1:8899 (tcp) is failing"
+        );
+        assert_eq!(slack["attachments"][0]["color"], "#e01e5a");
+        let titles: Vec<&str> = slack["attachments"][0]["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| field["title"].as_str().unwrap())
+            .collect();
+        assert_eq!(titles, ["Target", "Error", "Duration", "Timestamp"]);
+
+        let discord = change.payload(Format::Discord);
+        assert_eq!(discord["embeds"][0]["color"], 0xe01e5a);
+        assert_eq!(discord["embeds"][0]["fields"][1]["name"], "Error");
+        assert_eq!(discord["embeds"][0]["timestamp"], change.report.timestamp);
+        assert_eq!(change.payload(Format::Json)["state"], "failing");
+    }
+
+    // Changes are posted as JSON to the webhook's path.
+    #[tokio::test]
+    async fn test_notify() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let received = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+            let response = "HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n";
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+
+        let webhook = format!("http://{}/hooks/handshake", address)
+            .parse()
+            .unwrap();
+        Notifier::new(vec![Alert::new(webhook)], 1).notify(&report(true));
+        let request = received.await.unwrap();
+        assert!(request.starts_with("POST /hooks/handshake HTTP/1.1\r\n"));
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        let change: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(change["state"], "failing");
+        assert_eq!(change["error_class"], "timeout");
+        assert_eq!(change["report"]["target"], "127.0.0.1:8899");
+    }
+}
@@ -0,0 +1,1768 @@
+// Output formats for the CLI, the structured report of each handshake and the renderers shared by all subcommands.
+// Logs always go to stderr, so whatever is printed here on stdout can be consumed by scripts.
+#[cfg(feature = "kafka")]
+use crate::kafka::Producer;
+#[cfg(feature = "mqtt")]
+use crate::mqtt::Publisher;
+use {
+    crate::{
+        daemon::StatusReport,
+        geoip::{AsnDb, GeoIp, Location, Network},
+        metrics::Metrics,
+        notify::{Notifier, StateChange},
+    },
+    anyhow::Context,
+    clap::ValueEnum,
+    handshake::solana::{
+        bench::{self, BenchResult, LatencyStats},
+        load::LoadInterval,
+        probe::ProbeResult,
+        scan::{self, FeatureSetDivergence, ScanResult},
+    },
+    schemars::{schema::RootSchema, schema_for, JsonSchema},
+    serde::Serialize,
+    std::{
+        collections::HashMap,
+        fmt::Write,
+        fs::{self, File, OpenOptions},
+        io::Write as _,
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        time::{Duration, SystemTime},
+    },
+};
+
+// The report of a handshake comes from the library, which the bindings share.
+pub use handshake::report::HandshakeReport;
+
+// Selects how results are printed on stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,   // Human-readable logs, with a table and version summary for multi-target runs.
+    Json,   // One JSON document per run.
+    Ndjson, // One compact JSON object per line and per handshake, streamed as the handshakes finish.
+    Csv,    // A header row followed by one row per target, for spreadsheets.
+    Table,  // An aligned table with one row per target.
+    Junit,  // A JUnit XML report with one test case per target, for CI pipelines.
+    Gha,    // GitHub Actions annotations, an error or a notice per target.
+}
+
+// Documents printed or posted as JSON, whose JSON Schema the `schema` subcommand prints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Document {
+    Report,       // A handshake, printed alone or, by multi-target runs, in an array.
+    Bench,        // The report of a benchmark.
+    Load,         // The statistics of an interval of a load test.
+    Comparison,   // The comparison of the handshakes with two nodes.
+    Status,       // The status of the targets the daemon serves on `/status`.
+    Notification, // A change of state posted to webhooks in the JSON format.
+}
+
+impl Document {
+    // The JSON Schema of the document, generated from the type it is serialized from.
+    pub fn schema(self) -> RootSchema {
+        match self {
+            Document::Report => schema_for!(HandshakeReport),
+            Document::Bench => schema_for!(BenchReport),
+            Document::Load => schema_for!(LoadReport),
+            Document::Comparison => schema_for!(ComparisonReport),
+            Document::Status => schema_for!(StatusReport),
+            Document::Notification => schema_for!(StateChange),
+        }
+    }
+}
+
+// Columns of the CSV and table renderings, in order.
+const COLUMNS: [&str; 17] = [
+    "timestamp",
+    "target",
+    "label",
+    "transport",
+    "duration_ms",
+    "resolve_ms",
+    "connect_ms",
+    "tls_ms",
+    "write_ms",
+    "read_ms",
+    "version",
+    "feature_set",
+    "country",
+    "city",
+    "asn",
+    "organization",
+    "error",
+];
+
+// Whether the CSV header was already printed, so repeated runs in watch mode produce a single CSV document.
+static CSV_HEADER_PRINTED: AtomicBool = AtomicBool::new(false);
+
+// What the binary adds to the library's report: where the node is, and its fields as the text of a row.
+trait ReportExt {
+    // Attaches where the node is, if known.
+    fn with_location(self, location: Option<Location>) -> Self;
+
+    // Attaches the network the node is in, if known.
+    fn with_network(self, network: Option<Network>) -> Self;
+
+    // The report's fields as text, in the order of `COLUMNS`.
+    fn fields(&self) -> [String; COLUMNS.len()];
+}
+
+impl ReportExt for HandshakeReport {
+    fn with_location(mut self, location: Option<Location>) -> Self {
+        let location = location.unwrap_or_default();
+        self.country = location.country;
+        self.city = location.city;
+        self
+    }
+
+    fn with_network(mut self, network: Option<Network>) -> Self {
+        let network = network.unwrap_or_default();
+        self.asn = network.asn;
+        self.organization = network.organization;
+        self
+    }
+
+    fn fields(&self) -> [String; COLUMNS.len()] {
+        let millis = |phase: Option<u64>| phase.map(|ms| ms.to_string()).unwrap_or_default();
+        [
+            self.timestamp.clone(),
+            self.target.clone(),
+            self.label.clone().unwrap_or_default(),
+            self.transport.clone(),
+            self.duration_ms.to_string(),
+            millis(self.resolve_ms),
+            millis(self.connect_ms),
+            millis(self.tls_ms),
+            millis(self.write_ms),
+            millis(self.read_ms),
+            self.version.clone().unwrap_or_default(),
+            self.feature_set.map(|f| f.to_string()).unwrap_or_default(),
+            self.country.clone().unwrap_or_default(),
+            self.city.clone().unwrap_or_default(),
+            self.asn.map(|asn| asn.to_string()).unwrap_or_default(),
+            self.organization.clone().unwrap_or_default(),
+            self.error.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+// Columns of the CSV and table renderings of a benchmark's latency, in order.
+const LATENCY_COLUMNS: [&str; 8] = [
+    "phase", "samples", "min_ms", "mean_ms", "p50_ms", "p95_ms", "p99_ms", "max_ms",
+];
+
+// Latency of the handshakes of a benchmark, or of one of their phases, in milliseconds.
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct LatencyReport {
+    pub phase: String,  // "total" for whole handshakes, or the name of the phase.
+    pub samples: usize, // Number of handshakes summarized.
+    pub min_ms: f64,    // Shortest.
+    pub mean_ms: f64,   // Arithmetic mean.
+    pub p50_ms: f64,    // Median.
+    pub p95_ms: f64,    // 95th percentile.
+    pub p99_ms: f64,    // 99th percentile.
+    pub max_ms: f64,    // Longest.
+}
+
+impl LatencyReport {
+    // Constructs the report of the latency statistics of a phase.
+    pub fn new(phase: &str, stats: &LatencyStats) -> Self {
+        // Milliseconds are kept to the microsecond, as handshakes with nearby nodes take very few.
+        let millis = |duration: Duration| duration.as_micros() as f64 / 1000.0;
+        LatencyReport {
+            phase: phase.to_string(),
+            samples: stats.samples,
+            min_ms: millis(stats.min),
+            mean_ms: millis(stats.mean),
+            p50_ms: millis(stats.p50),
+            p95_ms: millis(stats.p95),
+            p99_ms: millis(stats.p99),
+            max_ms: millis(stats.max),
+        }
+    }
+
+    // The report's fields as text, in the order of `LATENCY_COLUMNS`.
+    fn fields(&self) -> [String; LATENCY_COLUMNS.len()] {
+        let millis = |ms: f64| format!("{:.3}", ms);
+        [
+            self.phase.clone(),
+            self.samples.to_string(),
+            millis(self.min_ms),
+            millis(self.mean_ms),
+            millis(self.p50_ms),
+            millis(self.p95_ms),
+            millis(self.p99_ms),
+            millis(self.max_ms),
+        ]
+    }
+}
+
+// Structured result of a benchmark of a node.
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct BenchReport {
+    pub timestamp: String, // When the benchmark finished, in RFC 3339 format.
+    pub target: String,    // The address that was handshaken.
+    pub transport: String, // The transport used, e.g. "tcp" or "tls".
+    pub count: usize,      // Number of handshakes made.
+    pub failures: usize,   // Number of handshakes that failed.
+    pub latency: Vec<LatencyReport>, // Latency of the successful handshakes, then of each phase they went through.
+}
+
+impl BenchReport {
+    // Builds the report of a benchmark.
+    pub fn new(target: impl ToString, transport: impl ToString, result: &BenchResult) -> Self {
+        let total = result
+            .latency()
+            .map(|stats| LatencyReport::new("total", &stats));
+        let phases = bench::PHASES.iter().filter_map(|(name, phase)| {
+            let stats = result.phase_latency(*phase)?;
+            Some(LatencyReport::new(name, &stats))
+        });
+        BenchReport {
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            target: target.to_string(),
+            transport: transport.to_string(),
+            count: result.count(),
+            failures: result.failures.len(),
+            latency: total.into_iter().chain(phases).collect(),
+        }
+    }
+}
+
+// Columns of the CSV rendering of a load test's intervals, in order.
+const LOAD_COLUMNS: [&str; 11] = [
+    "timestamp",
+    "elapsed_s",
+    "started",
+    "finished",
+    "failures",
+    "in_flight",
+    "throughput",
+    "p50_ms",
+    "p95_ms",
+    "p99_ms",
+    "max_ms",
+];
+
+// Structured statistics of an interval of a load test.
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct LoadReport {
+    pub timestamp: String, // When the interval ended, in RFC 3339 format.
+    pub target: String,    // The address that was handshaken.
+    pub transport: String, // The transport used, e.g. "tcp" or "tls".
+    pub elapsed_s: f64,    // Time from the start of the test to the end of the interval.
+    pub started: usize,    // Handshakes started during the interval.
+    pub finished: usize,   // Handshakes finished during the interval, successful or not.
+    pub failures: usize,   // Handshakes that failed during the interval.
+    pub in_flight: usize,  // Handshakes still running at the end of the interval.
+    pub throughput: f64,   // Handshakes finished per second during the interval.
+    pub latency: Option<LatencyReport>, // Latency of the handshakes that succeeded during the interval, if any did.
+}
+
+impl LoadReport {
+    // Builds the report of an interval of a load test.
+    pub fn new(target: impl ToString, transport: impl ToString, interval: &LoadInterval) -> Self {
+        LoadReport {
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            target: target.to_string(),
+            transport: transport.to_string(),
+            elapsed_s: interval.elapsed.as_millis() as f64 / 1000.0,
+            started: interval.started,
+            finished: interval.result.count(),
+            failures: interval.result.failures.len(),
+            in_flight: interval.in_flight,
+            throughput: interval.throughput(),
+            latency: interval
+                .result
+                .latency()
+                .map(|stats| LatencyReport::new("interval", &stats)),
+        }
+    }
+
+    // The report's fields as text, in the order of `LOAD_COLUMNS`; latency is empty when nothing succeeded.
+    fn fields(&self) -> [String; LOAD_COLUMNS.len()] {
+        let millis = |ms: fn(&LatencyReport) -> f64| {
+            self.latency
+                .as_ref()
+                .map(|latency| format!("{:.3}", ms(latency)))
+                .unwrap_or_default()
+        };
+        [
+            self.timestamp.clone(),
+            format!("{:.3}", self.elapsed_s),
+            self.started.to_string(),
+            self.finished.to_string(),
+            self.failures.to_string(),
+            self.in_flight.to_string(),
+            format!("{:.1}", self.throughput),
+            millis(|latency| latency.p50_ms),
+            millis(|latency| latency.p95_ms),
+            millis(|latency| latency.p99_ms),
+            millis(|latency| latency.max_ms),
+        ]
+    }
+}
+
+// Columns of the CSV and table renderings of a comparison, in order.
+const COMPARISON_COLUMNS: [&str; 4] = ["field", "a", "b", "differs"];
+
+// A field of the handshakes with two nodes, side by side.
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct FieldDiff {
+    pub field: String,     // Name of the field, e.g. "version".
+    pub a: Option<String>, // The first node's value, if it reported one.
+    pub b: Option<String>, // The second node's value, if it reported one.
+    pub differs: bool, // Whether the values differ; latencies always do, so they are never counted.
+}
+
+impl FieldDiff {
+    // Puts the nodes' values side by side, counting a difference unless the field is informational.
+    fn new(field: &str, a: Option<String>, b: Option<String>, compared: bool) -> Self {
+        FieldDiff {
+            field: field.to_string(),
+            differs: compared && a != b,
+            a,
+            b,
+        }
+    }
+
+    // The diff's fields as text, in the order of `COMPARISON_COLUMNS`.
+    fn fields(&self) -> [String; COMPARISON_COLUMNS.len()] {
+        [
+            self.field.clone(),
+            self.a.clone().unwrap_or_default(),
+            self.b.clone().unwrap_or_default(),
+            self.differs.to_string(),
+        ]
+    }
+}
+
+// Structured comparison of the handshakes with two nodes, e.g. a new RPC node and the incumbent.
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct ComparisonReport {
+    pub a: HandshakeReport,     // The handshake with the first node.
+    pub b: HandshakeReport,     // The handshake with the second node.
+    pub fields: Vec<FieldDiff>, // Every field compared, in the order they are shown.
+}
+
+impl ComparisonReport {
+    // Compares the handshakes field by field: the version, feature set, outcome and TLS details must
+    // match, while the latencies are only shown.
+    pub fn new(a: HandshakeReport, b: HandshakeReport) -> Self {
+        let field = |name: &str, value: fn(&HandshakeReport) -> Option<String>, compared| {
+            FieldDiff::new(name, value(&a), value(&b), compared)
+        };
+        let fields = vec![
+            field("version", |r| r.version.clone(), true),
+            field(
+                "feature_set",
+                |r| r.feature_set.map(|f| f.to_string()),
+                true,
+            ),
+            field("error_class", |r| r.error_class.clone(), true),
+            field("protocol", |r| r.protocol.clone(), true),
+            field("tls_version", |r| r.tls_version.clone(), true),
+            field("cipher_suite", |r| r.cipher_suite.clone(), true),
+            field("key_exchange", |r| r.key_exchange.clone(), true),
+            field("duration_ms", |r| Some(r.duration_ms.to_string()), false),
+            field(
+                "connect_ms",
+                |r| r.connect_ms.map(|ms| ms.to_string()),
+                false,
+            ),
+            field("tls_ms", |r| r.tls_ms.map(|ms| ms.to_string()), false),
+        ];
+        ComparisonReport { a, b, fields }
+    }
+
+    // Indicates whether any compared field differs between the nodes.
+    pub fn differs(&self) -> bool {
+        self.fields.iter().any(|field| field.differs)
+    }
+}
+
+// Destination of the results: printed on stdout in the selected format and, when enabled, recorded as
+// metrics and watched for changes of state.
+pub struct Output {
+    pub format: OutputFormat,            // How results are printed on stdout.
+    pub metrics: Option<Arc<Metrics>>,   // Metrics every handshake is recorded into, if served.
+    pub file: Option<OutputFile>,        // File results are written to instead of stdout, if any.
+    pub notifier: Option<Arc<Notifier>>, // Notifier of the targets' changes of state, if any.
+    pub geoip: Option<Arc<GeoIp>>,       // Database scanned nodes are located with, if any.
+    pub asn: Option<Arc<AsnDb>>,         // Database scanned nodes' networks come from, if any.
+    #[cfg(feature = "mqtt")]
+    pub mqtt: Option<Arc<Publisher>>, // Publisher of every handshake to an MQTT broker, if any.
+    #[cfg(feature = "kafka")]
+    pub kafka: Option<Arc<Producer>>, // Producer of every handshake to a Kafka topic, if any.
+}
+
+// File the results of every run are written to, whole, once the run is over.
+pub struct OutputFile {
+    pub path: PathBuf,      // Path of the file.
+    pub append: bool,       // Whether runs are appended to the file rather than replacing it.
+    pending: Mutex<String>, // Results of the current run, not written yet.
+}
+
+impl OutputFile {
+    // Constructs an output file at the path, replacing it on every run unless appending.
+    pub fn new(path: PathBuf, append: bool) -> Self {
+        OutputFile {
+            path,
+            append,
+            pending: Mutex::new(String::new()),
+        }
+    }
+
+    // Appends results to the file, creating it if needed.
+    fn append(&self, rendered: &str) -> std::io::Result<()> {
+        if rendered.is_empty() {
+            return Ok(());
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(rendered.as_bytes())
+    }
+
+    // Replaces the file with the results, through a temporary file in the same directory renamed over
+    // it, so readers never see a partially written file.
+    fn replace(&self, rendered: &str) -> std::io::Result<()> {
+        let name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        let temporary = self
+            .path
+            .with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+        let mut file = File::create(&temporary)?;
+        file.write_all(rendered.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temporary, &self.path).inspect_err(|_| {
+            let _ = fs::remove_file(&temporary);
+        })
+    }
+}
+
+impl Output {
+    // Constructs an output printing in the given format on stdout, without metrics.
+    pub fn new(format: OutputFormat) -> Self {
+        Output {
+            format,
+            metrics: None,
+            file: None,
+            notifier: None,
+            geoip: None,
+            asn: None,
+            #[cfg(feature = "mqtt")]
+            mqtt: None,
+            #[cfg(feature = "kafka")]
+            kafka: None,
+        }
+    }
+
+    // Writes the results to a file instead of stdout.
+    pub fn with_file(mut self, file: OutputFile) -> Self {
+        // Appending to earlier results, a CSV header is only needed for a new file.
+        if file.append
+            && file
+                .path
+                .metadata()
+                .is_ok_and(|metadata| metadata.len() > 0)
+        {
+            CSV_HEADER_PRINTED.store(true, Ordering::Relaxed);
+        }
+        self.file = Some(file);
+        self
+    }
+
+    // Records every handshake into the metrics as well.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    // Publishes every handshake to an MQTT broker as well.
+    #[cfg(feature = "mqtt")]
+    pub fn with_mqtt(mut self, publisher: Arc<Publisher>) -> Self {
+        self.mqtt = Some(publisher);
+        self
+    }
+
+    // Produces every handshake to a Kafka topic as well.
+    #[cfg(feature = "kafka")]
+    pub fn with_kafka(mut self, producer: Arc<Producer>) -> Self {
+        self.kafka = Some(producer);
+        self
+    }
+
+    // Notifies changes of the targets' state as well.
+    pub fn with_notifier(mut self, notifier: Arc<Notifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    // Locates the nodes of multi-target runs in the database.
+    pub fn with_geoip(mut self, geoip: Arc<GeoIp>) -> Self {
+        self.geoip = Some(geoip);
+        self
+    }
+
+    // Looks the networks of the nodes of multi-target runs up in the database.
+    pub fn with_asn(mut self, asn: Arc<AsnDb>) -> Self {
+        self.asn = Some(asn);
+        self
+    }
+
+    // Attaches where the node of a scan result is and the network it is in, looked up by the address
+    // connected to or, failing that, the first one the target resolved to.
+    fn enrich(&self, report: HandshakeReport, result: &ScanResult) -> HandshakeReport {
+        let Some(address) = result
+            .connection
+            .connected
+            .or_else(|| result.target.address.resolved.first().copied())
+        else {
+            return report;
+        };
+        let location = self
+            .geoip
+            .as_ref()
+            .and_then(|geoip| geoip.locate(address.ip()));
+        let network = self.asn.as_ref().and_then(|asn| asn.lookup(address.ip()));
+        report.with_location(location).with_network(network)
+    }
+
+    // Prints and records the report of a single handshake.
+    pub fn report(&self, report: &HandshakeReport) -> anyhow::Result<()> {
+        self.observe(std::slice::from_ref(report));
+        self.print(render_report(self.format, report)?)
+    }
+
+    // Prints and records the results of a multi-target run.
+    // With a feature set comparison, each report tells whether its node diverges from the majority,
+    // and with GeoIP and ASN databases, where it is and who hosts it.
+    pub fn scan_results(
+        &self,
+        results: &[ScanResult],
+        divergence: Option<&FeatureSetDivergence>,
+    ) -> anyhow::Result<()> {
+        let reports: Vec<HandshakeReport> = results
+            .iter()
+            .enumerate()
+            .map(|(index, result)| {
+                let report = self.enrich(HandshakeReport::from_scan_result(result), result);
+                match (divergence, &result.result) {
+                    (Some(divergence), Ok(_)) => report.with_details(serde_json::json!({
+                        "majority_feature_set": divergence.majority,
+                        "feature_set_diverges": divergence.diverges(index),
+                    })),
+                    _ => report,
+                }
+            })
+            .collect();
+        self.observe(&reports);
+        self.print(render_scan_results(
+            self.format,
+            &reports,
+            results,
+            divergence,
+        )?)
+    }
+
+    // Prints and records the results of a probe of a host's well-known ports.
+    // Text mode adds which surfaces are reachable.
+    pub fn probe_results(&self, results: &[ProbeResult]) -> anyhow::Result<()> {
+        let reports: Vec<HandshakeReport> = results
+            .iter()
+            .map(HandshakeReport::from_probe_result)
+            .collect();
+        self.observe(&reports);
+        self.print(match self.format {
+            OutputFormat::Json => render_json(&reports)?,
+            OutputFormat::Ndjson => render_ndjson(&reports)?,
+            OutputFormat::Csv => {
+                render_csv(&reports, !CSV_HEADER_PRINTED.swap(true, Ordering::Relaxed))
+            }
+            OutputFormat::Table => render_table(&reports),
+            OutputFormat::Junit => render_junit(&reports),
+            OutputFormat::Gha => render_gha(&reports),
+            OutputFormat::Text => render_table(&reports) + &render_reachable(results),
+        })
+    }
+
+    // Prints the report of a benchmark.
+    pub fn bench(&self, report: &BenchReport) -> anyhow::Result<()> {
+        self.print(match self.format {
+            OutputFormat::Json => render_json(report)?,
+            OutputFormat::Ndjson => render_ndjson(std::slice::from_ref(report))?,
+            OutputFormat::Csv => render_latency_csv(&report.latency),
+            OutputFormat::Junit => render_test_cases(&[TestCase::from_bench(report)]),
+            OutputFormat::Gha => render_annotations(&[TestCase::from_bench(report)]),
+            OutputFormat::Text | OutputFormat::Table => render_bench(report),
+        })
+    }
+
+    // Prints the comparison of the handshakes with two nodes.
+    pub fn comparison(&self, report: &ComparisonReport) -> anyhow::Result<()> {
+        self.print(match self.format {
+            OutputFormat::Json => render_json(report)?,
+            OutputFormat::Ndjson => render_ndjson(std::slice::from_ref(report))?,
+            OutputFormat::Csv => render_comparison_csv(&report.fields),
+            OutputFormat::Junit => render_test_cases(&TestCase::from_comparison(report)),
+            OutputFormat::Gha => render_comparison_gha(report),
+            OutputFormat::Text | OutputFormat::Table => render_comparison(report),
+        })
+    }
+
+    // Prints the statistics of an interval of a load test as soon as it ends.
+    pub fn load_interval(&self, report: &LoadReport) -> anyhow::Result<()> {
+        self.print(match self.format {
+            OutputFormat::Json => render_json(report)?,
+            OutputFormat::Ndjson => render_ndjson(std::slice::from_ref(report))?,
+            OutputFormat::Csv => {
+                render_load_csv(report, !CSV_HEADER_PRINTED.swap(true, Ordering::Relaxed))
+            }
+            // The whole test is reported once it ends, as a single test case or annotation.
+            OutputFormat::Junit | OutputFormat::Gha => String::new(),
+            OutputFormat::Text | OutputFormat::Table => render_load_interval(report),
+        })
+    }
+
+    // Prints already rendered results on stdout or, with an output file, keeps them until `flush`.
+    pub fn print(&self, rendered: String) -> anyhow::Result<()> {
+        match &self.file {
+            Some(file) => file
+                .pending
+                .lock()
+                .expect("output lock poisoned")
+                .push_str(&rendered),
+            None => print!("{}", rendered),
+        }
+        Ok(())
+    }
+
+    // Writes the results of the run to the output file, if any: appended to it with `--append`, or
+    // replacing it atomically otherwise, in which case the next run's CSV starts with a header again.
+    pub fn flush(&self) -> anyhow::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let rendered = std::mem::take(&mut *file.pending.lock().expect("output lock poisoned"));
+        match file.append {
+            true => file.append(&rendered),
+            false => {
+                CSV_HEADER_PRINTED.store(false, Ordering::Relaxed);
+                file.replace(&rendered)
+            }
+        }
+        .with_context(|| format!("Could not write results to {}", file.path.display()))
+    }
+
+    // Records the reports into the metrics, the notifier and the MQTT and Kafka sinks, if any.
+    pub fn observe(&self, reports: &[HandshakeReport]) {
+        if let Some(metrics) = &self.metrics {
+            reports.iter().for_each(|report| metrics.observe(report));
+        }
+        if let Some(notifier) = &self.notifier {
+            reports.iter().for_each(|report| notifier.notify(report));
+        }
+        #[cfg(feature = "mqtt")]
+        if let Some(publisher) = &self.mqtt {
+            reports.iter().for_each(|report| publisher.publish(report));
+        }
+        #[cfg(feature = "kafka")]
+        if let Some(producer) = &self.kafka {
+            reports.iter().for_each(|report| producer.publish(report));
+        }
+    }
+}
+
+// Renders the report of a single handshake. In text mode nothing is rendered, as the logs already describe it.
+fn render_report(format: OutputFormat, report: &HandshakeReport) -> anyhow::Result<String> {
+    Ok(match format {
+        OutputFormat::Text => String::new(),
+        OutputFormat::Json => render_json(report)?,
+        OutputFormat::Ndjson => render_ndjson(std::slice::from_ref(report))?,
+        OutputFormat::Csv => render_csv(
+            std::slice::from_ref(report),
+            !CSV_HEADER_PRINTED.swap(true, Ordering::Relaxed),
+        ),
+        OutputFormat::Table => render_table(std::slice::from_ref(report)),
+        OutputFormat::Junit => render_junit(std::slice::from_ref(report)),
+        OutputFormat::Gha => render_gha(std::slice::from_ref(report)),
+    })
+}
+
+// Renders the results of a multi-target run, one entry per target.
+// Text mode adds the number of nodes that answered, how many run each version, how many each provider
+// hosts when looked up and, when compared, which nodes diverge from the majority feature set.
+fn render_scan_results(
+    format: OutputFormat,
+    reports: &[HandshakeReport],
+    results: &[ScanResult],
+    divergence: Option<&FeatureSetDivergence>,
+) -> anyhow::Result<String> {
+    Ok(match format {
+        OutputFormat::Json => render_json(&reports)?,
+        OutputFormat::Ndjson => render_ndjson(reports)?,
+        OutputFormat::Csv => render_csv(reports, !CSV_HEADER_PRINTED.swap(true, Ordering::Relaxed)),
+        OutputFormat::Table => render_table(reports),
+        OutputFormat::Junit => render_junit(reports),
+        OutputFormat::Gha => render_gha(reports),
+        OutputFormat::Text => {
+            let mut out = render_table(reports) + &render_summary(results);
+            if reports.iter().any(|report| report.asn.is_some()) {
+                out.push_str(&render_providers(reports));
+            }
+            if let Some(divergence) = divergence {
+                out.push_str(&render_divergence(results, divergence));
+            }
+            out
+        }
+    })
+}
+
+// Renders the JSON Schema of a document.
+pub fn render_schema(document: Document) -> anyhow::Result<String> {
+    render_json(&document.schema())
+}
+
+// Renders a value as pretty JSON, on a line of its own.
+fn render_json<T: Serialize>(value: &T) -> anyhow::Result<String> {
+    Ok(serde_json::to_string_pretty(value)? + "\n")
+}
+
+// Renders values as newline-delimited JSON, each compact on a line of its own.
+fn render_ndjson<T: Serialize>(values: &[T]) -> anyhow::Result<String> {
+    let mut out = String::new();
+    for value in values {
+        out.push_str(&serde_json::to_string(value)?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+// Renders the reports as CSV, optionally preceded by a header row.
+fn render_csv(reports: &[HandshakeReport], header: bool) -> String {
+    let mut out = String::new();
+    if header {
+        out.push_str(&COLUMNS.join(","));
+        out.push('\n');
+    }
+    for report in reports {
+        let row: Vec<String> = report.fields().iter().map(|f| csv_field(f)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+// Quotes a CSV field when it contains a separator, a quote or a line break, doubling any quotes.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// Renders the reports as a table, with every column as wide as its widest value.
+fn render_table(reports: &[HandshakeReport]) -> String {
+    let rows: Vec<[String; COLUMNS.len()]> = reports.iter().map(HandshakeReport::fields).collect();
+    render_aligned(COLUMNS, &rows)
+}
+
+// Renders rows under the columns' headers, with every column as wide as its widest value.
+fn render_aligned<const N: usize>(columns: [&str; N], rows: &[[String; N]]) -> String {
+    let headers = columns.map(|c| c.replace('_', " ").to_uppercase());
+
+    let mut widths = headers.clone().map(|h| h.len());
+    for row in rows {
+        for (width, field) in widths.iter_mut().zip(row) {
+            *width = (*width).max(field.len());
+        }
+    }
+
+    let mut out = String::new();
+    for row in std::iter::once(&headers).chain(rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(field, width)| format!("{:<width$}", field, width = width))
+            .collect();
+        let _ = writeln!(out, "{}", cells.join("  ").trim_end());
+    }
+    out
+}
+
+// A test case of a JUnit report, or an annotation of a GitHub Actions run.
+#[derive(Debug, Clone, PartialEq)]
+struct TestCase {
+    name: String,                      // Name of the case, e.g. the target.
+    classname: String,                 // Group of the case, e.g. "handshake.tcp".
+    time_ms: f64,                      // Time the case took.
+    summary: String,                   // What the case found when it passed, e.g. the version.
+    failure: Option<(String, String)>, // Type and message of the failure, if the case failed.
+}
+
+impl TestCase {
+    // A handshake, failed when the handshake did.
+    fn from_report(report: &HandshakeReport) -> Self {
+        let name = match &report.label {
+            Some(label) => format!("{} ({})", report.target, label),
+            None => report.target.clone(),
+        };
+        let summary = match &report.version {
+            Some(version) => format!(
+                "Answered in {} ms with version {}",
+                report.duration_ms, version
+            ),
+            None => format!("Answered in {} ms", report.duration_ms),
+        };
+        let failure = report.error.as_ref().map(|error| {
+            let class = report.error_class.as_deref().unwrap_or("error");
+            (class.to_string(), error.clone())
+        });
+        TestCase {
+            name,
+            classname: format!("handshake.{}", report.transport),
+            time_ms: report.duration_ms as f64,
+            summary,
+            failure,
+        }
+    }
+
+    // A benchmark, failed when any of its handshakes did; it takes the mean time of a handshake.
+    fn from_bench(report: &BenchReport) -> Self {
+        let mean_ms = report
+            .latency
+            .first()
+            .map_or(0.0, |latency| latency.mean_ms);
+        let failure = (report.failures > 0).then(|| {
+            let message = format!("{} of {} handshakes failed", report.failures, report.count);
+            ("error".to_string(), message)
+        });
+        TestCase {
+            name: report.target.clone(),
+            classname: format!("bench.{}", report.transport),
+            time_ms: mean_ms,
+            summary: format!(
+                "{} handshakes in {:.3} ms on average",
+                report.count, mean_ms
+            ),
+            failure,
+        }
+    }
+
+    // The fields of a comparison, each failed when the nodes differ on it.
+    fn from_comparison(report: &ComparisonReport) -> Vec<Self> {
+        let value = |value: &Option<String>| value.clone().unwrap_or_else(|| "nothing".to_string());
+        report
+            .fields
+            .iter()
+            .map(|diff| TestCase {
+                name: diff.field.clone(),
+                classname: "compare".to_string(),
+                time_ms: 0.0,
+                summary: format!("Both nodes report {}", value(&diff.a)),
+                failure: diff.differs.then(|| {
+                    let message = format!(
+                        "{} reports {}, {} reports {}",
+                        report.a.target,
+                        value(&diff.a),
+                        report.b.target,
+                        value(&diff.b)
+                    );
+                    ("mismatch".to_string(), message)
+                }),
+            })
+            .collect()
+    }
+}
+
+// Renders the reports as a JUnit XML document, with one test case per handshake.
+fn render_junit(reports: &[HandshakeReport]) -> String {
+    let cases: Vec<TestCase> = reports.iter().map(TestCase::from_report).collect();
+    render_test_cases(&cases)
+}
+
+// Renders the reports as GitHub Actions workflow commands, with one annotation per handshake.
+fn render_gha(reports: &[HandshakeReport]) -> String {
+    let cases: Vec<TestCase> = reports.iter().map(TestCase::from_report).collect();
+    render_annotations(&cases)
+}
+
+// Renders a comparison as GitHub Actions workflow commands: an error for every field the nodes differ on,
+// or a single notice when they differ on none.
+fn render_comparison_gha(report: &ComparisonReport) -> String {
+    let differing: Vec<TestCase> = TestCase::from_comparison(report)
+        .into_iter()
+        .filter(|case| case.failure.is_some())
+        .collect();
+    match differing.is_empty() {
+        true => render_annotations(&[TestCase {
+            name: format!("{} and {}", report.a.target, report.b.target),
+            classname: "compare".to_string(),
+            time_ms: 0.0,
+            summary: "No field differs".to_string(),
+            failure: None,
+        }]),
+        false => render_annotations(&differing),
+    }
+}
+
+// Renders test cases as GitHub Actions workflow commands: an error annotation for every failed case and a
+// notice for every passed one, titled after the case.
+fn render_annotations(cases: &[TestCase]) -> String {
+    let mut out = String::new();
+    for case in cases {
+        let title = gha_property(&format!("{}: {}", case.classname, case.name));
+        let _ = match &case.failure {
+            Some((_, message)) => writeln!(out, "::error title={}::{}", title, gha_data(message)),
+            None => writeln!(out, "::notice title={}::{}", title, gha_data(&case.summary)),
+        };
+    }
+    out
+}
+
+// Escapes the message of a workflow command, which ends at the first line break.
+fn gha_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+// Escapes a property of a workflow command, e.g. its title, which ends at the first colon or comma.
+fn gha_property(text: &str) -> String {
+    gha_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+// Renders test cases as a JUnit XML document holding a single `handshake` test suite.
+fn render_test_cases(cases: &[TestCase]) -> String {
+    let failures = cases.iter().filter(|case| case.failure.is_some()).count();
+    let seconds = |ms: f64| format!("{:.3}", ms / 1000.0);
+    let time = seconds(cases.iter().map(|case| case.time_ms).sum());
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        out,
+        "<testsuites tests=\"{}\" failures=\"{}\" time=\"{}\">",
+        cases.len(),
+        failures,
+        time
+    );
+    let _ = writeln!(
+        out,
+        "  <testsuite name=\"handshake\" tests=\"{}\" failures=\"{}\" errors=\"0\" time=\"{}\">",
+        cases.len(),
+        failures,
+        time
+    );
+    for case in cases {
+        let attributes = format!(
+            "name=\"{}\" classname=\"{}\" time=\"{}\"",
+            xml_escape(&case.name),
+            xml_escape(&case.classname),
+            seconds(case.time_ms)
+        );
+        match &case.failure {
+            Some((kind, message)) => {
+                let message = xml_escape(message);
+                let _ = writeln!(out, "    <testcase {}>", attributes);
+                let _ = writeln!(
+                    out,
+                    "      <failure type=\"{}\" message=\"{}\">{}</failure>",
+                    xml_escape(kind),
+                    message,
+                    message
+                );
+                let _ = writeln!(out, "    </testcase>");
+            }
+            None => {
+                let _ = writeln!(out, "    <testcase {}/>", attributes);
+            }
+        }
+    }
+    out.push_str("  </testsuite>\n</testsuites>\n");
+    out
+}
+
+// Escapes text for XML content and attributes, dropping the control characters XML 1.0 cannot hold.
+fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\n' => out.push_str("&#10;"),
+            '\t' | '\r' => out.push(' '),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Renders the latency of a benchmark as CSV, with a header row.
+fn render_latency_csv(latency: &[LatencyReport]) -> String {
+    let mut out = LATENCY_COLUMNS.join(",");
+    out.push('\n');
+    for report in latency {
+        out.push_str(&report.fields().join(","));
+        out.push('\n');
+    }
+    out
+}
+
+// Renders how many handshakes a benchmark made and how many failed, then their latency as a table.
+fn render_bench(report: &BenchReport) -> String {
+    let mut out = format!(
+        "{} handshakes with {} over {}, {} failed\n",
+        report.count, report.target, report.transport, report.failures
+    );
+    if !report.latency.is_empty() {
+        let rows: Vec<_> = report.latency.iter().map(LatencyReport::fields).collect();
+        out.push('\n');
+        out.push_str(&render_aligned(LATENCY_COLUMNS, &rows));
+    }
+    out
+}
+
+// Renders the fields of a comparison as CSV, preceded by a header row.
+fn render_comparison_csv(fields: &[FieldDiff]) -> String {
+    let mut out = COMPARISON_COLUMNS.join(",");
+    out.push('\n');
+    for field in fields {
+        let row: Vec<String> = field.fields().iter().map(|f| csv_field(f)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+// Renders a comparison as the nodes compared, a table of the fields side by side and a closing line
+// telling whether they differ.
+fn render_comparison(report: &ComparisonReport) -> String {
+    let rows: Vec<_> = report
+        .fields
+        .iter()
+        .map(|field| {
+            [
+                field.field.clone(),
+                field.a.clone().unwrap_or_else(|| "-".to_string()),
+                field.b.clone().unwrap_or_else(|| "-".to_string()),
+                if field.differs { "differs" } else { "" }.to_string(),
+            ]
+        })
+        .collect();
+    let mut out = format!(
+        "A: {} over {}\nB: {} over {}\n\n",
+        report.a.target, report.a.transport, report.b.target, report.b.transport
+    );
+    out.push_str(&render_aligned(["field", "a", "b", ""], &rows));
+    let differing = report.fields.iter().filter(|field| field.differs).count();
+    match differing {
+        0 => out.push_str("\nThe nodes match\n"),
+        1 => out.push_str("\n1 field differs\n"),
+        n => {
+            let _ = writeln!(out, "\n{} fields differ", n);
+        }
+    }
+    out
+}
+
+// Renders an interval of a load test as a CSV row, optionally preceded by a header row.
+fn render_load_csv(report: &LoadReport, header: bool) -> String {
+    let mut out = String::new();
+    if header {
+        out.push_str(&LOAD_COLUMNS.join(","));
+        out.push('\n');
+    }
+    out.push_str(&report.fields().join(","));
+    out.push('\n');
+    out
+}
+
+// Renders an interval of a load test as a line: the handshakes started, finished and in flight, then
+// the latency of the successful ones, if any.
+fn render_load_interval(report: &LoadReport) -> String {
+    let mut out = format!(
+        "[{:>8.1}s] {} started, {} finished ({} failed) at {:.1}/s, {} in flight",
+        report.elapsed_s,
+        report.started,
+        report.finished,
+        report.failures,
+        report.throughput,
+        report.in_flight
+    );
+    if let Some(latency) = &report.latency {
+        let _ = write!(
+            out,
+            ", p50 {:.3} ms, p95 {:.3} ms, p99 {:.3} ms, max {:.3} ms",
+            latency.p50_ms, latency.p95_ms, latency.p99_ms, latency.max_ms
+        );
+    }
+    out.push('\n');
+    out
+}
+
+// Renders how many nodes answered and how many run each version and feature set.
+fn render_summary(results: &[ScanResult]) -> String {
+    let succeeded = results.iter().filter(|r| r.result.is_ok()).count();
+    let mut out = String::new();
+    let _ = writeln!(out);
+    let _ = writeln!(out, "{} of {} nodes answered", succeeded, results.len());
+    let _ = writeln!(
+        out,
+        "{:<12} {:<12} {:>6}",
+        "VERSION", "FEATURE SET", "NODES"
+    );
+    for count in scan::summarize(results) {
+        let _ = writeln!(
+            out,
+            "{:<12} {:<12} {:>6}",
+            count.version,
+            count.feature_set.map(|f| f.to_string()).unwrap_or_default(),
+            count.nodes
+        );
+    }
+    out
+}
+
+// Renders how many nodes each provider hosts, most first, so a cluster leaning on a few providers
+// stands out. The nodes not found in the ASN database are counted apart.
+fn render_providers(reports: &[HandshakeReport]) -> String {
+    let mut providers: HashMap<(u32, &str), usize> = HashMap::new();
+    let mut unknown = 0;
+    for report in reports {
+        match report.asn {
+            Some(asn) => {
+                let organization = report.organization.as_deref().unwrap_or_default();
+                *providers.entry((asn, organization)).or_default() += 1;
+            }
+            None => unknown += 1,
+        }
+    }
+    let mut providers: Vec<_> = providers.into_iter().collect();
+    providers.sort_by(|(a, a_nodes), (b, b_nodes)| b_nodes.cmp(a_nodes).then(a.cmp(b)));
+
+    let mut out = String::new();
+    let _ = writeln!(out);
+    let _ = writeln!(
+        out,
+        "{:<10} {:<32} {:>6} {:>7}",
+        "ASN", "ORGANIZATION", "NODES", "SHARE"
+    );
+    for ((asn, organization), nodes) in providers {
+        let share = nodes as f64 * 100.0 / reports.len() as f64;
+        let _ = writeln!(
+            out,
+            "{:<10} {:<32} {:>6} {:>6.1}%",
+            asn, organization, nodes, share
+        );
+    }
+    if unknown > 0 {
+        let _ = writeln!(out, "{} nodes in no known network", unknown);
+    }
+    out
+}
+
+// Renders which surfaces of a probed host answered, with their ports.
+fn render_reachable(results: &[ProbeResult]) -> String {
+    let reachable: Vec<String> = results
+        .iter()
+        .filter(|result| result.result.is_ok())
+        .map(|result| format!("{} ({})", result.probe.surface, result.probe.port))
+        .collect();
+    let mut out = String::new();
+    let _ = writeln!(out);
+    match reachable.is_empty() {
+        true => {
+            let _ = writeln!(out, "No surface is reachable");
+        }
+        false => {
+            let _ = writeln!(out, "Reachable surfaces: {}", reachable.join(", "));
+        }
+    }
+    out
+}
+
+// Renders the majority feature set and the nodes diverging from it.
+fn render_divergence(results: &[ScanResult], divergence: &FeatureSetDivergence) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out);
+    let Some(majority) = divergence.majority else {
+        let _ = writeln!(out, "No node reported a feature set");
+        return out;
+    };
+    let _ = writeln!(
+        out,
+        "Majority feature set {} reported by {} nodes, {} diverging",
+        majority,
+        divergence.agreeing,
+        divergence.diverging.len()
+    );
+    for &index in &divergence.diverging {
+        if let Ok(response) = &results[index].result {
+            let _ = writeln!(
+                out,
+                "  {} runs {} with feature set {}",
+                results[index].target,
+                response.solana_core,
+                response.feature_set.unwrap_or_default()
+            );
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        handshake::{
+            error::HandshakeError,
+            solana::{
+                probe::Surface,
+                rpc::node::RpcHandshakeResponse,
+                transport::{cert::CertificateInfo, ConnectionInfo, PhaseTimings},
+                TransportType,
+            },
+        },
+        test_case::test_case,
+    };
+
+    // A successful version handshake fills the version fields and leaves the error empty.
+    #[test]
+    fn test_report_from_version() {
+        let result = Ok(RpcHandshakeResponse {
+            solana_core: "1.18.6".to_string(),
+            feature_set: Some(3580551090),
+            timings: PhaseTimings::default(),
+        });
+        let report = HandshakeReport::new(
+            "127.0.0.1:8899",
+            TransportType::Tcp,
+            Duration::from_millis(12),
+        )
+        .with_version(&result)
+        .with_timings(PhaseTimings {
+            connect: Some(Duration::from_millis(3)),
+            read: Some(Duration::from_millis(8)),
+            ..PhaseTimings::default()
+        })
+        .with_connection(ConnectionInfo {
+            connected: Some("127.0.0.1:8899".parse().unwrap()),
+            ..ConnectionInfo::default()
+        });
+
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            serde_json::json!({
+                "timestamp": report.timestamp,
+                "target": "127.0.0.1:8899",
+                "transport": "tcp",
+                "duration_ms": 12,
+                "resolve_ms": null,
+                "connect_ms": 3,
+                "tls_ms": null,
+                "write_ms": null,
+                "read_ms": 8,
+                "version": "1.18.6",
+                "feature_set": 3580551090u64,
+                "error": null,
+                "connected_to": "127.0.0.1:8899"
+            })
+        );
+    }
+
+    // A failed handshake carries the error message and no version.
+    #[test]
+    fn test_report_from_error() {
+        let result: Result<RpcHandshakeResponse, HandshakeError> = Err(HandshakeError::Timeout {
+            phase: "read".to_string(),
+            seconds: 5,
+        });
+        let report = HandshakeReport::new("127.0.0.1:8899", TransportType::Tls, Duration::ZERO)
+            .with_version(&result);
+
+        assert_eq!(report.version, None);
+        assert_eq!(
+            report.error.as_deref(),
+            Some("Timed out after 5s during read")
+        );
+    }
+
+    // The timestamp recorded for the reports rendered in the tests.
+    const TIMESTAMP: &str = "2024-03-01T12:00:00.000Z";
+
+    // CSV rows follow the header, and fields with separators or quotes are quoted.
+    #[test]
+    fn test_render_csv() {
+        let report = HandshakeReport {
+            timestamp: TIMESTAMP.to_string(),
+            error: Some("Protocol error: \"bad\", retry".to_string()),
+            ..HandshakeReport::new("127.0.0.1:8899", TransportType::Tcp, Duration::ZERO)
+        };
+        let row = "2024-03-01T12:00:00.000Z,127.0.0.1:8899,,tcp,0,,,,,,,,,,,,\"Protocol error: \"\"bad\"\", retry\"\n";
+
+        assert_eq!(
+            render_csv(std::slice::from_ref(&report), true),
+            format!(
+                "timestamp,target,label,transport,duration_ms,resolve_ms,connect_ms,tls_ms,write_ms,read_ms,version,feature_set,country,city,asn,organization,error\n{}",
+                row
+            )
+        );
+        // Later runs in watch mode leave the header out.
+        assert_eq!(render_csv(&[report], false), row);
+    }
+
+    // Every report is a compact JSON object on a line of its own.
+    #[test]
+    fn test_render_ndjson() {
+        let reports: Vec<HandshakeReport> = ["127.0.0.1:8899", "127.0.0.2:8899"]
+            .into_iter()
+            .map(|target| HandshakeReport {
+                timestamp: TIMESTAMP.to_string(),
+                ..HandshakeReport::new(target, TransportType::Tcp, Duration::from_millis(12))
+            })
+            .collect();
+
+        let rendered = render_ndjson(&reports).unwrap();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(
+            "{\"timestamp\":\"2024-03-01T12:00:00.000Z\",\"target\":\"127.0.0.1:8899\",\"transport\":\"tcp\",\"duration_ms\":12,"
+        ));
+        for (line, report) in lines.iter().zip(&reports) {
+            let parsed: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(parsed, serde_json::to_value(report).unwrap());
+        }
+        assert!(rendered.ends_with("}\n"));
+    }
+
+    // Table columns are padded to their widest value.
+    #[test]
+    fn test_render_table() {
+        let result = Ok(RpcHandshakeResponse {
+            solana_core: "1.18.6".to_string(),
+            feature_set: Some(1),
+            timings: PhaseTimings::default(),
+        });
+        let report = HandshakeReport {
+            timestamp: TIMESTAMP.to_string(),
+            ..HandshakeReport::new("127.0.0.1:8899", TransportType::Tcp, Duration::ZERO)
+        }
+        .with_version(&result)
+        .with_timings(PhaseTimings {
+            connect: Some(Duration::from_millis(3)),
+            ..PhaseTimings::default()
+        })
+        .with_label(Some("node".to_string()))
+        .with_location(Some(Location {
+            country: Some("DE".to_string()),
+            city: None,
+        }));
+
+        let table = render_table(&[report]);
+        assert_eq!(
+            table.lines().collect::<Vec<_>>(),
+            vec![
+                "TIMESTAMP                 TARGET          LABEL  TRANSPORT  DURATION MS  RESOLVE MS  CONNECT MS  TLS MS  WRITE MS  READ MS  VERSION  FEATURE SET  COUNTRY  CITY  ASN  ORGANIZATION  ERROR",
+                "2024-03-01T12:00:00.000Z  127.0.0.1:8899  node   tcp        0                        3                                      1.18.6   1            DE",
+            ]
+        );
+    }
+
+    // Every handshake is a test case, failed with its error when the handshake failed.
+    #[test]
+    fn test_render_junit() {
+        let passed = HandshakeReport::new(
+            "127.0.0.1:8899",
+            TransportType::Tcp,
+            Duration::from_millis(12),
+        )
+        .with_label(Some("node".to_string()));
+        let failed = HandshakeReport::new(
+            "127.0.0.2:8899",
+            TransportType::Tls,
+            Duration::from_millis(5000),
+        )
+        .with_error::<()>(&Err(HandshakeError::Protocol(
+            "Expected <version> & \"feature-set\"".to_string(),
+        )));
+
+        assert_eq!(
+            render_junit(&[passed, failed]).lines().collect::<Vec<_>>(),
+            vec![
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>",
+                "<testsuites tests=\"2\" failures=\"1\" time=\"5.012\">",
+                "  <testsuite name=\"handshake\" tests=\"2\" failures=\"1\" errors=\"0\" time=\"5.012\">",
+                "    <testcase name=\"127.0.0.1:8899 (node)\" classname=\"handshake.tcp\" time=\"0.012\"/>",
+                "    <testcase name=\"127.0.0.2:8899\" classname=\"handshake.tls\" time=\"5.000\">",
+                "      <failure type=\"protocol\" message=\"Protocol error: Expected &lt;version&gt; &amp; &quot;feature-set&quot;\">Protocol error: Expected &lt;version&gt; &amp; &quot;feature-set&quot;</failure>",
+                "    </testcase>",
+                "  </testsuite>",
+                "</testsuites>",
+            ]
+        );
+    }
+
+    // Text is escaped for XML, dropping the control characters it cannot hold.
+    #[test_case("plain", "plain"; "when text is plain")]
+    #[test_case("<a href='x'>", "&lt;a href=&apos;x&apos;&gt;"; "when text is markup")]
+    #[test_case("first\nsecond\u{7}", "first&#10;second"; "when text has control characters")]
+    fn test_xml_escape(text: &str, escaped: &str) {
+        assert_eq!(xml_escape(text), escaped);
+    }
+
+    // Failed handshakes are annotated as errors and successful ones as notices.
+    #[test]
+    fn test_render_gha() {
+        let result = Ok(RpcHandshakeResponse {
+            solana_core: "1.18.6".to_string(),
+            feature_set: Some(1),
+            timings: PhaseTimings::default(),
+        });
+        let passed = HandshakeReport::new(
+            "127.0.0.1:8899",
+            TransportType::Tcp,
+            Duration::from_millis(12),
+        )
+        .with_version(&result);
+        let failed = HandshakeReport::new("127.0.0.2:8899", TransportType::Tls, Duration::ZERO)
+            .with_error::<()>(&Err(HandshakeError::Tls("bad\ncertificate".to_string())));
+
+        assert_eq!(
+            render_gha(&[passed, failed]),
+            "::notice title=handshake.tcp%3A 127.0.0.1%3A8899::Answered in 12 ms with version 1.18.6\n\
+             ::error title=handshake.tls%3A 127.0.0.2%3A8899::TLS error: bad%0Acertificate\n"
+        );
+    }
+
+    // Workflow command messages end at line breaks, and their properties at colons and commas too.
+    #[test_case("plain", "plain", "plain"; "when text is plain")]
+    #[test_case("100%\r\n", "100%25%0D%0A", "100%25%0D%0A"; "when text has line breaks")]
+    #[test_case("a:b,c", "a:b,c", "a%3Ab%2Cc"; "when text has separators")]
+    fn test_gha_escape(text: &str, data: &str, property: &str) {
+        assert_eq!(gha_data(text), data);
+        assert_eq!(gha_property(text), property);
+    }
+
+    // The majority feature set is rendered with every node diverging from it.
+    #[test]
+    fn test_render_divergence() {
+        let result = |address: &str, feature_set: u64| ScanResult {
+            target: handshake::solana::scan::ScanTarget::new(
+                address.parse::<std::net::SocketAddr>().unwrap(),
+                TransportType::Tcp,
+            ),
+            duration: Duration::ZERO,
+            timings: PhaseTimings::default(),
+            connection: ConnectionInfo::default(),
+            result: Ok(RpcHandshakeResponse {
+                solana_core: "1.18.6".to_string(),
+                feature_set: Some(feature_set),
+                timings: PhaseTimings::default(),
+            }),
+        };
+        let results = [
+            result("127.0.0.1:8899", 1),
+            result("127.0.0.2:8899", 1),
+            result("127.0.0.3:8899", 2),
+        ];
+
+        let rendered = render_divergence(&results, &scan::feature_set_divergence(&results));
+        assert_eq!(
+            rendered.lines().collect::<Vec<_>>(),
+            vec![
+                "",
+                "Majority feature set 1 reported by 2 nodes, 1 diverging",
+                "  127.0.0.3:8899 runs 1.18.6 with feature set 2",
+            ]
+        );
+    }
+
+    // Providers are ranked by the nodes they host, with their share of all nodes.
+    #[test]
+    fn test_render_providers() {
+        let report = |network: Option<(u32, &str)>| {
+            HandshakeReport::new("127.0.0.1:8899", "tcp", Duration::ZERO).with_network(network.map(
+                |(asn, organization)| Network {
+                    asn: Some(asn),
+                    organization: Some(organization.to_string()),
+                },
+            ))
+        };
+        let reports = [
+            report(Some((24940, "Hetzner Online GmbH"))),
+            report(Some((16509, "AMAZON-02"))),
+            report(Some((24940, "Hetzner Online GmbH"))),
+            report(None),
+        ];
+
+        assert_eq!(
+            render_providers(&reports).lines().collect::<Vec<_>>(),
+            vec![
+                "",
+                "ASN        ORGANIZATION                      NODES   SHARE",
+                "24940      Hetzner Online GmbH                   2   50.0%",
+                "16509      AMAZON-02                             1   25.0%",
+                "1 nodes in no known network",
+            ]
+        );
+    }
+
+    // Benchmarks render their latency overall, then per phase, in milliseconds.
+    #[test]
+    fn test_render_bench() {
+        let mut result = BenchResult::default();
+        for millis in [10, 30] {
+            let timings = PhaseTimings {
+                connect: Some(Duration::from_micros(millis * 100)),
+                ..PhaseTimings::default()
+            };
+            result.record(Duration::from_millis(millis), timings, Ok(()));
+        }
+        result.record::<()>(
+            Duration::ZERO,
+            PhaseTimings::default(),
+            Err(HandshakeError::Protocol("bad".to_string())),
+        );
+        let report = BenchReport::new("127.0.0.1:8899", TransportType::Tcp, &result);
+
+        assert_eq!((report.count, report.failures), (3, 1));
+        assert_eq!(
+            render_bench(&report).lines().collect::<Vec<_>>(),
+            vec![
+                "3 handshakes with 127.0.0.1:8899 over tcp, 1 failed",
+                "",
+                "PHASE    SAMPLES  MIN MS  MEAN MS  P50 MS  P95 MS  P99 MS  MAX MS",
+                "total    2        10.000  20.000   10.000  30.000  30.000  30.000",
+                "connect  2        1.000   2.000    1.000   3.000   3.000   3.000",
+            ]
+        );
+        assert_eq!(
+            render_latency_csv(&report.latency[1..]),
+            "phase,samples,min_ms,mean_ms,p50_ms,p95_ms,p99_ms,max_ms\nconnect,2,1.000,2.000,1.000,3.000,3.000,3.000\n"
+        );
+        assert!(render_test_cases(&[TestCase::from_bench(&report)]).contains(
+            "<testcase name=\"127.0.0.1:8899\" classname=\"bench.tcp\" time=\"0.020\">\n      <failure type=\"error\" message=\"1 of 3 handshakes failed\">"
+        ));
+    }
+
+    // Versions and TLS details are compared, while latencies are only shown side by side.
+    #[test]
+    fn test_render_comparison() {
+        let report = |address: &str, version: &str, millis| {
+            let result = Ok(RpcHandshakeResponse {
+                solana_core: version.to_string(),
+                feature_set: Some(1),
+                timings: PhaseTimings::default(),
+            });
+            HandshakeReport::new(address, TransportType::Tls, Duration::from_millis(millis))
+                .with_version(&result)
+                .with_connection(ConnectionInfo {
+                    tls_version: Some("TLSv1.3".to_string()),
+                    ..ConnectionInfo::default()
+                })
+        };
+        let same = ComparisonReport::new(
+            report("127.0.0.1:8899", "1.18.6", 12),
+            report("127.0.0.2:8899", "1.18.6", 30),
+        );
+        assert!(!same.differs());
+
+        let comparison = ComparisonReport::new(
+            report("127.0.0.1:8899", "1.18.7", 12),
+            report("127.0.0.2:8899", "1.18.6", 30),
+        );
+        assert!(comparison.differs());
+        let rendered = render_comparison(&comparison);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[0], "A: 127.0.0.1:8899 over tls");
+        assert_eq!(lines[3], "FIELD         A        B");
+        assert_eq!(lines[4], "version       1.18.7   1.18.6   differs");
+        assert_eq!(lines[7], "protocol      -        -");
+        assert_eq!(lines[8], "tls_version   TLSv1.3  TLSv1.3");
+        assert_eq!(lines[11], "duration_ms   12       30");
+        assert_eq!(lines.last(), Some(&"1 field differs"));
+        assert!(
+            render_comparison_csv(&comparison.fields).contains("\nversion,1.18.7,1.18.6,true\n")
+        );
+        let junit = render_test_cases(&TestCase::from_comparison(&comparison));
+        assert!(junit.contains(&format!(
+            "<testsuite name=\"handshake\" tests=\"{}\" failures=\"1\"",
+            comparison.fields.len()
+        )));
+        assert!(junit.contains("<testcase name=\"version\" classname=\"compare\" time=\"0.000\">\n      <failure type=\"mismatch\" message=\"127.0.0.1:8899 reports 1.18.7, 127.0.0.2:8899 reports 1.18.6\">"));
+        assert!(
+            junit.contains("<testcase name=\"protocol\" classname=\"compare\" time=\"0.000\"/>")
+        );
+        assert_eq!(
+            render_comparison_gha(&comparison),
+            "::error title=compare%3A version::127.0.0.1:8899 reports 1.18.7, 127.0.0.2:8899 reports 1.18.6\n"
+        );
+        assert_eq!(
+            render_comparison_gha(&same),
+            "::notice title=compare%3A 127.0.0.1%3A8899 and 127.0.0.2%3A8899::No field differs\n"
+        );
+    }
+
+    // Intervals of a load test render as a line of text, or a CSV row with empty latency when nothing succeeded.
+    #[test]
+    fn test_render_load_interval() {
+        let mut interval = LoadInterval {
+            elapsed: Duration::from_secs(5),
+            length: Duration::from_secs(5),
+            started: 50,
+            in_flight: 2,
+            ..LoadInterval::default()
+        };
+        let mut report = LoadReport {
+            timestamp: TIMESTAMP.to_string(),
+            ..LoadReport::new("127.0.0.1:8899", TransportType::Tcp, &interval)
+        };
+        assert_eq!(
+            render_load_csv(&report, true),
+            "timestamp,elapsed_s,started,finished,failures,in_flight,throughput,p50_ms,p95_ms,p99_ms,max_ms\n2024-03-01T12:00:00.000Z,5.000,50,0,0,2,0.0,,,,\n"
+        );
+
+        for millis in [10, 20] {
+            interval.result.record(
+                Duration::from_millis(millis),
+                PhaseTimings::default(),
+                Ok(()),
+            );
+        }
+        interval.result.record::<()>(
+            Duration::ZERO,
+            PhaseTimings::default(),
+            Err(HandshakeError::Protocol("bad".to_string())),
+        );
+        report = LoadReport::new("127.0.0.1:8899", TransportType::Tcp, &interval);
+        assert_eq!(
+            render_load_interval(&report),
+            "[     5.0s] 50 started, 3 finished (1 failed) at 0.6/s, 2 in flight, p50 10.000 ms, p95 20.000 ms, p99 20.000 ms, max 20.000 ms\n"
+        );
+    }
+
+    // Probed ports are labelled with their surface, and the reachable ones are listed with their ports.
+    #[test]
+    fn test_render_reachable() {
+        let result = |surface, port, result| ProbeResult {
+            probe: handshake::solana::probe::PortProbe { surface, port },
+            target: format!("127.0.0.1:{}", port)
+                .parse::<std::net::SocketAddr>()
+                .unwrap()
+                .into(),
+            duration: Duration::ZERO,
+            timings: PhaseTimings::default(),
+            connection: ConnectionInfo::default(),
+            result,
+        };
+        let results = [
+            result(
+                Surface::Rpc,
+                8899,
+                Ok(Some(RpcHandshakeResponse {
+                    solana_core: "1.18.6".to_string(),
+                    feature_set: Some(1),
+                    timings: PhaseTimings::default(),
+                })),
+            ),
+            result(Surface::Gossip, 8001, Ok(None)),
+            result(
+                Surface::Tpu,
+                8009,
+                Err(HandshakeError::Protocol("bad".to_string())),
+            ),
+        ];
+
+        let report = HandshakeReport::from_probe_result(&results[0]);
+        assert_eq!(
+            (report.label.as_deref(), report.transport.as_str()),
+            (Some("rpc"), "tcp")
+        );
+        assert_eq!(report.version.as_deref(), Some("1.18.6"));
+        assert_eq!(
+            render_reachable(&results),
+            "\nReachable surfaces: rpc (8899), gossip (8001)\n"
+        );
+        assert_eq!(
+            render_reachable(&results[2..]),
+            "\nNo surface is reachable\n"
+        );
+    }
+
+    // Every document's schema is titled after the type it is generated from.
+    #[test_case(Document::Report, "HandshakeReport"; "when document is report")]
+    #[test_case(Document::Bench, "BenchReport"; "when document is bench")]
+    #[test_case(Document::Load, "LoadReport"; "when document is load")]
+    #[test_case(Document::Comparison, "ComparisonReport"; "when document is comparison")]
+    #[test_case(Document::Status, "StatusReport"; "when document is status")]
+    #[test_case(Document::Notification, "StateChange"; "when document is notification")]
+    fn test_schema_title(document: Document, title: &str) {
+        let schema: serde_json::Value =
+            serde_json::from_str(&render_schema(document).unwrap()).unwrap();
+        assert_eq!(schema["title"], title);
+        assert_eq!(schema["type"], "object");
+    }
+
+    // The report's schema describes every field it is serialized with, requiring only those always present.
+    #[test]
+    fn test_report_schema() {
+        let mut report = HandshakeReport::new("127.0.0.1:8899", "tls", Duration::from_millis(20))
+            .with_label(Some("node".to_string()))
+            .with_network(Some(Network {
+                asn: Some(16509),
+                organization: Some("AMAZON-02".to_string()),
+            }))
+            .with_details(serde_json::json!({ "health": "ok" }));
+        report.tls_version = Some("TLSv1.3".to_string());
+        report.certificates = vec![CertificateInfo {
+            subject: "CN=node".to_string(),
+            issuer: "CN=node".to_string(),
+            sans: vec!["localhost".to_string()],
+            not_after: 67_090_118_400,
+            days_until_expiry: 10,
+            pin_sha256: "pin".to_string(),
+        }];
+
+        let schema = serde_json::to_value(Document::Report.schema()).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+        let serialized = serde_json::to_value(&report).unwrap();
+        for field in serialized.as_object().unwrap().keys() {
+            assert!(properties.contains_key(field), "{} is not described", field);
+        }
+        let required = schema["required"].as_array().unwrap();
+        assert!(required.contains(&serde_json::json!("timestamp")));
+        assert!(!required.contains(&serde_json::json!("label")));
+        assert!(!required.contains(&serde_json::json!("certificates")));
+        assert!(schema["definitions"]["CertificateInfo"].is_object());
+    }
+
+    // Runs replace the output file, unless appended to it, and nothing reaches the file before a flush.
+    #[test]
+    fn test_output_file() {
+        let path = std::env::temp_dir().join(format!("results-{}.txt", std::process::id()));
+        let output =
+            Output::new(OutputFormat::Text).with_file(OutputFile::new(path.clone(), false));
+        for round in ["first\n", "second\n"] {
+            output.print(round.to_string()).unwrap();
+            output.flush().unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second\n");
+
+        let output = Output::new(OutputFormat::Text).with_file(OutputFile::new(path.clone(), true));
+        output.print("third\n".to_string()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second\n");
+        output.flush().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second\nthird\n");
+        fs::remove_file(&path).unwrap();
+    }
+}
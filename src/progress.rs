@@ -0,0 +1,124 @@
+// Progress of multi-target scans, drawn as a bar on stderr while the handshakes run: how many finished
+// out of the total, how many failed so far and when the scan should be over.
+// Log lines written to stderr meanwhile clear the bar, which is redrawn below them.
+use {
+    crate::output::OutputFormat,
+    handshake::solana::scan::{Progress, ScanResult},
+    indicatif::{ProgressBar, ProgressStyle},
+    std::{
+        io::{self, IsTerminal, Write},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+    },
+};
+
+// Layout of the bar, e.g. "[00:00:12] ######---- 120/300 handshakes, 4 failed, ETA 18s".
+const TEMPLATE: &str = "[{elapsed_precise}] {wide_bar} {pos}/{len} handshakes, {msg}, ETA {eta}";
+
+// The bar being drawn, if any, so log lines can be written above it.
+static BAR: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+// Progress bar of the handshakes of a scan.
+pub struct ScanProgress {
+    bar: ProgressBar,    // The bar, hidden when not drawn.
+    failures: AtomicU64, // Handshakes failed so far.
+}
+
+impl ScanProgress {
+    // Starts the bar of a scan of `total` targets. It is only drawn when the results are printed on a
+    // terminal, and not as JSON, NDJSON or JUnit XML, which are meant for other programs.
+    pub fn new(total: usize, format: OutputFormat) -> Arc<Self> {
+        let drawn = std::io::stdout().is_terminal()
+            && !matches!(
+                format,
+                OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Junit
+            );
+        let bar = match drawn {
+            true => ProgressBar::new(total as u64),
+            false => ProgressBar::hidden(),
+        };
+        if let Ok(style) = ProgressStyle::with_template(TEMPLATE) {
+            bar.set_style(style);
+        }
+        bar.set_message("0 failed");
+        *BAR.lock().expect("progress lock poisoned") = Some(bar.clone());
+        Arc::new(ScanProgress {
+            bar,
+            failures: AtomicU64::new(0),
+        })
+    }
+
+    // Counts a handshake that finished.
+    pub fn record(&self, result: &ScanResult) {
+        self.bar.inc(1);
+        if result.result.is_err() {
+            let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+            self.bar.set_message(format!("{} failed", failures));
+        }
+    }
+
+    // The callback a scanner tells of every handshake that finished.
+    pub fn callback(self: &Arc<Self>) -> Progress {
+        let progress = self.clone();
+        Arc::new(move |result| progress.record(result))
+    }
+
+    // Removes the bar once the scan is over, before its results are printed.
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+        *BAR.lock().expect("progress lock poisoned") = None;
+    }
+}
+
+// Writes log lines to stderr, clearing the bar being drawn, if any, while it does.
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let bar = BAR.lock().expect("progress lock poisoned").clone();
+        match bar {
+            Some(bar) => bar.suspend(|| io::stderr().write(buf)),
+            None => io::stderr().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        handshake::{
+            error::HandshakeError,
+            solana::{scan::ScanTarget, TransportType},
+        },
+        std::{net::SocketAddr, time::Duration},
+    };
+
+    // Finished and failed handshakes are counted, and the bar is forgotten once finished.
+    #[test]
+    fn test_record() {
+        let address: SocketAddr = "127.0.0.1:8899".parse().unwrap();
+        let result = |result| ScanResult {
+            target: ScanTarget::new(address, TransportType::Tcp),
+            duration: Duration::ZERO,
+            timings: Default::default(),
+            connection: Default::default(),
+            result,
+        };
+        let progress = ScanProgress::new(3, OutputFormat::Json);
+        let callback = progress.callback();
+        callback(&result(Err(HandshakeError::Protocol("bad".to_string()))));
+        callback(&result(Err(HandshakeError::Dns("unresolved".to_string()))));
+
+        assert_eq!(progress.bar.position(), 2);
+        assert_eq!(progress.bar.message(), "2 failed");
+        progress.finish();
+        assert!(BAR.lock().unwrap().is_none());
+    }
+}
@@ -0,0 +1,198 @@
+// Registries of handshake profiles, so new chains or custom services can be probed without code changes.
+// A registry is a TOML or JSON file of profiles, each describing its request and how the response is read:
+//
+// ```toml
+// [[profile]]
+// name = "near"
+// payload = { jsonrpc = "2.0", id = "handshake", method = "status", params = [] }
+// version = "$.result.version.version"
+// details = { chain_id = "$.result.chain_id" }
+// expect = [{ path = "$.result.sync_info.syncing", equals = false }]
+// ```
+
+// The `path` module selects values of a response with JSONPaths.
+pub mod path;
+
+// The `profile` module describes a handshake as data: a payload template, paths and expectations.
+pub mod profile;
+
+// The `node` module handshakes with a node as a profile describes, over the shared transports.
+pub mod node;
+
+// The types needed to load a registry and handshake with its profiles.
+pub use {
+    node::ProfileNode,
+    path::JsonPath,
+    profile::{Expectation, Profile},
+};
+
+use {
+    serde::Deserialize,    // Import deserialization of registries.
+    std::{fs, path::Path}, // Import reading registries from files.
+};
+
+// A registry of handshake profiles.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Registry {
+    #[serde(default, rename = "profile", alias = "profiles")]
+    pub profiles: Vec<Profile>, // The profiles, in the order they were defined.
+}
+
+impl Registry {
+    // Loads a registry file, read as JSON when its extension says so and as TOML otherwise.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        let registry = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json(&text),
+            _ => Self::from_toml(&text),
+        };
+        registry.map_err(|e| format!("Invalid registry {}: {}", path.display(), e))
+    }
+
+    // Parses a TOML registry, with a `[[profile]]` table per profile.
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let registry: Registry = toml::from_str(text).map_err(|e| e.to_string())?;
+        registry.validated()
+    }
+
+    // Parses a JSON registry, with a `profiles` array.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let registry: Registry = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        registry.validated()
+    }
+
+    // Rejects registries defining a profile twice, which would make selecting it ambiguous.
+    fn validated(self) -> Result<Self, String> {
+        for (index, profile) in self.profiles.iter().enumerate() {
+            if self.profiles[..index]
+                .iter()
+                .any(|other| other.name == profile.name)
+            {
+                return Err(format!("profile {} is defined twice", profile.name));
+            }
+        }
+        Ok(self)
+    }
+
+    // Names of the profiles, in the order they were defined.
+    pub fn names(&self) -> Vec<&str> {
+        self.profiles
+            .iter()
+            .map(|profile| profile.name.as_str())
+            .collect()
+    }
+
+    // The named profile with its placeholders set, failing when it is unknown or a placeholder has no value.
+    pub fn profile(
+        &self,
+        name: &str,
+        variables: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Profile, String> {
+        let profile = self
+            .profiles
+            .iter()
+            .find(|profile| profile.name == name)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown profile {}; the registry defines: {}",
+                    name,
+                    self.names().join(", ")
+                )
+            })?
+            .clone()
+            .with_variables(variables);
+        match profile.unbound_variables().as_slice() {
+            [] => Ok(profile),
+            unbound => Err(format!(
+                "Profile {} needs a value for: {}",
+                name,
+                unbound.join(", ")
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::chain::ChainHandshake};
+
+    const TOML: &str = r#"
+[[profile]]
+name = "near"
+payload = { jsonrpc = "2.0", id = "handshake", method = "status", params = [] }
+version = "$.result.version.version"
+details = { chain_id = "$.result.chain_id" }
+expect = [{ path = "$.result.sync_info.syncing", equals = false }]
+
+[[profile]]
+name = "substrate"
+payload = { jsonrpc = "2.0", id = 1, method = "{{method}}", params = [] }
+version = "$.result"
+"#;
+
+    // TOML registries are parsed with every field of their profiles.
+    #[test]
+    fn test_from_toml() {
+        let registry = Registry::from_toml(TOML).unwrap();
+
+        assert_eq!(registry.names(), vec!["near", "substrate"]);
+        let near = &registry.profiles[0];
+        assert_eq!(near.payload["method"], "status");
+        assert_eq!(near.version.to_string(), "$.result.version.version");
+        assert_eq!(near.details["chain_id"].to_string(), "$.result.chain_id");
+        assert_eq!(near.expect[0].equals, Some(serde_json::Value::Bool(false)));
+    }
+
+    // JSON registries describe the same profiles.
+    #[test]
+    fn test_from_json() {
+        let json = r#"{"profiles":[{"name":"near","payload":{"jsonrpc":"2.0","id":"handshake","method":"status","params":[]},"version":"$.result.version.version","details":{"chain_id":"$.result.chain_id"},"expect":[{"path":"$.result.sync_info.syncing","equals":false}]}]}"#;
+        let registry = Registry::from_json(json).unwrap();
+
+        assert_eq!(
+            registry.profiles[0],
+            Registry::from_toml(TOML).unwrap().profiles[0]
+        );
+    }
+
+    // Duplicate profiles, invalid paths and unknown fields are rejected when loading.
+    #[test]
+    fn test_invalid_registries() {
+        let duplicated = format!(
+            "{}\n{}",
+            TOML,
+            &TOML[TOML.find("[[profile]]\nname = \"substrate\"").unwrap()..]
+        );
+        assert!(Registry::from_toml(&duplicated)
+            .unwrap_err()
+            .contains("defined twice"));
+        assert!(
+            Registry::from_toml(&TOML.replace("$.result.chain_id", "result.chain_id")).is_err()
+        );
+        assert!(Registry::from_toml(&TOML.replace("details =", "detail =")).is_err());
+    }
+
+    // Profiles are selected by name, with every placeholder given a value.
+    #[test]
+    fn test_profile() {
+        let registry = Registry::from_toml(TOML).unwrap();
+
+        let substrate = registry
+            .profile(
+                "substrate",
+                [("method".to_string(), "system_version".to_string())],
+            )
+            .unwrap();
+        assert_eq!(substrate.payload()["method"], "system_version");
+        assert!(registry
+            .profile("substrate", [])
+            .unwrap_err()
+            .contains("method"));
+        assert!(registry
+            .profile("cardano", [])
+            .unwrap_err()
+            .contains("near, substrate"));
+    }
+}
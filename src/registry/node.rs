@@ -0,0 +1,138 @@
+// Module for handshaking with any node a profile describes, over the transports shared with the Solana nodes.
+use crate::chain::{self, NodeInfo}; // Chain-agnostic handshakes.
+use crate::error::HandshakeError; // Crate-wide error type reported by failed handshakes.
+use crate::registry::profile::Profile; // Handshake profiles loaded from a registry.
+use crate::solana::{
+    node::Handshake,
+    target::Target,
+    transport::{
+        select,
+        tcp::{ConnectOptions, Connectable},
+        traced, ChooseTransport, Transport,
+    },
+    TransportType,
+}; // Import necessary traits and structures for handshake and transport.
+use async_trait::async_trait; // Enables async trait methods, crucial for async network operations.
+use std::fmt; // Standard library import for display formatting.
+
+// Struct defining a node handshaken with a profile, including its remote address and transport type.
+pub struct ProfileNode {
+    pub remote: Target, // The remote endpoint: its name as given and the addresses it resolved to.
+    pub transport_type: TransportType, // Enum specifying the type of transport to use.
+    pub profile: Profile, // The profile describing the handshake.
+    pub connect: ConnectOptions, // Proxy and TLS options of the endpoint's connections.
+}
+
+impl ProfileNode {
+    // Constructor for ProfileNode, taking a target, or a socket address, transport type and profile.
+    pub fn new(remote: impl Into<Target>, transport_type: TransportType, profile: Profile) -> Self {
+        ProfileNode {
+            remote: remote.into(),
+            transport_type,
+            profile,
+            connect: ConnectOptions::default(),
+        }
+    }
+
+    // Sends the profile's payload, returning what the node reported once the response meets the profile.
+    pub async fn probe(&self, timeout: Option<u32>) -> Result<NodeInfo, HandshakeError> {
+        let transport = self.get_transport();
+        let exchange = chain::shake(&self.profile, transport.as_ref(), timeout);
+        traced(&self.remote, self.transport_type, exchange).await
+    }
+}
+
+impl Connectable for ProfileNode {
+    fn connect_options(&mut self) -> &mut ConnectOptions {
+        &mut self.connect
+    }
+}
+
+// Implement the Display trait for ProfileNode for easy logging and debugging.
+impl fmt::Display for ProfileNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ProfileNode({}, {})", self.profile.name, self.remote) // Custom display format showing the profile and remote address.
+    }
+}
+
+// Chooses the appropriate transport mechanism based on the transport_type attribute.
+impl ChooseTransport for ProfileNode {
+    fn get_transport(&self) -> Box<dyn Transport> {
+        select(
+            &self.remote,
+            self.transport_type,
+            self.connect.route(&self.remote),
+            self.connect.tls.clone(),
+        )
+    }
+}
+
+// Async trait for performing the handshake operation, returning the version the node reported.
+#[async_trait]
+impl Handshake for ProfileNode {
+    async fn shake(&self, timeout: Option<u32>) -> Result<String, HandshakeError> {
+        Ok(self.probe(timeout).await?.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Test suite for ProfileNode functionality, run against an in-process JSON-RPC server.
+    use {super::*, crate::test_server::serve_once, serde_json::json, std::net::SocketAddr};
+
+    // A profile asking a NEAR node for its status.
+    fn near() -> Profile {
+        serde_json::from_value(json!({
+            "name": "near",
+            "payload": {"jsonrpc": "2.0", "id": "handshake", "method": "status", "params": []},
+            "version": "$.result.version.version",
+            "details": {"chain_id": "$.result.chain_id"},
+            "expect": [{"path": "$.result.sync_info.syncing", "equals": false}],
+        }))
+        .unwrap()
+    }
+
+    // Verifies that a ProfileNode can be correctly instantiated and displayed.
+    #[test]
+    fn test_profile_node_creation() {
+        let addr: SocketAddr = "127.0.0.1:3030".parse().unwrap();
+        let node = ProfileNode::new(addr, TransportType::Tcp, near());
+
+        assert_eq!(node.transport_type, TransportType::Tcp);
+        assert_eq!(node.to_string(), "ProfileNode(near, 127.0.0.1:3030)"); // Checks the display format.
+    }
+
+    // The profile's payload is sent and the response read as the profile describes.
+    #[tokio::test]
+    async fn test_profile_handshake() {
+        let (address, server) = serve_once(
+            r#"{"jsonrpc":"2.0","id":"handshake","result":{"chain_id":"mainnet","sync_info":{"syncing":false},"version":{"version":"1.39.1","build":"crates-0.20.1"}}}"#,
+        )
+        .await;
+
+        let info = ProfileNode::new(address, TransportType::Tcp, near())
+            .probe(Some(5))
+            .await
+            .unwrap();
+        assert_eq!(info.chain, "near");
+        assert_eq!(info.version, "1.39.1");
+        assert_eq!(info.details["chain_id"], "mainnet");
+
+        let request = server.await.unwrap();
+        assert!(request.contains(r#""method":"status""#));
+    }
+
+    // A node failing the profile's expectations fails the handshake.
+    #[tokio::test]
+    async fn test_unmet_expectation() {
+        let (address, _) = serve_once(
+            r#"{"jsonrpc":"2.0","id":"handshake","result":{"chain_id":"mainnet","sync_info":{"syncing":true},"version":{"version":"1.39.1"}}}"#,
+        )
+        .await;
+
+        let result = ProfileNode::new(address, TransportType::Tcp, near())
+            .shake(Some(5))
+            .await;
+        assert!(matches!(result, Err(HandshakeError::Protocol(_))));
+    }
+}
@@ -0,0 +1,146 @@
+// JSONPaths into handshake responses, e.g. `$.result.version` or `$.result.peers[0]['node id']`.
+// Only the subset a profile needs is supported: member names, in dot or bracket notation, and array
+// indices, counted from the end when negative.
+use {
+    serde::Deserialize,       // Import deserialization of paths from registries.
+    serde_json::Value,        // Import JSON values paths select from.
+    std::{fmt, str::FromStr}, // Import parsing and display formatting.
+};
+
+// A step of a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Member(String), // A member of an object.
+    Index(i64),     // An element of an array, from the end when negative.
+}
+
+// A parsed JSONPath, kept with its text for error messages.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct JsonPath {
+    text: String,           // The path as written.
+    segments: Vec<Segment>, // The steps from the root.
+}
+
+impl JsonPath {
+    // Selects the value at the path, if the response has one.
+    pub fn select<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.segments
+            .iter()
+            .try_fold(value, |value, segment| match (segment, value) {
+                (Segment::Member(name), Value::Object(members)) => members.get(name),
+                (Segment::Index(index), Value::Array(elements)) => {
+                    let index = match *index < 0 {
+                        true => elements.len().checked_sub(index.unsigned_abs() as usize)?,
+                        false => *index as usize,
+                    };
+                    elements.get(index)
+                }
+                _ => None,
+            })
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| format!("Invalid JSONPath '{}': {}", text, reason);
+        let mut rest = text
+            .strip_prefix('$')
+            .ok_or_else(|| invalid("expected it to start with '$'"))?;
+        let mut segments = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                // A dotted member runs until the next step.
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                if end == 0 {
+                    return Err(invalid("expected a member name after '.'"));
+                }
+                segments.push(Segment::Member(after[..end].to_string()));
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after
+                    .find(']')
+                    .ok_or_else(|| invalid("expected a closing ']'"))?;
+                let inner = after[..end].trim();
+                let quoted = inner
+                    .strip_prefix('\'')
+                    .and_then(|inner| inner.strip_suffix('\''))
+                    .or_else(|| {
+                        inner
+                            .strip_prefix('"')
+                            .and_then(|inner| inner.strip_suffix('"'))
+                    });
+                segments.push(match quoted {
+                    Some(name) => Segment::Member(name.to_string()),
+                    None => Segment::Index(
+                        inner
+                            .parse()
+                            .map_err(|_| invalid("expected an index or a quoted member name"))?,
+                    ),
+                });
+                rest = &after[end + 1..];
+            } else {
+                return Err(invalid("expected '.' or '['"));
+            }
+        }
+        Ok(JsonPath {
+            text: text.to_string(),
+            segments,
+        })
+    }
+}
+
+// Registries give paths as strings, parsed when the registry is loaded.
+impl TryFrom<String> for JsonPath {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+// Implement the Display trait for JsonPath, giving back the path as written.
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json, test_case::test_case};
+
+    // Paths select members and elements in any of their notations.
+    #[test_case("$", json!({"result": {"version": "1.18.6", "peers": ["a", "b"]}}); "when path is the root")]
+    #[test_case("$.result.version", json!("1.18.6"); "when path is dotted")]
+    #[test_case("$['result'][\"version\"]", json!("1.18.6"); "when path is bracketed")]
+    #[test_case("$.result.peers[1]", json!("b"); "when path has an index")]
+    #[test_case("$.result.peers[-2]", json!("a"); "when path has an index from the end")]
+    fn test_select(path: &str, expected: Value) {
+        let response = json!({"result": {"version": "1.18.6", "peers": ["a", "b"]}});
+        let path: JsonPath = path.parse().unwrap();
+        assert_eq!(path.select(&response), Some(&expected));
+    }
+
+    // Paths missing from the response select nothing.
+    #[test_case("$.error"; "when member is missing")]
+    #[test_case("$.result.peers[2]"; "when index is out of range")]
+    #[test_case("$.result.peers[-3]"; "when index from the end is out of range")]
+    #[test_case("$.result.version.major"; "when value is not an object")]
+    fn test_select_missing(path: &str) {
+        let response = json!({"result": {"version": "1.18.6", "peers": ["a", "b"]}});
+        let path: JsonPath = path.parse().unwrap();
+        assert_eq!(path.select(&response), None);
+    }
+
+    // Anything outside the supported subset is rejected.
+    #[test_case("result.version"; "when root is missing")]
+    #[test_case("$..version"; "when member name is missing")]
+    #[test_case("$.peers[0"; "when bracket is unclosed")]
+    #[test_case("$.peers[*]"; "when index is a wildcard")]
+    fn test_parse_failures(path: &str) {
+        assert!(path.parse::<JsonPath>().is_err());
+    }
+}
@@ -0,0 +1,288 @@
+// Handshake profiles: a network's handshake described as data instead of code.
+// The payload is a JSON template whose strings may hold `{{name}}` placeholders, filled from the
+// profile's variables; the response is read with JSONPaths and must meet every expectation.
+use {
+    super::path::JsonPath, // Import JSONPaths into the response.
+    crate::{
+        chain::{ChainHandshake, NodeInfo},
+        error::HandshakeError,
+        solana::rpc::node::RpcError,
+    }, // Import the chain-agnostic handshake, the crate-wide error type and JSON-RPC error objects.
+    serde::Deserialize,    // Import deserialization of profiles from registries.
+    serde_json::Value,     // Import JSON payloads and responses.
+    std::collections::BTreeMap, // Import ordered maps of variables and details.
+};
+
+// A handshake profile, as defined in a registry.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub name: String, // Name the profile is selected by, also reported as the node's chain.
+    pub payload: Value, // Template of the JSON payload sent to the node.
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>, // Values of the payload's placeholders, overridable from the command line.
+    pub version: JsonPath, // Path of the version the node reports.
+    #[serde(default)]
+    pub details: BTreeMap<String, JsonPath>, // Paths of further values reported under their names.
+    #[serde(default)]
+    pub expect: Vec<Expectation>, // Predicates the response must satisfy for the handshake to succeed.
+}
+
+// A predicate on the response: the path must select a value, which must meet the given conditions.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+    pub path: JsonPath,           // Path of the value checked.
+    pub equals: Option<Value>,    // Value it must equal, if any.
+    pub contains: Option<String>, // Text its string form must contain, if any.
+}
+
+impl Expectation {
+    // Checks the response, describing how it fell short.
+    pub fn check(&self, response: &Value) -> Result<(), String> {
+        let value = self
+            .path
+            .select(response)
+            .filter(|value| !value.is_null())
+            .ok_or_else(|| format!("expected a value at {}", self.path))?;
+        if let Some(expected) = &self.equals {
+            if value != expected {
+                return Err(format!(
+                    "expected {} at {}, got {}",
+                    expected, self.path, value
+                ));
+            }
+        }
+        if let Some(needle) = &self.contains {
+            if !text(value).contains(needle.as_str()) {
+                return Err(format!(
+                    "expected {} to contain '{}', got {}",
+                    self.path, needle, value
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// The text of a value: strings without their quotes, anything else as JSON.
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+impl Profile {
+    // Sets the values of placeholders, overriding those of the registry.
+    pub fn with_variables(mut self, variables: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.variables.extend(variables);
+        self
+    }
+
+    // Names of the payload's placeholders without a value.
+    pub fn unbound_variables(&self) -> Vec<String> {
+        let mut unbound = Vec::new();
+        collect_placeholders(&self.payload, &mut unbound);
+        unbound.retain(|name| !self.variables.contains_key(name));
+        unbound.sort();
+        unbound.dedup();
+        unbound
+    }
+}
+
+// Collects the names of the placeholders in the strings of a template.
+fn collect_placeholders(template: &Value, names: &mut Vec<String>) {
+    match template {
+        Value::String(text) => {
+            let mut rest = text.as_str();
+            while let Some((_, after)) = rest.split_once("{{") {
+                let Some((name, after)) = after.split_once("}}") else {
+                    break;
+                };
+                names.push(name.trim().to_string());
+                rest = after;
+            }
+        }
+        Value::Array(elements) => elements
+            .iter()
+            .for_each(|element| collect_placeholders(element, names)),
+        Value::Object(members) => members
+            .values()
+            .for_each(|member| collect_placeholders(member, names)),
+        _ => {}
+    }
+}
+
+// Fills the placeholders in the strings of a template.
+fn render(template: &Value, variables: &BTreeMap<String, String>) -> Value {
+    match template {
+        Value::String(text) => {
+            let mut rendered = text.clone();
+            for (name, value) in variables {
+                rendered = rendered.replace(&format!("{{{{{}}}}}", name), value);
+            }
+            Value::String(rendered)
+        }
+        Value::Array(elements) => Value::Array(
+            elements
+                .iter()
+                .map(|element| render(element, variables))
+                .collect(),
+        ),
+        Value::Object(members) => Value::Object(
+            members
+                .iter()
+                .map(|(name, member)| (name.clone(), render(member, variables)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+impl ChainHandshake for Profile {
+    // The payload template with its placeholders filled.
+    fn payload(&self) -> Value {
+        render(&self.payload, &self.variables)
+    }
+
+    // Checks the expectations, then reads the version and details at their paths.
+    // A JSON-RPC error is reported as such when the node answers with one instead of a version.
+    fn parse(&self, raw: &str) -> Result<NodeInfo, HandshakeError> {
+        let response: Value = serde_json::from_str(raw)
+            .map_err(|e| HandshakeError::InvalidResponse(format!("Response is not JSON: {}", e)))?;
+        for expectation in &self.expect {
+            expectation
+                .check(&response)
+                .map_err(|e| HandshakeError::Protocol(format!("Profile {} {}", self.name, e)))?;
+        }
+        let version = match (self.version.select(&response), response.get("error")) {
+            (Some(version), _) if !version.is_null() => text(version),
+            (_, Some(error)) => {
+                // Error objects of the JSON-RPC shape are RPC errors; any other is only a protocol error.
+                return Err(match serde_json::from_value::<RpcError>(error.clone()) {
+                    Ok(error) => error.into(),
+                    Err(_) => HandshakeError::Protocol(format!("JSON-RPC error {}", error)),
+                });
+            }
+            _ => {
+                return Err(HandshakeError::InvalidResponse(format!(
+                    "No version at {}",
+                    self.version
+                )))
+            }
+        };
+        let details = self
+            .details
+            .iter()
+            .map(|(name, path)| {
+                let value = path.select(&response).cloned().unwrap_or_default();
+                (name.clone(), value)
+            })
+            .collect();
+        Ok(NodeInfo {
+            chain: self.name.clone(),
+            version,
+            details: Value::Object(details),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json};
+
+    // A profile asking a Solana node for its version, with the method as a placeholder.
+    fn profile() -> Profile {
+        serde_json::from_value(json!({
+            "name": "solana",
+            "payload": {"jsonrpc": "2.0", "id": 1, "method": "{{method}}", "params": []},
+            "variables": {"method": "getVersion"},
+            "version": "$.result.solana-core",
+            "details": {"feature_set": "$.result.feature-set", "commitment": "$.result.commitment"},
+            "expect": [{"path": "$.jsonrpc", "equals": "2.0"}],
+        }))
+        .unwrap()
+    }
+
+    // Placeholders are filled from the variables, the command line's overriding the registry's.
+    #[test]
+    fn test_payload() {
+        assert_eq!(profile().payload()["method"], "getVersion");
+
+        let profile = profile().with_variables([("method".to_string(), "getHealth".to_string())]);
+        assert_eq!(profile.payload()["method"], "getHealth");
+        assert_eq!(profile.payload()["id"], 1);
+        assert!(profile.unbound_variables().is_empty());
+
+        let mut profile = profile;
+        profile.variables.clear();
+        assert_eq!(profile.unbound_variables(), vec!["method"]);
+    }
+
+    // The version and details are read at their paths, missing details reported as null.
+    #[test]
+    fn test_parse() {
+        let raw = r#"{"jsonrpc":"2.0","id":1,"result":{"feature-set":4215500110,"solana-core":"1.18.6"}}"#;
+        let info = profile().parse(raw).unwrap();
+
+        assert_eq!(info.chain, "solana");
+        assert_eq!(info.version, "1.18.6");
+        assert_eq!(info.details["feature_set"], 4215500110u64);
+        assert_eq!(info.details["commitment"], Value::Null);
+    }
+
+    // Unmet expectations, JSON-RPC errors and missing versions fail the handshake.
+    #[test]
+    fn test_parse_errors() {
+        let raw = r#"{"jsonrpc":"1.0","id":1,"result":{"solana-core":"1.18.6"}}"#;
+        match profile().parse(raw) {
+            Err(HandshakeError::Protocol(message)) => {
+                assert!(message.contains("expected \"2.0\" at $.jsonrpc"))
+            }
+            other => panic!("Expected a protocol error, got {:?}", other),
+        }
+
+        let raw =
+            r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":"Method not found"}}"#;
+        assert!(matches!(
+            profile().parse(raw),
+            Err(HandshakeError::Rpc { code: -32601, .. })
+        ));
+
+        let raw = r#"{"jsonrpc":"2.0","id":1,"result":{}}"#;
+        assert!(matches!(
+            profile().parse(raw),
+            Err(HandshakeError::InvalidResponse(_))
+        ));
+    }
+
+    // Expectations check that a value is present, equal to another or contains a text.
+    #[test]
+    fn test_expectation() {
+        let response = json!({"result": {"network": "cosmoshub-4", "catching_up": false}});
+        let expectation = |value: Value| -> Expectation { serde_json::from_value(value).unwrap() };
+
+        assert!(expectation(json!({"path": "$.result.network"}))
+            .check(&response)
+            .is_ok());
+        assert!(
+            expectation(json!({"path": "$.result.catching_up", "equals": false}))
+                .check(&response)
+                .is_ok()
+        );
+        assert!(
+            expectation(json!({"path": "$.result.network", "contains": "cosmoshub"}))
+                .check(&response)
+                .is_ok()
+        );
+        assert!(
+            expectation(json!({"path": "$.result.network", "contains": "osmosis"}))
+                .check(&response)
+                .is_err()
+        );
+        assert!(expectation(json!({"path": "$.result.height"}))
+            .check(&response)
+            .is_err());
+    }
+}
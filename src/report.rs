@@ -0,0 +1,184 @@
+// Structured report of a handshake, printed by the binary and returned by the bindings, so every consumer of the
+// library describes a handshake with the same fields.
+use {
+    crate::{
+        error::HandshakeError,
+        solana::{
+            probe::ProbeResult,
+            rpc::node::RpcHandshakeResponse,
+            scan::ScanResult,
+            transport::{cert::CertificateInfo, ConnectionInfo, PhaseTimings},
+        },
+    },
+    schemars::JsonSchema,
+    serde::Serialize,
+    std::time::{Duration, SystemTime},
+};
+
+// Structured result of a single handshake.
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct HandshakeReport {
+    pub timestamp: String, // When the handshake finished, in RFC 3339 format.
+    pub target: String,    // The address that was handshaken.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>, // Optional name for the target, e.g. the node's identity pubkey.
+    pub transport: String, // The transport used, e.g. "tcp" or "quic".
+    pub duration_ms: u64,  // Time taken by the handshake, successful or not.
+    pub resolve_ms: Option<u64>, // Time spent resolving the address, if the transport did.
+    pub connect_ms: Option<u64>, // Time spent connecting, if the transport did.
+    pub tls_ms: Option<u64>, // Time spent negotiating TLS or QUIC, if the transport did.
+    pub write_ms: Option<u64>, // Time spent writing the request, if the transport did.
+    pub read_ms: Option<u64>, // Time spent reading the response, if the transport did.
+    pub version: Option<String>, // The solana-core version, when the handshake reports one.
+    pub feature_set: Option<u64>, // The feature set, when the handshake reports one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>, // Country code of the address, when looked up with `--geoip`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>, // City of the address, when looked up with `--geoip` and known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asn: Option<u32>, // Autonomous system of the address, when looked up with `--asn`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>, // Organization operating the autonomous system, e.g. a hosting provider.
+    pub error: Option<String>, // Why the handshake failed, if it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_class: Option<String>, // The class of the failure, e.g. "timeout", if the handshake failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connected_to: Option<String>, // The address connected to, when the target resolved to several.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>, // The protocol negotiated with the node, e.g. "h2", when known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_version: Option<String>, // The TLS version negotiated with the node, e.g. "TLSv1.3", when known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher_suite: Option<String>, // The cipher suite negotiated with the node, when known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_exchange: Option<String>, // The key exchange group negotiated with the node, e.g. "X25519", when known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub early_data: Option<bool>, // Whether the node accepted the request sent as TLS early data, when it was.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub early_data_saved_ms: Option<u64>, // Time the request did not wait for the TLS handshake, when accepted.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[schemars(default)]
+    pub certificates: Vec<CertificateInfo>, // The certificate chain the node presented over TLS, leaf first.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>, // Subcommand-specific results, e.g. health or identity.
+}
+
+impl HandshakeReport {
+    // Constructs a report for a handshake with no result recorded yet.
+    pub fn new(target: impl ToString, transport: impl ToString, duration: Duration) -> Self {
+        HandshakeReport {
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            target: target.to_string(),
+            label: None,
+            transport: transport.to_string(),
+            duration_ms: duration.as_millis() as u64,
+            resolve_ms: None,
+            connect_ms: None,
+            tls_ms: None,
+            write_ms: None,
+            read_ms: None,
+            version: None,
+            feature_set: None,
+            country: None,
+            city: None,
+            asn: None,
+            organization: None,
+            error: None,
+            error_class: None,
+            connected_to: None,
+            protocol: None,
+            tls_version: None,
+            cipher_suite: None,
+            key_exchange: None,
+            early_data: None,
+            early_data_saved_ms: None,
+            certificates: Vec::new(),
+            details: None,
+        }
+    }
+
+    // Records the outcome of a `getVersion` handshake.
+    pub fn with_version(mut self, result: &Result<RpcHandshakeResponse, HandshakeError>) -> Self {
+        if let Ok(response) = result {
+            self.version = Some(response.solana_core.clone());
+            self.feature_set = response.feature_set;
+        }
+        self.with_error(result)
+    }
+
+    // Records the time spent in each phase of the handshake.
+    pub fn with_timings(mut self, timings: PhaseTimings) -> Self {
+        let millis = |phase: Option<Duration>| phase.map(|d| d.as_millis() as u64);
+        self.resolve_ms = millis(timings.resolve);
+        self.connect_ms = millis(timings.connect);
+        self.tls_ms = millis(timings.tls);
+        self.write_ms = millis(timings.write);
+        self.read_ms = millis(timings.read);
+        self
+    }
+
+    // Records what the handshake learnt about its connection: the address connected to, what TLS
+    // negotiated and the certificate chain the node presented.
+    pub fn with_connection(mut self, connection: ConnectionInfo) -> Self {
+        self.connected_to = connection.connected.map(|address| address.to_string());
+        self.protocol = connection.protocol;
+        self.tls_version = connection.tls_version;
+        self.cipher_suite = connection.cipher_suite;
+        self.key_exchange = connection.key_exchange;
+        self.early_data = connection.early_data;
+        self.early_data_saved_ms = connection
+            .early_data_saved
+            .map(|saved| saved.as_millis() as u64);
+        self.certificates = connection.certificates;
+        self
+    }
+
+    // Records the error of a failed handshake.
+    pub fn with_error<T>(mut self, result: &Result<T, HandshakeError>) -> Self {
+        self.error = result.as_ref().err().map(ToString::to_string);
+        self.error_class = result.as_ref().err().map(|e| e.class().to_string());
+        self
+    }
+
+    // Attaches subcommand-specific results.
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    // Builds the report of one target of a scan.
+    pub fn from_scan_result(result: &ScanResult) -> Self {
+        HandshakeReport::new(
+            &result.target.address,
+            result.target.transport_type,
+            result.duration,
+        )
+        .with_version(&result.result)
+        .with_timings(result.timings.clone())
+        .with_connection(result.connection.clone())
+        .with_label(result.target.label.clone())
+    }
+
+    // Builds the report of one port of a probe, labelled with the surface expected on it.
+    pub fn from_probe_result(result: &ProbeResult) -> Self {
+        let report = HandshakeReport::new(
+            &result.target,
+            result.probe.surface.transport(),
+            result.duration,
+        )
+        .with_error(&result.result)
+        .with_timings(result.timings.clone())
+        .with_connection(result.connection.clone())
+        .with_label(Some(result.probe.surface.to_string()));
+        match &result.result {
+            Ok(Some(response)) => report.with_version(&Ok(response.clone())),
+            _ => report,
+        }
+    }
+
+    // Attaches the target's label, if any.
+    pub fn with_label(mut self, label: Option<String>) -> Self {
+        self.label = label;
+        self
+    }
+}
@@ -0,0 +1,1102 @@
+// The subcommands handshaking Solana RPC nodes: single handshakes, checks and races, scans of many nodes,
+// benchmarks and load tests, each reporting its handshakes as they finish.
+use {
+    crate::{
+        cli::{BenchArgs, CompareArgs, HealthArgs, IdentityArgs, LoadArgs, NodeArgs, ScanArgs},
+        connect::{resolve, shake_reported},
+        output::{
+            BenchReport, ComparisonReport, HandshakeReport, LoadReport, Output, OutputFormat,
+        },
+        progress::ScanProgress,
+        Outcome, UsageError,
+    },
+    handshake::{
+        solana::{
+            bench::{self, BenchResult},
+            cancel::Cancel,
+            discovery,
+            load::LoadTest,
+            pacer::Pacer,
+            precheck::Precheck,
+            rpc::node::{RpcBatchRequest, RpcHandshakeResponse, RpcHealth},
+            scan::{self, ScanResult, ScanTarget, Scanner},
+            target::{AddressFamily, Target},
+            traceroute::Traceroute,
+            transport::{
+                pool::{self, ConnectionPool},
+                retry::RetryPolicy,
+                tcp::{ConnectOptions, Connectable},
+                timed,
+                ws::PingOptions,
+            },
+            ConnectionInfo, PhaseTimings, RpcNode, TransportType,
+        },
+        HandshakeError,
+    },
+    semver::VersionReq,
+    std::{
+        sync::Arc,
+        time::{Duration, Instant},
+    },
+    tracing::{error, info, warn},
+};
+
+// Performs the JSON RPC handshake over the raw socket, or the hyper client when asked to, racing it
+// against the WebSocket one when asked to.
+pub async fn connect(
+    node: &NodeArgs,
+    family: AddressFamily,
+    output: &Output,
+    cancel: &Cancel,
+) -> anyhow::Result<Outcome> {
+    if node.subscribe || node.ping.is_some() {
+        anyhow::bail!(UsageError(
+            "--subscribe and --ping can only be used with connect-rpc-with-websocket"
+        ));
+    }
+    let trans_type = TransportType::rpc(false, node.secure);
+
+    // With the `hyper` feature, the hyper client can replace the raw socket transport.
+    #[cfg(feature = "hyper")]
+    let trans_type = match (node.hyper, node.http2, node.secure) {
+        (true, true, true) => TransportType::H2,
+        (true, true, false) => TransportType::H2c,
+        (true, false, true) => TransportType::Https,
+        (true, false, false) => TransportType::Http,
+        (false, _, _) => trans_type,
+    };
+
+    if node.race {
+        let ws_type = TransportType::rpc(true, node.secure);
+        return race_rpc(node, family, trans_type, ws_type, output).await;
+    }
+    shake_rpc(node, family, trans_type, output, cancel).await
+}
+
+// Performs the handshake over the PubSub WebSocket, or instead checks a slot subscription, pings the node
+// or races the handshake against the JSON RPC one, when asked to.
+pub async fn connect_websocket(
+    node: &NodeArgs,
+    family: AddressFamily,
+    output: &Output,
+    cancel: &Cancel,
+) -> anyhow::Result<Outcome> {
+    let trans_type = TransportType::rpc(true, node.secure);
+    if node.subscribe {
+        return check_subscription(
+            &node.address,
+            family,
+            trans_type,
+            node.connect.options(),
+            output,
+        )
+        .await;
+    }
+    if let Some(count) = node.ping {
+        let options = PingOptions {
+            count,
+            interval: Duration::from_millis(node.ping_interval),
+        };
+        return ping_node(node, family, trans_type, options, output).await;
+    }
+    if node.race {
+        let http_type = TransportType::rpc(false, node.secure);
+        return race_rpc(node, family, http_type, trans_type, output).await;
+    }
+    shake_rpc(node, family, trans_type, output, cancel).await
+}
+
+// Calls getHealth, failing the check when the node is unhealthy.
+pub async fn check_health(
+    node: &HealthArgs,
+    family: AddressFamily,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let trans_type = TransportType::rpc(false, node.secure);
+    let target = resolve(&node.address, family, trans_type, output).await?;
+    let rpc_node = RpcNode::new(target.clone(), trans_type).with_connect(node.connect.options());
+    info!("Checking health of {}", rpc_node);
+
+    let health = shake_reported(
+        target,
+        trans_type,
+        rpc_node.check_health(None),
+        |health| {
+            let slots_behind = match health {
+                RpcHealth::Behind { slots_behind, .. } => *slots_behind,
+                RpcHealth::Ok => None,
+            };
+            Some(serde_json::json!({
+                "healthy": health.is_healthy(),
+                "slots_behind": slots_behind,
+            }))
+        },
+        output,
+    )
+    .await?;
+    info!("Health response was {:?}", health);
+    match health.is_healthy() {
+        true => Ok(Outcome::Passed),
+        false => Ok(Outcome::CheckFailed),
+    }
+}
+
+// Calls getIdentity, failing the check when the node reports another identity than the expected one.
+pub async fn check_identity(
+    node: &IdentityArgs,
+    family: AddressFamily,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let trans_type = TransportType::rpc(false, node.secure);
+    let target = resolve(&node.address, family, trans_type, output).await?;
+    let rpc_node = RpcNode::new(target.clone(), trans_type).with_connect(node.connect.options());
+    info!("Checking identity of {}", rpc_node);
+
+    let identity = shake_reported(
+        target,
+        trans_type,
+        rpc_node.get_identity(None),
+        |identity| {
+            Some(serde_json::json!({
+                "identity": identity.to_string(),
+                "expected_identity": node.expect_identity.map(|pubkey| pubkey.to_string()),
+            }))
+        },
+        output,
+    )
+    .await?;
+    info!("Node identity is {}", identity);
+    match node.expect_identity {
+        Some(expected) if identity != expected => {
+            error!(
+                "Expected identity {} but node reported {}",
+                expected, identity
+            );
+            Ok(Outcome::CheckFailed)
+        }
+        _ => Ok(Outcome::Passed),
+    }
+}
+
+// Discovers the RPC nodes of the cluster through the entrypoint and handshakes them concurrently,
+// comparing their feature sets with the majority when asked to.
+pub async fn scan_cluster(
+    node: &ScanArgs,
+    family: AddressFamily,
+    output: &Output,
+    cancel: &Cancel,
+) -> anyhow::Result<Outcome> {
+    let trans_type = TransportType::rpc(false, node.secure);
+    let target = resolve(&node.address, family, trans_type, output).await?;
+    let entrypoint = RpcNode::new(target, trans_type).with_connect(node.connect.options());
+    info!("Discovering cluster through {}", entrypoint);
+
+    let Some(nodes) = cancel.run(discovery::discover(&entrypoint, None)).await else {
+        anyhow::bail!("Interrupted before the cluster was discovered");
+    };
+    let nodes = nodes?;
+    let targets = discovery::rpc_targets(&nodes);
+    info!("Scanning {} RPC nodes", targets.len());
+
+    let progress = ScanProgress::new(targets.len(), output.format);
+    let results = Scanner::new()
+        .with_concurrency(node.concurrency)
+        .with_pacer(node.rate.map(|rate| Arc::new(Pacer::new(rate))))
+        .with_connect(node.connect.options())
+        .with_cancel(cancel.clone())
+        .with_progress(Some(progress.callback()))
+        .scan(targets)
+        .await;
+    progress.finish();
+
+    // Compare the feature sets with the majority when asked to, logging every diverging node.
+    let divergence = node
+        .check_feature_set
+        .then(|| scan::feature_set_divergence(&results));
+    output.scan_results(&results, divergence.as_ref())?;
+    match divergence {
+        Some(divergence) if !divergence.diverging.is_empty() => {
+            for &index in &divergence.diverging {
+                if let Ok(response) = &results[index].result {
+                    error!(
+                        "{} reports feature set {:?} instead of the majority {:?}",
+                        results[index].target, response.feature_set, divergence.majority
+                    );
+                }
+            }
+            Ok(Outcome::CheckFailed)
+        }
+        _ => scan_outcome(results),
+    }
+}
+
+// Handshakes a node repeatedly, reporting the latency percentiles.
+pub async fn bench(
+    node: &BenchArgs,
+    family: AddressFamily,
+    output: &Output,
+    cancel: &Cancel,
+) -> anyhow::Result<Outcome> {
+    let trans_type = TransportType::rpc(node.websocket, node.secure);
+    let target = resolve(&node.address, family, trans_type, output).await?;
+    let mut rpc_node =
+        RpcNode::new(target.clone(), trans_type).with_connect(node.connect.options());
+    // With keep-alive, the handshakes share a pool holding the connection between them.
+    if node.keep_alive {
+        let pool = ConnectionPool::new(pool::DEFAULT_IDLE_TIMEOUT).with_max_idle_per_key(1);
+        rpc_node = rpc_node.with_pool(Arc::new(pool));
+    }
+    info!("Benchmarking {} with {} handshakes", rpc_node, node.count);
+
+    let result = bench::bench_until(&rpc_node, node.count, None, cancel).await;
+    output.bench(&BenchReport::new(target, trans_type, &result))?;
+    bench_outcome(result)
+}
+
+// Starts handshakes with a node at the rate, printing their statistics every interval.
+pub async fn load(
+    node: &LoadArgs,
+    family: AddressFamily,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let trans_type = TransportType::rpc(node.websocket, node.secure);
+    let target = resolve(&node.address, family, trans_type, output).await?;
+    let rpc_node = RpcNode::new(target.clone(), trans_type).with_connect(node.connect.options());
+    info!(
+        "Loading {} with {} handshakes per second for {}s",
+        rpc_node, node.rate, node.duration
+    );
+
+    let load = LoadTest::new(node.rate, Duration::from_secs(node.duration))
+        .with_interval(Duration::from_secs(node.interval));
+    let total = load
+        .run(Arc::new(rpc_node), |interval| {
+            output.load_interval(&LoadReport::new(&target, trans_type, interval))
+        })
+        .await?;
+
+    // CSV output stays a single table of intervals; the other formats end with the whole test's latency.
+    if output.format != OutputFormat::Csv {
+        output.bench(&BenchReport::new(&target, trans_type, &total.result))?;
+    }
+    bench_outcome(total.result)
+}
+
+// Handshakes two nodes at once and prints how their responses differ, failing the check when they do.
+pub async fn compare(
+    node: &CompareArgs,
+    family: AddressFamily,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let trans_type = TransportType::rpc(node.websocket, node.secure);
+    info!("Comparing {} with {} over {}", node.a, node.b, trans_type);
+
+    let ((a, a_result), (b, b_result)) = tokio::try_join!(
+        shake_compared(&node.a, node, family, trans_type),
+        shake_compared(&node.b, node, family, trans_type),
+    )?;
+    let report = ComparisonReport::new(a, b);
+    output.comparison(&report)?;
+
+    // A node that did not answer fails the comparison like a failed handshake.
+    a_result?;
+    b_result?;
+    match report.differs() {
+        true => {
+            for field in report.fields.iter().filter(|field| field.differs) {
+                error!(
+                    "{} differs: {:?} on {} but {:?} on {}",
+                    field.field, field.a, node.a, field.b, node.b
+                );
+            }
+            Ok(Outcome::CheckFailed)
+        }
+        false => Ok(Outcome::Passed),
+    }
+}
+
+// Outcome of a series of handshakes with a node. A node that never answered fails like a single
+// handshake would, with the last error.
+fn bench_outcome(mut result: BenchResult) -> anyhow::Result<Outcome> {
+    match result.failures.len() {
+        0 => Ok(Outcome::Passed),
+        failures if failures == result.count() => Err(result.failures.pop().unwrap().into()),
+        failures => {
+            error!("{} of {} handshakes failed", failures, result.count());
+            Ok(Outcome::PartialFailure)
+        }
+    }
+}
+
+// Handshakes one of the nodes compared, returning the report of the handshake with its result.
+async fn shake_compared(
+    address: &Target,
+    node: &CompareArgs,
+    family: AddressFamily,
+    trans_type: TransportType,
+) -> anyhow::Result<(
+    HandshakeReport,
+    Result<RpcHandshakeResponse, HandshakeError>,
+)> {
+    let target = address.lookup(family).await?;
+    let rpc_node = RpcNode::new(target.clone(), trans_type).with_connect(node.connect.options());
+    let started = Instant::now();
+    let (result, timings, connection) = timed(rpc_node.shake_typed(None)).await;
+    let report = HandshakeReport::new(target, trans_type, started.elapsed())
+        .with_version(&result)
+        .with_timings(timings)
+        .with_connection(connection);
+    Ok((report, result))
+}
+
+// Performs the RPC handshake with every target.
+// A single target is handshaken directly, racing its addresses; several are handshaken concurrently
+// and reported per target.
+async fn shake_rpc(
+    node: &NodeArgs,
+    family: AddressFamily,
+    trans_type: TransportType,
+    output: &Output,
+    cancel: &Cancel,
+) -> anyhow::Result<Outcome> {
+    let file_targets = node.file_targets(trans_type)?;
+    if let ([target], []) = (&node.address[..], &file_targets[..]) {
+        // Instantiate an RPC node with the target's addresses and log the connection attempt.
+        let target = resolve(target, family, trans_type, output).await?;
+        let mut rpc_node = RpcNode::new(target, trans_type).with_connect(node.connect.options());
+        if let Some(method) = &node.method {
+            rpc_node = rpc_node.with_method(method, node.params.clone());
+        }
+        info!("Connecting to {}", rpc_node);
+        if let Some(precheck) = node.precheck {
+            precheck_rpc_node(&rpc_node, precheck, output).await?;
+        }
+        let outcome = shake_rpc_node(
+            &rpc_node,
+            node.methods.clone(),
+            node.expect_version.as_ref(),
+            node.retry_policy(),
+            node.traceroute(),
+            output,
+        )
+        .await?;
+
+        // Once the handshake passed, compare the node's slot with the reference's, if one was given.
+        return match (&node.reference, outcome) {
+            (Some(reference), Outcome::Passed) => {
+                let reference = resolve(reference, family, trans_type, output).await?;
+                let reference_node =
+                    RpcNode::new(reference, trans_type).with_connect(node.connect.options());
+                check_slot_lag(&rpc_node, &reference_node, node.max_slot_lag, output).await
+            }
+            (_, outcome) => Ok(outcome),
+        };
+    }
+    if node.methods.is_some() {
+        anyhow::bail!(UsageError(
+            "--methods can only be used with a single address"
+        ));
+    }
+    if node.reference.is_some() {
+        anyhow::bail!(UsageError(
+            "--reference can only be used with a single address"
+        ));
+    }
+    if node.method.is_some() {
+        anyhow::bail!(UsageError(
+            "--method can only be used with a single address"
+        ));
+    }
+    if node.traceroute.is_some() {
+        anyhow::bail!(UsageError(
+            "--traceroute can only be used with a single address"
+        ));
+    }
+
+    // Handshake every address, then every target of the file, concurrently with the transport's default
+    // timeout, each host being resolved before its handshake.
+    let targets: Vec<ScanTarget> = node
+        .address
+        .iter()
+        .map(|target| ScanTarget::new(target.clone(), trans_type))
+        .chain(file_targets)
+        .collect();
+    info!("Connecting to {} targets", targets.len());
+    let progress = ScanProgress::new(targets.len(), output.format);
+    let results = Scanner::new()
+        .with_concurrency(node.concurrency)
+        .with_pacer(node.rate.map(|rate| Arc::new(Pacer::new(rate))))
+        .with_timeout(None)
+        .with_connect(node.connect.options())
+        .with_retry(node.retry_policy())
+        .with_precheck(node.precheck)
+        .with_lookup(Some(family))
+        .with_cancel(cancel.clone())
+        .with_progress(Some(progress.callback()))
+        .scan(targets)
+        .await;
+    progress.finish();
+    output.scan_results(&results, None)?;
+
+    // Every node that answered must run a version satisfying the expected one, if any.
+    if let Some(requirement) = &node.expect_version {
+        let mismatched = results
+            .iter()
+            .filter(|r| match &r.result {
+                Ok(response) => !version_satisfied(&r.target, response, requirement),
+                Err(_) => false,
+            })
+            .count();
+        if mismatched > 0 {
+            return Ok(Outcome::VersionMismatch);
+        }
+    }
+    scan_outcome(results)
+}
+
+// Handshakes a single target over HTTP and WebSocket at once, reporting both handshakes and passing
+// with whichever succeeded first. The winner is reported as soon as it succeeds, and the slower one once
+// it answers or `--race-timeout` runs out; the race fails, with the HTTP handshake's error, only when both fail.
+async fn race_rpc(
+    node: &NodeArgs,
+    family: AddressFamily,
+    http_type: TransportType,
+    ws_type: TransportType,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let ([target], None) = (&node.address[..], &node.targets_file) else {
+        anyhow::bail!(UsageError("--race can only be used with a single address"));
+    };
+    let http_target = resolve(target, family, http_type, output).await?;
+    let ws_target = match node.ws_port {
+        Some(port) => http_target.with_port(port),
+        None => http_target.clone(),
+    };
+    let racers = [(http_target, http_type), (ws_target, ws_type)].map(|(target, trans_type)| {
+        RpcNode::new(target, trans_type).with_connect(node.connect.options())
+    });
+    info!(
+        "Racing {} over {} against {} over {}",
+        racers[0], http_type, racers[1], ws_type
+    );
+
+    let report_racer = |i: usize, outcome: &RaceOutcome, won: bool| -> anyhow::Result<()> {
+        let (result, timings, connection, elapsed) = outcome;
+        let rpc_node = &racers[i];
+        let report = HandshakeReport::new(&rpc_node.remote, rpc_node.transport_type, *elapsed)
+            .with_version(result)
+            .with_timings(timings.clone())
+            .with_connection(connection.clone())
+            .with_details(serde_json::json!({
+                "race": {
+                    "won": won,
+                    "against": racers[1 - i].transport_type.to_string(),
+                }
+            }));
+        output.report(&report)?;
+        match result {
+            Ok(response) => info!(
+                "{} over {} answered after {:?}: {:?}",
+                rpc_node, rpc_node.transport_type, elapsed, response
+            ),
+            Err(e) => warn!(
+                "{} over {} failed after {:?}: {}",
+                rpc_node, rpc_node.transport_type, elapsed, e
+            ),
+        }
+        Ok(())
+    };
+    let budget = node.race_timeout;
+    let (results, winner) = run_race(&racers, node.retry_policy(), budget, report_racer).await?;
+
+    let Some(winner) = winner else {
+        let [(http, ..), _] = results;
+        return Err(http.expect_err("neither handshake succeeded").into());
+    };
+    info!(
+        "{} won the race over {}",
+        racers[winner], racers[winner].transport_type
+    );
+    if let (Ok(response), Some(requirement)) = (&results[winner].0, &node.expect_version) {
+        if !version_satisfied(&racers[winner], response, requirement) {
+            return Ok(Outcome::VersionMismatch);
+        }
+    }
+    Ok(Outcome::Passed)
+}
+
+// One side of a race: its handshake result, phases and connection, and the time since the race started.
+type RaceOutcome = (
+    Result<RpcHandshakeResponse, HandshakeError>,
+    PhaseTimings,
+    ConnectionInfo,
+    Duration,
+);
+
+// Handshakes both nodes at once, passing each outcome to `report` as soon as it is known, along with its
+// index and whether it won, so the winner is reported without waiting for the other. Once one succeeds, the
+// other is given `budget` seconds to finish before it is stopped and reported as timed out; after a failure,
+// the other runs to its end, being the only one that can still succeed. Both outcomes are returned with the
+// index of the winner. Times are measured from the start of the race, so the winner has the shortest.
+async fn run_race(
+    racers: &[RpcNode; 2],
+    retry: RetryPolicy,
+    budget: u32,
+    mut report: impl FnMut(usize, &RaceOutcome, bool) -> anyhow::Result<()>,
+) -> anyhow::Result<([RaceOutcome; 2], Option<usize>)> {
+    let started = Instant::now();
+    let http = shake_racer(&racers[0], retry, started);
+    let ws = shake_racer(&racers[1], retry, started);
+    tokio::pin!(http, ws);
+    let (first, outcome) = tokio::select! {
+        outcome = &mut http => (0, outcome),
+        outcome = &mut ws => (1, outcome),
+    };
+    report(first, &outcome, outcome.0.is_ok())?;
+    let rest = async {
+        match first {
+            0 => ws.await,
+            _ => http.await,
+        }
+    };
+    let other = match outcome.0.is_ok() {
+        true => tokio::time::timeout(Duration::from_secs(budget.into()), rest)
+            .await
+            .unwrap_or_else(|_| {
+                let timed_out = HandshakeError::Timeout {
+                    phase: "race".to_string(),
+                    seconds: budget,
+                };
+                (
+                    Err(timed_out),
+                    PhaseTimings::default(),
+                    ConnectionInfo::default(),
+                    started.elapsed(),
+                )
+            }),
+        false => rest.await,
+    };
+    report(1 - first, &other, outcome.0.is_err() && other.0.is_ok())?;
+    let winner = match (&outcome.0, &other.0) {
+        (Ok(_), _) => Some(first),
+        (_, Ok(_)) => Some(1 - first),
+        _ => None,
+    };
+    match first {
+        0 => Ok(([outcome, other], winner)),
+        _ => Ok(([other, outcome], winner)),
+    }
+}
+
+// Handshakes one of the transports of a race with getVersion, returning its result, phases and connection
+// along with the time since the race started. Races compare versions, so `--race` rejects `--method`.
+async fn shake_racer(rpc_node: &RpcNode, retry: RetryPolicy, started: Instant) -> RaceOutcome {
+    let (result, timings, connection) =
+        timed(retry.run(&rpc_node.remote, || rpc_node.shake_typed(None))).await;
+    (result, timings, connection, started.elapsed())
+}
+
+// Calls `getSlot` on the node and on the reference, reporting how far apart they are. The check fails
+// when the node is more than the maximum lag behind, if one was given.
+async fn check_slot_lag(
+    rpc_node: &RpcNode,
+    reference_node: &RpcNode,
+    max_slot_lag: Option<u64>,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    info!("Comparing the slot of {} with {}", rpc_node, reference_node);
+    let started = Instant::now();
+    let result = rpc_node.slot_lag(reference_node, None).await;
+    let mut report =
+        HandshakeReport::new(&rpc_node.remote, rpc_node.transport_type, started.elapsed())
+            .with_error(&result);
+    if let Ok(lag) = &result {
+        report = report.with_details(serde_json::json!({
+            "slot": lag.slot,
+            "reference": reference_node.remote.to_string(),
+            "reference_slot": lag.reference_slot,
+            "slot_delta": lag.delta(),
+            "max_slot_lag": max_slot_lag,
+        }));
+    }
+    output.report(&report)?;
+
+    let lag = result?;
+    info!(
+        "{} is at slot {}, {} slots from the reference at slot {}",
+        rpc_node,
+        lag.slot,
+        lag.delta(),
+        lag.reference_slot
+    );
+    match max_slot_lag {
+        Some(max) if lag.behind() > max => {
+            error!(
+                "{} is {} slots behind the reference, more than the {} allowed",
+                rpc_node,
+                lag.behind(),
+                max
+            );
+            Ok(Outcome::CheckFailed)
+        }
+        _ => Ok(Outcome::Passed),
+    }
+}
+
+// Subscribes to slot updates of a single target and waits for a notification, reporting the slot received.
+async fn check_subscription(
+    targets: &[Target],
+    family: AddressFamily,
+    trans_type: TransportType,
+    connect: ConnectOptions,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let target = match targets {
+        [target] => resolve(target, family, trans_type, output).await?,
+        _ => anyhow::bail!(UsageError(
+            "--subscribe can only be used with a single address"
+        )),
+    };
+    let rpc_node = RpcNode::new(target.clone(), trans_type).with_connect(connect);
+    info!("Subscribing to slot updates of {}", rpc_node);
+
+    let subscription = shake_reported(
+        target,
+        trans_type,
+        rpc_node.verify_slot_subscription(None),
+        |subscription| {
+            Some(serde_json::json!({
+                "subscription": subscription.subscription,
+                "slot": subscription.notification.slot,
+                "parent": subscription.notification.parent,
+                "root": subscription.notification.root,
+            }))
+        },
+        output,
+    )
+    .await?;
+    info!(
+        "Received slot {} through subscription {}",
+        subscription.notification.slot, subscription.subscription
+    );
+    Ok(Outcome::Passed)
+}
+
+// Pings a single target over WebSocket, reporting the round trip of each pong and, unless only
+// pings were asked for, the handshake sent on the same connection first.
+async fn ping_node(
+    node: &NodeArgs,
+    family: AddressFamily,
+    trans_type: TransportType,
+    options: PingOptions,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let target = match &node.address[..] {
+        [target] => resolve(target, family, trans_type, output).await?,
+        _ => anyhow::bail!(UsageError("--ping can only be used with a single address")),
+    };
+    let rpc_node = RpcNode::new(target.clone(), trans_type).with_connect(node.connect.options());
+    info!("Pinging {} {} times", rpc_node, options.count);
+
+    let started = Instant::now();
+    let (result, timings, connection) = timed(rpc_node.ping(options, !node.ping_only, None)).await;
+    let mut report = HandshakeReport::new(&target, trans_type, started.elapsed())
+        .with_error(&result)
+        .with_timings(timings)
+        .with_connection(connection);
+    if let Ok(response) = &result {
+        if let Some(handshake) = &response.handshake {
+            report = report.with_version(&Ok(handshake.clone()));
+        }
+        let round_trips: Vec<f64> = response
+            .round_trips
+            .iter()
+            .map(|round_trip| round_trip.as_secs_f64() * 1000.0)
+            .collect();
+        report = report.with_details(serde_json::json!({ "ping_round_trips_ms": round_trips }));
+    }
+    output.report(&report)?;
+
+    let response = result?;
+    info!("Received {} pongs", response.round_trips.len());
+    Ok(Outcome::Passed)
+}
+
+// A multi-target run passes only if every target answered. A run no target answered fails like a single
+// handshake would, with the first error of the most common class, so the exit code tells the cause.
+fn scan_outcome(results: Vec<ScanResult>) -> anyhow::Result<Outcome> {
+    let total = results.len();
+    let mut failures: Vec<HandshakeError> =
+        results.into_iter().filter_map(|r| r.result.err()).collect();
+    match failures.len() {
+        0 => Ok(Outcome::Passed),
+        failed if failed < total => Ok(Outcome::PartialFailure),
+        _ => {
+            let counts: Vec<usize> = failures
+                .iter()
+                .map(|e| failures.iter().filter(|f| f.class() == e.class()).count())
+                .collect();
+            // Ties go to the class failing first; `max_by_key` keeps the last maximum, hence the reversal.
+            let (index, _) = counts
+                .iter()
+                .enumerate()
+                .rev()
+                .max_by_key(|(_, count)| **count)
+                .expect("a failed run has failures");
+            Err(failures.swap_remove(index).into())
+        }
+    }
+}
+
+// Checks the version a node reported against the requirement, logging why it is not satisfied.
+fn version_satisfied(
+    node: &impl std::fmt::Display,
+    response: &RpcHandshakeResponse,
+    requirement: &VersionReq,
+) -> bool {
+    match response.satisfies(requirement) {
+        Ok(true) => true,
+        Ok(false) => {
+            error!(
+                "{} runs solana-core {}, which does not satisfy {}",
+                node, response.solana_core, requirement
+            );
+            false
+        }
+        Err(e) => {
+            error!("{} reported no comparable version: {}", node, e);
+            false
+        }
+    }
+}
+
+// Checks the host of the node is reachable before its handshake, reporting it as unreachable when not.
+async fn precheck_rpc_node(
+    rpc_node: &RpcNode,
+    precheck: Precheck,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let Some(address) = rpc_node.remote.resolved.first() else {
+        return Ok(());
+    };
+    let started = Instant::now();
+    let result = precheck.check(address.ip(), None).await;
+    if result.is_err() {
+        let report =
+            HandshakeReport::new(&rpc_node.remote, rpc_node.transport_type, started.elapsed())
+                .with_error(&result);
+        output.report(&report)?;
+    }
+    result?;
+    Ok(())
+}
+
+// Performs the RPC handshake, as a JSON-RPC batch when methods were requested or as the node's method
+// when it was given one, and logs the response. A `getVersion` handshake is checked against the
+// expected version, if any. Failed handshakes are attempted again as the retry policy says, and the
+// path to the node is traced when the last attempt could not connect, if a trace was asked for.
+async fn shake_rpc_node(
+    rpc_node: &RpcNode,
+    methods: Option<Vec<String>>,
+    expect_version: Option<&VersionReq>,
+    retry: RetryPolicy,
+    traceroute: Option<Traceroute>,
+    output: &Output,
+) -> anyhow::Result<Outcome> {
+    let started = Instant::now();
+    match (methods, &rpc_node.method) {
+        (Some(methods), _) => {
+            // Issue every requested method in a single batch and log each result.
+            let batch = methods
+                .iter()
+                .fold(RpcBatchRequest::new(), |batch, method| batch.method(method));
+            let (result, timings, connection) =
+                timed(retry.run(&rpc_node.remote, || rpc_node.shake_batch(&batch, None))).await;
+            let mut report =
+                HandshakeReport::new(&rpc_node.remote, rpc_node.transport_type, started.elapsed())
+                    .with_error(&result)
+                    .with_timings(timings)
+                    .with_connection(connection);
+            if let Ok(results) = &result {
+                let details: serde_json::Map<String, serde_json::Value> = results
+                    .iter()
+                    .map(|r| {
+                        let value = match &r.result {
+                            Ok(value) => serde_json::json!({ "result": value }),
+                            Err(error) => serde_json::json!({ "error": error }),
+                        };
+                        (r.method.clone(), value)
+                    })
+                    .collect();
+                report = report.with_details(serde_json::Value::Object(details));
+            }
+            let report = trace_failure(report, result.as_ref().err(), rpc_node, traceroute).await;
+            output.report(&report)?;
+
+            for result in result? {
+                info!("{} response was {:?}", result.method, result.result);
+            }
+        }
+        (None, Some(method)) => {
+            // Report and log the result of the method called as the handshake, whatever its shape.
+            let (result, timings, connection) =
+                timed(retry.run(&rpc_node.remote, || rpc_node.shake_result(None))).await;
+            let mut report =
+                HandshakeReport::new(&rpc_node.remote, rpc_node.transport_type, started.elapsed())
+                    .with_error(&result)
+                    .with_timings(timings)
+                    .with_connection(connection);
+            if let Ok(value) = &result {
+                report = report.with_details(serde_json::json!({ method: { "result": value } }));
+            }
+            let report = trace_failure(report, result.as_ref().err(), rpc_node, traceroute).await;
+            output.report(&report)?;
+            info!("{} response was {:?}", method, result?);
+        }
+        (None, None) => {
+            // Report and log the response from the handshake operation.
+            let (result, timings, connection) =
+                timed(retry.run(&rpc_node.remote, || rpc_node.shake_typed(None))).await;
+            let mut report =
+                HandshakeReport::new(&rpc_node.remote, rpc_node.transport_type, started.elapsed())
+                    .with_version(&result)
+                    .with_timings(timings)
+                    .with_connection(connection);
+            if let (Ok(_), Some(requirement)) = (&result, expect_version) {
+                report = report.with_details(
+                    serde_json::json!({ "expected_version": requirement.to_string() }),
+                );
+            }
+            let report = trace_failure(report, result.as_ref().err(), rpc_node, traceroute).await;
+            output.report(&report)?;
+
+            let response = result?;
+            info!("Handshake response was {:?}", response);
+            if let Some(requirement) = expect_version {
+                if !version_satisfied(rpc_node, &response, requirement) {
+                    return Ok(Outcome::VersionMismatch);
+                }
+            }
+        }
+    }
+    Ok(Outcome::Passed)
+}
+
+// Traces the path to the node when its handshake failed to connect, attaching the hops to the report.
+// A trace that cannot run, e.g. without the privileges raw sockets require, leaves the report as it is.
+async fn trace_failure(
+    report: HandshakeReport,
+    error: Option<&HandshakeError>,
+    rpc_node: &RpcNode,
+    traceroute: Option<Traceroute>,
+) -> HandshakeReport {
+    let connecting = match error {
+        Some(HandshakeError::Connect(_)) => true,
+        Some(HandshakeError::Timeout { phase, .. }) => phase == "connect",
+        _ => false,
+    };
+    let (Some(traceroute), Some(address), true) =
+        (traceroute, rpc_node.remote.resolved.first(), connecting)
+    else {
+        return report;
+    };
+    info!(
+        "Tracing the path to {} over {}",
+        address, traceroute.protocol
+    );
+    let hops = match traceroute.trace(*address).await {
+        Ok(hops) => hops,
+        Err(e) => {
+            warn!("Could not trace the path to {}: {}", address, e);
+            return report;
+        }
+    };
+    for hop in &hops {
+        match (hop.address, hop.round_trip) {
+            (Some(address), Some(round_trip)) => {
+                info!("{:>2}  {}  {:?}", hop.ttl, address, round_trip)
+            }
+            _ => info!("{:>2}  *", hop.ttl),
+        }
+    }
+    let hops: Vec<serde_json::Value> = hops
+        .iter()
+        .map(|hop| {
+            serde_json::json!({
+                "ttl": hop.ttl,
+                "address": hop.address.map(|address| address.to_string()),
+                "rtt_ms": hop.round_trip.map(|round_trip| round_trip.as_secs_f64() * 1000.0),
+                "reached": hop.reached,
+            })
+        })
+        .collect();
+    report.with_details(serde_json::json!({ "traceroute": hops }))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            outcome_exit_code, CONNECT_EXIT_CODE, PARTIAL_FAILURE_EXIT_CODE, TIMEOUT_EXIT_CODE,
+        },
+        std::{
+            io::{self, ErrorKind},
+            net::SocketAddr,
+        },
+        tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        },
+    };
+
+    // A scan result of a target with the given handshake result.
+    fn scan_result(result: Result<RpcHandshakeResponse, HandshakeError>) -> ScanResult {
+        ScanResult {
+            target: ScanTarget::new(
+                "127.0.0.1:8899".parse::<SocketAddr>().unwrap(),
+                TransportType::Tcp,
+            ),
+            duration: Duration::ZERO,
+            timings: PhaseTimings::default(),
+            connection: ConnectionInfo::default(),
+            result,
+        }
+    }
+
+    fn answered() -> Result<RpcHandshakeResponse, HandshakeError> {
+        Ok(RpcHandshakeResponse {
+            solana_core: "1.18.6".to_string(),
+            feature_set: None,
+            timings: PhaseTimings::default(),
+        })
+    }
+
+    fn refused() -> Result<RpcHandshakeResponse, HandshakeError> {
+        Err(HandshakeError::Connect(io::Error::from(
+            ErrorKind::ConnectionRefused,
+        )))
+    }
+
+    fn timed_out() -> Result<RpcHandshakeResponse, HandshakeError> {
+        Err(HandshakeError::Timeout {
+            phase: "connect".to_string(),
+            seconds: 10,
+        })
+    }
+
+    // A scan every target answered passes.
+    #[test]
+    fn test_scan_passed() {
+        let outcome = scan_outcome(vec![scan_result(answered()), scan_result(answered())]);
+        assert_eq!(outcome_exit_code(&outcome), 0);
+    }
+
+    // A scan some targets answered exits with the partial failure code.
+    #[test]
+    fn test_scan_partially_failed() {
+        let outcome = scan_outcome(vec![scan_result(answered()), scan_result(refused())]);
+        assert_eq!(outcome_exit_code(&outcome), PARTIAL_FAILURE_EXIT_CODE);
+    }
+
+    // A scan no target answered exits with the code of the most common failure, the first on a tie.
+    #[test]
+    fn test_scan_failed() {
+        let results = vec![
+            scan_result(refused()),
+            scan_result(timed_out()),
+            scan_result(timed_out()),
+        ];
+        assert_eq!(outcome_exit_code(&scan_outcome(results)), TIMEOUT_EXIT_CODE);
+
+        let results = vec![scan_result(timed_out()), scan_result(refused())];
+        assert_eq!(outcome_exit_code(&scan_outcome(results)), TIMEOUT_EXIT_CODE);
+        let results = vec![scan_result(refused()), scan_result(timed_out())];
+        assert_eq!(outcome_exit_code(&scan_outcome(results)), CONNECT_EXIT_CODE);
+    }
+
+    // Starts an RPC server answering getVersion, or, when stalled, accepting connections but never answering.
+    async fn rpc_server(stalled: bool) -> Target {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut buf = vec![0; 4096];
+                    let n = socket.read(&mut buf).await.unwrap_or_default();
+                    if stalled {
+                        return tokio::time::sleep(Duration::from_secs(3600)).await;
+                    }
+                    let request = String::from_utf8_lossy(&buf[..n]);
+                    let (_, payload) = request.split_once("\r\n\r\n").unwrap_or_default();
+                    let id =
+                        serde_json::from_str::<serde_json::Value>(payload).unwrap()["id"].clone();
+                    let body = format!(
+                        r#"{{"jsonrpc":"2.0","result":{{"solana-core":"1.18.6","feature-set":1}},"id":{}}}"#,
+                        id
+                    );
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        Target::from(address)
+    }
+
+    // A race reports the side that answers as soon as it wins, then gives the stalled side the race's
+    // budget, well short of its own timeout, and reports it as timed out.
+    #[tokio::test]
+    async fn test_race_reports_winner_before_loser_ends() {
+        let racers = [rpc_server(true).await, rpc_server(false).await]
+            .map(|target| RpcNode::builder().address(target).build().unwrap());
+
+        let started = Instant::now();
+        let mut reported = Vec::new();
+        let ([stalled, answered], winner) =
+            run_race(&racers, RetryPolicy::default(), 1, |i, _, won| {
+                reported.push((i, won, started.elapsed()));
+                Ok(())
+            })
+            .await
+            .unwrap();
+        assert_eq!(winner, Some(1));
+        assert!(answered.0.is_ok());
+        match &stalled.0 {
+            Err(HandshakeError::Timeout { phase, seconds }) => {
+                assert_eq!((&phase[..], *seconds), ("race", 1))
+            }
+            other => panic!("Expected the race to time out, got {:?}", other),
+        }
+        let [(1, true, won_at), (0, false, lost_at)] = reported[..] else {
+            panic!(
+                "Expected the winner to be reported first, got {:?}",
+                reported
+            );
+        };
+        assert!(won_at < Duration::from_secs(1)); // Reported before the loser's budget ran out.
+        assert!(lost_at >= Duration::from_secs(1) && lost_at < Duration::from_secs(5));
+    }
+
+    // When the first side to finish fails, the race waits for the other.
+    #[tokio::test]
+    async fn test_race_waits_after_first_failure() {
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let refusing = Target::from(closed.local_addr().unwrap());
+        drop(closed);
+        let racers = [refusing, rpc_server(false).await]
+            .map(|target| RpcNode::new(target, TransportType::Tcp));
+
+        let ([refused, answered], winner) =
+            run_race(&racers, RetryPolicy::default(), 1, |_, _, _| Ok(()))
+                .await
+                .unwrap();
+        assert_eq!(winner, Some(1));
+        assert!(answered.0.is_ok());
+        assert!(matches!(refused.0, Err(HandshakeError::Connect(_))));
+    }
+}
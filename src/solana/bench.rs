@@ -0,0 +1,239 @@
+// Benchmarking of a node: a series of handshakes whose durations are summarized as latency
+// percentiles, overall and per phase, so RPC providers can be compared quantitatively.
+use {
+    crate::{
+        error::HandshakeError,
+        solana::{
+            cancel::Cancel,
+            node::Handshake,
+            transport::{timed, PhaseTimings},
+        },
+    },
+    std::time::{Duration, Instant},
+};
+
+// Default number of handshakes in a benchmark.
+pub const DEFAULT_BENCH_COUNT: usize = 100;
+
+// Reads the duration of a phase from the timings of a handshake.
+pub type PhaseDuration = fn(&PhaseTimings) -> Option<Duration>;
+
+// Phases whose latency is summarized, by name, with how to read each from the timings of a handshake.
+pub const PHASES: [(&str, PhaseDuration); 5] = [
+    ("resolve", |timings| timings.resolve),
+    ("connect", |timings| timings.connect),
+    ("tls", |timings| timings.tls),
+    ("write", |timings| timings.write),
+    ("read", |timings| timings.read),
+];
+
+// Minimum, mean, nearest-rank percentiles and maximum of a set of durations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyStats {
+    pub samples: usize, // Number of durations summarized.
+    pub min: Duration,  // Shortest duration.
+    pub mean: Duration, // Arithmetic mean.
+    pub p50: Duration,  // Median.
+    pub p95: Duration,  // 95th percentile.
+    pub p99: Duration,  // 99th percentile.
+    pub max: Duration,  // Longest duration.
+}
+
+impl LatencyStats {
+    // Summarizes the durations, or nothing when there are none.
+    pub fn from_durations(durations: impl IntoIterator<Item = Duration>) -> Option<Self> {
+        let mut sorted: Vec<Duration> = durations.into_iter().collect();
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort();
+        let total: Duration = sorted.iter().sum();
+        Some(LatencyStats {
+            samples: sorted.len(),
+            min: sorted[0],
+            mean: total / sorted.len() as u32,
+            p50: percentile(&sorted, 50),
+            p95: percentile(&sorted, 95),
+            p99: percentile(&sorted, 99),
+            max: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+// The nearest-rank percentile of sorted durations: the smallest one that at least the given percentage
+// of them do not exceed.
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (percent * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+// Outcome of the handshakes of a benchmark.
+#[derive(Debug, Default)]
+pub struct BenchResult {
+    pub samples: Vec<(Duration, PhaseTimings)>, // Duration and phase timings of each successful handshake.
+    pub failures: Vec<HandshakeError>,          // Why each failed handshake failed.
+}
+
+impl BenchResult {
+    // Adds the outcome of a handshake.
+    pub fn record<T>(
+        &mut self,
+        duration: Duration,
+        timings: PhaseTimings,
+        result: Result<T, HandshakeError>,
+    ) {
+        match result {
+            Ok(_) => self.samples.push((duration, timings)),
+            Err(e) => self.failures.push(e),
+        }
+    }
+
+    // Adds the outcomes of other handshakes.
+    pub fn merge(&mut self, other: BenchResult) {
+        self.samples.extend(other.samples);
+        self.failures.extend(other.failures);
+    }
+
+    // Number of handshakes made, successful or not.
+    pub fn count(&self) -> usize {
+        self.samples.len() + self.failures.len()
+    }
+
+    // Latency of the successful handshakes as a whole.
+    pub fn latency(&self) -> Option<LatencyStats> {
+        LatencyStats::from_durations(self.samples.iter().map(|(duration, _)| *duration))
+    }
+
+    // Latency of a phase of the successful handshakes that went through it.
+    pub fn phase_latency(&self, phase: PhaseDuration) -> Option<LatencyStats> {
+        LatencyStats::from_durations(
+            self.samples
+                .iter()
+                .filter_map(|(_, timings)| phase(timings)),
+        )
+    }
+}
+
+// Handshakes with the node the given number of times, one after another, so each handshake's
+// latency is not skewed by the others.
+pub async fn bench(node: &impl Handshake, count: usize, timeout: Option<u32>) -> BenchResult {
+    bench_until(node, count, timeout, &Cancel::new()).await
+}
+
+// Benchmarks the node like `bench`, stopping early when the signal is fired. The handshake in flight
+// is dropped, and the result holds the handshakes that finished before.
+pub async fn bench_until(
+    node: &impl Handshake,
+    count: usize,
+    timeout: Option<u32>,
+    cancel: &Cancel,
+) -> BenchResult {
+    let mut result = BenchResult::default();
+    for _ in 0..count {
+        let started = Instant::now();
+        let Some((outcome, timings, _)) = cancel.run(timed(node.shake(timeout))).await else {
+            break;
+        };
+        result.record(started.elapsed(), timings, outcome);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        async_trait::async_trait,
+        std::sync::atomic::{AtomicUsize, Ordering},
+        test_case::test_case,
+    };
+
+    // Durations of 1 to 100 milliseconds, shuffled.
+    fn millis() -> Vec<Duration> {
+        (1..=100)
+            .map(|n| Duration::from_millis((n * 37) % 100 + 1))
+            .collect()
+    }
+
+    // Percentiles are the nearest-rank values.
+    #[test]
+    fn test_latency_stats() {
+        let stats = LatencyStats::from_durations(millis()).unwrap();
+        assert_eq!(stats.samples, 100);
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.mean, Duration::from_micros(50_500));
+        assert_eq!(stats.p50, Duration::from_millis(50));
+        assert_eq!(stats.p95, Duration::from_millis(95));
+        assert_eq!(stats.p99, Duration::from_millis(99));
+        assert_eq!(stats.max, Duration::from_millis(100));
+        assert_eq!(LatencyStats::from_durations([]), None);
+    }
+
+    // Ranks falling between two samples are rounded up to the next one.
+    #[test_case(&[7], 99, 7; "when there is one sample")]
+    #[test_case(&[1, 2, 3, 4], 50, 2; "when rank falls on a sample")]
+    #[test_case(&[1, 2, 3, 4], 60, 3; "when rank is rounded up")]
+    fn test_percentile(sorted: &[u64], percent: usize, expected: u64) {
+        let sorted: Vec<Duration> = sorted.iter().map(|&n| Duration::from_millis(n)).collect();
+        assert_eq!(
+            percentile(&sorted, percent),
+            Duration::from_millis(expected)
+        );
+    }
+
+    // Failures are counted apart, and phases only summarize the handshakes that went through them.
+    #[test]
+    fn test_bench_result() {
+        let mut result = BenchResult::default();
+        let connect = |millis| PhaseTimings {
+            connect: Some(Duration::from_millis(millis)),
+            ..PhaseTimings::default()
+        };
+        result.record(Duration::from_millis(10), connect(4), Ok(()));
+        result.record(Duration::from_millis(20), connect(6), Ok(()));
+        result.record(Duration::from_millis(30), PhaseTimings::default(), Ok(()));
+        result.record::<()>(
+            Duration::from_millis(60),
+            PhaseTimings::default(),
+            Err(HandshakeError::Protocol("bad".to_string())),
+        );
+
+        assert_eq!(result.count(), 4);
+        assert_eq!(result.failures.len(), 1);
+        assert_eq!(result.latency().unwrap().mean, Duration::from_millis(20));
+        let connect = result.phase_latency(PHASES[1].1).unwrap();
+        assert_eq!(
+            (connect.samples, connect.max),
+            (2, Duration::from_millis(6))
+        );
+        assert_eq!(result.phase_latency(PHASES[2].1), None);
+    }
+
+    // A node cancelling the benchmark on its third handshake.
+    struct Cancelling {
+        shakes: AtomicUsize, // Handshakes made so far.
+        cancel: Cancel,      // Signal fired on the third.
+    }
+
+    #[async_trait]
+    impl Handshake for Cancelling {
+        async fn shake(&self, _timeout: Option<u32>) -> Result<String, HandshakeError> {
+            if self.shakes.fetch_add(1, Ordering::SeqCst) == 2 {
+                self.cancel.cancel();
+            }
+            Ok("{}".to_string())
+        }
+    }
+
+    // A cancelled benchmark keeps the handshakes that finished and starts no others.
+    #[tokio::test]
+    async fn test_bench_until_cancelled() {
+        let node = Cancelling {
+            shakes: AtomicUsize::new(0),
+            cancel: Cancel::new(),
+        };
+        let result = bench_until(&node, 10, None, &node.cancel).await;
+        assert_eq!(result.count(), 3);
+        assert_eq!(node.shakes.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,118 @@
+// Cancellation of a run's handshakes, e.g. on Ctrl+C, so it can stop early with the results gathered so far,
+// or on the shutdown of a service embedding the library, so it is not held up by a slow node.
+// Handshakes in flight are cancelled by dropping them, which closes their connections, so the
+// transports need no plumbing of their own.
+use {
+    crate::error::HandshakeError,
+    std::{future::Future, sync::Arc},
+    tokio::sync::watch,
+};
+
+// A signal shared by the handshakes of a run; every clone observes the same signal.
+#[derive(Debug, Clone)]
+pub struct Cancel {
+    sender: Arc<watch::Sender<bool>>, // Whether the run was cancelled.
+}
+
+impl Cancel {
+    // Constructs a signal not fired yet.
+    pub fn new() -> Self {
+        Cancel {
+            sender: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    // Fires the signal: the handshakes in flight are dropped and no others start.
+    pub fn cancel(&self) {
+        self.sender.send_replace(true);
+    }
+
+    // Indicates whether the signal was fired.
+    pub fn is_cancelled(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    // Waits until the signal is fired, returning at once if it already was.
+    pub async fn cancelled(&self) {
+        let mut receiver = self.sender.subscribe();
+        // The sender lives as long as the signal, so it is never closed while waiting.
+        let _ = receiver.wait_for(|cancelled| *cancelled).await;
+    }
+
+    // Runs the future to completion, unless the signal is fired first, in which case it is dropped.
+    pub async fn run<F: Future>(&self, future: F) -> Option<F::Output> {
+        tokio::select! {
+            biased;
+            _ = self.cancelled() => None,
+            output = future => Some(output),
+        }
+    }
+
+    // Runs a handshake, or any other call returning a handshake result, failing with
+    // `HandshakeError::Cancelled` when the signal is fired first, e.g. `cancel.guard(node.shake_typed(None))`.
+    pub async fn guard<T, F>(&self, future: F) -> Result<T, HandshakeError>
+    where
+        F: Future<Output = Result<T, HandshakeError>>,
+    {
+        self.run(future)
+            .await
+            .unwrap_or(Err(HandshakeError::Cancelled))
+    }
+}
+
+impl Default for Cancel {
+    fn default() -> Self {
+        Cancel::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::solana::node::Handshake, async_trait::async_trait, std::time::Duration};
+
+    // Futures complete unless the signal is fired first, which every clone observes.
+    #[tokio::test]
+    async fn test_run() {
+        let cancel = Cancel::new();
+        assert_eq!(cancel.run(async { 42 }).await, Some(42));
+
+        let clone = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            clone.cancel();
+        });
+        let pending = std::future::pending::<()>();
+        assert_eq!(cancel.run(pending).await, None);
+        assert!(cancel.is_cancelled());
+
+        // Once fired, nothing else starts.
+        assert_eq!(cancel.run(async { 42 }).await, None);
+    }
+
+    // A node that never answers.
+    struct Silent;
+
+    #[async_trait]
+    impl Handshake for Silent {
+        async fn shake(&self, _timeout: Option<u32>) -> Result<String, HandshakeError> {
+            std::future::pending().await
+        }
+    }
+
+    // A handshake aborted by the signal fails as cancelled.
+    #[tokio::test]
+    async fn test_shake_with_cancel() {
+        let cancel = Cancel::new();
+        let clone = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            clone.cancel();
+        });
+        let result = Silent.shake_with_cancel(None, &cancel).await;
+        assert!(matches!(result, Err(HandshakeError::Cancelled)));
+        assert!(matches!(
+            cancel.guard(async { Ok(42) }).await,
+            Err(HandshakeError::Cancelled)
+        ));
+    }
+}
@@ -0,0 +1,118 @@
+// Solana's handshake as a chain: `getVersion` over JSON-RPC, reporting the solana-core version and feature set.
+use crate::{
+    chain::{ChainHandshake, NodeInfo},
+    error::HandshakeError,
+    solana::rpc::message::{request_id, RpcHandshakeRequest, RpcHandshakeResponse, RpcResponse},
+};
+
+// The Solana network, handshaken with a request whose id the response must echo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Solana {
+    id: u64, // Id of the `getVersion` request.
+}
+
+impl Solana {
+    // Constructs the handshake under a random request id.
+    pub fn new() -> Self {
+        Solana::with_id(request_id())
+    }
+
+    // Constructs the handshake under the given request id.
+    pub fn with_id(id: u64) -> Self {
+        Solana { id }
+    }
+}
+
+impl Default for Solana {
+    fn default() -> Self {
+        Solana::new()
+    }
+}
+
+impl ChainHandshake for Solana {
+    // Requesting the version of the solana-core over JSON RPC version 2.0.
+    fn payload(&self) -> serde_json::Value {
+        serde_json::json!(RpcHandshakeRequest::new(self.id, "getVersion"))
+    }
+
+    // Reads the `getVersion` result, failing on JSON-RPC errors and on responses of another shape.
+    fn parse(&self, raw: &str) -> Result<NodeInfo, HandshakeError> {
+        let response: RpcHandshakeResponse =
+            RpcResponse::parse_validated(raw, self.id, &RpcHandshakeResponse::shape())?;
+        Ok(NodeInfo {
+            chain: "solana".to_string(),
+            version: response.solana_core,
+            details: serde_json::json!({ "feature_set": response.feature_set }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{chain, solana::transport::mock::MockTransport},
+    };
+
+    // The version and feature set of a `getVersion` result are reported.
+    #[test]
+    fn test_parse() {
+        let raw = r#"{"jsonrpc":"2.0","result":{"feature-set":3580551090,"solana-core":"1.18.6"},"id":1}"#;
+        let info = Solana::with_id(1).parse(raw).unwrap();
+
+        assert_eq!(info.chain, "solana");
+        assert_eq!(info.version, "1.18.6");
+        assert_eq!(info.details["feature_set"], 3580551090u64);
+    }
+
+    // A response to another request is a protocol error, and each handshake asks under its own id.
+    #[test]
+    fn test_parse_other_id() {
+        let raw = r#"{"jsonrpc":"2.0","result":{"solana-core":"1.18.6"},"id":2}"#;
+        let error = Solana::with_id(1).parse(raw).unwrap_err();
+        assert!(matches!(error, HandshakeError::Protocol(_)), "{}", error);
+        assert!(Solana::with_id(2).parse(raw).is_ok());
+
+        let ids: Vec<_> = (0..4)
+            .map(|_| Solana::new().payload()["id"].clone())
+            .collect();
+        assert!(ids.iter().any(|id| *id != ids[0]));
+    }
+
+    // The handshake echoes its own request id over any transport, and transport failures are its own.
+    #[tokio::test]
+    async fn test_shake_over_mock() {
+        let transport = MockTransport::new()
+            .with_reply(|payload| {
+                Ok(format!(
+                    r#"{{"jsonrpc":"2.0","result":{{"solana-core":"1.18.6"}},"id":{}}}"#,
+                    payload["id"]
+                ))
+            })
+            .with_error(HandshakeError::Timeout {
+                phase: "read".to_string(),
+                seconds: 5,
+            });
+        let info = chain::shake(&Solana::new(), &transport, None)
+            .await
+            .unwrap();
+        assert_eq!(info.version, "1.18.6");
+        assert_eq!(transport.requests()[0]["method"], "getVersion");
+
+        let error = chain::shake(&Solana::new(), &transport, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, HandshakeError::Timeout { .. }));
+    }
+
+    // A JSON-RPC error is not a version.
+    #[test]
+    fn test_parse_error() {
+        let raw =
+            r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found"},"id":1}"#;
+        assert!(matches!(
+            Solana::with_id(1).parse(raw),
+            Err(HandshakeError::Rpc { .. })
+        ));
+    }
+}
@@ -0,0 +1,115 @@
+// Presets for the public Solana clusters, expanding a cluster's name into the host, port and security of
+// its official RPC and PubSub endpoints, so they need not be typed out.
+use {
+    crate::solana::target::Target,
+    std::{fmt, str::FromStr},
+};
+
+// A Solana cluster with well-known endpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cluster {
+    // The main cluster.
+    MainnetBeta,
+    // The cluster validators test releases on.
+    Testnet,
+    // The cluster application developers test on.
+    Devnet,
+    // A test validator on the local machine, with its default ports.
+    Localnet,
+}
+
+impl Cluster {
+    // Host and port of the cluster's JSON RPC endpoint.
+    pub fn rpc_endpoint(&self) -> &'static str {
+        match self {
+            Cluster::MainnetBeta => "api.mainnet-beta.solana.com:443",
+            Cluster::Testnet => "api.testnet.solana.com:443",
+            Cluster::Devnet => "api.devnet.solana.com:443",
+            Cluster::Localnet => "127.0.0.1:8899",
+        }
+    }
+
+    // Host and port of the cluster's PubSub endpoint; the public clusters serve it on the RPC endpoint.
+    pub fn pubsub_endpoint(&self) -> &'static str {
+        match self {
+            Cluster::Localnet => "127.0.0.1:8900",
+            public => public.rpc_endpoint(),
+        }
+    }
+
+    // Indicates whether the endpoints are reached over TLS.
+    pub fn is_secure(&self) -> bool {
+        !matches!(self, Cluster::Localnet)
+    }
+
+    // The JSON RPC endpoint or, with `websocket`, the PubSub endpoint, resolved when it is handshaken.
+    pub fn target(&self, websocket: bool) -> Target {
+        let endpoint = match websocket {
+            true => self.pubsub_endpoint(),
+            false => self.rpc_endpoint(),
+        };
+        Target::parse(endpoint).expect("cluster endpoints are valid targets")
+    }
+}
+
+// Implement the Display trait for Cluster, using the names accepted on the command line.
+impl fmt::Display for Cluster {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Cluster::MainnetBeta => "mainnet-beta",
+            Cluster::Testnet => "testnet",
+            Cluster::Devnet => "devnet",
+            Cluster::Localnet => "localnet",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Clusters are named as on the command line, or by the monikers of the Solana CLI's `--url`.
+impl FromStr for Cluster {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "mainnet" | "mainnet-beta" | "m" => Ok(Cluster::MainnetBeta),
+            "testnet" | "t" => Ok(Cluster::Testnet),
+            "devnet" | "d" => Ok(Cluster::Devnet),
+            "localnet" | "localhost" | "l" => Ok(Cluster::Localnet),
+            _ => Err(format!(
+                "Unknown cluster '{}': expected mainnet, testnet, devnet or localnet",
+                name
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, test_case::test_case};
+
+    // Clusters are found by name or moniker, and display under their canonical name.
+    #[test_case("mainnet", Cluster::MainnetBeta; "when name is mainnet")]
+    #[test_case("m", Cluster::MainnetBeta; "when name is a moniker")]
+    #[test_case("devnet", Cluster::Devnet; "when name is devnet")]
+    #[test_case("localhost", Cluster::Localnet; "when name is localhost")]
+    fn test_from_str(name: &str, expected: Cluster) {
+        let cluster: Cluster = name.parse().unwrap();
+        assert_eq!(cluster, expected);
+        assert_eq!(cluster.to_string().parse::<Cluster>().unwrap(), expected);
+    }
+
+    // Public clusters serve PubSub on their secure RPC endpoint; a local validator on the next port, in the clear.
+    #[test]
+    fn test_endpoints() {
+        assert_eq!(
+            Cluster::Testnet.pubsub_endpoint(),
+            Cluster::Testnet.rpc_endpoint()
+        );
+        assert!(Cluster::Testnet.is_secure());
+
+        let pubsub = Cluster::Localnet.target(true);
+        assert_eq!(pubsub.to_string(), "127.0.0.1:8900");
+        assert!(!Cluster::Localnet.is_secure());
+        assert!("mainnet-alpha".parse::<Cluster>().is_err());
+    }
+}
@@ -0,0 +1,89 @@
+// Cluster discovery through an entrypoint's `getClusterNodes` RPC method.
+// Every node in the cluster advertises its gossip, TPU and (optionally) RPC addresses,
+// so a single reachable entrypoint is enough to find the rest of the cluster.
+use {
+    crate::{
+        error::HandshakeError,
+        solana::{rpc::node::RpcNode, scan::ScanTarget, TransportType},
+    },
+    serde::{Deserialize, Serialize}, // Deserialization of the getClusterNodes result.
+    std::net::SocketAddr,
+    tracing::info, // Import the `info` macro for logging informational messages.
+};
+
+// A node as advertised by `getClusterNodes`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterNode {
+    pub pubkey: String,             // Identity pubkey of the node, base58-encoded.
+    pub gossip: Option<String>,     // Gossip address, if advertised.
+    pub tpu: Option<String>,        // TPU address, if advertised.
+    pub rpc: Option<String>,        // JSON RPC address, only advertised by nodes serving RPC.
+    pub version: Option<String>,    // Software version reported through gossip.
+    pub feature_set: Option<u64>,   // Feature set reported through gossip.
+    pub shred_version: Option<u16>, // Shred version, identifying the cluster fork.
+}
+
+impl ClusterNode {
+    // Returns the advertised RPC address, if the node serves RPC and the address is valid.
+    pub fn rpc_address(&self) -> Option<SocketAddr> {
+        self.rpc.as_deref()?.parse().ok()
+    }
+}
+
+// Handshakes the entrypoint and asks it for the nodes of its cluster.
+pub async fn discover(
+    entrypoint: &RpcNode,
+    timeout: Option<u32>,
+) -> Result<Vec<ClusterNode>, HandshakeError> {
+    let version = entrypoint.shake_typed(timeout).await?;
+    info!(
+        "Entrypoint {} is running solana-core {}",
+        entrypoint, version.solana_core
+    );
+
+    let nodes: Vec<ClusterNode> = entrypoint.call("getClusterNodes", timeout).await?;
+    info!("Discovered {} cluster nodes", nodes.len());
+    Ok(nodes)
+}
+
+// Builds scan targets for every node advertising an RPC address.
+// Advertised addresses are bare IPs, which cannot be validated by TLS, so plain TCP is used.
+pub fn rpc_targets(nodes: &[ClusterNode]) -> Vec<ScanTarget> {
+    nodes
+        .iter()
+        .filter_map(|node| {
+            node.rpc_address().map(|address| {
+                ScanTarget::new(address, TransportType::Tcp).with_label(node.pubkey.clone())
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::solana::{rpc::node::RpcResponse, target::Target},
+    };
+
+    // A getClusterNodes result is parsed, and only nodes serving RPC become scan targets.
+    #[test]
+    fn test_rpc_targets_from_cluster_nodes() {
+        let body = r#"{"jsonrpc":"2.0","result":[
+            {"featureSet":3580551090,"gossip":"10.0.0.1:8001","pubkey":"A1","rpc":"10.0.0.1:8899","shredVersion":50093,"tpu":"10.0.0.1:8003","version":"1.18.6"},
+            {"featureSet":3580551090,"gossip":"10.0.0.2:8001","pubkey":"B2","rpc":null,"shredVersion":50093,"tpu":"10.0.0.2:8003","version":"1.18.6"}
+        ],"id":1}"#;
+        let nodes: Vec<ClusterNode> = RpcResponse::parse_result(body).unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].feature_set, Some(3580551090));
+
+        let targets = rpc_targets(&nodes);
+        assert_eq!(targets.len(), 1);
+        assert_eq!(
+            targets[0].address,
+            Target::from("10.0.0.1:8899".parse::<SocketAddr>().unwrap())
+        );
+        assert_eq!(targets[0].label.as_deref(), Some("A1"));
+    }
+}
@@ -0,0 +1,4 @@
+// Geyser node implementation
+pub mod node;
+// Geyser gRPC messages and client
+pub mod proto;
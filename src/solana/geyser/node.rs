@@ -0,0 +1,403 @@
+// Module for verifying a Yellowstone/Geyser gRPC endpoint, served by a Geyser plugin running in a validator.
+use crate::error::HandshakeError; // Crate-wide error type reported by failed handshakes.
+use crate::solana::{
+    geyser::proto::GeyserClient,
+    node::Handshake,
+    target::Target,
+    transport::{
+        grpc::{convert_status, grpc_connect},
+        tcp::{ConnectOptions, Connectable},
+        traced, with_timeout,
+    },
+}; // Import necessary traits and structures for handshake and transport.
+use async_trait::async_trait; // Enables async trait methods, crucial for async network operations.
+use std::fmt; // Standard library import for display formatting.
+use tonic::metadata::AsciiMetadataValue; // The access token sent as gRPC metadata.
+use tracing::info; // Logging macros for informational logging.
+
+// Count sent with the handshake's `Ping`, which the server is expected to echo back.
+const PING_COUNT: i32 = 1;
+
+// Struct defining a Geyser node, reached over gRPC with or without TLS.
+pub struct GeyserNode {
+    pub remote: Target, // The remote endpoint: its name as given and the addresses it resolved to.
+    pub is_secure: bool, // Whether the gRPC connection runs over TLS.
+    pub connect: ConnectOptions, // Proxy and TLS options of the gRPC connection.
+    pub x_token: Option<String>, // Access token sent as the `x-token` header, as hosted endpoints require.
+}
+
+impl GeyserNode {
+    // Constructor for GeyserNode, taking a target, or a socket address, and whether to use TLS.
+    pub fn new(remote: impl Into<Target>, is_secure: bool) -> Self {
+        GeyserNode {
+            remote: remote.into(),
+            is_secure,
+            connect: ConnectOptions::default(),
+            x_token: None,
+        }
+    }
+
+    // Sets the access token sent with every call.
+    pub fn with_x_token(mut self, x_token: Option<String>) -> Self {
+        self.x_token = x_token;
+        self
+    }
+
+    // Connects to the node and returns a client sending the access token, which must be a valid header value.
+    async fn client(&self, timeout: Option<u32>) -> Result<GeyserClient, HandshakeError> {
+        let x_token = self
+            .x_token
+            .as_deref()
+            .map(AsciiMetadataValue::try_from)
+            .transpose()
+            .map_err(|_| {
+                HandshakeError::Protocol("The x-token is not a valid header value".to_string())
+            })?;
+        let remote = self.remote.to_string();
+        let channel = grpc_connect(
+            &remote,
+            self.is_secure,
+            &self.connect.route(&self.remote),
+            &self.connect.tls,
+            timeout,
+        )
+        .await?;
+        Ok(GeyserClient::new(channel, x_token))
+    }
+
+    // Transport name used in spans and reports.
+    pub fn transport(&self) -> &'static str {
+        match self.is_secure {
+            false => "grpc",
+            true => "grpcs",
+        }
+    }
+}
+
+impl Connectable for GeyserNode {
+    fn connect_options(&mut self) -> &mut ConnectOptions {
+        &mut self.connect
+    }
+}
+
+// Implement the Display trait for GeyserNode for easy logging and debugging.
+impl fmt::Display for GeyserNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GeyserNode({})", self.remote) // Custom display format showing the remote address.
+    }
+}
+
+// Async trait for performing the handshake operation: `GetVersion` followed by a `Ping`,
+// whose count must be echoed back. The version document reported by the plugin is returned.
+#[async_trait]
+impl Handshake for GeyserNode {
+    async fn shake(&self, timeout: Option<u32>) -> Result<String, HandshakeError> {
+        traced(&self.remote, self.transport(), async {
+            let mut client = self.client(timeout).await?;
+            let version = with_timeout("request", timeout, async {
+                client.get_version().await.map_err(convert_status)
+            })
+            .await?
+            .version;
+            info!("Geyser version is {}", version);
+
+            let pong = with_timeout("request", timeout, async {
+                client.ping(PING_COUNT).await.map_err(convert_status)
+            })
+            .await?;
+            match pong.count {
+                PING_COUNT => Ok(version),
+                count => Err(HandshakeError::InvalidResponse(format!(
+                    "Ping count {} was answered with {}",
+                    PING_COUNT, count
+                ))),
+            }
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Test suite for GeyserNode functionality, run against an in-process gRPC server.
+    use {
+        super::*,
+        crate::solana::geyser::proto::{
+            GetVersionRequest, GetVersionResponse, PingRequest, PongResponse,
+        },
+        crate::solana::transport::{
+            tcp::{crypto_provider, TlsOptions},
+            timed,
+        },
+        std::{
+            convert::Infallible,
+            future::{ready, Ready},
+            io,
+            net::SocketAddr,
+            pin::Pin,
+            sync::Arc,
+            task::{Context, Poll},
+        },
+        tokio::{
+            io::{AsyncRead, AsyncWrite, ReadBuf},
+            net::{TcpListener, TcpStream},
+        },
+        tokio_rustls::{
+            rustls::{
+                pki_types::{CertificateDer, PrivateKeyDer},
+                ServerConfig,
+            },
+            server::TlsStream,
+            TlsAcceptor,
+        },
+        tonic::{
+            body::BoxBody,
+            codec::ProstCodec,
+            codegen::{http, BoxFuture, Service},
+            server::{Grpc, NamedService, UnaryService},
+            transport::{server::Connected, Body, Server},
+            Request, Response, Status,
+        },
+    };
+
+    // Answers a unary call with the given function.
+    struct Unary<F>(F);
+
+    impl<Req, Res, F> UnaryService<Req> for Unary<F>
+    where
+        F: FnMut(Req) -> Res,
+    {
+        type Response = Res;
+        type Future = Ready<Result<Response<Res>, Status>>;
+
+        fn call(&mut self, request: Request<Req>) -> Self::Future {
+            ready(Ok(Response::new((self.0)(request.into_inner()))))
+        }
+    }
+
+    // A Geyser service answering `GetVersion` and `Ping`, requiring the token when one is set.
+    #[derive(Clone)]
+    struct FakeGeyser {
+        x_token: Option<&'static str>, // Token the calls must carry.
+        pong: i32,                     // Count answered to every `Ping`.
+    }
+
+    impl NamedService for FakeGeyser {
+        const NAME: &'static str = "geyser.Geyser";
+    }
+
+    impl Service<http::Request<Body>> for FakeGeyser {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+            let pong = self.pong;
+            let authorised = match self.x_token {
+                Some(expected) => request
+                    .headers()
+                    .get("x-token")
+                    .is_some_and(|token| token == expected),
+                None => true,
+            };
+            Box::pin(async move {
+                let response = match request.uri().path() {
+                    _ if !authorised => Status::unauthenticated("Invalid x-token").to_http(),
+                    "/geyser.Geyser/GetVersion" => {
+                        let version = Unary(|_: GetVersionRequest| GetVersionResponse {
+                            version: r#"{"version":"1.14.1"}"#.to_string(),
+                        });
+                        Grpc::new(ProstCodec::default())
+                            .unary(version, request)
+                            .await
+                    }
+                    "/geyser.Geyser/Ping" => {
+                        let ping = Unary(|_: PingRequest| PongResponse { count: pong });
+                        Grpc::new(ProstCodec::default()).unary(ping, request).await
+                    }
+                    _ => Status::unimplemented("Unknown method").to_http(),
+                };
+                Ok(response)
+            })
+        }
+    }
+
+    // Serves the Geyser service on a local port and returns its address.
+    async fn serve(geyser: FakeGeyser) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let incoming = futures_util::stream::unfold(listener, |listener| async {
+            let accepted = listener.accept().await.map(|(socket, _)| socket);
+            Some((accepted, listener))
+        });
+        tokio::spawn(
+            Server::builder()
+                .add_service(geyser)
+                .serve_with_incoming(incoming),
+        );
+        address
+    }
+
+    // A TLS connection served to tonic, which only serves streams of its own TLS implementation otherwise.
+    struct TlsConnection(TlsStream<TcpStream>);
+
+    impl Connected for TlsConnection {
+        type ConnectInfo = ();
+
+        fn connect_info(&self) -> Self::ConnectInfo {}
+    }
+
+    impl AsyncRead for TlsConnection {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.0).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for TlsConnection {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.0).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.0).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.0).poll_shutdown(cx)
+        }
+    }
+
+    // Serves the Geyser service over TLS with the certificate, selecting h2 through ALPN as gRPC servers do,
+    // and returns its address.
+    async fn serve_tls(geyser: FakeGeyser, cert: &rcgen::Certificate) -> SocketAddr {
+        let mut config = ServerConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![CertificateDer::from(cert.serialize_der().unwrap())],
+                PrivateKeyDer::Pkcs8(cert.serialize_private_key_der().into()),
+            )
+            .unwrap();
+        config.alpn_protocols = vec![b"h2".to_vec()];
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let incoming = futures_util::stream::unfold(listener, move |listener| {
+            let acceptor = acceptor.clone();
+            async move {
+                let accepted = match listener.accept().await {
+                    Ok((socket, _)) => acceptor.accept(socket).await.map(TlsConnection),
+                    Err(e) => Err(e),
+                };
+                Some((accepted, listener))
+            }
+        });
+        tokio::spawn(
+            Server::builder()
+                .add_service(geyser)
+                .serve_with_incoming(incoming),
+        );
+        address
+    }
+
+    // Verifies that a GeyserNode can be correctly instantiated and displayed.
+    #[test]
+    fn test_geyser_node_creation() {
+        let addr: SocketAddr = "127.0.0.1:10000".parse().unwrap();
+        let node = GeyserNode::new(addr, true);
+
+        assert_eq!(node.transport(), "grpcs");
+        assert_eq!(node.to_string(), "GeyserNode(127.0.0.1:10000)"); // Checks the display format.
+    }
+
+    // The handshake returns the version reported by the plugin.
+    #[tokio::test]
+    async fn test_geyser_handshake() {
+        let address = serve(FakeGeyser {
+            x_token: None,
+            pong: PING_COUNT,
+        })
+        .await;
+
+        let version = GeyserNode::new(address, false).shake(Some(5)).await;
+        assert_eq!(version.unwrap(), r#"{"version":"1.14.1"}"#);
+    }
+
+    // Over TLS, the certificate is trusted through the CA file and the calls go over the h2 selected through ALPN.
+    #[tokio::test]
+    async fn test_geyser_handshake_over_tls() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let ca_file =
+            std::env::temp_dir().join(format!("handshake-geyser-ca-{}.pem", std::process::id()));
+        std::fs::write(&ca_file, cert.serialize_pem().unwrap()).unwrap();
+        let geyser = FakeGeyser {
+            x_token: None,
+            pong: PING_COUNT,
+        };
+        let address = serve_tls(geyser, &cert).await;
+
+        let mut node = GeyserNode::new(address, true);
+        node.connect.tls = TlsOptions {
+            ca_file: Some(ca_file.clone()),
+            server_name: Some("localhost".to_string()),
+            ..TlsOptions::default()
+        };
+        let (version, _, connection) = timed(node.shake(Some(5))).await;
+
+        std::fs::remove_file(ca_file).unwrap();
+        assert_eq!(version.unwrap(), r#"{"version":"1.14.1"}"#);
+        assert_eq!(connection.protocol.as_deref(), Some("h2"));
+    }
+
+    // Calls carry the access token; without it, an endpoint requiring one fails the handshake.
+    #[tokio::test]
+    async fn test_x_token_is_sent() {
+        let address = serve(FakeGeyser {
+            x_token: Some("secret"),
+            pong: PING_COUNT,
+        })
+        .await;
+
+        let error = GeyserNode::new(address, false)
+            .shake(Some(5))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, HandshakeError::Protocol(_)));
+        assert!(error.to_string().contains("Unauthenticated"));
+
+        let result = GeyserNode::new(address, false)
+            .with_x_token(Some("secret".to_string()))
+            .shake(Some(5))
+            .await;
+        assert!(result.is_ok());
+    }
+
+    // A Ping answered with the wrong count is an invalid response.
+    #[tokio::test]
+    async fn test_wrong_pong_is_invalid_response() {
+        let address = serve(FakeGeyser {
+            x_token: None,
+            pong: PING_COUNT + 1,
+        })
+        .await;
+
+        let error = GeyserNode::new(address, false)
+            .shake(Some(5))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, HandshakeError::InvalidResponse(_)));
+    }
+}
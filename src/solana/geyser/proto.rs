@@ -0,0 +1,88 @@
+// Messages and client of the Yellowstone `geyser.Geyser` gRPC service, as far as the handshake needs them.
+// They are written out rather than generated from `geyser.proto`, as only two unary calls are used
+// and the build then needs no protobuf compiler.
+use {
+    prost::Message, // Protobuf encoding of the messages.
+    tonic::{
+        client::Grpc, codec::ProstCodec, codegen::http::uri::PathAndQuery,
+        metadata::AsciiMetadataValue, transport::Channel, Request, Status,
+    }, // Import the tonic client and gRPC types.
+};
+
+// Request of `Ping`; the server echoes the count back.
+#[derive(Clone, PartialEq, Message)]
+pub struct PingRequest {
+    #[prost(int32, tag = "1")]
+    pub count: i32,
+}
+
+// Response of `Ping`.
+#[derive(Clone, PartialEq, Message)]
+pub struct PongResponse {
+    #[prost(int32, tag = "1")]
+    pub count: i32,
+}
+
+// Request of `GetVersion`, which takes no parameters.
+#[derive(Clone, PartialEq, Message)]
+pub struct GetVersionRequest {}
+
+// Response of `GetVersion`, a JSON document describing the plugin and the node it runs in.
+#[derive(Clone, PartialEq, Message)]
+pub struct GetVersionResponse {
+    #[prost(string, tag = "1")]
+    pub version: String,
+}
+
+// Client of the `geyser.Geyser` service, sending the access token of the endpoint, if any, with every call.
+pub struct GeyserClient {
+    inner: Grpc<Channel>,                // Underlying gRPC client.
+    x_token: Option<AsciiMetadataValue>, // Value of the `x-token` header expected by hosted endpoints.
+}
+
+impl GeyserClient {
+    // Constructs a client issuing calls over the channel.
+    pub fn new(channel: Channel, x_token: Option<AsciiMetadataValue>) -> Self {
+        GeyserClient {
+            inner: Grpc::new(channel),
+            x_token,
+        }
+    }
+
+    // Calls `Ping`, returning the count echoed back.
+    pub async fn ping(&mut self, count: i32) -> Result<PongResponse, Status> {
+        self.unary("/geyser.Geyser/Ping", PingRequest { count })
+            .await
+    }
+
+    // Calls `GetVersion`.
+    pub async fn get_version(&mut self) -> Result<GetVersionResponse, Status> {
+        self.unary("/geyser.Geyser/GetVersion", GetVersionRequest {})
+            .await
+    }
+
+    // Sends a unary call to the method at the given path.
+    async fn unary<M1, M2>(&mut self, path: &'static str, message: M1) -> Result<M2, Status>
+    where
+        M1: Message + Send + Sync + 'static,
+        M2: Message + Default + Send + Sync + 'static,
+    {
+        self.inner
+            .ready()
+            .await
+            .map_err(|e| Status::unavailable(format!("Service was not ready: {}", e)))?;
+        let mut request = Request::new(message);
+        if let Some(x_token) = &self.x_token {
+            request.metadata_mut().insert("x-token", x_token.clone());
+        }
+        let response = self
+            .inner
+            .unary(
+                request,
+                PathAndQuery::from_static(path),
+                ProstCodec::default(),
+            )
+            .await?;
+        Ok(response.into_inner())
+    }
+}
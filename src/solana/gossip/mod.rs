@@ -0,0 +1,4 @@
+// Gossip node implementation
+pub mod node;
+// Gossip wire protocol messages
+pub mod protocol;
@@ -0,0 +1,350 @@
+// Module for handshaking with a node over the native Solana gossip protocol.
+// A signed ping is sent over UDP and the handshake succeeds once a valid pong comes back.
+use crate::{
+    error::HandshakeError,
+    solana::{
+        bench::LatencyStats,
+        cancel::Cancel,
+        gossip::protocol::{Ping, Protocol, PING_TOKEN_SIZE},
+        node::Handshake,
+        transport::traced,
+    },
+}; // Import the crate error type, latency statistics, cancellation, the gossip messages, the handshake trait and handshake tracing.
+use async_trait::async_trait; // Enables async trait methods, crucial for async network operations.
+use solana_sdk::signature::Keypair; // Keypair used to sign the ping.
+use std::{
+    fmt,
+    net::SocketAddr,
+    time::{Duration, Instant},
+}; // Standard library imports for networking, display formatting and timing round trips.
+use tokio::{net::UdpSocket, time}; // Asynchronous UDP socket and timers.
+use tracing::info; // Import the `info` macro for logging informational messages.
+
+// Default number of seconds to wait for a pong.
+const GOSSIP_TIMEOUT_SECONDS: u32 = 10;
+
+// Default number of seconds a ping of a series waits for its pong before it is counted as lost.
+pub const DEFAULT_GOSSIP_PING_TIMEOUT_SECONDS: u32 = 2;
+
+// Maximum size of a gossip packet, matching the validator's packet data size.
+pub const PACKET_DATA_SIZE: usize = 1232;
+
+// How a series of gossip pings is sent: how many, how long to wait between them and for each pong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GossipPingOptions {
+    pub count: u32,         // Number of pings sent.
+    pub interval: Duration, // Delay between one ping ending and the next being sent.
+    pub timeout: Duration,  // Time a ping waits for its pong before it is counted as lost.
+}
+
+// Outcome of a series of gossip pings.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GossipPingResult {
+    pub round_trips: Vec<Option<Duration>>, // Round trip of each ping in the order sent, or nothing when lost.
+}
+
+impl GossipPingResult {
+    // Number of pings sent.
+    pub fn sent(&self) -> usize {
+        self.round_trips.len()
+    }
+
+    // Number of pings answered within the timeout.
+    pub fn received(&self) -> usize {
+        self.round_trips.iter().flatten().count()
+    }
+
+    // Percentage of the pings lost, if any was sent.
+    pub fn loss_percent(&self) -> Option<f64> {
+        let sent = self.sent();
+        (sent > 0).then(|| (sent - self.received()) as f64 * 100.0 / sent as f64)
+    }
+
+    // Statistics of the round trips of the pings answered, if any was.
+    pub fn stats(&self) -> Option<LatencyStats> {
+        LatencyStats::from_durations(self.round_trips.iter().flatten().copied())
+    }
+}
+
+// Struct defining a gossip node, identified by the socket address of its gossip port.
+pub struct GossipNode {
+    pub remote: SocketAddr, // Socket address of the remote gossip endpoint.
+    keypair: Keypair,       // Ephemeral identity used to sign the ping.
+}
+
+impl GossipNode {
+    // Constructor for GossipNode, taking the socket address of the gossip port.
+    // A fresh identity is generated for each node so the handshake needs no configuration.
+    pub fn new(remote: SocketAddr) -> Self {
+        GossipNode {
+            remote,
+            keypair: Keypair::new(),
+        }
+    }
+
+    // Sends a series of signed pings one after another, measuring the round trip of each pong, like
+    // `ping` but over the gossip protocol. A ping whose pong does not arrive within the timeout is lost;
+    // only failures of the socket fail the series. Cancelling stops it, keeping the pings so far.
+    pub async fn ping(
+        &self,
+        options: GossipPingOptions,
+        cancel: &Cancel,
+    ) -> Result<GossipPingResult, HandshakeError> {
+        traced(self.remote, "udp", async {
+            let socket = bind(self.remote).await?;
+            let mut result = GossipPingResult::default();
+            for sequence in 0..options.count {
+                if sequence > 0 && cancel.run(time::sleep(options.interval)).await.is_none() {
+                    break;
+                }
+
+                // Every ping signs a token of its own, so each pong answers a single ping.
+                let token: [u8; PING_TOKEN_SIZE] = rand::random();
+                let ping = Ping::new(token, &self.keypair);
+                let sent = Instant::now();
+                send_ping(&socket, self.remote, &ping).await?;
+                let pong = time::timeout(options.timeout, await_pong(&socket, self.remote, &ping));
+                let Some(pong) = cancel.run(pong).await else {
+                    break;
+                };
+                let round_trip = match pong {
+                    Ok(received) => {
+                        received?;
+                        let round_trip = sent.elapsed();
+                        info!("Received gossip pong {} after {:?}", sequence, round_trip);
+                        Some(round_trip)
+                    }
+                    Err(_) => {
+                        info!("Gossip ping {} lost", sequence);
+                        None
+                    }
+                };
+                result.round_trips.push(round_trip);
+            }
+            Ok(result)
+        })
+        .await
+    }
+}
+
+// Implement the Display trait for GossipNode for easy logging and debugging.
+impl fmt::Display for GossipNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GossipNode({})", self.remote) // Custom display format showing the remote address.
+    }
+}
+
+// Binds an ephemeral UDP socket of the remote's address family.
+async fn bind(remote: SocketAddr) -> Result<UdpSocket, HandshakeError> {
+    let bind_addr: SocketAddr = match remote {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    }
+    .parse()
+    .expect("valid bind address");
+    Ok(UdpSocket::bind(bind_addr).await?)
+}
+
+// Sends a ping to the remote.
+async fn send_ping(
+    socket: &UdpSocket,
+    remote: SocketAddr,
+    ping: &Ping,
+) -> Result<(), HandshakeError> {
+    let packet = bincode::serialize(&Protocol::PingMessage(ping.clone()))
+        .map_err(|e| HandshakeError::Protocol(format!("Unable to encode ping: {}", e)))?;
+    socket.send_to(&packet, remote).await?;
+    Ok(())
+}
+
+// Sends the ping and waits until a pong answering it arrives from the remote.
+// Other gossip traffic (for instance the node pinging us back, or a pong to another ping) is ignored.
+async fn ping_pong(
+    socket: &UdpSocket,
+    remote: SocketAddr,
+    ping: &Ping,
+) -> Result<String, HandshakeError> {
+    send_ping(socket, remote, ping).await?;
+    info!("Sent gossip ping from {}", ping.from);
+
+    let mut buf = vec![0; PACKET_DATA_SIZE];
+    loop {
+        let (n, from) = socket.recv_from(&mut buf).await?;
+        if from != remote {
+            continue; // Only the targeted node can complete the handshake.
+        }
+        match bincode::deserialize::<Protocol>(&buf[..n]) {
+            Ok(Protocol::PongMessage(pong)) if pong.verify(ping) => {
+                info!("Received valid gossip pong from {}", pong.from);
+                return Ok(format!("Pong received from {} ({})", pong.from, from));
+            }
+            _ => continue, // Ignore anything that is not a pong to this ping, e.g. a stale one.
+        }
+    }
+}
+
+// Waits until a pong answering the ping arrives from the remote. Pongs answering earlier pings of a
+// series may still arrive late, so they are ignored along with any other traffic.
+async fn await_pong(
+    socket: &UdpSocket,
+    remote: SocketAddr,
+    ping: &Ping,
+) -> Result<(), HandshakeError> {
+    let mut buf = vec![0; PACKET_DATA_SIZE];
+    loop {
+        let (n, from) = socket.recv_from(&mut buf).await?;
+        if from != remote {
+            continue;
+        }
+        if let Ok(Protocol::PongMessage(pong)) = bincode::deserialize::<Protocol>(&buf[..n]) {
+            if pong.verify(ping) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Async trait for performing the handshake operation over gossip.
+#[async_trait]
+impl Handshake for GossipNode {
+    async fn shake(&self, timeout: Option<u32>) -> Result<String, HandshakeError> {
+        traced(self.remote, "udp", async {
+            // Bind an ephemeral UDP socket of the matching address family.
+            let socket = bind(self.remote).await?;
+
+            // Sign a random token; the remote must echo its hash back.
+            let token: [u8; PING_TOKEN_SIZE] = rand::random();
+            let ping = Ping::new(token, &self.keypair);
+
+            // UDP gives no delivery guarantee, so always bound the wait for a pong.
+            let seconds = timeout.unwrap_or(GOSSIP_TIMEOUT_SECONDS);
+            time::timeout(
+                Duration::from_secs(seconds.into()),
+                ping_pong(&socket, self.remote, &ping),
+            )
+            .await
+            .map_err(|_| HandshakeError::Timeout {
+                phase: "gossip pong".to_string(),
+                seconds,
+            })?
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Test suite for GossipNode functionality.
+    use {super::*, crate::solana::gossip::protocol::Pong, std::str::FromStr};
+
+    // Verifies that a GossipNode can be correctly instantiated and displayed.
+    #[test]
+    fn test_gossip_node_creation() {
+        let addr = SocketAddr::from_str("127.0.0.1:8001").unwrap();
+        let node = GossipNode::new(addr);
+
+        assert_eq!(node.remote, addr); // Checks that the remote address matches.
+        assert_eq!(node.to_string(), "GossipNode(127.0.0.1:8001)"); // Checks the display format.
+    }
+
+    // Runs the handshake against a local UDP responder that answers pings with valid pongs.
+    #[tokio::test]
+    async fn test_shake_with_local_responder() {
+        let responder = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let node = GossipNode::new(responder.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let keypair = Keypair::new();
+            let mut buf = vec![0; PACKET_DATA_SIZE];
+            let (n, from) = responder.recv_from(&mut buf).await.unwrap();
+            if let Ok(Protocol::PingMessage(ping)) = bincode::deserialize(&buf[..n]) {
+                let pong = Protocol::PongMessage(Pong::new(&ping, &keypair));
+                let bytes = bincode::serialize(&pong).unwrap();
+                responder.send_to(&bytes, from).await.unwrap();
+            }
+        });
+
+        let response = node.shake(Some(5)).await;
+        assert!(response.is_ok(), "Expected a pong, got {:?}", response);
+    }
+
+    // A pong that does not answer the ping is ignored, and the one that does completes the handshake.
+    #[tokio::test]
+    async fn test_shake_ignores_mismatched_pong() {
+        let responder = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let node = GossipNode::new(responder.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let keypair = Keypair::new();
+            let mut buf = vec![0; PACKET_DATA_SIZE];
+            let (n, from) = responder.recv_from(&mut buf).await.unwrap();
+            if let Ok(Protocol::PingMessage(ping)) = bincode::deserialize(&buf[..n]) {
+                let stale = Ping::new(rand::random(), &keypair);
+                for answered in [&stale, &ping] {
+                    let pong = Protocol::PongMessage(Pong::new(answered, &keypair));
+                    let bytes = bincode::serialize(&pong).unwrap();
+                    responder.send_to(&bytes, from).await.unwrap();
+                }
+            }
+        });
+
+        let response = node.shake(Some(5)).await;
+        assert!(response.is_ok(), "Expected a pong, got {:?}", response);
+    }
+
+    // Pings whose pong does not come back are counted as lost, and the others' round trips measured.
+    #[tokio::test]
+    async fn test_ping_counts_lost_pings() {
+        let responder = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let node = GossipNode::new(responder.local_addr().unwrap());
+
+        // The second ping goes unanswered.
+        tokio::spawn(async move {
+            let keypair = Keypair::new();
+            let mut buf = vec![0; PACKET_DATA_SIZE];
+            for sequence in 0.. {
+                let (n, from) = responder.recv_from(&mut buf).await.unwrap();
+                if let Ok(Protocol::PingMessage(ping)) = bincode::deserialize(&buf[..n]) {
+                    if sequence != 1 {
+                        let pong = Protocol::PongMessage(Pong::new(&ping, &keypair));
+                        let bytes = bincode::serialize(&pong).unwrap();
+                        responder.send_to(&bytes, from).await.unwrap();
+                    }
+                }
+            }
+        });
+
+        let options = GossipPingOptions {
+            count: 3,
+            interval: Duration::from_millis(10),
+            timeout: Duration::from_millis(500),
+        };
+        let result = node.ping(options, &Cancel::new()).await.unwrap();
+        assert_eq!(result.sent(), 3);
+        assert_eq!(result.received(), 2);
+        assert!(result.round_trips[1].is_none());
+        assert!((result.loss_percent().unwrap() - 100.0 / 3.0).abs() < 1e-9);
+        assert_eq!(result.stats().unwrap().samples, 2);
+    }
+
+    // A series that sent nothing has no loss or statistics.
+    #[test]
+    fn test_ping_result_empty() {
+        let result = GossipPingResult::default();
+        assert_eq!(result.loss_percent(), None);
+        assert_eq!(result.stats(), None);
+    }
+
+    // Ensures the handshake times out when nothing answers.
+    #[tokio::test]
+    async fn test_shake_times_out() {
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let node = GossipNode::new(silent.local_addr().unwrap());
+
+        let response = node.shake(Some(1)).await;
+        assert!(matches!(
+            response.unwrap_err(),
+            HandshakeError::Timeout { .. }
+        ));
+    }
+}
@@ -0,0 +1,121 @@
+// Wire-level messages of the Solana gossip protocol that are needed for a ping/pong handshake.
+// Messages are serialized with bincode, matching the encoding used by validators.
+use serde::{Deserialize, Serialize}; // Allows for easy serialization and deserialization of data structures.
+use solana_sdk::{
+    hash::{hashv, Hash},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+}; // Solana primitives for identities, hashes and signatures.
+
+// Prefix mixed into the ping token when computing the pong hash.
+const PING_PONG_HASH_PREFIX: &[u8] = b"SOLANA_PING_PONG";
+
+// Size of the random token carried by a ping.
+pub const PING_TOKEN_SIZE: usize = 32;
+
+// Ping message sent to a gossip node, carrying a random token signed by the sender.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Ping {
+    pub from: Pubkey,                 // Identity of the node sending the ping.
+    pub token: [u8; PING_TOKEN_SIZE], // Random token the remote must echo back (hashed) in its pong.
+    pub signature: Signature,         // Signature of the token by `from`.
+}
+
+// Pong message sent in response to a ping, carrying the hash of the ping token.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Pong {
+    pub from: Pubkey,         // Identity of the node answering the ping.
+    pub hash: Hash,           // Hash of the ping-pong prefix and the ping token.
+    pub signature: Signature, // Signature of the hash by `from`.
+}
+
+// Top-level gossip message envelope.
+// Only the ping and pong variants are modelled; the preceding variants are kept as
+// placeholders so that the bincode variant indices match the validator's `Protocol` enum.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum Protocol {
+    PullRequest,
+    PullResponse,
+    PushMessage,
+    PruneMessage,
+    PingMessage(Ping),
+    PongMessage(Pong),
+}
+
+impl Ping {
+    // Creates a ping with the given token, signed by the keypair.
+    pub fn new(token: [u8; PING_TOKEN_SIZE], keypair: &Keypair) -> Self {
+        Ping {
+            from: keypair.pubkey(),
+            token,
+            signature: keypair.sign_message(&token),
+        }
+    }
+
+    // Hash a well-behaved node returns in its pong to this ping.
+    pub fn pong_hash(&self) -> Hash {
+        hashv(&[PING_PONG_HASH_PREFIX, &self.token])
+    }
+}
+
+impl Pong {
+    // Creates the pong answering a ping, signed by the keypair.
+    pub fn new(ping: &Ping, keypair: &Keypair) -> Self {
+        let hash = ping.pong_hash();
+        Pong {
+            from: keypair.pubkey(),
+            hash,
+            signature: keypair.sign_message(hash.as_ref()),
+        }
+    }
+
+    // Checks that this pong answers the given ping and is signed by its sender.
+    pub fn verify(&self, ping: &Ping) -> bool {
+        self.hash == ping.pong_hash()
+            && self
+                .signature
+                .verify(self.from.as_ref(), self.hash.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Test suite for the gossip wire messages.
+    use super::*;
+
+    // Verifies the serialized ping layout: a u32 variant index followed by pubkey, token and signature.
+    #[test]
+    fn test_ping_serialization_layout() {
+        let keypair = Keypair::new();
+        let ping = Ping::new([7u8; PING_TOKEN_SIZE], &keypair);
+        let bytes = bincode::serialize(&Protocol::PingMessage(ping)).unwrap();
+
+        assert_eq!(bytes.len(), 4 + 32 + PING_TOKEN_SIZE + 64); // Variant, pubkey, token, signature.
+        assert_eq!(&bytes[..4], &4u32.to_le_bytes()); // Ping is the fifth protocol variant.
+        assert_eq!(&bytes[4..36], keypair.pubkey().as_ref()); // Sender identity follows the variant.
+    }
+
+    // Ensures a pong created for a ping round-trips through bincode and verifies against it.
+    #[test]
+    fn test_pong_verifies_against_ping() {
+        let ping = Ping::new([1u8; PING_TOKEN_SIZE], &Keypair::new());
+        let pong = Pong::new(&ping, &Keypair::new());
+        let bytes = bincode::serialize(&Protocol::PongMessage(pong)).unwrap();
+
+        match bincode::deserialize::<Protocol>(&bytes).unwrap() {
+            Protocol::PongMessage(pong) => assert!(pong.verify(&ping)),
+            other => panic!("Expected a pong message, got {:?}", other),
+        }
+    }
+
+    // Ensures a pong for a different token is rejected.
+    #[test]
+    fn test_pong_for_other_ping_is_rejected() {
+        let keypair = Keypair::new();
+        let ping = Ping::new([1u8; PING_TOKEN_SIZE], &keypair);
+        let other = Ping::new([2u8; PING_TOKEN_SIZE], &keypair);
+        let pong = Pong::new(&other, &Keypair::new());
+
+        assert!(!pong.verify(&ping));
+    }
+}
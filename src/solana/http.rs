@@ -0,0 +1,369 @@
+// Minimal HTTP/1.1 response parsing for the raw TCP/TLS transport.
+// Handles the status line, headers, Content-Length delimited bodies and chunked transfer encoding.
+use std::io::{Error, ErrorKind, Result}; // Standard IO error types used to report malformed responses.
+
+// Separator between the response head (status line and headers) and the body.
+const HEAD_TERMINATOR: &[u8] = b"\r\n\r\n";
+
+// A parsed HTTP response.
+#[derive(Debug, PartialEq)]
+pub struct HttpResponse {
+    pub version: String, // Protocol version from the status line, e.g. "HTTP/1.1".
+    pub status: u16,     // Numeric status code, e.g. 200.
+    pub reason: String,  // Reason phrase, e.g. "OK".
+    pub headers: Vec<(String, String)>, // Header name/value pairs in the order they were received.
+    pub body: Vec<u8>,   // Decoded body bytes.
+}
+
+impl HttpResponse {
+    // Parses a complete HTTP response from raw bytes.
+    pub fn parse(raw: &[u8]) -> Result<HttpResponse> {
+        // Without the end of the head, the response is incomplete.
+        let (mut response, head_len) = HttpResponse::parse_head(raw)?
+            .ok_or_else(|| invalid(ErrorKind::UnexpectedEof, "Incomplete HTTP response head"))?;
+        let rest = &raw[head_len..];
+
+        // Decode the body according to the framing announced in the headers.
+        response.body = if response.is_chunked() {
+            decode_chunked(rest)?
+        } else if let Some(length) = response.content_length()? {
+            if rest.len() < length {
+                return Err(invalid(
+                    ErrorKind::UnexpectedEof,
+                    &format!(
+                        "Incomplete HTTP body: expected {} bytes, received {}",
+                        length,
+                        rest.len()
+                    ),
+                ));
+            }
+            rest[..length].to_vec()
+        } else {
+            rest.to_vec() // Close-delimited body: everything after the head.
+        };
+
+        Ok(response)
+    }
+
+    // Parses the head of a response (status line and headers) once all of it arrived, returning the
+    // response without its body and the length of the head, so the body can be read as it is framed.
+    // Returns `Ok(None)` while the end of the head is yet to arrive.
+    pub fn parse_head(raw: &[u8]) -> Result<Option<(HttpResponse, usize)>> {
+        let Some(head_end) = find(raw, HEAD_TERMINATOR) else {
+            return Ok(None);
+        };
+        let head = std::str::from_utf8(&raw[..head_end])
+            .map_err(|_| invalid(ErrorKind::InvalidData, "HTTP response head is not UTF-8"))?;
+        // Parse the status line, e.g. "HTTP/1.1 200 OK".
+        let mut lines = head.split("\r\n");
+        let status_line = lines.next().unwrap_or_default();
+        let mut parts = status_line.splitn(3, ' ');
+        let version = parts.next().unwrap_or_default();
+        if !version.starts_with("HTTP/") {
+            return Err(invalid(
+                ErrorKind::InvalidData,
+                &format!("Invalid HTTP status line '{}'", status_line),
+            ));
+        }
+        let status = parts
+            .next()
+            .and_then(|code| code.parse::<u16>().ok())
+            .ok_or_else(|| {
+                invalid(
+                    ErrorKind::InvalidData,
+                    &format!("Invalid HTTP status code in '{}'", status_line),
+                )
+            })?;
+        let reason = parts.next().unwrap_or_default();
+
+        // Parse the header lines into name/value pairs.
+        let headers = lines
+            .map(|line| {
+                line.split_once(':')
+                    .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                    .ok_or_else(|| {
+                        invalid(
+                            ErrorKind::InvalidData,
+                            &format!("Invalid HTTP header '{}'", line),
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let response = HttpResponse {
+            version: version.to_string(),
+            status,
+            reason: reason.to_string(),
+            headers,
+            body: Vec::new(),
+        };
+        Ok(Some((response, head_end + HEAD_TERMINATOR.len())))
+    }
+
+    // Parses a response whose end is determined by its framing, as on a keep-alive connection.
+    // Returns `Ok(None)` while more bytes are needed, including for close-delimited bodies,
+    // which are only complete once the peer closes the connection.
+    pub fn parse_framed(raw: &[u8]) -> Result<Option<HttpResponse>> {
+        let response = match HttpResponse::parse(raw) {
+            Ok(response) => response,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        // Only chunked, Content-Length and body-less responses carry their own end marker.
+        let has_no_body = response.status == 204 || response.status == 304;
+        if response.is_chunked() || response.content_length()?.is_some() || has_no_body {
+            Ok(Some(response))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Indicates whether the server allows the connection to be reused for another request.
+    pub fn is_keep_alive(&self) -> bool {
+        match self.header("Connection") {
+            Some(value) if value.eq_ignore_ascii_case("close") => false,
+            Some(value) if value.eq_ignore_ascii_case("keep-alive") => true,
+            _ => self.version == "HTTP/1.1", // HTTP/1.1 connections persist by default.
+        }
+    }
+
+    // Returns the value of the first header with the given name, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Returns the declared Content-Length, if any.
+    pub fn content_length(&self) -> Result<Option<usize>> {
+        self.header("Content-Length")
+            .map(|value| {
+                value.parse::<usize>().map_err(|_| {
+                    invalid(
+                        ErrorKind::InvalidData,
+                        &format!("Invalid Content-Length '{}'", value),
+                    )
+                })
+            })
+            .transpose()
+    }
+
+    // Indicates whether the body uses chunked transfer encoding.
+    pub fn is_chunked(&self) -> bool {
+        self.header("Transfer-Encoding")
+            .map(|value| value.to_ascii_lowercase().contains("chunked"))
+            .unwrap_or(false)
+    }
+
+    // Indicates whether the status code is in the 2xx success range.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    // Returns the body as text, replacing any invalid UTF-8 sequences.
+    pub fn body_text(&self) -> String {
+        String::from_utf8_lossy(&self.body).to_string()
+    }
+}
+
+// Decodes a body sent with chunked transfer encoding.
+fn decode_chunked(mut data: &[u8]) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        // Each chunk starts with its size in hex, optionally followed by extensions.
+        let line_end = find(data, b"\r\n")
+            .ok_or_else(|| invalid(ErrorKind::UnexpectedEof, "Incomplete chunk size line"))?;
+        let size_line = std::str::from_utf8(&data[..line_end])
+            .map_err(|_| invalid(ErrorKind::InvalidData, "Chunk size line is not UTF-8"))?;
+        let size_hex = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_hex, 16).map_err(|_| {
+            invalid(
+                ErrorKind::InvalidData,
+                &format!("Invalid chunk size '{}'", size_hex),
+            )
+        })?;
+        data = &data[line_end + 2..];
+
+        // A zero-sized chunk terminates the body; any trailers are ignored.
+        if size == 0 {
+            return Ok(body);
+        }
+        if data.len().saturating_sub(2) < size {
+            return Err(invalid(ErrorKind::UnexpectedEof, "Incomplete chunk data"));
+        }
+        if &data[size..size + 2] != b"\r\n" {
+            return Err(invalid(
+                ErrorKind::InvalidData,
+                &format!("Chunk data does not match its declared size {}", size),
+            ));
+        }
+        body.extend_from_slice(&data[..size]);
+        data = &data[size + 2..]; // Skip the chunk data and its trailing CRLF.
+    }
+}
+
+// Returns the index of the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+// Builds an IO error of the given kind with a message.
+fn invalid(kind: ErrorKind, message: &str) -> Error {
+    Error::new(kind, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    // Test suite for HTTP response parsing.
+    use {super::*, proptest::prelude::*, test_case::test_case};
+
+    // Parses a Content-Length delimited response and exposes its parts.
+    #[test]
+    fn test_parse_content_length_response() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 2\r\n\r\n{}trailing";
+        let response = HttpResponse::parse(raw).unwrap();
+
+        assert_eq!(response.version, "HTTP/1.1");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.reason, "OK");
+        assert_eq!(response.header("content-type"), Some("application/json")); // Case-insensitive lookup.
+        assert_eq!(response.body_text(), "{}"); // Bytes beyond Content-Length are ignored.
+        assert!(response.is_success());
+    }
+
+    // Decodes a chunked body, including chunk extensions and trailers.
+    #[test]
+    fn test_parse_chunked_response() {
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4;ext=1\r\n{\"a\"\r\n3\r\n:1}\r\n0\r\nX-Trailer: y\r\n\r\n";
+        let response = HttpResponse::parse(raw).unwrap();
+
+        assert_eq!(response.body_text(), "{\"a\":1}");
+    }
+
+    // Non-2xx responses are parsed but reported as unsuccessful.
+    #[test]
+    fn test_parse_error_status() {
+        let raw = b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n";
+        let response = HttpResponse::parse(raw).unwrap();
+
+        assert_eq!(response.status, 503);
+        assert_eq!(response.reason, "Service Unavailable");
+        assert!(!response.is_success());
+    }
+
+    // Close-delimited bodies take everything after the head.
+    #[test]
+    fn test_parse_close_delimited_body() {
+        let raw = b"HTTP/1.0 200 OK\r\n\r\nhello";
+        assert_eq!(HttpResponse::parse(raw).unwrap().body_text(), "hello");
+    }
+
+    // Framed parsing waits for the full body, and for EOF when there is no framing.
+    #[test]
+    fn test_parse_framed() {
+        let partial = b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\n{}";
+        assert_eq!(HttpResponse::parse_framed(partial).unwrap(), None);
+
+        let complete = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}";
+        let response = HttpResponse::parse_framed(complete).unwrap().unwrap();
+        assert!(response.is_keep_alive());
+
+        let unframed = b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n{}";
+        assert_eq!(HttpResponse::parse_framed(unframed).unwrap(), None);
+    }
+
+    // The head is parsed once complete, with its length, whatever part of the body arrived.
+    #[test]
+    fn test_parse_head() {
+        assert_eq!(
+            HttpResponse::parse_head(b"HTTP/1.1 200 OK\r\n").unwrap(),
+            None
+        );
+
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\n{}";
+        let (response, head_len) = HttpResponse::parse_head(raw).unwrap().unwrap();
+        assert_eq!(response.content_length().unwrap(), Some(4));
+        assert!(response.body.is_empty());
+        assert_eq!(&raw[head_len..], b"{}");
+    }
+
+    #[test_case(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n"; "when head is incomplete")]
+    #[test_case(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n{}"; "when body is shorter than content length")]
+    #[test_case(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n10\r\nabc"; "when chunk is incomplete")]
+    #[test_case(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffff\r\nabc"; "when chunk size is the largest")]
+    // Truncated responses are reported as unexpected EOF.
+    fn test_parse_incomplete(raw: &[u8]) {
+        let error = HttpResponse::parse(raw).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test_case(b"SSH-2.0-OpenSSH\r\n\r\n"; "when status line is not http")]
+    #[test_case(b"HTTP/1.1 abc OK\r\n\r\n"; "when status code is not numeric")]
+    #[test_case(b"HTTP/1.1 200 OK\r\nbroken header\r\n\r\n"; "when header has no colon")]
+    #[test_case(b"HTTP/1.1 200 OK\r\nContent-Length: x\r\n\r\n"; "when content length is invalid")]
+    #[test_case(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nabc\r\n0\r\n\r\n"; "when chunk is longer than its size")]
+    // Malformed responses are reported as invalid data.
+    fn test_parse_malformed(raw: &[u8]) {
+        let error = HttpResponse::parse(raw).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    // Strategy for bodies: arbitrary bytes, or chunks of any size line and data, so the chunked decoder
+    // is driven past its first size line.
+    fn body() -> impl Strategy<Value = Vec<u8>> {
+        let chunk = (
+            "[0-9a-fA-F]{1,17}(;[a-z]=1)?",
+            prop::collection::vec(any::<u8>(), 0..16),
+        );
+        let chunks = prop::collection::vec(chunk, 0..4).prop_map(|chunks| {
+            let mut body = Vec::new();
+            for (size, data) in chunks {
+                body.extend_from_slice(format!("{}\r\n", size).as_bytes());
+                body.extend(data);
+                body.extend_from_slice(b"\r\n");
+            }
+            body
+        });
+        prop_oneof![prop::collection::vec(any::<u8>(), 0..256), chunks]
+    }
+
+    // Strategy for responses with a well-formed head announcing any framing, followed by any body.
+    fn response() -> impl Strategy<Value = Vec<u8>> {
+        let framing = prop_oneof![
+            Just(String::new()),
+            "[0-9]{1,25}".prop_map(|length| format!("Content-Length: {}\r\n", length)),
+            Just("Transfer-Encoding: chunked\r\n".to_string()),
+        ];
+        (framing, body()).prop_map(|(framing, body)| {
+            let mut raw = format!("HTTP/1.1 200 OK\r\n{}\r\n", framing).into_bytes();
+            raw.extend(body);
+            raw
+        })
+    }
+
+    // Fuzzes the parsers with what an untrusted peer may send: nothing makes them panic, the head's
+    // length stays within the response, and whatever framed parsing accepts, complete parsing agrees with.
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(2000))]
+        #[test]
+        fn test_parse_arbitrary_bytes(raw in prop::collection::vec(any::<u8>(), 0..512)) {
+            let _ = HttpResponse::parse(&raw);
+            let _ = HttpResponse::parse_framed(&raw);
+            if let Ok(Some((_, head_len))) = HttpResponse::parse_head(&raw) {
+                prop_assert!(head_len <= raw.len());
+            }
+        }
+
+        #[test]
+        fn test_parse_arbitrary_bodies(raw in response()) {
+            let parsed = HttpResponse::parse(&raw);
+            if let Ok(Some(response)) = HttpResponse::parse_framed(&raw) {
+                prop_assert_eq!(parsed.unwrap(), response);
+            }
+        }
+    }
+}
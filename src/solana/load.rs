@@ -0,0 +1,237 @@
+// Sustained load testing of a node: handshakes are started at a fixed rate for a duration, whether or
+// not the earlier ones have finished (open loop), so a node falling behind shows as growing latency,
+// failures and handshakes in flight rather than as a lower load. Statistics are reported per interval.
+use {
+    crate::{
+        error::HandshakeError,
+        solana::{
+            bench::BenchResult,
+            node::Handshake,
+            transport::{timed, PhaseTimings},
+        },
+    },
+    std::{sync::Arc, time::Duration},
+    tokio::{
+        sync::mpsc,
+        time::{self, Instant, MissedTickBehavior},
+    },
+};
+
+// Default duration of a load test, in seconds.
+pub const DEFAULT_LOAD_DURATION_SECONDS: u64 = 60;
+
+// Default length of the intervals statistics are reported for, in seconds.
+pub const DEFAULT_LOAD_INTERVAL_SECONDS: u64 = 5;
+
+// Statistics of an interval of a load test, or of the whole test.
+#[derive(Debug, Default)]
+pub struct LoadInterval {
+    pub elapsed: Duration, // Time from the start of the test to the end of the interval.
+    pub length: Duration,  // Length of the interval.
+    pub started: usize,    // Handshakes started during the interval.
+    pub in_flight: usize,  // Handshakes still running at the end of the interval.
+    pub result: BenchResult, // Handshakes finished during the interval.
+}
+
+impl LoadInterval {
+    // Handshakes finished per second during the interval, successful or not.
+    pub fn throughput(&self) -> f64 {
+        match self.length.is_zero() {
+            true => 0.0,
+            false => self.result.count() as f64 / self.length.as_secs_f64(),
+        }
+    }
+
+    // Adds the statistics of the interval that followed.
+    fn add(&mut self, interval: LoadInterval) {
+        self.elapsed = interval.elapsed;
+        self.started += interval.started;
+        self.result.merge(interval.result);
+    }
+}
+
+// A load test starting handshakes with a node at a target rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadTest {
+    pub rate: f64,            // Handshakes started per second.
+    pub duration: Duration,   // Time during which handshakes are started.
+    pub interval: Duration,   // Length of the intervals statistics are reported for.
+    pub timeout: Option<u32>, // Optional timeout in seconds for each handshake phase.
+}
+
+impl LoadTest {
+    // Constructs a load test at the given rate for the given duration, reporting at the default interval.
+    // Rates are kept to at least one handshake an hour.
+    pub fn new(rate: f64, duration: Duration) -> Self {
+        LoadTest {
+            rate: rate.max(1.0 / 3600.0),
+            duration,
+            interval: Duration::from_secs(DEFAULT_LOAD_INTERVAL_SECONDS),
+            timeout: None,
+        }
+    }
+
+    // Sets the length of the intervals statistics are reported for.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval.max(Duration::from_millis(1));
+        self
+    }
+
+    // Sets the timeout for each handshake phase.
+    pub fn with_timeout(mut self, timeout: Option<u32>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // Runs the load test against the node, passing the statistics of every interval to `report` as it
+    // ends, and returns those of the whole test. Once the duration is over, no handshake is started
+    // and the test ends when the running ones finish. It stops early when `report` fails.
+    pub async fn run<N, E>(
+        &self,
+        node: Arc<N>,
+        mut report: impl FnMut(&LoadInterval) -> Result<(), E>,
+    ) -> Result<LoadInterval, E>
+    where
+        N: Handshake + Send + Sync + 'static,
+    {
+        let (sender, mut receiver) =
+            mpsc::unbounded_channel::<(Duration, PhaseTimings, Result<String, HandshakeError>)>();
+        let start = Instant::now();
+        let deadline = time::sleep_until(start + self.duration);
+        tokio::pin!(deadline);
+
+        // Handshakes missed while the runtime was busy are started at once, so the rate holds on average.
+        let mut ticks = time::interval(Duration::from_secs_f64(1.0 / self.rate));
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Burst);
+        let mut intervals = time::interval_at(start + self.interval, self.interval);
+
+        let mut total = LoadInterval::default();
+        let mut interval = LoadInterval::default();
+        let mut in_flight = 0;
+        let mut starting = true;
+        while starting || in_flight > 0 {
+            tokio::select! {
+                biased;
+                _ = &mut deadline, if starting => starting = false,
+                Some((duration, timings, result)) = receiver.recv() => {
+                    in_flight -= 1;
+                    interval.result.record(duration, timings, result);
+                }
+                _ = intervals.tick() => {
+                    let ended = end_interval(&mut interval, &total, start, in_flight);
+                    report(&ended)?;
+                    total.add(ended);
+                }
+                _ = ticks.tick(), if starting => {
+                    let (node, sender, timeout) = (node.clone(), sender.clone(), self.timeout);
+                    tokio::spawn(async move {
+                        let started = Instant::now();
+                        let (result, timings, _) = timed(node.shake(timeout)).await;
+                        // The receiver outlives every handshake, so sending cannot fail.
+                        let _ = sender.send((started.elapsed(), timings, result));
+                    });
+                    interval.started += 1;
+                    in_flight += 1;
+                }
+            }
+        }
+
+        // The last interval is cut short when the test ends between two reports.
+        if interval.started > 0 || interval.result.count() > 0 {
+            let ended = end_interval(&mut interval, &total, start, in_flight);
+            report(&ended)?;
+            total.add(ended);
+        }
+        total.elapsed = start.elapsed();
+        total.length = total.elapsed;
+        Ok(total)
+    }
+}
+
+// Ends the current interval, which started where the statistics of the test so far end, and starts the next one.
+fn end_interval(
+    interval: &mut LoadInterval,
+    total: &LoadInterval,
+    start: Instant,
+    in_flight: usize,
+) -> LoadInterval {
+    let mut ended = std::mem::take(interval);
+    ended.elapsed = start.elapsed();
+    ended.length = ended.elapsed - total.elapsed;
+    ended.in_flight = in_flight;
+    ended
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, async_trait::async_trait};
+
+    // A node answering every handshake after a delay, failing every given number of them.
+    struct SlowNode {
+        delay: Duration,
+        fail_every: usize,
+        shaken: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Handshake for SlowNode {
+        async fn shake(&self, _timeout: Option<u32>) -> Result<String, HandshakeError> {
+            let count = self
+                .shaken
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            time::sleep(self.delay).await;
+            match (count + 1) % self.fail_every {
+                0 => Err(HandshakeError::Protocol("overloaded".to_string())),
+                _ => Ok("1.18.6".to_string()),
+            }
+        }
+    }
+
+    // Handshakes start at the rate whether or not earlier ones have finished, and every one started
+    // is reported once finished.
+    #[tokio::test]
+    async fn test_run() {
+        let node = Arc::new(SlowNode {
+            delay: Duration::from_millis(50),
+            fail_every: 4,
+            shaken: Default::default(),
+        });
+        let load = LoadTest::new(100.0, Duration::from_millis(400))
+            .with_interval(Duration::from_millis(100));
+
+        let mut intervals = Vec::new();
+        let total = load
+            .run(node, |interval| {
+                intervals.push((interval.started, interval.in_flight));
+                Ok::<_, ()>(())
+            })
+            .await
+            .unwrap();
+
+        assert!((36..=41).contains(&total.started), "{}", total.started);
+        assert_eq!(total.result.count(), total.started);
+        assert_eq!(total.result.failures.len(), total.started / 4);
+        assert!(intervals.len() >= 4);
+        assert_eq!(
+            intervals.iter().map(|(started, _)| started).sum::<usize>(),
+            total.started
+        );
+        // Handshakes outlast the rate, so several are in flight at the first report.
+        assert!(intervals[0].1 >= 3, "{:?}", intervals);
+    }
+
+    // A failing report stops the load test.
+    #[tokio::test]
+    async fn test_run_stops() {
+        let node = Arc::new(SlowNode {
+            delay: Duration::ZERO,
+            fail_every: usize::MAX,
+            shaken: Default::default(),
+        });
+        let load =
+            LoadTest::new(100.0, Duration::from_secs(10)).with_interval(Duration::from_millis(50));
+
+        let result = load.run(node, |_| Err("stdout closed")).await;
+        assert_eq!(result.unwrap_err(), "stdout closed");
+    }
+}
@@ -3,20 +3,119 @@
 // handling requests, and responses.
 pub mod rpc;
 
+// The `chain` module describes Solana's handshake request and response as a chain,
+// the abstraction letting other networks share the transports.
+pub mod chain;
+
+// The `gossip` module speaks the native Solana gossip protocol over UDP,
+// proving a node's peer-to-peer layer is alive rather than just its RPC service.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod gossip;
+
+// The `tpu` module contains the node used to verify a validator's
+// transaction processing unit (TPU), which is reached over QUIC.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tpu;
+
+// The `geyser` module verifies the gRPC service of Yellowstone/Geyser plugins,
+// which stream account and slot updates out of a validator. Optional, behind the `geyser` feature.
+#[cfg(all(feature = "geyser", not(target_arch = "wasm32")))]
+pub mod geyser;
+
 // The `transport` module is responsible for the underlying communication
 // mechanisms. It likely abstracts over different transport layers
 // such as TCP, TLS, WebSocket, etc., providing a unified interface for the RPC system.
 pub mod transport;
 
+// The `http` module parses HTTP/1.1 responses received by the raw TCP/TLS transport,
+// handling status lines, headers, Content-Length and chunked bodies.
+pub mod http;
+
+// The `discovery` module finds the other nodes of a cluster by asking an
+// entrypoint for `getClusterNodes`, exposing their advertised RPC endpoints.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod discovery;
+
+// The `scan` module handshakes many RPC endpoints concurrently, with a limit
+// on how many are in flight, and summarizes the versions they report.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod scan;
+
+// The `probe` module handshakes a host on the well-known ports of a Solana node, each with the
+// protocol its service speaks, to tell which services are reachable.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod probe;
+
+// The `precheck` module checks a host answers at the network layer before it is handshaken, so an
+// unreachable host is told apart from a closed or filtered port.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod precheck;
+
+// The `traceroute` module traces the path to a target whose connection failed, hop by hop, so a timeout
+// can be pinned on the router or firewall where the path ends.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod traceroute;
+
+// The `mtu` module binary-searches the largest datagram reaching a UDP port of a node with fragmentation
+// forbidden, finding the path MTU so blackholes dropping large packets can be spotted.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod mtu;
+
+// The `bench` module handshakes with a node repeatedly and summarizes the latency of the handshakes,
+// overall and per phase, as percentiles.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bench;
+
+// The `load` module starts handshakes with a node at a target rate for a duration, whether or not
+// earlier ones have finished, and reports their statistics at regular intervals.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod load;
+
+// The `pacer` module spaces handshakes out with a token bucket, so scans and sustained monitoring
+// stay under the rate limits of RPC providers.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pacer;
+
+// The `monitor` module handshakes a set of endpoints at a fixed interval, streaming each round's
+// results and folding them into the status of every target.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod monitor;
+
+// The `cancel` module lets scans and benchmarks be cancelled, e.g. on Ctrl+C, keeping the results
+// of the handshakes that finished.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cancel;
+
+// The `cluster` module expands the names of the public clusters into their official endpoints.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cluster;
+
+// The `target` module keeps the host and port named on the command line together with
+// every address they resolved to, so an address family can be chosen among them.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod target;
+
 // The `node` module could represent entities within the RPC system,
 // such as client and server nodes. It might contain implementations
 // for initiating connections, managing state, and sending or receiving messages.
+#[cfg(not(target_arch = "wasm32"))]
 pub mod node;
 
+// The types needed to handshake with a node, re-exported so embedders need not know the module layout.
+pub use transport::{ConnectionInfo, PhaseTimings, Transport};
+#[cfg(not(target_arch = "wasm32"))]
+pub use {
+    node::Handshake,
+    rpc::node::{RpcHandshakeResponse, RpcNode, RpcNodeBuilder},
+    target::Target,
+};
+
+use std::{fmt, str::FromStr}; // Display formatting and parsing of transport types.
+
 // `TransportType` enum defines the supported types of transport protocols
 // for the RPC system. Each variant represents a different protocol
 // that can be used for communication between nodes.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TransportType {
     // Represents plain TCP transport, a standard, low-level protocol
     // for network communication without encryption.
@@ -34,4 +133,87 @@ pub enum TransportType {
     // Represents Secure WebSocket (Wss) transport, an extension of WebSocket
     // that runs over TLS for secure communication.
     Wss,
+
+    // Represents QUIC transport, a UDP-based protocol with built-in TLS 1.3,
+    // used by Solana validators to receive transactions on the TPU port.
+    Quic,
+
+    // Represents HTTP transport through the hyper client, which handles
+    // keep-alive, response framing and redirects. Optional, behind the `hyper` feature.
+    #[cfg(feature = "hyper")]
+    Http,
+
+    // Represents HTTPS transport through the hyper client, the secure
+    // counterpart of `Http`. Optional, behind the `hyper` feature.
+    #[cfg(feature = "hyper")]
+    Https,
+
+    // Represents HTTP/2 over TLS through the hyper client, negotiated with ALPN,
+    // falling back to HTTP/1.1 when the server does not offer it. Optional, behind the `hyper` feature.
+    #[cfg(feature = "hyper")]
+    H2,
+
+    // Represents cleartext HTTP/2 through the hyper client, spoken with prior knowledge
+    // rather than negotiated. Optional, behind the `hyper` feature.
+    #[cfg(feature = "hyper")]
+    H2c,
+}
+
+impl TransportType {
+    // Transport of a JSON-RPC endpoint from the usual flags: its WebSocket or HTTP over the raw socket,
+    // secured with TLS or not.
+    pub fn rpc(websocket: bool, secure: bool) -> Self {
+        match (websocket, secure) {
+            (true, true) => TransportType::Wss,
+            (true, false) => TransportType::Ws,
+            (false, true) => TransportType::Tls,
+            (false, false) => TransportType::Tcp,
+        }
+    }
+}
+
+// Implement the Display trait for TransportType, using the lowercase protocol names shown to users.
+impl fmt::Display for TransportType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TransportType::Tcp => "tcp",
+            TransportType::Tls => "tls",
+            TransportType::Ws => "ws",
+            TransportType::Wss => "wss",
+            TransportType::Quic => "quic",
+            #[cfg(feature = "hyper")]
+            TransportType::Http => "http",
+            #[cfg(feature = "hyper")]
+            TransportType::Https => "https",
+            #[cfg(feature = "hyper")]
+            TransportType::H2 => "h2",
+            #[cfg(feature = "hyper")]
+            TransportType::H2c => "h2c",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Transport types are named as they are displayed, e.g. in lists of targets.
+impl FromStr for TransportType {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "tcp" => Ok(TransportType::Tcp),
+            "tls" => Ok(TransportType::Tls),
+            "ws" => Ok(TransportType::Ws),
+            "wss" => Ok(TransportType::Wss),
+            "quic" => Ok(TransportType::Quic),
+            #[cfg(feature = "hyper")]
+            "http" => Ok(TransportType::Http),
+            #[cfg(feature = "hyper")]
+            "https" => Ok(TransportType::Https),
+            #[cfg(feature = "hyper")]
+            "h2" => Ok(TransportType::H2),
+            #[cfg(feature = "hyper")]
+            "h2c" => Ok(TransportType::H2c),
+            _ => Err(format!("Unknown transport '{}'", name)),
+        }
+    }
 }
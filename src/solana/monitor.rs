@@ -0,0 +1,247 @@
+// Continuous monitoring of a set of RPC endpoints: every interval, the targets are handshaken through a
+// scanner and the round's results are sent as an event, so dashboards and status reports follow the
+// same stream. The status of each target is folded from the rounds it took part in.
+use {
+    crate::solana::scan::{ScanResult, ScanTarget, Scanner},
+    std::{
+        collections::VecDeque,
+        time::{Duration, SystemTime},
+    },
+    tokio::{
+        sync::mpsc,
+        time::{self, MissedTickBehavior},
+    },
+};
+
+// Number of recent handshakes whose latency is kept for each target.
+pub const LATENCY_HISTORY: usize = 60;
+
+// Results of one round of handshakes with every target.
+#[derive(Debug)]
+pub struct Round {
+    pub number: u64,              // Number of the round, from 1.
+    pub finished_at: SystemTime,  // When the last handshake of the round finished.
+    pub results: Vec<ScanResult>, // Result of each target, in the order of the targets.
+}
+
+// Handshakes a set of targets again and again, at a fixed interval.
+pub struct Monitor {
+    pub targets: Vec<ScanTarget>, // The endpoints handshaken every round.
+    pub interval: Duration,       // Time between the starts of two rounds.
+    pub scanner: Scanner,         // How each round's handshakes are made.
+}
+
+impl Monitor {
+    // Constructs a monitor of the targets at the interval, handshaking them with the default scanner.
+    pub fn new(targets: Vec<ScanTarget>, interval: Duration) -> Self {
+        Monitor {
+            targets,
+            interval,
+            scanner: Scanner::new(),
+        }
+    }
+
+    // Sets the scanner making each round's handshakes.
+    pub fn with_scanner(mut self, scanner: Scanner) -> Self {
+        self.scanner = scanner;
+        self
+    }
+
+    // Starts the rounds in the background and returns the stream of their results.
+    // The rounds stop once the receiver is dropped.
+    pub fn spawn(self) -> mpsc::Receiver<Round> {
+        let (sender, receiver) = mpsc::channel(1);
+        tokio::spawn(async move {
+            let mut ticker = time::interval(self.interval);
+            // A slow round delays the next one instead of triggering a burst of catch-up rounds.
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            for number in 1.. {
+                ticker.tick().await;
+                let results = self.scanner.scan(self.targets.clone()).await;
+                let round = Round {
+                    number,
+                    finished_at: SystemTime::now(),
+                    results,
+                };
+                if sender.send(round).await.is_err() {
+                    break;
+                }
+            }
+        });
+        receiver
+    }
+}
+
+// What the rounds so far tell of a target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetStatus {
+    pub target: ScanTarget,                    // The endpoint monitored.
+    pub rounds: u64,                           // Handshakes made with the target.
+    pub successes: u64,                        // Handshakes that succeeded.
+    pub consecutive_failures: u64,             // Handshakes failed since the last success.
+    pub last_checked: Option<SystemTime>,      // When the last handshake finished, if any did.
+    pub last_error: Option<String>,            // Why the last handshake failed, if it did.
+    pub version: Option<String>,               // Version reported by the last successful handshake.
+    pub latencies: VecDeque<Option<Duration>>, // Latency of the recent handshakes, oldest first; failures have none.
+}
+
+impl TargetStatus {
+    // Constructs the status of a target not handshaken yet.
+    pub fn new(target: ScanTarget) -> Self {
+        TargetStatus {
+            target,
+            rounds: 0,
+            successes: 0,
+            consecutive_failures: 0,
+            last_checked: None,
+            last_error: None,
+            version: None,
+            latencies: VecDeque::with_capacity(LATENCY_HISTORY),
+        }
+    }
+
+    // Adds the result of a handshake finished at the given time.
+    pub fn record(&mut self, result: &ScanResult, at: SystemTime) {
+        self.rounds += 1;
+        self.last_checked = Some(at);
+        let latency = match &result.result {
+            Ok(response) => {
+                self.successes += 1;
+                self.consecutive_failures = 0;
+                self.last_error = None;
+                self.version = Some(response.solana_core.clone());
+                Some(result.duration)
+            }
+            Err(e) => {
+                self.consecutive_failures += 1;
+                self.last_error = Some(e.to_string());
+                None
+            }
+        };
+        if self.latencies.len() == LATENCY_HISTORY {
+            self.latencies.pop_front();
+        }
+        self.latencies.push_back(latency);
+    }
+
+    // Indicates whether the last handshake succeeded; a target not handshaken yet is not healthy.
+    pub fn healthy(&self) -> bool {
+        self.last_checked.is_some() && self.last_error.is_none()
+    }
+
+    // Percentage of the handshakes that succeeded, if any was made.
+    pub fn uptime(&self) -> Option<f64> {
+        match self.rounds {
+            0 => None,
+            rounds => Some(self.successes as f64 * 100.0 / rounds as f64),
+        }
+    }
+}
+
+// The status of each target before any round.
+pub fn statuses(targets: &[ScanTarget]) -> Vec<TargetStatus> {
+    targets.iter().cloned().map(TargetStatus::new).collect()
+}
+
+// Adds the results of a round to the statuses of its targets, which are in the same order.
+pub fn record_round(statuses: &mut [TargetStatus], round: &Round) {
+    for (status, result) in statuses.iter_mut().zip(&round.results) {
+        status.record(result, round.finished_at);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            error::HandshakeError,
+            solana::{rpc::node::RpcHandshakeResponse, TransportType},
+        },
+        std::net::SocketAddr,
+    };
+
+    // A target on the loopback address.
+    fn target() -> ScanTarget {
+        let address: SocketAddr = "127.0.0.1:8899".parse().unwrap();
+        ScanTarget::new(address, TransportType::Tcp)
+    }
+
+    // The result of a handshake reporting the version, or failing without one.
+    fn result(version: Option<&str>) -> ScanResult {
+        ScanResult {
+            target: target(),
+            duration: Duration::from_millis(12),
+            timings: Default::default(),
+            connection: Default::default(),
+            result: match version {
+                Some(version) => Ok(RpcHandshakeResponse {
+                    solana_core: version.to_string(),
+                    feature_set: None,
+                    timings: Default::default(),
+                }),
+                None => Err(HandshakeError::Protocol("bad".to_string())),
+            },
+        }
+    }
+
+    // Failures count towards the uptime and keep the last version reported.
+    #[test]
+    fn test_record() {
+        let mut status = TargetStatus::new(target());
+        assert!(!status.healthy());
+        assert_eq!(status.uptime(), None);
+
+        status.record(&result(Some("1.18.6")), SystemTime::now());
+        status.record(&result(None), SystemTime::now());
+        status.record(&result(None), SystemTime::now());
+        assert!(!status.healthy());
+        assert_eq!(status.consecutive_failures, 2);
+        assert_eq!(status.version.as_deref(), Some("1.18.6"));
+        assert_eq!(status.last_error.as_deref(), Some("Protocol error: bad"));
+        assert!((status.uptime().unwrap() - 100.0 / 3.0).abs() < 1e-9);
+        assert_eq!(
+            status.latencies,
+            [Some(Duration::from_millis(12)), None, None]
+        );
+
+        status.record(&result(Some("1.18.7")), SystemTime::now());
+        assert!(status.healthy());
+        assert_eq!(status.consecutive_failures, 0);
+        assert_eq!(status.version.as_deref(), Some("1.18.7"));
+    }
+
+    // Only the most recent latencies are kept.
+    #[test]
+    fn test_latency_history() {
+        let mut status = TargetStatus::new(target());
+        status.record(&result(None), SystemTime::now());
+        for _ in 0..LATENCY_HISTORY {
+            status.record(&result(Some("1.18.6")), SystemTime::now());
+        }
+        assert_eq!(status.latencies.len(), LATENCY_HISTORY);
+        assert!(status.latencies.iter().all(Option::is_some));
+    }
+
+    // Rounds keep coming until the receiver is dropped, each with a result per target.
+    #[tokio::test]
+    async fn test_spawn() {
+        // Reserve a port, then free it, so nothing listens on it.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+
+        let targets = vec![ScanTarget::new(address, TransportType::Tcp)];
+        let mut statuses = statuses(&targets);
+        let mut rounds = Monitor::new(targets, Duration::from_millis(10))
+            .with_scanner(Scanner::new().with_timeout(Some(1)))
+            .spawn();
+        for number in 1..=2 {
+            let round = rounds.recv().await.unwrap();
+            assert_eq!(round.number, number);
+            record_round(&mut statuses, &round);
+        }
+        assert_eq!(statuses[0].rounds, 2);
+        assert_eq!(statuses[0].consecutive_failures, 2);
+    }
+}
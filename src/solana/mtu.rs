@@ -0,0 +1,448 @@
+// Path MTU discovery towards the UDP ports of a node. Datagrams are sent with fragmentation forbidden and
+// their size binary-searched for the largest one the node still answers, so MTU blackholes, where routers
+// silently drop large packets instead of reporting them, show up as a path MTU below the link's.
+// Gossip ports answer pings, which a node still parses when padded; QUIC ports, e.g. the TPU, answer
+// packets of an unknown version with a version negotiation packet, for datagrams of 1200 bytes or more.
+// Nodes drop gossip packets larger than 1232 bytes, so gossip probes stop there, at a path MTU of 1260
+// over IPv4 and 1280 over IPv6: a path answering them all has an MTU of at least that, not exactly that.
+use {
+    crate::{
+        error::HandshakeError,
+        solana::gossip::{
+            node::PACKET_DATA_SIZE,
+            protocol::{Ping, Protocol, PING_TOKEN_SIZE},
+        },
+    },
+    solana_sdk::signature::Keypair,
+    std::{fmt, io, net::SocketAddr, str::FromStr, time::Duration},
+    tokio::{net::UdpSocket, time},
+    tracing::{debug, info},
+};
+
+// Default largest path MTU probed, that of Ethernet.
+pub const DEFAULT_MAX_MTU: u16 = 1500;
+
+// Default number of seconds a probe waits for its answer before it is sent again.
+pub const DEFAULT_MTU_PROBE_TIMEOUT_SECONDS: u32 = 1;
+
+// Times a datagram of a size is sent before the size is deemed not to reach the node.
+const PROBE_ATTEMPTS: u32 = 3;
+
+// Smallest datagram QUIC servers answer, as RFC 9000 requires of the datagrams opening a connection.
+const QUIC_MIN_DATAGRAM_SIZE: usize = 1200;
+
+// Reserved QUIC version, which no server supports, so every server answers with version negotiation.
+const QUIC_PROBE_VERSION: u32 = 0x1a2a_3a4a;
+
+// Length of the connection IDs of the QUIC probes.
+const QUIC_CONNECTION_ID_SIZE: usize = 8;
+
+// Size of the IP and UDP headers in front of a datagram, which count towards the path MTU.
+const IPV4_UDP_HEADERS_SIZE: usize = 28;
+const IPV6_UDP_HEADERS_SIZE: usize = 48;
+
+// Largest datagram a node answers, which bounds the replies read.
+const MAX_REPLY_SIZE: usize = 1500;
+
+// Protocol of the port whose path is probed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MtuProtocol {
+    Gossip, // A gossip port, probed with padded pings.
+    Quic,   // A QUIC port, e.g. the TPU, probed with packets of an unknown version.
+}
+
+impl fmt::Display for MtuProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MtuProtocol::Gossip => write!(f, "gossip"),
+            MtuProtocol::Quic => write!(f, "quic"),
+        }
+    }
+}
+
+// Protocols are named as on the command line.
+impl FromStr for MtuProtocol {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "gossip" => Ok(MtuProtocol::Gossip),
+            "quic" | "tpu" => Ok(MtuProtocol::Quic),
+            _ => Err(format!(
+                "Unknown MTU probe protocol '{}': expected gossip or quic",
+                name
+            )),
+        }
+    }
+}
+
+// How the path is probed: up to which MTU, and how long each probe waits for its answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MtuProbeOptions {
+    pub max_mtu: u16,      // Largest path MTU probed, IP and UDP headers included.
+    pub timeout: Duration, // Time a probe waits for its answer before it is sent again.
+}
+
+// Outcome of the discovery of a path MTU.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MtuProbeResult {
+    pub probes: Vec<(usize, bool)>, // Size of each datagram probed in order, and whether the node answered it.
+    pub largest_datagram: usize,    // Largest datagram the node answered.
+    pub path_mtu: usize, // Path MTU the largest datagram implies, IP and UDP headers included.
+    pub max_mtu: usize,  // Largest path MTU probed, within the protocol limit.
+}
+
+impl MtuProbeResult {
+    // Whether the path carries the largest MTU probed, so its own may be larger still.
+    pub fn at_max(&self) -> bool {
+        self.path_mtu >= self.max_mtu
+    }
+}
+
+// Discovers the path MTU towards a UDP port of a node.
+pub struct MtuProbe {
+    pub remote: SocketAddr,    // Socket address of the port probed.
+    pub protocol: MtuProtocol, // Protocol the port speaks.
+    keypair: Keypair,          // Ephemeral identity signing the gossip pings.
+}
+
+// What answers a probe.
+enum Expected {
+    // A pong to the ping.
+    Pong(Ping),
+    // A version negotiation packet echoing the destination and source connection IDs of the probe.
+    VersionNegotiation([u8; QUIC_CONNECTION_ID_SIZE], [u8; QUIC_CONNECTION_ID_SIZE]),
+}
+
+impl MtuProbe {
+    // Constructs a probe of the port, generating an identity for the gossip pings.
+    pub fn new(remote: SocketAddr, protocol: MtuProtocol) -> Self {
+        MtuProbe {
+            remote,
+            protocol,
+            keypair: Keypair::new(),
+        }
+    }
+
+    // Binary-searches the size of the largest datagram the node answers, between the smallest datagram of
+    // the protocol and the largest both the maximum MTU and the protocol allow. Fails as a timeout when even
+    // the smallest datagram goes unanswered, as the node is not reachable at all.
+    pub async fn run(&self, options: MtuProbeOptions) -> Result<MtuProbeResult, HandshakeError> {
+        let socket = bind(self.remote).await?;
+        let headers = match self.remote {
+            SocketAddr::V4(_) => IPV4_UDP_HEADERS_SIZE,
+            SocketAddr::V6(_) => IPV6_UDP_HEADERS_SIZE,
+        };
+        let min = self.min_datagram_size();
+        let max = usize::from(options.max_mtu)
+            .saturating_sub(headers)
+            .min(self.max_datagram_size())
+            .max(min);
+        let mut result = MtuProbeResult {
+            max_mtu: max + headers,
+            ..MtuProbeResult::default()
+        };
+
+        // The smallest datagram must be answered and the largest one bounds the search, unless answered too.
+        let (mut answered, mut dropped) = (min, max + 1);
+        for size in [min, max] {
+            let reached = self.probe(&socket, size, options.timeout).await?;
+            result.probes.push((size, reached));
+            match (reached, size == min) {
+                (false, true) => {
+                    return Err(HandshakeError::Timeout {
+                        phase: "MTU probe".to_string(),
+                        seconds: options.timeout.as_secs() as u32 * PROBE_ATTEMPTS,
+                    })
+                }
+                (true, _) => answered = size,
+                (false, false) => dropped = size,
+            }
+        }
+        while dropped - answered > 1 {
+            let size = (answered + dropped) / 2;
+            let reached = self.probe(&socket, size, options.timeout).await?;
+            result.probes.push((size, reached));
+            match reached {
+                true => answered = size,
+                false => dropped = size,
+            }
+        }
+
+        result.largest_datagram = answered;
+        result.path_mtu = answered + headers;
+        info!(
+            "Largest datagram reaching {} is {} bytes, a path MTU of {}",
+            self.remote, result.largest_datagram, result.path_mtu
+        );
+        Ok(result)
+    }
+
+    // Size of the smallest datagram the port answers.
+    fn min_datagram_size(&self) -> usize {
+        match self.protocol {
+            MtuProtocol::Gossip => self.datagram(0).0.len(),
+            MtuProtocol::Quic => QUIC_MIN_DATAGRAM_SIZE,
+        }
+    }
+
+    // Size of the largest datagram the port accepts, whatever the path.
+    fn max_datagram_size(&self) -> usize {
+        match self.protocol {
+            MtuProtocol::Gossip => PACKET_DATA_SIZE,
+            MtuProtocol::Quic => usize::MAX,
+        }
+    }
+
+    // Sends a datagram of the size until the node answers it, returning whether it did.
+    async fn probe(
+        &self,
+        socket: &UdpSocket,
+        size: usize,
+        timeout: Duration,
+    ) -> Result<bool, HandshakeError> {
+        for attempt in 1..=PROBE_ATTEMPTS {
+            let (datagram, expected) = self.datagram(size);
+            match socket.send_to(&datagram, self.remote).await {
+                Ok(_) => {}
+                // Larger than the MTU of the local link, or of the path as already reported to the system.
+                Err(e) if is_too_large(&e) => {
+                    debug!("Datagram of {} bytes is too large to send: {}", size, e);
+                    return Ok(false);
+                }
+                Err(e) => return Err(e.into()),
+            }
+            let answer = time::timeout(timeout, await_answer(socket, self.remote, &expected));
+            if answer.await.is_ok() {
+                debug!("Datagram of {} bytes answered", size);
+                return Ok(true);
+            }
+            debug!("Datagram of {} bytes unanswered, attempt {}", size, attempt);
+        }
+        Ok(false)
+    }
+
+    // Builds a datagram of the protocol padded to the size, when it is larger than the bare message, with
+    // what answers it.
+    fn datagram(&self, size: usize) -> (Vec<u8>, Expected) {
+        let (mut datagram, expected) = match self.protocol {
+            MtuProtocol::Gossip => {
+                let token: [u8; PING_TOKEN_SIZE] = rand::random();
+                let ping = Ping::new(token, &self.keypair);
+                let message = bincode::serialize(&Protocol::PingMessage(ping.clone()))
+                    .expect("pings serialize");
+                (message, Expected::Pong(ping))
+            }
+            MtuProtocol::Quic => {
+                let destination: [u8; QUIC_CONNECTION_ID_SIZE] = rand::random();
+                let source: [u8; QUIC_CONNECTION_ID_SIZE] = rand::random();
+                let mut packet = vec![0xc0];
+                packet.extend_from_slice(&QUIC_PROBE_VERSION.to_be_bytes());
+                packet.push(QUIC_CONNECTION_ID_SIZE as u8);
+                packet.extend_from_slice(&destination);
+                packet.push(QUIC_CONNECTION_ID_SIZE as u8);
+                packet.extend_from_slice(&source);
+                (packet, Expected::VersionNegotiation(destination, source))
+            }
+        };
+        if datagram.len() < size {
+            datagram.resize(size, 0);
+        }
+        (datagram, expected)
+    }
+}
+
+// Binds an ephemeral UDP socket of the remote's address family, forbidding the fragmentation of its datagrams.
+async fn bind(remote: SocketAddr) -> Result<UdpSocket, HandshakeError> {
+    let bind_addr: SocketAddr = match remote {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    }
+    .parse()
+    .expect("valid bind address");
+    let socket = UdpSocket::bind(bind_addr).await?;
+    forbid_fragmentation(&socket, remote)?;
+    Ok(socket)
+}
+
+// Sets the don't fragment bit on the datagrams of the socket, and has the system send them whatever path
+// MTU it last learnt, so every size is probed on the path itself.
+#[cfg(target_os = "linux")]
+fn forbid_fragmentation(socket: &UdpSocket, remote: SocketAddr) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let (level, option, value) = match remote {
+        SocketAddr::V4(_) => (
+            libc::IPPROTO_IP,
+            libc::IP_MTU_DISCOVER,
+            libc::IP_PMTUDISC_PROBE,
+        ),
+        SocketAddr::V6(_) => (
+            libc::IPPROTO_IPV6,
+            libc::IPV6_MTU_DISCOVER,
+            libc::IPV6_PMTUDISC_PROBE,
+        ),
+    };
+    // SAFETY: the socket is open for the duration of the call, and the value is a live c_int.
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            option,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    match result {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+// Fragmentation can only be forbidden on Linux; elsewhere large datagrams may be fragmented on the way.
+#[cfg(not(target_os = "linux"))]
+fn forbid_fragmentation(_: &UdpSocket, _: SocketAddr) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Path MTU discovery is only supported on Linux",
+    ))
+}
+
+// Whether sending failed because the datagram is larger than the system lets through.
+fn is_too_large(error: &io::Error) -> bool {
+    error.raw_os_error() == Some(libc::EMSGSIZE)
+}
+
+// Waits until the remote answers the probe. Answers to earlier probes, which may arrive late, are ignored
+// along with any other traffic.
+async fn await_answer(
+    socket: &UdpSocket,
+    remote: SocketAddr,
+    expected: &Expected,
+) -> Result<(), HandshakeError> {
+    let mut buf = vec![0; MAX_REPLY_SIZE];
+    loop {
+        let (n, from) = socket.recv_from(&mut buf).await?;
+        if from == remote && answers(expected, &buf[..n]) {
+            return Ok(());
+        }
+    }
+}
+
+// Whether a datagram answers a probe: a valid pong to its ping, or a version negotiation packet whose
+// connection IDs are the probe's, swapped.
+fn answers(expected: &Expected, datagram: &[u8]) -> bool {
+    match expected {
+        Expected::Pong(ping) => matches!(
+            bincode::deserialize::<Protocol>(datagram),
+            Ok(Protocol::PongMessage(pong)) if pong.verify(ping)
+        ),
+        Expected::VersionNegotiation(destination, source) => {
+            let Some(header) = datagram.get(..7 + 2 * QUIC_CONNECTION_ID_SIZE) else {
+                return false;
+            };
+            let size = QUIC_CONNECTION_ID_SIZE;
+            header[0] & 0x80 != 0
+                && header[1..5] == [0; 4]
+                && usize::from(header[5]) == size
+                && header[6..6 + size] == *source
+                && usize::from(header[6 + size]) == size
+                && header[7 + size..] == *destination
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::solana::gossip::protocol::Pong, test_case::test_case};
+
+    // Protocols are named as on the command line, the TPU by its protocol.
+    #[test_case("gossip", Ok(MtuProtocol::Gossip); "when protocol is gossip")]
+    #[test_case("quic", Ok(MtuProtocol::Quic); "when protocol is quic")]
+    #[test_case("tpu", Ok(MtuProtocol::Quic); "when protocol is tpu")]
+    #[test_case("tcp", Err("Unknown MTU probe protocol 'tcp': expected gossip or quic"); "when protocol is unknown")]
+    fn test_from_str(name: &str, expected: Result<MtuProtocol, &str>) {
+        assert_eq!(name.parse(), expected.map_err(str::to_string));
+    }
+
+    // Starts a node answering the probes of the protocol only up to the size, as behind an MTU blackhole.
+    async fn blackhole(protocol: MtuProtocol, largest: usize) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let keypair = Keypair::new();
+            let mut buf = vec![0; 65536];
+            loop {
+                let (n, from) = socket.recv_from(&mut buf).await.unwrap();
+                if n > largest {
+                    continue;
+                }
+                let reply = match protocol {
+                    MtuProtocol::Gossip => match bincode::deserialize(&buf[..n]) {
+                        Ok(Protocol::PingMessage(ping)) => {
+                            bincode::serialize(&Protocol::PongMessage(Pong::new(&ping, &keypair)))
+                                .unwrap()
+                        }
+                        _ => continue,
+                    },
+                    MtuProtocol::Quic => {
+                        // The connection IDs swapped, followed by the supported version.
+                        let (destination, source) = (&buf[6..14], &buf[15..23]);
+                        let mut reply = vec![0x80, 0, 0, 0, 0, 8];
+                        reply.extend_from_slice(source);
+                        reply.push(8);
+                        reply.extend_from_slice(destination);
+                        reply.extend_from_slice(&1u32.to_be_bytes());
+                        reply
+                    }
+                };
+                socket.send_to(&reply, from).await.unwrap();
+            }
+        });
+        address
+    }
+
+    // The search finds the largest datagram the node answers, from the smallest of the protocol up to the
+    // largest the protocol allows.
+    #[test_case(MtuProtocol::Gossip, 1000, 1000, 1028; "when gossip datagrams are dropped above 1000 bytes")]
+    #[test_case(MtuProtocol::Quic, 1350, 1350, 1378; "when quic datagrams are dropped above 1350 bytes")]
+    #[test_case(MtuProtocol::Quic, 9000, 1472, 1500; "when path carries the largest mtu")]
+    #[test_case(MtuProtocol::Gossip, 9000, 1232, 1260; "when path carries the largest gossip packet")]
+    #[tokio::test]
+    async fn test_run(
+        protocol: MtuProtocol,
+        largest: usize,
+        largest_datagram: usize,
+        path_mtu: usize,
+    ) {
+        let address = blackhole(protocol, largest).await;
+        let options = MtuProbeOptions {
+            max_mtu: DEFAULT_MAX_MTU,
+            timeout: Duration::from_millis(50),
+        };
+        let result = MtuProbe::new(address, protocol).run(options).await.unwrap();
+        assert_eq!(result.largest_datagram, largest_datagram);
+        assert_eq!(result.path_mtu, path_mtu);
+        assert_eq!(result.at_max(), largest > largest_datagram);
+        assert_eq!(
+            result.probes[0].0,
+            MtuProbe::new(address, protocol).min_datagram_size()
+        );
+        assert!(result
+            .probes
+            .iter()
+            .all(|&(size, reached)| reached == (size <= largest)));
+    }
+
+    // A node that answers nothing fails the probe as a timeout.
+    #[tokio::test]
+    async fn test_run_unanswered() {
+        let address = blackhole(MtuProtocol::Quic, 0).await;
+        let options = MtuProbeOptions {
+            max_mtu: DEFAULT_MAX_MTU,
+            timeout: Duration::from_millis(50),
+        };
+        let result = MtuProbe::new(address, MtuProtocol::Quic).run(options).await;
+        assert!(matches!(result, Err(HandshakeError::Timeout { .. })));
+    }
+}
@@ -1,16 +1,18 @@
 // Use the `async_trait` crate to enable asynchronous methods in traits,
 // which are not supported natively by Rust.
 use async_trait::async_trait;
-// Import the necessary types from the Tokio crate for asynchronous I/O operations.
-use tokio::io;
+// Import the crate-wide error type reported by failed handshakes.
+use crate::error::HandshakeError;
+// Import the signal cancelling handshakes in flight.
+use crate::solana::cancel::Cancel;
 
 /// The `Handshake` trait defines an asynchronous protocol handshake operation.
 ///
 /// Implementors of this trait must provide an asynchronous `shake` method,
 /// which may optionally accept a timeout specified as an `Option<u32>`.
-/// The method returns a `Result<String, io::Error>`, indicating the outcome
+/// The method returns a `Result<String, HandshakeError>`, indicating the outcome
 /// of the handshake operation: a success (`Ok`) returns a `String` (e.g., a session identifier),
-/// and a failure (`Err`) returns an `io::Error`.
+/// and a failure (`Err`) returns a `HandshakeError` classifying what went wrong.
 ///
 #[async_trait]
 pub trait Handshake {
@@ -20,12 +22,32 @@ pub trait Handshake {
     ///
     /// * `timeout`: An optional timeout for the handshake operation specified in seconds.
     /// If `Some(timeout)`, the operation should complete or fail within this duration.
-    /// If `None`, the operation can take indefinitely long.
+    /// If `None`, the default timeout of the underlying transport applies.
     ///
     /// # Returns
     ///
-    /// A `Result<String, io::Error>` indicating the outcome of the handshake:
+    /// A `Result<String, HandshakeError>` indicating the outcome of the handshake:
     /// - `Ok(String)`: Handshake succeeded, with the `String` representing success details.
-    /// - `Err(io::Error)`: Handshake failed due to an I/O error.
-    async fn shake(&self, timeout: Option<u32>) -> io::Result<String>;
+    /// - `Err(HandshakeError)`: Handshake failed, e.g. on DNS, connection, TLS, protocol or timeout errors.
+    async fn shake(&self, timeout: Option<u32>) -> Result<String, HandshakeError>;
+
+    /// Performs the handshake like `shake`, aborting it when the signal is fired first.
+    ///
+    /// Services embedding the library can fire the signal on their own shutdown, so a handshake
+    /// stuck on a slow node does not hold it up. The handshake in flight is dropped, closing its
+    /// connection.
+    ///
+    /// # Returns
+    ///
+    /// The outcome of the handshake, or `Err(HandshakeError::Cancelled)` if the signal was fired first.
+    async fn shake_with_cancel(
+        &self,
+        timeout: Option<u32>,
+        cancel: &Cancel,
+    ) -> Result<String, HandshakeError>
+    where
+        Self: Sync,
+    {
+        cancel.guard(self.shake(timeout)).await
+    }
 }
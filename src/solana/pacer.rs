@@ -0,0 +1,87 @@
+// Pacing of handshakes with a token bucket, so sustained monitoring and scans stay under the rate
+// limits of RPC providers. The bucket refills at the rate and holds up to a burst of tokens; each
+// handshake takes a token, waiting for one when the bucket is empty.
+use {
+    std::time::Duration,                 // Import the refill interval.
+    tokio::{sync::Mutex, time::Instant}, // Import the shared bucket state and the clock waits are timed with.
+};
+
+// A token bucket handing out permission to start a handshake, shared by concurrent handshakes.
+#[derive(Debug)]
+pub struct Pacer {
+    interval: Duration, // Time for the bucket to refill one token.
+    burst: u32,         // Tokens the bucket holds when full.
+    // When the bucket would be full again, were no more tokens taken; tokens are granted from
+    // `burst - 1` intervals before it.
+    full_at: Mutex<Instant>,
+}
+
+impl Pacer {
+    // Constructs a pacer allowing the given number of handshakes per second, one at a time.
+    // Rates are kept to at least one handshake an hour.
+    pub fn new(per_second: f64) -> Self {
+        let interval = Duration::from_secs_f64(1.0 / per_second.max(1.0 / 3600.0));
+        Pacer {
+            interval,
+            burst: 1,
+            full_at: Mutex::new(Instant::now()),
+        }
+    }
+
+    // Lets up to the given number of handshakes start at once while the bucket is full.
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    // Waits until a token is available and takes it.
+    pub async fn acquire(&self) {
+        let granted_at = {
+            let mut full_at = self.full_at.lock().await;
+            let now = Instant::now();
+            let full = (*full_at).max(now);
+            *full_at = full + self.interval;
+            let tokens_ahead = self.interval * (self.burst - 1);
+            full.checked_sub(tokens_ahead).map_or(now, |at| at.max(now))
+        };
+        tokio::time::sleep_until(granted_at).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Handshakes beyond the burst are spaced by the refill interval.
+    #[tokio::test]
+    async fn test_acquire_paces() {
+        let pacer = Pacer::new(50.0);
+        let started = Instant::now();
+        for _ in 0..5 {
+            pacer.acquire().await;
+        }
+        assert!(started.elapsed() >= Duration::from_millis(80));
+    }
+
+    // A full bucket lets the burst through at once, then refills at the rate.
+    #[tokio::test]
+    async fn test_acquire_bursts() {
+        let pacer = Pacer::new(5.0).with_burst(3);
+        let started = Instant::now();
+        for _ in 0..3 {
+            pacer.acquire().await;
+        }
+        assert!(started.elapsed() < Duration::from_millis(100));
+
+        pacer.acquire().await;
+        assert!(started.elapsed() >= Duration::from_millis(200));
+
+        // An idle pacer refills, up to the burst.
+        tokio::time::sleep(Duration::from_millis(700)).await;
+        let rested = Instant::now();
+        for _ in 0..3 {
+            pacer.acquire().await;
+        }
+        assert!(rested.elapsed() < Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,340 @@
+// Reachability pre-checks run before a handshake, so a host that is down or unrouted at the network layer
+// fails as `unreachable` rather than as a connection failure or timeout. Once the host answers, a failed
+// handshake points at the service: its port is closed when refused, and filtered when it times out.
+// Hosts that drop ICMP fail the ICMP pre-check even when their services are up.
+use {
+    crate::error::HandshakeError,
+    socket2::{Domain, Protocol, Socket, Type},
+    std::{
+        fmt,
+        io::{self, ErrorKind},
+        net::{IpAddr, SocketAddr},
+        str::FromStr,
+        time::{Duration, Instant},
+    },
+    tokio::{net::UdpSocket, time},
+    tracing::{info, warn},
+};
+
+// Default number of seconds to wait for the host to answer the pre-check.
+pub const DEFAULT_PRECHECK_TIMEOUT_SECONDS: u32 = 2;
+
+// Delay before an unanswered echo request is sent again, so a single lost packet does not fail the check.
+const ECHO_RESEND_INTERVAL: Duration = Duration::from_millis(500);
+
+// Size of the random payload matching echo replies with the requests of a check.
+const ECHO_TOKEN_SIZE: usize = 16;
+
+// Size of an ICMP header: type, code, checksum, identifier and sequence number.
+pub(crate) const ICMP_HEADER_SIZE: usize = 8;
+
+// Size of the fixed header of an IPv6 packet.
+pub(crate) const IPV6_HEADER_SIZE: usize = 40;
+
+// ICMP message types of the echo exchange and of the errors routers send back.
+const ICMPV4_ECHO_REPLY: u8 = 0;
+pub(crate) const ICMPV4_DESTINATION_UNREACHABLE: u8 = 3;
+const ICMPV4_ECHO_REQUEST: u8 = 8;
+pub(crate) const ICMPV4_TIME_EXCEEDED: u8 = 11;
+pub(crate) const ICMPV6_DESTINATION_UNREACHABLE: u8 = 1;
+pub(crate) const ICMPV6_TIME_EXCEEDED: u8 = 3;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+
+// A check of the host's reachability run before the handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precheck {
+    Icmp, // An ICMP echo request, which the host must answer.
+}
+
+impl Precheck {
+    // Checks the host answers, returning the round trip of its answer. The check is skipped, with a
+    // warning, when its socket cannot be opened, e.g. without the privileges ICMP requires.
+    pub async fn check(
+        &self,
+        host: IpAddr,
+        timeout: Option<u32>,
+    ) -> Result<Option<Duration>, HandshakeError> {
+        let seconds = timeout.unwrap_or(DEFAULT_PRECHECK_TIMEOUT_SECONDS);
+        match self {
+            Precheck::Icmp => {
+                let socket = match open(host) {
+                    Ok(socket) => socket,
+                    Err(e) => {
+                        warn!("Skipping the ICMP pre-check of {}: {}", host, e);
+                        return Ok(None);
+                    }
+                };
+                let round_trip = echo(&socket, host, Duration::from_secs(seconds.into())).await?;
+                info!("{} answered the ICMP pre-check in {:?}", host, round_trip);
+                Ok(Some(round_trip))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Precheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Precheck::Icmp => write!(f, "icmp"),
+        }
+    }
+}
+
+// Pre-checks are named as on the command line.
+impl FromStr for Precheck {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "icmp" => Ok(Precheck::Icmp),
+            _ => Err(format!("Unknown pre-check '{}': expected icmp", name)),
+        }
+    }
+}
+
+// An ICMP socket, and whether it is raw, so its IPv4 packets keep their IP header.
+struct IcmpSocket {
+    socket: UdpSocket,
+    raw: bool,
+}
+
+// Opens an ICMP socket of the host's family: an unprivileged datagram socket where the system allows
+// one, a raw socket otherwise.
+fn open(host: IpAddr) -> io::Result<IcmpSocket> {
+    let (domain, protocol) = match host {
+        IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4),
+        IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6),
+    };
+    let (socket, raw) = match Socket::new(domain, Type::DGRAM, Some(protocol)) {
+        Ok(socket) => (socket, false),
+        Err(_) => (Socket::new(domain, Type::RAW, Some(protocol))?, true),
+    };
+    socket.set_nonblocking(true)?;
+    let socket = UdpSocket::from_std(socket.into())?;
+    Ok(IcmpSocket { socket, raw })
+}
+
+// Sends echo requests to the host until one is answered or the timeout elapses.
+async fn echo(
+    socket: &IcmpSocket,
+    host: IpAddr,
+    timeout: Duration,
+) -> Result<Duration, HandshakeError> {
+    let identifier: u16 = rand::random();
+    let token: [u8; ECHO_TOKEN_SIZE] = rand::random();
+    let started = Instant::now();
+    let deadline = started + timeout;
+    let mut buf = vec![0; 1500];
+    let mut sequence: u16 = 0;
+    loop {
+        let request = echo_request(host, identifier, sequence, &token);
+        socket
+            .socket
+            .send_to(&request, SocketAddr::new(host, 0))
+            .await?;
+        let wait = ECHO_RESEND_INTERVAL.min(deadline.saturating_duration_since(Instant::now()));
+        let answer = async {
+            loop {
+                let (n, from) = match socket.socket.recv_from(&mut buf).await {
+                    Ok(received) => received,
+                    Err(e) if is_unreachable(&e) => return Err(unreachable(host, &e.to_string())),
+                    Err(e) => return Err(e.into()),
+                };
+                let packet = match (host, socket.raw) {
+                    (IpAddr::V4(_), true) => strip_ipv4_header(&buf[..n]),
+                    _ => Some(&buf[..n]),
+                };
+                match packet.map(|packet| parse(host, packet, identifier, &token, socket.raw)) {
+                    Some(Some(Answer::Reply)) if from.ip() == host => return Ok(()),
+                    Some(Some(Answer::Error(reason))) => {
+                        return Err(unreachable(host, &format!("{} from {}", reason, from.ip())))
+                    }
+                    _ => continue, // Replies to other checks and other ICMP traffic.
+                }
+            }
+        };
+        match time::timeout(wait, answer).await {
+            Ok(answered) => return answered.map(|_| started.elapsed()),
+            Err(_) if Instant::now() >= deadline => {
+                let reason = format!("no ICMP echo reply within {}s", timeout.as_secs());
+                return Err(unreachable(host, &reason));
+            }
+            Err(_) => sequence = sequence.wrapping_add(1),
+        }
+    }
+}
+
+// Whether a socket error tells the host cannot be reached.
+fn is_unreachable(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::HostUnreachable | ErrorKind::NetworkUnreachable
+    )
+}
+
+// The error of a host that did not pass the pre-check.
+fn unreachable(host: IpAddr, reason: &str) -> HandshakeError {
+    HandshakeError::Unreachable(format!("{} failed the ICMP pre-check: {}", host, reason))
+}
+
+// An ICMP message answering an echo request of the check.
+#[derive(Debug, PartialEq, Eq)]
+enum Answer {
+    Reply,               // The host answered the request.
+    Error(&'static str), // A router reported the host cannot be reached.
+}
+
+// Builds an echo request carrying the token. The checksum of ICMPv6 covers a pseudo-header of the IP
+// addresses, so the system computes it; that of ICMPv4 is computed here.
+fn echo_request(host: IpAddr, identifier: u16, sequence: u16, token: &[u8]) -> Vec<u8> {
+    let kind = match host {
+        IpAddr::V4(_) => ICMPV4_ECHO_REQUEST,
+        IpAddr::V6(_) => ICMPV6_ECHO_REQUEST,
+    };
+    let mut packet = vec![kind, 0, 0, 0];
+    packet.extend_from_slice(&identifier.to_be_bytes());
+    packet.extend_from_slice(&sequence.to_be_bytes());
+    packet.extend_from_slice(token);
+    if host.is_ipv4() {
+        let checksum = checksum(&packet);
+        packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+    }
+    packet
+}
+
+// The Internet checksum: the one's complement of the one's complement sum of the 16-bit words.
+fn checksum(packet: &[u8]) -> u16 {
+    let mut sum: u32 = packet
+        .chunks(2)
+        .map(|word| u32::from(word[0]) << 8 | u32::from(*word.get(1).unwrap_or(&0)))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+// The ICMP message of an IPv4 packet received on a raw socket, after its header.
+pub(crate) fn strip_ipv4_header(packet: &[u8]) -> Option<&[u8]> {
+    let length = usize::from(packet.first()? & 0x0f) * 4;
+    packet.get(length..)
+}
+
+// Parses an ICMP message, telling whether it answers an echo request of the check. Datagram sockets only
+// receive the replies to their own requests, the system replacing their identifier with one of its own;
+// raw sockets receive every ICMP message, so replies are matched on their identifier too.
+fn parse(host: IpAddr, message: &[u8], identifier: u16, token: &[u8], raw: bool) -> Option<Answer> {
+    let header = message.get(..ICMP_HEADER_SIZE)?;
+    let (reply, errors) = match host {
+        IpAddr::V4(_) => (
+            ICMPV4_ECHO_REPLY,
+            [ICMPV4_DESTINATION_UNREACHABLE, ICMPV4_TIME_EXCEEDED],
+        ),
+        IpAddr::V6(_) => (
+            ICMPV6_ECHO_REPLY,
+            [ICMPV6_DESTINATION_UNREACHABLE, ICMPV6_TIME_EXCEEDED],
+        ),
+    };
+    let matches = |echo: &[u8]| !raw || echo[4..6] == identifier.to_be_bytes();
+    if header[0] == reply {
+        return (matches(header) && message[ICMP_HEADER_SIZE..] == *token).then_some(Answer::Reply);
+    }
+
+    // Errors quote the IP header of the request they are about, followed by the start of its ICMP message.
+    let index = errors.iter().position(|&kind| kind == header[0])?;
+    let quoted = &message[ICMP_HEADER_SIZE..];
+    let echo = match host {
+        IpAddr::V4(_) => strip_ipv4_header(quoted)?,
+        IpAddr::V6(_) => quoted.get(IPV6_HEADER_SIZE..)?,
+    };
+    let request = match host {
+        IpAddr::V4(_) => ICMPV4_ECHO_REQUEST,
+        IpAddr::V6(_) => ICMPV6_ECHO_REQUEST,
+    };
+    let echo = echo.get(..ICMP_HEADER_SIZE)?;
+    (echo[0] == request && matches(echo)).then_some(Answer::Error(match index {
+        0 => "destination unreachable",
+        _ => "time exceeded",
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, test_case::test_case};
+
+    // Pre-checks are named as on the command line.
+    #[test]
+    fn test_from_str() {
+        assert_eq!("icmp".parse::<Precheck>(), Ok(Precheck::Icmp));
+        assert_eq!(Precheck::Icmp.to_string(), "icmp");
+        assert_eq!(
+            "tcp".parse::<Precheck>(),
+            Err("Unknown pre-check 'tcp': expected icmp".to_string())
+        );
+    }
+
+    // ICMPv4 requests carry their checksum, which sums to zero over the whole message.
+    #[test]
+    fn test_echo_request_checksum() {
+        let request = echo_request("127.0.0.1".parse().unwrap(), 0x1234, 7, &[0xab; 16]);
+        assert_eq!(&request[..2], &[ICMPV4_ECHO_REQUEST, 0]);
+        assert_eq!(&request[4..8], &[0x12, 0x34, 0, 7]);
+        assert_eq!(checksum(&request), 0);
+    }
+
+    // Replies must carry the check's token, and its identifier on raw sockets; routers' errors must quote
+    // one of its requests.
+    #[test_case("127.0.0.1", ICMPV4_ECHO_REPLY, 0x1234, [1; 16], true, Some(Answer::Reply); "when reply matches")]
+    #[test_case("127.0.0.1", ICMPV4_ECHO_REPLY, 0x4321, [1; 16], true, None; "when reply is for another raw socket")]
+    #[test_case("127.0.0.1", ICMPV4_ECHO_REPLY, 0x4321, [1; 16], false, Some(Answer::Reply); "when datagram reply has system identifier")]
+    #[test_case("127.0.0.1", ICMPV4_ECHO_REPLY, 0x1234, [2; 16], true, None; "when reply carries another token")]
+    #[test_case("::1", ICMPV6_ECHO_REPLY, 0x1234, [1; 16], true, Some(Answer::Reply); "when ipv6 reply matches")]
+    #[test_case("127.0.0.1", ICMPV4_ECHO_REQUEST, 0x1234, [1; 16], true, None; "when message is a request")]
+    fn test_parse_reply(
+        host: &str,
+        kind: u8,
+        identifier: u16,
+        token: [u8; 16],
+        raw: bool,
+        expected: Option<Answer>,
+    ) {
+        let mut message = vec![kind, 0, 0, 0];
+        message.extend_from_slice(&identifier.to_be_bytes());
+        message.extend_from_slice(&[0, 1]);
+        message.extend_from_slice(&token);
+        let answer = parse(host.parse().unwrap(), &message, 0x1234, &[1; 16], raw);
+        assert_eq!(answer, expected);
+    }
+
+    // Destination unreachable errors quoting a request of the check fail it.
+    #[test_case(0x1234, Some(Answer::Error("destination unreachable")); "when error quotes the request")]
+    #[test_case(0x4321, None; "when error quotes another request")]
+    fn test_parse_error(identifier: u16, expected: Option<Answer>) {
+        let mut message = vec![ICMPV4_DESTINATION_UNREACHABLE, 1, 0, 0, 0, 0, 0, 0];
+        message.extend_from_slice(&[0x45; 1]);
+        message.extend_from_slice(&[0; 19]);
+        message.extend_from_slice(&[ICMPV4_ECHO_REQUEST, 0, 0, 0]);
+        message.extend_from_slice(&identifier.to_be_bytes());
+        message.extend_from_slice(&[0, 1]);
+        let answer = parse(
+            "10.0.0.1".parse().unwrap(),
+            &message,
+            0x1234,
+            &[1; 16],
+            true,
+        );
+        assert_eq!(answer, expected);
+    }
+
+    // The loopback address answers, where the system lets ICMP sockets be opened.
+    #[tokio::test]
+    async fn test_check_loopback() {
+        let host: IpAddr = "127.0.0.1".parse().unwrap();
+        if open(host).is_err() {
+            return; // Unprivileged ICMP sockets are not allowed here, so there is nothing to check.
+        }
+        let round_trip = Precheck::Icmp.check(host, Some(2)).await.unwrap();
+        assert!(round_trip.is_some());
+    }
+}
@@ -0,0 +1,218 @@
+// Probing of a host on the well-known ports of a Solana node, each with the handshake its surface
+// speaks: JSON RPC over TCP, PubSub over WebSocket, gossip over UDP and the TPU over QUIC. It tells
+// which surfaces of a node are reachable without knowing beforehand which services it exposes.
+use {
+    crate::{
+        error::HandshakeError,
+        solana::{
+            gossip::node::GossipNode,
+            node::Handshake,
+            rpc::node::{RpcHandshakeResponse, RpcNode},
+            target::{AddressFamily, Target},
+            tpu::node::TpuNode,
+            transport::{timed, ConnectionInfo, PhaseTimings},
+            TransportType,
+        },
+    },
+    std::{
+        fmt,
+        ops::RangeInclusive,
+        time::{Duration, Instant},
+    },
+    tokio::task::JoinSet,
+};
+
+// Default port of the JSON RPC endpoint.
+pub const RPC_PORT: u16 = 8899;
+
+// Default port of the PubSub WebSocket endpoint, the one after the JSON RPC endpoint's.
+pub const PUBSUB_PORT: u16 = 8900;
+
+// Default gossip port, the first of the validator's default dynamic port range.
+pub const GOSSIP_PORT: u16 = 8001;
+
+// Default QUIC ports of the TPU and of TPU forwards, 6 above their UDP counterparts in the default
+// dynamic port range.
+pub const TPU_PORTS: RangeInclusive<u16> = 8009..=8010;
+
+// Default timeout, in seconds, for each phase of a probe.
+// Short, as closed ports of UDP and QUIC surfaces only ever time out.
+pub const DEFAULT_PROBE_TIMEOUT_SECONDS: u32 = 5;
+
+// A service exposed by a Solana node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Surface {
+    Rpc,    // The JSON RPC endpoint, handshaken with getVersion over TCP.
+    PubSub, // The PubSub endpoint, handshaken with getVersion over WebSocket.
+    Gossip, // The gossip port, handshaken with a ping/pong over UDP.
+    Tpu,    // A TPU port, handshaken over QUIC.
+}
+
+impl Surface {
+    // Name of the transport the surface is handshaken over.
+    pub fn transport(&self) -> &'static str {
+        match self {
+            Surface::Rpc => "tcp",
+            Surface::PubSub => "ws",
+            Surface::Gossip => "udp",
+            Surface::Tpu => "quic",
+        }
+    }
+}
+
+// Implement the Display trait for Surface, using the lowercase names shown to users.
+impl fmt::Display for Surface {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Surface::Rpc => "rpc",
+            Surface::PubSub => "pubsub",
+            Surface::Gossip => "gossip",
+            Surface::Tpu => "tpu",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// A port probed with the handshake of a surface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortProbe {
+    pub surface: Surface, // The surface expected on the port.
+    pub port: u16,        // The port probed.
+}
+
+// The well-known ports of a Solana node, with the given TPU ports.
+pub fn well_known_ports(tpu_ports: RangeInclusive<u16>) -> Vec<PortProbe> {
+    let probe = |surface, port| PortProbe { surface, port };
+    [
+        probe(Surface::Rpc, RPC_PORT),
+        probe(Surface::PubSub, PUBSUB_PORT),
+        probe(Surface::Gossip, GOSSIP_PORT),
+    ]
+    .into_iter()
+    .chain(tpu_ports.map(|port| probe(Surface::Tpu, port)))
+    .collect()
+}
+
+// Outcome of the probe of a port.
+#[derive(Debug)]
+pub struct ProbeResult {
+    pub probe: PortProbe,           // The port probed.
+    pub target: Target,             // The host on the port.
+    pub duration: Duration,         // Time taken by the handshake, successful or not.
+    pub timings: PhaseTimings,      // Time spent in each phase of the handshake, successful or not.
+    pub connection: ConnectionInfo, // What the handshake learnt about its connection, e.g. the TLS version.
+    // Version reported by RPC surfaces, nothing for the others, or why the handshake failed.
+    pub result: Result<Option<RpcHandshakeResponse>, HandshakeError>,
+}
+
+// Handshakes a port of a host as its surface expects.
+async fn probe_port(
+    target: &Target,
+    surface: Surface,
+    timeout: Option<u32>,
+) -> Result<Option<RpcHandshakeResponse>, HandshakeError> {
+    match surface {
+        Surface::Rpc => RpcNode::new(target.clone(), TransportType::Tcp)
+            .shake_typed(timeout)
+            .await
+            .map(Some),
+        Surface::PubSub => RpcNode::new(target.clone(), TransportType::Ws)
+            .shake_typed(timeout)
+            .await
+            .map(Some),
+        Surface::Gossip => {
+            let address = target.address(AddressFamily::Any)?;
+            GossipNode::new(address).shake(timeout).await.map(|_| None)
+        }
+        Surface::Tpu => {
+            let address = target.address(AddressFamily::Any)?;
+            TpuNode::new(address).shake(timeout).await.map(|_| None)
+        }
+    }
+}
+
+// Probes every port of the host concurrently and returns the results in the order of the probes.
+pub async fn probe(
+    host: &Target,
+    probes: Vec<PortProbe>,
+    timeout: Option<u32>,
+) -> Vec<ProbeResult> {
+    let mut tasks = JoinSet::new();
+    for (index, probe) in probes.into_iter().enumerate() {
+        let target = host.with_port(probe.port);
+        tasks.spawn(async move {
+            let started = Instant::now();
+            let (result, timings, connection) =
+                timed(probe_port(&target, probe.surface, timeout)).await;
+            let probe_result = ProbeResult {
+                probe,
+                target,
+                duration: started.elapsed(),
+                timings,
+                connection,
+                result,
+            };
+            (index, probe_result)
+        });
+    }
+
+    let mut results = Vec::with_capacity(tasks.len());
+    while let Some(joined) = tasks.join_next().await {
+        results.push(joined.expect("probe task panicked"));
+    }
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tokio::net::TcpListener};
+
+    // The RPC, PubSub and gossip ports come first, then every TPU port.
+    #[test]
+    fn test_well_known_ports() {
+        let probes = well_known_ports(8009..=8011);
+        let ports: Vec<(Surface, u16)> = probes.iter().map(|p| (p.surface, p.port)).collect();
+        assert_eq!(
+            ports,
+            vec![
+                (Surface::Rpc, 8899),
+                (Surface::PubSub, 8900),
+                (Surface::Gossip, 8001),
+                (Surface::Tpu, 8009),
+                (Surface::Tpu, 8010),
+                (Surface::Tpu, 8011),
+            ]
+        );
+    }
+
+    // Every port is reported, a closed one as failed, in the order of the probes.
+    #[tokio::test]
+    async fn test_probe() {
+        // Reserve a port, then free it, so nothing listens on it.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let host = Target::resolve_host("127.0.0.1").unwrap();
+        let probes = vec![
+            PortProbe {
+                surface: Surface::Rpc,
+                port: closed,
+            },
+            PortProbe {
+                surface: Surface::PubSub,
+                port: closed,
+            },
+        ];
+        let results = probe(&host, probes, Some(1)).await;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].probe.surface, Surface::Rpc);
+        assert_eq!(
+            results[1].target.to_string(),
+            format!("127.0.0.1:{}", closed)
+        );
+        assert!(results.iter().all(|result| result.result.is_err()));
+    }
+}
@@ -0,0 +1,197 @@
+// JSON-RPC 2.0 messages: the request, the response envelope with its error object, and the `getVersion`
+// result a handshake reads. They hold no connection, so the handshake can be built and read in the browser too.
+use {
+    super::validate::{self, Member, Shape},
+    crate::{error::HandshakeError, solana::transport::PhaseTimings},
+    rand::Rng,
+    semver::{Version, VersionReq},
+    serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize},
+};
+
+// Draws the id of a single request at random, so a response to another request, e.g. one a caching
+// proxy kept, is told apart. Ids stay below 2^31, which every JSON implementation reads exactly.
+pub fn request_id() -> u64 {
+    rand::thread_rng().gen_range(1..=i32::MAX as u64)
+}
+
+// Response structure expected from an RPC handshake, defining how to deserialize the JSON response.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RpcHandshakeResponse {
+    #[serde(rename = "solana-core")] // Maps "solana-core" field in JSON to solana_core attribute.
+    pub solana_core: String, // The version of the solana-core software running on the node.
+    #[serde(rename = "feature-set")]
+    // Optional field indicating the feature set supported by the node.
+    pub feature_set: Option<u64>,
+    #[serde(skip)] // Measured by the client rather than reported by the node.
+    pub timings: PhaseTimings, // Time spent in each phase of the handshake.
+}
+
+impl RpcHandshakeResponse {
+    // Shape of the `getVersion` result: the solana-core version and, from nodes recent enough, the feature set.
+    pub fn shape() -> Shape {
+        Shape::Object(vec![
+            Member::required("solana-core", Shape::String),
+            Member::optional("feature-set", Shape::Nullable(Box::new(Shape::Integer))),
+        ])
+    }
+
+    // Parses the solana-core version the node reported.
+    pub fn version(&self) -> Result<Version, HandshakeError> {
+        Version::parse(&self.solana_core).map_err(|e| {
+            HandshakeError::InvalidResponse(format!(
+                "Invalid solana-core version {}: {}",
+                self.solana_core, e
+            ))
+        })
+    }
+
+    // Indicates whether the reported version satisfies the requirement, e.g. `1.18.*`.
+    pub fn satisfies(&self, requirement: &VersionReq) -> Result<bool, HandshakeError> {
+        Ok(requirement.matches(&self.version()?))
+    }
+}
+
+// Error object returned in a JSON-RPC envelope when the call fails.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RpcError {
+    pub code: i64,       // JSON-RPC error code, e.g. -32601 for an unknown method.
+    pub message: String, // Human-readable description of the error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>, // Optional structured details, e.g. how far a node is behind.
+}
+
+// An error object the node answered with is the handshake's error.
+impl From<RpcError> for HandshakeError {
+    fn from(error: RpcError) -> Self {
+        HandshakeError::Rpc {
+            code: error.code,
+            message: error.message,
+        }
+    }
+}
+
+// JSON-RPC 2.0 response envelope wrapping either a result or an error.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(bound(deserialize = "T: Deserialize<'de>"))]
+pub struct RpcResponse<T> {
+    pub jsonrpc: String, // JSON-RPC version, expected to be "2.0".
+    pub id: Option<u64>, // Identifier echoed from the request.
+    #[serde(default, deserialize_with = "present")]
+    pub result: Option<T>, // Result of a successful call, which may be null.
+    pub error: Option<RpcError>, // Error of a failed call.
+}
+
+// Deserializes a member that is present, even as null, into `Some`, so a call whose result is null
+// is told apart from a response without a result.
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+impl<T: DeserializeOwned> RpcResponse<T> {
+    // Parses a JSON-RPC response body into its envelope; a body that is not a valid envelope is an invalid response.
+    pub fn parse(body: &str) -> Result<RpcResponse<T>, HandshakeError> {
+        serde_json::from_str(body).map_err(|e| {
+            HandshakeError::InvalidResponse(format!("Unable to parse JSON-RPC response: {}", e))
+        })
+    }
+
+    // Parses a JSON-RPC response body and extracts its result.
+    pub fn parse_result(body: &str) -> Result<T, HandshakeError> {
+        RpcResponse::parse(body)?.into_result()
+    }
+
+    // Parses a JSON-RPC response body and extracts its result, once the body is validated strictly as the
+    // response to the request with the id, whose result has the shape.
+    pub fn parse_validated(body: &str, id: u64, shape: &Shape) -> Result<T, HandshakeError> {
+        validate::validate(body, id, shape)?;
+        RpcResponse::parse_result(body)
+    }
+
+    // Extracts the result of the call; an error object is an RPC error carrying its code and message.
+    pub fn into_result(self) -> Result<T, HandshakeError> {
+        match (self.result, self.error) {
+            (_, Some(error)) => Err(error.into()),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(HandshakeError::InvalidResponse(
+                "JSON-RPC response has neither a result nor an error".to_string(),
+            )),
+        }
+    }
+}
+
+// Request structure for initiating an RPC handshake, specifying the expected JSON structure.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RpcHandshakeRequest {
+    #[serde(rename = "jsonrpc")] // Specifies the JSON RPC version being used.
+    json_rpc: String,
+    pub(crate) id: u64,        // Unique identifier for the request.
+    pub(crate) method: String, // The RPC method being called, in this case, to get the node version.
+    #[serde(skip_serializing_if = "Option::is_none")] // Parameterless methods omit the member.
+    params: Option<serde_json::Value>, // Positional or named parameters of the method.
+}
+
+impl RpcHandshakeRequest {
+    // Constructs a JSON-RPC 2.0 request for a parameterless method.
+    pub fn new(id: u64, method: &str) -> Self {
+        RpcHandshakeRequest {
+            json_rpc: "2.0".to_string(),
+            id,
+            method: method.to_string(),
+            params: None,
+        }
+    }
+
+    // Sets the parameters of the method: an array of positional ones, or an object of named ones.
+    pub fn with_params(mut self, params: Option<serde_json::Value>) -> Self {
+        self.params = params;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, test_case::test_case};
+
+    // Parses the result of a successful getVersion call.
+    #[test]
+    fn test_parse_version_result() {
+        let body = r#"{"jsonrpc":"2.0","result":{"feature-set":3580551090,"solana-core":"1.18.6"},"id":1}"#;
+        let response: RpcHandshakeResponse = RpcResponse::parse_result(body).unwrap();
+
+        assert_eq!(response.solana_core, "1.18.6");
+        assert_eq!(response.feature_set, Some(3580551090));
+    }
+
+    // The reported version is checked against semver requirements; an unparsable one is invalid.
+    #[test_case("1.18.*", true; "when minor matches the wildcard")]
+    #[test_case(">=1.17, <1.19", true; "when version is within the range")]
+    #[test_case("~1.17", false; "when minor differs")]
+    #[test_case("=1.18.5", false; "when patch differs")]
+    fn test_version_satisfies(requirement: &str, expected: bool) {
+        let body = r#"{"jsonrpc":"2.0","result":{"feature-set":3580551090,"solana-core":"1.18.6"},"id":1}"#;
+        let response: RpcHandshakeResponse = RpcResponse::parse_result(body).unwrap();
+        let requirement = VersionReq::parse(requirement).unwrap();
+
+        assert_eq!(response.satisfies(&requirement).unwrap(), expected);
+        let response = RpcHandshakeResponse {
+            solana_core: "unknown".to_string(),
+            ..response
+        };
+        assert!(response.satisfies(&requirement).is_err());
+    }
+
+    // A JSON-RPC error object is reported as an RPC error carrying the code and message.
+    #[test]
+    fn test_parse_error_object() {
+        let body =
+            r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found"},"id":1}"#;
+        let error = RpcResponse::<RpcHandshakeResponse>::parse_result(body).unwrap_err();
+
+        assert!(matches!(error, HandshakeError::Rpc { code: -32601, .. }));
+        assert_eq!(error.to_string(), "RPC error -32601: Method not found");
+    }
+}
@@ -1,2 +1,10 @@
+// JSON-RPC requests and responses, shared by the RPC node and the browser
+pub mod message;
 // RPC node implementation
+#[cfg(not(target_arch = "wasm32"))]
 pub mod node;
+// Slot subscription check of the WebSocket PubSub endpoint
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pubsub;
+// Strict validation of JSON-RPC responses, reporting the field at fault
+pub mod validate;
@@ -1,60 +1,544 @@
 // Main module for RPC functionality with support for multiple transport types.
 // This includes the definitions for the RpcNode struct, transport selection, and handshake mechanisms.
+use crate::chain::{ChainHandshake, NodeInfo}; // Chain-agnostic handshakes.
+use crate::error::HandshakeError; // Crate-wide error type reported by failed handshakes.
 use crate::solana::{
+    chain::Solana,
     node::Handshake,
-    transport::{tcp::Tcp, ws::Ws, ChooseTransport, Transport},
+    rpc::{
+        pubsub::{self, SlotSubscription},
+        validate::{self, Shape},
+    },
+    target::Target,
+    transport::{
+        pool::{ConnectionPool, PooledTransport},
+        select,
+        tcp::{ConnectOptions, Connectable},
+        timed, traced,
+        ws::{rationalise_url, ws_connect, ws_exchange, ws_ping, PingOptions},
+        ChooseTransport, Transport,
+    },
     TransportType,
 }; // Import necessary traits and structures for handshake and transport.
+   // The JSON-RPC messages, declared where the browser can use them too and re-exported under their original paths.
+pub use crate::solana::rpc::message::{
+    request_id, RpcError, RpcHandshakeRequest, RpcHandshakeResponse, RpcResponse,
+};
 use async_trait::async_trait; // Enables async trait methods, crucial for async network operations.
-use serde::{Deserialize, Serialize}; // Allows for easy serialization and deserialization of data structures.
-use std::{fmt, io, net::SocketAddr}; // Standard library imports for networking and display formatting.
+use serde::{de::DeserializeOwned, Deserialize, Serialize}; // Allows for easy serialization and deserialization of data structures.
+use solana_sdk::pubkey::Pubkey; // Identity pubkeys reported by getIdentity.
+use std::{fmt, str::FromStr, sync::Arc, time::Duration}; // Standard library imports for parsing, sharing, ping round trips and display formatting.
+
+// Where an RPC node's calls get their transport from.
+// `Dynamic`, the default, selects one for every call from the node's transport type and options, as
+// `ChooseTransport` does. Any `Transport` stored in the node is used as is instead, so calls are
+// monomorphized over it and no transport is boxed; the node's pool, proxy and TLS options are then unused.
+pub trait Dispatch: Sized + Send + Sync {
+    // Returns the transport the node's next call goes through.
+    fn transport<'a>(&'a self, node: &'a RpcNode<Self>) -> impl Transport + 'a;
+}
+
+// Selects the transport of every call at runtime, from the node's transport type and options.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Dynamic;
+
+impl Dispatch for Dynamic {
+    fn transport<'a>(&'a self, node: &'a RpcNode<Self>) -> impl Transport + 'a {
+        node.get_transport()
+    }
+}
+
+impl<T: Transport> Dispatch for T {
+    fn transport<'a>(&'a self, _node: &'a RpcNode<Self>) -> impl Transport + 'a {
+        self
+    }
+}
 
 // Struct defining an RPC node, including its remote address and transport type for connectivity.
-pub struct RpcNode {
-    pub remote: SocketAddr,            // Socket address of the remote endpoint.
+// The transport is selected per call unless the node was given one with `with_transport`.
+pub struct RpcNode<T = Dynamic> {
+    pub remote: Target, // The remote endpoint: its name as given and the addresses it resolved to.
     pub transport_type: TransportType, // Enum specifying the type of transport to use.
+    pub pool: Option<Arc<ConnectionPool>>, // Optional pool for reusing connections across handshakes.
+    pub connect: ConnectOptions,           // Proxy and TLS options of the TCP-based connections.
+    pub timeout: Option<u32>, // Timeout in seconds applied to each phase when a call gives none.
+    pub method: Option<String>, // JSON-RPC method called as the handshake instead of `getVersion`.
+    pub params: Option<serde_json::Value>, // Parameters of that method, if any.
+    pub transport: T, // Where calls get their transport from, `Dynamic` unless one was given.
+}
+
+// Builder of RpcNode, so options can be added without growing the signature of `RpcNode::new`.
+// Only the address is required; the transport defaults to plain TCP.
+#[derive(Default)]
+pub struct RpcNodeBuilder {
+    address: Option<Target>,               // The remote endpoint.
+    transport_type: Option<TransportType>, // Transport to use, TCP when not given.
+    timeout: Option<u32>,                  // Default timeout in seconds of each phase.
+    pool: Option<Arc<ConnectionPool>>,     // Pool for reusing connections across handshakes.
+    connect: ConnectOptions,               // How connections are made.
+    method: Option<String>,                // Method called instead of `getVersion`.
+    params: Option<serde_json::Value>,     // Parameters of the method.
+}
+
+// Result of pinging a node over WebSocket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcPingResponse {
+    pub handshake: Option<RpcHandshakeResponse>, // The `getVersion` handshake, when one preceded the pings.
+    pub round_trips: Vec<Duration>,              // Round-trip time of each Ping, in the order sent.
 }
 
-// Response structure expected from an RPC handshake, defining how to deserialize the JSON response.
-#[derive(Serialize, Deserialize, Debug)]
-pub struct RpcHandshakeResponse {
-    #[serde(rename = "solana-core")] // Maps "solana-core" field in JSON to solana_core attribute.
-    solana_core: String, // The version of the solana-core software running on the node.
-    #[serde(rename = "feature-set")]
-    // Optional field indicating the feature set supported by the node.
-    feature_set: Option<u64>,
+// Response structure of `getIdentity`, carrying the node's identity pubkey in base58.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RpcIdentityResponse {
+    pub identity: String, // Base58-encoded identity pubkey of the node.
 }
 
-// Request structure for initiating an RPC handshake, specifying the expected JSON structure.
-#[derive(Serialize, Deserialize, Debug)]
-pub struct RpcHandshakeRequest {
-    #[serde(rename = "jsonrpc")] // Specifies the JSON RPC version being used.
-    json_rpc: String,
-    id: u64,        // Unique identifier for the request.
-    method: String, // The RPC method being called, in this case, to get the node version.
+impl RpcIdentityResponse {
+    // Parses the identity into a pubkey; anything else is an invalid response.
+    pub fn pubkey(&self) -> Result<Pubkey, HandshakeError> {
+        Pubkey::from_str(&self.identity).map_err(|e| {
+            HandshakeError::InvalidResponse(format!("Invalid identity '{}': {}", self.identity, e))
+        })
+    }
+}
+
+// Slots reported by `getSlot` on a node and on the reference it is compared with.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotLag {
+    pub slot: u64,           // Slot of the node.
+    pub reference_slot: u64, // Slot of the reference node.
+}
+
+impl SlotLag {
+    // Slots the node is behind the reference, negative when it is ahead.
+    pub fn delta(&self) -> i64 {
+        self.reference_slot as i64 - self.slot as i64
+    }
+
+    // Slots the node is behind the reference, zero when it is level or ahead.
+    pub fn behind(&self) -> u64 {
+        self.reference_slot.saturating_sub(self.slot)
+    }
+}
+
+// JSON-RPC error code Solana nodes use to report that they are unhealthy.
+pub const NODE_UNHEALTHY_ERROR_CODE: i64 = -32005;
+
+// Health of a node as reported by `getHealth`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpcHealth {
+    Ok, // The node is healthy.
+    Behind {
+        slots_behind: Option<u64>, // Number of slots behind the cluster, when the node reports it.
+        message: String,           // The node's own description of the problem.
+    },
+}
+
+impl RpcHealth {
+    // Interprets a `getHealth` response body.
+    // Healthy nodes answer with the result "ok"; unhealthy ones answer with a node-unhealthy error.
+    pub fn from_response(body: &str) -> Result<RpcHealth, HandshakeError> {
+        let response = RpcResponse::<String>::parse(body)?;
+        let unhealthy = response
+            .error
+            .as_ref()
+            .filter(|error| error.code == NODE_UNHEALTHY_ERROR_CODE);
+        if let Some(error) = unhealthy {
+            let slots_behind = error
+                .data
+                .as_ref()
+                .and_then(|data| data.get("numSlotsBehind"))
+                .and_then(serde_json::Value::as_u64);
+            return Ok(RpcHealth::Behind {
+                slots_behind,
+                message: error.message.clone(),
+            });
+        }
+        match response.into_result()?.as_str() {
+            "ok" => Ok(RpcHealth::Ok),
+            other => Err(HandshakeError::InvalidResponse(format!(
+                "Unexpected getHealth result '{}'",
+                other
+            ))),
+        }
+    }
+
+    // Indicates whether the node reported itself healthy.
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, RpcHealth::Ok)
+    }
+}
+
+// Builder for a JSON-RPC batch, issuing several methods in a single request.
+// Each request is given a distinct random id, as single requests are, so responses can be matched back by id
+// and a response to another batch is not mistaken for one.
+#[derive(Debug, Clone, Default)]
+pub struct RpcBatchRequest {
+    requests: Vec<RpcHandshakeRequest>, // The requests making up the batch.
+}
+
+// Result of one method in a batch: either its result value or the JSON-RPC error it produced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcMethodResult {
+    pub method: String,                              // The method that was called.
+    pub result: Result<serde_json::Value, RpcError>, // The method's result or error.
+}
+
+impl RpcBatchRequest {
+    // Creates an empty batch.
+    pub fn new() -> Self {
+        RpcBatchRequest::default()
+    }
+
+    // Adds a method call to the batch, under an id no other request of the batch has.
+    pub fn method(mut self, method: &str) -> Self {
+        let id = loop {
+            let id = request_id();
+            if self.requests.iter().all(|request| request.id != id) {
+                break id;
+            }
+        };
+        self.requests.push(RpcHandshakeRequest::new(id, method));
+        self
+    }
+
+    // Returns the number of requests in the batch.
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    // Indicates whether the batch holds no requests.
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    // Generates the JSON payload for the batch: an array of JSON-RPC requests.
+    pub fn to_payload(&self) -> serde_json::Value {
+        serde_json::json!(self.requests)
+    }
+
+    // Parses a batch response body, returning one result per request in request order.
+    // Servers may answer batch entries in any order, so responses are matched to requests by id.
+    pub fn parse_results(&self, body: &str) -> Result<Vec<RpcMethodResult>, HandshakeError> {
+        let value: serde_json::Value = serde_json::from_str(body).map_err(|e| {
+            HandshakeError::InvalidResponse(format!("Unable to parse JSON-RPC response: {}", e))
+        })?;
+
+        // A single object instead of an array means the batch as a whole was rejected.
+        let responses: Vec<RpcResponse<serde_json::Value>> = match value {
+            serde_json::Value::Array(_) => serde_json::from_value(value).map_err(|e| {
+                HandshakeError::InvalidResponse(format!("Invalid JSON-RPC batch response: {}", e))
+            })?,
+            _ => {
+                RpcResponse::<serde_json::Value>::parse_result(body)?;
+                return Err(HandshakeError::InvalidResponse(
+                    "Expected a JSON-RPC batch response".to_string(),
+                ));
+            }
+        };
+
+        self.requests
+            .iter()
+            .map(|request| {
+                let response = responses
+                    .iter()
+                    .find(|response| response.id == Some(request.id))
+                    .ok_or_else(|| {
+                        HandshakeError::InvalidResponse(format!(
+                            "No response for {} (id {})",
+                            request.method, request.id
+                        ))
+                    })?;
+                let result = match (&response.result, &response.error) {
+                    (_, Some(error)) => Err(error.clone()),
+                    (Some(result), None) => Ok(result.clone()),
+                    (None, None) => {
+                        return Err(HandshakeError::InvalidResponse(format!(
+                            "Response for {} (id {}) has neither result nor error",
+                            request.method, request.id
+                        )))
+                    }
+                };
+                Ok(RpcMethodResult {
+                    method: request.method.clone(),
+                    result,
+                })
+            })
+            .collect()
+    }
 }
 
 impl RpcNode {
-    // Constructor for RpcNode, taking a socket address and transport type.
-    pub fn new(remote: SocketAddr, transport_type: TransportType) -> Self {
+    // Constructor for RpcNode, taking a target, or a socket address, and transport type.
+    // The target's name is kept for TLS SNI, WebSocket URLs and Host headers, while its resolved
+    // addresses are the ones connected to.
+    pub fn new(remote: impl Into<Target>, transport_type: TransportType) -> Self {
         RpcNode {
-            remote,
+            remote: remote.into(),
             transport_type,
+            pool: None,
+            connect: ConnectOptions::default(),
+            timeout: None,
+            method: None,
+            params: None,
+            transport: Dynamic,
         }
     }
 
-    // Generates the JSON payload for the handshake request, conforming to the RPC's expected format.
-    pub fn get_handshake_payload(&self) -> serde_json::Value {
-        serde_json::json!(RpcHandshakeRequest {
-            json_rpc: "2.0".to_string(),      // Using JSON RPC version 2.0.
-            id: 1,                            // Example request ID.
-            method: "getVersion".to_string(), // Requesting the version of the solana-core.
+    // Starts building a node, e.g. `RpcNode::builder().address(a).transport(TransportType::Tls).timeout(5).build()`.
+    pub fn builder() -> RpcNodeBuilder {
+        RpcNodeBuilder::default()
+    }
+}
+
+impl<T> RpcNode<T> {
+    // Sends every call of the node through the transport, e.g. `RpcNode::new(a, t).with_transport(Tcp::new(..))`.
+    // The transport type is still the one reported in traces and checked by WebSocket calls.
+    pub fn with_transport<U: Transport>(self, transport: U) -> RpcNode<U> {
+        RpcNode {
+            remote: self.remote,
+            transport_type: self.transport_type,
+            pool: self.pool,
+            connect: self.connect,
+            timeout: self.timeout,
+            method: self.method,
+            params: self.params,
+            transport,
+        }
+    }
+
+    // Shares a connection pool with the node, so repeated handshakes reuse open connections.
+    pub fn with_pool(mut self, pool: Arc<ConnectionPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    // Calls the given method as the handshake instead of `getVersion`.
+    pub fn with_method(mut self, method: &str, params: Option<serde_json::Value>) -> Self {
+        self.method = Some(method.to_string());
+        self.params = params;
+        self
+    }
+}
+
+impl<T> Connectable for RpcNode<T> {
+    fn connect_options(&mut self) -> &mut ConnectOptions {
+        &mut self.connect
+    }
+}
+
+impl<T: Dispatch> RpcNode<T> {
+    // Timeout of a call: the one it was given, or else the node's.
+    fn timeout(&self, timeout: Option<u32>) -> Option<u32> {
+        timeout.or(self.timeout)
+    }
+
+    // Whether the node's WebSocket is secure; subscriptions and pings need a WebSocket transport.
+    fn websocket_security(&self) -> Result<bool, HandshakeError> {
+        match self.transport_type {
+            TransportType::Ws => Ok(false),
+            TransportType::Wss => Ok(true),
+            other => Err(HandshakeError::Protocol(format!(
+                "A WebSocket transport is needed, not {}",
+                other
+            ))),
+        }
+    }
+
+    // Generates the JSON payload for the handshake request with the id: the method the node was given,
+    // or else the `getVersion` request the Solana chain defines.
+    pub fn get_handshake_payload(&self, id: u64) -> serde_json::Value {
+        match &self.method {
+            Some(method) => serde_json::json!(
+                RpcHandshakeRequest::new(id, method).with_params(self.params.clone())
+            ),
+            None => Solana::with_id(id).payload(),
+        }
+    }
+
+    // Sends the payload over the node's transport and returns the raw response body.
+    async fn send(
+        &self,
+        payload: serde_json::Value,
+        timeout: Option<u32>,
+    ) -> Result<String, HandshakeError> {
+        let transport = self.transport.transport(self); // Selects the transport, unless the node has one.
+        let exchange = transport.connect_and_send(self.timeout(timeout), payload);
+        traced(&self.remote, self.transport_type, exchange).await
+    }
+}
+
+impl<T: Dispatch> RpcNode<T> {
+    // Performs the handshake and parses the `getVersion` result into a typed response.
+    pub async fn shake_typed(
+        &self,
+        timeout: Option<u32>,
+    ) -> Result<RpcHandshakeResponse, HandshakeError> {
+        let id = request_id();
+        let (body, timings, _) = timed(self.send(self.get_handshake_payload(id), timeout)).await;
+        let mut response: RpcHandshakeResponse =
+            RpcResponse::parse_validated(&body?, id, &RpcHandshakeResponse::shape())?;
+        response.timings = timings;
+        Ok(response)
+    }
+
+    // Performs the handshake and returns the result of its method, whatever its shape, failing on JSON-RPC errors.
+    pub async fn shake_result(
+        &self,
+        timeout: Option<u32>,
+    ) -> Result<serde_json::Value, HandshakeError> {
+        let id = request_id();
+        let body = self.send(self.get_handshake_payload(id), timeout).await?;
+        RpcResponse::parse_validated(&body, id, &Shape::Any)
+    }
+
+    // Performs the handshake as the Solana chain, reporting what the node said about itself.
+    pub async fn node_info(&self, timeout: Option<u32>) -> Result<NodeInfo, HandshakeError> {
+        let solana = Solana::new();
+        let body = self.send(solana.payload(), timeout).await?;
+        solana.parse(&body)
+    }
+
+    // Sends every method of the batch in a single request and returns their results in order.
+    pub async fn shake_batch(
+        &self,
+        batch: &RpcBatchRequest,
+        timeout: Option<u32>,
+    ) -> Result<Vec<RpcMethodResult>, HandshakeError> {
+        let body = self.send(batch.to_payload(), timeout).await?;
+        batch.parse_results(&body)
+    }
+
+    // Sends a single parameterless JSON-RPC method and returns the raw response body, once it is known
+    // to answer this request.
+    pub async fn send_method(
+        &self,
+        method: &str,
+        timeout: Option<u32>,
+    ) -> Result<String, HandshakeError> {
+        let id = request_id();
+        let body = self.send_method_with_id(method, id, timeout).await?;
+        validate::correlate(&body, id)?;
+        Ok(body)
+    }
+
+    // Sends a single parameterless JSON-RPC method under the id and returns the raw response body.
+    async fn send_method_with_id(
+        &self,
+        method: &str,
+        id: u64,
+        timeout: Option<u32>,
+    ) -> Result<String, HandshakeError> {
+        let payload = serde_json::json!(RpcHandshakeRequest::new(id, method));
+        self.send(payload, timeout).await
+    }
+
+    // Calls a single parameterless JSON-RPC method and parses its result.
+    pub async fn call<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        timeout: Option<u32>,
+    ) -> Result<R, HandshakeError> {
+        let id = request_id();
+        let body = self.send_method_with_id(method, id, timeout).await?;
+        RpcResponse::parse_validated(&body, id, &Shape::Any)
+    }
+
+    // Calls `getHealth` and interprets whether the node reports itself healthy.
+    // A node that is behind is not a failed handshake: it answered, so its health is returned.
+    pub async fn check_health(&self, timeout: Option<u32>) -> Result<RpcHealth, HandshakeError> {
+        let body = self.send_method("getHealth", timeout).await?;
+        RpcHealth::from_response(&body)
+    }
+
+    // Calls `getIdentity` and returns the identity pubkey of the node behind the endpoint.
+    pub async fn get_identity(&self, timeout: Option<u32>) -> Result<Pubkey, HandshakeError> {
+        let response: RpcIdentityResponse = self.call("getIdentity", timeout).await?;
+        response.pubkey()
+    }
+
+    // Calls `getSlot` and returns the slot the node has processed.
+    pub async fn get_slot(&self, timeout: Option<u32>) -> Result<u64, HandshakeError> {
+        self.call("getSlot", timeout).await
+    }
+
+    // Calls `getSlot` on the node and on a reference node concurrently, so the slots compared are
+    // taken as close together as possible.
+    pub async fn slot_lag(
+        &self,
+        reference: &RpcNode<impl Dispatch>,
+        timeout: Option<u32>,
+    ) -> Result<SlotLag, HandshakeError> {
+        let (slot, reference_slot) =
+            tokio::try_join!(self.get_slot(timeout), reference.get_slot(timeout))?;
+        Ok(SlotLag {
+            slot,
+            reference_slot,
         })
     }
+
+    // Pings the node over WebSocket and measures each Pong's round trip, checking the keepalive
+    // behaviour of whatever sits in front of the node. With `handshake`, the `getVersion` handshake
+    // runs on the same connection first.
+    pub async fn ping(
+        &self,
+        options: PingOptions,
+        handshake: bool,
+        timeout: Option<u32>,
+    ) -> Result<RpcPingResponse, HandshakeError> {
+        let url = rationalise_url(&self.remote.to_string(), self.websocket_security()?);
+        let timeout = self.timeout(timeout);
+        let exchange = async {
+            let mut ws_stream = ws_connect(
+                &url,
+                &self.connect.route(&self.remote),
+                &self.connect.tls,
+                timeout,
+            )
+            .await?;
+            let handshake = match handshake {
+                true => {
+                    let id = request_id();
+                    let payload = self.get_handshake_payload(id);
+                    let body = ws_exchange(&mut ws_stream, timeout, payload).await?;
+                    validate::correlate(&body, id)?;
+                    Some(RpcResponse::parse_result(&body)?)
+                }
+                false => None,
+            };
+            let round_trips = ws_ping(&mut ws_stream, options, timeout).await?;
+            Ok(RpcPingResponse {
+                handshake,
+                round_trips,
+            })
+        };
+        traced(&self.remote, self.transport_type, exchange).await
+    }
+
+    // Subscribes to slot updates, waits for a notification and unsubscribes again, proving the node's
+    // PubSub pipeline delivers data. Subscriptions are only available over WebSocket transports.
+    pub async fn verify_slot_subscription(
+        &self,
+        timeout: Option<u32>,
+    ) -> Result<SlotSubscription, HandshakeError> {
+        let url = rationalise_url(&self.remote.to_string(), self.websocket_security()?);
+        let timeout = self.timeout(timeout);
+        let exchange = async {
+            let mut ws_stream = ws_connect(
+                &url,
+                &self.connect.route(&self.remote),
+                &self.connect.tls,
+                timeout,
+            )
+            .await?;
+            pubsub::verify_slot_subscription(&mut ws_stream, timeout).await
+        };
+        traced(&self.remote, self.transport_type, exchange).await
+    }
 }
 
 // Implement the Display trait for RpcNode for easy logging and debugging.
-impl fmt::Display for RpcNode {
+impl<T> fmt::Display for RpcNode<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "RpcNode({})", self.remote) // Custom display format showing the remote address.
     }
@@ -63,35 +547,114 @@ impl fmt::Display for RpcNode {
 // Chooses the appropriate transport mechanism based on the transport_type attribute.
 impl ChooseTransport for RpcNode {
     fn get_transport(&self) -> Box<dyn Transport> {
-        match self.transport_type {
-            TransportType::Ws => Box::new(Ws::new(self.remote.to_string(), false)), // WebSocket transport.
-            TransportType::Wss => Box::new(Ws::new(self.remote.to_string(), true)), // Secure WebSocket transport.
-            TransportType::Tls => Box::new(Tcp::new(self.remote.to_string(), true)), // TLS transport over TCP.
-            TransportType::Tcp => Box::new(Tcp::new(self.remote.to_string(), false)), // Plain TCP transport.
+        // Draw connections from the pool when one is configured and the transport supports it.
+        if let Some(pool) = &self.pool {
+            if ConnectionPool::supports(&self.transport_type) {
+                return Box::new(
+                    PooledTransport::new(
+                        pool.clone(),
+                        self.remote.to_string(),
+                        self.transport_type,
+                    )
+                    .with_route(self.connect.route(&self.remote))
+                    .with_tls(self.connect.tls.clone()),
+                );
+            }
         }
+
+        // Otherwise, every handshake opens its own connection.
+        select(
+            &self.remote,
+            self.transport_type,
+            self.connect.route(&self.remote),
+            self.connect.tls.clone(),
+        )
     }
 }
 
 // Async trait for performing the handshake operation, utilizing the selected transport to connect to the remote node.
 #[async_trait]
-impl Handshake for RpcNode {
-    async fn shake(&self, timeout: Option<u32>) -> io::Result<String> {
-        let transport = self.get_transport(); // Dynamically selects the appropriate transport.
-        let payload = self.get_handshake_payload(); // Constructs the handshake payload.
-                                                    // Initiates the handshake, sending the payload and waiting for a response.
-        let raw_response = transport.connect_and_send(timeout, payload).await?;
-        // Processes the raw response to extract the JSON payload.
-        let json_start = raw_response.find("\r\n\r\n").unwrap_or(0) + 4;
-        let json_str = &raw_response[json_start..];
+impl<T: Dispatch> Handshake for RpcNode<T> {
+    async fn shake(&self, timeout: Option<u32>) -> Result<String, HandshakeError> {
+        let id = request_id(); // Identifies the request, so its response can be told apart.
+        let payload = self.get_handshake_payload(id); // Constructs the handshake payload.
 
-        Ok(json_str.to_owned()) // Returns the JSON string extracted from the response.
+        // Initiates the handshake, sending the payload and waiting for a response.
+        // Transports strip any protocol framing, so the response is the JSON body itself.
+        let body = self.send(payload, timeout).await?;
+        validate::correlate(&body, id)?;
+        Ok(body)
+    }
+}
+
+impl RpcNodeBuilder {
+    // Sets the address of the node, as a resolved target or a socket address.
+    pub fn address(mut self, address: impl Into<Target>) -> Self {
+        self.address = Some(address.into());
+        self
+    }
+
+    // Sets the transport used to reach the node.
+    pub fn transport(mut self, transport_type: TransportType) -> Self {
+        self.transport_type = Some(transport_type);
+        self
+    }
+
+    // Sets the timeout in seconds applied to each phase of calls made without one.
+    pub fn timeout(mut self, seconds: u32) -> Self {
+        self.timeout = Some(seconds);
+        self
+    }
+
+    // Shares a connection pool with the node.
+    pub fn pool(mut self, pool: Arc<ConnectionPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    // Sets the proxy and TLS options the node's connections are made with.
+    pub fn connect(mut self, options: ConnectOptions) -> Self {
+        self.connect = options;
+        self
+    }
+
+    // Sets the method called as the handshake and its parameters.
+    pub fn method(mut self, method: &str, params: Option<serde_json::Value>) -> Self {
+        self.method = Some(method.to_string());
+        self.params = params;
+        self
+    }
+
+    // Builds the node; a node without an address has nothing to connect to.
+    pub fn build(self) -> Result<RpcNode, HandshakeError> {
+        let remote = self.address.ok_or_else(|| {
+            HandshakeError::InvalidConfig("No address given for the node".to_string())
+        })?;
+        Ok(RpcNode {
+            remote,
+            transport_type: self.transport_type.unwrap_or(TransportType::Tcp),
+            pool: self.pool,
+            connect: self.connect,
+            timeout: self.timeout,
+            method: self.method,
+            params: self.params,
+            transport: Dynamic,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     // Test suite for RpcNode functionality, ensuring correct creation and payload generation.
-    use {super::*, std::str::FromStr};
+    use {
+        super::*,
+        crate::solana::transport::mock::MockTransport,
+        proptest::prelude::*,
+        serde_json::Value,
+        std::net::SocketAddr,
+        test_case::test_case,
+        tokio::io::{AsyncReadExt, AsyncWriteExt},
+    };
 
     // Verifies that an RpcNode can be correctly instantiated with specified parameters.
     #[test]
@@ -100,7 +663,7 @@ mod tests {
         let addr = SocketAddr::from_str(address).unwrap();
         let node = RpcNode::new(addr, TransportType::Tcp); // Testing with TCP transport type.
 
-        assert_eq!(node.remote, addr); // Checks that the remote address matches.
+        assert_eq!(node.remote, Target::from(addr)); // Checks that the remote address matches.
         assert_eq!(node.transport_type, TransportType::Tcp); // Ensures the transport type is correctly set.
     }
 
@@ -111,8 +674,8 @@ mod tests {
         let addr = SocketAddr::from_str(address).unwrap();
         let node = RpcNode::new(addr, TransportType::Ws); // WebSocket as the transport type for this test.
 
-        let payload = node.get_handshake_payload(); // Generate the handshake payload.
-                                                    // Expected JSON structure of the payload.
+        let payload = node.get_handshake_payload(1); // Generate the handshake payload.
+                                                     // Expected JSON structure of the payload.
         let expected_payload = serde_json::json!({
             "jsonrpc": "2.0",
             "id": 1,
@@ -121,4 +684,434 @@ mod tests {
 
         assert_eq!(payload, expected_payload); // Compare the generated payload to the expected payload.
     }
+
+    // A node given a method sends it as the handshake, with its parameters when there are any.
+    #[test]
+    fn test_method_payload() {
+        let addr = SocketAddr::from_str("127.0.0.1:8080").unwrap();
+        let node = RpcNode::new(addr, TransportType::Tcp).with_method("getGenesisHash", None);
+        assert_eq!(
+            node.get_handshake_payload(1),
+            serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "getGenesisHash" })
+        );
+
+        let params = serde_json::json!([{ "commitment": "finalized" }]);
+        let node = RpcNode::builder()
+            .address(addr)
+            .method("getSlot", Some(params.clone()))
+            .build()
+            .unwrap();
+        assert_eq!(node.get_handshake_payload(1)["params"], params);
+    }
+
+    // A batch payload holds its requests in the order they were added, each under its own random id.
+    #[test]
+    fn test_batch_payload() {
+        let batch = RpcBatchRequest::new()
+            .method("getVersion")
+            .method("getSlot");
+        let [version, slot] = [&batch.requests[0], &batch.requests[1]].map(|request| request.id);
+        let expected_payload = serde_json::json!([
+            { "jsonrpc": "2.0", "id": version, "method": "getVersion" },
+            { "jsonrpc": "2.0", "id": slot, "method": "getSlot" }
+        ]);
+
+        assert_eq!(batch.len(), 2);
+        assert_ne!(version, slot);
+        assert_eq!(batch.to_payload(), expected_payload);
+    }
+
+    // Batch responses are matched to their requests by id, whatever order they arrive in.
+    #[test]
+    fn test_batch_results_are_matched_by_id() {
+        let batch = RpcBatchRequest::new()
+            .method("getVersion")
+            .method("getHealth")
+            .method("getSlot");
+        let ids: Vec<u64> = batch.requests.iter().map(|request| request.id).collect();
+        let body = format!(
+            r#"[
+            {{"jsonrpc":"2.0","result":250000000,"id":{}}},
+            {{"jsonrpc":"2.0","error":{{"code":-32005,"message":"Node is behind"}},"id":{}}},
+            {{"jsonrpc":"2.0","result":{{"solana-core":"1.18.6"}},"id":{}}}
+        ]"#,
+            ids[2], ids[1], ids[0]
+        );
+        let results = batch.parse_results(&body).unwrap();
+
+        assert_eq!(results[0].method, "getVersion");
+        assert_eq!(
+            results[0].result,
+            Ok(serde_json::json!({ "solana-core": "1.18.6" }))
+        );
+        assert_eq!(results[1].result.as_ref().unwrap_err().code, -32005);
+        assert_eq!(results[2].result, Ok(serde_json::json!(250000000)));
+    }
+
+    #[test_case(r#"[{"jsonrpc":"2.0","result":"ok","id":{health}}]"#; "when a response is missing")]
+    #[test_case(r#"{"jsonrpc":"2.0","result":"ok","id":{health}}"#; "when response is not a batch")]
+    #[test_case(r#"[{"jsonrpc":"2.0","result":"ok","id":1},{"jsonrpc":"2.0","result":42,"id":2}]"#; "when responses are to another batch")]
+    #[test_case(r#"[{"jsonrpc":"2.0","result":"ok","id":{health}},{"jsonrpc":"2.0","id":{slot}}]"#; "when a response has neither result nor error")]
+    // Batch responses that do not answer every request, or answer one with neither a result nor an error,
+    // are invalid responses.
+    fn test_batch_invalid_response(body: &str) {
+        let batch = RpcBatchRequest::new().method("getHealth").method("getSlot");
+        let body = body
+            .replace("{health}", &batch.requests[0].id.to_string())
+            .replace("{slot}", &batch.requests[1].id.to_string());
+        let error = batch.parse_results(&body).unwrap_err();
+        assert!(matches!(error, HandshakeError::InvalidResponse(_)));
+    }
+
+    // A healthy node answers getHealth with "ok".
+    #[test]
+    fn test_health_ok() {
+        let body = r#"{"jsonrpc":"2.0","result":"ok","id":1}"#;
+        assert_eq!(RpcHealth::from_response(body).unwrap(), RpcHealth::Ok);
+    }
+
+    #[test_case(r#"{"jsonrpc":"2.0","error":{"code":-32005,"message":"Node is behind by 42 slots","data":{"numSlotsBehind":42}},"id":1}"#, Some(42); "when slots behind are reported")]
+    #[test_case(r#"{"jsonrpc":"2.0","error":{"code":-32005,"message":"Node is unhealthy","data":{"numSlotsBehind":null}},"id":1}"#, None; "when slots behind are unknown")]
+    // An unhealthy node is reported as behind rather than as a failed handshake.
+    fn test_health_behind(body: &str, expected_slots: Option<u64>) {
+        match RpcHealth::from_response(body).unwrap() {
+            RpcHealth::Behind { slots_behind, .. } => assert_eq!(slots_behind, expected_slots),
+            other => panic!("Expected the node to be behind, got {:?}", other),
+        }
+    }
+
+    // The identity returned by getIdentity is parsed into a pubkey.
+    #[test]
+    fn test_identity_pubkey() {
+        let pubkey = Pubkey::new_unique();
+        let body = format!(
+            r#"{{"jsonrpc":"2.0","result":{{"identity":"{}"}},"id":1}}"#,
+            pubkey
+        );
+        let response: RpcIdentityResponse = RpcResponse::parse_result(&body).unwrap();
+        assert_eq!(response.pubkey().unwrap(), pubkey);
+    }
+
+    // An identity that is not a valid pubkey is an invalid response.
+    #[test]
+    fn test_identity_invalid_pubkey() {
+        let response = RpcIdentityResponse {
+            identity: "not-a-pubkey".to_string(),
+        };
+        assert!(matches!(
+            response.pubkey().unwrap_err(),
+            HandshakeError::InvalidResponse(_)
+        ));
+    }
+
+    // Other JSON-RPC errors are still RPC errors.
+    #[test]
+    fn test_health_other_error() {
+        let body =
+            r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found"},"id":1}"#;
+        let error = RpcHealth::from_response(body).unwrap_err();
+        assert!(matches!(error, HandshakeError::Rpc { .. }));
+    }
+
+    #[test_case("not json"; "when body is not json")]
+    #[test_case(r#"{"jsonrpc":"2.0","id":1}"#; "when envelope is empty")]
+    #[test_case(r#"{"jsonrpc":"2.0","result":{"version":1},"id":1}"#; "when result has the wrong shape")]
+    // Bodies that do not carry a usable result are invalid responses.
+    fn test_parse_invalid_response(body: &str) {
+        let error = RpcResponse::<RpcHandshakeResponse>::parse_result(body).unwrap_err();
+        assert!(matches!(error, HandshakeError::InvalidResponse(_)));
+    }
+
+    // Reads the id of the JSON-RPC request carried by a raw HTTP request, which the response must echo.
+    fn id_of(request: &[u8]) -> serde_json::Value {
+        let request = String::from_utf8_lossy(request);
+        let (_, payload) = request.split_once("\r\n\r\n").unwrap_or_default();
+        serde_json::from_str::<serde_json::Value>(payload).unwrap_or_default()["id"].clone()
+    }
+
+    // The target's name is sent in the Host header, while its resolved address is connected to.
+    #[tokio::test]
+    async fn test_host_header_uses_target_name() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 1024];
+            let n = socket.read(&mut buf).await.unwrap();
+            let body = format!(
+                r#"{{"jsonrpc":"2.0","result":{{"solana-core":"1.18.6"}},"id":{}}}"#,
+                id_of(&buf[..n])
+            );
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+
+        let target = Target {
+            host: "node.internal".to_string(),
+            port: address.port(),
+            resolved: vec![address],
+        };
+        let node = RpcNode::new(target, TransportType::Tcp);
+        node.send_method("getVersion", Some(5)).await.unwrap();
+        let request = server.await.unwrap();
+        assert!(request.contains(&format!("Host: node.internal:{}\r\n", address.port())));
+    }
+
+    // A response to an earlier request, as a caching proxy may replay, is a protocol error.
+    #[tokio::test]
+    async fn test_response_to_another_request() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 1024];
+            let n = socket.read(&mut buf).await.unwrap();
+            let id = id_of(&buf[..n]).as_u64().unwrap() + 1;
+            let body = format!(
+                r#"{{"jsonrpc":"2.0","result":{{"solana-core":"1.18.6"}},"id":{}}}"#,
+                id
+            );
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let node = RpcNode::new(address, TransportType::Tcp);
+        let error = node.shake_typed(Some(5)).await.unwrap_err();
+        assert!(matches!(error, HandshakeError::Protocol(_)), "{}", error);
+    }
+
+    // The lag is how far the node is behind its reference, never negative, unlike the delta.
+    #[test_case(1000, 1000, 0, 0; "when node is level")]
+    #[test_case(990, 1000, 10, 10; "when node is behind")]
+    #[test_case(1003, 1000, -3, 0; "when node is ahead")]
+    fn test_slot_lag(slot: u64, reference_slot: u64, delta: i64, behind: u64) {
+        let lag = SlotLag {
+            slot,
+            reference_slot,
+        };
+        assert_eq!(lag.delta(), delta);
+        assert_eq!(lag.behind(), behind);
+    }
+
+    // The slots of the node and of its reference are both fetched with getSlot.
+    #[tokio::test]
+    async fn test_slot_lag_against_reference() {
+        async fn serve_slot(slot: u64) -> SocketAddr {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            tokio::spawn(async move {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 1024];
+                let n = socket.read(&mut buf).await.unwrap();
+                assert!(String::from_utf8_lossy(&buf[..n]).contains(r#""method":"getSlot""#));
+                let body = format!(
+                    r#"{{"jsonrpc":"2.0","result":{},"id":{}}}"#,
+                    slot,
+                    id_of(&buf[..n])
+                );
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            });
+            address
+        }
+
+        let node = RpcNode::new(serve_slot(250_000_100).await, TransportType::Tcp);
+        let reference = RpcNode::new(serve_slot(250_000_142).await, TransportType::Tcp);
+        let lag = node.slot_lag(&reference, Some(5)).await.unwrap();
+        assert_eq!(
+            lag,
+            SlotLag {
+                slot: 250_000_100,
+                reference_slot: 250_000_142,
+            }
+        );
+        assert_eq!(lag.behind(), 42);
+    }
+
+    // The builder sets every option, defaulting to TCP, and requires an address.
+    #[test]
+    fn test_builder() {
+        let addr = SocketAddr::from_str("127.0.0.1:8899").unwrap();
+        let node = RpcNode::builder()
+            .address(addr)
+            .transport(TransportType::Tls)
+            .timeout(5)
+            .build()
+            .unwrap();
+        assert_eq!(node.remote, Target::from(addr));
+        assert_eq!(node.transport_type, TransportType::Tls);
+        assert_eq!(node.timeout, Some(5));
+
+        let node = RpcNode::builder().address(addr).build().unwrap();
+        assert_eq!(node.transport_type, TransportType::Tcp);
+        let Err(error) = RpcNode::builder().build() else {
+            panic!("a node without an address was built");
+        };
+        assert!(matches!(error, HandshakeError::InvalidConfig(_)));
+        assert_eq!(error.class(), "invalid_config");
+    }
+
+    // Calls made without a timeout use the node's.
+    #[tokio::test]
+    async fn test_node_timeout_is_default() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            // Accept the connection but never answer.
+            let (_socket, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        });
+
+        let node = RpcNode::builder()
+            .address(address)
+            .timeout(1)
+            .build()
+            .unwrap();
+        let error = node.shake(None).await.unwrap_err();
+        assert!(matches!(error, HandshakeError::Timeout { seconds: 1, .. }));
+    }
+
+    // A node given a transport sends every call through it, whatever its transport type.
+    #[tokio::test]
+    async fn test_node_with_transport() {
+        let version = |payload: &Value| {
+            Ok(format!(
+                r#"{{"jsonrpc":"2.0","result":{{"solana-core":"1.18.6"}},"id":{}}}"#,
+                payload["id"]
+            ))
+        };
+        let transport = MockTransport::new()
+            .with_reply(version)
+            .with_reply(|payload| {
+                Ok(format!(
+                    r#"{{"jsonrpc":"2.0","result":42,"id":{}}}"#,
+                    payload["id"]
+                ))
+            });
+        let addr = SocketAddr::from_str("127.0.0.1:8899").unwrap();
+        let node = RpcNode::new(addr, TransportType::Quic).with_transport(&transport);
+
+        let response = node.shake_typed(Some(5)).await.unwrap();
+        assert_eq!(response.solana_core, "1.18.6");
+        assert_eq!(node.get_slot(Some(5)).await.unwrap(), 42);
+        let methods: Vec<_> = transport
+            .requests()
+            .iter()
+            .map(|r| r["method"].clone())
+            .collect();
+        assert_eq!(methods, ["getVersion", "getSlot"]);
+    }
+
+    // Strategy for arbitrary JSON values, nested a few levels deep.
+    fn json() -> impl Strategy<Value = Value> {
+        let leaf = prop_oneof![
+            Just(Value::Null),
+            any::<bool>().prop_map(Value::from),
+            any::<i64>().prop_map(Value::from),
+            any::<u64>().prop_map(Value::from),
+            // Fractions serde_json reads back exactly, as it may round others by an ulp.
+            any::<i32>().prop_map(|n| Value::from(f64::from(n) / 4.0)),
+            ".{0,8}".prop_map(Value::from),
+        ];
+        leaf.prop_recursive(3, 24, 4, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..4).prop_map(Value::from),
+                prop::collection::btree_map("[a-z-]{1,12}", inner, 0..4)
+                    .prop_map(|members| Value::Object(members.into_iter().collect())),
+            ]
+        })
+    }
+
+    // Strategy for JSON-RPC envelopes whose members are each present or not, holding plausible or arbitrary values.
+    fn envelope() -> impl Strategy<Value = Value> {
+        let jsonrpc = prop_oneof![Just(Value::from("2.0")), json()];
+        let id = prop_oneof![(0..3u64).prop_map(Value::from), json()];
+        let error = prop_oneof![
+            (any::<i64>(), ".{0,8}").prop_map(|(code, message)| {
+                serde_json::json!({ "code": code, "message": message })
+            }),
+            json(),
+        ];
+        let members = (
+            prop::option::of(jsonrpc),
+            prop::option::of(id),
+            prop::option::of(json()),
+            prop::option::of(error),
+        );
+        members.prop_map(|(jsonrpc, id, result, error)| {
+            let members = [
+                ("jsonrpc", jsonrpc),
+                ("id", id),
+                ("result", result),
+                ("error", error),
+            ];
+            Value::Object(
+                members
+                    .into_iter()
+                    .filter_map(|(name, value)| Some((name.to_string(), value?)))
+                    .collect(),
+            )
+        })
+    }
+
+    // Reads a response body the way every handshake does, checking that nothing panics.
+    fn extract(body: &str) {
+        let _ = RpcResponse::<RpcHandshakeResponse>::parse_validated(
+            body,
+            1,
+            &RpcHandshakeResponse::shape(),
+        );
+        let _ = RpcResponse::<RpcIdentityResponse>::parse_result(body).map(|r| r.pubkey());
+        let _ = RpcHealth::from_response(body);
+        let batch = RpcBatchRequest::new()
+            .method("getVersion")
+            .method("getSlot");
+        let _ = batch.parse_results(body);
+    }
+
+    // Fuzzes the extraction of results from what an untrusted node may answer: arbitrary text and
+    // arbitrary envelopes never panic, and every envelope strict validation accepts can be parsed.
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(2000))]
+        #[test]
+        fn test_extract_arbitrary_bodies(body in ".{0,256}") {
+            extract(&body);
+        }
+
+        #[test]
+        fn test_extract_arbitrary_envelopes(envelope in envelope()) {
+            let body = envelope.to_string();
+            extract(&body);
+            let _ = validate::mismatches(&envelope, 1, &RpcHandshakeResponse::shape());
+            if validate::validate(&body, 1, &Shape::Any).is_ok() {
+                let result = RpcResponse::<Value>::parse_validated(&body, 1, &Shape::Any);
+                prop_assert!(
+                    matches!(result, Ok(_) | Err(HandshakeError::Rpc { .. })),
+                    "{} was validated but not parsed: {:?}",
+                    body,
+                    result
+                );
+            }
+        }
+
+        #[test]
+        fn test_extract_any_result(result in json(), id in 1..=i32::MAX as u64) {
+            let body = serde_json::json!({ "jsonrpc": "2.0", "result": result, "id": id });
+            let parsed = RpcResponse::<Value>::parse_validated(&body.to_string(), id, &Shape::Any);
+            prop_assert_eq!(parsed.unwrap(), result);
+        }
+    }
 }
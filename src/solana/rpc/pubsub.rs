@@ -0,0 +1,236 @@
+// Verification of a node's PubSub pipeline over WebSocket.
+// A `getVersion` round trip only proves request/response works, so a slot subscription is opened,
+// one notification awaited and the subscription cancelled again, proving the node delivers data.
+use {
+    super::node::RpcResponse, // JSON-RPC envelope of the subscribe and unsubscribe responses.
+    crate::{
+        error::HandshakeError,
+        solana::transport::ws::{ws_read, ws_write, WsStream},
+    },
+    serde::{de::DeserializeOwned, Deserialize, Serialize},
+    tracing::info,
+};
+
+// Request ids of the subscribe and unsubscribe calls, so their responses can be told apart from notifications.
+const SUBSCRIBE_ID: u64 = 1;
+const UNSUBSCRIBE_ID: u64 = 2;
+
+// Slot update pushed by the node in a `slotNotification`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotInfo {
+    pub slot: u64,   // The slot the node has just processed.
+    pub parent: u64, // Parent of that slot.
+    pub root: u64,   // Current root slot of the node.
+}
+
+// Outcome of a successful slot subscription check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotSubscription {
+    pub subscription: u64,      // Subscription id assigned by the node.
+    pub notification: SlotInfo, // The first notification received for the subscription.
+}
+
+// Notification pushed by the node for an active subscription.
+#[derive(Deserialize, Debug)]
+struct RpcNotification<T> {
+    method: String,                   // Name of the notification, e.g. "slotNotification".
+    params: RpcNotificationParams<T>, // The subscription the notification belongs to, and its payload.
+}
+
+// Parameters of a subscription notification.
+#[derive(Deserialize, Debug)]
+struct RpcNotificationParams<T> {
+    result: T,         // Payload of the notification.
+    subscription: u64, // Subscription id the notification belongs to.
+}
+
+// Subscribes to slot updates, waits for the first notification and unsubscribes again.
+// Each write and read is bounded by the timeout, so a node that never notifies fails with a read timeout.
+pub(crate) async fn verify_slot_subscription(
+    ws_stream: &mut WsStream,
+    timeout: Option<u32>, // Optional timeout in seconds, applied to each phase.
+) -> Result<SlotSubscription, HandshakeError> {
+    let subscribe = request(SUBSCRIBE_ID, "slotSubscribe", serde_json::json!([]));
+    ws_write(ws_stream, timeout, &subscribe).await?;
+    let subscription: u64 = read_response(ws_stream, timeout, SUBSCRIBE_ID).await?;
+    info!(
+        "Subscribed to slot updates as subscription {}",
+        subscription
+    );
+
+    let notification = read_slot_notification(ws_stream, timeout, subscription).await?;
+    info!("Received notification for slot {}", notification.slot);
+
+    let unsubscribe = request(
+        UNSUBSCRIBE_ID,
+        "slotUnsubscribe",
+        serde_json::json!([subscription]),
+    );
+    ws_write(ws_stream, timeout, &unsubscribe).await?;
+    match read_response::<bool>(ws_stream, timeout, UNSUBSCRIBE_ID).await? {
+        true => Ok(SlotSubscription {
+            subscription,
+            notification,
+        }),
+        false => Err(HandshakeError::Protocol(format!(
+            "Node did not cancel subscription {}",
+            subscription
+        ))),
+    }
+}
+
+// Formats a JSON-RPC 2.0 request with parameters.
+fn request(id: u64, method: &str, params: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+// Reads messages until the response to the request with the given id, skipping notifications sent before it.
+async fn read_response<T: DeserializeOwned>(
+    ws_stream: &mut WsStream,
+    timeout: Option<u32>,
+    id: u64,
+) -> Result<T, HandshakeError> {
+    loop {
+        let message = next_message(ws_stream, timeout).await?;
+        let response = RpcResponse::<T>::parse(&message)?;
+        if response.id == Some(id) {
+            return response.into_result();
+        }
+    }
+}
+
+// Reads messages until a slot notification for the subscription arrives.
+async fn read_slot_notification(
+    ws_stream: &mut WsStream,
+    timeout: Option<u32>,
+    subscription: u64,
+) -> Result<SlotInfo, HandshakeError> {
+    loop {
+        let message = next_message(ws_stream, timeout).await?;
+        let notification: RpcNotification<SlotInfo> =
+            serde_json::from_str(&message).map_err(|e| {
+                HandshakeError::InvalidResponse(format!("Unable to parse slot notification: {}", e))
+            })?;
+        if notification.method == "slotNotification"
+            && notification.params.subscription == subscription
+        {
+            return Ok(notification.params.result);
+        }
+    }
+}
+
+// Reads the next message; the node closing the connection mid-check is a WebSocket error.
+async fn next_message(
+    ws_stream: &mut WsStream,
+    timeout: Option<u32>,
+) -> Result<String, HandshakeError> {
+    match ws_read(ws_stream, timeout).await? {
+        message if message.is_empty() => Err(HandshakeError::WebSocket(
+            "Connection closed during the subscription check".to_string(),
+        )),
+        message => Ok(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::solana::transport::{
+            tcp::{Route, TlsOptions},
+            ws::ws_connect,
+        },
+        futures_util::{SinkExt, StreamExt},
+        tokio::net::TcpListener,
+        tokio_tungstenite::tungstenite::Message,
+    };
+
+    // Starts a PubSub server answering slotSubscribe and slotUnsubscribe, sending the given notifications first.
+    async fn pubsub_server(notifications: Vec<&'static str>, unsubscribed: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            while let Some(Ok(Message::Text(text))) = ws.next().await {
+                let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+                let result = match request["method"].as_str().unwrap() {
+                    "slotSubscribe" => serde_json::json!(7),
+                    _ => serde_json::json!(unsubscribed),
+                };
+                let response =
+                    serde_json::json!({ "jsonrpc": "2.0", "result": result, "id": request["id"] });
+                ws.send(Message::Text(response.to_string())).await.unwrap();
+                if request["method"] == "slotSubscribe" {
+                    for notification in &notifications {
+                        ws.send(Message::Text(notification.to_string()))
+                            .await
+                            .unwrap();
+                    }
+                }
+            }
+        });
+        format!("ws://{}", address)
+    }
+
+    // A notification for another subscription is skipped until one for ours arrives.
+    #[tokio::test]
+    async fn test_slot_subscription() {
+        let url = pubsub_server(
+            vec![
+                r#"{"jsonrpc":"2.0","method":"slotNotification","params":{"result":{"slot":1,"parent":0,"root":0},"subscription":3}}"#,
+                r#"{"jsonrpc":"2.0","method":"slotNotification","params":{"result":{"slot":42,"parent":41,"root":10},"subscription":7}}"#,
+            ],
+            true,
+        )
+        .await;
+        let mut ws_stream = ws_connect(&url, &Route::default(), &TlsOptions::default(), Some(5))
+            .await
+            .unwrap();
+
+        let subscription = verify_slot_subscription(&mut ws_stream, Some(5))
+            .await
+            .unwrap();
+        assert_eq!(subscription.subscription, 7);
+        assert_eq!(
+            subscription.notification,
+            SlotInfo {
+                slot: 42,
+                parent: 41,
+                root: 10
+            }
+        );
+    }
+
+    // A node that never sends a notification fails the check with a read timeout.
+    #[tokio::test]
+    async fn test_missing_notification_times_out() {
+        let url = pubsub_server(vec![], true).await;
+        let mut ws_stream = ws_connect(&url, &Route::default(), &TlsOptions::default(), Some(1))
+            .await
+            .unwrap();
+
+        let error = verify_slot_subscription(&mut ws_stream, Some(1))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, HandshakeError::Timeout { phase, .. } if phase == "read"));
+    }
+
+    // A subscription the node refuses to cancel is a protocol error.
+    #[tokio::test]
+    async fn test_failed_unsubscribe_is_protocol_error() {
+        let url = pubsub_server(
+            vec![r#"{"jsonrpc":"2.0","method":"slotNotification","params":{"result":{"slot":42,"parent":41,"root":10},"subscription":7}}"#],
+            false,
+        )
+        .await;
+        let mut ws_stream = ws_connect(&url, &Route::default(), &TlsOptions::default(), Some(5))
+            .await
+            .unwrap();
+
+        let error = verify_slot_subscription(&mut ws_stream, Some(5))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, HandshakeError::Protocol(_)));
+    }
+}
@@ -0,0 +1,359 @@
+// Strict validation of JSON-RPC 2.0 responses: the envelope must carry `"jsonrpc": "2.0"`, the id of
+// the request and exactly one of a result or an error object, and the result must have the shape the
+// method defines. Every deviation is reported with the path of the field at fault, so a proxy mangling
+// responses, or a node answering another request, is told apart from a node running odd software.
+use {crate::error::HandshakeError, serde_json::Value, std::fmt};
+
+// Expected shape of a JSON value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Any,                  // Any value, left unchecked.
+    Boolean,              // `true` or `false`.
+    Integer,              // A number without a fractional part.
+    String,               // A string.
+    Array(Box<Shape>),    // An array whose every item has the shape.
+    Object(Vec<Member>),  // An object with these members and no others.
+    Nullable(Box<Shape>), // The shape, or null.
+}
+
+// A member of an object shape.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub name: &'static str, // Name of the member.
+    pub shape: Shape,       // Shape of its value.
+    pub required: bool,     // Whether the member must be present.
+}
+
+impl Member {
+    // A member the object must have.
+    pub fn required(name: &'static str, shape: Shape) -> Self {
+        Member {
+            name,
+            shape,
+            required: true,
+        }
+    }
+
+    // A member the object may leave out.
+    pub fn optional(name: &'static str, shape: Shape) -> Self {
+        Member {
+            name,
+            shape,
+            required: false,
+        }
+    }
+}
+
+impl Shape {
+    // Describes the values of the shape, as a mismatch reports what was expected.
+    fn describe(&self) -> String {
+        match self {
+            Shape::Any => "any value".to_string(),
+            Shape::Boolean => "a boolean".to_string(),
+            Shape::Integer => "an integer".to_string(),
+            Shape::String => "a string".to_string(),
+            Shape::Array(_) => "an array".to_string(),
+            Shape::Object(_) => "an object".to_string(),
+            Shape::Nullable(shape) => format!("{} or null", shape.describe()),
+        }
+    }
+}
+
+// A way a response deviates from the JSON-RPC envelope or from the shape of the expected result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    // A required field is absent.
+    Missing {
+        path: String,
+    },
+    // A field holds a value of another type.
+    WrongType {
+        path: String,
+        expected: String,
+        found: &'static str,
+    },
+    // A field holds a value of the right type, but not the expected one, e.g. another id.
+    WrongValue {
+        path: String,
+        expected: String,
+        found: String,
+    },
+    // A field is present that the shape does not allow.
+    Unexpected {
+        path: String,
+    },
+}
+
+// Implement the Display trait for Mismatch, naming the field at fault by its path.
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mismatch::Missing { path } => write!(f, "{} is missing", path),
+            Mismatch::WrongType {
+                path,
+                expected,
+                found,
+            } => write!(f, "{} is {}, expected {}", path, found, expected),
+            Mismatch::WrongValue {
+                path,
+                expected,
+                found,
+            } => write!(f, "{} is {}, expected {}", path, found, expected),
+            Mismatch::Unexpected { path } => write!(f, "{} is unexpected", path),
+        }
+    }
+}
+
+// The type of a JSON value, as a mismatch reports what was found.
+fn type_of(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+// Checks a value against a shape, adding every mismatch found at or below the path.
+pub fn check(value: &Value, shape: &Shape, path: &str, mismatches: &mut Vec<Mismatch>) {
+    let wrong_type = || Mismatch::WrongType {
+        path: path.to_string(),
+        expected: shape.describe(),
+        found: type_of(value),
+    };
+    match (shape, value) {
+        (Shape::Any, _) | (Shape::Boolean, Value::Bool(_)) | (Shape::String, Value::String(_)) => {}
+        (Shape::Integer, Value::Number(number)) if number.is_i64() || number.is_u64() => {}
+        (Shape::Nullable(_), Value::Null) => {}
+        (Shape::Nullable(inner), value) => {
+            // A value of the wrong type is reported as neither the inner shape nor null.
+            let mut inner_mismatches = Vec::new();
+            check(value, inner, path, &mut inner_mismatches);
+            for mismatch in inner_mismatches {
+                match mismatch {
+                    Mismatch::WrongType { path: at, .. } if at == path => {
+                        mismatches.push(wrong_type())
+                    }
+                    mismatch => mismatches.push(mismatch),
+                }
+            }
+        }
+        (Shape::Array(item), Value::Array(items)) => {
+            for (index, value) in items.iter().enumerate() {
+                check(value, item, &format!("{}[{}]", path, index), mismatches);
+            }
+        }
+        (Shape::Object(members), Value::Object(object)) => {
+            for member in members {
+                let path = format!("{}.{}", path, member.name);
+                match object.get(member.name) {
+                    Some(value) => check(value, &member.shape, &path, mismatches),
+                    None if member.required => mismatches.push(Mismatch::Missing { path }),
+                    None => {}
+                }
+            }
+            let unexpected = object
+                .keys()
+                .filter(|name| !members.iter().any(|member| member.name == name.as_str()));
+            for name in unexpected {
+                let path = format!("{}.{}", path, name);
+                mismatches.push(Mismatch::Unexpected { path });
+            }
+        }
+        _ => mismatches.push(wrong_type()),
+    }
+}
+
+// Shape of the error object of a failed call.
+fn error_shape() -> Shape {
+    Shape::Object(vec![
+        Member::required("code", Shape::Integer),
+        Member::required("message", Shape::String),
+        Member::optional("data", Shape::Any),
+    ])
+}
+
+// Lists every way a response deviates from a JSON-RPC 2.0 response to the request with the id, whose
+// result has the shape. A failed call may carry a null id, as servers unable to read the request's do.
+pub fn mismatches(response: &Value, id: u64, result: &Shape) -> Vec<Mismatch> {
+    let Value::Object(envelope) = response else {
+        return vec![Mismatch::WrongType {
+            path: "$".to_string(),
+            expected: "an object".to_string(),
+            found: type_of(response),
+        }];
+    };
+    let mut mismatches = Vec::new();
+
+    match envelope.get("jsonrpc") {
+        Some(Value::String(version)) if version == "2.0" => {}
+        Some(version @ Value::String(_)) => mismatches.push(Mismatch::WrongValue {
+            path: "$.jsonrpc".to_string(),
+            expected: "\"2.0\"".to_string(),
+            found: version.to_string(),
+        }),
+        Some(version) => check(version, &Shape::String, "$.jsonrpc", &mut mismatches),
+        None => mismatches.push(Mismatch::Missing {
+            path: "$.jsonrpc".to_string(),
+        }),
+    }
+
+    let failed = envelope.contains_key("error");
+    match envelope.get("id") {
+        Some(Value::Null) if failed => {}
+        Some(found) if found.as_u64() == Some(id) => {}
+        Some(found @ (Value::Number(_) | Value::String(_))) => {
+            mismatches.push(Mismatch::WrongValue {
+                path: "$.id".to_string(),
+                expected: id.to_string(),
+                found: found.to_string(),
+            })
+        }
+        Some(found) => check(found, &Shape::Integer, "$.id", &mut mismatches),
+        None => mismatches.push(Mismatch::Missing {
+            path: "$.id".to_string(),
+        }),
+    }
+
+    match (envelope.get("result"), envelope.get("error")) {
+        (Some(value), None) => check(value, result, "$.result", &mut mismatches),
+        (None, Some(error)) => check(error, &error_shape(), "$.error", &mut mismatches),
+        // A response is either a success or a failure; the error is the odd one out.
+        (Some(_), Some(_)) => mismatches.push(Mismatch::Unexpected {
+            path: "$.error".to_string(),
+        }),
+        (None, None) => mismatches.push(Mismatch::Missing {
+            path: "$.result".to_string(),
+        }),
+    }
+
+    for name in envelope.keys() {
+        if !["jsonrpc", "id", "result", "error"].contains(&name.as_str()) {
+            mismatches.push(Mismatch::Unexpected {
+                path: format!("$.{}", name),
+            });
+        }
+    }
+    mismatches
+}
+
+// Fails when the response carries the id of another request, as a caching proxy serving a response it
+// kept for someone else would. This is a protocol error rather than an invalid response.
+fn check_id(response: &Value, id: u64) -> Result<(), HandshakeError> {
+    match response.get("id") {
+        Some(found @ (Value::Number(_) | Value::String(_))) if found.as_u64() != Some(id) => {
+            Err(HandshakeError::Protocol(format!(
+                "JSON-RPC response id {} does not match request id {}",
+                found, id
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Checks that a response body answers the request with the id, leaving any other validation to the
+// parsing of the body; a body that is not JSON passes, for that parsing to report.
+pub fn correlate(body: &str, id: u64) -> Result<(), HandshakeError> {
+    match serde_json::from_str::<Value>(body) {
+        Ok(response) => check_id(&response, id),
+        Err(_) => Ok(()),
+    }
+}
+
+// Validates a JSON-RPC 2.0 response body, failing with every mismatch found. A response to another
+// request than the one with the id fails as a protocol error first.
+pub fn validate(body: &str, id: u64, result: &Shape) -> Result<(), HandshakeError> {
+    let response: Value = serde_json::from_str(body).map_err(|e| {
+        HandshakeError::InvalidResponse(format!("Unable to parse JSON-RPC response: {}", e))
+    })?;
+    check_id(&response, id)?;
+    match mismatches(&response, id, result).as_slice() {
+        [] => Ok(()),
+        mismatches => {
+            let mismatches: Vec<String> = mismatches.iter().map(ToString::to_string).collect();
+            Err(HandshakeError::InvalidResponse(format!(
+                "Invalid JSON-RPC response: {}",
+                mismatches.join("; ")
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, test_case::test_case};
+
+    // Shape of a `getVersion` result.
+    fn version() -> Shape {
+        Shape::Object(vec![
+            Member::required("solana-core", Shape::String),
+            Member::optional("feature-set", Shape::Nullable(Box::new(Shape::Integer))),
+        ])
+    }
+
+    #[test_case(r#"{"jsonrpc":"2.0","result":{"feature-set":3580551090,"solana-core":"1.18.6"},"id":1}"#; "when a result")]
+    #[test_case(r#"{"jsonrpc":"2.0","result":{"feature-set":null,"solana-core":"1.18.6"},"id":1}"#; "when the feature set is null")]
+    #[test_case(r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found"},"id":1}"#; "when an error")]
+    #[test_case(r#"{"jsonrpc":"2.0","error":{"code":-32700,"message":"Parse error","data":"x"},"id":null}"#; "when an error without id")]
+    // Tests valid responses pass.
+    fn test_validate(body: &str) {
+        assert!(validate(body, 1, &version()).is_ok());
+    }
+
+    #[test_case(r#"[]"#, "$ is an array, expected an object"; "when not an object")]
+    #[test_case(r#"{"jsonrpc":"1.0","result":{"solana-core":"1.18.6"},"id":1}"#, r#"$.jsonrpc is "1.0", expected "2.0""#; "when another version")]
+    #[test_case(r#"{"result":{"solana-core":"1.18.6"},"id":1}"#, "$.jsonrpc is missing"; "when the version is missing")]
+    #[test_case(r#"{"jsonrpc":"2.0","result":{"solana-core":"1.18.6"},"id":true}"#, "$.id is a boolean, expected an integer"; "when the id is not a number")]
+    #[test_case(r#"{"jsonrpc":"2.0","id":1}"#, "$.result is missing"; "when the result is missing")]
+    #[test_case(r#"{"jsonrpc":"2.0","result":{"solana-core":"1.18.6"},"error":{"code":1,"message":"x"},"id":1}"#, "$.error is unexpected"; "when both a result and an error")]
+    #[test_case(r#"{"jsonrpc":"2.0","result":{"feature-set":1},"id":1}"#, "$.result.solana-core is missing"; "when a result field is missing")]
+    #[test_case(r#"{"jsonrpc":"2.0","result":{"solana-core":1},"id":1}"#, "$.result.solana-core is a number, expected a string"; "when a result field has the wrong type")]
+    #[test_case(r#"{"jsonrpc":"2.0","result":{"solana-core":"1.18.6","feature-set":"1"},"id":1}"#, "$.result.feature-set is a string, expected an integer or null"; "when a nullable field has the wrong type")]
+    #[test_case(r#"{"jsonrpc":"2.0","result":{"solana-core":"1.18.6","commit":"abc"},"id":1}"#, "$.result.commit is unexpected"; "when a result field is unexpected")]
+    #[test_case(r#"{"jsonrpc":"2.0","result":{"solana-core":"1.18.6"},"id":1,"extra":0}"#, "$.extra is unexpected"; "when an envelope field is unexpected")]
+    #[test_case(r#"{"jsonrpc":"2.0","error":{"code":"x","message":"y"},"id":1}"#, "$.error.code is a string, expected an integer"; "when the error code is not an integer")]
+    // Tests every mismatch is reported with the path of the field at fault.
+    fn test_validate_failures(body: &str, mismatch: &str) {
+        let error = validate(body, 1, &version()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("Invalid response: Invalid JSON-RPC response: {}", mismatch)
+        );
+    }
+
+    #[test_case(r#"{"jsonrpc":"2.0","result":{"solana-core":"1.18.6"},"id":2}"#, "2"; "when another id")]
+    #[test_case(r#"{"jsonrpc":"2.0","result":{"solana-core":"1.18.6"},"id":"1a"}"#, r#""1a""#; "when a string id")]
+    // Tests a response to another request is a protocol error, whatever else is wrong with it.
+    fn test_validate_other_request(body: &str, found: &str) {
+        let error = validate(body, 1, &version()).unwrap_err();
+        assert!(matches!(error, HandshakeError::Protocol(_)));
+        assert!(error
+            .to_string()
+            .ends_with(&format!("id {} does not match request id 1", found)));
+        assert_eq!(
+            correlate(body, 1).unwrap_err().to_string(),
+            error.to_string()
+        );
+    }
+
+    // Items of arrays are checked one by one, and every mismatch is reported.
+    #[test]
+    fn test_mismatches() {
+        let response = serde_json::json!({ "jsonrpc": "2.0", "result": [1, "2", 3.5], "id": 7 });
+        let shape = Shape::Array(Box::new(Shape::Integer));
+        let found: Vec<String> = mismatches(&response, 1, &shape)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            found,
+            [
+                "$.id is 7, expected 1",
+                "$.result[1] is a string, expected an integer",
+                "$.result[2] is a number, expected an integer",
+            ]
+        );
+    }
+}
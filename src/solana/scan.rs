@@ -0,0 +1,679 @@
+// Concurrent handshake engine for scanning many RPC endpoints at once.
+// Handshakes run as tasks on a join set, and a semaphore bounds how many are in flight,
+// so large scans do not open thousands of sockets at the same time.
+use {
+    crate::{
+        error::HandshakeError,
+        solana::{
+            cancel::Cancel,
+            pacer::Pacer,
+            precheck::Precheck,
+            rpc::node::{RpcHandshakeResponse, RpcNode},
+            target::{AddressFamily, Target},
+            transport::{
+                retry::RetryPolicy,
+                tcp::{ConnectOptions, Connectable},
+                timed, ConnectionInfo, PhaseTimings,
+            },
+            TransportType,
+        },
+    },
+    std::{
+        collections::HashMap,
+        fmt,
+        io::{self, ErrorKind},
+        sync::Arc,
+        time::{Duration, Instant},
+    },
+    tokio::{sync::Semaphore, task::JoinSet},
+};
+
+// Default number of handshakes in flight at once.
+pub const DEFAULT_CONCURRENCY: usize = 32;
+
+// Default timeout, in seconds, for each phase of a scanned handshake.
+// Shorter than the transport default, so unreachable nodes do not stall a scan.
+pub const DEFAULT_SCAN_TIMEOUT_SECONDS: u32 = 10;
+
+// An endpoint to handshake during a scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanTarget {
+    pub address: Target,               // The RPC endpoint, by name or socket address.
+    pub transport_type: TransportType, // Transport used for the handshake.
+    pub label: Option<String>, // Optional name for the target, e.g. the node's identity pubkey.
+}
+
+impl ScanTarget {
+    // Constructs a target for the address and transport type.
+    pub fn new(address: impl Into<Target>, transport_type: TransportType) -> Self {
+        ScanTarget {
+            address: address.into(),
+            transport_type,
+            label: None,
+        }
+    }
+
+    // Attaches a label to the target.
+    pub fn with_label(mut self, label: String) -> Self {
+        self.label = Some(label);
+        self
+    }
+}
+
+// Implement the Display trait for ScanTarget, showing the label alongside the address when there is one.
+impl fmt::Display for ScanTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.label {
+            Some(label) => write!(f, "{} ({})", self.address, label),
+            None => write!(f, "{}", self.address),
+        }
+    }
+}
+
+// Outcome of the handshake with one target.
+#[derive(Debug)]
+pub struct ScanResult {
+    pub target: ScanTarget,         // The target that was handshaken.
+    pub duration: Duration,         // Time taken by the handshake, successful or not.
+    pub timings: PhaseTimings,      // Time spent in each phase of the handshake, successful or not.
+    pub connection: ConnectionInfo, // What the handshake learnt about its connection, e.g. the TLS version.
+    pub result: Result<RpcHandshakeResponse, HandshakeError>, // Version reported by the node, or why the handshake failed.
+}
+
+// Number of nodes reporting the same version and feature set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionCount {
+    pub version: String,          // The solana-core version.
+    pub feature_set: Option<u64>, // The feature set, if reported.
+    pub nodes: usize,             // Number of nodes reporting this combination.
+}
+
+// How the feature sets of a scan compare with the one most nodes report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeatureSetDivergence {
+    pub majority: Option<u64>, // The most common feature set, if any node reported one.
+    pub agreeing: usize,       // Number of nodes reporting the majority feature set.
+    pub diverging: Vec<usize>, // Indices of the results reporting another feature set.
+}
+
+impl FeatureSetDivergence {
+    // Indicates whether the result at the index reports another feature set than the majority.
+    pub fn diverges(&self, index: usize) -> bool {
+        self.diverging.contains(&index)
+    }
+}
+
+// Called with the result of every handshake of a scan as soon as it finishes, e.g. to report progress.
+pub type Progress = Arc<dyn Fn(&ScanResult) + Send + Sync>;
+
+// Handshakes a set of targets concurrently.
+pub struct Scanner {
+    pub concurrency: usize,            // Maximum number of handshakes in flight.
+    pub timeout: Option<u32>,          // Optional timeout in seconds for each handshake phase.
+    pub connect: ConnectOptions,       // Proxy and TLS options every target is reached with.
+    pub retry: RetryPolicy,            // How failed handshakes are attempted again.
+    pub pacer: Option<Arc<Pacer>>,     // Optional pacer every handshake waits for before starting.
+    pub precheck: Option<Precheck>,    // Optional check of each host before its handshake.
+    pub lookup: Option<AddressFamily>, // Optional family each host is resolved to first.
+    pub cancel: Cancel,                // Signal stopping the scan early, keeping results so far.
+    pub progress: Option<Progress>,    // Optional callback told of every handshake that finished.
+}
+
+impl Scanner {
+    // Constructs a scanner with the default concurrency and timeout.
+    pub fn new() -> Self {
+        Scanner {
+            concurrency: DEFAULT_CONCURRENCY,
+            timeout: Some(DEFAULT_SCAN_TIMEOUT_SECONDS),
+            connect: ConnectOptions::default(),
+            retry: RetryPolicy::default(),
+            pacer: None,
+            precheck: None,
+            lookup: None,
+            cancel: Cancel::new(),
+            progress: None,
+        }
+    }
+
+    // Sets the maximum number of handshakes in flight; at least one always runs.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    // Sets the timeout for each handshake phase.
+    pub fn with_timeout(mut self, timeout: Option<u32>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // Attempts failed handshakes again as the policy says; the result of each target is its last attempt's.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    // Spaces the handshakes out with a pacer, which may be shared with other scanners.
+    pub fn with_pacer(mut self, pacer: Option<Arc<Pacer>>) -> Self {
+        self.pacer = pacer;
+        self
+    }
+
+    // Checks the host of every target is reachable before its handshake, failing the target as
+    // unreachable when it is not.
+    pub fn with_precheck(mut self, precheck: Option<Precheck>) -> Self {
+        self.precheck = precheck;
+        self
+    }
+
+    // Resolves every target's host afresh before its handshake, keeping the addresses of the family, so
+    // the scans of a monitor follow the hosts' DNS records as they change. A host that does not resolve
+    // fails its target as a DNS error.
+    pub fn with_lookup(mut self, family: Option<AddressFamily>) -> Self {
+        self.lookup = family;
+        self
+    }
+
+    // Stops the scan when the signal is fired: handshakes in flight are dropped and no others start.
+    pub fn with_cancel(mut self, cancel: Cancel) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    // Tells the callback of every handshake as soon as it finishes, in the order they finish.
+    pub fn with_progress(mut self, progress: Option<Progress>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    // Handshakes every target and returns the results in the order of the targets.
+    // A cancelled scan only returns the results of the handshakes that finished before it was.
+    pub async fn scan(&self, targets: Vec<ScanTarget>) -> Vec<ScanResult> {
+        let permits = Arc::new(Semaphore::new(self.concurrency));
+        let mut tasks = JoinSet::new();
+
+        for (index, target) in targets.into_iter().enumerate() {
+            let permits = permits.clone();
+            let timeout = self.timeout;
+            let (connect, retry) = (self.connect.clone(), self.retry);
+            let (pacer, precheck, lookup, cancel) = (
+                self.pacer.clone(),
+                self.precheck,
+                self.lookup,
+                self.cancel.clone(),
+            );
+            let handshake = async move {
+                // The semaphore is never closed, so acquiring a permit cannot fail.
+                let _permit = permits.acquire_owned().await.expect("semaphore closed");
+                if let Some(pacer) = pacer {
+                    pacer.acquire().await;
+                }
+                let started = Instant::now();
+                let (target, unresolved) = match lookup {
+                    Some(family) => match target.address.lookup(family).await {
+                        Ok(address) => (ScanTarget { address, ..target }, None),
+                        Err(error) => (target, Some(error)),
+                    },
+                    None => (target, None),
+                };
+                let node = RpcNode::new(target.address.clone(), target.transport_type)
+                    .with_connect(connect);
+                let unreachable = match (unresolved, precheck, target.address.resolved.first()) {
+                    (Some(error), _, _) => Some(error),
+                    (None, Some(precheck), Some(address)) => {
+                        precheck.check(address.ip(), None).await.err()
+                    }
+                    _ => None,
+                };
+                let (result, timings, connection) = match unreachable {
+                    Some(error) => (Err(error), Default::default(), Default::default()),
+                    None => timed(retry.run(&node.remote, || node.shake_typed(timeout))).await,
+                };
+                let scan_result = ScanResult {
+                    target,
+                    duration: started.elapsed(),
+                    timings,
+                    connection,
+                    result,
+                };
+                (index, scan_result)
+            };
+            tasks.spawn(async move { cancel.run(handshake).await });
+        }
+
+        let mut results = Vec::with_capacity(tasks.len());
+        while let Some(joined) = tasks.join_next().await {
+            if let Some((index, result)) = joined.expect("handshake task panicked") {
+                if let Some(progress) = &self.progress {
+                    progress(&result);
+                }
+                results.push((index, result));
+            }
+        }
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
+
+impl Connectable for Scanner {
+    fn connect_options(&mut self) -> &mut ConnectOptions {
+        &mut self.connect
+    }
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Scanner::new()
+    }
+}
+
+// Parses a list of targets, one `host:port[,transport]` per line, without resolving their hosts.
+// Lines without a transport use the default one; blank lines and `#` comments are skipped.
+pub fn parse_targets(list: &str, default: TransportType) -> io::Result<Vec<ScanTarget>> {
+    let mut targets = Vec::new();
+    for (number, line) in list.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let invalid = |reason: String| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid target on line {}: {}", number + 1, reason),
+            )
+        };
+        let (address, transport_type) = match line.split_once(',') {
+            Some((address, transport)) => {
+                (address.trim(), transport.trim().parse().map_err(invalid)?)
+            }
+            None => (line, default),
+        };
+        if transport_type == TransportType::Quic {
+            return Err(invalid("QUIC is not an RPC transport".to_string()));
+        }
+        let address = Target::parse(address).map_err(|e| invalid(e.to_string()))?;
+        targets.push(ScanTarget::new(address, transport_type));
+    }
+    Ok(targets)
+}
+
+// Counts the nodes per version and feature set, most common first.
+// Failed handshakes are left out, as they report no version.
+pub fn summarize(results: &[ScanResult]) -> Vec<VersionCount> {
+    let mut counts: HashMap<(String, Option<u64>), usize> = HashMap::new();
+    for response in results.iter().filter_map(|r| r.result.as_ref().ok()) {
+        *counts
+            .entry((response.solana_core.clone(), response.feature_set))
+            .or_default() += 1;
+    }
+
+    let mut summary: Vec<VersionCount> = counts
+        .into_iter()
+        .map(|((version, feature_set), nodes)| VersionCount {
+            version,
+            feature_set,
+            nodes,
+        })
+        .collect();
+    summary.sort_by(|a, b| {
+        b.nodes
+            .cmp(&a.nodes)
+            .then_with(|| a.version.cmp(&b.version))
+    });
+    summary
+}
+
+// Compares every reported feature set with the modal one, so nodes that missed, or jumped ahead of,
+// a feature activation stand out. Ties go to the lowest feature set, keeping the majority stable.
+// Failed handshakes and nodes reporting no feature set are left out.
+pub fn feature_set_divergence(results: &[ScanResult]) -> FeatureSetDivergence {
+    let feature_sets: Vec<(usize, u64)> = results
+        .iter()
+        .enumerate()
+        .filter_map(|(index, r)| Some((index, r.result.as_ref().ok()?.feature_set?)))
+        .collect();
+    let mut counts: HashMap<u64, usize> = HashMap::new();
+    for (_, feature_set) in &feature_sets {
+        *counts.entry(*feature_set).or_default() += 1;
+    }
+
+    let majority = counts
+        .iter()
+        .max_by(|(a, a_nodes), (b, b_nodes)| a_nodes.cmp(b_nodes).then_with(|| b.cmp(a)))
+        .map(|(feature_set, _)| *feature_set);
+    FeatureSetDivergence {
+        majority,
+        agreeing: majority.map(|f| counts[&f]).unwrap_or_default(),
+        diverging: feature_sets
+            .iter()
+            .filter(|(_, feature_set)| Some(*feature_set) != majority)
+            .map(|(index, _)| *index)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{
+            net::SocketAddr,
+            sync::atomic::{AtomicUsize, Ordering},
+        },
+        test_case::test_case,
+        tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        },
+    };
+
+    // Reads the id of the JSON-RPC request carried by a raw HTTP request, which the response must echo.
+    fn id_of(request: &[u8]) -> serde_json::Value {
+        let request = String::from_utf8_lossy(request);
+        let (_, payload) = request.split_once("\r\n\r\n").unwrap_or_default();
+        serde_json::from_str::<serde_json::Value>(payload).unwrap_or_default()["id"].clone()
+    }
+
+    // Starts an RPC server answering getVersion with the given version, tracking the peak number of open connections.
+    async fn version_server(version: &'static str, peak: Arc<AtomicUsize>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let open = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let (open, peak) = (open.clone(), peak.clone());
+                tokio::spawn(async move {
+                    let now_open = open.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now_open, Ordering::SeqCst);
+                    let mut buf = vec![0; 4096];
+                    let n = socket.read(&mut buf).await.unwrap_or_default();
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    let body = format!(
+                        r#"{{"jsonrpc":"2.0","result":{{"solana-core":"{}","feature-set":1}},"id":{}}}"#,
+                        version,
+                        id_of(&buf[..n])
+                    );
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                    open.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        address
+    }
+
+    // Lines name a target and, optionally, its transport; comments and blank lines are skipped.
+    #[test]
+    fn test_parse_targets() {
+        let list = "# Curated nodes\n127.0.0.1:8899\n\n127.0.0.2:8900, wss # PubSub\n";
+        let targets = parse_targets(list, TransportType::Tcp).unwrap();
+        assert_eq!(
+            targets,
+            vec![
+                ScanTarget::new(
+                    "127.0.0.1:8899".parse::<SocketAddr>().unwrap(),
+                    TransportType::Tcp
+                ),
+                ScanTarget::new(
+                    "127.0.0.2:8900".parse::<SocketAddr>().unwrap(),
+                    TransportType::Wss
+                ),
+            ]
+        );
+    }
+
+    // Invalid lines fail the whole list, naming the line.
+    #[test_case("127.0.0.1:8899,carrier-pigeon"; "when transport is unknown")]
+    #[test_case("127.0.0.1:8899,quic"; "when transport is not an RPC one")]
+    #[test_case("127.0.0.1"; "when port is missing")]
+    fn test_parse_targets_failures(line: &str) {
+        let list = format!("127.0.0.1:8899\n{}\n", line);
+        let error = parse_targets(&list, TransportType::Tcp).unwrap_err();
+        assert!(error.to_string().starts_with("Invalid target on line 2"));
+    }
+
+    // Every target is handshaken, results keep the target order, and the concurrency limit holds.
+    #[tokio::test]
+    async fn test_scan_respects_concurrency() {
+        let peak = Arc::new(AtomicUsize::new(0));
+        let address = version_server("1.18.6", peak.clone()).await;
+        let targets = vec![ScanTarget::new(address, TransportType::Tcp); 6];
+
+        let results = Scanner::new()
+            .with_concurrency(2)
+            .with_timeout(Some(5))
+            .scan(targets)
+            .await;
+
+        assert_eq!(results.len(), 6);
+        assert!(results.iter().all(|r| r.result.is_ok()));
+        assert!(results
+            .iter()
+            .all(|r| r.timings.connect.is_some() && r.timings.read.is_some()));
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+    }
+
+    // Failed handshakes are reported per target without stopping the scan.
+    #[tokio::test]
+    async fn test_scan_reports_failures() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = listener.local_addr().unwrap();
+        drop(listener); // Nothing listens here any more, so the connection is refused.
+        let address = version_server("1.18.6", Arc::new(AtomicUsize::new(0))).await;
+
+        let results = Scanner::new()
+            .with_timeout(Some(5))
+            .scan(vec![
+                ScanTarget::new(closed, TransportType::Tcp),
+                ScanTarget::new(address, TransportType::Tcp),
+            ])
+            .await;
+
+        assert!(results[0].result.is_err());
+        assert_eq!(results[0].target.address, Target::from(closed));
+        assert!(results[1].result.is_ok());
+    }
+
+    // Hosts are resolved afresh by every scan looking them up, and one that does not resolve fails its
+    // target as a DNS error.
+    #[tokio::test]
+    async fn test_scan_with_lookup() {
+        let address = version_server("1.18.6", Arc::new(AtomicUsize::new(0))).await;
+        let named = Target::parse(&format!("localhost:{}", address.port())).unwrap();
+        assert!(named.resolved.is_empty());
+        let targets = vec![
+            ScanTarget::new(named, TransportType::Tcp),
+            ScanTarget::new(
+                Target::parse("node.invalid:8899").unwrap(),
+                TransportType::Tcp,
+            ),
+        ];
+
+        let results = Scanner::new()
+            .with_timeout(Some(5))
+            .with_lookup(Some(AddressFamily::Ipv4))
+            .scan(targets)
+            .await;
+
+        assert!(results[0].result.is_ok());
+        assert_eq!(results[0].target.address.resolved, vec![address]);
+        assert_eq!(results[1].result.as_ref().unwrap_err().class(), "dns");
+    }
+
+    // Targets whose host passes the pre-check are handshaken as usual.
+    #[tokio::test]
+    async fn test_scan_with_precheck() {
+        let address = version_server("1.18.6", Arc::new(AtomicUsize::new(0))).await;
+
+        let results = Scanner::new()
+            .with_timeout(Some(5))
+            .with_precheck(Some(Precheck::Icmp))
+            .scan(vec![ScanTarget::new(address, TransportType::Tcp)])
+            .await;
+
+        assert!(results[0].result.is_ok());
+    }
+
+    // The progress callback is told of every handshake, failed or not.
+    #[tokio::test]
+    async fn test_scan_progress() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = listener.local_addr().unwrap();
+        drop(listener); // Nothing listens here any more, so the connection is refused.
+        let address = version_server("1.18.6", Arc::new(AtomicUsize::new(0))).await;
+
+        let (finished, failed) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let (f, e) = (finished.clone(), failed.clone());
+        let progress: Progress = Arc::new(move |result: &ScanResult| {
+            f.fetch_add(1, Ordering::SeqCst);
+            if result.result.is_err() {
+                e.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        Scanner::new()
+            .with_timeout(Some(5))
+            .with_progress(Some(progress))
+            .scan(vec![
+                ScanTarget::new(closed, TransportType::Tcp),
+                ScanTarget::new(address, TransportType::Tcp),
+                ScanTarget::new(address, TransportType::Tcp),
+            ])
+            .await;
+
+        assert_eq!(finished.load(Ordering::SeqCst), 3);
+        assert_eq!(failed.load(Ordering::SeqCst), 1);
+    }
+
+    // A cancelled scan returns the results of the handshakes that finished, and starts no others.
+    #[tokio::test]
+    async fn test_scan_cancelled() {
+        let address = version_server("1.18.6", Arc::new(AtomicUsize::new(0))).await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let silent = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            // Accept the connections but never answer.
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let cancel = Cancel::new();
+        let scanner = Scanner::new()
+            .with_concurrency(1)
+            .with_timeout(Some(5))
+            .with_cancel(cancel.clone());
+        let scan = scanner.scan(vec![
+            ScanTarget::new(address, TransportType::Tcp),
+            ScanTarget::new(silent, TransportType::Tcp),
+            ScanTarget::new(address, TransportType::Tcp),
+        ]);
+        let fire = async {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            cancel.cancel();
+        };
+        let (results, ()) = tokio::join!(scan, fire);
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].target.address, Target::from(address));
+        assert!(results[0].result.is_ok());
+    }
+
+    // Versions are counted, most common first, ignoring failures.
+    #[test]
+    fn test_summarize() {
+        let target = ScanTarget::new(
+            "127.0.0.1:8899".parse::<SocketAddr>().unwrap(),
+            TransportType::Tcp,
+        );
+        let result = |version: &str| ScanResult {
+            target: target.clone(),
+            duration: Duration::ZERO,
+            timings: PhaseTimings::default(),
+            connection: ConnectionInfo::default(),
+            result: Ok(RpcHandshakeResponse {
+                solana_core: version.to_string(),
+                feature_set: Some(1),
+                timings: PhaseTimings::default(),
+            }),
+        };
+        let failed = ScanResult {
+            target: target.clone(),
+            duration: Duration::ZERO,
+            timings: PhaseTimings::default(),
+            connection: ConnectionInfo::default(),
+            result: Err(HandshakeError::Dns("unresolved".to_string())),
+        };
+
+        let summary = summarize(&[result("1.17.0"), result("1.18.6"), result("1.18.6"), failed]);
+        assert_eq!(
+            summary,
+            vec![
+                VersionCount {
+                    version: "1.18.6".to_string(),
+                    feature_set: Some(1),
+                    nodes: 2
+                },
+                VersionCount {
+                    version: "1.17.0".to_string(),
+                    feature_set: Some(1),
+                    nodes: 1
+                },
+            ]
+        );
+    }
+
+    // Nodes reporting another feature set than the majority diverge; ties go to the lowest one.
+    #[test]
+    fn test_feature_set_divergence() {
+        let target = ScanTarget::new(
+            "127.0.0.1:8899".parse::<SocketAddr>().unwrap(),
+            TransportType::Tcp,
+        );
+        let result = |feature_set: Option<u64>| ScanResult {
+            target: target.clone(),
+            duration: Duration::ZERO,
+            timings: PhaseTimings::default(),
+            connection: ConnectionInfo::default(),
+            result: Ok(RpcHandshakeResponse {
+                solana_core: "1.18.6".to_string(),
+                feature_set,
+                timings: PhaseTimings::default(),
+            }),
+        };
+        let failed = ScanResult {
+            target: target.clone(),
+            duration: Duration::ZERO,
+            timings: PhaseTimings::default(),
+            connection: ConnectionInfo::default(),
+            result: Err(HandshakeError::Dns("unresolved".to_string())),
+        };
+
+        let results = [
+            result(Some(7)),
+            result(Some(3)),
+            failed,
+            result(Some(3)),
+            result(None),
+        ];
+        let divergence = feature_set_divergence(&results);
+        assert_eq!(
+            divergence,
+            FeatureSetDivergence {
+                majority: Some(3),
+                agreeing: 2,
+                diverging: vec![0],
+            }
+        );
+        assert!(divergence.diverges(0) && !divergence.diverges(1));
+
+        let divergence = feature_set_divergence(&[result(Some(7)), result(Some(3))]);
+        assert_eq!(divergence.majority, Some(3));
+        assert_eq!(feature_set_divergence(&[]).majority, None);
+    }
+}
@@ -0,0 +1,337 @@
+// Targets named on the command line: the host and port as given, plus every address the host resolved to.
+// Keeping the whole resolution set lets callers pick an address family instead of the first record.
+use {
+    crate::error::HandshakeError,
+    std::{
+        fmt,
+        io::{self, ErrorKind},
+        net::{IpAddr, SocketAddr, ToSocketAddrs},
+    },
+};
+
+// Address family the resolved addresses are restricted to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFamily {
+    // Addresses of either family.
+    #[default]
+    Any,
+    // IPv4 addresses only.
+    Ipv4,
+    // IPv6 addresses only.
+    Ipv6,
+}
+
+impl AddressFamily {
+    // Indicates whether the address belongs to the family.
+    pub fn matches(&self, address: &SocketAddr) -> bool {
+        match self {
+            AddressFamily::Any => true,
+            AddressFamily::Ipv4 => address.is_ipv4(),
+            AddressFamily::Ipv6 => address.is_ipv6(),
+        }
+    }
+}
+
+impl fmt::Display for AddressFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AddressFamily::Any => "any",
+            AddressFamily::Ipv4 => "IPv4",
+            AddressFamily::Ipv6 => "IPv6",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// A host and port together with the addresses the host resolved to, in resolver order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub host: String,              // Host name or IP address, without brackets.
+    pub port: u16,                 // Port to connect to.
+    pub resolved: Vec<SocketAddr>, // Every address the host resolved to.
+}
+
+impl Target {
+    // Resolves a `host:port` target, e.g. "api.devnet.solana.com:443".
+    // This fails if the DNS lookup fails or returns no addresses.
+    pub fn resolve(target: &str) -> io::Result<Self> {
+        let resolved: Vec<SocketAddr> = target.to_socket_addrs()?.collect();
+        let port = match resolved.first() {
+            Some(address) => address.port(),
+            None => {
+                return Err(io::Error::new(
+                    ErrorKind::AddrNotAvailable,
+                    format!("Could not find destination {target}"),
+                ))
+            }
+        };
+        let host = target
+            .rsplit_once(':')
+            .map_or(target, |(host, _)| host)
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        Ok(Target {
+            host: host.to_string(),
+            port,
+            resolved,
+        })
+    }
+
+    // Resolves a host without a port, e.g. "node.internal" or "::1", so several of its ports can be reached.
+    pub fn resolve_host(host: &str) -> io::Result<Self> {
+        match host.contains(':') && !host.starts_with('[') {
+            true => Target::resolve(&format!("[{host}]:0")),
+            false => Target::resolve(&format!("{host}:0")),
+        }
+    }
+
+    // Parses a `host:port` target without resolving it, e.g. "api.devnet.solana.com:443" or "[::1]:8899".
+    // Only an IP address comes with its address; a name is resolved by `lookup` when it is handshaken.
+    // This fails if the host or port is missing, or the port is invalid.
+    pub fn parse(target: &str) -> io::Result<Self> {
+        let invalid = |reason: &str| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid target {target}: {reason}"),
+            )
+        };
+        let (host, port) = target
+            .rsplit_once(':')
+            .ok_or_else(|| invalid("expected 'host:port'"))?;
+        let port: u16 = port.parse().map_err(|_| invalid("invalid port"))?;
+        let host = host
+            .strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+            .unwrap_or(host);
+        if host.is_empty() {
+            return Err(invalid("missing host"));
+        }
+        let resolved = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![SocketAddr::new(ip, port)],
+            Err(_) => Vec::new(),
+        };
+        Ok(Target {
+            host: host.to_string(),
+            port,
+            resolved,
+        })
+    }
+
+    // Parses a host without a port, e.g. "node.internal" or "::1", without resolving it.
+    pub fn parse_host(host: &str) -> io::Result<Self> {
+        match host.contains(':') && !host.starts_with('[') {
+            true => Target::parse(&format!("[{host}]:0")),
+            false => Target::parse(&format!("{host}:0")),
+        }
+    }
+
+    // Resolves the host afresh, keeping its addresses of the given family. This fails as a DNS error if the
+    // host does not resolve or has no address of the family. Targets are looked up every time they are
+    // handshaken rather than once, so repeated handshakes follow the host's records as they change.
+    pub async fn lookup(&self, family: AddressFamily) -> Result<Target, HandshakeError> {
+        let resolved: Vec<SocketAddr> = tokio::net::lookup_host((self.host.as_str(), self.port))
+            .await
+            .map_err(|e| HandshakeError::Dns(format!("Could not resolve {}: {}", self, e)))?
+            .collect();
+        if resolved.is_empty() {
+            return Err(HandshakeError::Dns(format!(
+                "Could not find destination {}",
+                self
+            )));
+        }
+        Target {
+            resolved,
+            ..self.clone()
+        }
+        .with_family(family)
+    }
+
+    // The target on another port of the same host.
+    pub fn with_port(&self, port: u16) -> Target {
+        Target {
+            host: self.host.clone(),
+            port,
+            resolved: self
+                .resolved
+                .iter()
+                .map(|address| SocketAddr::new(address.ip(), port))
+                .collect(),
+        }
+    }
+
+    // Resolved addresses of the given family, in resolver order.
+    pub fn addresses(&self, family: AddressFamily) -> Vec<SocketAddr> {
+        self.resolved
+            .iter()
+            .filter(|address| family.matches(address))
+            .copied()
+            .collect()
+    }
+
+    // The target with only its resolved addresses of the given family, failing when there are none.
+    pub fn with_family(&self, family: AddressFamily) -> Result<Target, HandshakeError> {
+        self.address(family)?;
+        Ok(Target {
+            resolved: self.addresses(family),
+            ..self.clone()
+        })
+    }
+
+    // First resolved address of the given family.
+    pub fn address(&self, family: AddressFamily) -> Result<SocketAddr, HandshakeError> {
+        self.addresses(family)
+            .first()
+            .copied()
+            .ok_or_else(|| HandshakeError::Dns(format!("{} has no {} address", self, family)))
+    }
+}
+
+impl From<SocketAddr> for Target {
+    // A target for a known address, e.g. one discovered from the cluster.
+    fn from(address: SocketAddr) -> Self {
+        Target {
+            host: address.ip().to_string(),
+            port: address.port(),
+            resolved: vec![address],
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.host.contains(':') {
+            true => write!(f, "[{}]:{}", self.host, self.port),
+            false => write!(f, "{}:{}", self.host, self.port),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::test_util::{domain, port},
+        proptest::prelude::*,
+        test_case::test_case,
+    };
+
+    // A dual-stack target, as a host with both A and AAAA records would resolve.
+    fn dual_stack() -> Target {
+        Target {
+            host: "node.internal".to_string(),
+            port: 8899,
+            resolved: vec![
+                "10.0.0.1:8899".parse().unwrap(),
+                "[2001:db8::1]:8899".parse().unwrap(),
+            ],
+        }
+    }
+
+    // The host and port are kept alongside the resolved addresses.
+    #[test_case("127.0.0.1:8899", "127.0.0.1"; "when host is ipv4")]
+    #[test_case("[::1]:8899", "::1"; "when host is ipv6")]
+    fn test_resolve(target: &str, host: &str) {
+        let target = Target::resolve(target).unwrap();
+        assert_eq!(target.host, host);
+        assert_eq!(target.port, 8899);
+        assert_eq!(target.resolved.len(), 1);
+    }
+
+    // Hosts are resolved without a port, then moved to the ports reached.
+    #[test_case("127.0.0.1", "127.0.0.1:8001"; "when host is ipv4")]
+    #[test_case("::1", "[::1]:8001"; "when host is ipv6")]
+    #[test_case("[::1]", "[::1]:8001"; "when host is bracketed ipv6")]
+    fn test_resolve_host(host: &str, expected: &str) {
+        let target = Target::resolve_host(host).unwrap().with_port(8001);
+        assert_eq!(target.to_string(), expected);
+        assert_eq!(target.resolved, vec![expected.parse().unwrap()]);
+    }
+
+    // Targets are parsed without being resolved; only an IP address comes with its address.
+    #[test_case("127.0.0.1:8899", "127.0.0.1", 1; "when host is ipv4")]
+    #[test_case("[::1]:8899", "::1", 1; "when host is ipv6")]
+    #[test_case("node.invalid:8899", "node.invalid", 0; "when host is a name")]
+    fn test_parse(target: &str, host: &str, resolved: usize) {
+        let target = Target::parse(target).unwrap();
+        assert_eq!(target.host, host);
+        assert_eq!(target.port, 8899);
+        assert_eq!(target.resolved.len(), resolved);
+    }
+
+    // Targets without a host or a valid port are rejected.
+    #[test_case("node.internal"; "when port is missing")]
+    #[test_case("node.internal:65536"; "when port is too high")]
+    #[test_case(":8899"; "when host is missing")]
+    fn test_parse_failures(target: &str) {
+        let error = Target::parse(target).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+
+    // Names are resolved when looked up, and a name that does not resolve is a DNS error.
+    #[tokio::test]
+    async fn test_lookup() {
+        let target = Target::parse("localhost:8899").unwrap();
+        let resolved = target.lookup(AddressFamily::Any).await.unwrap();
+        assert_eq!(resolved.host, "localhost");
+        assert!(resolved
+            .resolved
+            .iter()
+            .all(|address| address.port() == 8899));
+        assert!(!resolved.resolved.is_empty());
+
+        let target = Target::parse("node.invalid:8899").unwrap();
+        let error = target.lookup(AddressFamily::Any).await.unwrap_err();
+        assert!(matches!(error, HandshakeError::Dns(_)));
+    }
+
+    // Addresses are filtered to the requested family.
+    #[test_case(AddressFamily::Any, "10.0.0.1:8899"; "when family is any")]
+    #[test_case(AddressFamily::Ipv4, "10.0.0.1:8899"; "when family is ipv4")]
+    #[test_case(AddressFamily::Ipv6, "[2001:db8::1]:8899"; "when family is ipv6")]
+    fn test_address_family(family: AddressFamily, expected: &str) {
+        assert_eq!(
+            dual_stack().address(family).unwrap(),
+            expected.parse().unwrap()
+        );
+    }
+
+    // Restricting a target to a family keeps its name and only that family's addresses.
+    #[test]
+    fn test_with_family() {
+        let target = dual_stack().with_family(AddressFamily::Ipv6).unwrap();
+        assert_eq!(target.host, "node.internal");
+        assert_eq!(target.resolved, vec!["[2001:db8::1]:8899".parse().unwrap()]);
+    }
+
+    // A target without an address of the requested family is a DNS error.
+    #[test]
+    fn test_missing_address_family() {
+        let target = Target::from("127.0.0.1:8899".parse::<SocketAddr>().unwrap());
+        let error = target.address(AddressFamily::Ipv6).unwrap_err();
+        assert!(matches!(error, HandshakeError::Dns(_)));
+        assert!(error
+            .to_string()
+            .contains("127.0.0.1:8899 has no IPv6 address"));
+    }
+
+    // IPv6 hosts are bracketed when displayed.
+    #[test]
+    fn test_display() {
+        assert_eq!(dual_stack().to_string(), "node.internal:8899");
+        let target = Target::from("[::1]:8899".parse::<SocketAddr>().unwrap());
+        assert_eq!(target.to_string(), "[::1]:8899");
+    }
+
+    // Property-based test of parsing constructed domains with valid ports: the host and port are kept,
+    // and nothing is resolved, so no DNS lookup is made.
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(1000))]
+        #[test]
+        fn test_parse_domains(domain in domain(), port in port()) {
+            let target = Target::parse(&format!("{}:{}", domain, port)).unwrap();
+            prop_assert_eq!(target.host, domain);
+            prop_assert_eq!(target.port, port);
+            prop_assert!(target.resolved.is_empty());
+        }
+    }
+}
@@ -0,0 +1,2 @@
+// TPU node implementation
+pub mod node;
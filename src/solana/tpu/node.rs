@@ -0,0 +1,77 @@
+// Module for verifying a validator's TPU (transaction processing unit) endpoint over QUIC.
+use crate::error::HandshakeError; // Crate-wide error type reported by failed handshakes.
+use crate::solana::{
+    node::Handshake,
+    transport::{
+        quic::{Quic, SOLANA_TPU_ALPN},
+        traced, ChooseTransport, Transport,
+    },
+    TransportType,
+}; // Import necessary traits and structures for handshake and transport.
+use async_trait::async_trait; // Enables async trait methods, crucial for async network operations.
+use std::{fmt, net::SocketAddr}; // Standard library imports for networking and display formatting.
+
+// Struct defining a TPU node, identified by the socket address of its QUIC TPU port.
+pub struct TpuNode {
+    pub remote: SocketAddr, // Socket address of the remote TPU endpoint.
+    pub alpn: Vec<String>,  // Protocols offered through ALPN, the TPU's by default.
+}
+
+impl TpuNode {
+    // Constructor for TpuNode, taking the socket address of the TPU port.
+    pub fn new(remote: SocketAddr) -> Self {
+        TpuNode {
+            remote,
+            alpn: vec![SOLANA_TPU_ALPN.to_string()],
+        }
+    }
+
+    // Sets the protocols offered through ALPN, e.g. to check how the TPU answers other protocols.
+    pub fn with_alpn(mut self, alpn: Vec<String>) -> Self {
+        self.alpn = alpn;
+        self
+    }
+}
+
+// Implement the Display trait for TpuNode for easy logging and debugging.
+impl fmt::Display for TpuNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TpuNode({})", self.remote) // Custom display format showing the remote address.
+    }
+}
+
+// The TPU is only reachable over QUIC, so the transport is always the QUIC transport.
+impl ChooseTransport for TpuNode {
+    fn get_transport(&self) -> Box<dyn Transport> {
+        Box::new(Quic::new(self.remote.to_string()).with_alpn(self.alpn.clone()))
+    }
+}
+
+// Async trait for performing the handshake operation; a completed QUIC handshake proves the TPU is alive.
+#[async_trait]
+impl Handshake for TpuNode {
+    async fn shake(&self, timeout: Option<u32>) -> Result<String, HandshakeError> {
+        let transport = self.get_transport(); // Selects the QUIC transport.
+
+        // No payload is sent to the TPU, only the connection is established.
+        let exchange = transport.connect_and_send(timeout, serde_json::Value::Null);
+        traced(self.remote, TransportType::Quic, exchange).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Test suite for TpuNode functionality.
+    use {super::*, std::str::FromStr};
+
+    // Verifies that a TpuNode can be correctly instantiated and displayed.
+    #[test]
+    fn test_tpu_node_creation() {
+        let addr = SocketAddr::from_str("127.0.0.1:8003").unwrap();
+        let node = TpuNode::new(addr);
+
+        assert_eq!(node.remote, addr); // Checks that the remote address matches.
+        assert_eq!(node.alpn, vec!["solana-tpu"]); // Checks the TPU's protocol is offered by default.
+        assert_eq!(node.to_string(), "TpuNode(127.0.0.1:8003)"); // Checks the display format.
+    }
+}
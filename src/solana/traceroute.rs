@@ -0,0 +1,337 @@
+// Traceroute-style diagnostics of the path to a target, run when a connection to it fails. Probes are sent
+// to the target's port with increasing TTLs, as UDP datagrams or TCP SYNs, and each router dropping one
+// answers with an ICMP time exceeded message naming itself. The answers arrive on a raw ICMP socket, so
+// tracing needs root or the CAP_NET_RAW capability.
+use {
+    crate::solana::precheck::{
+        strip_ipv4_header, ICMPV4_DESTINATION_UNREACHABLE, ICMPV4_TIME_EXCEEDED,
+        ICMPV6_DESTINATION_UNREACHABLE, ICMPV6_TIME_EXCEEDED, ICMP_HEADER_SIZE, IPV6_HEADER_SIZE,
+    },
+    socket2::{Domain, Protocol, SockRef, Socket, Type},
+    std::{
+        fmt,
+        io::{self, ErrorKind},
+        net::{IpAddr, SocketAddr},
+        str::FromStr,
+        time::{Duration, Instant},
+    },
+    tokio::{
+        net::{TcpSocket, UdpSocket},
+        time,
+    },
+    tracing::debug,
+};
+
+// Default number of hops probed before the trace gives up on reaching the target.
+pub const DEFAULT_TRACEROUTE_MAX_HOPS: u8 = 30;
+
+// Time a probe waits for an answer before its hop is reported as silent.
+const HOP_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Silent hops in a row after which the trace stops, as the path most likely ends at a filter.
+const MAX_SILENT_HOPS: usize = 5;
+
+// Payload of the UDP probes.
+const UDP_PROBE: &[u8] = b"handshake traceroute";
+
+// IP protocol numbers of the probes, as quoted by the ICMP errors about them.
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+
+// Protocol the probes of a trace are sent over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TracerouteProtocol {
+    Udp, // UDP datagrams, which the target answers with an ICMP port unreachable when its port is closed.
+    Tcp, // TCP SYNs, which the target answers with a SYN-ACK or a reset, and firewalls tend to let through.
+}
+
+impl fmt::Display for TracerouteProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TracerouteProtocol::Udp => write!(f, "udp"),
+            TracerouteProtocol::Tcp => write!(f, "tcp"),
+        }
+    }
+}
+
+// Protocols are named as on the command line.
+impl FromStr for TracerouteProtocol {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "udp" => Ok(TracerouteProtocol::Udp),
+            "tcp" => Ok(TracerouteProtocol::Tcp),
+            _ => Err(format!(
+                "Unknown traceroute protocol '{}': expected udp or tcp",
+                name
+            )),
+        }
+    }
+}
+
+// One hop of the path to the target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hop {
+    pub ttl: u8,                      // TTL of the probe, i.e. the distance of the hop.
+    pub address: Option<IpAddr>,      // Router or target that answered, if any did in time.
+    pub round_trip: Option<Duration>, // Time the answer took, when there was one.
+    pub reached: bool,                // Whether the answer came from the target itself.
+}
+
+// Traces the path to a target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Traceroute {
+    pub protocol: TracerouteProtocol, // Protocol the probes are sent over.
+    pub max_hops: u8,                 // Number of hops probed before giving up.
+}
+
+impl Traceroute {
+    // Constructs a trace over the protocol, probing up to the default number of hops.
+    pub fn new(protocol: TracerouteProtocol) -> Self {
+        Traceroute {
+            protocol,
+            max_hops: DEFAULT_TRACEROUTE_MAX_HOPS,
+        }
+    }
+
+    // Sets the number of hops probed before giving up; at least one always is.
+    pub fn with_max_hops(mut self, max_hops: u8) -> Self {
+        self.max_hops = max_hops.max(1);
+        self
+    }
+
+    // Probes the hops towards the target's port one after another, until the target answers, the maximum
+    // number of hops is reached, or several hops in a row stay silent. Fails when the ICMP socket cannot be
+    // opened, e.g. without the privileges raw sockets require.
+    pub async fn trace(&self, remote: SocketAddr) -> io::Result<Vec<Hop>> {
+        let icmp = open_icmp(remote.ip())?;
+        let mut hops: Vec<Hop> = Vec::new();
+        for ttl in 1..=self.max_hops {
+            let hop = match self.protocol {
+                TracerouteProtocol::Udp => probe_udp(&icmp, remote, ttl).await?,
+                TracerouteProtocol::Tcp => probe_tcp(&icmp, remote, ttl).await?,
+            };
+            debug!("Hop {} towards {}: {:?}", ttl, remote, hop.address);
+            let reached = hop.reached;
+            hops.push(hop);
+            let silent = hops.iter().rev().take_while(|hop| hop.address.is_none());
+            if reached || silent.count() >= MAX_SILENT_HOPS {
+                break;
+            }
+        }
+        Ok(hops)
+    }
+}
+
+// What an ICMP error says about a probe.
+#[derive(Debug, PartialEq, Eq)]
+enum Answer {
+    TimeExceeded, // A router on the way dropped the probe.
+    Unreachable,  // The probe could not be delivered, e.g. to a closed UDP port of the target.
+}
+
+// Opens a raw ICMP socket of the target's family, which receives the errors about every probe sent.
+fn open_icmp(host: IpAddr) -> io::Result<UdpSocket> {
+    let (domain, protocol) = match host {
+        IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4),
+        IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6),
+    };
+    let socket = Socket::new(domain, Type::RAW, Some(protocol))?;
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket.into())
+}
+
+// Sets the TTL, or hop limit, of the packets sent on a socket.
+fn set_ttl(socket: SockRef<'_>, remote: SocketAddr, ttl: u8) -> io::Result<()> {
+    match remote {
+        SocketAddr::V4(_) => socket.set_ttl_v4(ttl.into()),
+        SocketAddr::V6(_) => socket.set_unicast_hops_v6(ttl.into()),
+    }
+}
+
+// The unspecified address of the target's family, to bind the probes to.
+fn unspecified(remote: SocketAddr) -> SocketAddr {
+    match remote {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    }
+    .parse()
+    .expect("valid bind address")
+}
+
+// Sends a UDP datagram to the target's port, answered by a router with time exceeded, by the target
+// with port unreachable when the port is closed, or by the service itself.
+async fn probe_udp(icmp: &UdpSocket, remote: SocketAddr, ttl: u8) -> io::Result<Hop> {
+    let socket = UdpSocket::bind(unspecified(remote)).await?;
+    set_ttl(SockRef::from(&socket), remote, ttl)?;
+    let port = socket.local_addr()?.port();
+    let sent = Instant::now();
+    socket.send_to(UDP_PROBE, remote).await?;
+
+    let mut buf = [0; 1500];
+    let answer = async {
+        let answered: io::Result<Option<(IpAddr, bool)>> = tokio::select! {
+            Ok((_, from)) = socket.recv_from(&mut buf) => Ok(Some((from.ip(), true))),
+            answered = await_icmp(icmp, remote, IPPROTO_UDP, port) => {
+                let (from, answer) = answered?;
+                Ok(Some((from, answer == Answer::Unreachable && from == remote.ip())))
+            }
+        };
+        answered
+    };
+    let answered = time::timeout(HOP_TIMEOUT, answer)
+        .await
+        .unwrap_or(Ok(None))?;
+    Ok(hop(ttl, sent, answered))
+}
+
+// Starts a TCP connection to the target's port, answered by a router with time exceeded, or by the
+// target with a SYN-ACK or a reset.
+async fn probe_tcp(icmp: &UdpSocket, remote: SocketAddr, ttl: u8) -> io::Result<Hop> {
+    let socket = match remote {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+    set_ttl(SockRef::from(&socket), remote, ttl)?;
+    socket.bind(unspecified(remote))?;
+    let port = socket.local_addr()?.port();
+    let sent = Instant::now();
+
+    let answer = async {
+        let answered: io::Result<Option<(IpAddr, bool)>> = tokio::select! {
+            connected = socket.connect(remote) => match connected {
+                Ok(_) => Ok(Some((remote.ip(), true))),
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => Ok(Some((remote.ip(), true))),
+                // A router rejecting the SYN only leaves the hop silent, unless its ICMP error is matched first.
+                Err(_) => Ok(None),
+            },
+            answered = await_icmp(icmp, remote, IPPROTO_TCP, port) => {
+                let (from, _) = answered?;
+                Ok(Some((from, from == remote.ip())))
+            }
+        };
+        answered
+    };
+    let answered = time::timeout(HOP_TIMEOUT, answer)
+        .await
+        .unwrap_or(Ok(None))?;
+    Ok(hop(ttl, sent, answered))
+}
+
+// The hop of a probe from who answered it and whether that was the target, if anything did in time.
+fn hop(ttl: u8, sent: Instant, answered: Option<(IpAddr, bool)>) -> Hop {
+    Hop {
+        ttl,
+        address: answered.map(|(address, _)| address),
+        round_trip: answered.map(|_| sent.elapsed()),
+        reached: answered.is_some_and(|(_, reached)| reached),
+    }
+}
+
+// Waits until an ICMP error about the probe from the local port arrives, returning who sent it.
+async fn await_icmp(
+    icmp: &UdpSocket,
+    remote: SocketAddr,
+    protocol: u8,
+    port: u16,
+) -> io::Result<(IpAddr, Answer)> {
+    let mut buf = [0; 1500];
+    loop {
+        let (n, from) = icmp.recv_from(&mut buf).await?;
+        let message = match remote {
+            SocketAddr::V4(_) => strip_ipv4_header(&buf[..n]),
+            SocketAddr::V6(_) => Some(&buf[..n]),
+        };
+        if let Some(answer) = message.and_then(|message| parse(remote, message, protocol, port)) {
+            return Ok((from.ip(), answer));
+        }
+    }
+}
+
+// Parses an ICMP message, telling whether it is an error about the probe from the local port to the
+// target. Errors quote the IP header of the probe, followed by the start of its UDP or TCP header, where
+// the source and destination ports come first.
+fn parse(remote: SocketAddr, message: &[u8], protocol: u8, port: u16) -> Option<Answer> {
+    let header = message.get(..ICMP_HEADER_SIZE)?;
+    let answer = match (remote, header[0]) {
+        (SocketAddr::V4(_), ICMPV4_TIME_EXCEEDED) | (SocketAddr::V6(_), ICMPV6_TIME_EXCEEDED) => {
+            Answer::TimeExceeded
+        }
+        (SocketAddr::V4(_), ICMPV4_DESTINATION_UNREACHABLE)
+        | (SocketAddr::V6(_), ICMPV6_DESTINATION_UNREACHABLE) => Answer::Unreachable,
+        _ => return None,
+    };
+    let quoted = &message[ICMP_HEADER_SIZE..];
+    let (quoted_protocol, transport) = match remote {
+        SocketAddr::V4(_) => (*quoted.get(9)?, strip_ipv4_header(quoted)?),
+        SocketAddr::V6(_) => (*quoted.get(6)?, quoted.get(IPV6_HEADER_SIZE..)?),
+    };
+    let ports = transport.get(..4)?;
+    let matches = quoted_protocol == protocol
+        && ports[..2] == port.to_be_bytes()
+        && ports[2..] == remote.port().to_be_bytes();
+    matches.then_some(answer)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, test_case::test_case};
+
+    // Protocols are named as on the command line.
+    #[test]
+    fn test_from_str() {
+        assert_eq!("udp".parse(), Ok(TracerouteProtocol::Udp));
+        assert_eq!("tcp".parse(), Ok(TracerouteProtocol::Tcp));
+        assert_eq!(TracerouteProtocol::Tcp.to_string(), "tcp");
+        assert_eq!(
+            "icmp".parse::<TracerouteProtocol>(),
+            Err("Unknown traceroute protocol 'icmp': expected udp or tcp".to_string())
+        );
+    }
+
+    // An ICMPv4 error of the given type quoting a probe of the protocol between the ports.
+    fn icmp_error(kind: u8, protocol: u8, source: u16, destination: u16) -> Vec<u8> {
+        let mut message = vec![kind, 0, 0, 0, 0, 0, 0, 0];
+        let mut quoted = [0; 20];
+        quoted[0] = 0x45;
+        quoted[9] = protocol;
+        message.extend_from_slice(&quoted);
+        message.extend_from_slice(&source.to_be_bytes());
+        message.extend_from_slice(&destination.to_be_bytes());
+        message.extend_from_slice(&[0; 4]);
+        message
+    }
+
+    // Errors must quote a probe of the protocol from the local port to the target's port.
+    #[test_case(ICMPV4_TIME_EXCEEDED, IPPROTO_UDP, 40000, 8899, Some(Answer::TimeExceeded); "when router drops the probe")]
+    #[test_case(ICMPV4_DESTINATION_UNREACHABLE, IPPROTO_UDP, 40000, 8899, Some(Answer::Unreachable); "when port is unreachable")]
+    #[test_case(ICMPV4_TIME_EXCEEDED, IPPROTO_TCP, 40000, 8899, None; "when probe is of another protocol")]
+    #[test_case(ICMPV4_TIME_EXCEEDED, IPPROTO_UDP, 40001, 8899, None; "when probe is from another port")]
+    #[test_case(ICMPV4_TIME_EXCEEDED, IPPROTO_UDP, 40000, 8900, None; "when probe is to another port")]
+    #[test_case(0, IPPROTO_UDP, 40000, 8899, None; "when message is an echo reply")]
+    fn test_parse(kind: u8, protocol: u8, source: u16, destination: u16, expected: Option<Answer>) {
+        let remote: SocketAddr = "10.0.0.1:8899".parse().unwrap();
+        let message = icmp_error(kind, protocol, source, destination);
+        assert_eq!(parse(remote, &message, IPPROTO_UDP, 40000), expected);
+    }
+
+    // A trace to the loopback reaches it at the first hop, which accepts the SYN or rejects the datagram,
+    // where raw sockets are allowed.
+    #[test_case(TracerouteProtocol::Tcp; "when probes are tcp")]
+    #[test_case(TracerouteProtocol::Udp; "when probes are udp")]
+    #[tokio::test]
+    async fn test_trace_loopback(protocol: TracerouteProtocol) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = listener.local_addr().unwrap();
+        match open_icmp(remote.ip()) {
+            // Without root or CAP_NET_RAW there is nothing to trace with; any other failure is a bug.
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => return,
+            icmp => drop(icmp.unwrap()),
+        }
+        let hops = Traceroute::new(protocol).trace(remote).await.unwrap();
+        assert_eq!(hops.len(), 1);
+        assert_eq!(hops[0].address, Some(remote.ip()));
+        assert!(hops[0].reached);
+    }
+}
@@ -0,0 +1,173 @@
+// WebSockets over the browser's `WebSocket`, for handshakes run from a page built for wasm32-unknown-unknown.
+// The browser owns the connection, so there are no routes or TLS options to set and nothing is learnt about
+// the connection beyond the timings of its connect, write and read phases.
+use {
+    super::{with_timeout, Transport},
+    crate::error::HandshakeError,
+    async_trait::async_trait,
+    js_sys::{ArrayBuffer, Uint8Array},
+    send_wrapper::SendWrapper,
+    std::io::{Error, ErrorKind},
+    tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    tracing::info,
+    wasm_bindgen::{closure::Closure, JsCast, JsValue},
+    web_sys::{BinaryType, CloseEvent, MessageEvent, WebSocket},
+};
+
+// What the socket reported, in the order its callbacks ran.
+enum SocketEvent {
+    Open,            // The connection is established.
+    Message(String), // A text message, or a binary one read as UTF-8.
+    Error,           // The connection failed; the browser does not say why.
+    Close(u16),      // The connection is closed, with the code of the closing frame.
+}
+
+// A callback of the socket, turning the event it receives into a `SocketEvent`.
+type Callback = Closure<dyn FnMut(JsValue)>;
+
+// Represents a WebSocket transport through the browser, to a `ws://` or `wss://` URL.
+pub struct BrowserWs {
+    url: String, // URL of the remote server, with its scheme.
+}
+
+impl BrowserWs {
+    // Constructs a new instance of a browser WebSocket transport to the given URL.
+    pub fn new(url: String) -> Self {
+        BrowserWs { url }
+    }
+}
+
+// An open socket and the callbacks feeding its events into a channel; the socket is closed when dropped.
+struct Socket {
+    socket: WebSocket,
+    events: UnboundedReceiver<SocketEvent>,
+    _callbacks: [Callback; 4], // Kept alive for as long as the socket may call them.
+}
+
+impl Socket {
+    // Starts connecting to the URL. A URL the browser rejects, e.g. without a `ws` or `wss` scheme, fails
+    // as an unresolvable address would.
+    fn open(url: &str) -> Result<Self, HandshakeError> {
+        let socket = WebSocket::new(url)
+            .map_err(|e| HandshakeError::Dns(format!("URL error: {}", describe(&e))))?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+
+        let (sender, events) = unbounded_channel();
+        let callbacks = [
+            callback(&sender, |_| SocketEvent::Open),
+            callback(&sender, message_event),
+            callback(&sender, |_| SocketEvent::Error),
+            callback(&sender, |event| {
+                SocketEvent::Close(event.unchecked_into::<CloseEvent>().code())
+            }),
+        ];
+        socket.set_onopen(Some(callbacks[0].as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(callbacks[1].as_ref().unchecked_ref()));
+        socket.set_onerror(Some(callbacks[2].as_ref().unchecked_ref()));
+        socket.set_onclose(Some(callbacks[3].as_ref().unchecked_ref()));
+        Ok(Socket {
+            socket,
+            events,
+            _callbacks: callbacks,
+        })
+    }
+
+    // Awaits the connection; a socket failing or closing first is refused.
+    async fn connected(&mut self, url: &str) -> Result<(), Error> {
+        match self.events.recv().await {
+            Some(SocketEvent::Open) => Ok(()),
+            _ => Err(Error::new(
+                ErrorKind::ConnectionRefused,
+                format!("WebSocket connection to {} failed", url),
+            )),
+        }
+    }
+
+    // Sends the payload as a text message.
+    fn write(&self, payload: &serde_json::Value) -> Result<(), HandshakeError> {
+        self.socket
+            .send_with_str(&payload.to_string())
+            .map_err(|e| HandshakeError::WebSocket(describe(&e)))?;
+        info!("Sent message payload {}", payload);
+        Ok(())
+    }
+
+    // Awaits the next text or binary message. An empty string is returned if the socket closes first.
+    async fn read(&mut self) -> Result<String, HandshakeError> {
+        while let Some(event) = self.events.recv().await {
+            match event {
+                SocketEvent::Message(message) => {
+                    info!("Received message {}", &message);
+                    return Ok(message);
+                }
+                SocketEvent::Error => {
+                    return Err(HandshakeError::WebSocket(
+                        "Connection failed before a message arrived".to_string(),
+                    ))
+                }
+                SocketEvent::Close(code) => {
+                    info!("Connection closed with code {}", code);
+                    break;
+                }
+                SocketEvent::Open => continue,
+            }
+        }
+        Ok(String::new())
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        self.socket.set_onopen(None);
+        self.socket.set_onmessage(None);
+        self.socket.set_onerror(None);
+        self.socket.set_onclose(None);
+        let _ = self.socket.close();
+    }
+}
+
+// A callback sending the events it receives, as read by `event`, to the channel.
+fn callback(sender: &UnboundedSender<SocketEvent>, event: fn(JsValue) -> SocketEvent) -> Callback {
+    let sender = sender.clone();
+    Closure::new(move |received| {
+        let _ = sender.send(event(received));
+    })
+}
+
+// Reads a message event: text as it is, binary as UTF-8 for consistency with the native transport.
+fn message_event(event: JsValue) -> SocketEvent {
+    let data = event.unchecked_into::<MessageEvent>().data();
+    let message = match data.dyn_into::<ArrayBuffer>() {
+        Ok(buffer) => String::from_utf8_lossy(&Uint8Array::new(&buffer).to_vec()).to_string(),
+        Err(data) => data.as_string().unwrap_or_default(),
+    };
+    SocketEvent::Message(message)
+}
+
+// Describes an exception thrown by the browser, with its message when it has one.
+fn describe(exception: &JsValue) -> String {
+    match exception.dyn_ref::<js_sys::Error>() {
+        Some(error) => String::from(error.message()),
+        None => format!("{:?}", exception),
+    }
+}
+
+// Implements the Transport trait over the browser's WebSocket. The browser is single-threaded, so the
+// exchange, holding JavaScript values, is only ever polled on the thread it started on.
+#[async_trait]
+impl Transport for BrowserWs {
+    async fn connect_and_send(
+        &self,
+        timeout: Option<u32>, // Optional timeout in seconds, applied to each phase.
+        payload: serde_json::Value, // JSON payload to be sent to the remote server.
+    ) -> Result<String, HandshakeError> {
+        let url = self.url.clone();
+        SendWrapper::new(async move {
+            let mut socket = Socket::open(&url)?;
+            with_timeout("connect", timeout, socket.connected(&url)).await?;
+            with_timeout("write", timeout, async { socket.write(&payload) }).await?;
+            with_timeout("read", timeout, socket.read()).await
+        })
+        .await
+    }
+}
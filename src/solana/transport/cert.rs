@@ -0,0 +1,146 @@
+// Certificates presented by TLS servers, as reported with the handshake: who they were issued to and by,
+// the names they cover and how long until they expire, so certificates about to expire are caught
+// before clients start failing.
+use {
+    base64::{engine::general_purpose::STANDARD, Engine},
+    schemars::JsonSchema,
+    serde::Serialize,
+    sha2::{Digest, Sha256},
+    std::{
+        net::IpAddr,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+    x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer},
+};
+
+// Seconds in a day, the unit expiry is reported in.
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+// The fields of a certificate reported with the handshake.
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct CertificateInfo {
+    pub subject: String, // Distinguished name of the subject, e.g. "CN=rpc.example.com".
+    pub issuer: String,  // Distinguished name of the issuer.
+    pub sans: Vec<String>, // DNS names and IP addresses the certificate covers.
+    pub not_after: i64,  // When the certificate expires, in seconds since the Unix epoch.
+    pub days_until_expiry: i64, // Whole days left until it expires, negative once it has.
+    pub pin_sha256: String, // SHA-256 hash of its public key in base64, as `--pin-sha256` takes it.
+}
+
+impl CertificateInfo {
+    // Parses a DER-encoded certificate; `None` when it is not one.
+    pub fn from_der(der: &[u8]) -> Option<Self> {
+        let (_, certificate) = X509Certificate::from_der(der).ok()?;
+        let sans = match certificate.subject_alternative_name() {
+            Ok(Some(extension)) => extension
+                .value
+                .general_names
+                .iter()
+                .filter_map(general_name)
+                .collect(),
+            _ => Vec::new(),
+        };
+        let not_after = certificate.validity().not_after.timestamp();
+        Some(CertificateInfo {
+            subject: certificate.subject().to_string(),
+            issuer: certificate.issuer().to_string(),
+            sans,
+            not_after,
+            days_until_expiry: days_until(not_after, SystemTime::now()),
+            pin_sha256: spki_sha256(der).map(|pin| STANDARD.encode(pin))?,
+        })
+    }
+}
+
+// The SHA-256 hash of a DER-encoded certificate's public key; `None` when it is not a certificate.
+pub fn spki_sha256(der: &[u8]) -> Option<[u8; 32]> {
+    let (_, certificate) = X509Certificate::from_der(der).ok()?;
+    Some(Sha256::digest(certificate.public_key().raw).into())
+}
+
+// Renders the names a certificate covers; other kinds of names, e.g. e-mail addresses, are left out.
+fn general_name(name: &GeneralName) -> Option<String> {
+    match name {
+        GeneralName::DNSName(name) => Some(name.to_string()),
+        GeneralName::IPAddress(&[a, b, c, d]) => Some(IpAddr::from([a, b, c, d]).to_string()),
+        GeneralName::IPAddress(octets) => <[u8; 16]>::try_from(*octets)
+            .ok()
+            .map(|octets| IpAddr::from(octets).to_string()),
+        _ => None,
+    }
+}
+
+// Whole days from `now` until the timestamp, rounded down, so a certificate expiring later today has 0 left.
+fn days_until(timestamp: i64, now: SystemTime) -> i64 {
+    let now = match now.duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_secs() as i64,
+        Err(_) => 0,
+    };
+    (timestamp - now).div_euclid(SECONDS_PER_DAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::time::Duration, test_case::test_case};
+
+    // The subject, issuer and names covered are read from the certificate.
+    #[test]
+    fn test_from_der() {
+        let mut params = rcgen::CertificateParams::new(vec!["localhost".to_string()]);
+        for address in ["127.0.0.1", "::1"] {
+            let address = address.parse().unwrap();
+            params
+                .subject_alt_names
+                .push(rcgen::SanType::IpAddress(address));
+        }
+        params.distinguished_name = rcgen::DistinguishedName::new();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "node");
+        params.not_after = rcgen::date_time_ymd(4096, 1, 1);
+        let certificate = rcgen::Certificate::from_params(params).unwrap();
+
+        let info = CertificateInfo::from_der(&certificate.serialize_der().unwrap()).unwrap();
+        assert_eq!(info.subject, "CN=node");
+        assert_eq!(info.issuer, "CN=node");
+        assert_eq!(info.sans, vec!["localhost", "127.0.0.1", "::1"]);
+        assert_eq!(info.not_after, 67_090_118_400);
+        assert!(info.days_until_expiry > 365 * 2000);
+        assert_eq!(
+            info.pin_sha256,
+            STANDARD.encode(spki_sha256(&certificate.serialize_der().unwrap()).unwrap())
+        );
+        assert!(CertificateInfo::from_der(b"not a certificate").is_none());
+    }
+
+    // The pin hashes the certificate's SubjectPublicKeyInfo, so certificates sharing a key share it,
+    // whatever else differs.
+    #[test]
+    fn test_spki_sha256() {
+        let key = rcgen::KeyPair::generate(&rcgen::PKCS_ECDSA_P256_SHA256).unwrap();
+        let expected: [u8; 32] = Sha256::digest(key.public_key_der()).into();
+        let der = |name: &str, key: rcgen::KeyPair| {
+            let mut params = rcgen::CertificateParams::new(vec![name.to_string()]);
+            params.key_pair = Some(key);
+            rcgen::Certificate::from_params(params)
+                .unwrap()
+                .serialize_der()
+                .unwrap()
+        };
+        let renewed = rcgen::KeyPair::from_der(&key.serialize_der()).unwrap();
+
+        assert_eq!(spki_sha256(&der("localhost", key)), Some(expected));
+        assert_eq!(spki_sha256(&der("node.internal", renewed)), Some(expected));
+        assert_eq!(spki_sha256(b"not a certificate"), None);
+    }
+
+    // Days are counted whole, rounding down, and negative once expired.
+    #[test_case(10 * SECONDS_PER_DAY, 10; "when expiring in ten days")]
+    #[test_case(SECONDS_PER_DAY - 1, 0; "when expiring later today")]
+    #[test_case(0, 0; "when expiring now")]
+    #[test_case(-1, -1; "when expired a second ago")]
+    fn test_days_until(remaining: i64, days: i64) {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(days_until(1_700_000_000 + remaining, now), days);
+    }
+}
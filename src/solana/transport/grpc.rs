@@ -0,0 +1,88 @@
+// gRPC transport over HTTP/2, available with the `geyser` cargo feature.
+// The connection is opened like any other TCP connection, so gRPC shares the route, proxy and TLS options
+// of the TCP transport; over TLS, h2 is offered through ALPN as gRPC servers expect.
+use {
+    super::{
+        tcp::{open_stream, tls_handshake, Route, TlsOptions},
+        with_timeout,
+        ws::ByteStream,
+    }, // Import the connection helpers of the TCP transport and the per-phase timeout helper.
+    crate::error::HandshakeError, // Import the crate-wide error type.
+    std::{
+        io::{Error, ErrorKind},
+        sync::Mutex,
+    }, // Import standard IO types and the lock handing the stream over to the channel.
+    tonic::{
+        transport::{Channel, Endpoint, Uri},
+        Code, Status,
+    }, // Import the tonic channel and gRPC status types.
+    tower::service_fn, // Import service_fn to turn the established stream into a connector.
+    tracing::info,     // Import logging macros for informational logging.
+};
+
+// ALPN id of HTTP/2, the only protocol gRPC is spoken over.
+const H2: &str = "h2";
+
+// Connects to a gRPC server and completes the HTTP/2 handshake, returning a channel to issue calls on.
+// The stream is established up front, so connection and TLS failures keep their own error kinds,
+// and is then handed to the channel; a channel needing to reconnect fails instead of connecting again.
+pub(crate) async fn grpc_connect(
+    remote: &str,         // Remote host address.
+    is_secure: bool,      // Whether the connection runs over TLS.
+    route: &Route,        // How the connection reaches the remote.
+    tls: &TlsOptions,     // Certificates, SNI and pins used over TLS.
+    timeout: Option<u32>, // Optional timeout in seconds, applied to each phase.
+) -> Result<Channel, HandshakeError> {
+    let url = match is_secure {
+        false => format!("http://{}", remote),
+        true => format!("https://{}", remote),
+    };
+    let endpoint = Endpoint::from_shared(url)
+        .map_err(|e| HandshakeError::Dns(format!("Invalid address {}: {}", remote, e)))?;
+
+    // Connect, then wrap the stream in TLS for secure connections, offering h2 unless other protocols are configured.
+    let stream = open_stream(remote, route, timeout).await?;
+    let stream: Box<dyn ByteStream> = match is_secure {
+        true => {
+            let tls = match tls.alpn.is_empty() {
+                true => TlsOptions {
+                    alpn: vec![H2.to_string()],
+                    ..tls.clone()
+                },
+                false => tls.clone(),
+            };
+            Box::new(tls_handshake(stream, remote, &tls, H2, timeout).await?)
+        }
+        false => Box::new(stream),
+    };
+
+    let stream = Mutex::new(Some(stream));
+    let connector = service_fn(move |_: Uri| {
+        let stream = stream.lock().ok().and_then(|mut stream| stream.take());
+        async move {
+            stream.ok_or_else(|| Error::new(ErrorKind::NotConnected, "Connection already used"))
+        }
+    });
+    let channel = with_timeout("http2 handshake", timeout, async {
+        endpoint
+            .connect_with_connector(connector)
+            .await
+            .map_err(|e| HandshakeError::Protocol(format!("HTTP/2 handshake failed: {}", e)))
+    })
+    .await?;
+
+    info!("Connected to gRPC server {}", remote);
+    Ok(channel)
+}
+
+// Maps a gRPC status to a handshake error.
+// A call that could not reach the server is a connection error; any other status is a protocol error.
+pub(crate) fn convert_status(status: Status) -> HandshakeError {
+    match status.code() {
+        Code::Unavailable => HandshakeError::Connect(Error::new(
+            ErrorKind::ConnectionAborted,
+            status.message().to_string(),
+        )),
+        code => HandshakeError::Protocol(format!("gRPC {:?}: {}", code, status.message())),
+    }
+}
@@ -0,0 +1,381 @@
+// HTTP transport backed by the hyper client, available with the `hyper` cargo feature.
+// Unlike the raw TCP transport, hyper handles connection keep-alive and response framing,
+// and this transport additionally follows redirects and checks the response status.
+// It can also speak HTTP/2, which some RPC providers prefer, reporting the protocol each response came over.
+use {
+    super::{record_protocol, success_body, with_timeout, Transport}, // Import the Transport trait and the per-phase helpers.
+    crate::{error::HandshakeError, solana::http::HttpResponse}, // Import the crate-wide error type and HTTP responses.
+    async_trait::async_trait, // Import async_trait for asynchronous trait methods.
+    hyper::{
+        client::HttpConnector,
+        header::{CONTENT_TYPE, LOCATION},
+        Body, Client, Method, Request, StatusCode, Uri, Version,
+    }, // Import the hyper client and HTTP types.
+    hyper_rustls::{HttpsConnector, HttpsConnectorBuilder}, // Import the rustls connector for HTTPS.
+    std::{
+        io::{Error, ErrorKind}, // Import standard IO types for connection errors.
+        sync::OnceLock,
+    },
+    tracing::info, // Import logging macros for informational logging.
+};
+
+// Maximum number of redirects followed before giving up.
+const MAX_REDIRECTS: usize = 5;
+
+// The pooled hyper client, over HTTP or HTTPS.
+type HyperClient = Client<HttpsConnector<HttpConnector>>;
+
+// Clients shared by every transport with the same security and HTTP version, so that connections kept
+// alive by one handshake are reused by the next instead of each transport opening its own.
+static CLIENTS: [OnceLock<HyperClient>; 4] = [const { OnceLock::new() }; 4];
+
+// Define the Http struct representing an HTTP(S) transport built on a pooled hyper client.
+pub struct Http {
+    remote: String,      // The remote server's address as a string.
+    is_secure: bool,     // Flag indicating whether to use HTTPS or not.
+    client: HyperClient, // Pooled client shared across transports.
+}
+
+// Returns the client shared by transports with the same security and HTTP version, built on first use.
+fn shared_client(is_secure: bool, http2: bool) -> HyperClient {
+    let index = usize::from(is_secure) * 2 + usize::from(http2);
+    CLIENTS[index]
+        .get_or_init(|| client(is_secure, http2))
+        .clone()
+}
+
+// Builds a pooled client. With `http2`, HTTPS connections offer h2 through ALPN, falling back
+// to HTTP/1.1, while plain HTTP connections speak HTTP/2 with prior knowledge.
+fn client(is_secure: bool, http2: bool) -> HyperClient {
+    let builder = HttpsConnectorBuilder::new()
+        .with_webpki_roots()
+        .https_or_http()
+        .enable_http1();
+    let connector = match http2 {
+        true => builder.enable_http2().build(),
+        false => builder.build(),
+    };
+    Client::builder()
+        .http2_only(http2 && !is_secure)
+        .build(connector)
+}
+
+// Name of the HTTP version, as its ALPN protocol identifier where it has one.
+fn protocol_name(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "http/0.9",
+        Version::HTTP_10 => "http/1.0",
+        Version::HTTP_11 => "http/1.1",
+        Version::HTTP_2 => "h2",
+        Version::HTTP_3 => "h3",
+        _ => "unknown",
+    }
+}
+
+// Implementation block for Http.
+impl Http {
+    // Constructs a new Http instance with the specified remote address and security preference.
+    pub fn new(remote: String, is_secure: bool) -> Self {
+        Http {
+            remote,
+            is_secure,
+            client: shared_client(is_secure, false),
+        }
+    }
+
+    // Sends requests over HTTP/2: negotiated with ALPN for HTTPS, with prior knowledge for plain HTTP.
+    pub fn with_http2(mut self) -> Self {
+        self.client = shared_client(self.is_secure, true);
+        self
+    }
+
+    // Builds the URL of the JSON-RPC endpoint from the remote address and security preference.
+    fn url(&self) -> String {
+        match self.is_secure {
+            false => format!("http://{}/", self.remote),
+            true => format!("https://{}/", self.remote),
+        }
+    }
+}
+
+// Resolves a redirect location against the URI that produced it.
+// Absolute locations are used as-is; relative ones keep the scheme and authority of the current URI.
+fn resolve_location(current: &Uri, location: &str) -> Result<Uri, HandshakeError> {
+    let invalid = || HandshakeError::Protocol(format!("Invalid redirect location '{}'", location));
+    let target: Uri = location.parse().map_err(|_| invalid())?;
+    if target.scheme().is_some() {
+        return Ok(target);
+    }
+
+    let mut parts = current.clone().into_parts();
+    parts.path_and_query = target.path_and_query().cloned();
+    Uri::from_parts(parts).map_err(|_| invalid())
+}
+
+// Maps a hyper client error to a handshake error.
+// Failures to establish the connection, including the TLS handshake, are connection errors.
+fn convert_error(error: hyper::Error) -> HandshakeError {
+    if error.is_connect() {
+        HandshakeError::Connect(Error::new(ErrorKind::ConnectionRefused, error.to_string()))
+    } else {
+        HandshakeError::Protocol(format!("HTTP error: {}", error))
+    }
+}
+
+// Indicates whether the status asks the client to repeat the request elsewhere.
+fn is_redirect(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+    )
+}
+
+// Implement the Transport trait for the Http struct.
+// The JSON payload is POSTed, redirects are followed by re-sending the same body,
+// and only the body of a successful response is returned by `connect_and_send`.
+#[async_trait]
+impl Transport for Http {
+    async fn connect_and_send(
+        &self,
+        timeout: Option<u32>, // Optional timeout in seconds, applied to each phase.
+        payload: serde_json::Value, // JSON payload to be sent.
+    ) -> Result<String, HandshakeError> {
+        success_body(self.send_request(timeout, payload).await?)
+    }
+
+    // Returns the response the redirects led to, whatever its status.
+    async fn send_request(
+        &self,
+        timeout: Option<u32>, // Optional timeout in seconds, applied to each phase.
+        payload: serde_json::Value, // JSON payload to be sent.
+    ) -> Result<HttpResponse, HandshakeError> {
+        let body = payload.to_string();
+        let mut uri: Uri = self
+            .url()
+            .parse()
+            .map_err(|e| HandshakeError::Dns(format!("Invalid address {}: {}", self.remote, e)))?;
+
+        for _ in 0..=MAX_REDIRECTS {
+            // Build and send the POST request.
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri(uri.clone())
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body.clone()))
+                .map_err(|e| HandshakeError::Protocol(format!("Invalid request: {}", e)))?;
+            info!("Sent message payload {} to {}", &body, &uri);
+            let response = with_timeout("request", timeout, async {
+                self.client.request(request).await.map_err(convert_error)
+            })
+            .await?;
+            info!("Response came over {:?}", response.version());
+            record_protocol(protocol_name(response.version()));
+
+            // Follow redirects to the announced location.
+            let status = response.status();
+            if is_redirect(status) {
+                let location = response
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|value| value.to_str().ok())
+                    .ok_or_else(|| {
+                        HandshakeError::Protocol("Redirect without a Location header".to_string())
+                    })?;
+                uri = resolve_location(&uri, location)?;
+                info!("Following {} redirect to {}", status, &uri);
+                continue;
+            }
+
+            // Read the full body alongside the status line and headers.
+            let version = format!("{:?}", response.version());
+            let headers = response
+                .headers()
+                .iter()
+                .map(|(name, value)| {
+                    let value = String::from_utf8_lossy(value.as_bytes()).to_string();
+                    (name.to_string(), value)
+                })
+                .collect();
+            let bytes = with_timeout("read", timeout, async {
+                hyper::body::to_bytes(response.into_body())
+                    .await
+                    .map_err(|e| HandshakeError::InvalidResponse(e.to_string()))
+            })
+            .await?;
+            info!("Received message was {}", String::from_utf8_lossy(&bytes));
+            return Ok(HttpResponse {
+                version,
+                status: status.as_u16(),
+                reason: status.canonical_reason().unwrap_or_default().to_string(),
+                headers,
+                body: bytes.to_vec(),
+            });
+        }
+
+        Err(HandshakeError::Protocol(format!(
+            "Too many redirects (more than {})",
+            MAX_REDIRECTS
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{super::timed, *},
+        tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        },
+    };
+
+    // Serves the canned responses, one per connection, and returns the server address.
+    async fn serve(responses: Vec<String>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+                let _ = socket.read(&mut buf).await; // Consume the request.
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        remote
+    }
+
+    // A successful response body is returned as-is, and the protocol it came over recorded.
+    #[tokio::test]
+    async fn test_success_body_is_returned() {
+        let remote = serve(vec![
+            "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\n{}".to_string(),
+        ])
+        .await;
+
+        let (result, _, connection) =
+            timed(Http::new(remote, false).connect_and_send(Some(5), serde_json::json!({}))).await;
+        assert_eq!(result.unwrap(), "{}");
+        assert_eq!(connection.protocol.as_deref(), Some("http/1.1"));
+    }
+
+    // The whole response is returned, whatever its status, with its status line and headers.
+    #[tokio::test]
+    async fn test_send_request_returns_response() {
+        let remote = serve(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nConnection: close\r\nRetry-After: 5\r\nContent-Length: 4\r\n\r\nbusy"
+                .to_string(),
+        ])
+        .await;
+
+        let response = Http::new(remote, false)
+            .send_request(Some(5), serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(response.version, "HTTP/1.1");
+        assert_eq!(
+            (response.status, response.reason.as_str()),
+            (503, "Service Unavailable")
+        );
+        assert_eq!(response.header("retry-after"), Some("5"));
+        assert_eq!(response.body_text(), "busy");
+    }
+
+    // Transports to the same node share a client, so a kept-alive connection serves them all.
+    #[tokio::test]
+    async fn test_connection_is_shared_across_transports() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = listener.local_addr().unwrap().to_string();
+        let accepted = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut buf = vec![0; 4096];
+                    while let Ok(n) = socket.read(&mut buf).await {
+                        let response = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}";
+                        if n == 0 || socket.write_all(response.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        for _ in 0..3 {
+            let result = Http::new(remote.clone(), false)
+                .connect_and_send(Some(5), serde_json::json!({}))
+                .await;
+            assert_eq!(result.unwrap(), "{}");
+        }
+        assert_eq!(accepted.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    // Plain HTTP/2 is spoken with prior knowledge, so an HTTP/1.1-only server cannot answer it.
+    #[tokio::test]
+    async fn test_h2c_needs_http2_server() {
+        let remote = serve(vec![
+            "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\n{}".to_string(),
+        ])
+        .await;
+
+        let result = Http::new(remote, false)
+            .with_http2()
+            .connect_and_send(Some(5), serde_json::json!({}))
+            .await;
+        assert!(result.is_err());
+    }
+
+    // A relative redirect is followed to the same server.
+    #[tokio::test]
+    async fn test_redirect_is_followed() {
+        let remote = serve(vec![
+            "HTTP/1.1 307 Temporary Redirect\r\nConnection: close\r\nLocation: /rpc\r\nContent-Length: 0\r\n\r\n"
+                .to_string(),
+            "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 4\r\n\r\n\"ok\"".to_string(),
+        ])
+        .await;
+
+        let result = Http::new(remote, false)
+            .connect_and_send(Some(5), serde_json::json!({}))
+            .await;
+        assert_eq!(result.unwrap(), "\"ok\"");
+    }
+
+    // A non-2xx response is reported as an error carrying the status.
+    #[tokio::test]
+    async fn test_error_status_is_reported() {
+        let remote = serve(vec![
+            "HTTP/1.1 429 Too Many Requests\r\nConnection: close\r\nContent-Length: 0\r\n\r\n"
+                .to_string(),
+        ])
+        .await;
+
+        let error = Http::new(remote, false)
+            .connect_and_send(Some(5), serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, HandshakeError::Protocol(_)));
+        assert!(error.to_string().contains("429"));
+    }
+
+    // Relative and absolute redirect locations are resolved correctly.
+    #[test]
+    fn test_resolve_location() {
+        let current: Uri = "http://127.0.0.1:8899/".parse().unwrap();
+        assert_eq!(
+            resolve_location(&current, "/rpc").unwrap().to_string(),
+            "http://127.0.0.1:8899/rpc"
+        );
+        assert_eq!(
+            resolve_location(&current, "https://example.com/")
+                .unwrap()
+                .to_string(),
+            "https://example.com/"
+        );
+    }
+}
@@ -0,0 +1,124 @@
+// A scripted transport for testing handshake logic without a live node.
+// Each call to `connect_and_send` takes the next reply of the script: a canned body, an error, or a
+// body computed from the payload, e.g. to echo a request id. Every payload sent is recorded.
+use {
+    super::Transport, // Import the Transport trait the mock implements.
+    crate::error::HandshakeError,
+    async_trait::async_trait, // Import async_trait for asynchronous trait methods.
+    std::{
+        collections::VecDeque,
+        io::{Error, ErrorKind}, // Import standard IO types for the exhausted script.
+        sync::Mutex,
+    },
+};
+
+// A reply of the script, computed from the payload it answers.
+type Reply = Box<dyn FnOnce(&serde_json::Value) -> Result<String, HandshakeError> + Send>;
+
+// A transport answering with scripted replies, in order, instead of connecting anywhere.
+// Once the script is exhausted, every call fails with a connection error.
+#[derive(Default)]
+pub struct MockTransport {
+    replies: Mutex<VecDeque<Reply>>, // Replies still to be given, in order.
+    requests: Mutex<Vec<serde_json::Value>>, // Payloads sent so far, in order.
+}
+
+impl MockTransport {
+    // Constructs a transport with an empty script.
+    pub fn new() -> Self {
+        MockTransport::default()
+    }
+
+    // Adds a reply with the body, e.g. a JSON-RPC response.
+    pub fn with_response(self, body: impl Into<String>) -> Self {
+        let body = body.into();
+        self.with_reply(move |_| Ok(body))
+    }
+
+    // Adds a reply failing with the error, e.g. a timeout.
+    pub fn with_error(self, error: HandshakeError) -> Self {
+        self.with_reply(move |_| Err(error))
+    }
+
+    // Adds a reply computed from the payload it answers.
+    pub fn with_reply<F>(self, reply: F) -> Self
+    where
+        F: FnOnce(&serde_json::Value) -> Result<String, HandshakeError> + Send + 'static,
+    {
+        self.replies
+            .lock()
+            .expect("mock lock poisoned")
+            .push_back(Box::new(reply));
+        self
+    }
+
+    // Returns the payloads sent so far, in order.
+    pub fn requests(&self) -> Vec<serde_json::Value> {
+        self.requests.lock().expect("mock lock poisoned").clone()
+    }
+
+    // Returns the number of replies not given yet.
+    pub fn remaining(&self) -> usize {
+        self.replies.lock().expect("mock lock poisoned").len()
+    }
+}
+
+// Implement the Transport trait by replaying the script.
+#[async_trait]
+impl Transport for MockTransport {
+    async fn connect_and_send(
+        &self,
+        _timeout: Option<u32>,      // Ignored: replies are immediate.
+        payload: serde_json::Value, // JSON payload to be sent.
+    ) -> Result<String, HandshakeError> {
+        self.requests
+            .lock()
+            .expect("mock lock poisoned")
+            .push(payload.clone());
+        let reply = self.replies.lock().expect("mock lock poisoned").pop_front();
+        match reply {
+            Some(reply) => reply(&payload),
+            None => Err(HandshakeError::Connect(Error::new(
+                ErrorKind::NotConnected,
+                "mock transport has no more replies",
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Replies are given in order, recording the payloads, and fail once the script is exhausted.
+    #[tokio::test]
+    async fn test_script() {
+        let transport = MockTransport::new()
+            .with_response("{}")
+            .with_error(HandshakeError::Protocol("bad".to_string()))
+            .with_reply(|payload| Ok(payload["id"].to_string()));
+        let payload = |id: u64| serde_json::json!({ "id": id });
+
+        assert_eq!(
+            transport.connect_and_send(None, payload(1)).await.unwrap(),
+            "{}"
+        );
+        assert!(matches!(
+            transport.connect_and_send(None, payload(2)).await,
+            Err(HandshakeError::Protocol(_))
+        ));
+        assert_eq!(
+            transport.connect_and_send(None, payload(3)).await.unwrap(),
+            "3"
+        );
+        assert_eq!(transport.remaining(), 0);
+        assert!(matches!(
+            transport.connect_and_send(None, payload(4)).await,
+            Err(HandshakeError::Connect(_))
+        ));
+        assert_eq!(
+            transport.requests(),
+            [payload(1), payload(2), payload(3), payload(4)]
+        );
+    }
+}
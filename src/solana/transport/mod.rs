@@ -1,12 +1,288 @@
 // Imports the `async_trait` macro for enabling asynchronous methods within traits,
-// and the standard `io` module for input/output operations, including network communication and error handling.
-use {async_trait::async_trait, std::io};
+// and the crate-wide `HandshakeError` used to report network communication failures.
+use {
+    crate::{error::HandshakeError, solana::http::HttpResponse},
+    async_trait::async_trait,
+    cert::CertificateInfo,
+    std::{cell::RefCell, fmt::Display, future::Future, net::SocketAddr, time::Duration},
+    tracing::{debug, field::Empty, info_span, Instrument, Span},
+};
+// The browser has no clock behind `std::time::Instant`, so on wasm32 web-time reads `performance.now()`.
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    crate::solana::{target::Target, TransportType},
+    std::time::Instant,
+};
 
-// Module declarations for TCP and WebSocket implementations,
-// allowing for specific transport protocol functionality to be encapsulated within these modules.
+// Module declarations: each transport (TCP and TLS, WebSocket, QUIC, the mock) lives in its own module,
+// next to the pieces they share: pooled connections, HTTP proxies, retries, certificate inspection and
+// pinning, and the handshake tap. The hyper transport needs the `hyper` feature, and the gRPC one `geyser`.
+// Sockets are native only: on wasm32, the browser's WebSocket transport needs the `wasm` feature.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub mod browser;
+pub mod cert;
+#[cfg(all(feature = "geyser", not(target_arch = "wasm32")))]
+pub mod grpc;
+#[cfg(all(feature = "hyper", not(target_arch = "wasm32")))]
+pub mod http;
+pub mod mock;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pin;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pool;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod proxy;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod quic;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod retry;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tap;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod tcp;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod ws;
 
+// Define a constant for the default timeout, in seconds, applied to each transport phase.
+pub const DEFAULT_TIMEOUT_SECONDS: u32 = 60;
+
+// Time spent in each phase of a handshake; phases a transport does not go through stay empty.
+// Phases repeated within a handshake, e.g. when a stale pooled connection is retried, are summed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PhaseTimings {
+    pub resolve: Option<Duration>, // DNS resolution of the remote address.
+    pub connect: Option<Duration>, // TCP connect, including any proxy tunnel and WebSocket upgrade.
+    pub tls: Option<Duration>,     // TLS negotiation, or the QUIC, Noise or RLPx handshake.
+    pub write: Option<Duration>,   // Writing the request.
+    pub read: Option<Duration>,    // Reading the response.
+}
+
+// What a handshake learnt about the connection it made, recorded alongside its phase timings. A name may
+// resolve to several addresses, so the one connected to is kept; the rest is what TLS negotiated.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    pub connected: Option<SocketAddr>, // Address connected to, when the transport connected directly.
+    pub protocol: Option<String>, // Protocol negotiated with the remote, e.g. "h2", when the transport reports one.
+    pub tls_version: Option<String>, // TLS version negotiated, e.g. "TLSv1.3", when the transport reports one.
+    pub cipher_suite: Option<String>, // Cipher suite negotiated, when the transport reports one.
+    pub key_exchange: Option<String>, // Key exchange group negotiated, e.g. "X25519", when the transport reports one.
+    pub certificates: Vec<CertificateInfo>, // Certificate chain the TLS server presented, leaf first.
+    pub early_data: Option<bool>, // Whether the server accepted the request sent as TLS early data, when it was.
+    pub early_data_saved: Option<Duration>, // Time the request did not wait for the handshake, when accepted as early data.
+}
+
+impl PhaseTimings {
+    // Adds the time spent in a phase, identified by the name given to `with_timeout`.
+    // Phases without a matching field, such as hyper's combined request phase, are not recorded.
+    fn record(&mut self, phase: &str, elapsed: Duration) {
+        let field = match phase {
+            "resolve" => &mut self.resolve,
+            "connect" | "proxy connect" | "websocket upgrade" => &mut self.connect,
+            "tls handshake" | "quic handshake" | "noise handshake" | "rlpx handshake" => {
+                &mut self.tls
+            }
+            "write" => &mut self.write,
+            "read" => &mut self.read,
+            _ => return,
+        };
+        *field = Some(field.unwrap_or_default() + elapsed);
+    }
+
+    // Adds every phase of another set of timings.
+    fn merge(&mut self, other: &PhaseTimings) {
+        let phases = [
+            ("resolve", other.resolve),
+            ("connect", other.connect),
+            ("tls handshake", other.tls),
+            ("write", other.write),
+            ("read", other.read),
+        ];
+        for (phase, elapsed) in phases {
+            if let Some(elapsed) = elapsed {
+                self.record(phase, elapsed);
+            }
+        }
+    }
+}
+
+impl ConnectionInfo {
+    // Takes whatever another connection reported, keeping what it did not.
+    fn merge(&mut self, other: &ConnectionInfo) {
+        self.connected = other.connected.or(self.connected);
+        self.protocol = other.protocol.clone().or(self.protocol.take());
+        self.tls_version = other.tls_version.clone().or(self.tls_version.take());
+        self.cipher_suite = other.cipher_suite.clone().or(self.cipher_suite.take());
+        self.key_exchange = other.key_exchange.clone().or(self.key_exchange.take());
+        self.early_data = other.early_data.or(self.early_data);
+        self.early_data_saved = other.early_data_saved.or(self.early_data_saved);
+        if !other.certificates.is_empty() {
+            self.certificates = other.certificates.clone();
+        }
+    }
+}
+
+tokio::task_local! {
+    // Timings of the phases run by the handshake currently being timed, if any.
+    static PHASE_TIMINGS: RefCell<PhaseTimings>;
+    // What the handshake currently being timed learnt about its connection, if any.
+    static CONNECTION_INFO: RefCell<ConnectionInfo>;
+}
+
+// Runs a handshake and returns its result along with the time spent in each of its phases and what it
+// learnt about its connection. Timed handshakes can be nested; what the inner one records also counts
+// towards the outer one.
+pub async fn timed<T, F>(future: F) -> (T, PhaseTimings, ConnectionInfo)
+where
+    F: Future<Output = T>,
+{
+    let recorded = async {
+        let result = future.await;
+        let timings = PHASE_TIMINGS.with(|timings| timings.borrow().clone());
+        let connection = CONNECTION_INFO.with(|connection| connection.borrow().clone());
+        (result, timings, connection)
+    };
+    let (result, timings, connection) = PHASE_TIMINGS
+        .scope(
+            RefCell::new(PhaseTimings::default()),
+            CONNECTION_INFO.scope(RefCell::new(ConnectionInfo::default()), recorded),
+        )
+        .await;
+    let _ = PHASE_TIMINGS.try_with(|outer| outer.borrow_mut().merge(&timings));
+    let _ = CONNECTION_INFO.try_with(|outer| outer.borrow_mut().merge(&connection));
+    (result, timings, connection)
+}
+
+// Updates what the handshake being timed learnt about its connection; outside of `timed`, there is
+// nothing to record.
+#[cfg(not(target_arch = "wasm32"))]
+fn record_connection(update: impl FnOnce(&mut ConnectionInfo)) {
+    let _ = CONNECTION_INFO.try_with(|connection| update(&mut connection.borrow_mut()));
+}
+
+// Records the address the handshake being timed connected to.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn record_connected(address: SocketAddr) {
+    record_connection(|connection| connection.connected = Some(address));
+}
+
+// Records the protocol negotiated by the handshake being timed.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn record_protocol(protocol: &str) {
+    record_connection(|connection| connection.protocol = Some(protocol.to_string()));
+}
+
+// Records the TLS version, cipher suite and, when known, key exchange group negotiated by the handshake
+// being timed.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn record_tls(version: &str, cipher_suite: &str, key_exchange: Option<&str>) {
+    record_connection(|connection| {
+        connection.tls_version = Some(version.to_string());
+        connection.cipher_suite = Some(cipher_suite.to_string());
+        connection.key_exchange = key_exchange.map(str::to_string);
+    });
+}
+
+// Records the certificate chain presented to the handshake being timed.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn record_certificates(certificates: &[CertificateInfo]) {
+    record_connection(|connection| connection.certificates = certificates.to_vec());
+}
+
+// Records whether the server accepted the request sent as early data by the handshake being timed and,
+// if so, the time saved.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn record_early_data(accepted: bool, saved: Option<Duration>) {
+    record_connection(|connection| {
+        connection.early_data = Some(accepted);
+        connection.early_data_saved = saved;
+    });
+}
+
+// Runs a single transport phase (connect, write, read, ...) under a timeout.
+// If the phase does not complete in time, `HandshakeError::Timeout` naming the phase is returned,
+// so callers can tell timeouts apart from other failures. Errors of the phase itself are converted
+// into `HandshakeError`, with plain I/O errors becoming connection errors.
+// Each phase runs in its own span, named after the phase, under the span of the handshake.
+pub async fn with_timeout<T, E, F>(
+    phase: &str,
+    timeout: Option<u32>,
+    future: F,
+) -> Result<T, HandshakeError>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Into<HandshakeError>,
+{
+    let span = info_span!("phase", phase, otel.name = phase, otel.status_code = Empty);
+    let seconds = timeout.unwrap_or(DEFAULT_TIMEOUT_SECONDS);
+    let started = Instant::now();
+    let bounded = deadline(Duration::from_secs(seconds.into()), future);
+    let outcome = bounded.instrument(span.clone()).await;
+    let elapsed = started.elapsed();
+    // Record the phase for the handshake being timed; outside of `timed`, there is nothing to record.
+    let _ = PHASE_TIMINGS.try_with(|timings| timings.borrow_mut().record(phase, elapsed));
+    let result = match outcome {
+        Some(result) => result.map_err(Into::into),
+        None => Err(HandshakeError::Timeout {
+            phase: phase.to_string(),
+            seconds,
+        }),
+    };
+    record_status(&span, &result);
+    let elapsed_ms = elapsed.as_millis() as u64;
+    match &result {
+        Ok(_) => debug!(phase, elapsed_ms, "Phase completed"),
+        Err(e) => debug!(phase, elapsed_ms, error = %e, "Phase failed"),
+    }
+    result
+}
+
+// Awaits the future until the timeout, on tokio's timer; `None` if it elapsed first.
+#[cfg(not(target_arch = "wasm32"))]
+async fn deadline<F: Future>(timeout: Duration, future: F) -> Option<F::Output> {
+    tokio::time::timeout(timeout, future).await.ok()
+}
+
+// Awaits the future until the timeout, on the browser's timer; `None` if it elapsed first.
+#[cfg(target_arch = "wasm32")]
+async fn deadline<F: Future>(timeout: Duration, future: F) -> Option<F::Output> {
+    use futures_util::future::{select, Either};
+    let future = std::pin::pin!(future);
+    match select(future, gloo_timers::future::sleep(timeout)).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
+
+// Runs a whole handshake with a node under a `handshake` span, so its phases form a span tree
+// (resolve, connect, tls handshake, write, read) that tracing layers such as OpenTelemetry can export.
+pub async fn traced<T, F>(
+    target: impl Display,
+    transport: impl Display,
+    future: F,
+) -> Result<T, HandshakeError>
+where
+    F: Future<Output = Result<T, HandshakeError>>,
+{
+    let span = info_span!(
+        "handshake",
+        target = %target,
+        transport = %transport,
+        otel.status_code = Empty
+    );
+    let result = future.instrument(span.clone()).await;
+    record_status(&span, &result);
+    result
+}
+
+// Marks the span as failed when its work returned an error.
+fn record_status<T>(span: &Span, result: &Result<T, HandshakeError>) {
+    if result.is_err() {
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
 // Define the `Transport` trait for asynchronous network communication.
 // This trait provides a generic interface for sending data across a network and receiving a response.
 #[async_trait]
@@ -16,9 +292,76 @@ pub trait Transport: Send + Sync {
     // and await a response. It accepts an optional timeout and a JSON payload as parameters.
     async fn connect_and_send(
         &self,
-        timeout: Option<u32>,       // Optional timeout in seconds.
+        timeout: Option<u32>, // Optional timeout in seconds, applied to each phase of the exchange.
         payload: serde_json::Value, // The payload to be sent, encapsulated as JSON.
-    ) -> io::Result<String>; // Returns an `io::Result` encapsulating the response as a `String` or an error.
+    ) -> Result<String, HandshakeError>; // Returns the response as a `String`, or the failure classified by `HandshakeError`.
+
+    // Sends the payload as an HTTP request and returns the whole response, whatever its status, so its
+    // status line and headers can be inspected. Transports that do not exchange HTTP responses, e.g.
+    // WebSocket and QUIC, fail without sending anything.
+    async fn send_request(
+        &self,
+        _timeout: Option<u32>,
+        _payload: serde_json::Value,
+    ) -> Result<HttpResponse, HandshakeError> {
+        Err(HandshakeError::InvalidConfig(
+            "The transport does not exchange HTTP responses".to_string(),
+        ))
+    }
+}
+
+// Returns the body of a successful HTTP response, or a protocol error carrying the status and body.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn success_body(response: HttpResponse) -> Result<String, HandshakeError> {
+    if !response.is_success() {
+        return Err(HandshakeError::Protocol(format!(
+            "HTTP {} {}: {}",
+            response.status,
+            response.reason,
+            response.body_text()
+        )));
+    }
+    Ok(response.body_text())
+}
+
+// A borrowed transport is a transport, so one can be shared without being moved.
+#[async_trait]
+impl<T: Transport + ?Sized> Transport for &T {
+    async fn connect_and_send(
+        &self,
+        timeout: Option<u32>,
+        payload: serde_json::Value,
+    ) -> Result<String, HandshakeError> {
+        (**self).connect_and_send(timeout, payload).await
+    }
+
+    async fn send_request(
+        &self,
+        timeout: Option<u32>,
+        payload: serde_json::Value,
+    ) -> Result<HttpResponse, HandshakeError> {
+        (**self).send_request(timeout, payload).await
+    }
+}
+
+// A boxed transport is a transport, so the ones `select` returns can be used where a type is expected.
+#[async_trait]
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    async fn connect_and_send(
+        &self,
+        timeout: Option<u32>,
+        payload: serde_json::Value,
+    ) -> Result<String, HandshakeError> {
+        (**self).connect_and_send(timeout, payload).await
+    }
+
+    async fn send_request(
+        &self,
+        timeout: Option<u32>,
+        payload: serde_json::Value,
+    ) -> Result<HttpResponse, HandshakeError> {
+        (**self).send_request(timeout, payload).await
+    }
 }
 
 // Define the `ChooseTransport` trait for selecting the appropriate transport mechanism at runtime.
@@ -30,3 +373,116 @@ pub trait ChooseTransport {
         Self: Sized; // Ensures this method can only be called on types that are sized, allowing for self-references.
 }
 
+// Opens a new connection of the given transport type to the remote for every exchange.
+// QUIC runs over UDP and hyper manages its own connections, so only the others can be proxied.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn select(
+    remote: &Target,               // The remote endpoint.
+    transport_type: TransportType, // Transport to connect with.
+    route: tcp::Route,             // How TCP-based connections reach the remote.
+    tls: tcp::TlsOptions,          // Options for TLS and WSS connections.
+) -> Box<dyn Transport> {
+    match transport_type {
+        // WebSocket transport, secure or plain.
+        TransportType::Ws | TransportType::Wss => {
+            let secure = transport_type == TransportType::Wss;
+            let ws = ws::Ws::new(remote.to_string(), secure);
+            Box::new(ws.with_route(route).with_tls(tls))
+        }
+        // TLS transport over TCP, or plain TCP.
+        TransportType::Tls | TransportType::Tcp => {
+            let secure = transport_type == TransportType::Tls;
+            let tcp = tcp::Tcp::new(remote.to_string(), secure);
+            Box::new(tcp.with_route(route).with_tls(tls))
+        }
+        // QUIC transport, which connects to a socket address rather than a name.
+        TransportType::Quic => {
+            let address = match remote.resolved.first() {
+                Some(address) => address.to_string(),
+                None => remote.to_string(),
+            };
+            Box::new(quic::Quic::new(address))
+        }
+        #[cfg(feature = "hyper")]
+        TransportType::Http => Box::new(http::Http::new(remote.to_string(), false)), // HTTP via hyper.
+        #[cfg(feature = "hyper")]
+        TransportType::Https => Box::new(http::Http::new(remote.to_string(), true)), // HTTPS via hyper.
+        #[cfg(feature = "hyper")]
+        TransportType::H2 => Box::new(http::Http::new(remote.to_string(), true).with_http2()), // HTTP/2 over TLS via hyper.
+        #[cfg(feature = "hyper")]
+        TransportType::H2c => Box::new(http::Http::new(remote.to_string(), false).with_http2()), // Cleartext HTTP/2 via hyper.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Phases run under `timed` are recorded, summed when repeated, and count towards enclosing timings.
+    #[tokio::test]
+    async fn test_timed_records_phases() {
+        let phase =
+            |name: &'static str| with_timeout(name, Some(1), async { Ok::<_, HandshakeError>(()) });
+
+        let (inner, outer, _) = timed(async {
+            let ((), inner, _) = timed(async {
+                phase("connect").await.unwrap();
+                phase("read").await.unwrap();
+                phase("read").await.unwrap();
+            })
+            .await;
+            phase("write").await.unwrap();
+            inner
+        })
+        .await;
+
+        assert!(inner.connect.is_some() && inner.read.is_some());
+        assert_eq!((inner.resolve, inner.tls, inner.write), (None, None, None));
+        assert!(outer.connect.is_some() && outer.read.is_some() && outer.write.is_some());
+    }
+
+    // What a handshake learnt about its connection is recorded apart from its timings, and also counts
+    // towards enclosing handshakes.
+    #[tokio::test]
+    async fn test_timed_records_connection() {
+        let address: SocketAddr = "127.0.0.1:8899".parse().unwrap();
+        let (inner, timings, outer) = timed(async {
+            let ((), _, inner) = timed(async {
+                record_connected(address);
+                record_protocol("http/1.1");
+            })
+            .await;
+            inner
+        })
+        .await;
+
+        assert_eq!(inner.connected, Some(address));
+        assert_eq!(timings, PhaseTimings::default());
+        assert_eq!(outer, inner);
+    }
+
+    // A phase that completes in time returns its own result.
+    #[tokio::test]
+    async fn test_with_timeout_passes_result_through() {
+        let result = with_timeout("read", Some(1), async { Ok::<_, HandshakeError>(42) }).await;
+        assert_eq!(result.unwrap(), 42);
+    }
+
+    // A phase that never completes fails with a dedicated timed-out error naming the phase.
+    #[tokio::test]
+    async fn test_with_timeout_reports_timed_out() {
+        let result: Result<(), HandshakeError> = with_timeout(
+            "connect",
+            Some(0),
+            std::future::pending::<Result<(), HandshakeError>>(),
+        )
+        .await;
+        match result.unwrap_err() {
+            HandshakeError::Timeout { phase, seconds } => {
+                assert_eq!(phase, "connect");
+                assert_eq!(seconds, 0);
+            }
+            other => panic!("Expected a timeout, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+// Pinning of server certificates by the SHA-256 hash of their public key (SPKI), as in HPKP.
+// Pins survive the renewal of certificates that keep their key, e.g. behind load balancers that come
+// and go, and can stand in for chain validation when nodes present self-signed certificates.
+use {
+    super::cert::spki_sha256,
+    base64::{engine::general_purpose::STANDARD, Engine},
+    rustls::{
+        client::{
+            danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+            WebPkiServerVerifier,
+        },
+        crypto::{self, CryptoProvider, WebPkiSupportedAlgorithms},
+        pki_types::{CertificateDer, ServerName, UnixTime},
+        DigitallySignedStruct, Error, SignatureScheme,
+    },
+    std::sync::Arc,
+};
+
+// Verifies that the server's certificate has one of the pinned keys, after validating its chain
+// unless the pins are trusted alone.
+#[derive(Debug)]
+pub(crate) struct PinVerifier {
+    chain: Option<Arc<WebPkiServerVerifier>>, // Validates the chain first, unless the pins are trusted alone.
+    pins: Vec<[u8; 32]>,                      // SHA-256 hashes of the public keys accepted.
+    algorithms: WebPkiSupportedAlgorithms, // Algorithms the handshake's signatures are verified with.
+}
+
+impl PinVerifier {
+    // Constructs a verifier accepting the pinned keys, validated by `chain` as well when given, and
+    // verifying the handshake's signatures with the provider's algorithms.
+    pub(crate) fn new(
+        chain: Option<Arc<WebPkiServerVerifier>>,
+        pins: Vec<[u8; 32]>,
+        provider: &CryptoProvider,
+    ) -> Self {
+        PinVerifier {
+            chain,
+            pins,
+            algorithms: provider.signature_verification_algorithms,
+        }
+    }
+}
+
+impl ServerCertVerifier for PinVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        if let Some(chain) = &self.chain {
+            chain.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        }
+        let spki = spki_sha256(end_entity)
+            .ok_or_else(|| Error::General("Invalid server certificate".to_string()))?;
+        match self.pins.contains(&spki) {
+            true => Ok(ServerCertVerified::assertion()),
+            false => Err(Error::General(format!(
+                "Public key {} matches none of the pins",
+                STANDARD.encode(spki)
+            ))),
+        }
+    }
+
+    // Pinned or not, the server must prove it holds the certificate's key.
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
@@ -0,0 +1,569 @@
+// Connection pooling for repeated handshakes against the same node.
+// Idle TCP, TLS and WebSocket connections are kept open between calls and reused,
+// avoiding a reconnect (and TLS handshake) per request; connections idle for too long are evicted.
+use {
+    super::{
+        success_body,
+        tap::Tapped,
+        tcp::{
+            http_request, open_stream, read_http_response, tls_connect, Route, TlsOptions, HTTP_1_1,
+        },
+        with_timeout,
+        ws::{rationalise_url, ws_connect, ws_exchange, WsStream},
+        Transport,
+    }, // Import the transport helpers shared with the non-pooled transports.
+    crate::{
+        error::HandshakeError,
+        solana::{http::HttpResponse, TransportType},
+    },
+    async_trait::async_trait, // Import async_trait for asynchronous trait methods.
+    std::{
+        collections::HashMap,
+        io::{Error, ErrorKind}, // Import standard IO types for unsupported transports.
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+    tokio::{
+        io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+        net::TcpStream,
+    },
+    tokio_rustls::client::TlsStream,
+    tracing::info, // Import logging macros for informational logging.
+};
+
+// Default time a connection may sit idle in the pool before it is evicted.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Default number of idle connections kept per pool key.
+const DEFAULT_MAX_IDLE_PER_KEY: usize = 4;
+
+// What came back over a connection: an HTTP response, or a WebSocket message.
+enum Reply {
+    Http(HttpResponse),
+    Ws(String),
+}
+
+// A live connection that can be reused for another request.
+enum Connection {
+    Tcp(TcpStream), // Plain TCP connection speaking HTTP/1.1 keep-alive.
+    Tls(Box<TlsStream<Tapped<TcpStream>>>), // TLS connection speaking HTTP/1.1 keep-alive.
+    Ws(Box<WsStream>), // WebSocket connection, plain or secure.
+}
+
+// An idle connection together with the moment it was returned to the pool.
+struct IdleConnection {
+    connection: Connection,
+    since: Instant,
+}
+
+// Pool key: connections are only shared between requests to the same remote over the same transport,
+// reached the same way (directly or through the same proxy) and secured with the same TLS options.
+type PoolKey = (String, TransportType, Route, TlsOptions);
+
+// Keeps idle connections alive so they can be reused across handshakes.
+// The pool is shared between nodes and transports through an `Arc`.
+pub struct ConnectionPool {
+    idle_timeout: Duration,  // How long a connection may sit idle before eviction.
+    max_idle_per_key: usize, // Maximum idle connections kept per key.
+    idle: Mutex<HashMap<PoolKey, Vec<IdleConnection>>>, // Idle connections grouped by key.
+}
+
+impl ConnectionPool {
+    // Creates a pool that evicts connections left idle for longer than `idle_timeout`.
+    pub fn new(idle_timeout: Duration) -> Self {
+        ConnectionPool {
+            idle_timeout,
+            max_idle_per_key: DEFAULT_MAX_IDLE_PER_KEY,
+            idle: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Sets the maximum number of idle connections kept per pool key.
+    pub fn with_max_idle_per_key(mut self, max_idle_per_key: usize) -> Self {
+        self.max_idle_per_key = max_idle_per_key;
+        self
+    }
+
+    // Indicates whether connections of the given transport type can be pooled.
+    pub fn supports(transport_type: &TransportType) -> bool {
+        matches!(
+            transport_type,
+            TransportType::Tcp | TransportType::Tls | TransportType::Ws | TransportType::Wss
+        )
+    }
+
+    // Drops every connection that has been idle for longer than the idle timeout.
+    pub fn evict_idle(&self) {
+        let mut idle = self.idle.lock().expect("pool lock poisoned");
+        for connections in idle.values_mut() {
+            connections.retain(|c| c.since.elapsed() < self.idle_timeout);
+        }
+        idle.retain(|_, connections| !connections.is_empty());
+    }
+
+    // Returns the number of idle connections currently held.
+    pub fn idle_count(&self) -> usize {
+        let idle = self.idle.lock().expect("pool lock poisoned");
+        idle.values().map(Vec::len).sum()
+    }
+
+    // Takes the most recently used idle connection for the key, evicting expired ones first.
+    fn checkout(&self, key: &PoolKey) -> Option<Connection> {
+        self.evict_idle();
+        let mut idle = self.idle.lock().expect("pool lock poisoned");
+        idle.get_mut(key)
+            .and_then(|connections| connections.pop())
+            .map(|c| c.connection)
+    }
+
+    // Returns a connection to the pool, unless the key already holds the maximum number of idle connections.
+    fn checkin(&self, key: PoolKey, connection: Connection) {
+        let mut idle = self.idle.lock().expect("pool lock poisoned");
+        let connections = idle.entry(key).or_default();
+        if connections.len() < self.max_idle_per_key {
+            connections.push(IdleConnection {
+                connection,
+                since: Instant::now(),
+            });
+        }
+    }
+}
+
+// A transport that draws its connections from a `ConnectionPool`.
+pub struct PooledTransport {
+    pool: Arc<ConnectionPool>,     // Shared pool of idle connections.
+    remote: String,                // The remote server's address as a string.
+    transport_type: TransportType, // Transport used when a new connection is needed.
+    route: Route,                  // How new connections reach the remote.
+    tls: TlsOptions,               // Options for new secure connections.
+}
+
+impl PooledTransport {
+    // Constructs a pooled transport for the remote and transport type.
+    pub fn new(pool: Arc<ConnectionPool>, remote: String, transport_type: TransportType) -> Self {
+        PooledTransport {
+            pool,
+            remote,
+            transport_type,
+            route: Route::default(),
+            tls: TlsOptions::default(),
+        }
+    }
+
+    // Sets how new connections reach the remote, e.g. through an HTTP proxy.
+    pub fn with_route(mut self, route: Route) -> Self {
+        self.route = route;
+        self
+    }
+
+    // Sets the options used for new secure connections.
+    pub fn with_tls(mut self, tls: TlsOptions) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    // Opens a fresh connection of the configured transport type.
+    async fn connect(&self, timeout: Option<u32>) -> Result<Connection, HandshakeError> {
+        match self.transport_type {
+            TransportType::Tcp => Ok(Connection::Tcp(
+                open_stream(&self.remote, &self.route, timeout).await?,
+            )),
+            TransportType::Tls => Ok(Connection::Tls(Box::new(
+                tls_connect(&self.remote, &self.route, &self.tls, HTTP_1_1, timeout).await?,
+            ))),
+            TransportType::Ws | TransportType::Wss => {
+                let secure = self.transport_type == TransportType::Wss;
+                let url = rationalise_url(&self.remote, secure);
+                Ok(Connection::Ws(Box::new(
+                    ws_connect(&url, &self.route, &self.tls, timeout).await?,
+                )))
+            }
+            _ => Err(HandshakeError::Connect(Error::new(
+                ErrorKind::Unsupported,
+                format!("{:?} connections cannot be pooled", self.transport_type),
+            ))),
+        }
+    }
+
+    // Performs one request/response exchange on the connection.
+    // The connection is handed back alongside the response when it can be reused.
+    async fn exchange(
+        &self,
+        connection: Connection,
+        timeout: Option<u32>,
+        payload: &serde_json::Value,
+    ) -> Result<(Reply, Option<Connection>), HandshakeError> {
+        match connection {
+            Connection::Tcp(mut stream) => {
+                let response = http_exchange(&mut stream, &self.remote, timeout, payload).await?;
+                let reusable = response.is_keep_alive().then_some(Connection::Tcp(stream));
+                Ok((Reply::Http(response), reusable))
+            }
+            Connection::Tls(mut stream) => {
+                let response = http_exchange(&mut *stream, &self.remote, timeout, payload).await?;
+                let reusable = response.is_keep_alive().then_some(Connection::Tls(stream));
+                Ok((Reply::Http(response), reusable))
+            }
+            Connection::Ws(mut stream) => {
+                let response = ws_exchange(&mut stream, timeout, payload.clone()).await?;
+                if response.is_empty() {
+                    // The stream ended before a message arrived, e.g. the server dropped it while idle.
+                    return Err(HandshakeError::WebSocket(
+                        "connection closed before a response".to_string(),
+                    ));
+                }
+                Ok((Reply::Ws(response), Some(Connection::Ws(stream))))
+            }
+        }
+    }
+
+    // Sends the payload over an idle connection if there is one, or else over a fresh connection.
+    // If the server has dropped the idle connection in the meantime, i.e. it failed or closed before
+    // a response, the request is retried once on a fresh connection.
+    async fn request(
+        &self,
+        timeout: Option<u32>,
+        payload: serde_json::Value,
+    ) -> Result<Reply, HandshakeError> {
+        let key = (
+            self.remote.clone(),
+            self.transport_type,
+            self.route.clone(),
+            self.tls.clone(),
+        );
+
+        if let Some(connection) = self.pool.checkout(&key) {
+            info!("Reusing pooled connection to {}", &self.remote);
+            match self.exchange(connection, timeout, &payload).await {
+                Ok((reply, reusable)) => {
+                    if let Some(connection) = reusable {
+                        self.pool.checkin(key, connection);
+                    }
+                    return Ok(reply);
+                }
+                // Only a connection lost before the response may be stale; the server's answer,
+                // e.g. an HTTP error status or an unparseable body, is the result of the request.
+                Err(e @ (HandshakeError::Connect(_) | HandshakeError::WebSocket(_))) => {
+                    info!("Pooled connection failed ({}), reconnecting", e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let connection = self.connect(timeout).await?;
+        info!("Opened new pooled connection to {}", &self.remote);
+        let (reply, reusable) = self.exchange(connection, timeout, &payload).await?;
+        if let Some(connection) = reusable {
+            self.pool.checkin(key, connection);
+        }
+        Ok(reply)
+    }
+}
+
+// Writes a keep-alive HTTP request and reads the framed response from the stream.
+async fn http_exchange<S>(
+    stream: &mut S,
+    remote: &str,
+    timeout: Option<u32>,
+    payload: &serde_json::Value,
+) -> Result<HttpResponse, HandshakeError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let req = http_request(remote, "/", payload, true);
+    with_timeout("write", timeout, stream.write_all(req.as_bytes())).await?;
+    info!("Sent message payload {}", &req);
+    with_timeout("read", timeout, read_http_response(stream)).await
+}
+
+// Implement the Transport trait for pooled connections, only the body of a successful HTTP response
+// or the WebSocket message being returned by `connect_and_send`.
+#[async_trait]
+impl Transport for PooledTransport {
+    async fn connect_and_send(
+        &self,
+        timeout: Option<u32>, // Optional timeout in seconds, applied to each phase.
+        payload: serde_json::Value, // JSON payload to be sent.
+    ) -> Result<String, HandshakeError> {
+        match self.request(timeout, payload).await? {
+            Reply::Http(response) => success_body(response),
+            Reply::Ws(message) => Ok(message),
+        }
+    }
+
+    // Only pooled TCP and TLS connections exchange HTTP responses.
+    async fn send_request(
+        &self,
+        timeout: Option<u32>,
+        payload: serde_json::Value,
+    ) -> Result<HttpResponse, HandshakeError> {
+        let not_http = || {
+            HandshakeError::InvalidConfig(format!(
+                "{} connections do not exchange HTTP responses",
+                self.transport_type
+            ))
+        };
+        if matches!(self.transport_type, TransportType::Ws | TransportType::Wss) {
+            return Err(not_http());
+        }
+        match self.request(timeout, payload).await? {
+            Reply::Http(response) => Ok(response),
+            Reply::Ws(_) => Err(not_http()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        futures_util::{SinkExt, StreamExt},
+        std::sync::atomic::{AtomicUsize, Ordering},
+        tokio::{io::AsyncReadExt, net::TcpListener},
+    };
+
+    // Starts a keep-alive HTTP server answering every request with `{}` and counting accepted connections.
+    async fn keep_alive_server() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = listener.local_addr().unwrap().to_string();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut buf = vec![0; 4096];
+                    while let Ok(n) = socket.read(&mut buf).await {
+                        if n == 0 {
+                            break;
+                        }
+                        let response = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}";
+                        if socket.write_all(response.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        (remote, accepted)
+    }
+
+    // Repeated requests through the pool share a single TCP connection.
+    #[tokio::test]
+    async fn test_tcp_connection_is_reused() {
+        let (remote, accepted) = keep_alive_server().await;
+        let pool = Arc::new(ConnectionPool::new(Duration::from_secs(60)));
+        let transport = PooledTransport::new(pool.clone(), remote, TransportType::Tcp);
+
+        for _ in 0..3 {
+            let response = transport
+                .connect_and_send(Some(5), serde_json::json!({}))
+                .await;
+            assert_eq!(response.unwrap(), "{}");
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 1); // Only one connection was opened.
+        assert_eq!(pool.idle_count(), 1); // It is back in the pool, ready for reuse.
+    }
+
+    // Whole HTTP responses come back over pooled connections too, which stay reusable; WebSocket
+    // connections have none to return.
+    #[tokio::test]
+    async fn test_send_request_over_pooled_connection() {
+        let (remote, accepted) = keep_alive_server().await;
+        let pool = Arc::new(ConnectionPool::new(Duration::from_secs(60)));
+        let transport = PooledTransport::new(pool.clone(), remote.clone(), TransportType::Tcp);
+
+        for _ in 0..2 {
+            let response = transport
+                .send_request(Some(5), serde_json::json!({}))
+                .await
+                .unwrap();
+            assert_eq!(
+                (response.status, response.header("content-length")),
+                (200, Some("2"))
+            );
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+
+        let ws = PooledTransport::new(pool, remote, TransportType::Ws);
+        let error = ws.send_request(Some(5), serde_json::json!({})).await;
+        assert!(matches!(error, Err(HandshakeError::InvalidConfig(_))));
+        assert_eq!(accepted.load(Ordering::SeqCst), 1); // Nothing was sent.
+    }
+
+    // A connection the server announced it would close is not kept for another request.
+    #[tokio::test]
+    async fn test_closing_connection_is_not_reused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 4096];
+            let _ = socket.read(&mut buf).await; // Consume the request.
+            let response = "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\n{}";
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        let pool = Arc::new(ConnectionPool::new(Duration::from_secs(60)));
+        let transport = PooledTransport::new(pool.clone(), remote, TransportType::Tcp);
+
+        let response = transport
+            .connect_and_send(Some(5), serde_json::json!({}))
+            .await;
+        assert_eq!(response.unwrap(), "{}");
+        assert_eq!(pool.idle_count(), 0);
+    }
+
+    // Connections idle for longer than the idle timeout are evicted instead of reused.
+    #[tokio::test]
+    async fn test_idle_connections_are_evicted() {
+        let (remote, accepted) = keep_alive_server().await;
+        let pool = Arc::new(ConnectionPool::new(Duration::ZERO));
+        let transport = PooledTransport::new(pool.clone(), remote, TransportType::Tcp);
+
+        for _ in 0..2 {
+            transport
+                .connect_and_send(Some(5), serde_json::json!({}))
+                .await
+                .unwrap();
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 2); // The idle connection was not reused.
+
+        pool.evict_idle();
+        assert_eq!(pool.idle_count(), 0);
+    }
+
+    // Transports reaching the same remote with different options never share a connection.
+    #[tokio::test]
+    async fn test_differently_configured_transports_do_not_share_connections() {
+        let (remote, accepted) = keep_alive_server().await;
+        let pool = Arc::new(ConnectionPool::new(Duration::from_secs(60)));
+        let plain = PooledTransport::new(pool.clone(), remote.clone(), TransportType::Tcp);
+        let pinned =
+            PooledTransport::new(pool.clone(), remote, TransportType::Tcp).with_tls(TlsOptions {
+                pins: vec![[0; 32]],
+                ..TlsOptions::default()
+            });
+
+        for transport in [&plain, &pinned, &plain, &pinned] {
+            transport
+                .connect_and_send(Some(5), serde_json::json!({}))
+                .await
+                .unwrap();
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 2); // One connection per configuration.
+        assert_eq!(pool.idle_count(), 2);
+    }
+
+    // Repeated requests through the pool share a single WebSocket connection.
+    #[tokio::test]
+    async fn test_ws_connection_is_reused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = listener.local_addr().unwrap().to_string();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+                    while let Some(Ok(message)) = ws.next().await {
+                        if message.is_text() && ws.send(message).await.is_err() {
+                            break; // Echo text frames back to the client.
+                        }
+                    }
+                });
+            }
+        });
+
+        let pool = Arc::new(ConnectionPool::new(Duration::from_secs(60)));
+        let transport = PooledTransport::new(pool, remote, TransportType::Ws);
+        for i in 0..3 {
+            let response = transport
+                .connect_and_send(Some(5), serde_json::json!({ "id": i }))
+                .await;
+            assert_eq!(response.unwrap(), format!("{{\"id\":{}}}", i));
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+    }
+
+    // Starts a server answering the requests on each connection with the given responses in turn,
+    // closing the connection after the last one, and counting accepted connections and requests.
+    async fn scripted_server(
+        responses: &'static [&'static str],
+    ) -> (String, Arc<AtomicUsize>, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = listener.local_addr().unwrap().to_string();
+        let (accepted, requests) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let (connections, received) = (accepted.clone(), requests.clone());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                connections.fetch_add(1, Ordering::SeqCst);
+                let received = received.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0; 4096];
+                    for response in responses {
+                        match socket.read(&mut buf).await {
+                            Ok(n) if n > 0 => received.fetch_add(1, Ordering::SeqCst),
+                            _ => break,
+                        };
+                        if socket.write_all(response.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        (remote, accepted, requests)
+    }
+
+    // A pooled connection the server dropped while idle is replaced by a fresh one.
+    #[tokio::test]
+    async fn test_dropped_connection_is_replaced() {
+        let (remote, accepted, _) =
+            scripted_server(&["HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}"]).await;
+        let pool = Arc::new(ConnectionPool::new(Duration::from_secs(60)));
+        let transport = PooledTransport::new(pool, remote, TransportType::Tcp);
+
+        for _ in 0..2 {
+            let response = transport
+                .connect_and_send(Some(5), serde_json::json!({}))
+                .await;
+            assert_eq!(response.unwrap(), "{}");
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 2); // The dropped connection was replaced.
+    }
+
+    // An error status on a reused connection is the server's answer, so it is not retried.
+    #[tokio::test]
+    async fn test_error_status_is_not_retried() {
+        let (remote, accepted, requests) = scripted_server(&[
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}",
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 4\r\n\r\nbusy",
+        ])
+        .await;
+        let pool = Arc::new(ConnectionPool::new(Duration::from_secs(60)));
+        let transport = PooledTransport::new(pool, remote, TransportType::Tcp);
+
+        transport
+            .connect_and_send(Some(5), serde_json::json!({}))
+            .await
+            .unwrap();
+        let error = transport
+            .connect_and_send(Some(5), serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, HandshakeError::Protocol(message) if message.contains("503")));
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+        assert_eq!(requests.load(Ordering::SeqCst), 2); // The request was not sent again.
+    }
+
+    // Transports that manage their own connections cannot be pooled.
+    #[test]
+    fn test_supports() {
+        assert!(ConnectionPool::supports(&TransportType::Tls));
+        assert!(!ConnectionPool::supports(&TransportType::Quic));
+    }
+}
@@ -0,0 +1,202 @@
+// Import the Transport trait from the parent module for polymorphic use across different transport implementations.
+// Grouped import for clarity and organization.
+use {
+    super::{record_protocol, with_timeout, Transport}, // Import the Transport trait and the per-phase helpers.
+    crate::error::HandshakeError,                      // Import the crate-wide error type.
+    async_trait::async_trait, // Import async_trait for asynchronous trait methods.
+    quinn::{ClientConfig, ConnectionError, Endpoint}, // Import the QUIC client types.
+    rustls_quic::{
+        client::{ServerCertVerified, ServerCertVerifier}, // Import the verifier hooks for server certificates.
+        Certificate,
+        PrivateKey,
+        ServerName,
+    },
+    std::{
+        io::{Error, ErrorKind}, // Import standard IO types for connection errors.
+        net::SocketAddr,
+        sync::Arc,        // Import Arc for thread-safe reference counting.
+        time::SystemTime, // Import SystemTime for the verifier signature.
+    },
+    tracing::info, // Import logging macros for informational logging.
+};
+
+// ALPN protocol identifier advertised by Solana TPU QUIC servers, offered unless other protocols are configured.
+pub const SOLANA_TPU_ALPN: &str = "solana-tpu";
+
+// Server name used by Solana QUIC clients; the TPU does not validate it.
+const SOLANA_TPU_SERVER_NAME: &str = "connect";
+
+// Define the Quic struct representing a QUIC transport to a validator's TPU port.
+pub struct Quic {
+    remote: String,    // The remote validator's TPU address as a string.
+    alpn: Vec<String>, // Protocols offered through ALPN, most preferred first.
+}
+
+// Implementation block for Quic.
+impl Quic {
+    // Constructs a new Quic instance with the specified remote address.
+    pub fn new(remote: String) -> Self {
+        Quic {
+            remote,
+            alpn: vec![SOLANA_TPU_ALPN.to_string()],
+        }
+    }
+
+    // Sets the protocols offered through ALPN, replacing the TPU's.
+    pub fn with_alpn(mut self, alpn: Vec<String>) -> Self {
+        self.alpn = alpn;
+        self
+    }
+}
+
+// Certificate verifier that accepts any server certificate.
+// Validators present self-signed certificates derived from their identity keypair,
+// so there is no CA chain to validate against.
+struct SkipServerVerification;
+
+impl ServerCertVerifier for SkipServerVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls_quic::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+// Creates the QUIC client configuration used to reach a Solana TPU.
+// The TPU requires a client certificate, so an ephemeral self-signed one is generated per connection;
+// the validator treats such connections as unstaked.
+fn create_quic_config(alpn: &[String]) -> Result<ClientConfig, Box<dyn std::error::Error>> {
+    // Generate an ephemeral self-signed client certificate.
+    let cert = rcgen::generate_simple_self_signed(vec![SOLANA_TPU_SERVER_NAME.to_string()])?;
+    let cert_der = Certificate(cert.serialize_der()?);
+    let key_der = PrivateKey(cert.serialize_private_key_der());
+
+    // Build a TLS config that skips server verification and advertises the ALPN protocols.
+    let mut crypto = rustls_quic::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(SkipServerVerification))
+        .with_client_auth_cert(vec![cert_der], key_der)?;
+    crypto.alpn_protocols = alpn
+        .iter()
+        .map(|protocol| protocol.as_bytes().to_vec())
+        .collect();
+
+    Ok(ClientConfig::new(Arc::new(crypto)))
+}
+
+// Maps a failed QUIC handshake to a handshake error.
+// Transport errors carry the TLS alerts raised during the handshake; anything else means the connection failed.
+fn convert_error(error: ConnectionError) -> HandshakeError {
+    match error {
+        ConnectionError::TransportError(e) => {
+            HandshakeError::Tls(format!("QUIC handshake failed: {}", e))
+        }
+        e => HandshakeError::Connect(Error::new(
+            ErrorKind::ConnectionRefused,
+            format!("QUIC handshake failed: {}", e),
+        )),
+    }
+}
+
+// Asynchronously establishes a QUIC connection to the remote TPU and closes it cleanly.
+async fn quic_connect(
+    remote: &str,         // Remote TPU address.
+    alpn: &[String],      // Protocols offered through ALPN.
+    timeout: Option<u32>, // Optional timeout in seconds, applied to the QUIC handshake.
+) -> Result<String, HandshakeError> {
+    // Parse the remote address; QUIC connects directly to a socket address.
+    let addr: SocketAddr = remote
+        .parse()
+        .map_err(|_| HandshakeError::Dns(format!("Invalid socket address {}", remote)))?;
+
+    // Create the QUIC client configuration or return an error.
+    let config = create_quic_config(alpn)
+        .map_err(|e| HandshakeError::Tls(format!("Unable to create QUIC config: {}", e)))?;
+
+    // Bind a client endpoint on an ephemeral port of the matching address family.
+    let bind_addr: SocketAddr = match addr {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    }
+    .parse()
+    .expect("valid bind address");
+    let mut endpoint = Endpoint::client(bind_addr)?;
+    endpoint.set_default_client_config(config);
+
+    // Perform the QUIC handshake with the remote TPU.
+    let connecting = endpoint
+        .connect(addr, SOLANA_TPU_SERVER_NAME)
+        .map_err(|e| {
+            HandshakeError::Connect(Error::new(
+                ErrorKind::InvalidInput,
+                format!("QUIC connect error: {}", e),
+            ))
+        })?;
+    let connection = with_timeout("quic handshake", timeout, async {
+        connecting.await.map_err(convert_error)
+    })
+    .await?;
+    info!("Connected to remote quic endpoint {}", remote);
+
+    // Report the negotiated ALPN protocol, if the server selected one.
+    let alpn = connection
+        .handshake_data()
+        .and_then(|data| data.downcast::<quinn::crypto::rustls::HandshakeData>().ok())
+        .and_then(|data| data.protocol)
+        .map(|p| String::from_utf8_lossy(&p).to_string());
+    if let Some(alpn) = &alpn {
+        record_protocol(alpn);
+    }
+    let alpn = alpn.unwrap_or_else(|| "none".to_string());
+    let response = format!(
+        "QUIC connection established with {} (alpn: {})",
+        connection.remote_address(),
+        alpn
+    );
+
+    // Close the connection gracefully and wait for the endpoint to drain.
+    connection.close(0u32.into(), b"handshake complete");
+    endpoint.wait_idle().await;
+
+    info!("Received message was {}", &response);
+    Ok(response)
+}
+
+// Implement the Transport trait for the Quic struct.
+// The TPU only accepts transactions, so the payload is not sent; a completed QUIC handshake is the response.
+#[async_trait]
+impl Transport for Quic {
+    async fn connect_and_send(
+        &self,                       // Reference to self for method invocation on an instance.
+        timeout: Option<u32>,        // Optional timeout in seconds, applied to the QUIC handshake.
+        _payload: serde_json::Value, // Unused: the TPU does not speak JSON.
+    ) -> Result<String, HandshakeError> {
+        quic_connect(&self.remote, &self.alpn, timeout).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ensures the QUIC client configuration can be built with the ephemeral client certificate.
+    #[test]
+    fn test_create_quic_config() {
+        assert!(create_quic_config(&[SOLANA_TPU_ALPN.to_string()]).is_ok());
+    }
+
+    // An address that is not a socket address should be rejected before any network activity.
+    #[tokio::test]
+    async fn test_invalid_remote_is_rejected() {
+        let result = Quic::new("not-an-address".to_string())
+            .connect_and_send(None, serde_json::json!({}))
+            .await;
+        assert!(matches!(result.unwrap_err(), HandshakeError::Dns(_)));
+    }
+}
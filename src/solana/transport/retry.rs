@@ -0,0 +1,110 @@
+// Retrying of flaky handshakes: a failed attempt is repeated after a delay, until one succeeds or
+// the retries run out. The result is always the last attempt's.
+use {
+    crate::error::HandshakeError, // Import the crate-wide error type of failed attempts.
+    std::{fmt::Display, future::Future, time::Duration}, // Import the attempts, the delay between them and the remote they reach.
+    tracing::debug, // Import logging of each attempt's outcome.
+};
+
+// Default delay between attempts, in milliseconds.
+pub const DEFAULT_RETRY_DELAY_MILLIS: u64 = 1000;
+
+// How many times a failed handshake is attempted again, and how long to wait before each retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: u32,    // Attempts made after the first one fails.
+    pub delay: Duration, // Wait before each retry.
+}
+
+impl RetryPolicy {
+    // Constructs a policy retrying the given number of times with the given delay.
+    pub fn new(retries: u32, delay: Duration) -> Self {
+        RetryPolicy { retries, delay }
+    }
+
+    // Runs the attempt until it succeeds or the retries run out, logging the outcome of each attempt
+    // with the remote it was made to.
+    pub async fn run<T, F, Fut>(
+        &self,
+        remote: impl Display,
+        mut attempt: F,
+    ) -> Result<T, HandshakeError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, HandshakeError>>,
+    {
+        let attempts = self.retries + 1;
+        let mut number = 1;
+        loop {
+            match attempt().await {
+                Ok(value) => {
+                    debug!(%remote, attempt = number, attempts, "Attempt succeeded");
+                    return Ok(value);
+                }
+                Err(e) if number < attempts => {
+                    debug!(
+                        %remote,
+                        attempt = number,
+                        attempts,
+                        error = %e,
+                        "Attempt failed, retrying in {:?}",
+                        self.delay
+                    );
+                    tokio::time::sleep(self.delay).await;
+                    number += 1;
+                }
+                Err(e) => {
+                    debug!(%remote, attempt = number, attempts, error = %e, "Attempt failed");
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+// Without retries, the first attempt is the only one.
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(0, Duration::from_millis(DEFAULT_RETRY_DELAY_MILLIS))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::cell::Cell, test_case::test_case};
+
+    // Fails the given number of times, then succeeds with the number of the attempt.
+    async fn flaky(attempts: &Cell<u32>, failures: u32) -> Result<u32, HandshakeError> {
+        let attempt = attempts.get() + 1;
+        attempts.set(attempt);
+        match attempt <= failures {
+            true => Err(HandshakeError::Protocol(format!("attempt {}", attempt))),
+            false => Ok(attempt),
+        }
+    }
+
+    // Attempts stop at the first success, or once the retries run out with the last error.
+    #[test_case(0, 0, Ok(1); "when first attempt succeeds")]
+    #[test_case(2, 1, Ok(2); "when a retry succeeds")]
+    #[test_case(2, 3, Err(3); "when every attempt fails")]
+    #[test_case(0, 1, Err(1); "when there are no retries")]
+    #[tokio::test]
+    async fn test_run(retries: u32, failures: u32, expected: Result<u32, u32>) {
+        let attempts = Cell::new(0);
+        let policy = RetryPolicy::new(retries, Duration::from_millis(1));
+        let result = policy
+            .run("127.0.0.1:8899", || flaky(&attempts, failures))
+            .await;
+        match (result, expected) {
+            (Ok(attempt), Ok(expected)) => assert_eq!(attempt, expected),
+            (Err(e), Err(expected)) => {
+                assert_eq!(
+                    e.to_string(),
+                    format!("Protocol error: attempt {}", expected)
+                );
+                assert_eq!(attempts.get(), expected);
+            }
+            (result, expected) => panic!("Expected {:?}, got {:?}", expected, result),
+        }
+    }
+}
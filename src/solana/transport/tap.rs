@@ -0,0 +1,240 @@
+// Taps the bytes a TLS server sends during the handshake, to read the key exchange group it selected,
+// which rustls does not expose. The group is in the clear: in the ServerHello's key share with TLS 1.3,
+// and in the ServerKeyExchange with TLS 1.2.
+use {
+    std::{
+        io,
+        pin::Pin,
+        task::{Context, Poll},
+    },
+    tokio::io::{AsyncRead, AsyncWrite, ReadBuf},
+};
+
+// Bytes recorded at most, enough for the ServerHello and a TLS 1.2 certificate chain before the
+// ServerKeyExchange.
+const TAP_LIMIT: usize = 64 * 1024;
+
+// TLS record and handshake message types, and the key share extension, as assigned by IANA.
+const CHANGE_CIPHER_SPEC: u8 = 20;
+const HANDSHAKE: u8 = 22;
+const SERVER_HELLO: u8 = 2;
+const SERVER_KEY_EXCHANGE: u8 = 12;
+const KEY_SHARE: u16 = 0x0033;
+const NAMED_CURVE: u8 = 3;
+
+// A stream recording what it reads until told to stop, and passing everything through.
+pub struct Tapped<S> {
+    inner: S,                  // The stream tapped.
+    received: Option<Vec<u8>>, // Bytes read so far, until the tap is removed.
+}
+
+impl<S> Tapped<S> {
+    // Taps the stream from its first byte.
+    pub fn new(inner: S) -> Self {
+        Tapped {
+            inner,
+            received: Some(Vec::new()),
+        }
+    }
+
+    // Stops recording, returning the bytes read so far.
+    pub fn untap(&mut self) -> Vec<u8> {
+        self.received.take().unwrap_or_default()
+    }
+
+    // The stream tapped.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Tapped<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Some(received) = &mut self.received {
+            let read = &buf.filled()[filled..];
+            let room = TAP_LIMIT.saturating_sub(received.len());
+            received.extend_from_slice(&read[..read.len().min(room)]);
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Tapped<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+// Reads the key exchange group selected by the server from the bytes it sent during the handshake.
+// Only the plaintext handshake records are read; with TLS 1.3 everything after the ServerHello is encrypted.
+pub fn key_exchange_group(received: &[u8]) -> Option<u16> {
+    // Reassemble the handshake messages, which may span records.
+    let mut handshake = Vec::new();
+    let mut records = received;
+    while let [kind, _, _, high, low, rest @ ..] = records {
+        let length = u16::from_be_bytes([*high, *low]) as usize;
+        let Some(fragment) = rest.get(..length) else {
+            break;
+        };
+        match *kind {
+            HANDSHAKE => handshake.extend_from_slice(fragment),
+            CHANGE_CIPHER_SPEC => {}
+            _ => break,
+        }
+        records = &rest[length..];
+    }
+
+    let mut group = None;
+    let mut messages = handshake.as_slice();
+    while let [kind, a, b, c, rest @ ..] = messages {
+        let length = u32::from_be_bytes([0, *a, *b, *c]) as usize;
+        let Some(body) = rest.get(..length) else {
+            break;
+        };
+        match *kind {
+            SERVER_HELLO => group = server_hello_group(body).or(group),
+            SERVER_KEY_EXCHANGE => {
+                if let [NAMED_CURVE, high, low, ..] = body {
+                    group = Some(u16::from_be_bytes([*high, *low]));
+                }
+            }
+            _ => {}
+        }
+        messages = &rest[length..];
+    }
+    group
+}
+
+// Reads the group of the key share extension of a ServerHello, which only TLS 1.3 servers send.
+fn server_hello_group(body: &[u8]) -> Option<u16> {
+    // Skip the version and random, then the session id, cipher suite and compression method.
+    let session_id = *body.get(34)? as usize;
+    let mut extensions = body.get(35 + session_id + 3..)?;
+    let length = u16::from_be_bytes([*extensions.first()?, *extensions.get(1)?]) as usize;
+    extensions = extensions.get(2..2 + length)?;
+    while let [a, b, c, d, rest @ ..] = extensions {
+        let kind = u16::from_be_bytes([*a, *b]);
+        let length = u16::from_be_bytes([*c, *d]) as usize;
+        let data = rest.get(..length)?;
+        if let (KEY_SHARE, [high, low, ..]) = (kind, data) {
+            return Some(u16::from_be_bytes([*high, *low]));
+        }
+        extensions = &rest[length..];
+    }
+    None
+}
+
+// The name of a key exchange group, as registered with IANA, or its code point when unknown.
+pub fn group_name(group: u16) -> String {
+    match group {
+        0x0017 => "secp256r1".to_string(),
+        0x0018 => "secp384r1".to_string(),
+        0x0019 => "secp521r1".to_string(),
+        0x001d => "X25519".to_string(),
+        0x001e => "X448".to_string(),
+        0x0100 => "ffdhe2048".to_string(),
+        0x0101 => "ffdhe3072".to_string(),
+        0x0102 => "ffdhe4096".to_string(),
+        0x0103 => "ffdhe6144".to_string(),
+        0x0104 => "ffdhe8192".to_string(),
+        0x11ec => "X25519MLKEM768".to_string(),
+        other => format!("0x{:04x}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, test_case::test_case};
+
+    // Wraps handshake messages into a single plaintext record.
+    fn record(kind: u8, messages: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        let mut fragment = Vec::new();
+        for (message, body) in messages {
+            fragment.push(*message);
+            fragment.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+            fragment.extend_from_slice(body);
+        }
+        let mut record = vec![kind, 3, 3];
+        record.extend_from_slice(&(fragment.len() as u16).to_be_bytes());
+        record.extend_from_slice(&fragment);
+        record
+    }
+
+    // A ServerHello with the given extensions and a session id of 32 bytes.
+    fn server_hello(extensions: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut body = vec![3, 3];
+        body.extend_from_slice(&[0; 32]); // Random.
+        body.push(32);
+        body.extend_from_slice(&[0; 32]); // Session id.
+        body.extend_from_slice(&[0x13, 0x01, 0]); // Cipher suite and compression method.
+        let mut encoded = Vec::new();
+        for (kind, data) in extensions {
+            encoded.extend_from_slice(&kind.to_be_bytes());
+            encoded.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            encoded.extend_from_slice(data);
+        }
+        body.extend_from_slice(&(encoded.len() as u16).to_be_bytes());
+        body.extend_from_slice(&encoded);
+        body
+    }
+
+    // The group is read from the key share of TLS 1.3 or the ServerKeyExchange of TLS 1.2, and is
+    // missing from anything else.
+    #[test]
+    fn test_key_exchange_group() {
+        let supported_versions = (0x002b, vec![3, 4]);
+        let key_share = (KEY_SHARE, vec![0x00, 0x1d, 0, 32, 1, 2, 3]);
+        let tls13 = [
+            record(
+                HANDSHAKE,
+                &[(SERVER_HELLO, server_hello(&[supported_versions, key_share]))],
+            ),
+            vec![CHANGE_CIPHER_SPEC, 3, 3, 0, 1, 1],
+            vec![23, 3, 3, 0, 2, 0xff, 0xff], // Encrypted extensions.
+        ]
+        .concat();
+        assert_eq!(key_exchange_group(&tls13), Some(0x001d));
+
+        // The ServerHello and the ServerKeyExchange may come in separate records.
+        let tls12 = [
+            record(HANDSHAKE, &[(SERVER_HELLO, server_hello(&[]))]),
+            record(HANDSHAKE, &[(11, vec![0; 100])]), // Certificate.
+            record(
+                HANDSHAKE,
+                &[(SERVER_KEY_EXCHANGE, vec![NAMED_CURVE, 0x00, 0x17, 65])],
+            ),
+        ]
+        .concat();
+        assert_eq!(key_exchange_group(&tls12), Some(0x0017));
+
+        // A handshake cut short, or no handshake at all, has no group.
+        assert_eq!(key_exchange_group(&tls12[..tls12.len() - 2]), None);
+        assert_eq!(key_exchange_group(b"HTTP/1.1 400 Bad Request\r\n"), None);
+    }
+
+    // Known groups are named, others shown as their code point.
+    #[test_case(0x001d, "X25519"; "when group is x25519")]
+    #[test_case(0x0017, "secp256r1"; "when group is p256")]
+    #[test_case(0x0a0a, "0x0a0a"; "when group is unknown")]
+    fn test_group_name(group: u16, name: &str) {
+        assert_eq!(group_name(group), name);
+    }
+}
@@ -1,177 +1,1381 @@
 // Import the Transport trait from the parent module for polymorphic use across different transport implementations.
 // Import the async_trait macro to enable async function definitions in traits, which is not natively supported in Rust.
 use tokio::net::TcpStream; // Import the TcpStream struct from the tokio asynchronous runtime for handling TCP operations.
-// Grouped import for clarity and organization.
+                           // Grouped import for clarity and organization.
 use {
-    super::Transport, // Import the Transport trait for implementing custom transport logic.
+    super::{
+        cert::CertificateInfo,
+        pin::PinVerifier,
+        proxy::HttpProxy,
+        record_certificates, record_connected, record_early_data, record_protocol, record_tls,
+        success_body,
+        tap::{self, Tapped},
+        with_timeout, Transport,
+    }, // Import the Transport trait, the HTTP proxy, certificate inspection, the handshake tap and the per-phase helpers.
+    crate::{
+        error::HandshakeError,
+        solana::{http::HttpResponse, target::Target},
+    }, // Import the crate error type, the HTTP response parser and the targets routes are built for.
     async_trait::async_trait, // Import async_trait for asynchronous trait methods.
-    tracing::info,    // Import logging macros for structured error and informational logging.
+    futures_util::stream::{FuturesUnordered, StreamExt}, // Import the stream of racing connection attempts.
+    tracing::info, // Import logging macros for structured error and informational logging.
 };
 // Grouped import for TLS configuration and asynchronous IO operations.
 use {
-    rustls::ServerName, // Import ServerName for DNS name validation in TLS connections.
+    rustls::{pki_types::ServerName, ProtocolVersion}, // Import the TLS versions reported and ServerName for DNS name validation.
     std::{
-        io::{self, Error, ErrorKind}, // Import standard IO types for error handling.
-        sync::Arc,                    // Import Arc for thread-safe reference counting.
+        collections::HashMap, // Import HashMap for the shared configurations, by their options.
+        fs::File,             // Import File for reading CA bundles.
+        io,                   // Import standard IO types for error handling.
+        net::SocketAddr,      // Import SocketAddr for resolved remote addresses.
+        path::{Path, PathBuf}, // Import path types for the location of CA bundles.
+        sync::{Arc, Mutex, OnceLock}, // Import Arc for thread-safe reference counting, and OnceLock and Mutex for the shared configurations.
+        time::{Duration, Instant}, // Import Duration for the delay between connection attempts, and Instant for timing early data.
     },
-    tokio::io::{AsyncReadExt, AsyncWriteExt}, // Import extensions for asynchronous reading and writing.
+    tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt}, // Import extensions for asynchronous reading and writing.
     tokio_rustls::{
-        rustls::{ClientConfig, RootCertStore}, // Import TLS types for configuration.
-        TlsConnector, // Import TlsConnector for initiating TLS connections.
+        client::TlsStream, // Import the client-side TLS stream type.
+        rustls::{
+            client::{danger::ServerCertVerifier, WebPkiServerVerifier},
+            crypto::CryptoProvider,
+            pki_types::{CertificateDer, PrivateKeyDer},
+            ClientConfig, RootCertStore,
+        }, // Import TLS types for configuration.
+        TlsConnector,      // Import TlsConnector for initiating TLS connections.
     },
     webpki_roots::TLS_SERVER_ROOTS, // Import TLS server root certificates for trusted CA validation.
 };
 
-// Define a constant for the default TCP timeout duration in seconds.
-const TCP_TIMEOUT_SECONDS: u32 = 60;
+// Configurations of connections attempting early data, shared by those with the same options so a repeat
+// connection to the same server can resume a session and send its request along with the ClientHello;
+// rustls only resumes the sessions of the verifier that verified them.
+static EARLY_DATA_CONFIGS: OnceLock<Mutex<HashMap<TlsOptions, Arc<ClientConfig>>>> =
+    OnceLock::new();
+
+// Roots of the platform's certificate store, loaded once as reading it takes a while; none when it could not
+// be read or holds no usable certificates.
+#[cfg(feature = "native-roots")]
+static NATIVE_ROOTS: OnceLock<Option<RootCertStore>> = OnceLock::new();
+
+// ALPN id of HTTP/1.1, the protocol the raw, pooled and WebSocket transports speak over TLS.
+pub(crate) const HTTP_1_1: &str = "http/1.1";
+
+// Delay before starting the next connection attempt while earlier ones are still pending, as RFC 8305 recommends.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+// Options for TLS connections, shared by the TLS and WSS transports.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct TlsOptions {
+    pub ca_file: Option<PathBuf>, // PEM bundle of trusted roots, replacing the webpki roots.
+    pub client_cert: Option<PathBuf>, // PEM certificate chain presented for mutual TLS.
+    pub client_key: Option<PathBuf>, // PEM private key of the client certificate.
+    pub server_name: Option<String>, // Name sent as SNI and validated, instead of the remote host.
+    pub alpn: Vec<String>, // Protocols offered through ALPN, most preferred first; none when empty.
+    pub warn_cert_expiry: Option<u32>, // Days before expiry within which the server certificate fails the handshake.
+    pub pins: Vec<[u8; 32]>, // SHA-256 hashes of the public keys the server certificate must have one of; any when empty.
+    pub pin_only: bool,      // Trusts the pins alone, without validating the certificate chain.
+    pub early_data: bool, // Sends requests as TLS 1.3 early data (0-RTT) when resuming a session allowing it.
+}
+
+// How TCP connections reach the remote: directly, or tunnelled through an HTTP proxy.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Route {
+    pub addresses: Vec<SocketAddr>, // Addresses raced for a direct connection; when empty, the remote is resolved.
+    pub proxy: Option<HttpProxy>, // Optional HTTP proxy the connection is tunnelled through instead.
+}
+
+// How the TCP-based connections to a node are made: the HTTP proxy they are tunnelled through, if any, and
+// the options of those secured with TLS. Nodes, scanners and services embed it and implement `Connectable`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct ConnectOptions {
+    pub proxy: Option<HttpProxy>, // Optional HTTP proxy the connections are tunnelled through.
+    pub tls: TlsOptions, // Options of TLS and WSS connections; unused by protocols securing TCP themselves.
+}
+
+impl ConnectOptions {
+    // How the connections reach a remote: racing the addresses its target resolved to, or through the proxy.
+    pub fn route(&self, remote: &Target) -> Route {
+        Route {
+            addresses: remote.resolved.clone(),
+            proxy: self.proxy.clone(),
+        }
+    }
+}
+
+// Anything whose connections are made with `ConnectOptions`, sharing the builder methods that set them.
+pub trait Connectable: Sized {
+    // The options connections are made with.
+    fn connect_options(&mut self) -> &mut ConnectOptions;
+
+    // Replaces all the options, e.g. with those given on the command line.
+    fn with_connect(mut self, options: ConnectOptions) -> Self {
+        *self.connect_options() = options;
+        self
+    }
+
+    // Tunnels the TCP-based connections through an HTTP proxy.
+    fn with_proxy(mut self, proxy: Option<HttpProxy>) -> Self {
+        self.connect_options().proxy = proxy;
+        self
+    }
+
+    // Sets the options of TLS and WSS connections.
+    fn with_tls(mut self, tls: TlsOptions) -> Self {
+        self.connect_options().tls = tls;
+        self
+    }
+}
 
 // Define the Tcp struct representing a TCP transport layer with a remote address and security preference.
 pub struct Tcp {
     remote: String,  // The remote server's address as a string.
     is_secure: bool, // Flag indicating whether to use secure WebSocket (WSS) or not.
+    route: Route,    // How the connection reaches the remote.
+    tls: TlsOptions, // Options of the TLS connection, used when secure.
+    path: String,    // Path requests are posted to.
 }
 
 // Implementation block for Tcp.
 impl Tcp {
     // Constructs a new Tcp instance with the specified remote address and security preference.
     pub fn new(remote: String, is_secure: bool) -> Self {
-        Tcp { remote, is_secure }
+        Tcp {
+            remote,
+            is_secure,
+            route: Route::default(),
+            tls: TlsOptions::default(),
+            path: "/".to_string(),
+        }
+    }
+
+    // Sets how the connection reaches the remote, e.g. through an HTTP proxy.
+    pub fn with_route(mut self, route: Route) -> Self {
+        self.route = route;
+        self
+    }
+
+    // Sets the options used for secure connections.
+    pub fn with_tls(mut self, tls: TlsOptions) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    // Posts requests to the given path instead of the root, e.g. a webhook's.
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
     }
 }
 
+// The cryptography TLS connections are made with: aws-lc-rs with the `crypto-aws-lc` feature, ring otherwise.
+pub(crate) fn crypto_provider() -> Arc<CryptoProvider> {
+    #[cfg(feature = "crypto-aws-lc")]
+    return Arc::new(tokio_rustls::rustls::crypto::aws_lc_rs::default_provider());
+    #[cfg(not(feature = "crypto-aws-lc"))]
+    Arc::new(tokio_rustls::rustls::crypto::ring::default_provider())
+}
+
 // Creates a TLS configuration for secure TCP connections.
-fn create_tls_config() -> Result<ClientConfig, Box<dyn std::error::Error>> {
+fn create_tls_config(options: &TlsOptions) -> Result<ClientConfig, Box<dyn std::error::Error>> {
+    // Trust the configured CA bundle, or the default roots when there is none.
+    let root_store = match &options.ca_file {
+        Some(path) => load_root_store(path)?,
+        None => default_root_store(),
+    };
+
+    // Create a ClientConfig validating the chain against the populated root store for TLS connections.
+    // With pins, the server certificate must also have one of the pinned keys, or only that when the
+    // pins are trusted alone.
+    let provider = crypto_provider();
+    let chain = WebPkiServerVerifier::builder_with_provider(Arc::new(root_store), provider.clone())
+        .build()?;
+    let verifier: Arc<dyn ServerCertVerifier> = match (options.pins.is_empty(), options.pin_only) {
+        (true, _) => chain,
+        (false, false) => Arc::new(PinVerifier::new(
+            Some(chain),
+            options.pins.clone(),
+            &provider,
+        )),
+        (false, true) => Arc::new(PinVerifier::new(None, options.pins.clone(), &provider)),
+    };
+    let builder = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier);
+
+    // Present the client certificate to servers requiring mutual TLS, if one is configured.
+    let mut config = match (&options.client_cert, &options.client_key) {
+        (Some(cert), Some(key)) => {
+            builder.with_client_auth_cert(load_certs(cert)?, load_key(key)?)?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        _ => return Err("A client certificate and key must be given together".into()),
+    };
+
+    // Offer the application protocols, if any, e.g. h2 for gRPC.
+    config.alpn_protocols = options
+        .alpn
+        .iter()
+        .map(|protocol| protocol.as_bytes().to_vec())
+        .collect();
+
+    config.enable_early_data = options.early_data;
+    Ok(config)
+}
+
+// The TLS configuration of a connection. Connections attempting early data share theirs, so repeat connections
+// can resume their sessions; others get a configuration and session cache of their own, so each handshake is a
+// full one, as benchmarks expect.
+fn tls_config(options: &TlsOptions) -> Result<Arc<ClientConfig>, Box<dyn std::error::Error>> {
+    if !options.early_data {
+        return Ok(Arc::new(create_tls_config(options)?));
+    }
+    let mut configs = EARLY_DATA_CONFIGS
+        .get_or_init(Default::default)
+        .lock()
+        .expect("TLS configurations lock poisoned");
+    if let Some(config) = configs.get(options) {
+        return Ok(config.clone());
+    }
+    let config = Arc::new(create_tls_config(options)?);
+    configs.insert(options.clone(), config.clone());
+    Ok(config)
+}
+
+// Builds the root store trusted when no CA bundle is configured: the platform's certificate store with the
+// `native-roots` feature, so CAs installed by the system or an enterprise are honored, and the webpki roots
+// otherwise, or when the platform's store holds no usable certificates.
+fn default_root_store() -> RootCertStore {
+    #[cfg(feature = "native-roots")]
+    if let Some(root_store) = NATIVE_ROOTS.get_or_init(native_root_store) {
+        return root_store.clone();
+    }
+    webpki_root_store()
+}
+
+// Builds a root store from the platform's certificate store, skipping certificates rustls cannot parse.
+#[cfg(feature = "native-roots")]
+fn native_root_store() -> Option<RootCertStore> {
+    let certs = match rustls_native_certs::load_native_certs() {
+        Ok(certs) => certs,
+        Err(e) => {
+            tracing::warn!(
+                "Unable to load the platform's certificate store, trusting the webpki roots: {}",
+                e
+            );
+            return None;
+        }
+    };
+    let mut root_store = RootCertStore::empty();
+    let (added, skipped) = root_store.add_parsable_certificates(certs);
+    info!(
+        "Trusting {} certificates of the platform's store, skipped {}",
+        added, skipped
+    );
+    if root_store.is_empty() {
+        tracing::warn!("The platform's certificate store is empty, trusting the webpki roots");
+        return None;
+    }
+    Some(root_store)
+}
+
+// Builds a root store holding the webpki trust anchors.
+fn webpki_root_store() -> RootCertStore {
     let mut root_store = RootCertStore::empty(); // Initialize an empty RootCertStore.
 
     // Add server trust anchors from the webpki_roots crate to the root store.
-    root_store.add_server_trust_anchors(TLS_SERVER_ROOTS.0.iter().map(|ta| {
-        rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
-            ta.subject,
-            ta.spki,
-            ta.name_constraints,
-        )
-    }));
+    root_store.extend(TLS_SERVER_ROOTS.iter().cloned());
+    root_store
+}
 
-    // Create a ClientConfig with the populated root store for TLS connections.
-    let config = ClientConfig::builder()
-        .with_safe_defaults()
-        .with_root_certificates(root_store)
-        .with_no_client_auth(); // No client authentication for simplicity.
+// Builds a root store from the certificates of a PEM bundle, e.g. those of an internal CA.
+fn load_root_store(path: &Path) -> Result<RootCertStore, Box<dyn std::error::Error>> {
+    let mut root_store = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        root_store.add(cert)?;
+    }
+    Ok(root_store)
+}
 
-    Ok(config)
+// Reads the certificates of a PEM file, failing when there are none.
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, Box<dyn std::error::Error>> {
+    let mut reader = open_pem(path)?;
+    let certs = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {}", path.display()).into());
+    }
+    Ok(certs)
+}
+
+// Reads the first private key of a PEM file, whether PKCS#1, PKCS#8 or SEC1 encoded.
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>, Box<dyn std::error::Error>> {
+    let mut reader = open_pem(path)?;
+    match rustls_pemfile::private_key(&mut reader)? {
+        Some(key) => Ok(key),
+        None => Err(format!("No private key found in {}", path.display()).into()),
+    }
+}
+
+// Opens a PEM file for reading.
+fn open_pem(path: &Path) -> Result<io::BufReader<File>, Box<dyn std::error::Error>> {
+    let file = File::open(path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+    Ok(io::BufReader::new(file))
+}
+
+// Resolves the remote address, unless its addresses are already known, and opens a TCP connection
+// to one of them, each within the timeout. Resolution failures are reported as DNS errors,
+// so they can be told apart from refused connections.
+pub(crate) async fn tcp_connect(
+    remote: &str,             // Remote host address.
+    addresses: &[SocketAddr], // Known addresses of the remote; when empty, the remote is resolved.
+    timeout: Option<u32>,     // Optional timeout in seconds, applied to each phase.
+) -> Result<TcpStream, HandshakeError> {
+    let dns_error =
+        |reason: String| HandshakeError::Dns(format!("Unable to resolve {}: {}", remote, reason));
+    let addrs: Vec<SocketAddr> = match addresses {
+        [] => {
+            with_timeout("resolve", timeout, async {
+                tokio::net::lookup_host(remote)
+                    .await
+                    .map(|addrs| addrs.collect())
+                    .map_err(|e| dns_error(e.to_string()))
+            })
+            .await?
+        }
+        addresses => addresses.to_vec(),
+    };
+    if addrs.is_empty() {
+        return Err(dns_error("no addresses found".to_string()));
+    }
+
+    with_timeout("connect", timeout, happy_eyeballs(&addrs)).await
+}
+
+// Orders addresses for connection attempts, alternating between address families
+// and starting with the family of the first address, as RFC 8305 recommends.
+fn interleave_families(addrs: &[SocketAddr]) -> Vec<SocketAddr> {
+    let first_is_ipv6 = addrs.first().is_some_and(SocketAddr::is_ipv6);
+    let (preferred, other): (Vec<SocketAddr>, Vec<SocketAddr>) = addrs
+        .iter()
+        .partition(|addr| addr.is_ipv6() == first_is_ipv6);
+
+    let mut ordered = Vec::with_capacity(addrs.len());
+    let (mut preferred, mut other) = (preferred.into_iter(), other.into_iter());
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => return ordered,
+            (first, second) => ordered.extend(first.into_iter().chain(second)),
+        }
+    }
+}
+
+// Races connections to the addresses with staggered starts, per RFC 8305 (Happy Eyeballs), and
+// returns the first to succeed. Each attempt starts when the previous one fails or once the
+// attempt delay has passed, so an unreachable address family does not hold up the other.
+async fn happy_eyeballs(addrs: &[SocketAddr]) -> io::Result<TcpStream> {
+    let mut queued = interleave_families(addrs).into_iter();
+    let mut attempts = FuturesUnordered::new();
+    let mut last_error = None;
+
+    loop {
+        if let Some(addr) = queued.next() {
+            attempts.push(async move { (addr, TcpStream::connect(addr).await) });
+        }
+        if attempts.is_empty() {
+            return Err(last_error.unwrap_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::AddrNotAvailable,
+                    "no addresses to connect to",
+                )
+            }));
+        }
+
+        // Wait for an attempt to finish, or for the delay before starting the next one.
+        tokio::select! {
+            Some((addr, result)) = attempts.next() => match result {
+                Ok(stream) => {
+                    info!("Connected to {}", addr);
+                    return Ok(stream);
+                }
+                Err(e) => {
+                    info!("Connection to {} failed: {}", addr, e);
+                    last_error = Some(e);
+                }
+            },
+            _ = tokio::time::sleep(CONNECTION_ATTEMPT_DELAY), if queued.len() > 0 => {}
+        }
+    }
+}
+
+// Opens a TCP stream to the remote, tunnelled through the proxy when the route has one.
+// A direct connection records the address it was made to, which is reported with the handshake.
+pub(crate) async fn open_stream(
+    remote: &str,         // Remote host address.
+    route: &Route,        // How the connection reaches the remote.
+    timeout: Option<u32>, // Optional timeout in seconds, applied to each phase.
+) -> Result<TcpStream, HandshakeError> {
+    if let Some(proxy) = &route.proxy {
+        return proxy.connect(remote, timeout).await;
+    }
+    let stream = tcp_connect(remote, &route.addresses, timeout).await?;
+    if let Ok(address) = stream.peer_addr() {
+        record_connected(address);
+    }
+    Ok(stream)
+}
+
+// Parses the server name used for SNI and certificate validation from a `host:port` address.
+fn server_name(remote: &str) -> Result<ServerName<'static>, HandshakeError> {
+    let host = match remote.rsplit_once(':') {
+        Some((host, port)) if port.parse::<u16>().is_ok() => host,
+        _ => remote,
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    ServerName::try_from(host.to_string())
+        .map_err(|_| HandshakeError::Tls(format!("Invalid DNS name '{}'", host)))
+}
+
+// Asynchronously connects to a secure remote server and completes the TLS handshake.
+pub(crate) async fn tls_connect(
+    remote: &str,         // Remote host address.
+    route: &Route,        // How the connection reaches the remote.
+    tls: &TlsOptions,     // Options for the TLS connection.
+    speaks: &str, // Protocol spoken over the connection, e.g. "http/1.1", should ALPN select one.
+    timeout: Option<u32>, // Optional timeout in seconds, applied to each phase.
+) -> Result<TlsStream<Tapped<TcpStream>>, HandshakeError> {
+    let stream = open_stream(remote, route, timeout).await?;
+    tls_handshake(stream, remote, tls, speaks, timeout).await
+}
+
+// Completes the TLS handshake with the remote over an established stream, within the timeout.
+pub(crate) async fn tls_handshake(
+    stream: TcpStream, // Stream connected to the remote, directly or through a proxy.
+    remote: &str,      // Remote host address, from which the server name is taken by default.
+    tls: &TlsOptions,  // Options for the TLS connection.
+    speaks: &str,      // Protocol spoken over the connection, should ALPN select one.
+    timeout: Option<u32>, // Optional timeout in seconds.
+) -> Result<TlsStream<Tapped<TcpStream>>, HandshakeError> {
+    let (stream, _) = tls_handshake_with_request(stream, remote, tls, speaks, timeout, &[]).await?;
+    Ok(stream)
+}
+
+// Completes the TLS handshake with the remote over an established stream, within the timeout.
+// With early data enabled and a resumed session allowing it, the request is sent along with the
+// ClientHello, saving the round trip it would otherwise wait for; whether it was sent is returned,
+// and it is up to the caller to send it otherwise. The server may still reject early data, in which
+// case the request is sent again once the handshake completes.
+// The stream is tapped during the handshake, to read the key exchange group the server selected.
+pub(crate) async fn tls_handshake_with_request(
+    stream: TcpStream, // Stream connected to the remote, directly or through a proxy.
+    remote: &str,      // Remote host address, from which the server name is taken by default.
+    tls: &TlsOptions,  // Options for the TLS connection.
+    speaks: &str,      // Protocol spoken over the connection, should ALPN select one.
+    timeout: Option<u32>, // Optional timeout in seconds.
+    request: &[u8],    // Request sent as early data when possible; none when empty.
+) -> Result<(TlsStream<Tapped<TcpStream>>, bool), HandshakeError> {
+    // Validate and parse the remote server's DNS name, unless it is overridden.
+    let dns_name = server_name(tls.server_name.as_deref().unwrap_or(remote))?;
+
+    // Create a TLS configuration, or reuse the shared one, or return an error.
+    let config = tls_config(tls)
+        .map_err(|e| HandshakeError::Tls(format!("Unable to create TLS config: {}", e)))?;
+    let connector = TlsConnector::from(config).early_data(tls.early_data && !request.is_empty());
+
+    let started = Instant::now();
+    let (mut stream, sent) = with_timeout("tls handshake", timeout, async {
+        let failed = |e: io::Error| HandshakeError::Tls(format!("Handshake failed: {}", e));
+        let mut stream = connector
+            .connect(dns_name, Tapped::new(stream))
+            .await
+            .map_err(failed)?;
+        // Sending early data, the handshake is only completed by flushing once the request is written.
+        let sent = stream.get_ref().1.is_handshaking();
+        if sent {
+            stream.write_all(request).await.map_err(failed)?;
+            stream.flush().await.map_err(failed)?;
+        }
+        Ok::<_, HandshakeError>((stream, sent))
+    })
+    .await?;
+
+    // Report whether the server accepted the early data and, if so, the time the request did not
+    // wait for the handshake.
+    if sent {
+        let accepted = stream.get_ref().1.is_early_data_accepted();
+        info!(
+            "Server {} the request sent as early data",
+            if accepted { "accepted" } else { "rejected" }
+        );
+        record_early_data(accepted, accepted.then(|| started.elapsed()));
+    }
+
+    // Report the TLS version, cipher suite and key exchange group negotiated, as two nodes behind the
+    // same name may differ, and some may still accept weak parameters.
+    let received = stream.get_mut().0.untap();
+    let group = tap::key_exchange_group(&received).map(tap::group_name);
+    let connection = stream.get_ref().1;
+    if let (Some(version), Some(suite)) = (
+        connection.protocol_version(),
+        connection.negotiated_cipher_suite(),
+    ) {
+        let version = match version {
+            ProtocolVersion::TLSv1_3 => "TLSv1.3".to_string(),
+            ProtocolVersion::TLSv1_2 => "TLSv1.2".to_string(),
+            other => format!("{:?}", other),
+        };
+        let suite = format!("{:?}", suite.suite());
+        info!(
+            "Negotiated {} with {} over {}",
+            version,
+            suite,
+            group.as_deref().unwrap_or("an unknown group")
+        );
+        record_tls(&version, &suite, group.as_deref());
+    }
+
+    // Report the certificate chain the server presented, failing the handshake when the leaf certificate
+    // expires within the threshold, so renewals that did not happen are noticed before clients fail.
+    let certificates: Vec<CertificateInfo> = connection
+        .peer_certificates()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|certificate| CertificateInfo::from_der(certificate))
+        .collect();
+    record_certificates(&certificates);
+    if let Some(leaf) = certificates.first() {
+        info!(
+            "Server certificate for {} issued by {}, covering {}, expires in {} days",
+            leaf.subject,
+            leaf.issuer,
+            leaf.sans.join(", "),
+            leaf.days_until_expiry
+        );
+        if let Some(days) = tls.warn_cert_expiry {
+            if leaf.days_until_expiry < i64::from(days) {
+                return Err(HandshakeError::Tls(format!(
+                    "Certificate for {} expires in {} days, within the {} day threshold",
+                    leaf.subject, leaf.days_until_expiry, days
+                )));
+            }
+        }
+    }
+
+    // Report the protocol the server selected among those offered through ALPN.
+    // A server, or a proxy in front of it, ignoring ALPN selects none. The caller then speaks its own
+    // protocol, e.g. HTTP/1.1 for the raw transport or h2 for gRPC, so a server selecting anything else
+    // would reject what it is sent.
+    if !tls.alpn.is_empty() {
+        match stream.get_ref().1.alpn_protocol() {
+            Some(protocol) => {
+                let protocol = String::from_utf8_lossy(protocol);
+                info!("Negotiated ALPN protocol {}", protocol);
+                record_protocol(&protocol);
+                if protocol != speaks {
+                    return Err(HandshakeError::Protocol(format!(
+                        "server selected {}, but the transport speaks {}",
+                        protocol, speaks
+                    )));
+                }
+            }
+            None => info!("Server selected none of the offered ALPN protocols"),
+        }
+    }
+    Ok((stream, sent))
 }
 
 // Asynchronously connects to a secure remote server, sends a request, and receives the response.
 async fn connect_and_send_secure(
-    remote: &str,          // Remote host address.
-    _timeout: Option<u32>, // Optional TCP operation timeout in seconds.
-    req: String,           // Request payload to send.
-) -> io::Result<String> {
-    // Validate and parse the remote server's DNS name.
-    let dns_name = ServerName::try_from(remote)
-        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid DNS name"))?;
-
-    // Create a TLS configuration or return an error.
-    let config = match create_tls_config() {
-        Ok(c) => c,
-        Err(e) => {
-            return Err(Error::new(
-                ErrorKind::Other,
-                format!("Unable to create TLS config: {}", e),
-            ))
+    remote: &str,         // Remote host address.
+    route: &Route,        // How the connection reaches the remote.
+    tls: &TlsOptions,     // Options for the TLS connection.
+    timeout: Option<u32>, // Optional timeout in seconds, applied to each phase.
+    req: String,          // Request payload to send.
+) -> Result<Vec<u8>, HandshakeError> {
+    let stream = open_stream(remote, route, timeout).await?;
+    let (mut stream, sent) =
+        tls_handshake_with_request(stream, remote, tls, HTTP_1_1, timeout, req.as_bytes()).await?;
+
+    // Write the request to the TLS stream, unless it went as early data, and read the response.
+    if !sent {
+        with_timeout("write", timeout, stream.write_all(req.as_bytes())).await?;
+    }
+    with_timeout("read", timeout, read_response(&mut stream)).await
+}
+
+// Reads a whole HTTP response: the head, then exactly Content-Length bytes of body when announced,
+// or everything up to the peer closing the connection for chunked and close-delimited bodies.
+// Malformed or truncated responses are returned as they arrived, for the parser to report.
+async fn read_response<S>(stream: &mut S) -> io::Result<Vec<u8>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut buf = Vec::new();
+    let mut chunk = vec![0; 4096];
+    let head = loop {
+        match HttpResponse::parse_head(&buf) {
+            Ok(Some(head)) => break head,
+            Ok(None) => {}
+            Err(_) => return Ok(buf),
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(buf); // The peer closed the connection before the end of the head.
         }
+        buf.extend_from_slice(&chunk[..n]);
     };
-    let connector = TlsConnector::from(Arc::new(config)); // Wrap the config in an Arc for thread safety.
-    let stream = TcpStream::connect(&remote).await?; // Connect to the remote server asynchronously.
-    let mut stream = connector.connect(dns_name, stream).await?; // Establish a TLS connection.
 
-    // Write the request to the TLS stream and read the response.
-    stream.write_all(req.as_bytes()).await?;
+    let (response, head_len) = head;
+    match response.content_length() {
+        Ok(Some(length)) if !response.is_chunked() => {
+            let end = head_len.saturating_add(length);
+            if buf.len() < end {
+                let remaining = (end - buf.len()) as u64;
+                stream.take(remaining).read_to_end(&mut buf).await?;
+            }
+            buf.truncate(end);
+        }
+        _ => {
+            stream.read_to_end(&mut buf).await?;
+        }
+    }
+    info!("Received message of length {}", buf.len());
+    Ok(buf)
+}
+
+// Parses a raw HTTP response, reporting malformed or truncated responses as invalid.
+pub(crate) fn parse_http_response(raw: &[u8]) -> Result<HttpResponse, HandshakeError> {
+    HttpResponse::parse(raw).map_err(|e| HandshakeError::InvalidResponse(e.to_string()))
+}
+
+// Reads one complete HTTP response from a stream, relying on the response framing rather than
+// the connection closing, so the stream can be reused for a further request afterwards.
+pub(crate) async fn read_http_response<S>(stream: &mut S) -> Result<HttpResponse, HandshakeError>
+where
+    S: AsyncRead + Unpin,
+{
     let mut buf = Vec::new();
-    stream.read_to_end(&mut buf).await?;
+    let mut chunk = vec![0; 4096];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 && buf.is_empty() {
+            // The peer closed the connection before answering, e.g. an idle connection it dropped.
+            return Err(HandshakeError::Connect(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed before a response",
+            )));
+        }
+        if n == 0 {
+            // The peer closed the connection; whatever arrived must be the whole response.
+            return parse_http_response(&buf);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        let framed = HttpResponse::parse_framed(&buf)
+            .map_err(|e| HandshakeError::InvalidResponse(e.to_string()))?;
+        if let Some(response) = framed {
+            info!("Received message of length {}", buf.len());
+            return Ok(response);
+        }
+    }
+}
 
-    // Convert the response buffer to a UTF-8 string.
-    let response = String::from_utf8_lossy(&buf).to_string();
-    Ok(response)
+// Formats an HTTP POST request to the path carrying the JSON payload.
+// With `keep_alive` the server is asked to keep the connection open for further requests.
+pub(crate) fn http_request(
+    remote: &str,
+    path: &str,
+    payload: &serde_json::Value,
+    keep_alive: bool,
+) -> String {
+    let json_header = "Content-Type: application/json";
+    let connection = match keep_alive {
+        true => "keep-alive",
+        false => "close",
+    };
+    let body = payload.to_string();
+    format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\n{}\r\nConnection: {}\r\nContent-Length: {}\r\n\r\n{}",
+        path,
+        remote,
+        json_header,
+        connection,
+        body.len(),
+        body
+    )
 }
 
 // Asynchronously connects to an insecure remote server, sends a request, and receives the response.
 async fn connect_and_send_insecure(
     remote: &str,         // Remote host address.
-    timeout: Option<u32>, // Optional TCP operation timeout in seconds.
+    route: &Route,        // How the connection reaches the remote.
+    timeout: Option<u32>, // Optional timeout in seconds, applied to each phase.
     req: String,          // Request payload to send.
-) -> io::Result<String> {
-    let stream = TcpStream::connect(remote).await?; // Connect to the remote server asynchronously.
-    let _ = stream.set_ttl(timeout.unwrap_or(TCP_TIMEOUT_SECONDS)); // Set the TTL for TCP packets.
-    stream.writable().await?; // Wait until the stream is writable.
+) -> Result<Vec<u8>, HandshakeError> {
+    // Connect to the remote server asynchronously.
+    let mut stream = open_stream(remote, route, timeout).await?;
 
     // Send the request payload.
+    with_timeout("write", timeout, stream.write_all(req.as_bytes())).await?;
     info!("Sent message payload {}", &req);
-    let _ = stream.try_write(req.as_bytes());
 
     // Read the response into a buffer.
-    let mut buf = vec![0; 1024];
-    loop {
-        stream.readable().await?;
-        match stream.try_read(&mut buf) {
-            Ok(n) => {
-                buf.truncate(n); // Truncate the buffer to the size of the data read.
-                info!("Received message of length {}", &n);
-                break;
-            }
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue, // Continue reading if the operation would block.
-            Err(e) => return Err(e),
-        }
-    }
-
-    // Convert the response buffer to a UTF-8 string and return it.
-    let response: String = String::from_utf8_lossy(&buf).to_string();
-    info!("Received message was {}", &response);
-    Ok(response.trim_end().to_owned())
+    let buf = with_timeout("read", timeout, read_response(&mut stream)).await?;
+    info!("Received message was {}", String::from_utf8_lossy(&buf));
+    Ok(buf)
 }
 
 // Implement the Transport trait for the Tcp struct, allowing for asynchronous connection and data transfer.
+// The HTTP framing is stripped, so only the body of a successful response is returned; the whole
+// response, status and headers included, is available from `send_request`.
 #[async_trait]
 impl Transport for Tcp {
     async fn connect_and_send(
         &self,                      // Reference to self for method invocation on an instance.
-        timeout: Option<u32>,       // Optional TCP operation timeout in seconds.
+        timeout: Option<u32>,       // Optional timeout in seconds, applied to each phase.
         payload: serde_json::Value, // JSON payload to be sent.
-    ) -> io::Result<String> {
+    ) -> Result<String, HandshakeError> {
+        // Non-2xx statuses are protocol failures, reported with the status line and body.
+        success_body(self.send_request(timeout, payload).await?)
+    }
+
+    // Sends the JSON payload as an HTTP POST and returns the parsed HTTP response.
+    async fn send_request(
+        &self,
+        timeout: Option<u32>, // Optional timeout in seconds, applied to each phase.
+        payload: serde_json::Value, // JSON payload to be sent.
+    ) -> Result<HttpResponse, HandshakeError> {
         // Format the HTTP request with JSON content.
-        let json_header = "Content-Type: application/json";
-        let http_header = format!(
-            "POST / HTTP/1.1\r\nHost: {}\r\n{}\r\nConnection: close\r\nContent-Length: {}\r\n\r\n",
-            &self.remote,
-            json_header,
-            payload.to_string().as_bytes().len()
-        );
-        let req = format!("{}{}\r\n", http_header, payload);
+        let req = http_request(&self.remote, &self.path, &payload, false);
 
         // Log the attempt to connect to the remote endpoint.
         info!("Connected to remote tcp endpoint {}", &self.remote);
 
         // Choose between secure and insecure connections based on the is_secure flag.
-        if self.is_secure {
-            connect_and_send_secure(&self.remote, timeout, req).await
+        let raw = if self.is_secure {
+            connect_and_send_secure(&self.remote, &self.route, &self.tls, timeout, req).await?
         } else {
-            connect_and_send_insecure(&self.remote, timeout, req).await
-        }
+            connect_and_send_insecure(&self.remote, &self.route, timeout, req).await?
+        };
+
+        // Parse the raw bytes into a structured HTTP response.
+        parse_http_response(&raw)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use {super::*, proptest::prelude::*};
+    use {
+        super::super::timed, super::*, proptest::prelude::*, test_case::test_case,
+        tokio_rustls::rustls::server::WebPkiClientVerifier,
+    };
 
     // Property-based tests to ensure that URL formatting does not cause panics.
     proptest! {
         #[test]
         fn test_url_formatting_does_not_panic(remote in "[a-zA-Z0-9]+") {
             let result = std::panic::catch_unwind(|| {
-               let _ = Tcp::new(remote, false).connect_and_send(None, serde_json::json!({}));
+               drop(Tcp::new(remote, false).connect_and_send(None, serde_json::json!({})));
             });
             assert!(result.is_ok());
         }
     }
+
+    // Serves a single canned response to the first connection and returns the server address.
+    async fn serve_once(response: &'static str) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 1024];
+            let _ = socket.read(&mut buf).await; // Consume the request.
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        remote
+    }
+
+    // A chunked response is decoded and only its body is returned.
+    #[tokio::test]
+    async fn test_chunked_body_is_returned() {
+        let remote =
+            serve_once("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\n{}\r\n0\r\n\r\n")
+                .await;
+
+        let result = Tcp::new(remote, false)
+            .connect_and_send(Some(5), serde_json::json!({}))
+            .await;
+        assert_eq!(result.unwrap(), "{}");
+    }
+
+    // A response larger than a single read is received whole, in several writes, without waiting
+    // for the server to close the connection when its length is announced.
+    #[tokio::test]
+    async fn test_large_body_is_read_whole() {
+        let body = format!("{{\"data\":\"{}\"}}", "x".repeat(20_000));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = listener.local_addr().unwrap().to_string();
+        let served = body.clone();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 1024];
+            let _ = socket.read(&mut buf).await; // Consume the request.
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+                served.len()
+            );
+            socket.write_all(head.as_bytes()).await.unwrap();
+            for part in served.as_bytes().chunks(5_000) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                socket.write_all(part).await.unwrap();
+            }
+            tokio::time::sleep(Duration::from_secs(30)).await; // Keep the connection open.
+        });
+
+        let result = Tcp::new(remote, false)
+            .connect_and_send(Some(5), serde_json::json!({}))
+            .await;
+        assert_eq!(result.unwrap(), body);
+    }
+
+    #[test_case(&["HTTP/1.1 200 OK\r\nConte", "nt-Length: 2\r\n\r\n{}ignored"], "{}"; "when head is split")]
+    #[test_case(&["HTTP/1.0 200 OK\r\n\r\n{", "}"], "{}"; "when body is close delimited")]
+    #[test_case(&["HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n", "2\r\n{}\r\n0\r\n\r\n"], "{}"; "when body is chunked")]
+    // Responses are read according to their framing, however their bytes are split.
+    #[tokio::test]
+    async fn test_read_response(parts: &[&str], expected: &str) {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let parts: Vec<String> = parts.iter().map(|part| part.to_string()).collect();
+        tokio::spawn(async move {
+            for part in parts {
+                server.write_all(part.as_bytes()).await.unwrap();
+            }
+        });
+        let raw = read_response(&mut client).await.unwrap();
+        assert_eq!(parse_http_response(&raw).unwrap().body_text(), expected);
+    }
+
+    // Requests are posted to the root unless another path is given.
+    #[tokio::test]
+    async fn test_request_path() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = listener.local_addr().unwrap().to_string();
+        let received = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 1024];
+            let n = socket.read(&mut buf).await.unwrap();
+            let response = "HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n";
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+
+        let response = Tcp::new(remote, false)
+            .with_path("/hooks/abc")
+            .send_request(Some(5), serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(response.status, 204);
+        assert!(received
+            .await
+            .unwrap()
+            .starts_with("POST /hooks/abc HTTP/1.1\r\n"));
+    }
+
+    // A non-2xx response is reported as an error carrying the status.
+    #[tokio::test]
+    async fn test_error_status_is_reported() {
+        let remote =
+            serve_once("HTTP/1.1 503 Service Unavailable\r\nContent-Length: 4\r\n\r\nbusy").await;
+
+        let error = Tcp::new(remote, false)
+            .connect_and_send(Some(5), serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, HandshakeError::Protocol(_)));
+        assert!(error.to_string().contains("503"));
+    }
+
+    // A server that accepts the connection but never answers should trip the read timeout.
+    #[tokio::test]
+    async fn test_unresponsive_server_times_out() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (_socket, _) = listener.accept().await.unwrap();
+            std::future::pending::<()>().await; // Hold the connection open without responding.
+        });
+
+        let result = Tcp::new(remote, false)
+            .connect_and_send(Some(1), serde_json::json!({}))
+            .await;
+        assert!(matches!(
+            result.unwrap_err(),
+            HandshakeError::Timeout { .. }
+        ));
+    }
+
+    // A refused connection is a connection error rather than a DNS error.
+    #[tokio::test]
+    async fn test_refused_connection_is_connect_error() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = listener.local_addr().unwrap().to_string();
+        drop(listener); // Free the port so the connection is refused.
+
+        let error = tcp_connect(&remote, &[], Some(5)).await.unwrap_err();
+        assert!(matches!(error, HandshakeError::Connect(_)));
+    }
+
+    // Connection attempts alternate between address families, starting with the first address's.
+    #[test]
+    fn test_interleave_families() {
+        let addrs: Vec<SocketAddr> = [
+            "[::1]:1",
+            "[::1]:2",
+            "[::1]:3",
+            "127.0.0.1:4",
+            "127.0.0.1:5",
+        ]
+        .iter()
+        .map(|addr| addr.parse().unwrap())
+        .collect();
+        let ports: Vec<u16> = interleave_families(&addrs)
+            .iter()
+            .map(SocketAddr::port)
+            .collect();
+        assert_eq!(ports, vec![1, 4, 2, 5, 3]);
+    }
+
+    // An unreachable or refusing address does not stop the connection to a reachable one.
+    #[tokio::test]
+    async fn test_happy_eyeballs_falls_back() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let refused = listener.local_addr().unwrap();
+        drop(listener); // Free the port so the connection is refused.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let reachable = listener.local_addr().unwrap();
+        let unreachable = "192.0.2.1:8899".parse().unwrap(); // Documentation address that never answers.
+
+        let addrs = [unreachable, refused, reachable];
+        let stream = tcp_connect("node.internal:8899", &addrs, Some(5))
+            .await
+            .unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), reachable);
+    }
+
+    // The address a direct connection was made to is recorded with the handshake's timings.
+    #[tokio::test]
+    async fn test_connected_address_is_recorded() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let refused = listener.local_addr().unwrap();
+        drop(listener); // Free the port so the connection is refused.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let reachable = listener.local_addr().unwrap();
+
+        let route = Route {
+            addresses: vec![refused, reachable],
+            proxy: None,
+        };
+        let (result, _, connection) =
+            timed(open_stream("node.internal:8899", &route, Some(5))).await;
+        assert!(result.is_ok());
+        assert_eq!(connection.connected, Some(reachable));
+    }
+
+    // The server name is the host of the address, without its port.
+    #[test_case("node.internal:8899", "node.internal"; "when host is a name")]
+    #[test_case("127.0.0.1:8899", "127.0.0.1"; "when host is an ipv4 address")]
+    #[test_case("[::1]:8899", "::1"; "when host is an ipv6 address")]
+    #[test_case("node.internal", "node.internal"; "when there is no port")]
+    fn test_server_name(remote: &str, expected: &str) {
+        let name = server_name(remote).unwrap();
+        let host = match name {
+            ServerName::DnsName(name) => name.as_ref().to_string(),
+            ServerName::IpAddress(ip) => std::net::IpAddr::from(ip).to_string(),
+            _ => unreachable!(),
+        };
+        assert_eq!(host, expected);
+    }
+
+    // Writes PEM contents to a temporary file unique to the test process.
+    fn write_pem(name: &str, pem: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("handshake-{}-{}.pem", name, std::process::id()));
+        std::fs::write(&path, pem).unwrap();
+        path
+    }
+
+    // Serves a single TLS connection for localhost, requiring a client certificate signed by `client_ca` when given
+    // and selecting the first offered protocol it supports among `alpn`.
+    // The returned task reports whether the server completed the handshake.
+    async fn serve_tls(
+        cert: &rcgen::Certificate,
+        client_ca: Option<&rcgen::Certificate>,
+        alpn: &[&str],
+    ) -> (String, tokio::task::JoinHandle<bool>) {
+        let builder = tokio_rustls::rustls::ServerConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()
+            .unwrap();
+        let builder = match client_ca {
+            Some(ca) => {
+                let mut roots = RootCertStore::empty();
+                roots
+                    .add(CertificateDer::from(ca.serialize_der().unwrap()))
+                    .unwrap();
+                let verifier =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), crypto_provider())
+                        .build()
+                        .unwrap();
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let mut server_config = builder
+            .with_single_cert(
+                vec![CertificateDer::from(cert.serialize_der().unwrap())],
+                PrivateKeyDer::Pkcs8(cert.serialize_private_key_der().into()),
+            )
+            .unwrap();
+        server_config.alpn_protocols = alpn.iter().map(|p| p.as_bytes().to_vec()).collect();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(server_config));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = format!("localhost:{}", listener.local_addr().unwrap().port());
+        let handle = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            acceptor.accept(socket).await.is_ok()
+        });
+        (remote, handle)
+    }
+
+    // Serves `connections` TLS connections for localhost, allowing early data, answering each request with an
+    // empty JSON object. The returned task reports whether each request arrived as early data.
+    async fn serve_tls_early_data(
+        cert: &rcgen::Certificate,
+        connections: usize,
+    ) -> (String, tokio::task::JoinHandle<Vec<bool>>) {
+        let mut server_config =
+            tokio_rustls::rustls::ServerConfig::builder_with_provider(crypto_provider())
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(
+                    vec![CertificateDer::from(cert.serialize_der().unwrap())],
+                    PrivateKeyDer::Pkcs8(cert.serialize_private_key_der().into()),
+                )
+                .unwrap();
+        server_config.max_early_data_size = 16 * 1024;
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(server_config));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = format!("localhost:{}", listener.local_addr().unwrap().port());
+        let handle = tokio::spawn(async move {
+            let mut early = Vec::new();
+            for _ in 0..connections {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mut stream = acceptor.accept(socket).await.unwrap();
+                let mut request = Vec::new();
+                if let Some(mut reader) = stream.get_mut().1.early_data() {
+                    std::io::Read::read_to_end(&mut reader, &mut request).unwrap();
+                }
+                early.push(!request.is_empty());
+                if request.is_empty() {
+                    let mut buf = vec![0; 1024];
+                    let _ = stream.read(&mut buf).await; // Consume the request.
+                }
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}")
+                    .await
+                    .unwrap();
+                stream.flush().await.unwrap();
+            }
+            early
+        });
+        (remote, handle)
+    }
+
+    // A repeat connection resumes the session of the first and sends its request as early data, which the
+    // server accepts; the first connection has no session to resume, so it does not attempt early data.
+    #[tokio::test]
+    async fn test_early_data() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let ca_file = write_pem("early-data-ca", &cert.serialize_pem().unwrap());
+        let tls = TlsOptions {
+            ca_file: Some(ca_file.clone()),
+            early_data: true,
+            ..TlsOptions::default()
+        };
+
+        let (remote, served) = serve_tls_early_data(&cert, 2).await;
+        let tcp = Tcp::new(remote, true).with_tls(tls);
+        let (first, _, full) = timed(tcp.connect_and_send(Some(5), serde_json::json!({}))).await;
+        let (second, timings, resumed) =
+            timed(tcp.connect_and_send(Some(5), serde_json::json!({}))).await;
+
+        std::fs::remove_file(ca_file).unwrap();
+        assert_eq!(first.unwrap(), "{}");
+        assert_eq!(second.unwrap(), "{}");
+        assert_eq!(served.await.unwrap(), vec![false, true]);
+        assert_eq!((full.early_data, full.early_data_saved), (None, None));
+        assert_eq!(resumed.early_data, Some(true));
+        assert!(resumed.early_data_saved.is_some());
+        assert_eq!(timings.write, None);
+    }
+
+    // A TLS handshake succeeds against a server whose certificate is in the configured CA file.
+    #[tokio::test]
+    async fn test_tls_with_ca_file() {
+        // The self-signed certificate for localhost acts as its own CA.
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let ca_file = write_pem("ca", &cert.serialize_pem().unwrap());
+        let (remote, _) = serve_tls(&cert, None, &[]).await;
+
+        let tls = TlsOptions {
+            ca_file: Some(ca_file.clone()),
+            ..TlsOptions::default()
+        };
+        let result = tls_connect(&remote, &Route::default(), &tls, HTTP_1_1, Some(5)).await;
+        std::fs::remove_file(ca_file).unwrap();
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+
+    // The server name override is validated instead of the address connected to.
+    #[tokio::test]
+    async fn test_tls_with_server_name() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let ca_file = write_pem("sni-ca", &cert.serialize_pem().unwrap());
+        let tls = TlsOptions {
+            ca_file: Some(ca_file.clone()),
+            ..TlsOptions::default()
+        };
+
+        // Connecting by IP fails validation against a certificate for localhost.
+        let (remote, _) = serve_tls(&cert, None, &[]).await;
+        let remote = remote.replace("localhost", "127.0.0.1");
+        let without_override =
+            tls_connect(&remote, &Route::default(), &tls, HTTP_1_1, Some(5)).await;
+
+        let (remote, _) = serve_tls(&cert, None, &[]).await;
+        let remote = remote.replace("localhost", "127.0.0.1");
+        let with_override = TlsOptions {
+            server_name: Some("localhost".to_string()),
+            ..tls
+        };
+        let result = tls_connect(
+            &remote,
+            &Route::default(),
+            &with_override,
+            HTTP_1_1,
+            Some(5),
+        )
+        .await;
+
+        std::fs::remove_file(ca_file).unwrap();
+        assert!(matches!(without_override, Err(HandshakeError::Tls(_))));
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+
+    // A server requiring mutual TLS accepts the configured client certificate, and rejects clients without one.
+    #[tokio::test]
+    async fn test_tls_with_client_cert() {
+        let server_cert =
+            rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let client_cert = rcgen::generate_simple_self_signed(vec!["client".to_string()]).unwrap();
+        let ca_file = write_pem("mtls-ca", &server_cert.serialize_pem().unwrap());
+        let cert_file = write_pem("mtls-cert", &client_cert.serialize_pem().unwrap());
+        let key_file = write_pem("mtls-key", &client_cert.serialize_private_key_pem());
+
+        let without_cert = TlsOptions {
+            ca_file: Some(ca_file.clone()),
+            ..TlsOptions::default()
+        };
+        let with_cert = TlsOptions {
+            client_cert: Some(cert_file.clone()),
+            client_key: Some(key_file.clone()),
+            ..without_cert.clone()
+        };
+
+        let (remote, accepted) = serve_tls(&server_cert, Some(&client_cert), &[]).await;
+        let _ = tls_connect(&remote, &Route::default(), &with_cert, HTTP_1_1, Some(5)).await;
+        let accepted_with_cert = accepted.await.unwrap();
+
+        let (remote, accepted) = serve_tls(&server_cert, Some(&client_cert), &[]).await;
+        let _ = tls_connect(&remote, &Route::default(), &without_cert, HTTP_1_1, Some(5)).await;
+        let accepted_without_cert = accepted.await.unwrap();
+
+        for path in [ca_file, cert_file, key_file] {
+            std::fs::remove_file(path).unwrap();
+        }
+        assert!(accepted_with_cert);
+        assert!(!accepted_without_cert);
+    }
+
+    // The protocol the server selects through ALPN is recorded; a server ignoring ALPN leaves none.
+    #[tokio::test]
+    async fn test_negotiated_alpn_is_recorded() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let ca_file = write_pem("alpn-ca", &cert.serialize_pem().unwrap());
+        let tls = TlsOptions {
+            ca_file: Some(ca_file.clone()),
+            alpn: vec!["h2".to_string(), "http/1.1".to_string()],
+            ..TlsOptions::default()
+        };
+
+        let (remote, _) = serve_tls(&cert, None, &["http/1.1"]).await;
+        let (result, _, negotiated) = timed(tls_connect(
+            &remote,
+            &Route::default(),
+            &tls,
+            HTTP_1_1,
+            Some(5),
+        ))
+        .await;
+        let (remote, _) = serve_tls(&cert, None, &[]).await;
+        let (_, _, ignored) = timed(tls_connect(
+            &remote,
+            &Route::default(),
+            &tls,
+            HTTP_1_1,
+            Some(5),
+        ))
+        .await;
+
+        std::fs::remove_file(ca_file).unwrap();
+        assert!(result.is_ok(), "{:?}", result.err());
+        assert_eq!(negotiated.protocol.as_deref(), Some("http/1.1"));
+        assert_eq!(negotiated.tls_version.as_deref(), Some("TLSv1.3"));
+        assert!(negotiated.cipher_suite.unwrap().starts_with("TLS13_"));
+        assert_eq!(negotiated.key_exchange.as_deref(), Some("X25519"));
+        assert_eq!(ignored.protocol, None);
+    }
+
+    // A server selecting a protocol other than the one spoken, here h2 where HTTP/1.1 is, fails the
+    // handshake rather than being sent a request it cannot read; a caller speaking h2 completes it.
+    #[tokio::test]
+    async fn test_negotiated_h2_is_refused() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let ca_file = write_pem("alpn-h2-ca", &cert.serialize_pem().unwrap());
+        let tls = TlsOptions {
+            ca_file: Some(ca_file.clone()),
+            alpn: vec!["h2".to_string(), "http/1.1".to_string()],
+            ..TlsOptions::default()
+        };
+
+        let (remote, _) = serve_tls(&cert, None, &["h2"]).await;
+        let (result, _, negotiated) = timed(tls_connect(
+            &remote,
+            &Route::default(),
+            &tls,
+            HTTP_1_1,
+            Some(5),
+        ))
+        .await;
+
+        let (remote, _) = serve_tls(&cert, None, &["h2"]).await;
+        let accepted = tls_connect(&remote, &Route::default(), &tls, "h2", Some(5)).await;
+
+        std::fs::remove_file(ca_file).unwrap();
+        match result {
+            Err(HandshakeError::Protocol(message)) => assert_eq!(
+                message,
+                "server selected h2, but the transport speaks http/1.1"
+            ),
+            other => panic!("expected a protocol error, got {:?}", other.map(|_| ())),
+        }
+        assert!(accepted.is_ok(), "{:?}", accepted.err());
+        assert_eq!(negotiated.protocol.as_deref(), Some("h2"));
+    }
+
+    // The certificate the server presented is recorded, and fails the handshake when it expires within
+    // the threshold; the generated certificate expires in 4096.
+    #[test_case(None, true; "when no threshold")]
+    #[test_case(Some(30), true; "when expiring after the threshold")]
+    #[test_case(Some(1_000_000), false; "when expiring within the threshold")]
+    #[tokio::test]
+    async fn test_certificate_expiry(warn_cert_expiry: Option<u32>, succeeds: bool) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let ca_file = write_pem("expiry-ca", &cert.serialize_pem().unwrap());
+        let tls = TlsOptions {
+            ca_file: Some(ca_file.clone()),
+            warn_cert_expiry,
+            ..TlsOptions::default()
+        };
+
+        let (remote, _) = serve_tls(&cert, None, &[]).await;
+        let (result, _, connection) = timed(tls_connect(
+            &remote,
+            &Route::default(),
+            &tls,
+            HTTP_1_1,
+            Some(5),
+        ))
+        .await;
+
+        std::fs::remove_file(ca_file).unwrap();
+        assert_eq!(result.is_ok(), succeeds, "{:?}", result.err());
+        if !succeeds {
+            assert!(matches!(result, Err(HandshakeError::Tls(_))));
+        }
+        assert_eq!(connection.certificates.len(), 1);
+        assert_eq!(connection.certificates[0].sans, vec!["localhost"]);
+    }
+
+    // Pins are checked on top of chain validation, or instead of it when trusted alone.
+    #[test_case(true, false, true; "when key is pinned and chain is valid")]
+    #[test_case(false, false, false; "when key is not pinned")]
+    #[test_case(true, true, true; "when key is pinned and trusted alone")]
+    #[test_case(false, true, false; "when key is not pinned and trusted alone")]
+    #[tokio::test]
+    async fn test_certificate_pinning(pinned: bool, pin_only: bool, succeeds: bool) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let ca_file = write_pem("pin-ca", &cert.serialize_pem().unwrap());
+        let pin = match pinned {
+            true => super::super::cert::spki_sha256(&cert.serialize_der().unwrap()).unwrap(),
+            false => [0; 32],
+        };
+        // Trusted alone, the pins stand in for the CA file.
+        let tls = TlsOptions {
+            ca_file: (!pin_only).then(|| ca_file.clone()),
+            pins: vec![pin],
+            pin_only,
+            ..TlsOptions::default()
+        };
+
+        let (remote, _) = serve_tls(&cert, None, &[]).await;
+        let result = tls_connect(&remote, &Route::default(), &tls, HTTP_1_1, Some(5)).await;
+
+        std::fs::remove_file(ca_file).unwrap();
+        assert_eq!(result.is_ok(), succeeds, "{:?}", result.err());
+        if !succeeds {
+            assert!(matches!(result, Err(HandshakeError::Tls(_))));
+        }
+    }
+
+    // A client certificate without its key is rejected.
+    #[test]
+    fn test_client_cert_requires_key() {
+        let tls = TlsOptions {
+            client_cert: Some(PathBuf::from("client.pem")),
+            ..TlsOptions::default()
+        };
+        assert!(create_tls_config(&tls).is_err());
+    }
+
+    // Without a CA file, the platform's store or the webpki roots are trusted, never none at all.
+    #[test]
+    fn test_default_root_store() {
+        assert!(!default_root_store().is_empty());
+    }
+
+    // A CA file without certificates is rejected.
+    #[test]
+    fn test_empty_ca_file() {
+        let path = write_pem("empty-ca", "");
+        let result = load_root_store(&path);
+        std::fs::remove_file(path).unwrap();
+        assert!(result.is_err());
+    }
+
+    // An address without a port cannot be resolved.
+    #[tokio::test]
+    async fn test_unresolvable_address_is_dns_error() {
+        let error = tcp_connect("not-an-address", &[], Some(5))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, HandshakeError::Dns(_)));
+    }
 }
@@ -3,103 +3,249 @@
 use futures_util::{sink::SinkExt, stream::StreamExt};
 // Import WebSocket functionalities from the tokio_tungstenite crate, including asynchronous connection functions and relevant types and errors.
 use tokio_tungstenite::{
-    connect_async,
-    tungstenite::{error::Error as WsError, protocol::Message},
+    client_async,
+    tungstenite::{client::IntoClientRequest, error::Error as WsError, protocol::Message},
+    WebSocketStream,
 };
 // Group imports for organizing code dependencies, including the Transport trait for implementing custom transport logic, async_trait for asynchronous trait methods, and standard IO error handling utilities. Also, include tracing for structured logging.
 use {
-    super::Transport,
+    super::{
+        tcp::{open_stream, tls_handshake, Route, TlsOptions, HTTP_1_1},
+        with_timeout, Transport,
+    },
+    crate::error::HandshakeError,
     async_trait::async_trait,
-    std::io::{self, Error, ErrorKind},
+    std::time::{Duration, Instant},
+    tokio::io::{AsyncRead, AsyncWrite},
     tracing::{error, info},
 };
 
+// A byte stream a WebSocket can run over: a TCP stream, or a TLS stream on top of one.
+pub(crate) trait ByteStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> ByteStream for S {}
+
+// An established WebSocket connection, plain or over TLS.
+pub(crate) type WsStream = WebSocketStream<Box<dyn ByteStream>>;
+
+// How WebSocket pings are sent: how many, and how long to wait between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PingOptions {
+    pub count: u32,         // Number of Ping frames to send.
+    pub interval: Duration, // Delay between one Pong arriving and the next Ping being sent.
+}
+
 // Represents a WebSocket transport mechanism with attributes to store the remote server's URL and a flag indicating the use of secure WebSocket (WSS).
 pub struct Ws {
     remote: String,  // URL of the remote server.
     is_secure: bool, // Flag indicating whether a secure connection (WSS) should be used.
+    route: Route,    // How the connection reaches the remote.
+    tls: TlsOptions, // Options of the TLS connection to `wss` URLs.
 }
 
 impl Ws {
     // Constructs a new instance of a WebSocket transport with a specified remote URL and security preference.
     pub fn new(remote: String, is_secure: bool) -> Self {
-        Ws { remote, is_secure }
+        Ws {
+            remote,
+            is_secure,
+            route: Route::default(),
+            tls: TlsOptions::default(),
+        }
+    }
+
+    // Sets how the connection reaches the remote, e.g. through an HTTP proxy.
+    pub fn with_route(mut self, route: Route) -> Self {
+        self.route = route;
+        self
+    }
+
+    // Sets the options used for secure connections.
+    pub fn with_tls(mut self, tls: TlsOptions) -> Self {
+        self.tls = tls;
+        self
     }
 }
 
-// Function to map WebSocket-specific errors to standard IO errors, enabling consistent error handling across different transport mechanisms.
-fn convert_error(error: WsError) -> Error {
+// Function to map WebSocket-specific errors to handshake errors, enabling consistent error handling across different transport mechanisms.
+fn convert_error(error: WsError) -> HandshakeError {
     match error {
-        WsError::Io(io_err) => io_err,
-        // Map connection-related errors to BrokenPipe to signify connection issues.
-        WsError::ConnectionClosed | WsError::AlreadyClosed => {
-            Error::new(ErrorKind::BrokenPipe, error)
-        }
-        // Handle TLS and capacity errors with generic messages, categorizing them under 'Other'.
-        WsError::Tls(err) => Error::new(ErrorKind::Other, format!("TLS error: {}", err)),
-        WsError::Capacity(err) => Error::new(ErrorKind::Other, format!("Capacity error: {}", err)),
-        // Protocol and UTF-8 errors are treated as data-related issues.
-        WsError::Protocol(err) => {
-            Error::new(ErrorKind::InvalidData, format!("Protocol error: {}", err))
-        }
-        WsError::Utf8 => Error::new(ErrorKind::InvalidData, "UTF-8 encoding error"),
-        // URL errors are treated as input errors.
-        WsError::Url(err) => Error::new(ErrorKind::InvalidInput, format!("URL error: {}", err)),
-        // All other errors are mapped to 'Other' for simplicity.
-        _ => Error::new(ErrorKind::Other, "Unmapped WebSocket error"),
+        // IO errors signify connection issues.
+        WsError::Io(io_err) => HandshakeError::Connect(io_err),
+        // TLS errors are reported as such, regardless of the transport carrying them.
+        WsError::Tls(err) => HandshakeError::Tls(err.to_string()),
+        // Malformed frames and invalid UTF-8 mean the response cannot be understood.
+        WsError::Utf8 => HandshakeError::InvalidResponse("UTF-8 encoding error".to_string()),
+        // URL errors mean the address cannot be used to reach a server.
+        WsError::Url(err) => HandshakeError::Dns(format!("URL error: {}", err)),
+        // Closed connections, capacity, protocol and upgrade failures are WebSocket-level errors.
+        err => HandshakeError::WebSocket(err.to_string()),
     }
 }
 
-// Asynchronously establishes a WebSocket connection to the specified remote, sends a JSON payload, and awaits a response.
-#[warn(unused_assignments)]
-async fn ws_send(
+// Asynchronously establishes a WebSocket connection to the specified remote URL.
+// The TCP connection, optionally tunnelled through a proxy, and any TLS are set up here,
+// so WSS shares the TLS configuration of the TLS transport.
+pub(crate) async fn ws_connect(
     remote: &str,
-    _timeout: Option<u32>, // Currently unused. Placeholder for future timeout implementation.
-    payload: serde_json::Value,
-) -> Result<String, WsError> {
-    // Attempt to establish a WebSocket connection asynchronously.
-    let (ws_stream, _) = connect_async(remote).await?;
+    route: &Route,        // How the connection reaches the remote.
+    tls: &TlsOptions,     // Used when the URL's scheme is `wss`.
+    timeout: Option<u32>, // Optional timeout in seconds, applied to each phase.
+) -> Result<WsStream, HandshakeError> {
+    let request = remote.into_client_request().map_err(convert_error)?;
+    let secure = request.uri().scheme_str() == Some("wss");
+    let host = request
+        .uri()
+        .host()
+        .ok_or_else(|| HandshakeError::Dns(format!("URL error: no host in '{}'", remote)))?;
+    let port = request
+        .uri()
+        .port_u16()
+        .unwrap_or(if secure { 443 } else { 80 });
+    let address = format!("{}:{}", host, port);
+
+    // Connect, then wrap the stream in TLS for secure WebSockets.
+    let stream = open_stream(&address, route, timeout).await?;
+    let stream: Box<dyn ByteStream> = match secure {
+        true => Box::new(tls_handshake(stream, &address, tls, HTTP_1_1, timeout).await?),
+        false => Box::new(stream),
+    };
+
+    // Attempt to complete the WebSocket upgrade asynchronously.
+    let (ws_stream, _) = with_timeout("websocket upgrade", timeout, async {
+        client_async(request, stream).await.map_err(convert_error)
+    })
+    .await?;
 
     // Log successful connection establishment.
     info!("Connected to remote websocket {}", remote);
+    Ok(ws_stream)
+}
 
-    // Split the WebSocket stream into separate sender and receiver components.
-    let (mut write, mut read) = ws_stream.split();
+// Sends a JSON payload over an established WebSocket and awaits the response.
+// The stream is left open, so further exchanges can reuse it.
+pub(crate) async fn ws_exchange(
+    ws_stream: &mut WsStream,
+    timeout: Option<u32>, // Optional timeout in seconds, applied to each phase.
+    payload: serde_json::Value,
+) -> Result<String, HandshakeError> {
+    ws_write(ws_stream, timeout, &payload).await?;
+    ws_read(ws_stream, timeout).await
+}
 
-    // Send the JSON payload as a text message through the WebSocket.
-    write.send(Message::Text(payload.to_string())).await?;
+// Sends a JSON payload as a text message through the WebSocket.
+pub(crate) async fn ws_write(
+    ws_stream: &mut WsStream,
+    timeout: Option<u32>, // Optional timeout in seconds.
+    payload: &serde_json::Value,
+) -> Result<(), HandshakeError> {
+    with_timeout("write", timeout, async {
+        ws_stream
+            .send(Message::Text(payload.to_string()))
+            .await
+            .map_err(convert_error)
+    })
+    .await?;
 
     // Log the transmission of the payload.
     info!("Sent message payload {}", payload);
+    Ok(())
+}
 
-    // Initialize a placeholder for storing the response.
-    let mut resp = String::new();
-
+// Awaits the next text or binary message from the WebSocket.
+// An empty string is returned if the stream ends first.
+#[warn(unused_assignments)]
+pub(crate) async fn ws_read(
+    ws_stream: &mut WsStream,
+    timeout: Option<u32>, // Optional timeout in seconds.
+) -> Result<String, HandshakeError> {
     // Process incoming messages, looking for text or binary responses.
-    while let Some(message) = read.next().await {
-        match message? {
-            Message::Text(text) => {
-                // Store text responses directly.
-                resp = text;
-                info!("Received text message {}", &resp);
-                break;
-            }
-            Message::Binary(b) => {
-                // Convert binary messages to strings for consistency.
-                resp = String::from_utf8_lossy(&b).to_string();
-                info!("Received binary message {}", &resp);
-                break;
+    with_timeout("read", timeout, async {
+        // Initialize a placeholder for storing the response.
+        let mut resp = String::new();
+
+        while let Some(message) = ws_stream.next().await {
+            match message.map_err(convert_error)? {
+                Message::Text(text) => {
+                    // Store text responses directly.
+                    resp = text;
+                    info!("Received text message {}", &resp);
+                    break;
+                }
+                Message::Binary(b) => {
+                    // Convert binary messages to strings for consistency.
+                    resp = String::from_utf8_lossy(&b).to_string();
+                    info!("Received binary message {}", &resp);
+                    break;
+                }
+                // Ignore other message types for simplicity.
+                _ => continue,
             }
-            // Ignore other message types for simplicity.
-            _ => continue,
         }
+
+        Ok::<_, HandshakeError>(resp)
+    })
+    .await
+}
+
+// Sends Ping frames over an established WebSocket and returns the round-trip time of each Pong.
+// Each Ping carries its sequence number, so only the Pong echoing it completes the round trip;
+// other frames received meanwhile are skipped.
+pub(crate) async fn ws_ping(
+    ws_stream: &mut WsStream,
+    options: PingOptions,
+    timeout: Option<u32>, // Optional timeout in seconds, applied to each Ping and Pong.
+) -> Result<Vec<Duration>, HandshakeError> {
+    let mut round_trips = Vec::new();
+    for sequence in 0..options.count {
+        if sequence > 0 {
+            tokio::time::sleep(options.interval).await;
+        }
+        let payload = sequence.to_be_bytes().to_vec();
+        let sent = Instant::now();
+        with_timeout("write", timeout, async {
+            ws_stream
+                .send(Message::Ping(payload.clone()))
+                .await
+                .map_err(convert_error)
+        })
+        .await?;
+        with_timeout("read", timeout, async {
+            while let Some(message) = ws_stream.next().await {
+                if let Message::Pong(echo) = message.map_err(convert_error)? {
+                    if echo == payload {
+                        return Ok(());
+                    }
+                }
+            }
+            Err(HandshakeError::WebSocket(
+                "Connection closed before the pong arrived".to_string(),
+            ))
+        })
+        .await?;
+
+        let round_trip = sent.elapsed();
+        info!("Received pong {} after {:?}", sequence, round_trip);
+        round_trips.push(round_trip);
     }
+    Ok(round_trips)
+}
 
-    Ok(resp)
+// Asynchronously establishes a WebSocket connection to the specified remote, sends a JSON payload, and awaits a response.
+// The connect, write and read phases are each bounded by the timeout.
+async fn ws_send(
+    remote: &str,
+    route: &Route,        // How the connection reaches the remote.
+    tls: &TlsOptions,     // Passed on to `ws_connect`.
+    timeout: Option<u32>, // Optional timeout in seconds, applied to each phase.
+    payload: serde_json::Value,
+) -> Result<String, HandshakeError> {
+    let mut ws_stream = ws_connect(remote, route, tls, timeout).await?;
+    ws_exchange(&mut ws_stream, timeout, payload).await
 }
 
 // Prepares the remote server URL for connection, ensuring correct WebSocket protocol prefixes are used.
-fn rationalise_url(remote: &str, is_secure: bool) -> String {
+pub(crate) fn rationalise_url(remote: &str, is_secure: bool) -> String {
     if remote.starts_with("wss://") {
         remote.to_string()
     } else if remote.starts_with("ws://") && is_secure {
@@ -120,17 +266,14 @@ fn rationalise_url(remote: &str, is_secure: bool) -> String {
 impl Transport for Ws {
     async fn connect_and_send(
         &self,
-        timeout: Option<u32>, // Timeout parameter, to be utilized for managing connection and response timeouts.
+        timeout: Option<u32>, // Optional timeout in seconds, applied to each phase.
         payload: serde_json::Value, // JSON payload to be sent to the remote server.
-    ) -> io::Result<String> {
+    ) -> Result<String, HandshakeError> {
         // Ensure the remote URL is correctly formatted based on the security preference.
         let remote_url = rationalise_url(&self.remote, self.is_secure);
 
-        // Send the payload to the remote server and await the response, handling any WebSocket errors.
-        match ws_send(&remote_url, timeout, payload).await {
-            Ok(r) => Ok(r),
-            Err(e) => Err(convert_error(e)),
-        }
+        // Send the payload to the remote server and await the response; WebSocket errors are converted to handshake errors.
+        ws_send(&remote_url, &self.route, &self.tls, timeout, payload).await
     }
 }
 
@@ -139,13 +282,63 @@ impl Transport for Ws {
 mod tests {
     use {super::*, proptest::prelude::*};
 
-    // Test the conversion of WebSocket IO errors to standard IO errors.
+    // Test the conversion of WebSocket IO errors to connection errors.
     #[test]
     fn test_convert_error_io() {
         let io_error = std::io::Error::new(std::io::ErrorKind::NotFound, "not found");
         let ws_error = WsError::Io(io_error);
-        let converted_error = convert_error(ws_error);
-        assert_eq!(converted_error.kind(), std::io::ErrorKind::NotFound);
+        match convert_error(ws_error) {
+            HandshakeError::Connect(e) => assert_eq!(e.kind(), std::io::ErrorKind::NotFound),
+            other => panic!("Expected a connection error, got {:?}", other),
+        }
+    }
+
+    // Closed connections are WebSocket-level errors.
+    #[test]
+    fn test_convert_error_closed() {
+        let converted_error = convert_error(WsError::ConnectionClosed);
+        assert!(matches!(converted_error, HandshakeError::WebSocket(_)));
+    }
+
+    // A server that accepts the TCP connection but never completes the WebSocket upgrade should time out.
+    #[tokio::test]
+    async fn test_unresponsive_server_times_out() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (_socket, _) = listener.accept().await.unwrap();
+            std::future::pending::<()>().await; // Hold the connection open without responding.
+        });
+
+        let result = Ws::new(remote, false)
+            .connect_and_send(Some(1), serde_json::json!({}))
+            .await;
+        assert!(matches!(
+            result.unwrap_err(),
+            HandshakeError::Timeout { .. }
+        ));
+    }
+
+    // Every ping is answered by the server's pong, and its round trip measured.
+    #[tokio::test]
+    async fn test_ping_round_trips() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            while let Some(Ok(_)) = ws.next().await {} // Pings are answered while reading.
+        });
+
+        let mut ws_stream = ws_connect(&remote, &Route::default(), &TlsOptions::default(), Some(5))
+            .await
+            .unwrap();
+        let options = PingOptions {
+            count: 3,
+            interval: Duration::from_millis(10),
+        };
+        let round_trips = ws_ping(&mut ws_stream, options, Some(5)).await.unwrap();
+        assert_eq!(round_trips.len(), 3);
     }
 
     // Property-based testing to ensure URL formatting does not cause panics across a range of input values.
@@ -153,7 +346,7 @@ mod tests {
         #[test]
         fn test_url_formatting_does_not_panic(remote in "[a-zA-Z0-9]+") {
             let result = std::panic::catch_unwind(|| {
-                let _ = Ws::new(remote, false).connect_and_send(None, serde_json::json!({}));
+                drop(Ws::new(remote, false).connect_and_send(None, serde_json::json!({})));
             });
             assert!(result.is_ok());
         }
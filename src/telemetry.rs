@@ -0,0 +1,136 @@
+// Logging and tracing setup for the CLI.
+// Logs go to stderr, or to a rotated file with `--log-file`, as text or JSON lines, at the level
+// chosen with `-v` or `-q` unless `RUST_LOG` says otherwise.
+// With the `otlp` feature and `--otlp-endpoint`, every handshake's span tree is also exported over
+// OTLP, so handshakes can be correlated with the rest of an observability stack.
+#[cfg(feature = "otlp")]
+use opentelemetry::{global, KeyValue};
+#[cfg(feature = "otlp")]
+use opentelemetry_otlp::WithExportConfig;
+#[cfg(feature = "otlp")]
+use opentelemetry_sdk::{runtime, trace, Resource};
+use {
+    crate::{cli::Cli, progress::LogWriter},
+    anyhow::Context,
+    clap::ValueEnum,
+    std::{num::NonZeroUsize, path::Path},
+    tracing_appender::rolling::{RollingFileAppender, Rotation},
+    tracing_subscriber::{
+        fmt::writer::BoxMakeWriter, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer,
+    },
+};
+
+// Selects how logs are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    Text, // Human-readable lines.
+    Json, // One JSON object per line, carrying the fields of the event and of the spans it happened in.
+}
+
+// Selects how often the log file is rotated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogRotation {
+    Hourly, // A new file every hour.
+    Daily,  // A new file every day.
+    Never,  // A single file, growing for as long as the tool runs.
+}
+
+impl From<LogRotation> for Rotation {
+    fn from(rotation: LogRotation) -> Self {
+        match rotation {
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Never => Rotation::NEVER,
+        }
+    }
+}
+
+// Service name the exported spans are reported under.
+#[cfg(feature = "otlp")]
+const SERVICE_NAME: &str = "handshake";
+
+// Initializes `tracing` for application-wide logging and, when requested, trace export.
+// The level chosen with `-v` or `-q` applies to whatever the `RUST_LOG` directives leave unset.
+pub fn init(cli: &Cli) -> anyhow::Result<()> {
+    let filter = EnvFilter::builder()
+        .with_default_directive(cli.log_level().into())
+        .from_env_lossy();
+    // Colours are only meant for terminals, not for files.
+    let writer = match &cli.log_file {
+        Some(path) => BoxMakeWriter::new(log_file(path, cli.log_rotation, cli.log_max_files)?),
+        None => BoxMakeWriter::new(|| LogWriter),
+    };
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .with_ansi(cli.log_file.is_none())
+        .with_writer(writer);
+    // JSON lines list the spans of the event, so the target, transport and phase of a handshake
+    // can be queried without parsing the message.
+    let fmt_layer = match cli.log_format {
+        LogFormat::Text => fmt_layer.boxed(),
+        LogFormat::Json => fmt_layer
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+    };
+    let registry = tracing_subscriber::registry().with(filter).with(fmt_layer);
+
+    #[cfg(feature = "otlp")]
+    let registry = registry.with(cli.otlp_endpoint.as_deref().map(otlp_layer).transpose()?);
+
+    registry.try_init()?;
+    Ok(())
+}
+
+// Opens the log file, rotated into files named after it with the date, and hour when hourly, appended.
+// Only the most recent files are kept when a maximum is given.
+fn log_file(
+    path: &Path,
+    rotation: LogRotation,
+    max_files: Option<NonZeroUsize>,
+) -> anyhow::Result<RollingFileAppender> {
+    let name = path
+        .file_name()
+        .with_context(|| format!("{} does not name a file", path.display()))?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation.into())
+        .filename_prefix(name.to_string_lossy());
+    if let Some(max_files) = max_files {
+        builder = builder.max_log_files(max_files.get());
+    }
+    builder
+        .build(directory)
+        .with_context(|| format!("Unable to open the log file {}", path.display()))
+}
+
+// Builds the layer exporting spans in batches to the OTLP collector at the endpoint, over gRPC.
+#[cfg(feature = "otlp")]
+fn otlp_layer<S>(
+    endpoint: &str,
+) -> anyhow::Result<tracing_opentelemetry::OpenTelemetryLayer<S, trace::Tracer>>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            trace::config().with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                SERVICE_NAME,
+            )])),
+        )
+        .install_batch(runtime::Tokio)?;
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+// Flushes any spans still waiting to be exported; call it before the process exits.
+pub fn shutdown() {
+    #[cfg(feature = "otlp")]
+    global::shutdown_tracer_provider();
+}
@@ -0,0 +1,107 @@
+// CometBFT's handshake as a chain: the `status` JSON-RPC method, reporting the node's software version,
+// the network it belongs to and how far it has synced. Tendermint nodes answer it the same way.
+use crate::{
+    chain::{ChainHandshake, NodeInfo},
+    error::HandshakeError,
+    solana::rpc::node::{RpcHandshakeRequest, RpcResponse},
+};
+use serde::{Deserialize, Deserializer, Serialize}; // Typed deserialization of the `status` result.
+
+// Result of the `status` method, keeping the fields the handshake reports.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TendermintStatus {
+    pub node_info: TendermintNodeInfo, // How the node describes itself to its peers.
+    pub sync_info: TendermintSyncInfo, // How far the node has synced.
+}
+
+// The node's description of itself, as exchanged with its peers.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TendermintNodeInfo {
+    pub id: String,      // Hex ID of the node's P2P key.
+    pub network: String, // Chain ID of the network, e.g. "cosmoshub-4".
+    pub version: String, // Version of the CometBFT software, e.g. "0.37.2".
+    #[serde(default)]
+    pub moniker: String, // Human-readable name the operator gave the node.
+}
+
+// The node's sync state.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TendermintSyncInfo {
+    #[serde(deserialize_with = "from_string")]
+    pub latest_block_height: u64, // Height of the latest block the node has, sent as a string.
+    pub catching_up: bool, // Whether the node is still syncing with the network.
+}
+
+// Deserializes an integer CometBFT sends as a string, as it does every 64-bit integer.
+fn from_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    text.parse().map_err(serde::de::Error::custom)
+}
+
+// The CometBFT network, or any Tendermint-based network.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Tendermint;
+
+impl Tendermint {
+    // Reads the `status` result, failing on JSON-RPC errors.
+    pub fn status(&self, raw: &str) -> Result<TendermintStatus, HandshakeError> {
+        RpcResponse::parse_result(raw)
+    }
+}
+
+impl ChainHandshake for Tendermint {
+    // Requesting the status of the node over JSON RPC version 2.0.
+    fn payload(&self) -> serde_json::Value {
+        serde_json::json!(RpcHandshakeRequest::new(1, "status"))
+    }
+
+    // Reads the `status` result into the node's version and network.
+    fn parse(&self, raw: &str) -> Result<NodeInfo, HandshakeError> {
+        let status = self.status(raw)?;
+        Ok(NodeInfo {
+            chain: "tendermint".to_string(),
+            version: status.node_info.version,
+            details: serde_json::json!({
+                "network": status.node_info.network,
+                "node_id": status.node_info.id,
+                "moniker": status.node_info.moniker,
+                "latest_block_height": status.sync_info.latest_block_height,
+                "catching_up": status.sync_info.catching_up,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The version, network and sync state of a `status` result are reported.
+    #[test]
+    fn test_parse() {
+        let raw = r#"{"jsonrpc":"2.0","id":1,"result":{"node_info":{"protocol_version":{"p2p":"8","block":"11","app":"0"},"id":"a1b2c3","listen_addr":"tcp://0.0.0.0:26656","network":"cosmoshub-4","version":"0.37.2","channels":"40202122233038606100","moniker":"node-1","other":{"tx_index":"on","rpc_address":"tcp://0.0.0.0:26657"}},"sync_info":{"latest_block_height":"19876543","catching_up":false},"validator_info":{}}}"#;
+        let info = Tendermint.parse(raw).unwrap();
+
+        assert_eq!(info.chain, "tendermint");
+        assert_eq!(info.version, "0.37.2");
+        assert_eq!(info.details["network"], "cosmoshub-4");
+        assert_eq!(info.details["latest_block_height"], 19876543);
+    }
+
+    // A JSON-RPC error or a malformed height is not a status.
+    #[test]
+    fn test_parse_errors() {
+        let raw =
+            r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":"Method not found"}}"#;
+        assert!(matches!(
+            Tendermint.parse(raw),
+            Err(HandshakeError::Rpc { .. })
+        ));
+
+        let raw = r#"{"jsonrpc":"2.0","id":1,"result":{"node_info":{"id":"a1","network":"n","version":"0.38.0"},"sync_info":{"latest_block_height":"tall","catching_up":true}}}"#;
+        assert!(matches!(
+            Tendermint.parse(raw),
+            Err(HandshakeError::InvalidResponse(_))
+        ));
+    }
+}
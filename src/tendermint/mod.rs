@@ -0,0 +1,12 @@
+// The `chain` module describes CometBFT's handshake: the `status` JSON-RPC method and its typed result.
+pub mod chain;
+
+// The `node` module verifies a CometBFT RPC endpoint over the TCP, TLS and WebSocket transports
+// shared with the Solana nodes.
+pub mod node;
+
+// The types needed to handshake with a CometBFT node.
+pub use {
+    chain::{Tendermint, TendermintStatus},
+    node::TendermintNode,
+};
@@ -0,0 +1,148 @@
+// Module for verifying a CometBFT RPC endpoint, reached over the same transports as Solana RPC nodes.
+use crate::chain::ChainHandshake; // Chain-agnostic handshakes.
+use crate::error::HandshakeError; // Crate-wide error type reported by failed handshakes.
+use crate::solana::{
+    node::Handshake,
+    target::Target,
+    transport::{
+        select,
+        tcp::{ConnectOptions, Connectable},
+        traced,
+        ws::Ws,
+        ChooseTransport, Transport,
+    },
+    TransportType,
+}; // Import necessary traits and structures for handshake and transport.
+use crate::tendermint::chain::{Tendermint, TendermintStatus}; // CometBFT's handshake request and response.
+use async_trait::async_trait; // Enables async trait methods, crucial for async network operations.
+use std::fmt; // Standard library import for display formatting.
+
+// Path of the JSON-RPC WebSocket endpoint, which CometBFT serves apart from plain HTTP.
+const WEBSOCKET_PATH: &str = "/websocket";
+
+// Struct defining a CometBFT node, including its remote address and transport type for connectivity.
+pub struct TendermintNode {
+    pub remote: Target, // The remote endpoint: its name as given and the addresses it resolved to.
+    pub transport_type: TransportType, // Enum specifying the type of transport to use.
+    pub connect: ConnectOptions, // Proxy and TLS options of the RPC and WebSocket connections.
+}
+
+impl TendermintNode {
+    // Constructor for TendermintNode, taking a target, or a socket address, and transport type.
+    pub fn new(remote: impl Into<Target>, transport_type: TransportType) -> Self {
+        TendermintNode {
+            remote: remote.into(),
+            transport_type,
+            connect: ConnectOptions::default(),
+        }
+    }
+
+    // Calls `status`, returning the node's typed status.
+    // CometBFT does not serve JSON-RPC over QUIC, so that transport is refused before connecting.
+    pub async fn status(&self, timeout: Option<u32>) -> Result<TendermintStatus, HandshakeError> {
+        if self.transport_type == TransportType::Quic {
+            return Err(HandshakeError::Protocol(
+                "CometBFT nodes do not serve JSON-RPC over QUIC".to_string(),
+            ));
+        }
+        let transport = self.get_transport();
+        let exchange = async {
+            let body = transport
+                .connect_and_send(timeout, Tendermint.payload())
+                .await?;
+            Tendermint.status(&body)
+        };
+        traced(&self.remote, self.transport_type, exchange).await
+    }
+}
+
+impl Connectable for TendermintNode {
+    fn connect_options(&mut self) -> &mut ConnectOptions {
+        &mut self.connect
+    }
+}
+
+// Implement the Display trait for TendermintNode for easy logging and debugging.
+impl fmt::Display for TendermintNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TendermintNode({})", self.remote) // Custom display format showing the remote address.
+    }
+}
+
+// Chooses the appropriate transport mechanism based on the transport_type attribute.
+// WebSockets connect to the `/websocket` endpoint; every other transport posts to the root.
+impl ChooseTransport for TendermintNode {
+    fn get_transport(&self) -> Box<dyn Transport> {
+        match self.transport_type {
+            TransportType::Ws | TransportType::Wss => {
+                let secure = self.transport_type == TransportType::Wss;
+                let url = format!("{}{}", self.remote, WEBSOCKET_PATH);
+                let ws = Ws::new(url, secure);
+                Box::new(
+                    ws.with_route(self.connect.route(&self.remote))
+                        .with_tls(self.connect.tls.clone()),
+                )
+            }
+            _ => select(
+                &self.remote,
+                self.transport_type,
+                self.connect.route(&self.remote),
+                self.connect.tls.clone(),
+            ),
+        }
+    }
+}
+
+// Async trait for performing the handshake operation, returning the CometBFT version the node reported.
+#[async_trait]
+impl Handshake for TendermintNode {
+    async fn shake(&self, timeout: Option<u32>) -> Result<String, HandshakeError> {
+        Ok(self.status(timeout).await?.node_info.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Test suite for TendermintNode functionality, run against an in-process JSON-RPC server.
+    use {super::*, crate::test_server::serve_once, std::net::SocketAddr};
+
+    // Verifies that a TendermintNode can be correctly instantiated and displayed.
+    #[test]
+    fn test_tendermint_node_creation() {
+        let addr: SocketAddr = "127.0.0.1:26657".parse().unwrap();
+        let node = TendermintNode::new(addr, TransportType::Tcp);
+
+        assert_eq!(node.transport_type, TransportType::Tcp);
+        assert_eq!(node.to_string(), "TendermintNode(127.0.0.1:26657)"); // Checks the display format.
+    }
+
+    // The status is requested and returned typed.
+    #[tokio::test]
+    async fn test_tendermint_handshake() {
+        let (address, server) = serve_once(
+            r#"{"jsonrpc":"2.0","id":1,"result":{"node_info":{"id":"a1b2c3","network":"osmosis-1","version":"0.37.4"},"sync_info":{"latest_block_height":"15000000","catching_up":true}}}"#,
+        )
+        .await;
+
+        let status = TendermintNode::new(address, TransportType::Tcp)
+            .status(Some(5))
+            .await
+            .unwrap();
+        assert_eq!(status.node_info.network, "osmosis-1");
+        assert_eq!(status.sync_info.latest_block_height, 15_000_000);
+        assert!(status.sync_info.catching_up);
+
+        let request = server.await.unwrap();
+        assert!(request.contains(r#""method":"status""#));
+    }
+
+    // QUIC is refused without connecting.
+    #[tokio::test]
+    async fn test_quic_is_refused() {
+        let addr: SocketAddr = "127.0.0.1:26657".parse().unwrap();
+        let result = TendermintNode::new(addr, TransportType::Quic)
+            .shake(Some(1))
+            .await;
+        assert!(matches!(result, Err(HandshakeError::Protocol(_))));
+    }
+}
@@ -0,0 +1,41 @@
+// In-process HTTP responders for tests of the nodes speaking JSON over HTTP, e.g. the Ethereum,
+// CometBFT and registry nodes, so each test module does not carry its own copy.
+use {
+    std::net::SocketAddr,
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        task::JoinHandle,
+    },
+};
+
+// Answers a single HTTP request with the given JSON body, returning the request received.
+pub(crate) async fn serve_once(body: &'static str) -> (SocketAddr, JoinHandle<String>) {
+    serve_once_with(move |_| body.to_string()).await
+}
+
+// Answers a single HTTP request with the JSON body built from the request's payload, e.g. to echo the
+// ids of a batch, returning the request received.
+pub(crate) async fn serve_once_with<F>(respond: F) -> (SocketAddr, JoinHandle<String>)
+where
+    F: FnOnce(&serde_json::Value) -> String + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buf = vec![0; 1024];
+        let n = socket.read(&mut buf).await.unwrap();
+        let request = String::from_utf8_lossy(&buf[..n]).to_string();
+        let (_, payload) = request.split_once("\r\n\r\n").unwrap_or_default();
+        let body = respond(&serde_json::from_str(payload).unwrap_or_default());
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+        request
+    });
+    (address, server)
+}
@@ -0,0 +1,31 @@
+// Proptest strategies for property tests against the handshake API, exported with the `test-util`
+// feature: domain names, ports and the addresses and URLs made of them, as the CLI accepts them.
+use proptest::{
+    prelude::{Just, Strategy},
+    prop_oneof,
+};
+
+// Strategy for syntactically valid domain names, e.g. "www.qwxyzab.invalid", under the TLD reserved by
+// RFC 2606 so that they can never exist, for testing failure scenarios in DNS resolution.
+pub fn domain() -> impl Strategy<Value = String> {
+    let www = prop_oneof![Just("www."), Just("")];
+    let name = "[a-z]{5,10}";
+    let suffix = prop_oneof![Just(".invalid"), Just(".node.invalid")];
+    (www, name, suffix).prop_map(|(www, name, suffix)| format!("{}{}{}", www, name, suffix))
+}
+
+// Strategy for port numbers outside the reserved range.
+pub fn port() -> impl Strategy<Value = u16> {
+    1024u16..=u16::MAX
+}
+
+// Strategy for addresses as the CLI takes them, a domain and a port, e.g. "qwxyzab.invalid:8899".
+pub fn address() -> impl Strategy<Value = String> {
+    (domain(), port()).prop_map(|(domain, port)| format!("{}:{}", domain, port))
+}
+
+// Strategy for HTTP and HTTPS URLs of an address, e.g. "https://www.qwxyzab.invalid:8899".
+pub fn url() -> impl Strategy<Value = String> {
+    let scheme = prop_oneof![Just("http://"), Just("https://")];
+    (scheme, address()).prop_map(|(scheme, address)| format!("{}{}", scheme, address))
+}
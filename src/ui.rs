@@ -0,0 +1,196 @@
+// Live terminal dashboard of monitored nodes: a table of each target's last status, version, latency
+// history and uptime, redrawn whenever a round of the monitor finishes.
+use {
+    crate::cli::MonitorArgs,
+    handshake::solana::{
+        monitor::{self, Round, TargetStatus},
+        target::AddressFamily,
+    },
+    ratatui::{
+        crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+        layout::Constraint,
+        style::{Color, Modifier, Style},
+        widgets::{Block, Cell, Row, Table},
+        DefaultTerminal, Frame,
+    },
+    std::time::Duration,
+    tokio::sync::mpsc,
+};
+
+// Default time between two rounds of handshakes, in seconds, when `--watch` is not given.
+pub const DEFAULT_REFRESH_SECONDS: u64 = 5;
+
+// How often key presses are checked for, so quitting feels immediate.
+const KEY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Number of recent handshakes shown in the latency history.
+const HISTORY_WIDTH: usize = 30;
+
+// Bars of the latency history, from the shortest latency to the longest.
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// Renders latencies as a line of bars scaled to the longest one; failed handshakes leave a gap.
+pub fn sparkline<'a>(latencies: impl IntoIterator<Item = &'a Option<Duration>>) -> String {
+    let latencies: Vec<Option<Duration>> = latencies.into_iter().copied().collect();
+    let longest = latencies
+        .iter()
+        .flatten()
+        .max()
+        .copied()
+        .unwrap_or_default();
+    latencies
+        .iter()
+        .map(|latency| match latency {
+            None => ' ',
+            Some(_) if longest.is_zero() => BARS[0],
+            Some(latency) => {
+                let level = latency.as_secs_f64() / longest.as_secs_f64();
+                BARS[(level * (BARS.len() - 1) as f64).round() as usize]
+            }
+        })
+        .collect()
+}
+
+// Monitors the targets every `refresh` until `q`, Esc or Ctrl+C is pressed, showing their status on the dashboard.
+pub async fn dashboard(
+    node: &MonitorArgs,
+    family: AddressFamily,
+    refresh: Duration,
+) -> anyhow::Result<()> {
+    let monitor = node.monitor(family, refresh)?;
+    let mut statuses = monitor::statuses(&monitor.targets);
+    let mut rounds = monitor.spawn();
+
+    // The terminal is restored even when drawing fails, so the shell is left usable.
+    let mut terminal = ratatui::init();
+    let result = show(&mut terminal, &mut rounds, &mut statuses, refresh).await;
+    ratatui::restore();
+    result
+}
+
+// Redraws the dashboard after every round and every terminal event, until the user quits.
+async fn show(
+    terminal: &mut DefaultTerminal,
+    rounds: &mut mpsc::Receiver<Round>,
+    statuses: &mut [TargetStatus],
+    refresh: Duration,
+) -> anyhow::Result<()> {
+    let mut keys = tokio::time::interval(KEY_POLL_INTERVAL);
+    let mut round = 0;
+    let mut redraw = true;
+    loop {
+        if redraw {
+            terminal.draw(|frame| draw(frame, statuses, round, refresh))?;
+            redraw = false;
+        }
+        tokio::select! {
+            finished = rounds.recv() => match finished {
+                Some(finished) => {
+                    monitor::record_round(statuses, &finished);
+                    round = finished.number;
+                    redraw = true;
+                }
+                None => return Ok(()),
+            },
+            _ = keys.tick() => {
+                // Raw mode turns Ctrl+C into a key press rather than a signal.
+                while event::poll(Duration::ZERO)? {
+                    match event::read()? {
+                        Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                return Ok(())
+                            }
+                            _ => {}
+                        },
+                        _ => redraw = true,
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Draws the table of targets, one row each, in the order they were given.
+fn draw(frame: &mut Frame, statuses: &[TargetStatus], round: u64, refresh: Duration) {
+    let header = Row::new([
+        "Target",
+        "Transport",
+        "Status",
+        "Version",
+        "Latency",
+        "History",
+        "Uptime",
+        "Error",
+    ])
+    .style(Style::new().add_modifier(Modifier::BOLD));
+    let rows = statuses.iter().map(|status| {
+        let (label, color) = match (status.last_checked, status.healthy()) {
+            (None, _) => ("pending".to_string(), Color::Gray),
+            (Some(_), true) => ("ok".to_string(), Color::Green),
+            (Some(_), false) => (
+                format!("failed x{}", status.consecutive_failures),
+                Color::Red,
+            ),
+        };
+        let skipped = status.latencies.len().saturating_sub(HISTORY_WIDTH);
+        Row::new([
+            Cell::new(status.target.to_string()),
+            Cell::new(status.target.transport_type.to_string()),
+            Cell::new(label).style(Style::new().fg(color)),
+            Cell::new(status.version.clone().unwrap_or_default()),
+            Cell::new(
+                status
+                    .latencies
+                    .back()
+                    .copied()
+                    .flatten()
+                    .map(|latency| format!("{} ms", latency.as_millis()))
+                    .unwrap_or_default(),
+            ),
+            Cell::new(sparkline(status.latencies.iter().skip(skipped))),
+            Cell::new(
+                status
+                    .uptime()
+                    .map(|uptime| format!("{:.1}%", uptime))
+                    .unwrap_or_default(),
+            ),
+            Cell::new(status.last_error.clone().unwrap_or_default())
+                .style(Style::new().fg(Color::Red)),
+        ])
+    });
+    let widths = [
+        Constraint::Max(40),
+        Constraint::Length(9),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(9),
+        Constraint::Length(HISTORY_WIDTH as u16),
+        Constraint::Length(7),
+        Constraint::Fill(1),
+    ];
+    let title = format!(
+        " handshake: round {}, every {}s, q to quit ",
+        round,
+        refresh.as_secs()
+    );
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::bordered().title(title));
+    frame.render_widget(table, frame.area());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Bars are scaled to the longest latency, and failed handshakes leave a gap.
+    #[test]
+    fn test_sparkline() {
+        let millis = |millis| Some(Duration::from_millis(millis));
+        let latencies = [millis(10), millis(40), None, millis(80), millis(0)];
+        assert_eq!(sparkline(&latencies), "▂▅ █▁");
+        assert_eq!(sparkline(&[millis(0), millis(0)]), "▁▁");
+        assert_eq!(sparkline(&[]), "");
+    }
+}